@@ -0,0 +1,5 @@
+ALTER TABLE smoking_logs DROP COLUMN IF EXISTS device_name;
+
+DROP INDEX IF EXISTS idx_devices_discord_id;
+
+DROP TABLE IF EXISTS devices;
@@ -0,0 +1,12 @@
+CREATE TABLE devices (
+    id SERIAL PRIMARY KEY,
+    discord_id VARCHAR(20) NOT NULL REFERENCES users(discord_id),
+    device_name VARCHAR(100) NOT NULL,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    last_used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_devices_discord_id ON devices(discord_id);
+
+ALTER TABLE smoking_logs ADD COLUMN device_name VARCHAR(100);
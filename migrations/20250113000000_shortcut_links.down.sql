@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_shortcut_links_discord_id;
+
+DROP TABLE IF EXISTS shortcut_links;
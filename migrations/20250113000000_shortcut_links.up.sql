@@ -0,0 +1,10 @@
+CREATE TABLE shortcut_links (
+    id SERIAL PRIMARY KEY,
+    discord_id VARCHAR(20) NOT NULL REFERENCES users(discord_id),
+    smoking_type_id INTEGER NOT NULL REFERENCES smoking_types(id),
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    last_used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_shortcut_links_discord_id ON shortcut_links(discord_id);
@@ -0,0 +1,5 @@
+DROP TRIGGER IF EXISTS update_role_connections_updated_at ON role_connections;
+
+DROP TABLE IF EXISTS role_connections;
+
+ALTER TABLE users DROP COLUMN IF EXISTS daily_goal;
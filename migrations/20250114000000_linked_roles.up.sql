@@ -0,0 +1,16 @@
+ALTER TABLE users ADD COLUMN daily_goal INTEGER CHECK (daily_goal >= 0);
+
+CREATE TABLE role_connections (
+    discord_id VARCHAR(20) PRIMARY KEY REFERENCES users(discord_id),
+    access_token TEXT NOT NULL,
+    refresh_token TEXT NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    last_pushed_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TRIGGER update_role_connections_updated_at
+    BEFORE UPDATE ON role_connections
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_column();
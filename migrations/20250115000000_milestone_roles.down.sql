@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS milestone_roles;
+
+DROP INDEX IF EXISTS idx_smoking_logs_guild_id;
+
+ALTER TABLE smoking_logs DROP COLUMN IF EXISTS guild_id;
@@ -0,0 +1,11 @@
+ALTER TABLE smoking_logs ADD COLUMN guild_id VARCHAR(20);
+
+CREATE INDEX idx_smoking_logs_guild_id ON smoking_logs(guild_id);
+
+CREATE TABLE milestone_roles (
+    guild_id VARCHAR(20) NOT NULL,
+    role_id VARCHAR(20) NOT NULL,
+    smoke_free_days INTEGER NOT NULL CHECK (smoke_free_days > 0),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (guild_id, role_id)
+);
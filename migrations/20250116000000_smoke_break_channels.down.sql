@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_smoke_break_channels_guild_id;
+
+DROP TABLE IF EXISTS smoke_break_channels;
+
+ALTER TABLE users DROP COLUMN IF EXISTS smoke_break_prompt;
@@ -0,0 +1,9 @@
+ALTER TABLE users ADD COLUMN smoke_break_prompt BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE smoke_break_channels (
+    channel_id VARCHAR(20) PRIMARY KEY,
+    guild_id VARCHAR(20) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_smoke_break_channels_guild_id ON smoke_break_channels(guild_id);
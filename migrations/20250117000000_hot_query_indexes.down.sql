@@ -0,0 +1,5 @@
+CREATE INDEX IF NOT EXISTS idx_smoking_logs_guild_id ON smoking_logs(guild_id);
+CREATE INDEX IF NOT EXISTS idx_smoking_logs_discord_id ON smoking_logs(discord_id);
+
+DROP INDEX IF EXISTS idx_smoking_logs_guild_id_smoked_at;
+DROP INDEX IF EXISTS idx_smoking_logs_discord_id_smoked_at;
@@ -0,0 +1,8 @@
+CREATE INDEX idx_smoking_logs_discord_id_smoked_at ON smoking_logs(discord_id, smoked_at);
+
+CREATE INDEX idx_smoking_logs_guild_id_smoked_at ON smoking_logs(guild_id, smoked_at)
+    WHERE guild_id IS NOT NULL;
+
+-- Both are covered by the composite indexes above.
+DROP INDEX IF EXISTS idx_smoking_logs_discord_id;
+DROP INDEX IF EXISTS idx_smoking_logs_guild_id;
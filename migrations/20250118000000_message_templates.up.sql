@@ -0,0 +1,7 @@
+CREATE TABLE message_templates (
+    guild_id VARCHAR(20) NOT NULL,
+    template_key VARCHAR(32) NOT NULL,
+    template TEXT NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (guild_id, template_key)
+);
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_smoking_logs_idempotency_key;
+ALTER TABLE smoking_logs DROP COLUMN IF EXISTS idempotency_key;
@@ -0,0 +1,4 @@
+ALTER TABLE smoking_logs ADD COLUMN idempotency_key VARCHAR(128);
+
+CREATE UNIQUE INDEX idx_smoking_logs_idempotency_key ON smoking_logs(discord_id, idempotency_key)
+    WHERE idempotency_key IS NOT NULL;
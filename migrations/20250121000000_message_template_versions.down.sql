@@ -0,0 +1 @@
+ALTER TABLE message_templates DROP COLUMN IF EXISTS version;
@@ -0,0 +1 @@
+ALTER TABLE message_templates ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
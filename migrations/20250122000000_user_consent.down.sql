@@ -0,0 +1,2 @@
+ALTER TABLE users DROP COLUMN IF EXISTS consented_at;
+ALTER TABLE users DROP COLUMN IF EXISTS consent_version;
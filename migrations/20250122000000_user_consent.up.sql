@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN consent_version INTEGER;
+ALTER TABLE users ADD COLUMN consented_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS guild_acknowledgments;
+DROP TABLE IF EXISTS guild_acknowledgment_policies;
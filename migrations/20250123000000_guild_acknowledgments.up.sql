@@ -0,0 +1,12 @@
+CREATE TABLE guild_acknowledgment_policies (
+    guild_id VARCHAR(20) PRIMARY KEY,
+    message TEXT NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE guild_acknowledgments (
+    guild_id VARCHAR(20) NOT NULL,
+    discord_id VARCHAR(20) NOT NULL REFERENCES users(discord_id) ON DELETE CASCADE,
+    acknowledged_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (guild_id, discord_id)
+);
@@ -0,0 +1,10 @@
+CREATE TABLE tracking_pauses (
+    id SERIAL PRIMARY KEY,
+    discord_id VARCHAR(20) NOT NULL REFERENCES users(discord_id) ON DELETE CASCADE,
+    start_date DATE NOT NULL,
+    end_date DATE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    CHECK (start_date <= end_date)
+);
+
+CREATE INDEX idx_tracking_pauses_discord_id ON tracking_pauses(discord_id, end_date);
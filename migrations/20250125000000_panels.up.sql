@@ -0,0 +1,7 @@
+CREATE TABLE panels (
+    panel_id VARCHAR(100) PRIMARY KEY,
+    guild_id VARCHAR(20),
+    channel_id VARCHAR(20) NOT NULL,
+    smoking_type_ids INTEGER[],
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
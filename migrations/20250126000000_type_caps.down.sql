@@ -0,0 +1,3 @@
+ALTER TABLE users DROP COLUMN ignore_type_caps;
+
+DROP TABLE guild_type_caps;
@@ -0,0 +1,9 @@
+CREATE TABLE guild_type_caps (
+    guild_id VARCHAR(20) NOT NULL,
+    smoking_type_id INTEGER NOT NULL REFERENCES smoking_types(id) ON DELETE CASCADE,
+    daily_cap INTEGER NOT NULL CHECK (daily_cap >= 0),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (guild_id, smoking_type_id)
+);
+
+ALTER TABLE users ADD COLUMN ignore_type_caps BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,5 @@
+CREATE TABLE guild_confirmation_reactions (
+    guild_id VARCHAR(20) PRIMARY KEY,
+    emoji VARCHAR(64) NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
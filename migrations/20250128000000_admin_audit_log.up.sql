@@ -0,0 +1,8 @@
+CREATE TABLE admin_audit_log (
+    id SERIAL PRIMARY KEY,
+    actor_id VARCHAR(20) NOT NULL,
+    action VARCHAR(50) NOT NULL,
+    target_id VARCHAR(20) NOT NULL,
+    details TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
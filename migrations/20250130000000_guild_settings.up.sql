@@ -0,0 +1,5 @@
+CREATE TABLE guild_settings (
+    guild_id VARCHAR(20) PRIMARY KEY,
+    timezone VARCHAR(64),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
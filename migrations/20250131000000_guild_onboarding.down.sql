@@ -0,0 +1,3 @@
+ALTER TABLE guild_settings DROP COLUMN IF EXISTS onboarded_at;
+ALTER TABLE guild_settings DROP COLUMN IF EXISTS panel_smoking_type_ids;
+ALTER TABLE guild_settings DROP COLUMN IF EXISTS panel_channel_id;
@@ -0,0 +1,3 @@
+ALTER TABLE guild_settings ADD COLUMN panel_channel_id VARCHAR(20);
+ALTER TABLE guild_settings ADD COLUMN panel_smoking_type_ids INTEGER[];
+ALTER TABLE guild_settings ADD COLUMN onboarded_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,4 @@
+ALTER TABLE smoking_types DROP COLUMN IF EXISTS currency;
+ALTER TABLE smoking_types DROP COLUMN IF EXISTS typical_price;
+ALTER TABLE smoking_types DROP COLUMN IF EXISTS unit;
+ALTER TABLE smoking_types DROP COLUMN IF EXISTS emoji;
@@ -0,0 +1,4 @@
+ALTER TABLE smoking_types ADD COLUMN emoji VARCHAR(32);
+ALTER TABLE smoking_types ADD COLUMN unit VARCHAR(32);
+ALTER TABLE smoking_types ADD COLUMN typical_price INTEGER;
+ALTER TABLE smoking_types ADD COLUMN currency CHAR(3);
@@ -0,0 +1,2 @@
+ALTER TABLE smoking_types DROP COLUMN IF EXISTS merged_into;
+ALTER TABLE smoking_types DROP COLUMN IF EXISTS archived_at;
@@ -0,0 +1,2 @@
+ALTER TABLE smoking_types ADD COLUMN archived_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE smoking_types ADD COLUMN merged_into INTEGER REFERENCES smoking_types(id);
@@ -0,0 +1 @@
+ALTER TABLE users DROP COLUMN IF EXISTS share_logs_in_guild_exports;
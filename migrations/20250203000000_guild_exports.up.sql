@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN share_logs_in_guild_exports BOOLEAN NOT NULL DEFAULT FALSE;
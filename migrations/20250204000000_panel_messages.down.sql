@@ -0,0 +1 @@
+ALTER TABLE panels DROP COLUMN IF EXISTS message_id;
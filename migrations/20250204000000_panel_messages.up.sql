@@ -0,0 +1 @@
+ALTER TABLE panels ADD COLUMN message_id VARCHAR(20);
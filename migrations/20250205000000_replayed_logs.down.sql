@@ -0,0 +1 @@
+ALTER TABLE smoking_logs DROP COLUMN IF EXISTS replayed_at;
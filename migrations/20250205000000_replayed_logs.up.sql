@@ -0,0 +1,2 @@
+-- Set on logs that were buffered while the database was unreachable and written later
+ALTER TABLE smoking_logs ADD COLUMN replayed_at TIMESTAMP WITH TIME ZONE;
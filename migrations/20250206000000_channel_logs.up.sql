@@ -0,0 +1,7 @@
+-- Channel of logs recorded from panels outside any guild, so a group DM can be aggregated
+CREATE TABLE channel_logs (
+    log_id INTEGER PRIMARY KEY REFERENCES smoking_logs(id) ON DELETE CASCADE,
+    channel_id VARCHAR(20) NOT NULL
+);
+
+CREATE INDEX idx_channel_logs_channel_id ON channel_logs(channel_id);
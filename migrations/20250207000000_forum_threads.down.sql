@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS forum_threads;
+DROP TABLE IF EXISTS forum_channels;
@@ -0,0 +1,16 @@
+-- Forum channel a guild's weekly log threads are opened in
+CREATE TABLE forum_channels (
+    guild_id VARCHAR(20) PRIMARY KEY,
+    channel_id VARCHAR(20) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Weekly threads opened by the bot; archived_at is set once a later week's thread took over
+CREATE TABLE forum_threads (
+    guild_id VARCHAR(20) NOT NULL,
+    week_start DATE NOT NULL,
+    thread_id VARCHAR(20) NOT NULL,
+    archived_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (guild_id, week_start)
+);
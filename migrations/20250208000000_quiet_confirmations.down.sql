@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS daily_confirmations;
+ALTER TABLE users DROP COLUMN IF EXISTS quiet_confirmations;
@@ -0,0 +1,8 @@
+ALTER TABLE users ADD COLUMN quiet_confirmations BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- The message confirming a quiet user's presses of the day, edited by later presses
+CREATE TABLE daily_confirmations (
+    discord_id VARCHAR(20) PRIMARY KEY REFERENCES users(discord_id) ON DELETE CASCADE,
+    smoke_date DATE NOT NULL,
+    message_id VARCHAR(20) NOT NULL
+);
@@ -0,0 +1,11 @@
+-- Custom events users tag their days with (e.g. exam, overtime)
+CREATE TABLE user_events (
+    id SERIAL PRIMARY KEY,
+    discord_id VARCHAR(20) NOT NULL REFERENCES users(discord_id) ON DELETE CASCADE,
+    tag VARCHAR(32) NOT NULL,
+    event_date DATE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (discord_id, tag, event_date)
+);
+
+CREATE INDEX idx_user_events_discord_id ON user_events(discord_id, event_date);
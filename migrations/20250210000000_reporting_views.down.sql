@@ -0,0 +1,3 @@
+DROP VIEW IF EXISTS v_guild_leaderboard;
+DROP VIEW IF EXISTS v_user_streaks;
+DROP VIEW IF EXISTS v_daily_totals;
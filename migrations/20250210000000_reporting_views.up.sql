@@ -0,0 +1,68 @@
+-- Stable reporting views for dashboards (e.g. Grafana's Postgres datasource).
+-- Their names and columns are kept compatible across releases; change them
+-- only in new migrations. Days are counted in the session time zone, so set
+-- the reporting role's time zone to the bot's TIMEZONE, e.g.
+--   ALTER ROLE grafana SET timezone = 'Asia/Tokyo';
+
+-- Totals per day, user, guild and smoking type
+CREATE VIEW v_daily_totals AS
+SELECT
+    sl.smoked_at::date AS day,
+    sl.discord_id,
+    sl.guild_id,
+    sl.smoking_type_id,
+    st.type_name,
+    SUM(sl.quantity)::bigint AS total_quantity,
+    COUNT(*) AS log_count
+FROM smoking_logs sl
+JOIN smoking_types st ON sl.smoking_type_id = st.id
+GROUP BY 1, sl.discord_id, sl.guild_id, sl.smoking_type_id, st.type_name;
+
+-- Smoke-free streaks of every user who has logged: the days since their last
+-- smoking day and the longest run of days without a log
+CREATE VIEW v_user_streaks AS
+WITH smoking_days AS (
+    SELECT DISTINCT discord_id, smoked_at::date AS day
+    FROM smoking_logs
+),
+gaps AS (
+    SELECT
+        discord_id,
+        day,
+        day - LAG(day) OVER (PARTITION BY discord_id ORDER BY day) - 1 AS smoke_free_days_before
+    FROM smoking_days
+)
+SELECT
+    u.discord_id,
+    u.username,
+    MAX(g.day) AS last_smoking_day,
+    GREATEST(CURRENT_DATE - MAX(g.day), 0) AS current_streak_days,
+    GREATEST(
+        COALESCE(MAX(g.smoke_free_days_before), 0),
+        CURRENT_DATE - MAX(g.day)
+    ) AS longest_streak_days
+FROM gaps g
+JOIN users u ON g.discord_id = u.discord_id
+GROUP BY u.discord_id, u.username;
+
+-- Monthly standings of each guild's members; rank 1 smoked the least
+CREATE VIEW v_guild_leaderboard AS
+WITH monthly AS (
+    SELECT
+        sl.guild_id,
+        DATE_TRUNC('month', sl.smoked_at)::date AS month,
+        sl.discord_id,
+        SUM(sl.quantity)::bigint AS total_quantity
+    FROM smoking_logs sl
+    WHERE sl.guild_id IS NOT NULL
+    GROUP BY sl.guild_id, 2, sl.discord_id
+)
+SELECT
+    m.guild_id,
+    m.month,
+    m.discord_id,
+    u.username,
+    m.total_quantity,
+    RANK() OVER (PARTITION BY m.guild_id, m.month ORDER BY m.total_quantity) AS rank
+FROM monthly m
+JOIN users u ON m.discord_id = u.discord_id;
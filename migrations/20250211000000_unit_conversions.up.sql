@@ -0,0 +1,11 @@
+-- Conversion hints between the units of two smoking types (e.g. 1 本 of
+-- traditional = 10 puffs of a vape), suggested when a log is re-typed or a
+-- type is merged into one with another unit
+CREATE TABLE unit_conversions (
+    from_type_id INTEGER NOT NULL REFERENCES smoking_types(id) ON DELETE CASCADE,
+    to_type_id INTEGER NOT NULL REFERENCES smoking_types(id) ON DELETE CASCADE,
+    factor DOUBLE PRECISION NOT NULL CHECK (factor > 0),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (from_type_id, to_type_id),
+    CHECK (from_type_id <> to_type_id)
+);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS guild_challenge_weeks;
+DROP TABLE IF EXISTS guild_challenges;
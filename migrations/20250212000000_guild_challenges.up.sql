@@ -0,0 +1,24 @@
+-- Guilds taking part in the weekly challenge: a collective reduction of the
+-- week's total against the week before, with progress posted in a channel
+CREATE TABLE guild_challenges (
+    guild_id VARCHAR(20) PRIMARY KEY,
+    channel_id VARCHAR(20) NOT NULL,
+    target_percent INTEGER NOT NULL CHECK (target_percent BETWEEN 1 AND 100),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- One row per guild and challenge week; the baseline is the week before's
+-- total, fixed when the week's progress message is posted
+CREATE TABLE guild_challenge_weeks (
+    guild_id VARCHAR(20) NOT NULL,
+    week_start DATE NOT NULL,
+    channel_id VARCHAR(20) NOT NULL,
+    message_id VARCHAR(20) NOT NULL,
+    baseline BIGINT NOT NULL,
+    target_percent INTEGER NOT NULL,
+    updated_on DATE NOT NULL,
+    settled_at TIMESTAMP WITH TIME ZONE,
+    achieved BOOLEAN,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (guild_id, week_start)
+);
@@ -0,0 +1,22 @@
+-- Friendly wagers between two users over who logs less in a period; no
+-- stakes are tracked. The period starts when the opponent accepts.
+CREATE TABLE wagers (
+    id SERIAL PRIMARY KEY,
+    channel_id VARCHAR(20) NOT NULL,
+    challenger_id VARCHAR(20) NOT NULL,
+    opponent_id VARCHAR(20) NOT NULL,
+    metric VARCHAR(32) NOT NULL,
+    period VARCHAR(16) NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'accepted', 'declined', 'expired', 'settled')),
+    starts_at TIMESTAMP WITH TIME ZONE,
+    ends_at TIMESTAMP WITH TIME ZONE,
+    challenger_total BIGINT,
+    opponent_total BIGINT,
+    winner_id VARCHAR(20),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    settled_at TIMESTAMP WITH TIME ZONE,
+    CHECK (challenger_id <> opponent_id)
+);
+
+CREATE INDEX idx_wagers_due ON wagers (ends_at) WHERE status = 'accepted';
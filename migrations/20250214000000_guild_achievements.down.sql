@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS earned_achievements;
+DROP TABLE IF EXISTS guild_achievements;
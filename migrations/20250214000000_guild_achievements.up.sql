@@ -0,0 +1,18 @@
+-- Achievements guild admins define with a condition (e.g. `weekly_total<=10`)
+CREATE TABLE guild_achievements (
+    id SERIAL PRIMARY KEY,
+    guild_id VARCHAR(20) NOT NULL,
+    name VARCHAR(50) NOT NULL,
+    condition TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (guild_id, name)
+);
+
+-- Members who met an achievement's condition; earned achievements are kept
+CREATE TABLE earned_achievements (
+    achievement_id INTEGER NOT NULL REFERENCES guild_achievements(id) ON DELETE CASCADE,
+    guild_id VARCHAR(20) NOT NULL,
+    discord_id VARCHAR(20) NOT NULL,
+    earned_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    PRIMARY KEY (achievement_id, discord_id)
+);
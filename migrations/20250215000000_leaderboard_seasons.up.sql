@@ -0,0 +1,31 @@
+-- Guilds running leaderboard seasons; each season's standings are archived
+-- and its winners announced in the channel when it ends
+CREATE TABLE leaderboard_settings (
+    guild_id VARCHAR(20) PRIMARY KEY,
+    channel_id VARCHAR(20) NOT NULL,
+    season_length VARCHAR(10) NOT NULL CHECK (season_length IN ('month', 'quarter')),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Seasons of a guild, numbered from 1; ends_on is the first day after the
+-- season, and archived_at is set once its standings are archived
+CREATE TABLE leaderboard_seasons (
+    id SERIAL PRIMARY KEY,
+    guild_id VARCHAR(20) NOT NULL,
+    number INTEGER NOT NULL,
+    starts_on DATE NOT NULL,
+    ends_on DATE NOT NULL,
+    archived_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (guild_id, number)
+);
+
+-- Final standings of archived seasons
+CREATE TABLE season_standings (
+    season_id INTEGER NOT NULL REFERENCES leaderboard_seasons(id) ON DELETE CASCADE,
+    guild_id VARCHAR(20) NOT NULL,
+    discord_id VARCHAR(20) NOT NULL,
+    rank INTEGER NOT NULL,
+    total BIGINT NOT NULL,
+    PRIMARY KEY (season_id, discord_id)
+);
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS smoke_free_board_members;
@@ -0,0 +1,8 @@
+-- Members who opted in to a guild's smoke-free board, which ranks them by
+-- their current streak of smoke-free days
+CREATE TABLE smoke_free_board_members (
+    guild_id VARCHAR(20) NOT NULL,
+    discord_id VARCHAR(20) NOT NULL,
+    joined_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (guild_id, discord_id)
+);
@@ -0,0 +1,11 @@
+-- A channel whose topic (or name, for voice channels) shows a live counter
+-- of the guild; last_text and updated_at throttle the channel edits
+CREATE TABLE channel_counters (
+    guild_id VARCHAR(20) PRIMARY KEY,
+    channel_id VARCHAR(20) NOT NULL,
+    metric VARCHAR(20) NOT NULL CHECK (metric IN ('daily-total', 'days-since')),
+    target VARCHAR(10) NOT NULL CHECK (target IN ('topic', 'name')),
+    last_text TEXT,
+    updated_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
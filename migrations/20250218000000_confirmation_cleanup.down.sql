@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS confirmation_messages;
+DROP TABLE IF EXISTS confirmation_cleanup_settings;
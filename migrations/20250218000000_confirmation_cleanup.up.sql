@@ -0,0 +1,17 @@
+-- How long the bot's panel confirmations stay in a guild's channels before
+-- they are deleted
+CREATE TABLE confirmation_cleanup_settings (
+    guild_id VARCHAR(20) PRIMARY KEY,
+    max_age_hours INTEGER NOT NULL CHECK (max_age_hours BETWEEN 1 AND 720),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Panel confirmations sent in guilds with cleanup, forgotten once deleted
+CREATE TABLE confirmation_messages (
+    message_id VARCHAR(20) PRIMARY KEY,
+    guild_id VARCHAR(20) NOT NULL,
+    channel_id VARCHAR(20) NOT NULL,
+    sent_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX idx_confirmation_messages_sent_at ON confirmation_messages (sent_at);
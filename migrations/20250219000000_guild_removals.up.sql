@@ -0,0 +1,7 @@
+-- Guilds the bot was removed from. Their background work is paused, and
+-- their data is deleted once delete_after passes (never when it is NULL).
+CREATE TABLE guild_removals (
+    guild_id VARCHAR(20) PRIMARY KEY,
+    removed_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    delete_after TIMESTAMP WITH TIME ZONE
+);
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS category_goals;
+ALTER TABLE smoking_types DROP COLUMN IF EXISTS category_id;
+DROP TABLE IF EXISTS categories;
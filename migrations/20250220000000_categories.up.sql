@@ -0,0 +1,21 @@
+-- Categories of habits a deployment tracks (cigarettes, coffee, energy
+-- drinks). Every type in a category counts in the category's unit, so
+-- members can set a daily goal per category.
+CREATE TABLE categories (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(50) NOT NULL UNIQUE,
+    unit VARCHAR(32) NOT NULL,
+    emoji VARCHAR(32),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE smoking_types
+    ADD COLUMN category_id INTEGER REFERENCES categories(id) ON DELETE SET NULL;
+
+CREATE TABLE category_goals (
+    discord_id VARCHAR(20) NOT NULL REFERENCES users(discord_id) ON DELETE CASCADE,
+    category_id INTEGER NOT NULL REFERENCES categories(id) ON DELETE CASCADE,
+    daily_goal INTEGER NOT NULL CHECK (daily_goal >= 0),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (discord_id, category_id)
+);
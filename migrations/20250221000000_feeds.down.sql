@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS reached_milestones;
+DROP TABLE IF EXISTS feed_tokens;
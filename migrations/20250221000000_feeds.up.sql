@@ -0,0 +1,16 @@
+-- Tokens of the members' personal Atom feeds; one per member, replaced to revoke
+CREATE TABLE feed_tokens (
+    discord_id VARCHAR(20) PRIMARY KEY REFERENCES users(discord_id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Smoke-free milestones members reached, listed in their feeds. Reaching the
+-- same milestone in several guilds on one day is recorded once.
+CREATE TABLE reached_milestones (
+    discord_id VARCHAR(20) NOT NULL REFERENCES users(discord_id) ON DELETE CASCADE,
+    smoke_free_days INTEGER NOT NULL,
+    reached_on DATE NOT NULL,
+    reached_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    PRIMARY KEY (discord_id, smoke_free_days, reached_on)
+);
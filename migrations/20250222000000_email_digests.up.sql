@@ -0,0 +1,12 @@
+-- Members' weekly email digests. The verification token is hashed like the
+-- other tokens; the unsubscribe token is stored as is because every digest
+-- links to it, and it only allows stopping the digest.
+CREATE TABLE email_subscriptions (
+    discord_id VARCHAR(20) PRIMARY KEY REFERENCES users(discord_id) ON DELETE CASCADE,
+    email VARCHAR(254) NOT NULL,
+    verification_token_hash VARCHAR(64) UNIQUE,
+    verified_at TIMESTAMP WITH TIME ZONE,
+    unsubscribe_token VARCHAR(40) NOT NULL UNIQUE,
+    last_sent_week DATE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,8 @@
+-- Where members receive push notifications instead of Discord DMs: an ntfy
+-- topic or a Pushover user key.
+CREATE TABLE push_subscriptions (
+    discord_id VARCHAR(20) PRIMARY KEY REFERENCES users(discord_id) ON DELETE CASCADE,
+    service VARCHAR(16) NOT NULL CHECK (service IN ('ntfy', 'pushover')),
+    target VARCHAR(64) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,9 @@
+-- The channel a member prefers notifications on; DMs when unset.
+ALTER TABLE users ADD COLUMN notification_channel VARCHAR(16)
+    CHECK (notification_channel IN ('dm', 'email', 'ntfy', 'pushover'));
+
+-- Members who registered a push target were already notified through it.
+UPDATE users
+SET notification_channel = push_subscriptions.service
+FROM push_subscriptions
+WHERE push_subscriptions.discord_id = users.discord_id;
@@ -0,0 +1,384 @@
+//! Custom achievements guild admins define with a small condition DSL.
+//!
+//! An achievement's condition is one or more comparisons of a metric with a
+//! number, joined by `&&`, e.g. `weekly_total<=10 && smoke_free_days>=2`.
+//! Totals count the last *completed* day, week (Monday to Sunday) or month,
+//! so an upper bound is not met just because a new period has begun. A
+//! recurring job evaluates every guild's achievements for the members who
+//! logged there and records who earned them; earned achievements are kept
+//! even if the member stops meeting the condition.
+
+use std::{fmt, str::FromStr, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{Datelike, Months, NaiveDate};
+use poise::serenity_prelude::futures::lock::Mutex;
+use tracing::{error, warn};
+
+use crate::clock::Clock;
+use crate::database::{Database, ScheduledJob};
+use crate::forum::week_start;
+use crate::jobs::JobHandler;
+use crate::milestones::days_smoke_free;
+use crate::rollover::start_of_day;
+use crate::Error;
+
+/// Scheduled job kind evaluating the custom achievements
+pub const ACHIEVEMENT_JOB: &str = "achievement_evaluation";
+
+/// Interval between evaluations of the custom achievements
+const EVALUATION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Most comparisons in one condition
+pub const MAX_CLAUSES: usize = 5;
+
+/// A number achievements can be conditioned on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    /// Units logged on the last completed day
+    DailyTotal,
+    /// Units logged in the last completed week
+    WeeklyTotal,
+    /// Units logged in the last completed month
+    MonthlyTotal,
+    /// Full days since the last log
+    SmokeFreeDays,
+}
+
+impl Metric {
+    /// Every metric, in the order shown to users
+    pub const ALL: [Metric; 4] = [
+        Metric::DailyTotal,
+        Metric::WeeklyTotal,
+        Metric::MonthlyTotal,
+        Metric::SmokeFreeDays,
+    ];
+
+    /// Returns the name used in conditions.
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::DailyTotal => "daily_total",
+            Metric::WeeklyTotal => "weekly_total",
+            Metric::MonthlyTotal => "monthly_total",
+            Metric::SmokeFreeDays => "smoke_free_days",
+        }
+    }
+}
+
+/// How a metric is compared with the number of a condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    /// Operators as written in conditions; two-character ones first so they match before `<` and `>`
+    const OPERATORS: [(&'static str, Comparison); 5] = [
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("==", Comparison::Equal),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ];
+
+    /// Returns the operator as written in conditions.
+    pub fn operator(self) -> &'static str {
+        Self::OPERATORS
+            .iter()
+            .find(|(_, comparison)| *comparison == self)
+            .map(|(operator, _)| *operator)
+            .unwrap_or_default()
+    }
+
+    /// Compares a value with the number of a condition.
+    fn holds(self, value: i64, number: i64) -> bool {
+        match self {
+            Comparison::Less => value < number,
+            Comparison::LessOrEqual => value <= number,
+            Comparison::Equal => value == number,
+            Comparison::GreaterOrEqual => value >= number,
+            Comparison::Greater => value > number,
+        }
+    }
+}
+
+/// One comparison of a condition, e.g. `weekly_total<=10`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clause {
+    pub metric: Metric,
+    pub comparison: Comparison,
+    pub number: i64,
+}
+
+/// The condition of an achievement: every clause must hold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    pub clauses: Vec<Clause>,
+}
+
+/// Error returned when a condition cannot be parsed
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ConditionError {
+    #[error("Empty condition")]
+    Empty,
+    #[error("Too many comparisons (at most {MAX_CLAUSES})")]
+    TooManyClauses,
+    #[error("Missing comparison operator in: {0}")]
+    MissingOperator(String),
+    #[error("Unknown metric: {0}")]
+    UnknownMetric(String),
+    #[error("Invalid number: {0}")]
+    InvalidNumber(String),
+}
+
+impl FromStr for Condition {
+    type Err = ConditionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(ConditionError::Empty);
+        }
+
+        let clauses = s
+            .split("&&")
+            .map(|clause| {
+                let clause = clause.trim();
+                let (position, operator, comparison) = Comparison::OPERATORS
+                    .iter()
+                    .filter_map(|(operator, comparison)| {
+                        clause
+                            .find(operator)
+                            .map(|position| (position, *operator, *comparison))
+                    })
+                    // The leftmost operator wins; at a tie, the longer one listed first.
+                    .min_by_key(|(position, _, _)| *position)
+                    .ok_or_else(|| ConditionError::MissingOperator(clause.to_string()))?;
+                let name = clause[..position].trim();
+                let number = clause[position + operator.len()..].trim();
+
+                Ok(Clause {
+                    metric: Metric::ALL
+                        .into_iter()
+                        .find(|metric| metric.name() == name)
+                        .ok_or_else(|| ConditionError::UnknownMetric(name.to_string()))?,
+                    comparison,
+                    number: number
+                        .parse()
+                        .map_err(|_| ConditionError::InvalidNumber(number.to_string()))?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if clauses.len() > MAX_CLAUSES {
+            return Err(ConditionError::TooManyClauses);
+        }
+
+        Ok(Self { clauses })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let clauses: Vec<String> = self
+            .clauses
+            .iter()
+            .map(|clause| {
+                format!(
+                    "{}{}{}",
+                    clause.metric.name(),
+                    clause.comparison.operator(),
+                    clause.number
+                )
+            })
+            .collect();
+
+        write!(f, "{}", clauses.join(" && "))
+    }
+}
+
+impl Condition {
+    /// Returns whether a member's metrics meet the condition.
+    ///
+    /// # Arguments
+    /// * `metrics` - The member's metrics.
+    ///
+    /// # Returns
+    /// `true` if every clause holds.
+    pub fn evaluate(&self, metrics: &Metrics) -> bool {
+        self.clauses.iter().all(|clause| {
+            clause
+                .comparison
+                .holds(metrics.value(clause.metric), clause.number)
+        })
+    }
+}
+
+/// A member's values of every metric
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metrics {
+    pub daily_total: i64,
+    pub weekly_total: i64,
+    pub monthly_total: i64,
+    pub smoke_free_days: i64,
+}
+
+impl Metrics {
+    /// Returns the value of a metric.
+    pub fn value(&self, metric: Metric) -> i64 {
+        match metric {
+            Metric::DailyTotal => self.daily_total,
+            Metric::WeeklyTotal => self.weekly_total,
+            Metric::MonthlyTotal => self.monthly_total,
+            Metric::SmokeFreeDays => self.smoke_free_days,
+        }
+    }
+}
+
+/// Returns the last completed day, week and month before a date.
+///
+/// # Arguments
+/// * `today` - The current local date.
+///
+/// # Returns
+/// The first and last-plus-one dates of the day, the week and the month.
+pub fn completed_periods(today: NaiveDate) -> [(NaiveDate, NaiveDate); 3] {
+    let month = today.with_day(1).unwrap_or(today);
+    let week = week_start(today);
+
+    [
+        (today - chrono::Duration::days(1), today),
+        (week - chrono::Duration::days(7), week),
+        (month - Months::new(1), month),
+    ]
+}
+
+/// Computes a member's metrics.
+///
+/// # Arguments
+/// * `db` - The database.
+/// * `clock` - The clock determining the current date.
+/// * `discord_id` - The Discord ID of the member.
+///
+/// # Returns
+/// A Result containing the metrics or an `Error`.
+pub async fn user_metrics(
+    db: &Database,
+    clock: &dyn Clock,
+    discord_id: &str,
+) -> Result<Metrics, sqlx::Error> {
+    let timezone = clock.timezone();
+    let mut totals = [0; 3];
+    for (total, (start, end)) in totals.iter_mut().zip(completed_periods(clock.today())) {
+        *total = db
+            .get_user_type_totals(
+                discord_id,
+                start_of_day(timezone, start),
+                start_of_day(timezone, end),
+            )
+            .await?
+            .iter()
+            .map(|total| total.total_quantity)
+            .sum();
+    }
+
+    Ok(Metrics {
+        daily_total: totals[0],
+        weekly_total: totals[1],
+        monthly_total: totals[2],
+        smoke_free_days: days_smoke_free(db, clock, discord_id).await?,
+    })
+}
+
+/// Evaluates a guild's achievements for every member who logged there.
+///
+/// # Arguments
+/// * `database` - Database connection shared with the bot.
+/// * `clock` - The clock determining the current date.
+/// * `guild_id` - The ID of the guild.
+///
+/// # Returns
+/// A Result containing the number of newly earned achievements or an `Error`.
+pub async fn evaluate_guild(
+    database: &Mutex<Database>,
+    clock: &dyn Clock,
+    guild_id: &str,
+) -> Result<u64, Error> {
+    let db = database.lock().await;
+    let guild = db.guild(guild_id);
+    let achievements: Vec<(i32, Condition)> = guild
+        .get_achievements()
+        .await?
+        .into_iter()
+        .filter_map(|achievement| match achievement.condition.parse() {
+            Ok(condition) => Some((achievement.id, condition)),
+            Err(e) => {
+                warn!(
+                    "Skipping achievement {} of {}: {}",
+                    achievement.name, guild_id, e
+                );
+                None
+            }
+        })
+        .collect();
+
+    let mut earned = 0;
+    for user_id in guild.get_user_ids().await? {
+        let metrics = user_metrics(&db, clock, &user_id).await?;
+        for (achievement_id, condition) in &achievements {
+            if condition.evaluate(&metrics)
+                && guild
+                    .award_achievement(*achievement_id, &user_id, clock.now())
+                    .await?
+            {
+                earned += 1;
+            }
+        }
+    }
+
+    Ok(earned)
+}
+
+/// Job handler evaluating every guild's achievements every `EVALUATION_INTERVAL`
+pub struct AchievementJob {
+    database: Arc<Mutex<Database>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl AchievementJob {
+    /// Creates the handler.
+    ///
+    /// # Arguments
+    /// * `database` - Database connection shared with the bot.
+    /// * `clock` - The clock determining the current date.
+    pub fn new(database: Arc<Mutex<Database>>, clock: Arc<dyn Clock>) -> Self {
+        Self { database, clock }
+    }
+}
+
+#[async_trait]
+impl JobHandler for AchievementJob {
+    async fn run(&self, _job: &ScheduledJob) -> Result<(), Error> {
+        let guilds = match self.database.lock().await.get_achievement_guilds().await {
+            Ok(guilds) => guilds,
+            Err(e) => {
+                error!("Failed to load achievement guilds: {}", e);
+                return Err(e.into());
+            }
+        };
+        for guild_id in guilds {
+            if let Err(e) = evaluate_guild(&self.database, self.clock.as_ref(), &guild_id).await {
+                warn!("Failed to evaluate achievements of {}: {}", guild_id, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn repeat_every(&self) -> Option<Duration> {
+        Some(EVALUATION_INTERVAL)
+    }
+}
@@ -0,0 +1,111 @@
+//! Guild-specific messages members acknowledge before using the panel.
+//!
+//! Guild admins can require an acknowledgment (by default, that the member
+//! is of legal smoking age) with the `age_gate` command. A member pressing a
+//! panel button in that guild without having acknowledged the message gets
+//! an ephemeral prompt; its button records the acknowledgment and then logs
+//! the panel button that triggered the prompt.
+
+use poise::serenity_prelude as serenity;
+
+use crate::commands::panel::log_button_press;
+use crate::installation::interaction_guild;
+use crate::{deferral, Data, Error};
+
+/// Message acknowledged when the admin does not configure one
+pub const DEFAULT_MESSAGE: &str = "私は居住地域の法令で喫煙が認められる年齢に達しています。";
+
+/// Prefix of the custom ID of the acknowledge button, followed by the smoking type to log
+const ACCEPT_PREFIX: &str = "acknowledgment:accept:";
+
+/// Encodes the custom ID of the acknowledge button.
+///
+/// # Arguments
+/// * `smoking_type_id` - The smoking type logged once the message is acknowledged.
+///
+/// # Returns
+/// The custom ID.
+pub fn accept_id(smoking_type_id: i32) -> String {
+    format!("{}{}", ACCEPT_PREFIX, smoking_type_id)
+}
+
+/// Decodes the custom ID of an acknowledge button.
+///
+/// # Arguments
+/// * `custom_id` - The custom ID of a pressed button.
+///
+/// # Returns
+/// The smoking type to log, or `None` if the button is not an acknowledge button.
+pub fn parse_accept_id(custom_id: &str) -> Option<i32> {
+    let smoking_type_id = custom_id.strip_prefix(ACCEPT_PREFIX)?;
+    let parsed: i32 = smoking_type_id.parse().ok()?;
+
+    (parsed.to_string() == smoking_type_id).then_some(parsed)
+}
+
+/// Responds to a panel button press with an ephemeral prompt to acknowledge the guild's message.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `mci` - The button press that requires the acknowledgment.
+/// * `message` - The guild's message.
+/// * `smoking_type_id` - The smoking type of the pressed button, logged once acknowledged.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn request_acknowledgment(
+    ctx: &serenity::Context,
+    mci: &serenity::ComponentInteraction,
+    message: &str,
+    smoking_type_id: i32,
+) -> Result<(), Error> {
+    let button = serenity::CreateButton::new(accept_id(smoking_type_id))
+        .style(serenity::ButtonStyle::Success)
+        .label("確認しました");
+    deferral::respond(
+        ctx,
+        mci,
+        format!(
+            "このサーバーでパネルを使うには、次の内容の確認が必要です。\n> {}",
+            message
+        ),
+        vec![serenity::CreateActionRow::Buttons(vec![button])],
+        Vec::new(),
+        true,
+    )
+    .await
+}
+
+/// Handles a press of the acknowledge button: records the acknowledgment,
+/// then logs the button the member originally pressed.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `mci` - The acknowledge button press.
+/// * `smoking_type_id` - The smoking type to log.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn handle_accept(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &serenity::ComponentInteraction,
+    smoking_type_id: i32,
+) -> Result<(), Error> {
+    let Some(guild_id) = interaction_guild(mci) else {
+        return Ok(());
+    };
+
+    {
+        let db = data.database.lock().await;
+        let user = db
+            .get_or_create_user(&mci.user.id.get().to_string(), &mci.user.name)
+            .await?;
+        db.guild(&guild_id.to_string())
+            .acknowledge(&user.discord_id, data.clock.now())
+            .await?;
+    }
+
+    log_button_press(ctx, data, mci, smoking_type_id).await
+}
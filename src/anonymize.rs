@@ -0,0 +1,62 @@
+//! Pseudonymization of Discord IDs leaving the bot.
+//!
+//! Operators who forward data to analytics tools may not want personal
+//! identifiers stored downstream. When `ANONYMIZE_SALT` is set, user and
+//! guild IDs in exports, metrics and external integrations are replaced by a
+//! salted SHA-256 digest: the same ID always maps to the same value, so
+//! per-user aggregation still works, but the original ID cannot be recovered
+//! without the salt. IDs stored in the bot's own database are not affected.
+
+use sha2::{Digest, Sha256};
+
+/// Replaces Discord IDs in outgoing data, if configured to
+#[derive(Debug, Clone, Default)]
+pub struct Anonymizer {
+    salt: Option<String>,
+}
+
+impl Anonymizer {
+    /// Creates an anonymizer.
+    ///
+    /// # Arguments
+    /// * `salt` - The secret mixed into every digest; IDs are passed through unchanged when `None`.
+    pub fn new(salt: Option<String>) -> Self {
+        Self { salt }
+    }
+
+    /// Returns whether IDs are replaced.
+    pub fn is_enabled(&self) -> bool {
+        self.salt.is_some()
+    }
+
+    /// Anonymizes a Discord ID.
+    ///
+    /// # Arguments
+    /// * `id` - The user or guild ID.
+    ///
+    /// # Returns
+    /// The hex digest of the salted ID, or the ID itself when anonymization is disabled.
+    pub fn id(&self, id: &str) -> String {
+        match &self.salt {
+            Some(salt) => {
+                let mut hasher = Sha256::new();
+                hasher.update(salt.as_bytes());
+                hasher.update(b":");
+                hasher.update(id.as_bytes());
+                hex::encode(hasher.finalize())
+            }
+            None => id.to_string(),
+        }
+    }
+
+    /// Anonymizes an optional Discord ID, such as the guild of a log.
+    ///
+    /// # Arguments
+    /// * `id` - The ID, if any.
+    ///
+    /// # Returns
+    /// The anonymized ID, if any.
+    pub fn optional_id(&self, id: Option<&str>) -> Option<String> {
+        id.map(|id| self.id(id))
+    }
+}
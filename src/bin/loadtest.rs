@@ -0,0 +1,439 @@
+//! Load-test tool for the repository layer and the inbound HTTP API.
+//!
+//! Creates synthetic users and drives the same log+summary path as the
+//! panel buttons (or `POST /api/log` with `--http`) from many concurrent
+//! workers, then reports throughput and latency percentiles.
+//!
+//! ```text
+//! cargo run --release --bin loadtest -- [OPTIONS]
+//!
+//!   --database-url <URL>    Database to load (default: DATABASE_URL)
+//!   --users <N>             Number of synthetic users (default: 1000)
+//!   --logs-per-user <N>     Logs recorded per user (default: 10)
+//!   --concurrency <N>       Number of concurrent workers (default: 50)
+//!   --mode <mutex|direct>   Share one Database behind a Mutex like the bot
+//!                           does, or give workers unsynchronized access
+//!                           (default: mutex)
+//!   --http <URL>            Post to a running bot's API instead of calling
+//!                           the repository layer directly
+//!   --keep                  Keep the synthetic data instead of deleting it
+//! ```
+//!
+//! Synthetic users get Discord IDs starting at `LOADTEST_ID_BASE`, so they
+//! never collide with real accounts and can be removed afterwards.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use cigarette_counter::{
+    clock::{Clock, SystemClock},
+    consent::POLICY_VERSION,
+    database::Database,
+    http,
+};
+use poise::serenity_prelude::futures::lock::Mutex;
+use sqlx::{postgres::PgPoolOptions, PgPool};
+
+/// First Discord ID used for synthetic users
+const LOADTEST_ID_BASE: u64 = 900_000_000_000_000_000;
+
+/// Smoking type used for every synthetic log
+const SMOKING_TYPE_ID: i32 = 1;
+
+/// Errors that abort a load-test run
+#[derive(Debug, thiserror::Error)]
+enum LoadTestError {
+    #[error("Invalid arguments: {0}")]
+    Usage(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// How workers reach the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// One `Database` behind a `Mutex`, as shared by the bot today
+    Mutex,
+    /// One `Database` shared without a lock
+    Direct,
+}
+
+/// Parsed command-line options
+#[derive(Debug)]
+struct Options {
+    database_url: String,
+    users: usize,
+    logs_per_user: usize,
+    concurrency: usize,
+    mode: Mode,
+    http: Option<String>,
+    keep: bool,
+}
+
+impl Options {
+    /// Parses options from the command line.
+    ///
+    /// # Returns
+    /// A Result containing the options or a usage error.
+    fn parse() -> Result<Self, LoadTestError> {
+        let mut options = Self {
+            database_url: std::env::var("DATABASE_URL").unwrap_or_default(),
+            users: 1000,
+            logs_per_user: 10,
+            concurrency: 50,
+            mode: Mode::Mutex,
+            http: None,
+            keep: false,
+        };
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "--keep" {
+                options.keep = true;
+                continue;
+            }
+
+            let value = args
+                .next()
+                .ok_or_else(|| LoadTestError::Usage(format!("{} requires a value", arg)))?;
+            match arg.as_str() {
+                "--database-url" => options.database_url = value,
+                "--users" => options.users = parse_count(&arg, &value)?,
+                "--logs-per-user" => options.logs_per_user = parse_count(&arg, &value)?,
+                "--concurrency" => options.concurrency = parse_count(&arg, &value)?,
+                "--mode" => {
+                    options.mode = match value.as_str() {
+                        "mutex" => Mode::Mutex,
+                        "direct" => Mode::Direct,
+                        _ => {
+                            return Err(LoadTestError::Usage(format!(
+                                "unknown mode '{}' (expected mutex or direct)",
+                                value
+                            )))
+                        }
+                    }
+                }
+                "--http" => options.http = Some(value.trim_end_matches('/').to_string()),
+                _ => return Err(LoadTestError::Usage(format!("unknown option '{}'", arg))),
+            }
+        }
+
+        if options.database_url.is_empty() {
+            return Err(LoadTestError::Usage(
+                "--database-url or DATABASE_URL is required".to_string(),
+            ));
+        }
+
+        Ok(options)
+    }
+}
+
+/// Parses a positive count argument.
+///
+/// # Arguments
+/// * `name` - The option name, for error messages.
+/// * `value` - The raw value.
+///
+/// # Returns
+/// A Result containing the count or a usage error.
+fn parse_count(name: &str, value: &str) -> Result<usize, LoadTestError> {
+    match value.parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(LoadTestError::Usage(format!(
+            "{} must be a positive integer",
+            name
+        ))),
+    }
+}
+
+/// How a worker performs one operation
+enum Target {
+    Mutex(Arc<Mutex<Database>>),
+    Direct(Arc<Database>),
+    Http {
+        client: reqwest::Client,
+        url: String,
+        tokens: Vec<String>,
+    },
+}
+
+impl Target {
+    /// Records one log for a synthetic user.
+    ///
+    /// # Arguments
+    /// * `user` - The index of the synthetic user.
+    ///
+    /// # Returns
+    /// A Result indicating whether the operation succeeded.
+    async fn run(&self, user: usize) -> Result<(), String> {
+        let discord_id = synthetic_id(user);
+        let clock = SystemClock::host();
+        let today = clock.today();
+        let timezone = clock.timezone();
+
+        match self {
+            Target::Mutex(database) => {
+                let db = database.lock().await;
+                db.log_smoking_with_summary(
+                    &discord_id,
+                    None,
+                    SMOKING_TYPE_ID,
+                    1,
+                    None,
+                    today,
+                    timezone,
+                )
+                .await
+                .map(drop)
+                .map_err(|e| e.to_string())
+            }
+            Target::Direct(db) => db
+                .log_smoking_with_summary(
+                    &discord_id,
+                    None,
+                    SMOKING_TYPE_ID,
+                    1,
+                    None,
+                    today,
+                    timezone,
+                )
+                .await
+                .map(drop)
+                .map_err(|e| e.to_string()),
+            Target::Http {
+                client,
+                url,
+                tokens,
+            } => {
+                let response = client
+                    .post(format!("{}/api/log", url))
+                    .bearer_auth(&tokens[user])
+                    .json(&serde_json::json!({ "smoking_type_id": SMOKING_TYPE_ID }))
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+
+                if response.status().is_success() {
+                    Ok(())
+                } else {
+                    Err(format!("HTTP {}", response.status()))
+                }
+            }
+        }
+    }
+}
+
+/// Returns the Discord ID of a synthetic user.
+///
+/// # Arguments
+/// * `user` - The index of the synthetic user.
+fn synthetic_id(user: usize) -> String {
+    (LOADTEST_ID_BASE + user as u64).to_string()
+}
+
+/// Creates the synthetic users, and device tokens for them when testing over HTTP.
+///
+/// # Arguments
+/// * `db` - The database to populate.
+/// * `options` - The load-test options.
+///
+/// # Returns
+/// A Result containing one device token per user (empty unless `--http` is set).
+async fn create_users(db: &Database, options: &Options) -> Result<Vec<String>, LoadTestError> {
+    let mut tokens = Vec::new();
+
+    for user in 0..options.users {
+        let discord_id = synthetic_id(user);
+        db.get_or_create_user(&discord_id, &format!("loadtest-{}", user))
+            .await?;
+        db.record_consent(&discord_id, POLICY_VERSION, chrono::Utc::now())
+            .await?;
+
+        if options.http.is_some() {
+            let (token, hash) = http::generate_token();
+            db.create_device(&discord_id, "loadtest", &hash).await?;
+            tokens.push(token);
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Deletes all synthetic users and their data.
+///
+/// # Arguments
+/// * `pool` - The database pool.
+///
+/// # Returns
+/// A Result containing the number of deleted users.
+async fn delete_users(pool: &PgPool) -> Result<u64, LoadTestError> {
+    let min_id = LOADTEST_ID_BASE.to_string();
+    let mut tx = pool.begin().await?;
+
+    for table in ["smoking_logs", "devices"] {
+        sqlx::query(&format!(
+            "DELETE FROM {} WHERE length(discord_id) = length($1) AND discord_id >= $1",
+            table
+        ))
+        .bind(&min_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    let deleted =
+        sqlx::query("DELETE FROM users WHERE length(discord_id) = length($1) AND discord_id >= $1")
+            .bind(&min_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+    tx.commit().await?;
+    Ok(deleted)
+}
+
+/// Result of a load-test run
+struct Report {
+    elapsed: Duration,
+    latencies: Vec<Duration>,
+    errors: Vec<String>,
+}
+
+impl Report {
+    /// Prints throughput, latency percentiles and a sample of errors.
+    fn print(mut self) {
+        self.latencies.sort();
+        let total = self.latencies.len() + self.errors.len();
+
+        println!("operations: {} ({} failed)", total, self.errors.len());
+        println!("elapsed:    {:.2}s", self.elapsed.as_secs_f64());
+        println!(
+            "throughput: {:.1} ops/s",
+            total as f64 / self.elapsed.as_secs_f64()
+        );
+        for (label, quantile) in [("p50", 0.50), ("p90", 0.90), ("p99", 0.99), ("max", 1.0)] {
+            println!(
+                "{}:        {:.2}ms",
+                label,
+                percentile(&self.latencies, quantile).as_secs_f64() * 1000.0
+            );
+        }
+        for error in self.errors.iter().take(5) {
+            println!("error:      {}", error);
+        }
+    }
+}
+
+/// Returns the latency at the given quantile of a sorted sample.
+///
+/// # Arguments
+/// * `sorted` - Latencies in ascending order.
+/// * `quantile` - The quantile between 0 and 1.
+fn percentile(sorted: &[Duration], quantile: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+
+    let index = ((sorted.len() - 1) as f64 * quantile).round() as usize;
+    sorted[index]
+}
+
+/// Runs the workload with the configured number of workers.
+///
+/// # Arguments
+/// * `target` - How operations are performed.
+/// * `options` - The load-test options.
+///
+/// # Returns
+/// The collected measurements.
+async fn run_workload(target: Arc<Target>, options: &Options) -> Report {
+    let total = options.users * options.logs_per_user;
+    let users = options.users;
+    let next = Arc::new(AtomicUsize::new(0));
+    let start = Instant::now();
+
+    let workers: Vec<_> = (0..options.concurrency)
+        .map(|_| {
+            let target = target.clone();
+            let next = next.clone();
+            tokio::spawn(async move {
+                let mut latencies = Vec::new();
+                let mut errors = Vec::new();
+
+                loop {
+                    let op = next.fetch_add(1, Ordering::Relaxed);
+                    if op >= total {
+                        break;
+                    }
+
+                    let started = Instant::now();
+                    match target.run(op % users).await {
+                        Ok(()) => latencies.push(started.elapsed()),
+                        Err(e) => errors.push(e),
+                    }
+                }
+
+                (latencies, errors)
+            })
+        })
+        .collect();
+
+    let mut report = Report {
+        elapsed: Duration::ZERO,
+        latencies: Vec::with_capacity(total),
+        errors: Vec::new(),
+    };
+    for worker in workers {
+        let (latencies, errors) = worker.await.expect("worker panicked");
+        report.latencies.extend(latencies);
+        report.errors.extend(errors);
+    }
+    report.elapsed = start.elapsed();
+
+    report
+}
+
+#[tokio::main]
+async fn main() -> Result<(), LoadTestError> {
+    let options = Options::parse()?;
+
+    let pool = PgPoolOptions::new()
+        .max_connections(options.concurrency as u32)
+        .connect(&options.database_url)
+        .await?;
+    let database = Database::new(pool.clone());
+
+    println!("creating {} synthetic users...", options.users);
+    let tokens = create_users(&database, &options).await?;
+
+    let target = match &options.http {
+        Some(url) => Target::Http {
+            client: reqwest::Client::new(),
+            url: url.clone(),
+            tokens,
+        },
+        None if options.mode == Mode::Mutex => Target::Mutex(Arc::new(Mutex::new(database))),
+        None => Target::Direct(Arc::new(database)),
+    };
+    let description = match &options.http {
+        Some(url) => format!("http {}", url),
+        None => format!("{:?}", options.mode).to_lowercase(),
+    };
+
+    println!(
+        "running {} x {} logs with {} workers ({})...",
+        options.users, options.logs_per_user, options.concurrency, description
+    );
+    let report = run_workload(Arc::new(target), &options).await;
+    report.print();
+
+    if !options.keep {
+        let deleted = delete_users(&pool).await?;
+        println!("deleted {} synthetic users", deleted);
+    }
+
+    Ok(())
+}
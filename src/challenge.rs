@@ -0,0 +1,307 @@
+//! Weekly guild challenges: a collective reduction target.
+//!
+//! Guilds that opt in with `challenge start` aim to bring the whole server's
+//! weekly total a percentage below the week before. A recurring job posts a
+//! progress embed in the chosen channel at the start of each week, with the
+//! week before's total fixed as the baseline, and updates it once a day.
+//! When a week ends, its embed shows the final total and, if the target was
+//! reached, the bot posts a celebration. Weeks start on Monday in the guild's
+//! time zone, like the weekly forum threads.
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+use tracing::warn;
+
+use crate::clock::Clock;
+use crate::database::{ChallengeWeek, Database, GuildChallenge, ScheduledJob};
+use crate::embed::{progress_bar, GoalStatus, ReplyEmbed, Title};
+use crate::format::{format_count, format_date, Locale};
+use crate::forum::week_start;
+use crate::jobs::JobHandler;
+use crate::rollover::start_of_day;
+use crate::Error;
+
+/// Scheduled job kind posting and updating the challenge progress
+pub const CHALLENGE_JOB: &str = "guild_weekly_challenge";
+
+/// Interval between checks for a new day or week
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Returns the most a guild may log in a challenge week to reach its target.
+///
+/// # Arguments
+/// * `baseline` - The total of the week before.
+/// * `target_percent` - The reduction aimed for, in percent.
+///
+/// # Returns
+/// The target total, rounded down.
+pub fn target_total(baseline: i64, target_percent: i32) -> i64 {
+    baseline * i64::from(100 - target_percent) / 100
+}
+
+/// Returns how much a week's total is below its baseline.
+///
+/// # Arguments
+/// * `baseline` - The total of the week before.
+/// * `total` - The total of the week.
+///
+/// # Returns
+/// The reduction in percent, negative for an increase, or `None` without a baseline.
+pub fn reduction_percent(baseline: i64, total: i64) -> Option<i64> {
+    (baseline > 0).then(|| (baseline - total) * 100 / baseline)
+}
+
+/// Builds the progress embed of a challenge week.
+///
+/// # Arguments
+/// * `week` - The challenge week.
+/// * `total` - The guild's total of the week so far.
+/// * `finished` - Whether the week is over.
+///
+/// # Returns
+/// The embed, colored like the daily goal by how close the total is to the target.
+pub fn progress_embed(week: &ChallengeWeek, total: i64, finished: bool) -> ReplyEmbed {
+    let locale = Locale::Japanese;
+    let target = target_total(week.baseline, week.target_percent);
+    let heading = format!(
+        "{}〜{}のチャレンジ{}",
+        format_date(week.week_start, locale),
+        format_date(week.week_start + chrono::Duration::days(6), locale),
+        if finished { "（終了）" } else { "" }
+    );
+    let description = format!(
+        "{}\n先週の合計: {}\n目標: {}以下（{}%削減）\n{} {} / {}",
+        heading,
+        format_count(week.baseline, locale),
+        format_count(target, locale),
+        week.target_percent,
+        progress_bar(total, i32::try_from(target).unwrap_or(i32::MAX)),
+        format_count(total, locale),
+        format_count(target, locale)
+    );
+    let remaining = target - total;
+    let footer = match (finished, remaining >= 0) {
+        (true, true) => "目標達成！".to_string(),
+        (true, false) => format!("目標まであと{}でした", format_count(-remaining, locale)),
+        (false, true) => format!("残り {}", format_count(remaining, locale)),
+        (false, false) => format!("目標を{}超えています", format_count(-remaining, locale)),
+    };
+
+    ReplyEmbed::styled(Some(Title::Challenge), description, locale)
+        .color(GoalStatus::of(total, i32::try_from(target).unwrap_or(i32::MAX)).color())
+        .footer(footer)
+}
+
+/// Renders the post celebrating a reached target.
+///
+/// # Arguments
+/// * `week` - The finished challenge week.
+/// * `total` - The guild's total of the week.
+///
+/// # Returns
+/// The celebration.
+pub fn celebration(week: &ChallengeWeek, total: i64) -> String {
+    let locale = Locale::Japanese;
+    match reduction_percent(week.baseline, total) {
+        Some(reduction) => format!(
+            "🎉 {}からの週のチャレンジを達成しました！ サーバー全体の合計は{}で、前の週の{}から{}%減りました。",
+            format_date(week.week_start, locale),
+            format_count(total, locale),
+            format_count(week.baseline, locale),
+            reduction
+        ),
+        None => format!(
+            "🎉 {}からの週のチャレンジを達成しました！ サーバー全体で記録はありませんでした。",
+            format_date(week.week_start, locale)
+        ),
+    }
+}
+
+/// Job handler posting, updating and settling the challenge weeks every `CHECK_INTERVAL`
+pub struct ChallengeJob {
+    http: Arc<serenity::Http>,
+    database: Arc<Mutex<Database>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl ChallengeJob {
+    /// Creates the handler.
+    ///
+    /// # Arguments
+    /// * `http` - The Discord HTTP client.
+    /// * `database` - Database connection shared with the bot.
+    /// * `clock` - The clock deciding the current day and week.
+    pub fn new(
+        http: Arc<serenity::Http>,
+        database: Arc<Mutex<Database>>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            http,
+            database,
+            clock,
+        }
+    }
+
+    /// Totals the guild's logs over the week starting on a Monday, up to `end` if given.
+    async fn week_total(
+        &self,
+        guild_id: &str,
+        timezone: Tz,
+        week: NaiveDate,
+        end: Option<DateTime<Utc>>,
+    ) -> Result<i64, Error> {
+        let totals = self
+            .database
+            .lock()
+            .await
+            .guild(guild_id)
+            .get_type_totals(
+                start_of_day(timezone, week),
+                end.unwrap_or_else(|| start_of_day(timezone, week + chrono::Duration::days(7))),
+            )
+            .await?;
+
+        Ok(totals.iter().map(|total| total.total_quantity).sum())
+    }
+
+    /// Settles the guild's finished weeks, then posts or updates the current week's progress.
+    ///
+    /// # Arguments
+    /// * `challenge` - The guild's challenge.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    async fn update_guild(&self, challenge: &GuildChallenge) -> Result<(), Error> {
+        let guild_id = &challenge.guild_id;
+        let timezone = {
+            let db = self.database.lock().await;
+            db.guild(guild_id).get_timezone().await?
+        }
+        .unwrap_or(self.clock.timezone());
+        let now = self.clock.now();
+        let today = now.with_timezone(&timezone).date_naive();
+        let week = week_start(today);
+
+        let finished = {
+            let db = self.database.lock().await;
+            db.guild(guild_id)
+                .get_unsettled_challenge_weeks_before(week)
+                .await?
+        };
+        for past in finished {
+            let total = self
+                .week_total(guild_id, timezone, past.week_start, None)
+                .await?;
+            if let Err(e) = self.edit_progress(&past, total, true).await {
+                warn!(
+                    "Failed to finish the challenge progress of {} in {}: {}",
+                    past.week_start, guild_id, e
+                );
+            }
+            let achieved = total <= target_total(past.baseline, past.target_percent);
+            if achieved {
+                serenity::ChannelId::new(past.channel_id.parse()?)
+                    .say(&self.http, celebration(&past, total))
+                    .await?;
+            }
+            self.database
+                .lock()
+                .await
+                .guild(guild_id)
+                .settle_challenge_week(past.week_start, achieved, now)
+                .await?;
+        }
+
+        let current = {
+            let db = self.database.lock().await;
+            db.guild(guild_id).get_challenge_week(week).await?
+        };
+        let total = self.week_total(guild_id, timezone, week, Some(now)).await?;
+        match current {
+            None => {
+                let baseline = self
+                    .week_total(guild_id, timezone, week - chrono::Duration::days(7), None)
+                    .await?;
+                let mut current = ChallengeWeek {
+                    week_start: week,
+                    channel_id: challenge.channel_id.clone(),
+                    message_id: String::new(),
+                    baseline,
+                    target_percent: challenge.target_percent,
+                    updated_on: today,
+                };
+                let message = serenity::ChannelId::new(challenge.channel_id.parse()?)
+                    .send_message(
+                        &self.http,
+                        serenity::CreateMessage::new()
+                            .embed(progress_embed(&current, total, false).to_discord()),
+                    )
+                    .await?;
+                current.message_id = message.id.to_string();
+                self.database
+                    .lock()
+                    .await
+                    .guild(guild_id)
+                    .record_challenge_week(&current)
+                    .await?;
+            }
+            Some(current) if current.updated_on < today => {
+                self.edit_progress(&current, total, false).await?;
+                self.database
+                    .lock()
+                    .await
+                    .guild(guild_id)
+                    .mark_challenge_week_updated(week, today)
+                    .await?;
+            }
+            Some(_) => {}
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the progress embed of a challenge week.
+    async fn edit_progress(
+        &self,
+        week: &ChallengeWeek,
+        total: i64,
+        finished: bool,
+    ) -> Result<(), Error> {
+        serenity::ChannelId::new(week.channel_id.parse()?)
+            .edit_message(
+                &self.http,
+                serenity::MessageId::new(week.message_id.parse()?),
+                serenity::EditMessage::new()
+                    .embed(progress_embed(week, total, finished).to_discord()),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl JobHandler for ChallengeJob {
+    async fn run(&self, _job: &ScheduledJob) -> Result<(), Error> {
+        let challenges = self.database.lock().await.get_guild_challenges().await?;
+        for challenge in challenges {
+            if let Err(e) = self.update_guild(&challenge).await {
+                warn!(
+                    "Failed to update the weekly challenge of {}: {}",
+                    challenge.guild_id, e
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn repeat_every(&self) -> Option<Duration> {
+        Some(CHECK_INTERVAL)
+    }
+}
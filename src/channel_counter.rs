@@ -0,0 +1,253 @@
+//! A live counter of the guild shown in a channel's topic or name.
+//!
+//! Guilds that set one with `counter set` get the topic of a text channel,
+//! or the name of a voice channel, updated by a recurring job with the
+//! guild's total of the day or the days since anyone last logged. Discord
+//! allows only two topic or name edits per channel every ten minutes, so the
+//! job edits a channel at most once per `MIN_EDIT_INTERVAL` and only when
+//! the text changed.
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+use tracing::warn;
+
+use crate::clock::Clock;
+use crate::database::{ChannelCounter, Database, ScheduledJob};
+use crate::format::{format_count, Locale};
+use crate::jobs::JobHandler;
+use crate::rollover::start_of_day;
+use crate::Error;
+
+/// Scheduled job kind updating the channel counters
+pub const CHANNEL_COUNTER_JOB: &str = "channel_counters";
+
+/// Interval between checks for a changed counter
+const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Shortest time between two edits of a counter's channel
+pub const MIN_EDIT_INTERVAL: chrono::Duration = chrono::Duration::minutes(10);
+
+/// What a channel counter shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterMetric {
+    /// The guild's total of the day
+    DailyTotal,
+    /// Full days since anyone in the guild last logged
+    DaysSince,
+}
+
+impl CounterMetric {
+    /// Every metric, in the order shown to users
+    pub const ALL: [CounterMetric; 2] = [CounterMetric::DailyTotal, CounterMetric::DaysSince];
+
+    /// Returns the name used in commands and stored with the counter.
+    pub fn name(self) -> &'static str {
+        match self {
+            CounterMetric::DailyTotal => "daily-total",
+            CounterMetric::DaysSince => "days-since",
+        }
+    }
+
+    /// Looks up a metric by name.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the metric (e.g. `daily-total`).
+    ///
+    /// # Returns
+    /// The metric, or `None` for unknown names.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|metric| metric.name() == name)
+    }
+}
+
+/// What a channel counter edits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterTarget {
+    /// The topic of a text channel
+    Topic,
+    /// The name of a voice channel, which has no topic
+    Name,
+}
+
+impl CounterTarget {
+    /// Returns the name stored with the counter.
+    pub fn name(self) -> &'static str {
+        match self {
+            CounterTarget::Topic => "topic",
+            CounterTarget::Name => "name",
+        }
+    }
+
+    /// Looks up a target by name.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the target (`topic` or `name`).
+    ///
+    /// # Returns
+    /// The target, or `None` for unknown names.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [CounterTarget::Topic, CounterTarget::Name]
+            .into_iter()
+            .find(|target| target.name() == name)
+    }
+
+    /// Returns what a counter in a channel of the given type edits.
+    ///
+    /// # Arguments
+    /// * `kind` - The type of the channel.
+    ///
+    /// # Returns
+    /// The target, or `None` if the channel can show no counter.
+    pub fn for_channel(kind: serenity::ChannelType) -> Option<Self> {
+        match kind {
+            serenity::ChannelType::Text | serenity::ChannelType::News => Some(CounterTarget::Topic),
+            serenity::ChannelType::Voice | serenity::ChannelType::Stage => {
+                Some(CounterTarget::Name)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Renders the text of a counter.
+///
+/// # Arguments
+/// * `metric` - What the counter shows.
+/// * `value` - The guild's total of the day, or the days since the last log (`None` if nobody logged yet).
+///
+/// # Returns
+/// The text, e.g. `今日の合計: 12本` or `最後の1本から3日`.
+pub fn counter_text(metric: CounterMetric, value: Option<i64>) -> String {
+    match (metric, value) {
+        (CounterMetric::DailyTotal, value) => format!(
+            "今日の合計: {}",
+            format_count(value.unwrap_or_default(), Locale::Japanese)
+        ),
+        (CounterMetric::DaysSince, Some(days)) => format!("最後の1本から{}日", days),
+        (CounterMetric::DaysSince, None) => "まだ記録はありません".to_string(),
+    }
+}
+
+/// Decides whether a counter's channel should be edited.
+///
+/// # Arguments
+/// * `counter` - The counter with its last edit.
+/// * `text` - The current text of the counter.
+/// * `now` - The current time.
+///
+/// # Returns
+/// `true` if the text changed and the channel was not edited in the last `MIN_EDIT_INTERVAL`.
+pub fn should_update(counter: &ChannelCounter, text: &str, now: DateTime<Utc>) -> bool {
+    counter.last_text.as_deref() != Some(text)
+        && counter
+            .updated_at
+            .is_none_or(|updated_at| now - updated_at >= MIN_EDIT_INTERVAL)
+}
+
+/// Job handler updating the channel counters every `CHECK_INTERVAL`
+pub struct ChannelCounterJob {
+    http: Arc<serenity::Http>,
+    database: Arc<Mutex<Database>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl ChannelCounterJob {
+    /// Creates the handler.
+    ///
+    /// # Arguments
+    /// * `http` - The Discord HTTP client.
+    /// * `database` - Database connection shared with the bot.
+    /// * `clock` - The clock deciding the current day.
+    pub fn new(
+        http: Arc<serenity::Http>,
+        database: Arc<Mutex<Database>>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            http,
+            database,
+            clock,
+        }
+    }
+
+    /// Edits a counter's channel if its text changed and the rate limit allows.
+    ///
+    /// # Arguments
+    /// * `counter` - The guild's counter.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    async fn update_counter(&self, counter: &ChannelCounter) -> Result<(), Error> {
+        let metric = CounterMetric::from_name(&counter.metric)
+            .ok_or_else(|| format!("unknown counter metric: {}", counter.metric))?;
+        let target = CounterTarget::from_name(&counter.target)
+            .ok_or_else(|| format!("unknown counter target: {}", counter.target))?;
+        let now = self.clock.now();
+
+        let value = {
+            let db = self.database.lock().await;
+            let guild = db.guild(&counter.guild_id);
+            let timezone = guild.get_timezone().await?.unwrap_or(self.clock.timezone());
+            let today = now.with_timezone(&timezone).date_naive();
+            match metric {
+                CounterMetric::DailyTotal => Some(
+                    guild
+                        .get_type_totals(start_of_day(timezone, today), now)
+                        .await?
+                        .iter()
+                        .map(|total| total.total_quantity)
+                        .sum(),
+                ),
+                CounterMetric::DaysSince => guild.get_last_smoked_at().await?.map(|smoked_at| {
+                    (today - smoked_at.with_timezone(&timezone).date_naive())
+                        .num_days()
+                        .max(0)
+                }),
+            }
+        };
+        let text = counter_text(metric, value);
+        if !should_update(counter, &text, now) {
+            return Ok(());
+        }
+
+        let edit = match target {
+            CounterTarget::Topic => serenity::EditChannel::new().topic(&text),
+            CounterTarget::Name => serenity::EditChannel::new().name(&text),
+        };
+        serenity::ChannelId::new(counter.channel_id.parse()?)
+            .edit(&self.http, edit)
+            .await?;
+        self.database
+            .lock()
+            .await
+            .guild(&counter.guild_id)
+            .record_channel_counter_update(&text, now)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl JobHandler for ChannelCounterJob {
+    async fn run(&self, _job: &ScheduledJob) -> Result<(), Error> {
+        let counters = self.database.lock().await.get_channel_counters().await?;
+        for counter in counters {
+            if let Err(e) = self.update_counter(&counter).await {
+                warn!(
+                    "Failed to update the channel counter of {}: {}",
+                    counter.guild_id, e
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn repeat_every(&self) -> Option<Duration> {
+        Some(CHECK_INTERVAL)
+    }
+}
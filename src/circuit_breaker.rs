@@ -0,0 +1,209 @@
+//! Circuit breaker around database access.
+//!
+//! Requests ask the breaker before touching the database and report whether
+//! the database answered. After `failure_threshold` outages in a row the
+//! circuit opens: requests fail at once with `CircuitOpen`, so the caller can
+//! answer with a friendly error instead of waiting for the pool to time out
+//! while interactions pile up. Once `open_for` has passed the circuit is half
+//! open and lets one request through as a probe; its outcome closes the
+//! circuit again or keeps it open for another `open_for`.
+//!
+//! Only errors showing that the database could not be reached count as
+//! outages. A query rejected by a constraint, or a request failing for a
+//! reason unrelated to the database, proves the database is answering.
+
+use std::{
+    error::Error as StdError,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use tracing::{info, warn};
+
+use crate::Error;
+
+/// Number of times the circuit opened
+static OPENED_TOTAL: AtomicU64 = AtomicU64::new(0);
+
+/// Number of requests rejected while the circuit was open
+static SHORT_CIRCUITED_TOTAL: AtomicU64 = AtomicU64::new(0);
+
+/// Reply sent instead of handling a request while the circuit is open
+pub const UNAVAILABLE_MESSAGE: &str =
+    "現在データベースに接続できないため、記録できません。しばらくしてからもう一度お試しください。";
+
+/// Error returned while the circuit is open
+#[derive(Debug, thiserror::Error)]
+#[error("Database circuit is open")]
+pub struct CircuitOpen;
+
+/// The state of a circuit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests pass; counts the outages in a row
+    Closed { failures: u32 },
+    /// Requests fail until the given time
+    Open { until: Instant },
+    /// A probe request started at the given time decides the next state
+    HalfOpen { probe_started: Instant },
+}
+
+/// Breaker failing requests fast while the database is unreachable
+pub struct CircuitBreaker {
+    state: Mutex<CircuitState>,
+    failure_threshold: u32,
+    open_for: Duration,
+}
+
+impl CircuitBreaker {
+    /// Creates a closed circuit breaker.
+    ///
+    /// # Arguments
+    /// * `failure_threshold` - The outages in a row after which the circuit opens.
+    /// * `open_for` - How long the circuit stays open before a probe is let through.
+    pub fn new(failure_threshold: u32, open_for: Duration) -> Self {
+        Self {
+            state: Mutex::new(CircuitState::Closed { failures: 0 }),
+            failure_threshold: failure_threshold.max(1),
+            open_for,
+        }
+    }
+
+    /// Returns the current state of the circuit.
+    pub fn state(&self) -> CircuitState {
+        *self.state.lock().unwrap()
+    }
+
+    /// Asks whether a request may touch the database.
+    ///
+    /// # Returns
+    /// `Ok` if the request may proceed (as the probe when the circuit is half open), or
+    /// `CircuitOpen` if it must fail at once.
+    pub fn allow(&self) -> Result<(), CircuitOpen> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        match *state {
+            CircuitState::Closed { .. } => Ok(()),
+            CircuitState::Open { until } if now >= until => {
+                *state = CircuitState::HalfOpen { probe_started: now };
+                Ok(())
+            }
+            // A probe that never reported back (e.g. a cancelled request) is replaced.
+            CircuitState::HalfOpen { probe_started } if now >= probe_started + self.open_for => {
+                *state = CircuitState::HalfOpen { probe_started: now };
+                Ok(())
+            }
+            CircuitState::Open { .. } | CircuitState::HalfOpen { .. } => {
+                SHORT_CIRCUITED_TOTAL.fetch_add(1, Ordering::Relaxed);
+                Err(CircuitOpen)
+            }
+        }
+    }
+
+    /// Records that the database answered a request, closing the circuit.
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if matches!(*state, CircuitState::HalfOpen { .. }) {
+            info!("Database reachable again, closing the circuit");
+        }
+        *state = CircuitState::Closed { failures: 0 };
+    }
+
+    /// Records that a request could not reach the database, opening the
+    /// circuit after `failure_threshold` outages in a row or a failed probe.
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        let failures = match *state {
+            CircuitState::Closed { failures } => failures + 1,
+            CircuitState::HalfOpen { .. } => self.failure_threshold,
+            // Requests let through before the circuit opened keep it open.
+            CircuitState::Open { .. } => return,
+        };
+
+        if failures < self.failure_threshold {
+            *state = CircuitState::Closed { failures };
+            return;
+        }
+        warn!(
+            "Database unreachable, opening the circuit for {}s",
+            self.open_for.as_secs()
+        );
+        OPENED_TOTAL.fetch_add(1, Ordering::Relaxed);
+        *state = CircuitState::Open {
+            until: Instant::now() + self.open_for,
+        };
+    }
+
+    /// Records the outcome of a request that was allowed through.
+    ///
+    /// # Arguments
+    /// * `result` - The result of the request.
+    pub fn record<T>(&self, result: &Result<T, Error>) {
+        self.record_outcome(result.as_ref().err().map(|e| e.as_ref() as _));
+    }
+
+    /// Records the outcome of a request that was allowed through.
+    ///
+    /// # Arguments
+    /// * `error` - The error the request failed with, or `None` if it succeeded.
+    pub fn record_outcome(&self, error: Option<&(dyn StdError + 'static)>) {
+        match error {
+            Some(e) if is_outage(e) => self.record_failure(),
+            _ => self.record_success(),
+        }
+    }
+}
+
+/// Returns whether an error shows that the database could not be reached.
+///
+/// # Arguments
+/// * `error` - The error; its sources are inspected too.
+///
+/// # Returns
+/// `true` if the error or one of its sources is a connection-level `sqlx::Error`.
+pub fn is_outage(error: &(dyn StdError + 'static)) -> bool {
+    let mut current = Some(error);
+    while let Some(error) = current {
+        if let Some(error) = error.downcast_ref::<sqlx::Error>() {
+            return matches!(
+                error,
+                sqlx::Error::Io(_)
+                    | sqlx::Error::Tls(_)
+                    | sqlx::Error::Protocol(_)
+                    | sqlx::Error::PoolTimedOut
+                    | sqlx::Error::PoolClosed
+                    | sqlx::Error::WorkerCrashed
+            );
+        }
+        current = error.source();
+    }
+
+    false
+}
+
+/// Renders the breaker metrics in the Prometheus text exposition format.
+///
+/// # Arguments
+/// * `breaker` - The breaker whose state is reported.
+///
+/// # Returns
+/// The metrics text.
+pub fn render_metrics(breaker: &CircuitBreaker) -> String {
+    let open = !matches!(breaker.state(), CircuitState::Closed { .. });
+
+    format!(
+        "# TYPE cigarette_counter_db_circuit_open gauge\n\
+         cigarette_counter_db_circuit_open {}\n\
+         # TYPE cigarette_counter_db_circuit_opened_total counter\n\
+         cigarette_counter_db_circuit_opened_total {}\n\
+         # TYPE cigarette_counter_db_short_circuited_total counter\n\
+         cigarette_counter_db_short_circuited_total {}\n",
+        u8::from(open),
+        OPENED_TOTAL.load(Ordering::Relaxed),
+        SHORT_CIRCUITED_TOTAL.load(Ordering::Relaxed)
+    )
+}
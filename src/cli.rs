@@ -0,0 +1,281 @@
+//! Command-line interface of the bot binary.
+//!
+//! Without a subcommand the binary runs the bot. Each subcommand runs one
+//! maintenance task against the configured database and exits, without
+//! connecting to the Discord gateway:
+//!
+//! ```text
+//! cigarette-counter migrate
+//! cigarette-counter export-guild <GUILD_ID> [--output <FILE>]
+//! cigarette-counter recompute-aggregates <USER_ID>...
+//! cigarette-counter register-commands [--guild <GUILD_ID>]
+//! cigarette-counter seed-demo [--profile <PROFILE>] [--users <N>] [--months <N>]
+//! ```
+//!
+//! Tasks read the same environment variables as the bot (see `Config::load`).
+
+use std::{path::PathBuf, sync::Arc};
+
+use async_trait::async_trait;
+use clap::{Parser, Subcommand};
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+use sqlx::PgPool;
+
+use crate::anonymize::Anonymizer;
+use crate::clock::{Clock, SystemClock};
+use crate::command_sync::{sync_commands, CommandChanges};
+use crate::commands::{
+    self,
+    admin::{recompute_aggregates, show_command_changes},
+};
+use crate::config::Config;
+use crate::database::Database;
+use crate::demo_seed::{seed_demo, SeedPlan, SeedProfile, DEMO_GUILD_ID};
+use crate::format::Locale;
+use crate::frontend::{Frontend, Reply};
+use crate::guild_archive::export_guild;
+use crate::service::StatsService;
+use crate::Error;
+
+/// Actor recorded in the audit log for changes made from the command line
+pub const CLI_ACTOR: &str = "cli";
+
+/// Cigarette counter Discord bot
+#[derive(Debug, Parser)]
+#[command(name = "cigarette-counter", version)]
+pub struct Cli {
+    /// Maintenance task to run instead of the bot
+    #[command(subcommand)]
+    pub task: Option<Task>,
+}
+
+/// A one-off maintenance task
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum Task {
+    /// Applies pending database migrations
+    Migrate,
+    /// Writes a guild's data archive as JSON
+    ExportGuild {
+        /// The ID of the guild
+        guild_id: String,
+        /// File the archive is written to; printed when omitted
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Recomputes users' aggregates from their logs and records it in the audit log
+    RecomputeAggregates {
+        /// The Discord IDs of the users
+        #[arg(required = true)]
+        user_ids: Vec<String>,
+    },
+    /// Registers the application commands with Discord
+    RegisterCommands {
+        /// Registers in this guild only, where changes apply immediately
+        #[arg(long)]
+        guild: Option<u64>,
+    },
+    /// Fills a guild with synthetic members and logs for demos and load tests
+    SeedDemo {
+        /// Volume of the generated data
+        #[arg(long, value_enum, default_value_t)]
+        profile: SeedProfile,
+        /// Number of members, overriding the profile
+        #[arg(long)]
+        users: Option<u32>,
+        /// Months of history, overriding the profile
+        #[arg(long)]
+        months: Option<u32>,
+        /// The guild the logs are made in
+        #[arg(long, default_value = DEMO_GUILD_ID)]
+        guild: String,
+        /// Seed of the random generator, for reproducible data
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+}
+
+/// Frontend printing replies to standard output
+#[derive(Debug, Default)]
+pub struct ConsoleFrontend;
+
+#[async_trait]
+impl Frontend for ConsoleFrontend {
+    fn locale(&self) -> Locale {
+        Locale::default()
+    }
+
+    fn channel_id(&self) -> Option<serenity::ChannelId> {
+        None
+    }
+
+    async fn send_reply(&self, reply: Reply) -> Result<(), Error> {
+        println!("{}", reply.content);
+        Ok(())
+    }
+
+    async fn respond(&self, reply: Reply) -> Result<(), Error> {
+        self.send_reply(reply).await
+    }
+
+    async fn respond_editable(&self, reply: Reply) -> Result<Option<serenity::MessageId>, Error> {
+        self.send_reply(reply).await?;
+        Ok(None)
+    }
+
+    async fn acknowledge(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn react(&self, _emoji: serenity::ReactionType) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn edit_message(
+        &self,
+        _message_id: serenity::MessageId,
+        reply: Reply,
+    ) -> Result<(), Error> {
+        self.send_reply(reply).await
+    }
+}
+
+/// Runs a maintenance task.
+///
+/// # Arguments
+/// * `task` - The task.
+/// * `config` - Loaded bot configuration.
+/// * `pool` - Connection pool of the configured database.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn run(task: Task, config: &Config, pool: PgPool) -> Result<(), Error> {
+    match task {
+        Task::Migrate => {
+            let applied = migrate(&pool).await?;
+            println!("Applied {} migrations", applied);
+        }
+        Task::ExportGuild { guild_id, output } => {
+            let database = open_database(config, pool);
+            let clock = SystemClock::new(config.timezone);
+            let archive = export_guild(&database, &guild_id, config.timezone, clock.now()).await?;
+            let json = serde_json::to_vec_pretty(&archive)?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, json)?;
+                    println!(
+                        "Exported {} members of guild {} to {}",
+                        archive.members.len(),
+                        guild_id,
+                        path.display()
+                    );
+                }
+                None => println!("{}", String::from_utf8(json)?),
+            }
+        }
+        Task::RecomputeAggregates { user_ids } => {
+            let database = Arc::new(open_database(config, pool));
+            let clock: Arc<dyn Clock> = Arc::new(SystemClock::new(config.timezone));
+            let stats = StatsService::new(database.clone(), clock);
+            for user_id in user_ids {
+                recompute_aggregates(&ConsoleFrontend, &database, &stats, CLI_ACTOR, &user_id)
+                    .await?;
+            }
+        }
+        Task::RegisterCommands { guild } => {
+            let changes = register_commands(config, guild.map(serenity::GuildId::new)).await?;
+            show_command_changes(&ConsoleFrontend, &changes, guild).await?;
+        }
+        Task::SeedDemo {
+            profile,
+            users,
+            months,
+            guild,
+            seed,
+        } => {
+            let (default_users, default_months) = profile.volume();
+            let plan = SeedPlan {
+                guild_id: guild,
+                users: users.unwrap_or(default_users),
+                months: months.unwrap_or(default_months),
+                seed,
+            };
+            let database = open_database(config, pool);
+            let clock = SystemClock::new(config.timezone);
+            let summary = seed_demo(&database, &plan, clock.today(), config.timezone).await?;
+            println!(
+                "Seeded {} members with {} logs in guild {}",
+                summary.members, summary.logs, summary.guild_id
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies the pending migrations embedded in the binary.
+///
+/// # Arguments
+/// * `pool` - Connection pool of the database.
+///
+/// # Returns
+/// A Result containing the number of migrations applied, or a `MigrateError`.
+pub async fn migrate(pool: &PgPool) -> Result<usize, sqlx::migrate::MigrateError> {
+    let migrator = sqlx::migrate!("./migrations");
+    let applied_before = count_applied(pool).await;
+    migrator.run(pool).await?;
+
+    Ok(count_applied(pool).await.saturating_sub(applied_before))
+}
+
+/// Counts the migrations recorded as applied.
+///
+/// # Arguments
+/// * `pool` - Connection pool of the database.
+///
+/// # Returns
+/// The number of applied migrations; 0 before the first migration.
+async fn count_applied(pool: &PgPool) -> usize {
+    sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM _sqlx_migrations WHERE success")
+        .fetch_one(pool)
+        .await
+        .map_or(0, |count| count as usize)
+}
+
+/// Registers the enabled application commands with Discord over HTTP.
+///
+/// # Arguments
+/// * `config` - Loaded bot configuration containing the token and disabled modules.
+/// * `guild_id` - The guild to register in, or `None` to register globally.
+///
+/// # Returns
+/// A Result containing what changed, or an `Error`.
+async fn register_commands(
+    config: &Config,
+    guild_id: Option<serenity::GuildId>,
+) -> Result<CommandChanges, Error> {
+    let http = serenity::Http::new(&config.bot_token);
+    let application = http.get_current_application_info().await?;
+    http.set_application_id(application.id);
+
+    let commands = commands::enabled_commands(&config.disabled_modules);
+    sync_commands(&http, &commands, guild_id).await
+}
+
+/// Wraps the pool like the bot does.
+///
+/// # Arguments
+/// * `config` - Loaded bot configuration containing the anonymization salt
+///   and log notification channel.
+/// * `pool` - Connection pool of the database.
+///
+/// # Returns
+/// The database behind a mutex.
+fn open_database(config: &Config, pool: PgPool) -> Mutex<Database> {
+    let mut database =
+        Database::new(pool).with_anonymizer(Anonymizer::new(config.anonymize_salt.clone()));
+    if let Some(channel) = &config.log_notify_channel {
+        database = database.with_log_notifications(channel);
+    }
+
+    Mutex::new(database)
+}
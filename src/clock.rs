@@ -0,0 +1,147 @@
+//! Wall-clock abstraction.
+//!
+//! Everything that depends on the current time or on which calendar day an
+//! instant falls on (daily summaries, streaks, milestone roles, token expiry)
+//! asks a `Clock` instead of calling `Utc::now()` / `Local::now()` directly,
+//! so that rollover behavior can be tested with a `MockClock`. The calendar
+//! rules themselves live in `rollover`.
+
+use std::sync::Mutex;
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Source of the current time and of the bot's calendar day
+pub trait Clock: Send + Sync {
+    /// Returns the current instant.
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Returns the time zone calendar days are counted in.
+    fn timezone(&self) -> Tz;
+
+    /// Returns the calendar date an instant falls on in the bot's time zone.
+    ///
+    /// # Arguments
+    /// * `instant` - The instant to convert.
+    fn local_date(&self, instant: DateTime<Utc>) -> NaiveDate {
+        instant.with_timezone(&self.timezone()).date_naive()
+    }
+
+    /// Returns the current calendar date in the bot's time zone.
+    fn today(&self) -> NaiveDate {
+        self.local_date(self.now())
+    }
+}
+
+/// The system clock
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    timezone: Tz,
+}
+
+impl SystemClock {
+    /// Creates a system clock counting days in the given time zone.
+    ///
+    /// # Arguments
+    /// * `timezone` - The time zone calendar dates are computed in.
+    pub fn new(timezone: Tz) -> Self {
+        Self { timezone }
+    }
+
+    /// Creates a system clock counting days in the host's time zone, or UTC if it cannot be determined.
+    pub fn host() -> Self {
+        Self::new(host_timezone())
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn timezone(&self) -> Tz {
+        self.timezone
+    }
+}
+
+/// Determines the host's IANA time zone.
+///
+/// # Returns
+/// The host time zone, or UTC if it cannot be determined.
+pub fn host_timezone() -> Tz {
+    iana_time_zone::get_timezone()
+        .ok()
+        .and_then(|name| name.parse().ok())
+        .unwrap_or(Tz::UTC)
+}
+
+/// A manually controlled clock in a fixed time zone, for tests
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<DateTime<Utc>>,
+    timezone: Tz,
+}
+
+impl MockClock {
+    /// Creates a clock stopped at the given instant.
+    ///
+    /// # Arguments
+    /// * `timezone` - The time zone calendar dates are computed in.
+    /// * `now` - The initial instant.
+    pub fn new(timezone: Tz, now: DateTime<Utc>) -> Self {
+        Self {
+            now: Mutex::new(now),
+            timezone,
+        }
+    }
+
+    /// Creates a clock stopped at a local wall-clock time.
+    ///
+    /// # Arguments
+    /// * `timezone` - The time zone calendar dates are computed in.
+    /// * `year`, `month`, `day`, `hour`, `minute` - The local wall-clock time.
+    ///
+    /// # Returns
+    /// The clock, or `None` if the time does not exist or is ambiguous in the time zone
+    /// (e.g. during a DST transition).
+    pub fn at_local(
+        timezone: Tz,
+        year: i32,
+        month: u32,
+        day: u32,
+        hour: u32,
+        minute: u32,
+    ) -> Option<Self> {
+        let local = timezone
+            .with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .single()?;
+
+        Some(Self::new(timezone, local.with_timezone(&Utc)))
+    }
+
+    /// Moves the clock to an instant.
+    ///
+    /// # Arguments
+    /// * `now` - The new instant.
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    /// Moves the clock forward.
+    ///
+    /// # Arguments
+    /// * `duration` - How far to advance.
+    pub fn advance(&self, duration: chrono::Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+
+    fn timezone(&self) -> Tz {
+        self.timezone
+    }
+}
@@ -0,0 +1,153 @@
+//! Registration of the application commands with Discord.
+//!
+//! Commands are registered globally or in a single guild, where changes
+//! apply immediately instead of after the global propagation delay. Each
+//! sync compares the registered commands with the enabled ones, so callers
+//! can report what an upgrade changed. Permissions and contexts come from
+//! the module access declared in the command registry.
+
+use poise::serenity_prelude::{self as serenity, Http};
+
+use crate::commands::application_commands;
+use crate::{Data, Error};
+
+/// The parts of a command definition that users notice when they change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandSignature {
+    /// The command name
+    pub name: String,
+    /// The command description; empty for context menu commands
+    pub description: String,
+    /// The names of the command's options, in order
+    pub options: Vec<String>,
+}
+
+impl CommandSignature {
+    /// Reads the signature of a command about to be registered.
+    ///
+    /// # Arguments
+    /// * `command` - The command builder.
+    ///
+    /// # Returns
+    /// The signature.
+    pub fn of_builder(command: &serenity::CreateCommand) -> Self {
+        let json = serde_json::to_value(command).unwrap_or_default();
+        let text = |value: &serde_json::Value| value.as_str().unwrap_or_default().to_string();
+
+        Self {
+            name: text(&json["name"]),
+            description: text(&json["description"]),
+            options: json["options"]
+                .as_array()
+                .map(|options| options.iter().map(|option| text(&option["name"])).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Reads the signature of a registered command.
+    ///
+    /// # Arguments
+    /// * `command` - The command as returned by Discord.
+    ///
+    /// # Returns
+    /// The signature.
+    pub fn of_registered(command: &serenity::Command) -> Self {
+        Self {
+            name: command.name.clone(),
+            description: command.description.clone(),
+            options: command
+                .options
+                .iter()
+                .map(|option| option.name.clone())
+                .collect(),
+        }
+    }
+}
+
+/// How the registered commands changed in a sync
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CommandChanges {
+    /// Commands that were not registered before
+    pub added: Vec<String>,
+    /// Commands whose description or options changed
+    pub updated: Vec<String>,
+    /// Commands that are no longer registered
+    pub removed: Vec<String>,
+    /// Number of commands registered unchanged
+    pub unchanged: usize,
+}
+
+impl CommandChanges {
+    /// Compares the registered commands with the ones about to be registered.
+    ///
+    /// # Arguments
+    /// * `registered` - The signatures of the registered commands.
+    /// * `desired` - The signatures of the commands about to be registered.
+    ///
+    /// # Returns
+    /// The changes, with command names in the order of their lists.
+    pub fn between(registered: &[CommandSignature], desired: &[CommandSignature]) -> Self {
+        let mut changes = Self::default();
+        for command in desired {
+            match registered.iter().find(|old| old.name == command.name) {
+                None => changes.added.push(command.name.clone()),
+                Some(old) if old != command => changes.updated.push(command.name.clone()),
+                Some(_) => changes.unchanged += 1,
+            }
+        }
+        changes.removed = registered
+            .iter()
+            .filter(|old| !desired.iter().any(|command| command.name == old.name))
+            .map(|old| old.name.clone())
+            .collect();
+
+        changes
+    }
+
+    /// Returns whether the sync changed nothing.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Replaces the registered application commands with the given ones.
+///
+/// # Arguments
+/// * `http` - The HTTP client; its application ID must be set.
+/// * `commands` - The enabled commands of the framework.
+/// * `guild_id` - The guild to register in, or `None` to register globally.
+///
+/// # Returns
+/// A Result containing what changed, or an `Error`.
+pub async fn sync_commands(
+    http: &Http,
+    commands: &[poise::Command<Data, Error>],
+    guild_id: Option<serenity::GuildId>,
+) -> Result<CommandChanges, Error> {
+    let create = application_commands(commands);
+    let registered = match guild_id {
+        Some(guild_id) => guild_id.get_commands(http).await?,
+        None => serenity::Command::get_global_commands(http).await?,
+    };
+    let changes = CommandChanges::between(
+        &registered
+            .iter()
+            .map(CommandSignature::of_registered)
+            .collect::<Vec<_>>(),
+        &create
+            .iter()
+            .map(CommandSignature::of_builder)
+            .collect::<Vec<_>>(),
+    );
+
+    match guild_id {
+        Some(guild_id) => {
+            guild_id.set_commands(http, create).await?;
+        }
+        None => {
+            serenity::Command::set_global_commands(http, create).await?;
+        }
+    }
+
+    Ok(changes)
+}
@@ -0,0 +1,200 @@
+//! Custom achievements of the guild; see `crate::achievements`.
+
+use std::collections::HashSet;
+
+use crate::achievements::{Condition, Metric};
+use crate::embed::Title;
+use crate::frontend::{Frontend, Reply};
+use crate::{Context, Error};
+
+use super::Command;
+
+/// Most achievements a guild can define
+pub const MAX_ACHIEVEMENTS: usize = 25;
+
+/// Longest name of an achievement, in characters
+pub const MAX_NAME_LENGTH: usize = 50;
+
+/// Reads a condition as typed, accepting an optional `condition:` prefix and quotes.
+///
+/// # Arguments
+/// * `input` - The condition as typed, e.g. `condition:"weekly_total<=10"`.
+///
+/// # Returns
+/// The condition without the prefix and quotes.
+pub fn strip_condition(input: &str) -> &str {
+    let input = input.trim();
+    let input = input.strip_prefix("condition:").unwrap_or(input).trim();
+
+    input
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .unwrap_or(input)
+}
+
+/// Lists the guild's achievements, marking the ones the author earned.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    subcommands("achievement_create", "achievement_delete")
+)]
+pub async fn achievement(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let (achievements, earned) = {
+        let db = ctx.data().database.lock().await;
+        let guild = db.guild(&guild_id);
+        (
+            guild.get_achievements().await?,
+            guild
+                .get_earned_achievements(&ctx.author().id.to_string())
+                .await?,
+        )
+    };
+
+    if achievements.is_empty() {
+        return ctx
+            .send_reply(Reply::new(
+                "このサーバーには実績がありません。\n使い方: achievement create <名前> <条件>",
+            ))
+            .await;
+    }
+
+    let earned: HashSet<i32> = earned.iter().map(|earned| earned.achievement_id).collect();
+    let lines: Vec<String> = achievements
+        .iter()
+        .map(|achievement| {
+            let mark = if earned.contains(&achievement.id) {
+                "🏅"
+            } else {
+                "▫️"
+            };
+            format!(
+                "{} **{}** `{}`",
+                mark, achievement.name, achievement.condition
+            )
+        })
+        .collect();
+
+    ctx.send_reply(Reply::new(format!(
+        "このサーバーの実績（{}/{}個達成）\n{}",
+        earned.len(),
+        achievements.len(),
+        lines.join("\n")
+    )))
+    .await
+}
+
+/// Defines a custom achievement, e.g. `achievement create 節煙週間 weekly_total<=10`.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `name` - The name of the achievement.
+/// * `condition` - The condition members must meet.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "create"
+)]
+pub async fn achievement_create(
+    ctx: Context<'_>,
+    name: String,
+    #[rest] condition: String,
+) -> Result<(), Error> {
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return ctx
+            .send_reply(Reply::error(format!(
+                "実績の名前は{}文字以内で指定してください。",
+                MAX_NAME_LENGTH
+            )))
+            .await;
+    }
+    let condition: Condition = match strip_condition(&condition).parse() {
+        Ok(condition) => condition,
+        Err(e) => {
+            let metrics: Vec<&str> = Metric::ALL.iter().map(|metric| metric.name()).collect();
+            return ctx
+                .send_reply(Reply::error(format!(
+                    "条件を読み取れません（{}）。\n例: weekly_total<=10 && smoke_free_days>=2\n使える値: {}\n比較: <= < == >= >",
+                    e,
+                    metrics.join(", ")
+                )))
+                .await;
+        }
+    };
+
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let created = {
+        let db = ctx.data().database.lock().await;
+        let guild = db.guild(&guild_id);
+        if guild.get_achievements().await?.len() >= MAX_ACHIEVEMENTS {
+            return ctx
+                .send_reply(Reply::error(format!(
+                    "実績は{}個まで作成できます。",
+                    MAX_ACHIEVEMENTS
+                )))
+                .await;
+        }
+        guild
+            .create_achievement(&name, &condition.to_string())
+            .await?
+    };
+
+    let reply = if created {
+        Reply::new(format!(
+            "実績「{}」を作成しました（条件: `{}`）。集計は前日・前週・前月の記録が対象で、1時間以内に判定が始まります。",
+            name, condition
+        ))
+        .titled(Title::Settings)
+    } else {
+        Reply::error(format!("実績「{}」はすでにあります。", name))
+    };
+    ctx.send_reply(reply).await
+}
+
+/// Deletes a custom achievement, together with who earned it.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `name` - The name of the achievement.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "delete"
+)]
+pub async fn achievement_delete(ctx: Context<'_>, #[rest] name: String) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let deleted = ctx
+        .data()
+        .database
+        .lock()
+        .await
+        .guild(&guild_id)
+        .delete_achievement(name.trim())
+        .await?;
+
+    let reply = if deleted {
+        Reply::new(format!("実績「{}」を削除しました。", name.trim())).titled(Title::Settings)
+    } else {
+        Reply::error(format!("実績「{}」は見つかりません。", name.trim()))
+    };
+    ctx.send_reply(reply).await
+}
+
+/// Returns the custom achievement commands.
+pub fn commands() -> Vec<Command> {
+    vec![achievement()]
+}
@@ -0,0 +1,861 @@
+//! Owner-only operational commands.
+//!
+//! Besides diagnostics, owners can fix data by hand: reassign a log's type
+//! (with a converted quantity when the units differ, see `crate::units`),
+//! shift a user's timestamps after a time zone misconfiguration, and recompute
+//! a user's aggregates. Each fix runs in one transaction together with its
+//! entry in the audit log, which `admin audit` lists. `admin jobs` lists the
+//! scheduled background jobs and `admin cancel-job` cancels one before it runs.
+//! `admin import` reads a guild archive written by `guild export` on another
+//! instance. `admin sync-commands` re-registers the application commands after
+//! an upgrade, globally or in one guild, and reports what changed; `register`
+//! does the same as a slash command too, for the guild it is used in unless
+//! told `global`. `admin shards` lists the gateway shards as seen by the
+//! `shards` monitor.
+
+use chrono::{DateTime, Utc};
+
+use crate::command_sync::{sync_commands, CommandChanges};
+use crate::database::{Database, SmokingLog, SmokingType};
+use crate::explain::{self, ParamSource};
+use crate::format::format_count;
+use crate::frontend::{Frontend, Reply};
+use crate::guild_archive::{import_guild, GuildArchive, ImportError};
+use crate::milestones;
+use crate::service::StatsService;
+use crate::shards::{self, ShardStatus};
+use crate::units::{convert, describe_conversion, unit_of, units_differ};
+use crate::{Context, Error};
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+
+use super::{chunk_lines, cleanup, Command, MESSAGE_CHUNK_LENGTH};
+
+/// Most hours a user's logs can be shifted by at once
+pub const MAX_SHIFT_HOURS: i32 = 48;
+
+/// Number of entries listed by `admin audit`
+const AUDIT_LOG_LIMIT: i64 = 20;
+
+/// Number of jobs listed by `admin jobs`
+const JOB_LIST_LIMIT: i64 = 20;
+
+/// Owner-only operational commands.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    owners_only,
+    subcommands(
+        "admin_explain",
+        "admin_reassign",
+        "admin_shift",
+        "admin_recompute",
+        "admin_duplicates",
+        "admin_audit",
+        "admin_jobs",
+        "admin_cancel_job",
+        "admin_import",
+        "admin_sync_commands",
+        "admin_shards"
+    )
+)]
+pub async fn admin(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new("使い方: admin explain <query-name> / admin reassign <ログID> <種類> [数量] / admin shift <ユーザー> <時間> / admin recompute <ユーザー> / admin duplicates [ユーザー] / admin audit / admin jobs / admin cancel-job <ジョブID> / admin import（アーカイブを添付） / admin sync-commands [サーバーID] / admin shards")).await
+}
+
+/// Runs `EXPLAIN ANALYZE` for a named query against the live database and posts the plan.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `query_name` - The name of the query, as listed in the `explain` module.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "explain")]
+pub async fn admin_explain(ctx: Context<'_>, query_name: Option<String>) -> Result<(), Error> {
+    let Some(query) = query_name.as_deref().and_then(explain::find) else {
+        return ctx
+            .send_reply(Reply::error(format!(
+                "クエリ名を指定してください: {}",
+                explain::query_names()
+            )))
+            .await;
+    };
+
+    let plan = {
+        let db = ctx.data().database.lock().await;
+        let user_id = ctx.author().id.get().to_string();
+        let user = db.get_or_create_user(&user_id, &ctx.author().name).await?;
+        let smoking_type_id = db
+            .get_smoking_types()
+            .await?
+            .first()
+            .map(|smoking_type| smoking_type.id)
+            .unwrap_or_default();
+
+        let params = ParamSource {
+            discord_id: user.discord_id,
+            guild_id: ctx
+                .guild_id()
+                .map(|guild_id| guild_id.to_string())
+                .unwrap_or_default(),
+            smoking_type_id,
+            today: ctx.data().clock.today(),
+            timezone: ctx.data().clock.timezone(),
+        }
+        .resolve(query.params);
+
+        db.explain_analyze(query.sql, &params).await?
+    };
+
+    for chunk in chunk_lines(&plan, MESSAGE_CHUNK_LENGTH) {
+        ctx.send_reply(Reply::new(format!("```\n{}\n```", chunk)))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Changes the smoking type of a log.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `log_id` - The ID of the log.
+/// * `type_name` - The type name of the new smoking type (e.g. `iqos`).
+/// * `quantity` - The quantity in the new type's unit, required when the units differ.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "reassign")]
+pub async fn admin_reassign(
+    ctx: Context<'_>,
+    log_id: i32,
+    type_name: String,
+    quantity: Option<i32>,
+) -> Result<(), Error> {
+    reassign_log(
+        &ctx,
+        &ctx.data().database,
+        &ctx.data().stats,
+        &ctx.author().id.get().to_string(),
+        log_id,
+        &type_name,
+        quantity,
+    )
+    .await
+}
+
+/// Shifts every log of a user by a number of hours, to recover from a time zone misconfiguration.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `user` - The user whose logs are shifted.
+/// * `hours` - The number of hours to add (negative to move the logs earlier).
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "shift")]
+pub async fn admin_shift(ctx: Context<'_>, user: serenity::User, hours: i32) -> Result<(), Error> {
+    shift_timestamps(
+        &ctx,
+        &ctx.data().database,
+        &ctx.data().stats,
+        &ctx.author().id.get().to_string(),
+        &user.id.get().to_string(),
+        hours,
+    )
+    .await
+}
+
+/// Recomputes a user's aggregates from their logs, and their milestone roles in this guild.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `user` - The user whose aggregates are recomputed.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "recompute")]
+pub async fn admin_recompute(ctx: Context<'_>, user: serenity::User) -> Result<(), Error> {
+    let data = ctx.data();
+    recompute_aggregates(
+        &ctx,
+        &data.database,
+        &data.stats,
+        &ctx.author().id.get().to_string(),
+        &user.id.get().to_string(),
+    )
+    .await?;
+
+    if let Some(guild_id) = ctx.guild_id() {
+        milestones::sync_member_roles(
+            ctx.http(),
+            &data.database,
+            &data.stats,
+            &data.events,
+            guild_id,
+            user.id,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Reports users with duplicate logs, or lists one user's duplicates with buttons to clean them up.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `user` - The user whose duplicates are listed, or `None` for a report over every user.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "duplicates")]
+pub async fn admin_duplicates(ctx: Context<'_>, user: Option<serenity::User>) -> Result<(), Error> {
+    let database = &ctx.data().database;
+    match user {
+        Some(user) => {
+            cleanup::suggest_cleanup(
+                &ctx,
+                database,
+                ctx.data().clock.timezone(),
+                &ctx.author().id.get().to_string(),
+                &user.id.get().to_string(),
+            )
+            .await
+        }
+        None => cleanup::duplicate_report(&ctx, database).await,
+    }
+}
+
+/// Lists the most recent data fixes.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "audit")]
+pub async fn admin_audit(ctx: Context<'_>) -> Result<(), Error> {
+    show_audit_log(&ctx, &ctx.data().database).await
+}
+
+/// Lists the scheduled jobs that have not finished.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "jobs")]
+pub async fn admin_jobs(ctx: Context<'_>) -> Result<(), Error> {
+    list_jobs(&ctx, &ctx.data().database).await
+}
+
+/// Cancels a scheduled job that has not started.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `job_id` - The ID of the job.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "cancel-job")]
+pub async fn admin_cancel_job(ctx: Context<'_>, job_id: i32) -> Result<(), Error> {
+    cancel_scheduled_job(&ctx, &ctx.data().database, job_id).await
+}
+
+/// Imports a guild archive attached to the message.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `archive` - The archive written by `guild export`.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "import")]
+pub async fn admin_import(ctx: Context<'_>, archive: serenity::Attachment) -> Result<(), Error> {
+    let contents = archive.download().await?;
+
+    import_archive(
+        &ctx,
+        &ctx.data().database,
+        &ctx.data().stats,
+        &ctx.author().id.get().to_string(),
+        &contents,
+    )
+    .await
+}
+
+/// Changes the smoking type of a log, audits the change and confirms it.
+///
+/// When the new type counts in another unit, the quantity is not carried
+/// over: without a converted `quantity` the owner is asked for one instead,
+/// with the configured conversion hint if there is one.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `stats` - The statistics service whose cached aggregates are dropped.
+/// * `actor_id` - The Discord ID of the owner.
+/// * `log_id` - The ID of the log.
+/// * `type_name` - The type name of the new smoking type.
+/// * `quantity` - The quantity in the new type's unit; kept if `None`.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn reassign_log(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    stats: &StatsService,
+    actor_id: &str,
+    log_id: i32,
+    type_name: &str,
+    quantity: Option<i32>,
+) -> Result<(), Error> {
+    if quantity.is_some_and(|quantity| quantity < 1) {
+        return frontend
+            .send_reply(Reply::error("数量は1以上で指定してください。"))
+            .await;
+    }
+
+    let reply = {
+        let db = database.lock().await;
+        let Some(smoking_type) = db.find_smoking_type_by_name(type_name).await? else {
+            let known: Vec<String> = db
+                .get_smoking_types()
+                .await?
+                .into_iter()
+                .map(|smoking_type| smoking_type.type_name)
+                .collect();
+            drop(db);
+            return frontend
+                .send_reply(Reply::error(format!(
+                    "不明な種類です: {}（使用できる種類: {}）",
+                    type_name,
+                    known.join(", ")
+                )))
+                .await;
+        };
+        let Some(log) = db.get_log(log_id).await? else {
+            drop(db);
+            return frontend
+                .send_reply(Reply::error(format!("ログ #{} は見つかりません。", log_id)))
+                .await;
+        };
+
+        let current_type = db.get_smoking_type(log.smoking_type_id).await?;
+        if quantity.is_none() && units_differ(&current_type, &smoking_type) {
+            let hint = db
+                .get_unit_conversion(current_type.id, smoking_type.id)
+                .await?;
+            drop(db);
+            return frontend
+                .send_reply(conversion_prompt(
+                    &log,
+                    &current_type,
+                    &smoking_type,
+                    type_name,
+                    hint,
+                ))
+                .await;
+        }
+
+        match db
+            .reassign_log_type(actor_id, log_id, smoking_type.id, quantity)
+            .await?
+        {
+            Some(updated) => {
+                stats.forget_user(&updated.discord_id);
+                let display_name = smoking_type
+                    .description
+                    .as_deref()
+                    .unwrap_or(&smoking_type.type_name);
+                if updated.quantity == log.quantity {
+                    Reply::new(format!(
+                        "ログ #{} の種類を{}に変更しました。",
+                        log_id, display_name
+                    ))
+                } else {
+                    Reply::new(format!(
+                        "ログ #{} の種類を{}に変更しました（数量 {}{} → {}{}）。",
+                        log_id,
+                        display_name,
+                        log.quantity,
+                        unit_of(&current_type),
+                        updated.quantity,
+                        unit_of(&smoking_type)
+                    ))
+                }
+            }
+            None => Reply::error(format!("ログ #{} は見つかりません。", log_id)),
+        }
+    };
+
+    frontend.send_reply(reply).await
+}
+
+/// Asks for the quantity of a log converted to the unit of its new type.
+///
+/// # Arguments
+/// * `log` - The log being re-typed.
+/// * `current_type` - The log's current type.
+/// * `new_type` - The new type.
+/// * `type_name` - The type name the owner gave for the new type.
+/// * `hint` - The configured conversion factor between the types, if any.
+///
+/// # Returns
+/// The question, with the converted quantity suggested by the hint.
+fn conversion_prompt(
+    log: &SmokingLog,
+    current_type: &SmokingType,
+    new_type: &SmokingType,
+    type_name: &str,
+    hint: Option<f64>,
+) -> Reply {
+    let from_unit = unit_of(current_type);
+    let to_unit = unit_of(new_type);
+    let hint = match hint {
+        Some(factor) => format!(
+            "換算の目安: {}（{}{} → {}{}）",
+            describe_conversion(from_unit, to_unit, factor),
+            log.quantity,
+            from_unit,
+            convert(log.quantity, factor),
+            to_unit
+        ),
+        None => "換算の目安は type conversion set で登録できます。".to_string(),
+    };
+
+    Reply::error(format!(
+        "ログ #{} の数量は{}{}ですが、変更先の単位は「{}」です。換算した数量を指定して admin reassign {} {} <数量> を実行し直してください。\n{}",
+        log.id, log.quantity, from_unit, to_unit, log.id, type_name, hint
+    ))
+}
+
+/// Shifts every log of a user by a number of hours, audits the change and confirms it.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `stats` - The statistics service whose cached aggregates are dropped.
+/// * `actor_id` - The Discord ID of the owner.
+/// * `user_id` - The Discord ID of the user.
+/// * `hours` - The number of hours to add, at most `MAX_SHIFT_HOURS` either way.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn shift_timestamps(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    stats: &StatsService,
+    actor_id: &str,
+    user_id: &str,
+    hours: i32,
+) -> Result<(), Error> {
+    if hours == 0 || hours.abs() > MAX_SHIFT_HOURS {
+        return frontend
+            .send_reply(Reply::error(format!(
+                "時間は0以外の-{}から{}の範囲で指定してください。",
+                MAX_SHIFT_HOURS, MAX_SHIFT_HOURS
+            )))
+            .await;
+    }
+
+    let shifted = database
+        .lock()
+        .await
+        .shift_user_logs(actor_id, user_id, hours)
+        .await?;
+    stats.forget_user(user_id);
+
+    frontend
+        .send_reply(Reply::new(format!(
+            "<@{}> のログ{}件を{:+}時間ずらしました。",
+            user_id, shifted, hours
+        )))
+        .await
+}
+
+/// Recomputes a user's cached aggregates from their logs, audits it and reports the result.
+///
+/// # Arguments
+/// * `frontend` - Where the result is sent.
+/// * `database` - The database.
+/// * `stats` - The statistics service.
+/// * `actor_id` - The Discord ID of the owner.
+/// * `user_id` - The Discord ID of the user.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn recompute_aggregates(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    stats: &StatsService,
+    actor_id: &str,
+    user_id: &str,
+) -> Result<(), Error> {
+    stats.forget_user(user_id);
+    let month_total: i64 = stats
+        .monthly_totals(user_id, stats.current_month(), true)
+        .await?
+        .value
+        .iter()
+        .map(|daily| daily.total_quantity)
+        .sum();
+    let days = stats.days_smoke_free(user_id).await?;
+
+    database
+        .lock()
+        .await
+        .record_admin_action(
+            actor_id,
+            "recompute_aggregates",
+            user_id,
+            &format!("month total {}, {} smoke-free days", month_total, days),
+        )
+        .await?;
+
+    frontend
+        .send_reply(Reply::new(format!(
+            "<@{}> の集計を再計算しました（今月の合計: {}、連続禁煙日数: {}日）。",
+            user_id,
+            format_count(month_total, frontend.locale()),
+            days
+        )))
+        .await
+}
+
+/// Lists the most recent entries of the audit log.
+///
+/// # Arguments
+/// * `frontend` - Where the list is sent.
+/// * `database` - The database.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn show_audit_log(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+) -> Result<(), Error> {
+    let entries = database
+        .lock()
+        .await
+        .get_admin_audit_entries(AUDIT_LOG_LIMIT)
+        .await?;
+
+    let reply = if entries.is_empty() {
+        "監査ログはありません。".to_string()
+    } else {
+        let lines: Vec<String> = entries
+            .into_iter()
+            .map(|entry| {
+                format!(
+                    "- {} <@{}> {} <@{}>: {}",
+                    entry.created_at.format("%Y-%m-%d %H:%M"),
+                    entry.actor_id,
+                    entry.action,
+                    entry.target_id,
+                    entry.details
+                )
+            })
+            .collect();
+        format!("監査ログ:\n{}", lines.join("\n"))
+    };
+
+    frontend.send_reply(Reply::new(reply)).await
+}
+
+/// Lists the pending, running and failed scheduled jobs.
+///
+/// # Arguments
+/// * `frontend` - Where the list is sent.
+/// * `database` - The database.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn list_jobs(frontend: &dyn Frontend, database: &Mutex<Database>) -> Result<(), Error> {
+    let jobs = database.lock().await.get_open_jobs(JOB_LIST_LIMIT).await?;
+
+    let reply = if jobs.is_empty() {
+        "予定されているジョブはありません。".to_string()
+    } else {
+        let lines: Vec<String> = jobs
+            .into_iter()
+            .map(|job| {
+                let mut line = format!(
+                    "- #{} {} {} {} (試行{}回)",
+                    job.id,
+                    job.kind,
+                    job.status,
+                    job.run_at.format("%Y-%m-%d %H:%M"),
+                    job.attempts
+                );
+                if let Some(error) = job.last_error {
+                    line.push_str(&format!(": {}", error));
+                }
+                line
+            })
+            .collect();
+        format!("ジョブ:\n{}", lines.join("\n"))
+    };
+
+    frontend.send_reply(Reply::new(reply)).await
+}
+
+/// Cancels a pending scheduled job and confirms it.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `job_id` - The ID of the job.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn cancel_scheduled_job(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    job_id: i32,
+) -> Result<(), Error> {
+    let cancelled = database.lock().await.cancel_job(job_id).await?;
+
+    let reply = if cancelled {
+        Reply::new(format!("ジョブ#{}を取り消しました。", job_id))
+    } else {
+        Reply::error(format!(
+            "ジョブ#{}は実行待ちではないため取り消せません。",
+            job_id
+        ))
+    };
+
+    frontend.send_reply(reply).await
+}
+
+/// Imports a guild archive, audits the import and reports what was carried over.
+///
+/// # Arguments
+/// * `frontend` - Where the report is sent.
+/// * `database` - The database.
+/// * `stats` - The statistics service whose cached statistics are dropped.
+/// * `actor_id` - The Discord ID of the owner.
+/// * `contents` - The archive as written by `guild export`.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn import_archive(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    stats: &StatsService,
+    actor_id: &str,
+    contents: &[u8],
+) -> Result<(), Error> {
+    let archive: GuildArchive = match serde_json::from_slice(contents) {
+        Ok(archive) => archive,
+        Err(e) => {
+            return frontend
+                .send_reply(Reply::error(format!("アーカイブを読み込めません: {}", e)))
+                .await;
+        }
+    };
+
+    let summary = match import_guild(database, &archive).await {
+        Ok(summary) => summary,
+        Err(ImportError::Database(e)) => return Err(e.into()),
+        Err(e) => {
+            return frontend
+                .send_reply(Reply::error(format!("アーカイブを取り込めません: {}", e)))
+                .await;
+        }
+    };
+    database
+        .lock()
+        .await
+        .record_admin_action(
+            actor_id,
+            "import_guild",
+            &summary.guild_id,
+            &format!(
+                "{} types added, {} logs of {} members imported",
+                summary.types_added, summary.logs, summary.members
+            ),
+        )
+        .await?;
+    stats.forget_all();
+
+    let mut lines = vec![
+        format!("サーバー{}のデータを取り込みました。", summary.guild_id),
+        format!(
+            "追加した種類: {}件、記録: {}人分{}件",
+            summary.types_added, summary.members, summary.logs
+        ),
+    ];
+    if summary.aggregate_only_quantity > 0 {
+        lines.push(format!(
+            "記録の共有を許可していないメンバーの{}は集計のみのため取り込まれません。",
+            format_count(summary.aggregate_only_quantity, frontend.locale())
+        ));
+    }
+
+    frontend.send_reply(Reply::new(lines.join("\n"))).await
+}
+
+/// Re-registers the application commands, globally or in one guild, and reports what changed.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `guild_id` - The guild to register in, or `None` to register globally.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "sync-commands")]
+pub async fn admin_sync_commands(ctx: Context<'_>, guild_id: Option<u64>) -> Result<(), Error> {
+    let changes = sync_commands(
+        ctx.http(),
+        &ctx.framework().options().commands,
+        guild_id.map(serenity::GuildId::new),
+    )
+    .await?;
+
+    show_command_changes(&ctx, &changes, guild_id).await
+}
+
+/// Registers the slash commands in this server, or globally with `global`.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `scope` - `global` to register globally; the current guild otherwise.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, slash_command, owners_only)]
+pub async fn register(
+    ctx: Context<'_>,
+    #[description = "global でグローバルに登録します"] scope: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = match scope.as_deref() {
+        Some("global") => None,
+        Some(_) => {
+            return ctx
+                .send_reply(Reply::error("使い方: register [global]"))
+                .await;
+        }
+        None => match ctx.guild_id() {
+            Some(guild_id) => Some(guild_id),
+            None => {
+                return ctx
+                    .send_reply(Reply::error(
+                        "サーバーの外ではグローバルにしか登録できません: register global",
+                    ))
+                    .await;
+            }
+        },
+    };
+    let changes = sync_commands(ctx.http(), &ctx.framework().options().commands, guild_id).await?;
+
+    show_command_changes(&ctx, &changes, guild_id.map(|guild_id| guild_id.get())).await
+}
+
+/// Reports how a sync changed the registered commands.
+///
+/// # Arguments
+/// * `frontend` - Where the report is sent.
+/// * `changes` - The changes.
+/// * `guild_id` - The guild the commands were registered in, or `None` if registered globally.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn show_command_changes(
+    frontend: &dyn Frontend,
+    changes: &CommandChanges,
+    guild_id: Option<u64>,
+) -> Result<(), Error> {
+    let mut lines = vec![match guild_id {
+        Some(guild_id) => format!("サーバー {} のコマンドを再登録しました。", guild_id),
+        None => "グローバルコマンドを再登録しました。".to_string(),
+    }];
+    if changes.is_empty() {
+        lines.push("変更はありません。".to_string());
+    }
+    for (label, names) in [
+        ("追加", &changes.added),
+        ("更新", &changes.updated),
+        ("削除", &changes.removed),
+    ] {
+        if !names.is_empty() {
+            lines.push(format!("{}: {}", label, names.join(", ")));
+        }
+    }
+    if changes.unchanged > 0 {
+        lines.push(format!("変更なし: {}件", changes.unchanged));
+    }
+    if guild_id.is_none() && !changes.is_empty() {
+        lines.push("すべてのサーバーに反映されるまで時間がかかることがあります。".to_string());
+    }
+
+    frontend.send_reply(Reply::new(lines.join("\n"))).await
+}
+
+/// Lists the gateway shards with their latency, guild count and last heartbeat.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "shards")]
+pub async fn admin_shards(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new(render_shards(
+        &shards::statuses(),
+        ctx.data().clock.now(),
+    )))
+    .await
+}
+
+/// Renders the shards, one line each.
+///
+/// # Arguments
+/// * `statuses` - The shards as seen at the last check.
+/// * `now` - The current time, to say how long ago each heartbeat was.
+///
+/// # Returns
+/// The report, e.g. `シャード0: 接続済み / 遅延 42ms / 12サーバー / 最後のハートビート 15秒前`.
+pub fn render_shards(statuses: &[ShardStatus], now: DateTime<Utc>) -> String {
+    if statuses.is_empty() {
+        return "シャードの情報はまだありません。".to_string();
+    }
+
+    statuses
+        .iter()
+        .map(|status| {
+            let stage = match status.stage {
+                serenity::ConnectionStage::Connected => "接続済み",
+                serenity::ConnectionStage::Disconnected => "切断",
+                _ => "接続中",
+            };
+            let latency = status.latency.map_or("不明".to_string(), |latency| {
+                format!("{}ms", latency.as_millis())
+            });
+            let heartbeat = status.last_heartbeat.map_or("なし".to_string(), |at| {
+                format!("{}秒前", (now - at).num_seconds().max(0))
+            });
+            let mut line = format!(
+                "シャード{}: {} / 遅延 {} / {}サーバー / 最後のハートビート {}",
+                status.id, stage, latency, status.guilds, heartbeat
+            );
+            if status.restarts > 0 {
+                line.push_str(&format!(" / 再起動 {}回", status.restarts));
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns the admin commands.
+pub fn commands() -> Vec<Command> {
+    vec![admin(), register()]
+}
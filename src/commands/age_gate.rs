@@ -0,0 +1,126 @@
+//! Per-guild acknowledgment required before members can use the panel.
+
+use crate::acknowledgment::DEFAULT_MESSAGE;
+use crate::database::Database;
+use crate::embed::Title;
+use crate::frontend::{Frontend, Reply};
+use crate::{Context, Error};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+use super::Command;
+
+/// Manages the acknowledgment members give before using the panel.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("age_gate_on", "age_gate_off")
+)]
+pub async fn age_gate(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new(
+        "使い方: age_gate on [確認メッセージ] / age_gate off",
+    ))
+    .await
+}
+
+/// Requires members to acknowledge a message before using the panel.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `message` - The message to acknowledge; defaults to a legal smoking age confirmation.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "on"
+)]
+pub async fn age_gate_on(ctx: Context<'_>, #[rest] message: Option<String>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?;
+
+    update_age_gate(
+        &ctx,
+        &ctx.data().database,
+        &guild_id.to_string(),
+        Some(message.as_deref().unwrap_or(DEFAULT_MESSAGE)),
+    )
+    .await
+}
+
+/// Stops requiring an acknowledgment.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "off"
+)]
+pub async fn age_gate_off(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?;
+
+    update_age_gate(&ctx, &ctx.data().database, &guild_id.to_string(), None).await
+}
+
+/// Stores (or removes) a guild's required acknowledgment and confirms the change.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `guild_id` - The ID of the guild.
+/// * `message` - The message members acknowledge, or `None` to stop requiring one.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn update_age_gate(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    guild_id: &str,
+    message: Option<&str>,
+) -> Result<(), Error> {
+    let message = message.map(str::trim);
+    if message.is_some_and(str::is_empty) {
+        return frontend
+            .send_reply(Reply::error("確認メッセージを入力してください。"))
+            .await;
+    }
+
+    let reply = {
+        let db = database.lock().await;
+        let guild = db.guild(guild_id);
+        match message {
+            Some(message) => {
+                guild.set_acknowledgment_policy(message).await?;
+                format!(
+                    "パネルの利用前に次の内容の確認を求めます。メンバーは改めて確認が必要です。\n> {}",
+                    message
+                )
+            }
+            None if guild.remove_acknowledgment_policy().await? => {
+                "確認の要求を解除しました。".to_string()
+            }
+            None => "確認は要求されていません。".to_string(),
+        }
+    };
+
+    frontend
+        .send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Returns the age-gate commands.
+pub fn commands() -> Vec<Command> {
+    vec![age_gate()]
+}
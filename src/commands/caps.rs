@@ -0,0 +1,307 @@
+//! Per-type daily caps set by guild admins, and the member's own override.
+
+use crate::database::Database;
+use crate::embed::Title;
+use crate::format::branding;
+use crate::frontend::{Frontend, Reply};
+use crate::{Context, Error};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+use super::Command;
+
+/// Manages the daily caps of smoking types in this guild.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("cap_set", "cap_remove", "cap_list")
+)]
+pub async fn cap(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new(
+        "使い方: cap set <種類> <本数> / cap remove <種類> / cap list",
+    ))
+    .await
+}
+
+/// Limits how many units of a smoking type members may log per day.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `type_name` - The type name of the smoking type (e.g. `iqos`).
+/// * `daily_cap` - The most units a member may log per day.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "set"
+)]
+pub async fn cap_set(ctx: Context<'_>, type_name: String, daily_cap: i32) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?;
+
+    update_type_cap(
+        &ctx,
+        &ctx.data().database,
+        &guild_id.to_string(),
+        &type_name,
+        Some(daily_cap),
+    )
+    .await
+}
+
+/// Removes the daily cap of a smoking type.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `type_name` - The type name of the smoking type.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "remove"
+)]
+pub async fn cap_remove(ctx: Context<'_>, type_name: String) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?;
+
+    update_type_cap(
+        &ctx,
+        &ctx.data().database,
+        &guild_id.to_string(),
+        &type_name,
+        None,
+    )
+    .await
+}
+
+/// Lists the daily caps of this guild.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "list"
+)]
+pub async fn cap_list(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?;
+
+    list_type_caps(&ctx, &ctx.data().database, &guild_id.to_string()).await
+}
+
+/// Chooses whether your own logs ignore the daily caps set by guild admins.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    rename = "ignore-caps",
+    subcommands("ignore_caps_on", "ignore_caps_off")
+)]
+pub async fn ignore_caps(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new("使い方: ignore-caps on / ignore-caps off"))
+        .await
+}
+
+/// Logs past the daily caps set by guild admins.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "on")]
+pub async fn ignore_caps_on(ctx: Context<'_>) -> Result<(), Error> {
+    update_ignore_type_caps(
+        &ctx,
+        &ctx.data().database,
+        &ctx.author().id.get().to_string(),
+        &ctx.author().name,
+        true,
+    )
+    .await
+}
+
+/// Follows the daily caps set by guild admins again.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "off")]
+pub async fn ignore_caps_off(ctx: Context<'_>) -> Result<(), Error> {
+    update_ignore_type_caps(
+        &ctx,
+        &ctx.data().database,
+        &ctx.author().id.get().to_string(),
+        &ctx.author().name,
+        false,
+    )
+    .await
+}
+
+/// Stores (or removes) a guild's daily cap of a smoking type and confirms the change.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `guild_id` - The ID of the guild.
+/// * `type_name` - The type name of the smoking type.
+/// * `daily_cap` - The new cap, or `None` to remove the cap.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn update_type_cap(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    guild_id: &str,
+    type_name: &str,
+    daily_cap: Option<i32>,
+) -> Result<(), Error> {
+    if daily_cap.is_some_and(|cap| cap < 0) {
+        return frontend
+            .send_reply(Reply::error("上限は0以上の本数で指定してください。"))
+            .await;
+    }
+
+    let reply = {
+        let db = database.lock().await;
+        let Some(smoking_type) = db.find_smoking_type_by_name(type_name).await? else {
+            let known: Vec<String> = db
+                .get_smoking_types()
+                .await?
+                .into_iter()
+                .map(|smoking_type| smoking_type.type_name)
+                .collect();
+            drop(db);
+            return frontend
+                .send_reply(Reply::error(format!(
+                    "不明な種類です: {}（使用できる種類: {}）",
+                    type_name,
+                    known.join(", ")
+                )))
+                .await;
+        };
+
+        let guild = db.guild(guild_id);
+        let name = smoking_type.description.unwrap_or(smoking_type.type_name);
+        match daily_cap {
+            Some(cap) => {
+                guild.set_type_cap(smoking_type.id, cap).await?;
+                format!(
+                    "{}の1日の上限を{}{}にしました。",
+                    name,
+                    cap,
+                    branding().count_unit
+                )
+            }
+            None if guild.remove_type_cap(smoking_type.id).await? => {
+                format!("{}の上限を解除しました。", name)
+            }
+            None => format!("{}に上限は設定されていません。", name),
+        }
+    };
+
+    frontend
+        .send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Lists a guild's daily caps.
+///
+/// # Arguments
+/// * `frontend` - Where the list is sent.
+/// * `database` - The database.
+/// * `guild_id` - The ID of the guild.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn list_type_caps(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    guild_id: &str,
+) -> Result<(), Error> {
+    let lines = {
+        let db = database.lock().await;
+        let caps = db.guild(guild_id).get_type_caps().await?;
+        let mut lines = Vec::with_capacity(caps.len());
+        for cap in caps {
+            let smoking_type = db.get_smoking_type(cap.smoking_type_id).await?;
+            lines.push(format!(
+                "- {}: {}{}",
+                smoking_type.description.unwrap_or(smoking_type.type_name),
+                cap.daily_cap,
+                branding().count_unit
+            ));
+        }
+        lines
+    };
+
+    let reply = if lines.is_empty() {
+        "1日の上限は設定されていません。".to_string()
+    } else {
+        format!("1日の上限:\n{}", lines.join("\n"))
+    };
+
+    frontend
+        .send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Stores whether a user's logs ignore guild caps and confirms the change.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `user_id` - The Discord ID of the user.
+/// * `username` - The current username of the user.
+/// * `ignore` - Whether caps are ignored.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn update_ignore_type_caps(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    user_id: &str,
+    username: &str,
+    ignore: bool,
+) -> Result<(), Error> {
+    {
+        let db = database.lock().await;
+        let user = db.get_or_create_user(user_id, username).await?;
+        db.set_ignore_type_caps(&user.discord_id, ignore).await?;
+    }
+
+    let reply = if ignore {
+        "サーバーで設定された1日の上限を超えても記録します。"
+    } else {
+        "サーバーで設定された1日の上限に従って記録します。"
+    };
+
+    frontend
+        .send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Returns the daily cap commands.
+pub fn commands() -> Vec<Command> {
+    vec![cap(), ignore_caps()]
+}
@@ -0,0 +1,133 @@
+//! The guild's weekly reduction challenge; see `crate::challenge`.
+
+use crate::embed::Title;
+use crate::frontend::{Frontend, Reply};
+use crate::{Context, Error};
+use poise::serenity_prelude as serenity;
+
+use super::Command;
+
+/// Largest reduction a challenge can aim for, in percent
+pub const MAX_TARGET_PERCENT: i32 = 100;
+
+/// Shows the guild's weekly challenge.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    subcommands("challenge_start", "challenge_off")
+)]
+pub async fn challenge(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let challenge = ctx
+        .data()
+        .database
+        .lock()
+        .await
+        .guild(&guild_id)
+        .get_challenge()
+        .await?;
+
+    let status = match challenge {
+        Some(challenge) => format!(
+            "毎週、前の週より{}%少なくすることを目標に <#{}> で進捗を投稿しています。",
+            challenge.target_percent, challenge.channel_id
+        ),
+        None => "週ごとのチャレンジは行っていません。".to_string(),
+    };
+    ctx.send_reply(Reply::new(format!(
+        "{}\n使い方: challenge start <削減率%> #チャンネル / challenge off",
+        status
+    )))
+    .await
+}
+
+/// Starts a weekly challenge to log a percentage less than the week before, or changes it.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `target_percent` - The reduction aimed for, in percent.
+/// * `channel` - The channel progress is posted in.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "start"
+)]
+pub async fn challenge_start(
+    ctx: Context<'_>,
+    target_percent: i32,
+    channel: serenity::GuildChannel,
+) -> Result<(), Error> {
+    if !(1..=MAX_TARGET_PERCENT).contains(&target_percent) {
+        return ctx
+            .send_reply(Reply::error(format!(
+                "削減率は1から{}の範囲で指定してください。",
+                MAX_TARGET_PERCENT
+            )))
+            .await;
+    }
+
+    ctx.data()
+        .database
+        .lock()
+        .await
+        .guild(&channel.guild_id.to_string())
+        .set_challenge(&channel.id.to_string(), target_percent)
+        .await?;
+
+    ctx.send_reply(
+        Reply::new(format!(
+            "前の週より{}%少なくするチャレンジを設定しました。進捗は{}に1日1回更新され、今週の分は1時間以内に投稿されます。変更は来週から反映されます。",
+            target_percent, channel.name
+        ))
+        .titled(Title::Settings),
+    )
+    .await
+}
+
+/// Stops the weekly challenge; posted progress is kept.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "off"
+)]
+pub async fn challenge_off(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let removed = ctx
+        .data()
+        .database
+        .lock()
+        .await
+        .guild(&guild_id)
+        .remove_challenge()
+        .await?;
+
+    let reply = if removed {
+        "週ごとのチャレンジを終了しました。"
+    } else {
+        "週ごとのチャレンジは行っていません。"
+    };
+    ctx.send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Returns the weekly challenge commands.
+pub fn commands() -> Vec<Command> {
+    vec![challenge()]
+}
@@ -0,0 +1,321 @@
+//! Detection and cleanup of duplicate logs.
+//!
+//! Before button presses were debounced, a double press could log the same
+//! cigarette twice. `cleanup` lists a member's logs that repeat an earlier
+//! log of the same type and quantity within a few seconds and offers buttons
+//! to merge them into the original logs or delete them. Owners get the same
+//! view for any user with `admin duplicates`. A button only works for the
+//! person who ran the command that showed it.
+
+use chrono::Duration;
+use chrono_tz::Tz;
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+
+use crate::database::{Database, DuplicateResolution};
+use crate::format::format_count;
+use crate::frontend::{Frontend, InteractionFrontend, Reply};
+use crate::service::StatsService;
+use crate::{Context, Data, Error};
+
+use super::Command;
+
+/// Prefix of the custom IDs of cleanup buttons
+const CLEANUP_PREFIX: &str = "cleanup:";
+
+/// Longest gap, in seconds, between two identical logs counted as duplicates
+const DUPLICATE_WINDOW_SECONDS: i64 = 5;
+
+/// Most duplicates listed in one message
+const MAX_LISTED_DUPLICATES: usize = 10;
+
+/// Returns the longest gap between two identical logs counted as duplicates.
+pub fn duplicate_window() -> Duration {
+    Duration::seconds(DUPLICATE_WINDOW_SECONDS)
+}
+
+/// A cleanup button: what to do with whose duplicates, and who may press it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CleanupId {
+    /// How the duplicates are cleaned up
+    pub resolution: DuplicateResolution,
+    /// The Discord ID of the user whose logs are cleaned up
+    pub target_id: String,
+    /// The Discord ID of the user who ran the command and may press the button
+    pub actor_id: String,
+}
+
+impl CleanupId {
+    /// Encodes the custom ID of the button.
+    pub fn custom_id(&self) -> String {
+        let resolution = match self.resolution {
+            DuplicateResolution::Merge => "merge",
+            DuplicateResolution::Delete => "delete",
+        };
+
+        format!(
+            "{}{}:{}:{}",
+            CLEANUP_PREFIX, resolution, self.target_id, self.actor_id
+        )
+    }
+
+    /// Decodes the custom ID of a cleanup button.
+    ///
+    /// # Arguments
+    /// * `custom_id` - The custom ID of a pressed button.
+    ///
+    /// # Returns
+    /// The cleanup to run, or `None` if the button is not a cleanup button.
+    pub fn parse(custom_id: &str) -> Option<Self> {
+        let mut parts = custom_id.strip_prefix(CLEANUP_PREFIX)?.split(':');
+        let (resolution, target_id, actor_id) = (parts.next()?, parts.next()?, parts.next()?);
+        let is_id = |id: &str| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit());
+        if parts.next().is_some() || !is_id(target_id) || !is_id(actor_id) {
+            return None;
+        }
+
+        let resolution = match resolution {
+            "merge" => DuplicateResolution::Merge,
+            "delete" => DuplicateResolution::Delete,
+            _ => return None,
+        };
+
+        Some(Self {
+            resolution,
+            target_id: target_id.to_string(),
+            actor_id: actor_id.to_string(),
+        })
+    }
+}
+
+/// Lists your logs that look like accidental duplicates and offers to clean them up.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command)]
+pub async fn cleanup(ctx: Context<'_>) -> Result<(), Error> {
+    let user_id = ctx.author().id.get().to_string();
+
+    suggest_cleanup(
+        &ctx,
+        &ctx.data().database,
+        ctx.data().clock.timezone(),
+        &user_id,
+        &user_id,
+    )
+    .await
+}
+
+/// Lists a user's duplicate logs with buttons to merge or delete them.
+///
+/// # Arguments
+/// * `frontend` - Where the list is sent.
+/// * `database` - The database.
+/// * `timezone` - The time zone log times are shown in.
+/// * `actor_id` - The Discord ID of the user who may press the buttons.
+/// * `target_id` - The Discord ID of the user whose logs are listed.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn suggest_cleanup(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    timezone: Tz,
+    actor_id: &str,
+    target_id: &str,
+) -> Result<(), Error> {
+    let (duplicates, smoking_types) = {
+        let db = database.lock().await;
+        (
+            db.find_duplicate_logs(Some(target_id), duplicate_window())
+                .await?,
+            db.get_smoking_types().await?,
+        )
+    };
+
+    if duplicates.is_empty() {
+        return frontend
+            .send_reply(Reply::new("重複していそうな記録は見つかりませんでした。"))
+            .await;
+    }
+
+    let mut lines = vec![format!(
+        "{}秒以内に同じ内容で記録された重複らしき記録が{}件あります。",
+        DUPLICATE_WINDOW_SECONDS,
+        duplicates.len()
+    )];
+    for duplicate in duplicates.iter().take(MAX_LISTED_DUPLICATES) {
+        let name = smoking_types
+            .iter()
+            .find(|smoking_type| smoking_type.id == duplicate.smoking_type_id)
+            .map_or("?", |smoking_type| {
+                smoking_type
+                    .description
+                    .as_deref()
+                    .unwrap_or(&smoking_type.type_name)
+            });
+        lines.push(format!(
+            "- #{}（#{}の重複）{} {} {}",
+            duplicate.id,
+            duplicate.original_id,
+            duplicate
+                .smoked_at
+                .with_timezone(&timezone)
+                .format("%Y/%m/%d %H:%M:%S"),
+            name,
+            format_count(i64::from(duplicate.quantity), frontend.locale())
+        ));
+    }
+    if duplicates.len() > MAX_LISTED_DUPLICATES {
+        lines.push(format!(
+            "ほか{}件",
+            duplicates.len() - MAX_LISTED_DUPLICATES
+        ));
+    }
+    lines.push(
+        "「統合」は本数を元の記録に加えて重複を消し、「削除」は重複をそのまま消します。"
+            .to_string(),
+    );
+
+    let button = |resolution| {
+        CleanupId {
+            resolution,
+            target_id: target_id.to_string(),
+            actor_id: actor_id.to_string(),
+        }
+        .custom_id()
+    };
+    frontend
+        .send_reply(
+            Reply::new(lines.join("\n"))
+                .button(button(DuplicateResolution::Merge), "統合")
+                .button(button(DuplicateResolution::Delete), "削除"),
+        )
+        .await
+}
+
+/// Reports how many duplicate logs every user has.
+///
+/// # Arguments
+/// * `frontend` - Where the report is sent.
+/// * `database` - The database.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn duplicate_report(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+) -> Result<(), Error> {
+    let duplicates = database
+        .lock()
+        .await
+        .find_duplicate_logs(None, duplicate_window())
+        .await?;
+
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for duplicate in duplicates {
+        match counts.last_mut() {
+            Some((discord_id, count)) if *discord_id == duplicate.discord_id => *count += 1,
+            _ => counts.push((duplicate.discord_id, 1)),
+        }
+    }
+
+    let reply = if counts.is_empty() {
+        "重複していそうな記録は見つかりませんでした。".to_string()
+    } else {
+        let lines: Vec<String> = counts
+            .iter()
+            .map(|(discord_id, count)| format!("- <@{}>: {}件", discord_id, count))
+            .collect();
+        format!(
+            "重複らしき記録があるユーザー（admin duplicates <ユーザー> で整理できます）:\n{}",
+            lines.join("\n")
+        )
+    };
+
+    frontend.send_reply(Reply::new(reply)).await
+}
+
+/// Handles a press of a cleanup button.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `mci` - The button press.
+/// * `cleanup` - The cleanup encoded in the button.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub(crate) async fn handle_cleanup(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &serenity::ComponentInteraction,
+    cleanup: &CleanupId,
+) -> Result<(), Error> {
+    apply_cleanup(
+        &InteractionFrontend::new(ctx, mci),
+        &data.database,
+        &data.stats,
+        &mci.user.id.get().to_string(),
+        cleanup,
+    )
+    .await
+}
+
+/// Cleans up a user's duplicate logs if the presser may do so, and responds with the result.
+///
+/// The duplicates are looked up again, so logs made since the list was shown are respected.
+///
+/// # Arguments
+/// * `frontend` - Where the response is sent.
+/// * `database` - The database.
+/// * `stats` - The statistics service whose cached aggregates are dropped.
+/// * `presser_id` - The Discord ID of the user who pressed the button.
+/// * `cleanup` - The cleanup encoded in the button.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn apply_cleanup(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    stats: &StatsService,
+    presser_id: &str,
+    cleanup: &CleanupId,
+) -> Result<(), Error> {
+    if presser_id != cleanup.actor_id {
+        return frontend
+            .respond(Reply::error("このボタンはコマンドを実行した人だけが使えます。").ephemeral())
+            .await;
+    }
+
+    let removed = database
+        .lock()
+        .await
+        .resolve_duplicate_logs(
+            presser_id,
+            &cleanup.target_id,
+            duplicate_window(),
+            cleanup.resolution,
+        )
+        .await?;
+    stats.forget_user(&cleanup.target_id);
+
+    let reply = match (removed, cleanup.resolution) {
+        (0, _) => "重複した記録はもうありません。".to_string(),
+        (removed, DuplicateResolution::Merge) => {
+            format!("重複した記録{}件を元の記録に統合しました。", removed)
+        }
+        (removed, DuplicateResolution::Delete) => {
+            format!("重複した記録{}件を削除しました。", removed)
+        }
+    };
+
+    frontend.respond(Reply::new(reply).ephemeral()).await
+}
+
+/// Returns the cleanup commands.
+pub fn commands() -> Vec<Command> {
+    vec![cleanup()]
+}
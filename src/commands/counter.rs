@@ -0,0 +1,141 @@
+//! A live counter of the guild in a channel's topic or name.
+
+use crate::channel_counter::{counter_text, CounterMetric, CounterTarget};
+use crate::embed::Title;
+use crate::frontend::{Frontend, Reply};
+use crate::{Context, Error};
+use poise::serenity_prelude as serenity;
+
+use super::Command;
+
+/// Shows the channel counter of this guild.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, guild_only, subcommands("counter_set", "counter_off"))]
+pub async fn counter(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let counter = ctx
+        .data()
+        .database
+        .lock()
+        .await
+        .guild(&guild_id)
+        .get_channel_counter()
+        .await?;
+
+    let status = match counter {
+        Some(counter) => format!(
+            "<#{}> に {} を表示しています。",
+            counter.channel_id, counter.metric
+        ),
+        None => "カウンターは設定されていません。".to_string(),
+    };
+    ctx.send_reply(Reply::new(format!(
+        "{}\n使い方: counter set <daily-total|days-since> #チャンネル / counter off",
+        status
+    )))
+    .await
+}
+
+/// Shows the guild's daily total or smoke-free days in a channel, updated
+/// every few minutes: in the topic of a text channel or the name of a voice
+/// channel.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `metric` - What to show (`daily-total` or `days-since`).
+/// * `channel` - The channel showing the counter.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_CHANNELS",
+    rename = "set"
+)]
+pub async fn counter_set(
+    ctx: Context<'_>,
+    metric: String,
+    channel: serenity::GuildChannel,
+) -> Result<(), Error> {
+    let Some(metric) = CounterMetric::from_name(&metric) else {
+        return ctx
+            .send_reply(Reply::error(
+                "表示する値を指定してください: daily-total / days-since",
+            ))
+            .await;
+    };
+    let Some(target) = CounterTarget::for_channel(channel.kind) else {
+        return ctx
+            .send_reply(Reply::error(
+                "テキストチャンネルかボイスチャンネルを指定してください。",
+            ))
+            .await;
+    };
+
+    ctx.data()
+        .database
+        .lock()
+        .await
+        .guild(&channel.guild_id.to_string())
+        .set_channel_counter(&channel.id.to_string(), metric.name(), target.name())
+        .await?;
+
+    let place = match target {
+        CounterTarget::Topic => "トピック",
+        CounterTarget::Name => "名前",
+    };
+    ctx.send_reply(
+        Reply::new(format!(
+            "{}の{}に「{}」のように表示します。数分以内に反映され、その後は最短10分ごとに更新されます。",
+            channel.name,
+            place,
+            counter_text(metric, Some(3))
+        ))
+        .titled(Title::Settings),
+    )
+    .await
+}
+
+/// Stops updating the channel counter; the channel keeps its last text.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_CHANNELS",
+    rename = "off"
+)]
+pub async fn counter_off(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let removed = ctx
+        .data()
+        .database
+        .lock()
+        .await
+        .guild(&guild_id)
+        .remove_channel_counter()
+        .await?;
+
+    let reply = if removed {
+        "カウンターの更新を停止しました。"
+    } else {
+        "カウンターは設定されていません。"
+    };
+    ctx.send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Returns the channel counter commands.
+pub fn commands() -> Vec<Command> {
+    vec![counter()]
+}
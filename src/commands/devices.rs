@@ -0,0 +1,106 @@
+//! Registration of devices and shortcut links for the inbound HTTP API.
+
+use crate::frontend::{Frontend, Reply};
+use crate::http::generate_token;
+use crate::{Context, Error};
+use poise::serenity_prelude as serenity;
+
+use super::Command;
+
+/// Registers a device for the inbound HTTP API and DMs its token to the author.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `name` - A human-readable name for the device, recorded on each log.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command)]
+pub async fn register_device(ctx: Context<'_>, #[rest] name: String) -> Result<(), Error> {
+    let (token, token_hash) = generate_token();
+
+    {
+        let db = ctx.data().database.lock().await;
+        let user_id = ctx.author().id.get().to_string();
+        let user = db.get_or_create_user(&user_id, &ctx.author().name).await?;
+        db.create_device(&user.discord_id, &name, &token_hash)
+            .await?;
+    }
+
+    ctx.author()
+        .direct_message(
+            ctx,
+            serenity::CreateMessage::new().content(format!(
+                "デバイス「{}」を登録しました。\nトークン: `{}`\nこのトークンは再表示できません。",
+                name, token
+            )),
+        )
+        .await?;
+
+    ctx.send_reply(Reply::new(
+        "デバイスを登録しました。トークンをDMで送信しました。",
+    ))
+    .await
+}
+
+/// Creates a shortcut link (for a QR code or NFC tag) that logs one unit of a smoking type when opened.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `type_name` - The type name of the smoking type to log (e.g. `iqos`).
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command)]
+pub async fn create_shortcut(ctx: Context<'_>, type_name: String) -> Result<(), Error> {
+    let Some(public_url) = ctx.data().config.public_url.clone() else {
+        return ctx
+            .send_reply(Reply::error(
+                "PUBLIC_URL が設定されていないため、ショートカットを作成できません。",
+            ))
+            .await;
+    };
+
+    let (token, token_hash) = generate_token();
+
+    let smoking_type = {
+        let db = ctx.data().database.lock().await;
+        let Some(smoking_type) = db.find_smoking_type_by_name(&type_name).await? else {
+            return ctx
+                .send_reply(Reply::error(format!(
+                    "種類「{}」は存在しません。",
+                    type_name
+                )))
+                .await;
+        };
+
+        let user_id = ctx.author().id.get().to_string();
+        let user = db.get_or_create_user(&user_id, &ctx.author().name).await?;
+        db.create_shortcut_link(&user.discord_id, smoking_type.id, &token_hash)
+            .await?;
+
+        smoking_type
+    };
+
+    ctx.author()
+        .direct_message(
+            ctx,
+            serenity::CreateMessage::new().content(format!(
+                "「{}」のショートカットを作成しました。\n{}/s/{}\nこのURLを開くと1本記録されます。",
+                smoking_type.description.unwrap_or(smoking_type.type_name),
+                public_url,
+                token
+            )),
+        )
+        .await?;
+
+    ctx.send_reply(Reply::new(
+        "ショートカットを作成しました。URLをDMで送信しました。",
+    ))
+    .await
+}
+
+/// Returns the device and shortcut commands.
+pub fn commands() -> Vec<Command> {
+    vec![register_device(), create_shortcut()]
+}
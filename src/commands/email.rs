@@ -0,0 +1,172 @@
+//! Weekly email digests, see `email`.
+
+use lettre::Address;
+use poise::serenity_prelude::futures::lock::Mutex;
+use tracing::warn;
+
+use crate::database::Database;
+use crate::email::{verification_email, Mailer};
+use crate::frontend::{Frontend, Reply};
+use crate::http::generate_token;
+use crate::{Context, Error};
+
+use super::Command;
+
+/// Manages the weekly summary emailed to you.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, subcommands("email_set", "email_off"))]
+pub async fn email(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new("使い方: email set <メールアドレス> / email off"))
+        .await
+}
+
+/// Subscribes an address to the weekly summary; a verification link is mailed to it first.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `address` - The email address.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "set")]
+pub async fn email_set(ctx: Context<'_>, address: String) -> Result<(), Error> {
+    let (Some(mailer), Some(public_url)) = (&ctx.data().mailer, &ctx.data().config.public_url)
+    else {
+        return ctx
+            .send_reply(Reply::error(
+                "このインスタンスではメール配信が有効になっていません。",
+            ))
+            .await;
+    };
+
+    subscribe_email(
+        &ctx,
+        &ctx.data().database,
+        mailer.as_ref(),
+        public_url,
+        &ctx.author().id.get().to_string(),
+        &ctx.author().name,
+        &address,
+    )
+    .await
+}
+
+/// Stops the weekly summary emails.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "off")]
+pub async fn email_off(ctx: Context<'_>) -> Result<(), Error> {
+    disable_email(
+        &ctx,
+        &ctx.data().database,
+        &ctx.author().id.get().to_string(),
+    )
+    .await
+}
+
+/// Subscribes a user's address to the weekly digest, replacing any previous
+/// one, and mails it a verification link.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `mailer` - The mailer sending the verification email.
+/// * `public_url` - The externally reachable base URL of the HTTP API.
+/// * `user_id` - The Discord ID of the user.
+/// * `username` - The current username of the user.
+/// * `address` - The email address.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn subscribe_email(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    mailer: &dyn Mailer,
+    public_url: &str,
+    user_id: &str,
+    username: &str,
+    address: &str,
+) -> Result<(), Error> {
+    let Ok(address) = address.parse::<Address>() else {
+        return frontend
+            .send_reply(Reply::error("メールアドレスの形式が正しくありません。"))
+            .await;
+    };
+
+    let (token, token_hash) = generate_token();
+    let (unsubscribe_token, _) = generate_token();
+    {
+        let db = database.lock().await;
+        db.get_or_create_user(user_id, username).await?;
+        db.set_email_subscription(user_id, address.as_ref(), &token_hash, &unsubscribe_token)
+            .await?;
+    }
+
+    let email = verification_email(public_url, &token);
+    if let Err(e) = mailer.send(address.as_ref(), &email).await {
+        warn!(
+            "Failed to email the verification link of {}: {}",
+            user_id, e
+        );
+        database
+            .lock()
+            .await
+            .remove_email_subscription(user_id)
+            .await?;
+        return frontend
+            .send_reply(Reply::error(
+                "確認メールを送信できませんでした。時間をおいて再度お試しください。",
+            ))
+            .await;
+    }
+
+    frontend
+        .send_reply(Reply::new(format!(
+            "{} に確認メールを送信しました。メール内のリンクを開くと登録が完了します。",
+            address
+        )))
+        .await
+}
+
+/// Stops a user's weekly digest and confirms it.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `user_id` - The Discord ID of the user.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn disable_email(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    user_id: &str,
+) -> Result<(), Error> {
+    let removed = database
+        .lock()
+        .await
+        .remove_email_subscription(user_id)
+        .await?;
+
+    let reply = if removed {
+        Reply::new("まとめメールの配信を停止しました。")
+    } else {
+        Reply::error("まとめメールは登録されていません。")
+    };
+
+    frontend.send_reply(reply).await
+}
+
+/// Returns the email commands.
+pub fn commands() -> Vec<Command> {
+    vec![email()]
+}
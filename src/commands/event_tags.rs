@@ -0,0 +1,255 @@
+//! Custom events users tag their days with, and how they relate to smoking.
+//!
+//! `event add exam` tags today (or a given date) with an event; `event
+//! analysis` compares the average per day on days with each tag against the
+//! days without it.
+
+use chrono::NaiveDate;
+
+use crate::database::{Database, EventCorrelation};
+use crate::embed::Title;
+use crate::format::format_date;
+use crate::frontend::{Frontend, Reply};
+use crate::service::StatsService;
+use crate::{Context, Error};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+use super::Command;
+
+/// Longest event tag, in characters
+pub const MAX_TAG_LENGTH: usize = 32;
+
+/// Number of days ending today the analysis covers
+pub const ANALYSIS_DAYS: i64 = 90;
+
+/// Manages custom events.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    subcommands("event_add", "event_remove", "event_analysis")
+)]
+pub async fn event(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new(
+        "使い方: event add <タグ> [日付] / event remove <タグ> [日付] / event analysis",
+    ))
+    .await
+}
+
+/// Tags a day with a custom event (e.g. `event add exam`).
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `tag` - The event tag.
+/// * `date` - The date of the event, or omitted for today.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "add")]
+pub async fn event_add(
+    ctx: Context<'_>,
+    tag: String,
+    date: Option<NaiveDate>,
+) -> Result<(), Error> {
+    update_event_tag(
+        &ctx,
+        &ctx.data().database,
+        &ctx.author().id.get().to_string(),
+        &ctx.author().name,
+        ctx.data().clock.today(),
+        EventChange::Add,
+        &tag,
+        date,
+    )
+    .await
+}
+
+/// Removes a custom event from a day.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `tag` - The event tag.
+/// * `date` - The date of the event, or omitted for today.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "remove")]
+pub async fn event_remove(
+    ctx: Context<'_>,
+    tag: String,
+    date: Option<NaiveDate>,
+) -> Result<(), Error> {
+    update_event_tag(
+        &ctx,
+        &ctx.data().database,
+        &ctx.author().id.get().to_string(),
+        &ctx.author().name,
+        ctx.data().clock.today(),
+        EventChange::Remove,
+        &tag,
+        date,
+    )
+    .await
+}
+
+/// Shows the average per day on days with and without each of your event tags.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "analysis")]
+pub async fn event_analysis(ctx: Context<'_>) -> Result<(), Error> {
+    show_event_analysis(&ctx, &ctx.data().stats, &ctx.author().id.get().to_string()).await
+}
+
+/// Whether a tag is added to or removed from a day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventChange {
+    Add,
+    Remove,
+}
+
+/// Normalizes an event tag.
+///
+/// # Arguments
+/// * `tag` - The tag as typed.
+///
+/// # Returns
+/// The lowercase tag, or `None` if it is empty, too long or contains whitespace.
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim().to_lowercase();
+    let length = tag.chars().count();
+
+    (length > 0
+        && length <= MAX_TAG_LENGTH
+        && !tag.chars().any(|c| c.is_whitespace() || c.is_control()))
+    .then_some(tag)
+}
+
+/// Adds a tag to or removes it from a user's day and confirms the change.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `user_id` - The Discord ID of the user.
+/// * `username` - The current username of the user.
+/// * `today` - The user's current local date.
+/// * `change` - Whether to add or remove the tag.
+/// * `tag` - The tag as typed.
+/// * `date` - The date of the event, or `None` for today.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[allow(clippy::too_many_arguments)]
+pub async fn update_event_tag(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    user_id: &str,
+    username: &str,
+    today: NaiveDate,
+    change: EventChange,
+    tag: &str,
+    date: Option<NaiveDate>,
+) -> Result<(), Error> {
+    let Some(tag) = normalize_tag(tag) else {
+        return frontend
+            .send_reply(Reply::error(format!(
+                "タグは空白を含まない{}文字以内で指定してください。",
+                MAX_TAG_LENGTH
+            )))
+            .await;
+    };
+    let date = date.unwrap_or(today);
+    if date > today {
+        return frontend
+            .send_reply(Reply::error("未来の日付には記録できません。"))
+            .await;
+    }
+    let day = format_date(date, frontend.locale());
+
+    let reply = {
+        let db = database.lock().await;
+        match change {
+            EventChange::Add => {
+                let user = db.get_or_create_user(user_id, username).await?;
+                if db.add_user_event(&user.discord_id, &tag, date).await? {
+                    format!("{}に「{}」を記録しました。", day, tag)
+                } else {
+                    format!("{}には「{}」が記録済みです。", day, tag)
+                }
+            }
+            EventChange::Remove => {
+                if db.remove_user_event(user_id, &tag, date).await? {
+                    format!("{}の「{}」を削除しました。", day, tag)
+                } else {
+                    format!("{}に「{}」は記録されていません。", day, tag)
+                }
+            }
+        }
+    };
+
+    frontend
+        .send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Sends the comparison of a user's days with and without each event tag.
+///
+/// # Arguments
+/// * `frontend` - Where the analysis is sent.
+/// * `stats` - The statistics service.
+/// * `discord_id` - The Discord ID of the user.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn show_event_analysis(
+    frontend: &dyn Frontend,
+    stats: &StatsService,
+    discord_id: &str,
+) -> Result<(), Error> {
+    let correlations = stats.event_correlations(discord_id, ANALYSIS_DAYS).await?;
+    let lines = correlation_lines(&correlations);
+
+    frontend
+        .send_reply(Reply::new(lines.join("\n")).titled(Title::Stats))
+        .await
+}
+
+/// Renders the comparison of days with and without each event tag.
+///
+/// # Arguments
+/// * `correlations` - The correlation of each tag.
+///
+/// # Returns
+/// The lines.
+pub fn correlation_lines(correlations: &[EventCorrelation]) -> Vec<String> {
+    let mut lines = vec![format!("直近{}日のイベント別の1日平均", ANALYSIS_DAYS)];
+    if correlations.is_empty() {
+        lines.push("イベントが記録されていません。event add <タグ> で記録できます。".to_string());
+    }
+    for correlation in correlations {
+        let difference = correlation.average_with - correlation.average_without;
+        lines.push(format!(
+            "- {}: あり {:.1}本（{}日） / なし {:.1}本（{}日） → {:+.1}本",
+            correlation.tag,
+            correlation.average_with,
+            correlation.days_with,
+            correlation.average_without,
+            correlation.days_without,
+            difference
+        ));
+    }
+
+    lines
+}
+
+/// Returns the custom event commands.
+pub fn commands() -> Vec<Command> {
+    vec![event()]
+}
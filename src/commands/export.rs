@@ -0,0 +1,98 @@
+//! Personal data exports (see `crate::export`).
+
+use poise::serenity_prelude as serenity;
+
+use crate::embed::Title;
+use crate::export::{encode_aggregates, ExportError, ExportFormat, Granularity};
+use crate::frontend::{Frontend, Reply};
+use crate::{Context, Error};
+
+use super::Command;
+
+/// Exports your statistics.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, subcommands("export_aggregates"))]
+pub async fn export(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new(
+        "使い方: export aggregates <day|week|month> [csv|parquet]",
+    ))
+    .await
+}
+
+/// Sends you a file of your totals per day, week or month and type by DM.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `granularity` - The length of the periods (`day`, `week` or `month`).
+/// * `format` - The file format (`csv` or `parquet`), CSV if omitted.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "aggregates")]
+pub async fn export_aggregates(
+    ctx: Context<'_>,
+    granularity: Granularity,
+    format: Option<ExportFormat>,
+) -> Result<(), Error> {
+    let format = format.unwrap_or_default();
+
+    let totals = {
+        let db = ctx.data().database.lock().await;
+        db.get_period_type_totals(
+            &ctx.author().id.get().to_string(),
+            granularity,
+            ctx.data().clock.timezone(),
+        )
+        .await?
+    };
+    if totals.is_empty() {
+        return ctx
+            .send_reply(Reply::error("書き出せる記録がありません。"))
+            .await;
+    }
+    let contents = match encode_aggregates(&totals, format) {
+        Ok(contents) => contents,
+        Err(ExportError::ParquetDisabled) => {
+            return ctx
+                .send_reply(Reply::error(
+                    "このボットはParquet形式の書き出しに対応していません。",
+                ))
+                .await;
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let file_name = format!(
+        "smoking-{}-{}.{}",
+        granularity,
+        ctx.data().clock.now().format("%Y%m%d"),
+        format.name()
+    );
+
+    ctx.author()
+        .direct_message(
+            ctx,
+            serenity::CreateMessage::new()
+                .content(format!("{}ごとの集計を書き出しました。", granularity))
+                .add_file(serenity::CreateAttachment::bytes(contents, file_name)),
+        )
+        .await?;
+
+    ctx.send_reply(
+        Reply::new(format!(
+            "集計を書き出しました。{}ファイルをDMで送信しました。",
+            format.name().to_uppercase()
+        ))
+        .titled(Title::Stats),
+    )
+    .await
+}
+
+/// Returns the export commands.
+pub fn commands() -> Vec<Command> {
+    vec![export()]
+}
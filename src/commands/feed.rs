@@ -0,0 +1,130 @@
+//! Personal Atom feeds of milestones and weekly summaries, see `feed`.
+
+use crate::database::Database;
+use crate::frontend::{Frontend, Reply};
+use crate::http::generate_token;
+use crate::{Context, Error};
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+
+use super::Command;
+
+/// Manages your personal feed of milestones and weekly summaries.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, subcommands("feed_url", "feed_off"))]
+pub async fn feed(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new("使い方: feed url / feed off"))
+        .await
+}
+
+/// Issues a new URL of your feed and DMs it to you; the previous URL stops working.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "url")]
+pub async fn feed_url(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(public_url) = ctx.data().config.public_url.clone() else {
+        return ctx
+            .send_reply(Reply::error(
+                "PUBLIC_URL が設定されていないため、フィードを作成できません。",
+            ))
+            .await;
+    };
+
+    let token = issue_feed_token(
+        &ctx.data().database,
+        &ctx.author().id.get().to_string(),
+        &ctx.author().name,
+    )
+    .await?;
+
+    ctx.author()
+        .direct_message(
+            ctx,
+            serenity::CreateMessage::new().content(format!(
+                "フィードのURLです。\n{}/feed/{}\n以前のURLは使えなくなりました。`feed off` で無効にできます。",
+                public_url, token
+            )),
+        )
+        .await?;
+
+    ctx.send_reply(Reply::new("フィードのURLをDMで送信しました。"))
+        .await
+}
+
+/// Disables your feed.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "off")]
+pub async fn feed_off(ctx: Context<'_>) -> Result<(), Error> {
+    disable_feed(
+        &ctx,
+        &ctx.data().database,
+        &ctx.author().id.get().to_string(),
+    )
+    .await
+}
+
+/// Issues a new feed token for a user, replacing any previous one.
+///
+/// # Arguments
+/// * `database` - The database.
+/// * `user_id` - The Discord ID of the user.
+/// * `username` - The current username of the user.
+///
+/// # Returns
+/// A Result containing the plain-text token (shown to the user once), or an `Error`.
+pub async fn issue_feed_token(
+    database: &Mutex<Database>,
+    user_id: &str,
+    username: &str,
+) -> Result<String, Error> {
+    let (token, token_hash) = generate_token();
+    let db = database.lock().await;
+
+    db.get_or_create_user(user_id, username).await?;
+    db.set_feed_token(user_id, &token_hash).await?;
+
+    Ok(token)
+}
+
+/// Disables a user's feed and confirms it.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `user_id` - The Discord ID of the user.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn disable_feed(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    user_id: &str,
+) -> Result<(), Error> {
+    let removed = database.lock().await.remove_feed_token(user_id).await?;
+
+    let reply = if removed {
+        Reply::new("フィードを無効にしました。")
+    } else {
+        Reply::error("フィードは作成されていません。")
+    };
+
+    frontend.send_reply(reply).await
+}
+
+/// Returns the feed commands.
+pub fn commands() -> Vec<Command> {
+    vec![feed()]
+}
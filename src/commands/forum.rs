@@ -0,0 +1,115 @@
+//! Weekly log threads opened in a forum channel.
+
+use crate::embed::Title;
+use crate::frontend::{Frontend, Reply};
+use crate::{Context, Error};
+use poise::serenity_prelude as serenity;
+
+use super::Command;
+
+/// Manages the weekly log threads of this guild.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, guild_only, subcommands("forum_channel", "forum_off"))]
+pub async fn forum(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let channel_id = ctx
+        .data()
+        .database
+        .lock()
+        .await
+        .guild(&guild_id)
+        .get_forum_channel()
+        .await?;
+
+    let status = match channel_id {
+        Some(channel_id) => format!("<#{}> に毎週スレッドを作成しています。", channel_id),
+        None => "週ごとのスレッドは作成していません。".to_string(),
+    };
+    ctx.send_reply(Reply::new(format!(
+        "{}\n使い方: forum channel #フォーラム / forum off",
+        status
+    )))
+    .await
+}
+
+/// Opens a log thread every week in a forum channel, starting this week.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `channel` - The forum channel.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_CHANNELS",
+    rename = "channel"
+)]
+pub async fn forum_channel(ctx: Context<'_>, channel: serenity::GuildChannel) -> Result<(), Error> {
+    if channel.kind != serenity::ChannelType::Forum {
+        return ctx
+            .send_reply(Reply::error("フォーラムチャンネルを指定してください。"))
+            .await;
+    }
+
+    ctx.data()
+        .database
+        .lock()
+        .await
+        .guild(&channel.guild_id.to_string())
+        .set_forum_channel(&channel.id.to_string())
+        .await?;
+
+    ctx.send_reply(
+        Reply::new(format!(
+            "{}に毎週の記録スレッドを作成します。今週のスレッドは1時間以内に作成されます。",
+            channel.name
+        ))
+        .titled(Title::Settings),
+    )
+    .await
+}
+
+/// Stops opening weekly log threads; existing threads are kept.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_CHANNELS",
+    rename = "off"
+)]
+pub async fn forum_off(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let removed = ctx
+        .data()
+        .database
+        .lock()
+        .await
+        .guild(&guild_id)
+        .remove_forum_channel()
+        .await?;
+
+    let reply = if removed {
+        "週ごとのスレッドの作成を停止しました。"
+    } else {
+        "週ごとのスレッドは作成していません。"
+    };
+    ctx.send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Returns the forum thread commands.
+pub fn commands() -> Vec<Command> {
+    vec![forum()]
+}
@@ -0,0 +1,161 @@
+//! Daily goals.
+//!
+//! `set_goal` caps the day's total over every type. When the owners grouped
+//! types into categories (see `type category`), `category_goal` sets a goal
+//! for one category, counted in its unit, e.g. 3 cups of coffee a day.
+
+use crate::database::Database;
+use crate::embed::Title;
+use crate::format::{format_count, format_quantity};
+use crate::frontend::{Frontend, Reply};
+use crate::{Context, Error};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+use super::Command;
+
+/// Sets or clears the author's daily goal (maximum cigarettes per day).
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `goal` - The daily goal, or omitted to clear it.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command)]
+pub async fn set_goal(ctx: Context<'_>, goal: Option<u32>) -> Result<(), Error> {
+    update_daily_goal(
+        &ctx,
+        &ctx.data().database,
+        &ctx.author().id.get().to_string(),
+        &ctx.author().name,
+        goal,
+    )
+    .await
+}
+
+/// Stores a user's daily goal and confirms the change.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `user_id` - The Discord ID of the user.
+/// * `username` - The current username of the user.
+/// * `goal` - The daily goal, or `None` to clear it.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn update_daily_goal(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    user_id: &str,
+    username: &str,
+    goal: Option<u32>,
+) -> Result<(), Error> {
+    let goal = goal.map(i32::try_from).transpose()?;
+
+    {
+        let db = database.lock().await;
+        let user = db.get_or_create_user(user_id, username).await?;
+        db.set_daily_goal(&user.discord_id, goal).await?;
+    }
+
+    let reply = match goal {
+        Some(goal) => format!(
+            "1日の目標を{}に設定しました。",
+            format_count(goal.into(), frontend.locale())
+        ),
+        None => "1日の目標を解除しました。".to_string(),
+    };
+    frontend
+        .send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Sets or clears the author's daily goal for a category.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `category` - The name of the category.
+/// * `goal` - The daily goal in the category's unit, or omitted to clear it.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "category-goal")]
+pub async fn category_goal(
+    ctx: Context<'_>,
+    category: String,
+    goal: Option<u32>,
+) -> Result<(), Error> {
+    update_category_goal(
+        &ctx,
+        &ctx.data().database,
+        &ctx.author().id.get().to_string(),
+        &ctx.author().name,
+        &category,
+        goal,
+    )
+    .await
+}
+
+/// Stores a user's daily goal for a category and confirms the change.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `user_id` - The Discord ID of the user.
+/// * `username` - The current username of the user.
+/// * `category` - The name of the category.
+/// * `goal` - The daily goal in the category's unit, or `None` to clear it.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn update_category_goal(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    user_id: &str,
+    username: &str,
+    category: &str,
+    goal: Option<u32>,
+) -> Result<(), Error> {
+    let goal = goal.map(i32::try_from).transpose()?;
+
+    let reply = {
+        let db = database.lock().await;
+        let categories = db.get_categories().await?;
+        match categories.iter().find(|known| known.name == category) {
+            Some(category) => {
+                let user = db.get_or_create_user(user_id, username).await?;
+                db.set_category_goal(&user.discord_id, category.id, goal)
+                    .await?;
+                let message = match goal {
+                    Some(goal) => format!(
+                        "{}の1日の目標を{}に設定しました。",
+                        category.label(),
+                        format_quantity(goal.into(), &category.unit, frontend.locale())
+                    ),
+                    None => format!("{}の1日の目標を解除しました。", category.label()),
+                };
+                Reply::new(message).titled(Title::Settings)
+            }
+            None if categories.is_empty() => {
+                Reply::error("カテゴリが登録されていません。ボットの管理者に連絡してください。")
+            }
+            None => Reply::error(format!(
+                "不明なカテゴリです: {}（使用できるカテゴリ: {}）",
+                category,
+                categories
+                    .iter()
+                    .map(|known| known.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    };
+
+    frontend.send_reply(reply).await
+}
+
+/// Returns the goal commands.
+pub fn commands() -> Vec<Command> {
+    vec![set_goal(), category_goal()]
+}
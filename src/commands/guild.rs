@@ -0,0 +1,80 @@
+//! Moving a guild's data between bot instances.
+//!
+//! `guild export` DMs the guild owner an archive of the guild's data (see
+//! `crate::guild_archive`). Only the owner may export, since the archive
+//! contains the raw logs of members who chose to share them.
+
+use poise::serenity_prelude as serenity;
+
+use crate::frontend::{Frontend, Reply};
+use crate::guild_archive::export_guild;
+use crate::{Context, Error};
+
+use super::Command;
+
+/// Moves this guild's data to or from another bot instance.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, guild_only, subcommands("guild_export"))]
+pub async fn guild(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new("使い方: guild export")).await
+}
+
+/// Sends the guild owner an archive of this guild's data by DM.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, guild_only, rename = "export")]
+pub async fn guild_export(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?;
+    let owner_id = ctx.partial_guild().await.ok_or("guild not found")?.owner_id;
+    if ctx.author().id != owner_id {
+        return ctx
+            .send_reply(Reply::error(
+                "サーバーのデータを書き出せるのはサーバーの所有者だけです。",
+            ))
+            .await;
+    }
+
+    let now = ctx.data().clock.now();
+    let archive = export_guild(
+        &ctx.data().database,
+        &guild_id.to_string(),
+        ctx.data().clock.timezone(),
+        now,
+    )
+    .await?;
+    let file_name = format!("guild-{}-{}.json", guild_id, now.format("%Y%m%d"));
+
+    ctx.author()
+        .direct_message(
+            ctx,
+            serenity::CreateMessage::new()
+                .content(format!(
+                    "サーバーのデータを書き出しました。生の記録は共有を許可した{}人分だけ含まれます。",
+                    archive.members.len()
+                ))
+                .add_file(serenity::CreateAttachment::bytes(
+                    serde_json::to_vec_pretty(&archive)?,
+                    file_name,
+                )),
+        )
+        .await?;
+
+    ctx.send_reply(Reply::new(
+        "サーバーのデータを書き出しました。ファイルをDMで送信しました。",
+    ))
+    .await
+}
+
+/// Returns the guild data commands.
+pub fn commands() -> Vec<Command> {
+    vec![guild()]
+}
@@ -0,0 +1,1046 @@
+//! Interactive help.
+//!
+//! `help` shows the commands of one category at a time, each with a short
+//! description in the user's language and an example, and a select menu to
+//! switch categories in place. `help <category>` and `help <command>` jump
+//! straight to a page. Command errors carry a help button that opens the
+//! page of the failing command's category.
+//!
+//! Descriptions are kept in `HELP_ENTRIES`; a new command needs an entry
+//! there to show up (the tests check that every command has one). Commands
+//! of disabled modules are left out of the pages.
+
+use poise::serenity_prelude as serenity;
+
+use crate::embed::Title;
+use crate::format::Locale;
+use crate::frontend::{Frontend, InteractionFrontend, Reply};
+use crate::{Context, Data, Error};
+
+use super::{Command, MODULES};
+
+/// Prefix of the custom IDs of help components
+const HELP_PREFIX: &str = "help:";
+
+/// Custom ID suffix of the category select menu
+const MENU: &str = "menu";
+
+/// A group of related commands shown on one help page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HelpCategory {
+    Logging,
+    Stats,
+    Personal,
+    Server,
+    Owner,
+}
+
+impl HelpCategory {
+    /// Every category, in menu order
+    pub const ALL: [Self; 5] = [
+        Self::Logging,
+        Self::Stats,
+        Self::Personal,
+        Self::Server,
+        Self::Owner,
+    ];
+
+    /// Returns the name used in custom IDs and `help <category>`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Logging => "logging",
+            Self::Stats => "stats",
+            Self::Personal => "personal",
+            Self::Server => "server",
+            Self::Owner => "owner",
+        }
+    }
+
+    /// Returns the category label in the locale's language.
+    ///
+    /// # Arguments
+    /// * `locale` - The locale of the user.
+    pub fn label(self, locale: Locale) -> &'static str {
+        match (self, locale) {
+            (Self::Logging, Locale::Japanese) => "記録",
+            (Self::Logging, _) => "Logging",
+            (Self::Stats, Locale::Japanese) => "統計",
+            (Self::Stats, _) => "Statistics",
+            (Self::Personal, Locale::Japanese) => "個人設定",
+            (Self::Personal, _) => "Personal settings",
+            (Self::Server, Locale::Japanese) => "サーバー管理",
+            (Self::Server, _) => "Server management",
+            (Self::Owner, Locale::Japanese) => "ボットのオーナー",
+            (Self::Owner, _) => "Bot owners",
+        }
+    }
+
+    /// Finds a category by name.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the category.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|category| category.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// The help of one command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HelpEntry {
+    /// The qualified command name, e.g. `stats month`
+    pub command: &'static str,
+    /// The page the command is shown on
+    pub category: HelpCategory,
+    /// What the command does, in Japanese
+    pub japanese: &'static str,
+    /// What the command does, in English
+    pub english: &'static str,
+    /// Arguments of an example invocation; empty for commands without arguments
+    pub example: &'static str,
+}
+
+impl HelpEntry {
+    /// Returns the description in the locale's language; English outside Japanese.
+    ///
+    /// # Arguments
+    /// * `locale` - The locale of the user.
+    pub fn description(&self, locale: Locale) -> &'static str {
+        match locale {
+            Locale::Japanese => self.japanese,
+            _ => self.english,
+        }
+    }
+
+    /// Returns the top-level command the entry belongs to.
+    pub fn top_level(&self) -> &'static str {
+        self.command.split(' ').next().unwrap_or(self.command)
+    }
+}
+
+/// Shorthand for a help entry
+const fn entry(
+    command: &'static str,
+    category: HelpCategory,
+    japanese: &'static str,
+    english: &'static str,
+    example: &'static str,
+) -> HelpEntry {
+    HelpEntry {
+        command,
+        category,
+        japanese,
+        english,
+        example,
+    }
+}
+
+/// The help of every command, in the order shown on the pages
+pub const HELP_ENTRIES: &[HelpEntry] = {
+    use HelpCategory::*;
+
+    &[
+        entry(
+            "create_cigarette_ui",
+            Logging,
+            "記録用のパネルを作成します。",
+            "Creates a panel to log with.",
+            "",
+        ),
+        entry(
+            "panel install",
+            Logging,
+            "このチャンネルにパネルを設置します。種類を絞り込めます。",
+            "Installs a panel in this channel, optionally limited to some types.",
+            "traditional iqos",
+        ),
+        entry(
+            "log",
+            Logging,
+            "種類と本数を指定して記録します。多い本数は確認してから記録します。",
+            "Logs a quantity of a type; unusually large ones are confirmed first.",
+            "traditional 3",
+        ),
+        entry(
+            "undo",
+            Logging,
+            "直前の記録を取り消し、その日の累計を表示し直します。記録の「取り消し」ボタンと同じです。",
+            "Deletes your most recent log and shows the corrected daily totals, like the undo button on confirmations.",
+            "",
+        ),
+        entry(
+            "register_device",
+            Logging,
+            "HTTP API用の端末を登録し、トークンをDMで送ります。",
+            "Registers a device for the HTTP API and DMs you its token.",
+            "watch",
+        ),
+        entry(
+            "create_shortcut",
+            Logging,
+            "開くと1本記録するショートカットリンク（QRコード・NFC用）を作成します。",
+            "Creates a shortcut link (for a QR code or NFC tag) that logs one unit when opened.",
+            "traditional",
+        ),
+        entry(
+            "cleanup",
+            Logging,
+            "誤って重複した記録を探して整理します。",
+            "Finds and cleans up accidentally duplicated logs.",
+            "",
+        ),
+        entry(
+            "stats month",
+            Stats,
+            "今月の日ごとの合計を表示します。",
+            "Shows your daily totals this month.",
+            "",
+        ),
+        entry(
+            "stats guild",
+            Stats,
+            "このサーバーの今月の種類ごとの合計を表示します。",
+            "Shows this server's totals per type this month.",
+            "",
+        ),
+        entry(
+            "stats group",
+            Stats,
+            "このDM・グループでのパネル記録の合計を表示します。",
+            "Shows the totals of panel presses in this DM or group.",
+            "",
+        ),
+        entry(
+            "stats weekly",
+            Stats,
+            "今週の種類ごとの合計と1日平均を先週と比べて表示します。",
+            "Shows your totals per type and daily average this week against last week.",
+            "",
+        ),
+        entry(
+            "stats monthly",
+            Stats,
+            "今月の種類ごとの合計と1日平均を先月と比べて表示します。",
+            "Shows your totals per type and daily average this month against last month.",
+            "",
+        ),
+        entry(
+            "compare-periods",
+            Stats,
+            "2つの期間の本数を比較します。",
+            "Compares your totals between two periods.",
+            "golden-week last-month",
+        ),
+        entry(
+            "leaderboard most-improved",
+            Stats,
+            "直近の週または月に、前の期間より減らした割合が大きいメンバーの順に並べます。",
+            "Ranks members by how much they cut down against the period before, largest reduction first.",
+            "month",
+        ),
+        entry(
+            "leaderboard least-improved",
+            Stats,
+            "直近の週または月に、前の期間より減らした割合が小さいメンバーの順に並べます。",
+            "Ranks members by how much they cut down against the period before, smallest reduction first.",
+            "week",
+        ),
+        entry(
+            "event add",
+            Stats,
+            "日にカスタムイベントのタグを付けます。",
+            "Tags a day with a custom event.",
+            "exam 2025-02-01",
+        ),
+        entry(
+            "event remove",
+            Stats,
+            "日からイベントのタグを外します。",
+            "Removes an event tag from a day.",
+            "exam 2025-02-01",
+        ),
+        entry(
+            "event analysis",
+            Stats,
+            "イベントのある日とない日の平均を比べます。",
+            "Compares your daily average on days with and without each event.",
+            "",
+        ),
+        entry(
+            "export aggregates",
+            Stats,
+            "期間・種類ごとの集計をファイルでDMに送ります。",
+            "DMs you your totals per period and type as a file.",
+            "week csv",
+        ),
+        entry(
+            "settings",
+            Personal,
+            "自分の設定を表示し、ボタンで変更します。",
+            "Shows your settings with controls to change them.",
+            "",
+        ),
+        entry(
+            "set_goal",
+            Personal,
+            "1日の目標本数を設定します。省略すると解除します。",
+            "Sets your daily goal, or clears it when omitted.",
+            "10",
+        ),
+        entry(
+            "category-goal",
+            Personal,
+            "カテゴリ（コーヒーなど）ごとの1日の目標を設定します。省略すると解除します。",
+            "Sets your daily goal for a category (e.g. coffee), or clears it when omitted.",
+            "coffee 3",
+        ),
+        entry(
+            "feed url",
+            Personal,
+            "マイルストーンと週ごとのまとめのAtomフィードのURLをDMで送ります。以前のURLは使えなくなります。",
+            "DMs you the URL of an Atom feed of your milestones and weekly summaries; the previous URL stops working.",
+            "",
+        ),
+        entry(
+            "feed off",
+            Personal,
+            "フィードを無効にします。",
+            "Disables your feed.",
+            "",
+        ),
+        entry(
+            "email set",
+            Personal,
+            "毎週のまとめを送るメールアドレスを登録します。届いた確認メールのリンクを開くと配信が始まります。",
+            "Subscribes an address to a weekly summary email; it starts once you open the link in the verification email.",
+            "you@example.com",
+        ),
+        entry(
+            "email off",
+            Personal,
+            "まとめメールの配信を停止します。",
+            "Stops the weekly summary emails.",
+            "",
+        ),
+        entry(
+            "push ntfy",
+            Personal,
+            "マイルストーンなどの通知をDMの代わりにntfyのトピックで受け取ります。",
+            "Sends your notifications, such as milestones, to an ntfy topic instead of DMs.",
+            "my-secret-topic",
+        ),
+        entry(
+            "push pushover",
+            Personal,
+            "マイルストーンなどの通知をDMの代わりにPushoverで受け取ります。",
+            "Sends your notifications, such as milestones, through Pushover instead of DMs.",
+            "uQiRzpo4DXghDmr9QzzfQu27cmVRsG",
+        ),
+        entry(
+            "push off",
+            Personal,
+            "通知をDMで受け取るように戻します。",
+            "Sends your notifications as DMs again.",
+            "",
+        ),
+        entry(
+            "notify",
+            Personal,
+            "マイルストーンなどの通知先（dm / email / ntfy / pushover）を表示・変更します。届かない場合はDMで送ります。",
+            "Shows or chooses where your notifications, such as milestones, are sent (dm / email / ntfy / pushover); DMs are used when it fails.",
+            "ntfy",
+        ),
+        entry(
+            "pause-tracking",
+            Personal,
+            "指定した日まで記録を一時停止します。",
+            "Pauses tracking until a date.",
+            "2025-02-01",
+        ),
+        entry(
+            "resume-tracking",
+            Personal,
+            "一時停止を早めに終えます。",
+            "Resumes tracking before the pause ends.",
+            "",
+        ),
+        entry(
+            "ignore-caps on",
+            Personal,
+            "サーバーの1日の上限を超えても記録します。",
+            "Logs past the server's daily caps.",
+            "",
+        ),
+        entry(
+            "ignore-caps off",
+            Personal,
+            "サーバーの1日の上限に再び従います。",
+            "Follows the server's daily caps again.",
+            "",
+        ),
+        entry(
+            "smoke_break on",
+            Personal,
+            "喫煙所のボイスチャンネルに入ったときにパネルを受け取ります。",
+            "Sends you a panel when you join a smoke-break voice channel.",
+            "",
+        ),
+        entry(
+            "smoke_break off",
+            Personal,
+            "喫煙所でのパネル送信を止めます。",
+            "Stops sending you panels in smoke-break channels.",
+            "",
+        ),
+        entry(
+            "leaderboard smoke-free join",
+            Personal,
+            "禁煙ボードに参加し、連続で記録のない日数をサーバーのメンバーと比べます。",
+            "Joins the smoke-free board, ranking your smoke-free streak against other members.",
+            "",
+        ),
+        entry(
+            "leaderboard smoke-free leave",
+            Personal,
+            "禁煙ボードから退出します。",
+            "Leaves the smoke-free board.",
+            "",
+        ),
+        entry(
+            "bet",
+            Personal,
+            "メンバーに記録本数の勝負を申し込みます（賭け金はありません）。",
+            "Proposes a friendly wager on tracked counts to a member (no money involved).",
+            "@friend lowest-count week",
+        ),
+        entry(
+            "settings timezone",
+            Server,
+            "このサーバーで日付の区切りに使うタイムゾーンを表示・設定します。",
+            "Shows or sets the time zone this server's days are counted in.",
+            "Asia/Tokyo",
+        ),
+        entry(
+            "settings setup",
+            Server,
+            "サーバーの初期設定ウィザードを表示します。",
+            "Posts the server setup wizard.",
+            "",
+        ),
+        entry(
+            "roles map",
+            Server,
+            "禁煙日数の節目にロールを割り当てます。",
+            "Maps a role to a smoke-free milestone.",
+            "7days @SmokeFreeWeek",
+        ),
+        entry(
+            "roles unmap",
+            Server,
+            "節目ロールの割り当てを外します。",
+            "Removes a milestone role mapping.",
+            "@SmokeFreeWeek",
+        ),
+        entry(
+            "roles list",
+            Server,
+            "節目ロールの一覧を表示します。",
+            "Lists the milestone roles.",
+            "",
+        ),
+        entry(
+            "smoke_break channel",
+            Server,
+            "ボイスチャンネルを喫煙所に指定します。",
+            "Designates a voice channel as a smoke-break channel.",
+            "#喫煙所",
+        ),
+        entry(
+            "smoke_break unset",
+            Server,
+            "喫煙所の指定を外します。",
+            "Removes a smoke-break channel designation.",
+            "#喫煙所",
+        ),
+        entry(
+            "forum channel",
+            Server,
+            "フォーラムチャンネルに毎週の記録スレッドを作成します。",
+            "Opens a weekly log thread in a forum channel.",
+            "#記録",
+        ),
+        entry(
+            "forum off",
+            Server,
+            "毎週の記録スレッドの作成を止めます。",
+            "Stops opening weekly log threads.",
+            "",
+        ),
+        entry(
+            "counter set",
+            Server,
+            "チャンネルのトピック（ボイスチャンネルは名前）にサーバーの今日の合計か最後の1本からの日数を表示します。",
+            "Shows the server's daily total or days since the last cigarette in a channel's topic, or a voice channel's name.",
+            "daily-total #喫煙所",
+        ),
+        entry(
+            "counter off",
+            Server,
+            "チャンネルのカウンターの更新を止めます。",
+            "Stops updating the channel counter.",
+            "",
+        ),
+        entry(
+            "challenge start",
+            Server,
+            "前の週より一定の割合だけ減らすサーバー全体の週間チャレンジを始めます。",
+            "Starts a weekly challenge to log a percentage less than the week before.",
+            "20 #チャレンジ",
+        ),
+        entry(
+            "challenge off",
+            Server,
+            "週間チャレンジを終了します。",
+            "Stops the weekly challenge.",
+            "",
+        ),
+        entry(
+            "leaderboard start",
+            Server,
+            "月ごとまたは四半期ごとのランキングのシーズンを始めます。終わったシーズンの順位は保存され、上位が発表されます。",
+            "Starts monthly or quarterly leaderboard seasons; finished seasons are archived and their winners announced.",
+            "month #ランキング",
+        ),
+        entry(
+            "leaderboard off",
+            Server,
+            "ランキングのシーズンを終了します。",
+            "Stops leaderboard seasons.",
+            "",
+        ),
+        entry(
+            "achievement create",
+            Server,
+            "条件を満たしたメンバーが獲得できるサーバー独自の実績を作成します。",
+            "Defines a custom achievement members earn by meeting a condition.",
+            "節煙週間 weekly_total<=10",
+        ),
+        entry(
+            "achievement delete",
+            Server,
+            "実績と、その獲得記録を削除します。",
+            "Deletes a custom achievement and who earned it.",
+            "節煙週間",
+        ),
+        entry(
+            "templates set",
+            Server,
+            "メッセージのテンプレートを上書きします。",
+            "Overrides a message template.",
+            "confirmation {user}さん、{total}本目です",
+        ),
+        entry(
+            "templates reset",
+            Server,
+            "テンプレートを既定に戻します。",
+            "Restores the default of a message template.",
+            "confirmation",
+        ),
+        entry(
+            "templates list",
+            Server,
+            "テンプレートとプレースホルダーの一覧を表示します。",
+            "Lists the message templates and their placeholders.",
+            "",
+        ),
+        entry(
+            "age_gate on",
+            Server,
+            "パネルを使う前にメッセージへの同意を求めます。",
+            "Requires members to acknowledge a message before using the panel.",
+            "20歳以上の方のみ利用できます。",
+        ),
+        entry(
+            "age_gate off",
+            Server,
+            "同意の確認をやめます。",
+            "Stops requiring an acknowledgment.",
+            "",
+        ),
+        entry(
+            "cap set",
+            Server,
+            "種類ごとに1日に記録できる上限を設定します。",
+            "Limits how many units of a type members may log per day.",
+            "traditional 20",
+        ),
+        entry(
+            "cap remove",
+            Server,
+            "1日の上限を外します。",
+            "Removes the daily cap of a type.",
+            "traditional",
+        ),
+        entry(
+            "cap list",
+            Server,
+            "1日の上限の一覧を表示します。",
+            "Lists the daily caps.",
+            "",
+        ),
+        entry(
+            "reaction_mode on",
+            Server,
+            "パネルの記録をメッセージの代わりにサーバーの絵文字のリアクションで知らせます。",
+            "Confirms panel presses with a reaction of a server emoji instead of a message.",
+            ":smoking:",
+        ),
+        entry(
+            "reaction_mode off",
+            Server,
+            "メッセージでの確認に戻します。",
+            "Confirms panel presses with a message again.",
+            "",
+        ),
+        entry(
+            "confirmation_cleanup on",
+            Server,
+            "パネルの確認メッセージを指定した時間が経ったら削除します。",
+            "Deletes panel confirmations once they are older than a number of hours.",
+            "24",
+        ),
+        entry(
+            "confirmation_cleanup off",
+            Server,
+            "パネルの確認メッセージを削除しないようにします。",
+            "Keeps panel confirmations again.",
+            "",
+        ),
+        entry(
+            "guild export",
+            Server,
+            "サーバーのデータのアーカイブをオーナーにDMで送ります。",
+            "DMs the server owner an archive of its data.",
+            "",
+        ),
+        entry(
+            "uptime",
+            Server,
+            "ボットの稼働時間と応答状況を表示します。",
+            "Shows the bot's uptime and health.",
+            "",
+        ),
+        entry(
+            "help",
+            Logging,
+            "このヘルプを表示します。カテゴリやコマンドを指定できます。",
+            "Shows this help, optionally for a category or command.",
+            "stats month",
+        ),
+        entry(
+            "type preset list",
+            Owner,
+            "用意された種類のプリセットを表示します。",
+            "Lists the curated type presets.",
+            "",
+        ),
+        entry(
+            "type preset apply",
+            Owner,
+            "プリセットの種類を追加します。",
+            "Adds the types of a preset.",
+            "japanese",
+        ),
+        entry(
+            "type merge",
+            Owner,
+            "種類を別の種類に統合します。",
+            "Merges a smoking type into another.",
+            "menthol traditional",
+        ),
+        entry(
+            "type conversion set",
+            Owner,
+            "種類の単位どうしの換算の目安を登録します。",
+            "Sets the conversion hint between the units of two types.",
+            "traditional vape 20",
+        ),
+        entry(
+            "type conversion remove",
+            Owner,
+            "換算の目安を削除します。",
+            "Removes a conversion hint.",
+            "traditional vape",
+        ),
+        entry(
+            "type conversion list",
+            Owner,
+            "換算の目安を一覧表示します。",
+            "Lists the conversion hints.",
+            "",
+        ),
+        entry(
+            "type category add",
+            Owner,
+            "単位を持つカテゴリ（コーヒーなど）を追加します。",
+            "Adds a category (e.g. coffee) counted in a unit.",
+            "coffee 杯 ☕",
+        ),
+        entry(
+            "type category remove",
+            Owner,
+            "カテゴリを削除します。その種類はカテゴリなしになります。",
+            "Removes a category, leaving its types uncategorized.",
+            "coffee",
+        ),
+        entry(
+            "type category assign",
+            Owner,
+            "種類をカテゴリに入れます。省略するとカテゴリなしにします。",
+            "Moves a type into a category, or out of any when omitted.",
+            "latte coffee",
+        ),
+        entry(
+            "type category list",
+            Owner,
+            "カテゴリとその種類を一覧表示します。",
+            "Lists the categories with their types.",
+            "",
+        ),
+        entry(
+            "admin explain",
+            Owner,
+            "名前付きクエリの実行計画を表示します。",
+            "Shows the query plan of a named query.",
+            "daily_summary",
+        ),
+        entry(
+            "admin reassign",
+            Owner,
+            "記録の種類を変更します。",
+            "Changes the smoking type of a log.",
+            "42 iqos",
+        ),
+        entry(
+            "admin shift",
+            Owner,
+            "ユーザーの全記録を時間単位でずらします。",
+            "Shifts every log of a user by a number of hours.",
+            "@user -9",
+        ),
+        entry(
+            "admin recompute",
+            Owner,
+            "ユーザーの集計と節目ロールを再計算します。",
+            "Recomputes a user's aggregates and milestone roles.",
+            "@user",
+        ),
+        entry(
+            "admin duplicates",
+            Owner,
+            "重複記録のあるユーザーを一覧し、整理します。",
+            "Reports users with duplicate logs, or cleans up one user's.",
+            "@user",
+        ),
+        entry(
+            "admin audit",
+            Owner,
+            "最近のデータ修正を表示します。",
+            "Lists the most recent data fixes.",
+            "",
+        ),
+        entry(
+            "admin jobs",
+            Owner,
+            "完了していないジョブを表示します。",
+            "Lists the scheduled jobs that have not finished.",
+            "",
+        ),
+        entry(
+            "admin cancel-job",
+            Owner,
+            "開始前のジョブを取り消します。",
+            "Cancels a scheduled job that has not started.",
+            "12",
+        ),
+        entry(
+            "admin import",
+            Owner,
+            "添付したサーバーのアーカイブを取り込みます。",
+            "Imports an attached server archive.",
+            "",
+        ),
+        entry(
+            "admin sync-commands",
+            Owner,
+            "アプリケーションコマンドを再登録します。",
+            "Re-registers the application commands.",
+            "",
+        ),
+        entry(
+            "admin shards",
+            Owner,
+            "シャードごとの遅延、サーバー数、最後のハートビートを表示します。",
+            "Shows each shard's latency, server count and last heartbeat.",
+            "",
+        ),
+        entry(
+            "register",
+            Owner,
+            "スラッシュコマンドをこのサーバー、または global でグローバルに登録します。",
+            "Registers the slash commands in this server, or globally with global.",
+            "global",
+        ),
+    ]
+};
+
+/// Finds the help of a command.
+///
+/// # Arguments
+/// * `command` - The qualified command name, e.g. `stats month`.
+pub fn find_entry(command: &str) -> Option<&'static HelpEntry> {
+    HELP_ENTRIES
+        .iter()
+        .find(|entry| entry.command.eq_ignore_ascii_case(command.trim()))
+}
+
+/// Returns the category a command is shown in, falling back to its
+/// top-level command's for parents without an entry of their own.
+///
+/// # Arguments
+/// * `command` - The qualified command name.
+pub fn category_of(command: &str) -> Option<HelpCategory> {
+    find_entry(command)
+        .or_else(|| {
+            let top_level = command.split(' ').next()?;
+            HELP_ENTRIES
+                .iter()
+                .find(|entry| entry.top_level() == top_level)
+        })
+        .map(|entry| entry.category)
+}
+
+/// A help component: the category menu, or a button opening a category
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HelpId {
+    /// The select menu switching the shown category
+    Menu,
+    /// A button opening the page of a category
+    Category(HelpCategory),
+}
+
+impl HelpId {
+    /// Encodes the custom ID of the component.
+    pub fn custom_id(&self) -> String {
+        match self {
+            Self::Menu => format!("{}{}", HELP_PREFIX, MENU),
+            Self::Category(category) => format!("{}{}", HELP_PREFIX, category.name()),
+        }
+    }
+
+    /// Decodes the custom ID of a help component.
+    ///
+    /// # Arguments
+    /// * `custom_id` - The custom ID of a used component.
+    ///
+    /// # Returns
+    /// The component, or `None` if it is not a help component.
+    pub fn parse(custom_id: &str) -> Option<Self> {
+        match custom_id.strip_prefix(HELP_PREFIX)? {
+            MENU => Some(Self::Menu),
+            name => HelpCategory::from_name(name).map(Self::Category),
+        }
+    }
+}
+
+/// Formats one command of a help page.
+fn entry_lines(entry: &HelpEntry, prefix: &str, locale: Locale) -> String {
+    let mut lines = format!(
+        "**`{}{}`**\n{}",
+        prefix,
+        entry.command,
+        entry.description(locale)
+    );
+    if !entry.example.is_empty() {
+        let label = match locale {
+            Locale::Japanese => "例",
+            _ => "Example",
+        };
+        lines.push_str(&format!(
+            "\n{}: `{}{} {}`",
+            label, prefix, entry.command, entry.example
+        ));
+    }
+
+    lines
+}
+
+/// Builds the help page of a category with the menu to switch categories.
+///
+/// # Arguments
+/// * `category` - The category shown.
+/// * `prefix` - The command prefix shown in examples.
+/// * `enabled` - The names of the registered top-level commands.
+/// * `locale` - The locale of the user.
+///
+/// # Returns
+/// The page.
+pub fn help_page(
+    category: HelpCategory,
+    prefix: &str,
+    enabled: &[String],
+    locale: Locale,
+) -> Reply {
+    let entries: Vec<String> = HELP_ENTRIES
+        .iter()
+        .filter(|entry| entry.category == category)
+        .filter(|entry| enabled.iter().any(|name| name == entry.top_level()))
+        .map(|entry| entry_lines(entry, prefix, locale))
+        .collect();
+    let body = if entries.is_empty() {
+        match locale {
+            Locale::Japanese => "このカテゴリのコマンドは無効になっています。".to_string(),
+            _ => "The commands of this category are disabled.".to_string(),
+        }
+    } else {
+        entries.join("\n\n")
+    };
+    let placeholder = match locale {
+        Locale::Japanese => "カテゴリを選ぶ",
+        _ => "Choose a category",
+    };
+    let options = HelpCategory::ALL
+        .into_iter()
+        .map(|category| {
+            (
+                category.name().to_string(),
+                category.label(locale).to_string(),
+            )
+        })
+        .collect();
+
+    Reply::new(format!("**{}**\n\n{}", category.label(locale), body))
+        .titled(Title::Help)
+        .select(HelpId::Menu.custom_id(), placeholder, options)
+}
+
+/// Adds a button opening the help of a command's category, if it has one.
+///
+/// # Arguments
+/// * `reply` - The reply, typically an error.
+/// * `command` - The qualified name of the command the reply is about.
+/// * `locale` - The locale of the user.
+///
+/// # Returns
+/// The reply with the button.
+pub fn with_help_button(reply: Reply, command: &str, locale: Locale) -> Reply {
+    let Some(category) = category_of(command) else {
+        return reply;
+    };
+    let label = match locale {
+        Locale::Japanese => "ヘルプを見る",
+        _ => "Help",
+    };
+
+    reply.button(HelpId::Category(category).custom_id(), label)
+}
+
+/// Returns the names of the top-level commands of the enabled modules.
+fn enabled_names(disabled_modules: &[String]) -> Vec<String> {
+    MODULES
+        .iter()
+        .filter(|module| !disabled_modules.iter().any(|name| name == module.name))
+        .flat_map(|module| (module.commands)())
+        .map(|command| command.name)
+        .collect()
+}
+
+/// Shows the commands by category, or the help of one category or command.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `query` - A category or command name, if any.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command)]
+pub async fn help(ctx: Context<'_>, #[rest] query: Option<String>) -> Result<(), Error> {
+    let locale = Frontend::locale(&ctx);
+    let prefix = ctx.data().config.shown_prefix();
+    let enabled = enabled_names(&ctx.data().config.disabled_modules);
+    let query = query.unwrap_or_default();
+    let query = query.trim();
+
+    if let Some(entry) = find_entry(query) {
+        if enabled.iter().any(|name| name == entry.top_level()) {
+            return ctx
+                .send_reply(
+                    with_help_button(
+                        Reply::new(entry_lines(entry, prefix, locale)),
+                        entry.command,
+                        locale,
+                    )
+                    .titled(Title::Help),
+                )
+                .await;
+        }
+    }
+
+    let category = HelpCategory::from_name(query)
+        .or_else(|| category_of(query))
+        .unwrap_or(HelpCategory::Logging);
+    ctx.send_reply(help_page(category, prefix, &enabled, locale))
+        .await
+}
+
+/// Handles a use of a help component: the menu redraws the page in place,
+/// a button answers with the category's page only the presser can see.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `mci` - The component interaction.
+/// * `control` - The component.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub(crate) async fn handle_help(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &serenity::ComponentInteraction,
+    control: &HelpId,
+) -> Result<(), Error> {
+    let frontend = InteractionFrontend::new(ctx, mci);
+    let locale = frontend.locale();
+    let enabled = enabled_names(&data.config.disabled_modules);
+    let prefix = data.config.shown_prefix();
+
+    match control {
+        HelpId::Menu => {
+            let category = match &mci.data.kind {
+                serenity::ComponentInteractionDataKind::StringSelect { values } => values
+                    .first()
+                    .and_then(|name| HelpCategory::from_name(name)),
+                _ => None,
+            };
+            // Anything but an offered option is a forged or outdated menu and is ignored.
+            let Some(category) = category else {
+                return frontend.acknowledge().await;
+            };
+            frontend
+                .update(help_page(category, prefix, &enabled, locale))
+                .await
+        }
+        HelpId::Category(category) => {
+            frontend
+                .respond(help_page(*category, prefix, &enabled, locale).ephemeral())
+                .await
+        }
+    }
+}
+
+/// Returns the help commands.
+pub fn commands() -> Vec<Command> {
+    vec![help()]
+}
@@ -0,0 +1,453 @@
+//! Leaderboard seasons of the guild; see `crate::seasons`.
+
+use poise::serenity_prelude as serenity;
+
+use crate::clock::Clock;
+use crate::database::GuildScope;
+use crate::embed::Title;
+use crate::frontend::{Frontend, Reply};
+use crate::improvement::{
+    comparison_heading, improvement_lines, rank_improvements, ImprovementOrder, ImprovementPeriod,
+};
+use crate::rollover::start_of_day;
+use crate::seasons::{rank, season_heading, standing_lines, SeasonLength, MAX_SHOWN};
+use crate::smoke_free::{rank_streaks, streak_lines};
+use crate::{Context, Error};
+
+use super::Command;
+
+/// Reads the season asked for, accepting an optional `season:` prefix.
+///
+/// # Arguments
+/// * `input` - The season as typed, e.g. `season:3`.
+///
+/// # Returns
+/// The number of the season, or `None` if it is not a number.
+pub fn parse_season(input: &str) -> Option<i32> {
+    let input = input.trim();
+    input
+        .strip_prefix("season:")
+        .unwrap_or(input)
+        .trim()
+        .trim_start_matches('#')
+        .parse()
+        .ok()
+}
+
+/// Builds the standings of the running season, or of an archived one.
+///
+/// # Arguments
+/// * `guild` - The guild.
+/// * `clock` - The clock deciding how far the running season has come.
+/// * `season` - The season as typed (e.g. `season:3`); the running season if `None`.
+///
+/// # Returns
+/// A Result containing the reply, an error reply for unknown seasons, or an `Error`.
+pub async fn standings_reply(
+    guild: &GuildScope<'_>,
+    clock: &dyn Clock,
+    season: Option<&str>,
+) -> Result<Reply, Error> {
+    let (season, standings) = match season {
+        Some(input) => {
+            let Some(number) = parse_season(input) else {
+                return Ok(Reply::error(
+                    "シーズンは番号で指定してください（例: season:3）。",
+                ));
+            };
+            match guild.get_season(number).await? {
+                Some(season) if season.archived_at.is_some() => {
+                    let standings = guild.get_season_standings(season.id).await?;
+                    (season, standings)
+                }
+                Some(_) => {
+                    return Ok(Reply::error(format!(
+                        "シーズン #{} は開催中です。開催中の順位は leaderboard で確認できます。",
+                        number
+                    )))
+                }
+                None => {
+                    return Ok(Reply::error(format!(
+                        "シーズン #{} は見つかりません。",
+                        number
+                    )))
+                }
+            }
+        }
+        None => {
+            let Some(season) = guild.get_open_season().await? else {
+                return Ok(Reply::new(
+                    "開催中のシーズンはありません。\n使い方: leaderboard start <month|quarter> #チャンネル / leaderboard off / leaderboard season:<番号>",
+                ));
+            };
+            let timezone = guild.get_timezone().await?.unwrap_or(clock.timezone());
+            let totals = guild
+                .get_member_totals(start_of_day(timezone, season.starts_on), clock.now())
+                .await?;
+            (season, rank(&totals))
+        }
+    };
+    let archived = guild.get_archived_seasons().await?;
+
+    let mut lines = vec![format!(
+        "{}{}",
+        season_heading(&season),
+        if season.archived_at.is_some() {
+            "の最終順位"
+        } else {
+            "の順位（開催中）"
+        }
+    )];
+    if standings.is_empty() {
+        lines.push("記録したメンバーはいません。".to_string());
+    }
+    lines.extend(standing_lines(&standings));
+    if !archived.is_empty() {
+        let numbers: Vec<String> = archived
+            .iter()
+            .map(|season| format!("#{}", season.number))
+            .collect();
+        lines.push(format!(
+            "過去のシーズン: {}（leaderboard season:<番号>）",
+            numbers.join(", ")
+        ));
+    }
+
+    Ok(Reply::new(lines.join("\n")).titled(Title::Leaderboard))
+}
+
+/// Shows the standings of the running season, or of an archived one with `season:<番号>`.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `season` - The number of an archived season; the running season if omitted.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    subcommands(
+        "leaderboard_most_improved",
+        "leaderboard_least_improved",
+        "leaderboard_smoke_free",
+        "leaderboard_start",
+        "leaderboard_off"
+    )
+)]
+pub async fn leaderboard(ctx: Context<'_>, season: Option<String>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let reply = {
+        let db = ctx.data().database.lock().await;
+        standings_reply(
+            &db.guild(&guild_id),
+            ctx.data().clock.as_ref(),
+            season.as_deref(),
+        )
+        .await?
+    };
+
+    ctx.send_reply(reply).await
+}
+
+/// Builds the ranking of members by their reduction between the last two completed periods.
+///
+/// # Arguments
+/// * `guild` - The guild.
+/// * `clock` - The clock deciding which periods are completed.
+/// * `period` - The period as typed (`week` or `month`); a week if `None`.
+/// * `order` - Which end of the ranking comes first.
+///
+/// # Returns
+/// A Result containing the reply, an error reply for unknown periods, or an `Error`.
+pub async fn improvement_reply(
+    guild: &GuildScope<'_>,
+    clock: &dyn Clock,
+    period: Option<&str>,
+    order: ImprovementOrder,
+) -> Result<Reply, Error> {
+    let Some(period) = ImprovementPeriod::from_name(period.unwrap_or("week")) else {
+        let names: Vec<&str> = ImprovementPeriod::ALL
+            .iter()
+            .map(|period| period.name())
+            .collect();
+        return Ok(Reply::error(format!(
+            "期間を指定してください: {}",
+            names.join(", ")
+        )));
+    };
+
+    let timezone = guild.get_timezone().await?.unwrap_or(clock.timezone());
+    let today = clock.now().with_timezone(&timezone).date_naive();
+    let (previous_start, current_start, current_end) = period.completed(today);
+    let totals = guild
+        .get_member_period_totals(
+            start_of_day(timezone, previous_start),
+            start_of_day(timezone, current_start),
+            start_of_day(timezone, current_end),
+        )
+        .await?;
+    let improvements = rank_improvements(&totals, order);
+
+    let mut lines = vec![format!(
+        "{}（{}）",
+        comparison_heading(period, today),
+        match order {
+            ImprovementOrder::Most => "減らした割合が大きい順",
+            ImprovementOrder::Least => "減らした割合が小さい順",
+        }
+    )];
+    if improvements.is_empty() {
+        lines.push("比べられる記録のあるメンバーはいません。".to_string());
+    }
+    lines.extend(improvement_lines(&improvements, MAX_SHOWN));
+    lines.push("前の期間に記録のないメンバーは含まれません。".to_string());
+
+    Ok(Reply::new(lines.join("\n")).titled(Title::Leaderboard))
+}
+
+/// Ranks members by how much they cut down, largest reduction first, e.g. `leaderboard most-improved month`.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `period` - The period compared with the one before (`week` or `month`); a week if omitted.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, guild_only, rename = "most-improved")]
+pub async fn leaderboard_most_improved(
+    ctx: Context<'_>,
+    period: Option<String>,
+) -> Result<(), Error> {
+    send_improvement(ctx, period, ImprovementOrder::Most).await
+}
+
+/// Ranks members by how much they cut down, smallest reduction first.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `period` - The period compared with the one before (`week` or `month`); a week if omitted.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, guild_only, rename = "least-improved")]
+pub async fn leaderboard_least_improved(
+    ctx: Context<'_>,
+    period: Option<String>,
+) -> Result<(), Error> {
+    send_improvement(ctx, period, ImprovementOrder::Least).await
+}
+
+/// Sends a ranking by reduction for the improvement subcommands.
+async fn send_improvement(
+    ctx: Context<'_>,
+    period: Option<String>,
+    order: ImprovementOrder,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let reply = {
+        let db = ctx.data().database.lock().await;
+        improvement_reply(
+            &db.guild(&guild_id),
+            ctx.data().clock.as_ref(),
+            period.as_deref(),
+            order,
+        )
+        .await?
+    };
+
+    ctx.send_reply(reply).await
+}
+
+/// Builds the guild's smoke-free board.
+///
+/// # Arguments
+/// * `guild` - The guild.
+/// * `clock` - The clock deciding the current day.
+///
+/// # Returns
+/// A Result containing the reply or an `Error`.
+pub async fn smoke_free_reply(guild: &GuildScope<'_>, clock: &dyn Clock) -> Result<Reply, Error> {
+    let timezone = guild.get_timezone().await?.unwrap_or(clock.timezone());
+    let today = clock.now().with_timezone(&timezone).date_naive();
+    let streaks = rank_streaks(&guild.get_smoke_free_board().await?, today, timezone);
+
+    let mut lines = vec!["禁煙ボード（連続で記録のない日数）".to_string()];
+    if streaks.is_empty() {
+        lines.push("参加しているメンバーはいません。".to_string());
+    }
+    lines.extend(streak_lines(&streaks));
+    lines
+        .push("参加: leaderboard smoke-free join / 退出: leaderboard smoke-free leave".to_string());
+
+    Ok(Reply::new(lines.join("\n")).titled(Title::Leaderboard))
+}
+
+/// Shows the smoke-free board, ranking the members who joined it by their smoke-free streak.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    rename = "smoke-free",
+    subcommands("smoke_free_join", "smoke_free_leave")
+)]
+pub async fn leaderboard_smoke_free(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let reply = {
+        let db = ctx.data().database.lock().await;
+        smoke_free_reply(&db.guild(&guild_id), ctx.data().clock.as_ref()).await?
+    };
+
+    ctx.send_reply(reply).await
+}
+
+/// Joins the smoke-free board of the guild.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, guild_only, rename = "join")]
+pub async fn smoke_free_join(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let joined = ctx
+        .data()
+        .database
+        .lock()
+        .await
+        .guild(&guild_id)
+        .join_smoke_free_board(&ctx.author().id.to_string())
+        .await?;
+
+    let reply = if joined {
+        "禁煙ボードに参加しました。連続で記録のない日数がボードと週ごとのまとめに表示されます。"
+    } else {
+        "すでに禁煙ボードに参加しています。"
+    };
+    ctx.send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Leaves the smoke-free board of the guild.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, guild_only, rename = "leave")]
+pub async fn smoke_free_leave(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let left = ctx
+        .data()
+        .database
+        .lock()
+        .await
+        .guild(&guild_id)
+        .leave_smoke_free_board(&ctx.author().id.to_string())
+        .await?;
+
+    let reply = if left {
+        "禁煙ボードから退出しました。"
+    } else {
+        "禁煙ボードには参加していません。"
+    };
+    ctx.send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Starts leaderboard seasons, or changes their length and announcement channel.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `length` - The length of the seasons (`month` or `quarter`).
+/// * `channel` - The channel season results are announced in.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "start"
+)]
+pub async fn leaderboard_start(
+    ctx: Context<'_>,
+    length: String,
+    channel: serenity::GuildChannel,
+) -> Result<(), Error> {
+    let Some(length) = SeasonLength::from_name(&length) else {
+        let names: Vec<&str> = SeasonLength::ALL
+            .iter()
+            .map(|length| length.name())
+            .collect();
+        return ctx
+            .send_reply(Reply::error(format!(
+                "シーズンの長さを指定してください: {}",
+                names.join(", ")
+            )))
+            .await;
+    };
+
+    ctx.data()
+        .database
+        .lock()
+        .await
+        .guild(&channel.guild_id.to_string())
+        .set_leaderboard(&channel.id.to_string(), length.name())
+        .await?;
+
+    ctx.send_reply(
+        Reply::new(format!(
+            "{}ごとのシーズンを設定しました。シーズンの結果は{}で発表され、今のシーズンは1時間以内に始まります。長さの変更は次のシーズンから反映されます。",
+            length.label(),
+            channel.name
+        ))
+        .titled(Title::Settings),
+    )
+    .await
+}
+
+/// Stops leaderboard seasons; archived seasons are kept.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "off"
+)]
+pub async fn leaderboard_off(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let removed = ctx
+        .data()
+        .database
+        .lock()
+        .await
+        .guild(&guild_id)
+        .remove_leaderboard()
+        .await?;
+
+    let reply = if removed {
+        "シーズンを終了しました。開催中だったシーズンの順位は記録されません。"
+    } else {
+        "シーズンは開催していません。"
+    };
+    ctx.send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Returns the leaderboard commands.
+pub fn commands() -> Vec<Command> {
+    vec![leaderboard()]
+}
@@ -0,0 +1,396 @@
+//! Bot commands, grouped into feature modules.
+//!
+//! Each module exposes a `commands()` function returning its top-level
+//! commands, and `MODULES` lists every module. `enabled_commands` assembles
+//! the set registered with the framework, leaving out the modules disabled
+//! with `DISABLED_MODULES`. New features add a module here instead of
+//! growing a single command list.
+//!
+//! Each module also declares its `CommandAccess`: the permissions a member
+//! needs to see its application commands and where they can be used. This
+//! only hides commands in Discord's command picker; the commands still check
+//! permissions themselves when run.
+
+pub mod achievements;
+pub mod admin;
+pub mod age_gate;
+pub mod caps;
+pub mod challenge;
+pub mod cleanup;
+pub mod counter;
+pub mod devices;
+pub mod email;
+pub mod event_tags;
+pub mod export;
+pub mod feed;
+pub mod forum;
+pub mod goals;
+pub mod guild;
+pub mod help;
+pub mod leaderboard;
+pub mod notify;
+pub mod panel;
+pub mod pauses;
+pub mod push;
+pub mod quantity;
+pub mod reactions;
+pub mod roles;
+pub mod settings;
+pub mod smoke_break;
+pub mod stats;
+pub mod status;
+pub mod templates;
+pub mod types;
+pub mod undo;
+pub mod wagers;
+
+use poise::serenity_prelude::{
+    CreateCommand, InstallationContext, InteractionContext, Permissions,
+};
+use tracing::warn;
+
+use crate::{Data, Error};
+
+/// A bot command
+pub type Command = poise::Command<Data, Error>;
+
+/// Who sees a module's application commands, and where
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandAccess {
+    /// Permissions a member needs to see the commands; empty for everyone
+    pub default_member_permissions: Permissions,
+    /// Where the commands can be used
+    pub contexts: &'static [InteractionContext],
+    /// Installations of the bot that provide the commands
+    pub installs: &'static [InstallationContext],
+}
+
+impl CommandAccess {
+    /// Commands for every member, in servers and in DMs with the bot
+    pub const EVERYONE: Self = Self {
+        default_member_permissions: Permissions::empty(),
+        contexts: &[InteractionContext::Guild, InteractionContext::BotDm],
+        installs: &[InstallationContext::Guild],
+    };
+
+    /// Personal commands, also available through a user install: in any DM
+    /// and in servers the bot is not a member of (see `installation`)
+    pub const PERSONAL: Self = Self {
+        default_member_permissions: Permissions::empty(),
+        contexts: &[
+            InteractionContext::Guild,
+            InteractionContext::BotDm,
+            InteractionContext::PrivateChannel,
+        ],
+        installs: &[InstallationContext::Guild, InstallationContext::User],
+    };
+
+    /// Commands for the bot owners, shown to server administrators only
+    pub const OWNERS: Self = Self {
+        default_member_permissions: Permissions::ADMINISTRATOR,
+        contexts: &[InteractionContext::Guild, InteractionContext::BotDm],
+        installs: &[InstallationContext::Guild],
+    };
+
+    /// Server commands shown only to members with the given permissions.
+    ///
+    /// # Arguments
+    /// * `permissions` - The permissions a member needs to see the commands.
+    pub const fn managers(permissions: Permissions) -> Self {
+        Self {
+            default_member_permissions: permissions,
+            contexts: &[InteractionContext::Guild],
+            installs: &[InstallationContext::Guild],
+        }
+    }
+
+    /// Applies the access to an application command about to be registered.
+    ///
+    /// # Arguments
+    /// * `command` - The command builder.
+    ///
+    /// # Returns
+    /// The builder with permissions and contexts set.
+    pub fn apply(&self, mut command: CreateCommand) -> CreateCommand {
+        // Discord reads an empty permission set as "administrators only".
+        if !self.default_member_permissions.is_empty() {
+            command = command.default_member_permissions(self.default_member_permissions);
+        }
+
+        command
+            .contexts(self.contexts.to_vec())
+            .integration_types(self.installs.to_vec())
+    }
+}
+
+/// A feature module contributing commands
+pub struct CommandModule {
+    /// The name used in `DISABLED_MODULES`
+    pub name: &'static str,
+    /// Returns the module's top-level commands
+    pub commands: fn() -> Vec<Command>,
+    /// Who sees the module's commands
+    pub access: CommandAccess,
+}
+
+/// Every command module, in registration order
+pub const MODULES: &[CommandModule] = &[
+    CommandModule {
+        name: "panel",
+        commands: panel::commands,
+        access: CommandAccess::PERSONAL,
+    },
+    CommandModule {
+        name: "quantity",
+        commands: quantity::commands,
+        access: CommandAccess::PERSONAL,
+    },
+    CommandModule {
+        name: "undo",
+        commands: undo::commands,
+        access: CommandAccess::PERSONAL,
+    },
+    CommandModule {
+        name: "devices",
+        commands: devices::commands,
+        access: CommandAccess::EVERYONE,
+    },
+    CommandModule {
+        name: "feed",
+        commands: feed::commands,
+        access: CommandAccess::PERSONAL,
+    },
+    CommandModule {
+        name: "email",
+        commands: email::commands,
+        access: CommandAccess::PERSONAL,
+    },
+    CommandModule {
+        name: "push",
+        commands: push::commands,
+        access: CommandAccess::PERSONAL,
+    },
+    CommandModule {
+        name: "notify",
+        commands: notify::commands,
+        access: CommandAccess::PERSONAL,
+    },
+    CommandModule {
+        name: "goals",
+        commands: goals::commands,
+        access: CommandAccess::PERSONAL,
+    },
+    CommandModule {
+        name: "pauses",
+        commands: pauses::commands,
+        access: CommandAccess::PERSONAL,
+    },
+    CommandModule {
+        name: "roles",
+        commands: roles::commands,
+        access: CommandAccess::managers(Permissions::MANAGE_ROLES),
+    },
+    CommandModule {
+        name: "smoke_break",
+        commands: smoke_break::commands,
+        access: CommandAccess::managers(Permissions::MANAGE_CHANNELS),
+    },
+    CommandModule {
+        name: "forum",
+        commands: forum::commands,
+        access: CommandAccess::managers(Permissions::MANAGE_CHANNELS),
+    },
+    CommandModule {
+        name: "counter",
+        commands: counter::commands,
+        access: CommandAccess::managers(Permissions::MANAGE_CHANNELS),
+    },
+    CommandModule {
+        name: "challenge",
+        commands: challenge::commands,
+        access: CommandAccess::managers(Permissions::MANAGE_GUILD),
+    },
+    CommandModule {
+        name: "achievements",
+        commands: achievements::commands,
+        access: CommandAccess::EVERYONE,
+    },
+    CommandModule {
+        name: "leaderboard",
+        commands: leaderboard::commands,
+        access: CommandAccess::EVERYONE,
+    },
+    CommandModule {
+        name: "wagers",
+        commands: wagers::commands,
+        access: CommandAccess::EVERYONE,
+    },
+    CommandModule {
+        name: "stats",
+        commands: stats::commands,
+        access: CommandAccess::PERSONAL,
+    },
+    CommandModule {
+        name: "event_tags",
+        commands: event_tags::commands,
+        access: CommandAccess::PERSONAL,
+    },
+    CommandModule {
+        name: "export",
+        commands: export::commands,
+        access: CommandAccess::PERSONAL,
+    },
+    CommandModule {
+        name: "templates",
+        commands: templates::commands,
+        access: CommandAccess::managers(Permissions::MANAGE_GUILD),
+    },
+    CommandModule {
+        name: "age_gate",
+        commands: age_gate::commands,
+        access: CommandAccess::managers(Permissions::MANAGE_GUILD),
+    },
+    CommandModule {
+        name: "caps",
+        commands: caps::commands,
+        access: CommandAccess::managers(Permissions::MANAGE_GUILD),
+    },
+    CommandModule {
+        name: "reactions",
+        commands: reactions::commands,
+        access: CommandAccess::managers(Permissions::MANAGE_GUILD),
+    },
+    CommandModule {
+        name: "cleanup",
+        commands: cleanup::commands,
+        access: CommandAccess::PERSONAL,
+    },
+    CommandModule {
+        name: "settings",
+        commands: settings::commands,
+        access: CommandAccess::managers(Permissions::MANAGE_GUILD),
+    },
+    CommandModule {
+        name: "types",
+        commands: types::commands,
+        access: CommandAccess::managers(Permissions::MANAGE_GUILD),
+    },
+    CommandModule {
+        name: "guild",
+        commands: guild::commands,
+        access: CommandAccess::managers(Permissions::MANAGE_GUILD),
+    },
+    CommandModule {
+        name: "help",
+        commands: help::commands,
+        access: CommandAccess::PERSONAL,
+    },
+    CommandModule {
+        name: "status",
+        commands: status::commands,
+        access: CommandAccess::EVERYONE,
+    },
+    CommandModule {
+        name: "admin",
+        commands: admin::commands,
+        access: CommandAccess::OWNERS,
+    },
+];
+
+/// Assembles the commands of all enabled modules.
+///
+/// Unknown module names are logged and otherwise ignored.
+///
+/// # Arguments
+/// * `disabled_modules` - The names of the modules to leave out.
+///
+/// # Returns
+/// The commands to register with the framework.
+pub fn enabled_commands(disabled_modules: &[String]) -> Vec<Command> {
+    for name in disabled_modules {
+        if !MODULES.iter().any(|module| module.name == name) {
+            warn!("Unknown command module in DISABLED_MODULES: {}", name);
+        }
+    }
+
+    MODULES
+        .iter()
+        .filter(|module| !disabled_modules.iter().any(|name| name == module.name))
+        .flat_map(|module| (module.commands)())
+        .collect()
+}
+
+/// Returns who sees a command.
+///
+/// # Arguments
+/// * `name` - The name of a top-level command.
+///
+/// # Returns
+/// The access of the command's module, or `CommandAccess::EVERYONE` for unknown commands.
+pub fn command_access(name: &str) -> CommandAccess {
+    MODULES
+        .iter()
+        .find(|module| {
+            (module.commands)()
+                .iter()
+                .any(|command| command.name == name)
+        })
+        .map_or(CommandAccess::EVERYONE, |module| module.access)
+}
+
+/// Builds the application commands to register, with each module's access applied.
+///
+/// # Arguments
+/// * `commands` - The enabled commands of the framework.
+///
+/// # Returns
+/// The slash and context menu command builders.
+pub fn application_commands(commands: &[Command]) -> Vec<CreateCommand> {
+    commands
+        .iter()
+        .flat_map(|command| {
+            let access = command_access(&command.name);
+            [
+                command.create_as_slash_command(),
+                command.create_as_context_menu_command(),
+            ]
+            .into_iter()
+            .flatten()
+            .map(move |builder| access.apply(builder))
+        })
+        .collect()
+}
+
+/// Maximum length of a single Discord message chunk for long outputs
+const MESSAGE_CHUNK_LENGTH: usize = 1900;
+
+/// Joins lines into chunks no longer than `max_length` characters.
+///
+/// Lines longer than `max_length` are truncated.
+///
+/// # Arguments
+/// * `lines` - The lines to join.
+/// * `max_length` - The maximum length of a chunk.
+///
+/// # Returns
+/// A vector of chunks.
+fn chunk_lines(lines: &[String], max_length: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for line in lines {
+        let line: String = line.chars().take(max_length).collect();
+        if !current.is_empty() && current.chars().count() + line.chars().count() + 1 > max_length {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(&line);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
@@ -0,0 +1,144 @@
+//! Choosing the channel personal notifications are delivered on, see `notify`.
+
+use poise::serenity_prelude::futures::lock::Mutex;
+
+use crate::database::Database;
+use crate::frontend::{Frontend, Reply};
+use crate::notify::NotifyChannel;
+use crate::push::PushService;
+use crate::{Context, Data, Error};
+
+use super::Command;
+
+/// Shows or chooses where you receive notifications such as milestones.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `channel` - `dm`, `email`, `ntfy` or `pushover`; shows the current channel when omitted.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command)]
+pub async fn notify(ctx: Context<'_>, channel: Option<String>) -> Result<(), Error> {
+    choose_notification_channel(
+        &ctx,
+        &ctx.data().database,
+        &enabled_channels(ctx.data()),
+        &ctx.author().id.get().to_string(),
+        &ctx.author().name,
+        channel.as_deref(),
+    )
+    .await
+}
+
+/// Returns the channels this instance can deliver notifications on.
+fn enabled_channels(data: &Data) -> Vec<NotifyChannel> {
+    NotifyChannel::ALL
+        .into_iter()
+        .filter(|channel| match channel {
+            NotifyChannel::Dm => true,
+            NotifyChannel::Email => data.mailer.is_some(),
+            NotifyChannel::Ntfy => data.push.get(PushService::Ntfy).is_some(),
+            NotifyChannel::Pushover => data.push.get(PushService::Pushover).is_some(),
+        })
+        .collect()
+}
+
+/// Shows a user's notification channel, or sets it if the user can be reached on it.
+///
+/// # Arguments
+/// * `frontend` - Where the reply is sent.
+/// * `database` - The database.
+/// * `enabled` - The channels this instance can deliver on.
+/// * `user_id` - The Discord ID of the user.
+/// * `username` - The current username of the user.
+/// * `channel` - The name of the chosen channel, or `None` to show the current one.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn choose_notification_channel(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    enabled: &[NotifyChannel],
+    user_id: &str,
+    username: &str,
+    channel: Option<&str>,
+) -> Result<(), Error> {
+    let reply = {
+        let db = database.lock().await;
+        notification_channel_reply(&db, enabled, user_id, username, channel).await?
+    };
+
+    frontend.send_reply(reply).await
+}
+
+/// Shows or sets a user's notification channel and builds the reply.
+async fn notification_channel_reply(
+    db: &Database,
+    enabled: &[NotifyChannel],
+    user_id: &str,
+    username: &str,
+    channel: Option<&str>,
+) -> Result<Reply, Error> {
+    let usage = format!(
+        "使い方: notify <{}>",
+        enabled
+            .iter()
+            .map(|channel| channel.name())
+            .collect::<Vec<_>>()
+            .join("|")
+    );
+
+    let Some(channel) = channel else {
+        let current = db
+            .get_notification_channel(user_id)
+            .await?
+            .and_then(|channel| channel.parse().ok())
+            .unwrap_or(NotifyChannel::Dm);
+        return Ok(Reply::new(format!(
+            "現在の通知先: {}\n{}",
+            current.display_name(),
+            usage
+        )));
+    };
+    let Some(channel) = channel
+        .parse::<NotifyChannel>()
+        .ok()
+        .filter(|channel| enabled.contains(channel))
+    else {
+        return Ok(Reply::error(format!(
+            "このインスタンスでは「{}」に通知できません。\n{}",
+            channel, usage
+        )));
+    };
+
+    let reachable = match channel {
+        NotifyChannel::Dm => true,
+        NotifyChannel::Email => db.get_verified_email(user_id).await?.is_some(),
+        NotifyChannel::Ntfy | NotifyChannel::Pushover => db
+            .get_push_subscription(user_id)
+            .await?
+            .is_some_and(|subscription| subscription.service == channel.name()),
+    };
+    if !reachable {
+        return Ok(Reply::error(match channel {
+            NotifyChannel::Ntfy => "先に `push ntfy` でトピックを登録してください。",
+            NotifyChannel::Pushover => "先に `push pushover` でユーザーキーを登録してください。",
+            _ => "先に `email set` でメールアドレスを登録してください。",
+        }));
+    }
+
+    db.get_or_create_user(user_id, username).await?;
+    db.set_notification_channel(user_id, Some(channel.name()))
+        .await?;
+
+    Ok(Reply::new(format!(
+        "通知を{}で受け取るように設定しました。",
+        channel.display_name()
+    )))
+}
+
+/// Returns the notification commands.
+pub fn commands() -> Vec<Command> {
+    vec![notify()]
+}
@@ -0,0 +1,842 @@
+//! The cigarette panel: buttons that record one cigarette per press.
+//!
+//! Panels are stored when they are posted and their buttons carry the panel
+//! ID (see `CustomId`), so the event dispatcher handles their presses instead
+//! of a collector tied to the command. Panels posted before a restart keep
+//! working; only the smoke-break prompts sent by DM expire (see `voice`).
+
+use crate::acknowledgment::request_acknowledgment;
+use crate::circuit_breaker::UNAVAILABLE_MESSAGE;
+use crate::consent::request_consent;
+use crate::custom_id::{CustomId, RefreshId};
+use crate::database::{BufferedLog, Database, SmokingType};
+use crate::deferral;
+use crate::embed::{category_goal_embed, goal_embed, Title};
+use crate::format::{
+    branding, format_count, format_date, format_quiet_confirmation, format_summary_lines, Locale,
+};
+use crate::frontend::{Frontend, InteractionFrontend, Reply};
+use crate::installation::interaction_guild;
+use crate::latency::RequestGuard;
+use crate::milestones::sync_member_roles;
+use crate::service::{LoggedSmoking, LoggingService, PressKey, ServiceError};
+use crate::tutorial::tutorial;
+use crate::voice::SMOKE_BREAK_PANEL_PREFIX;
+use crate::write_buffer::WriteBuffer;
+use crate::{Context, Data, Error};
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+use poise::CreateReply;
+use tracing::warn;
+
+use super::undo::{UndoId, UNDO_LABEL};
+use super::Command;
+
+/// Maximum length of a button label accepted by Discord
+pub const MAX_LABEL_LENGTH: usize = 80;
+
+/// Error returned when a smoking type cannot be shown as a button
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Smoking type {0} has no usable label")]
+pub struct UnusableLabelError(pub i32);
+
+/// Builds the button label of a smoking type.
+///
+/// The description is used, falling back to the type name when it is blank;
+/// labels longer than Discord's limit are truncated with an ellipsis.
+///
+/// # Arguments
+/// * `smoking_type` - The smoking type.
+///
+/// # Returns
+/// The label, or `UnusableLabelError` if the type has neither a description nor a name.
+pub fn button_label(smoking_type: &SmokingType) -> Result<String, UnusableLabelError> {
+    let label = smoking_type
+        .description
+        .as_deref()
+        .map(str::trim)
+        .filter(|description| !description.is_empty())
+        .unwrap_or(smoking_type.type_name.trim());
+    if label.is_empty() {
+        return Err(UnusableLabelError(smoking_type.id));
+    }
+
+    if label.chars().count() <= MAX_LABEL_LENGTH {
+        return Ok(label.to_string());
+    }
+    warn!(
+        "Truncating the button label of smoking type {} to {} characters",
+        smoking_type.id, MAX_LABEL_LENGTH
+    );
+    let mut truncated: String = label.chars().take(MAX_LABEL_LENGTH - 1).collect();
+    truncated.push('…');
+
+    Ok(truncated)
+}
+
+/// Creates a vector of buttons for each cigarette type.
+///
+/// # Arguments
+/// * `db` - The database.
+/// * `uuid` - A unique identifier for the interaction.
+/// * `smoking_type_ids` - The smoking types to show, or `None` for all types.
+///
+/// # Returns
+/// A Result containing a vector of `serenity::CreateButton`, or an `Error`
+/// (an `UnusableLabelError` if a type cannot be labelled).
+pub(crate) async fn create_cigarette_buttons(
+    db: &Database,
+    uuid: &str,
+    smoking_type_ids: Option<&[i32]>,
+) -> Result<Vec<serenity::CreateButton>, Error> {
+    let cigarette_types = db.get_smoking_types().await?;
+
+    Ok(cigarette_buttons(cigarette_types, uuid, smoking_type_ids)?)
+}
+
+/// Builds the buttons of a panel from the given smoking types.
+///
+/// Types without an emoji of their own show the branding's, if it sets one.
+///
+/// # Arguments
+/// * `cigarette_types` - Every smoking type that can be logged.
+/// * `uuid` - The ID of the panel.
+/// * `smoking_type_ids` - The smoking types to show, or `None` for all types.
+///
+/// # Returns
+/// The buttons, or `UnusableLabelError` if a type cannot be labelled.
+pub fn cigarette_buttons(
+    cigarette_types: Vec<SmokingType>,
+    uuid: &str,
+    smoking_type_ids: Option<&[i32]>,
+) -> Result<Vec<serenity::CreateButton>, UnusableLabelError> {
+    cigarette_types
+        .into_iter()
+        .filter(|cigarette_type| {
+            smoking_type_ids.is_none_or(|ids| ids.contains(&cigarette_type.id))
+        })
+        .map(|cigarette_type| {
+            let button =
+                serenity::CreateButton::new(CustomId::new(uuid, cigarette_type.id).encode())
+                    .style(serenity::ButtonStyle::Primary)
+                    .label(button_label(&cigarette_type)?);
+            Ok(
+                match cigarette_type
+                    .emoji
+                    .as_deref()
+                    .or(branding().emoji.as_deref())
+                    .and_then(|emoji| emoji.parse::<serenity::ReactionType>().ok())
+                {
+                    Some(emoji) => button.emoji(emoji),
+                    None => button,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Returns the panel of a pressed button if it belongs to a persistent panel.
+///
+/// # Arguments
+/// * `custom_id` - The custom ID of the pressed button.
+///
+/// # Returns
+/// The panel ID, or `None` for other components and for smoke-break prompts,
+/// whose presses are handled while they last.
+pub fn parse_panel_press(custom_id: &str) -> Option<String> {
+    let custom_id: CustomId = custom_id.parse().ok()?;
+
+    (!custom_id.panel.starts_with(SMOKE_BREAK_PANEL_PREFIX)).then_some(custom_id.panel)
+}
+
+/// Handles a component interaction.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `mci` - The component interaction.
+/// * `uuid` - A unique identifier for the interaction.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub(crate) async fn handle_interaction(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &serenity::ComponentInteraction,
+    uuid: &str,
+) -> Result<(), Error> {
+    let _request = RequestGuard::begin("interaction cigarette button");
+    deferral::run(ctx, mci, press_panel_button(ctx, data, mci, uuid)).await
+}
+
+/// Handles a panel button press, failing fast while the database is
+/// unreachable (buffering the press if the write buffer is enabled) or saturated.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `mci` - The component interaction.
+/// * `uuid` - The ID of the panel.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+async fn press_panel_button(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &serenity::ComponentInteraction,
+    uuid: &str,
+) -> Result<(), Error> {
+    let frontend = InteractionFrontend::new(ctx, mci);
+    if data.db_breaker.allow().is_err() {
+        let log = BufferedLog {
+            discord_id: mci.user.id.get().to_string(),
+            guild_id: interaction_guild(mci).map(|guild_id| guild_id.to_string()),
+            smoking_type_id: extract_cigarette_id(&mci.data.custom_id, uuid)?,
+            quantity: 1,
+            smoked_at: data.clock.now(),
+            idempotency_key: Some(interaction_key(mci.id)),
+        };
+        return buffer_press(&frontend, data.write_buffer.as_deref(), log).await;
+    }
+    let Ok(_permit) = data.db_limiter.acquire().await else {
+        frontend
+            .respond(
+                Reply::error("混雑中です。しばらくしてからもう一度お試しください。").ephemeral(),
+            )
+            .await?;
+        return Ok(());
+    };
+
+    let result = log_panel_press(ctx, data, mci, uuid).await;
+    data.db_breaker.record(&result);
+
+    result
+}
+
+/// Responds to a press taken while the database is unreachable, keeping it
+/// in the write buffer if one is enabled and has room.
+///
+/// # Arguments
+/// * `frontend` - Where the response is sent.
+/// * `buffer` - The write buffer, if enabled.
+/// * `log` - The press.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn buffer_press(
+    frontend: &dyn Frontend,
+    buffer: Option<&WriteBuffer>,
+    log: BufferedLog,
+) -> Result<(), Error> {
+    let reply = match buffer.map(|buffer| buffer.push(log)) {
+        Some(Ok(())) => Reply::new("データベースに一時的に接続できないため、この記録を保留しました。接続が戻りしだい記録します。"),
+        Some(Err(e)) => {
+            warn!("Failed to buffer a panel press: {}", e);
+            Reply::error(UNAVAILABLE_MESSAGE)
+        }
+        None => Reply::error(UNAVAILABLE_MESSAGE),
+    };
+
+    frontend.respond(reply.ephemeral()).await
+}
+
+/// Logs a panel button press, checking that its smoking type may be logged.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `mci` - The component interaction.
+/// * `uuid` - The ID of the panel.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+async fn log_panel_press(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &serenity::ComponentInteraction,
+    uuid: &str,
+) -> Result<(), Error> {
+    let cigarette_id = extract_cigarette_id(&mci.data.custom_id, uuid)?;
+    let guild_id = interaction_guild(mci).map(|guild_id| guild_id.to_string());
+    let (panel, exists) = {
+        let db = data.database.lock().await;
+        (
+            db.get_panel(uuid, guild_id.as_deref()).await?,
+            db.smoking_type_exists(cigarette_id).await?,
+        )
+    };
+    // Buttons of deleted or merged types are no longer on the stored panel.
+    if !exists {
+        return offer_refresh(ctx, mci, uuid).await;
+    }
+    // Custom IDs are untrusted: only types shown on the panel may be logged.
+    if panel
+        .and_then(|panel| panel.smoking_type_ids)
+        .is_some_and(|ids| !ids.contains(&cigarette_id))
+    {
+        return Err(Error::from("Smoking type is not shown on the panel"));
+    }
+
+    log_button_press(ctx, data, mci, cigarette_id).await
+}
+
+/// Responds to a press of a deleted smoking type's button with an offer to refresh the panel.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `mci` - The button press.
+/// * `uuid` - The ID of the panel.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+async fn offer_refresh(
+    ctx: &serenity::Context,
+    mci: &serenity::ComponentInteraction,
+    uuid: &str,
+) -> Result<(), Error> {
+    InteractionFrontend::new(ctx, mci)
+        .respond(
+            Reply::error("この種類は削除または統合されたため記録できません。パネルを更新すると現在の種類のボタンが表示されます。")
+                .button(RefreshId::new(mci.message.id.get(), uuid).encode(), "パネルを更新")
+                .ephemeral(),
+        )
+        .await
+}
+
+/// Handles a press of a refresh button: rebuilds the panel's buttons from the current smoking types.
+///
+/// The panel keeps its ID, so the new buttons are checked against the same stored panel.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `mci` - The refresh button press.
+/// * `refresh` - The decoded custom ID of the refresh button.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub(crate) async fn refresh_panel(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &serenity::ComponentInteraction,
+    refresh: &RefreshId,
+) -> Result<(), Error> {
+    let guild_id = interaction_guild(mci).map(|guild_id| guild_id.to_string());
+    let buttons = {
+        let db = data.database.lock().await;
+        let smoking_type_ids = db
+            .get_panel(&refresh.panel, guild_id.as_deref())
+            .await?
+            .and_then(|panel| panel.smoking_type_ids);
+        create_cigarette_buttons(&db, &refresh.panel, smoking_type_ids.as_deref()).await?
+    };
+
+    mci.channel_id
+        .edit_message(
+            ctx,
+            serenity::MessageId::new(refresh.message_id),
+            serenity::EditMessage::new()
+                .components(vec![serenity::CreateActionRow::Buttons(buttons)]),
+        )
+        .await?;
+    InteractionFrontend::new(ctx, mci)
+        .update(Reply::new("パネルを更新しました。"))
+        .await
+}
+
+/// Logs a pressed smoking type for the user of an interaction, asking for
+/// consent to the data policy and the guild's acknowledgment first if needed.
+/// A new user's first press is followed by the tutorial.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `mci` - The component interaction.
+/// * `cigarette_id` - The ID of the smoking type.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub(crate) async fn log_button_press(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &serenity::ComponentInteraction,
+    cigarette_id: i32,
+) -> Result<(), Error> {
+    let frontend = InteractionFrontend::new(ctx, mci);
+    let user_id = mci.user.id.get().to_string();
+    if !data.logging.has_consented(&user_id).await? {
+        return request_consent(ctx, mci, cigarette_id).await;
+    }
+    if let Some(guild_id) = interaction_guild(mci) {
+        let required = {
+            let db = data.database.lock().await;
+            let guild = db.guild(&guild_id.to_string());
+            match guild.get_acknowledgment_policy().await? {
+                Some(message) if !guild.has_acknowledged(&user_id).await? => Some(message),
+                _ => None,
+            }
+        };
+        if let Some(message) = required {
+            return request_acknowledgment(ctx, mci, &message, cigarette_id).await;
+        }
+    }
+
+    let guild_id = interaction_guild(mci).map(|guild_id| guild_id.to_string());
+    let new_user = data.logging.is_new_user(&user_id).await?;
+    record_cigarette(
+        &frontend,
+        &data.logging,
+        &user_id,
+        &mci.user.name,
+        guild_id.as_deref(),
+        cigarette_id,
+        Some(&interaction_key(mci.id)),
+    )
+    .await?;
+    if new_user {
+        if let Err(e) = frontend.send_reply(tutorial()).await {
+            warn!("Failed to send the tutorial: {}", e);
+        }
+    }
+
+    match interaction_guild(mci) {
+        Some(guild_id) => {
+            if let Err(e) = sync_member_roles(
+                &ctx.http,
+                &data.database,
+                &data.stats,
+                &data.events,
+                guild_id,
+                mci.user.id,
+            )
+            .await
+            {
+                warn!("Failed to sync milestone roles: {}", e);
+            }
+        }
+        None => {
+            // Outside guilds the panel's channel groups the logs, so group DMs can be aggregated.
+            let channel_id = mci.channel_id.to_string();
+            {
+                let db = data.database.lock().await;
+                db.assign_log_channel(&user_id, &interaction_key(mci.id), &channel_id)
+                    .await?;
+            }
+            data.stats.forget_channel(&channel_id);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the idempotency key of a component interaction.
+///
+/// Discord redelivers an interaction with the same ID, so logging with this
+/// key records each button press at most once.
+///
+/// # Arguments
+/// * `interaction_id` - The ID of the interaction.
+///
+/// # Returns
+/// The idempotency key.
+pub fn interaction_key(interaction_id: serenity::InteractionId) -> String {
+    format!("interaction:{}", interaction_id)
+}
+
+/// Records one cigarette for a user and responds with the day's summary (or
+/// the guild's confirmation reaction), or with an ephemeral rejection if the
+/// guild's daily cap of the type is reached.
+///
+/// Lines returned by `on_log_created` scripts are appended to the summary;
+/// users with a daily goal also get their progress toward it as an embed.
+/// Presses of the same type that follow within a few seconds edit the first
+/// press's confirmation into e.g. `紙タバコ x3` instead of sending another one;
+/// each press is still recorded as its own log. Users with quiet confirmations
+/// get one confirmation a day, which their later presses edit. Guilds with
+/// confirmation cleanup have the confirmations deleted once they are old.
+///
+/// # Arguments
+/// * `frontend` - Where the response is sent.
+/// * `logging` - The logging service.
+/// * `user_id` - The Discord ID of the user.
+/// * `username` - The current username of the user.
+/// * `guild_id` - The guild the panel was pressed in, if any.
+/// * `cigarette_id` - The ID of the smoking type.
+/// * `idempotency_key` - The key of the button press; a redelivered press with the
+///   same key responds with the original log instead of recording another one.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn record_cigarette(
+    frontend: &dyn Frontend,
+    logging: &LoggingService,
+    user_id: &str,
+    username: &str,
+    guild_id: Option<&str>,
+    cigarette_id: i32,
+    idempotency_key: Option<&str>,
+) -> Result<(), Error> {
+    let logged = match logging
+        .log_for_user(
+            user_id,
+            username,
+            guild_id,
+            cigarette_id,
+            1,
+            false,
+            idempotency_key,
+        )
+        .await
+    {
+        Ok(logged) => logged,
+        Err(ServiceError::DailyCapReached { display_name, cap }) => {
+            return frontend
+                .respond(
+                    Reply::error(format!(
+                        "本日の{}はこのサーバーの上限（{}{}）に達しているため記録できません。上限を超えて記録する場合は ignore-caps on を使ってください。",
+                        display_name,
+                        cap,
+                        branding().count_unit
+                    ))
+                    .ephemeral(),
+                )
+                .await;
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if let Some(emoji) = logging.confirmation_reaction(guild_id).await? {
+        return frontend.react(emoji.parse()?).await;
+    }
+    let template = logging.confirmation_template(guild_id).await?;
+
+    let locale = frontend.locale();
+    let mut lines = vec![template.render(&[
+        ("user", username),
+        ("date", &format_date(logged.date, locale)),
+        (
+            "summary",
+            &format_summary_lines(&logged.daily_summary, locale).join("\n"),
+        ),
+        ("total", &format_count(logged.today_total, locale)),
+    ])];
+    lines.extend(logged.script_lines.iter().cloned());
+    let content = lines.join("\n");
+
+    // A redelivered press is not a new press; it gets its own confirmation.
+    if logged.replayed {
+        return frontend
+            .respond(confirmation(content, &logged, locale))
+            .await;
+    }
+    if logging.quiet_confirmations(user_id).await? {
+        return confirm_quietly(frontend, logging, user_id, &logged, content).await;
+    }
+    let key = PressKey {
+        user_id: user_id.to_string(),
+        guild_id: guild_id.map(str::to_string),
+        smoking_type_id: cigarette_id,
+    };
+    if let Some(batch) = logging.extend_press_batch(&key) {
+        let name = logging.display_name(cigarette_id).await?;
+        let batched = format!("{} x{}\n{}", name, batch.count, content);
+        // The message may have been deleted; confirm with a new one then.
+        if frontend
+            .edit_message(batch.message_id, confirmation(batched, &logged, locale))
+            .await
+            .is_ok()
+        {
+            return frontend.acknowledge().await;
+        }
+        logging.close_press_batch(&key);
+    }
+
+    if let Some(message_id) = frontend
+        .respond_editable(confirmation(content, &logged, locale))
+        .await?
+    {
+        logging.open_press_batch(key, message_id);
+        logging
+            .remember_confirmation(guild_id, frontend.channel_id(), message_id)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Confirms a quiet user's press: the day's first press sends the full
+/// confirmation, later presses edit it into e.g. `今日: 8本, 最終 14:32`.
+///
+/// # Arguments
+/// * `frontend` - Where the response is sent.
+/// * `logging` - The logging service.
+/// * `user_id` - The Discord ID of the user.
+/// * `logged` - The recorded press.
+/// * `content` - The full confirmation.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+async fn confirm_quietly(
+    frontend: &dyn Frontend,
+    logging: &LoggingService,
+    user_id: &str,
+    logged: &LoggedSmoking,
+    content: String,
+) -> Result<(), Error> {
+    let locale = frontend.locale();
+    if let Some(message_id) = logging.daily_confirmation(user_id, logged.date).await? {
+        let mut lines = vec![format_quiet_confirmation(
+            logged.today_total,
+            logging.local_time(&logged.log),
+            locale,
+        )];
+        lines.extend(logged.script_lines.iter().cloned());
+        // The message may have been deleted or sent in another channel; confirm with a new one then.
+        if frontend
+            .edit_message(message_id, confirmation(lines.join("\n"), logged, locale))
+            .await
+            .is_ok()
+        {
+            return frontend.acknowledge().await;
+        }
+    }
+
+    if let Some(message_id) = frontend
+        .respond_editable(confirmation(content, logged, locale))
+        .await?
+    {
+        logging
+            .remember_daily_confirmation(user_id, logged.date, message_id)
+            .await?;
+        logging
+            .remember_confirmation(
+                logged.log.guild_id.as_deref(),
+                frontend.channel_id(),
+                message_id,
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Builds the confirmation of a press, with the goal embeds of the user's daily
+/// goal and of their goal for the type's category, if set, and a button undoing
+/// the user's last log.
+///
+/// # Arguments
+/// * `content` - The confirmation text.
+/// * `logged` - The recorded press.
+/// * `locale` - The locale to format for.
+///
+/// # Returns
+/// The `Reply`.
+fn confirmation(content: String, logged: &LoggedSmoking, locale: Locale) -> Reply {
+    let undo = UndoId {
+        user_id: logged.log.discord_id.clone(),
+    };
+    let mut reply = Reply::new(content)
+        .titled(Title::Confirmation)
+        .button(undo.custom_id(), UNDO_LABEL);
+    if let Some(goal) = logged.goal {
+        reply = reply.embed(goal_embed(logged.today_total, goal, locale));
+    }
+
+    match &logged.category_progress {
+        Some(progress) => reply.embed(category_goal_embed(progress, locale)),
+        None => reply,
+    }
+}
+
+/// Extracts the cigarette ID from the custom ID.
+///
+/// # Arguments
+/// * `custom_id` - The custom ID string.
+/// * `uuid` - The ID of the panel the interaction is expected to belong to.
+///
+/// # Returns
+/// A Result containing the cigarette ID as an `i32` or an `Error`.
+fn extract_cigarette_id(custom_id: &str, uuid: &str) -> Result<i32, Error> {
+    let custom_id = custom_id
+        .parse::<CustomId>()
+        .map_err(|e| Error::from(format!("Failed to parse cigarette ID: {}", e)))?;
+    if custom_id.panel != uuid {
+        return Err(Error::from("Custom ID belongs to another panel"));
+    }
+
+    Ok(custom_id.smoking_type_id)
+}
+
+/// Creates the cigarette counting user interface.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, slash_command)]
+pub async fn create_cigarette_ui(ctx: Context<'_>) -> Result<(), Error> {
+    run_panel(ctx, None).await
+}
+
+/// Manages cigarette panels.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, slash_command, subcommands("panel_install"))]
+pub async fn panel(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new("使い方: panel install [種類,...]"))
+        .await
+}
+
+/// Installs a panel in the channel, optionally limited to some smoking types.
+///
+/// For example, `panel install iqos` shows only the IQOS button.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `types` - Comma- or space-separated type names to show; the guild's default types when omitted.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, slash_command, rename = "install")]
+pub async fn panel_install(
+    ctx: Context<'_>,
+    #[rest]
+    #[description = "表示する種類の名前（カンマまたは空白区切り）"]
+    types: Option<String>,
+) -> Result<(), Error> {
+    run_panel(ctx, types.as_deref()).await
+}
+
+/// Installs a panel; its button presses are handled by the event dispatcher.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `types` - The type filter, if any.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+async fn run_panel(ctx: Context<'_>, types: Option<&str>) -> Result<(), Error> {
+    let uuid = ctx.id().to_string();
+
+    let Some(buttons) = install_panel(
+        &ctx,
+        &ctx.data().database,
+        &uuid,
+        ctx.guild_id()
+            .map(|guild_id| guild_id.to_string())
+            .as_deref(),
+        &ctx.channel_id().to_string(),
+        types,
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+    let components = vec![serenity::CreateActionRow::Buttons(buttons)];
+    let reply = CreateReply::default()
+        .content(&branding().name)
+        .components(components);
+
+    let message_id = ctx.send(reply).await?.message().await?.id;
+    ctx.data()
+        .database
+        .lock()
+        .await
+        .set_panel_message(&uuid, &message_id.to_string())
+        .await?;
+
+    Ok(())
+}
+
+/// Resolves a panel's type filter, stores the panel and builds its buttons.
+///
+/// # Arguments
+/// * `frontend` - Where errors in the filter are reported.
+/// * `database` - The database.
+/// * `panel_id` - The ID of the new panel.
+/// * `guild_id` - The guild the panel is installed in, if any.
+/// * `channel_id` - The channel the panel is installed in.
+/// * `types` - Comma- or space-separated type names to show, or `None` for the guild's default
+///   types (all types if it chose none).
+///
+/// # Returns
+/// A Result containing the panel's buttons, `None` if the filter was invalid, or an `Error`.
+pub async fn install_panel(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    panel_id: &str,
+    guild_id: Option<&str>,
+    channel_id: &str,
+    types: Option<&str>,
+) -> Result<Option<Vec<serenity::CreateButton>>, Error> {
+    let db = database.lock().await;
+    let names: Vec<&str> = types
+        .unwrap_or_default()
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|name| !name.is_empty())
+        .collect();
+
+    let smoking_type_ids = if names.is_empty() {
+        match guild_id {
+            Some(guild_id) => {
+                db.guild(guild_id)
+                    .get_settings()
+                    .await?
+                    .panel_smoking_type_ids
+            }
+            None => None,
+        }
+    } else {
+        let mut ids = Vec::new();
+        for name in names {
+            match db.find_smoking_type_by_name(name).await? {
+                Some(smoking_type) => ids.push(smoking_type.id),
+                None => {
+                    let known: Vec<String> = db
+                        .get_smoking_types()
+                        .await?
+                        .into_iter()
+                        .map(|smoking_type| smoking_type.type_name)
+                        .collect();
+                    frontend
+                        .send_reply(Reply::error(format!(
+                            "不明な種類です: {}（使用できる種類: {}）",
+                            name,
+                            known.join(", ")
+                        )))
+                        .await?;
+                    return Ok(None);
+                }
+            }
+        }
+        Some(ids)
+    };
+
+    let buttons = match create_cigarette_buttons(&db, panel_id, smoking_type_ids.as_deref()).await {
+        Ok(buttons) => buttons,
+        Err(e) => match e.downcast::<UnusableLabelError>() {
+            Ok(e) => {
+                frontend
+                    .send_reply(Reply::error(format!(
+                        "種類 ID {} の表示名が空のため、パネルを作成できません。種類の説明または名前を設定してください。",
+                        e.0
+                    )))
+                    .await?;
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        },
+    };
+    db.create_panel(panel_id, guild_id, channel_id, smoking_type_ids.as_deref())
+        .await?;
+
+    Ok(Some(buttons))
+}
+
+/// Returns the panel commands.
+pub fn commands() -> Vec<Command> {
+    vec![create_cigarette_ui(), panel()]
+}
@@ -0,0 +1,114 @@
+//! Vacation mode: pausing and resuming tracking.
+
+use chrono::NaiveDate;
+
+use crate::database::Database;
+use crate::embed::Title;
+use crate::format::format_date;
+use crate::frontend::{Frontend, Reply};
+use crate::{Context, Error};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+use super::Command;
+
+/// Maximum number of days a pause may last
+const MAX_PAUSE_DAYS: i64 = 366;
+
+/// Pauses tracking until a date (e.g. `pause-tracking 2025-02-01`).
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `until` - The last paused date.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "pause-tracking")]
+pub async fn pause_tracking(ctx: Context<'_>, until: NaiveDate) -> Result<(), Error> {
+    update_tracking_pause(
+        &ctx,
+        &ctx.data().database,
+        &ctx.author().id.get().to_string(),
+        &ctx.author().name,
+        ctx.data().clock.today(),
+        Some(until),
+    )
+    .await
+}
+
+/// Resumes tracking before the pause ends.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "resume-tracking")]
+pub async fn resume_tracking(ctx: Context<'_>) -> Result<(), Error> {
+    update_tracking_pause(
+        &ctx,
+        &ctx.data().database,
+        &ctx.author().id.get().to_string(),
+        &ctx.author().name,
+        ctx.data().clock.today(),
+        None,
+    )
+    .await
+}
+
+/// Pauses or resumes a user's tracking and confirms the change.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `user_id` - The Discord ID of the user.
+/// * `username` - The current username of the user.
+/// * `today` - The user's current local date; a pause starts on it.
+/// * `until` - The last paused date, or `None` to resume tracking today.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn update_tracking_pause(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    user_id: &str,
+    username: &str,
+    today: NaiveDate,
+    until: Option<NaiveDate>,
+) -> Result<(), Error> {
+    let reply = match until {
+        Some(until) if until < today => {
+            Reply::error("終了日には今日以降の日付を指定してください。")
+        }
+        Some(until) if (until - today).num_days() >= MAX_PAUSE_DAYS => {
+            Reply::error("一時停止できるのは1年先までです。")
+        }
+        Some(until) => {
+            {
+                let db = database.lock().await;
+                let user = db.get_or_create_user(user_id, username).await?;
+                db.pause_tracking(&user.discord_id, today, until).await?;
+            }
+            Reply::new(format!(
+                "{}まで記録を一時停止しました。この間はリマインダーが届かず、連続記録も途切れません。",
+                format_date(until, frontend.locale())
+            ))
+            .titled(Title::Settings)
+        }
+        None if database
+            .lock()
+            .await
+            .resume_tracking(user_id, today)
+            .await? =>
+        {
+            Reply::new("記録の一時停止を解除しました。").titled(Title::Settings)
+        }
+        None => Reply::new("記録は一時停止されていません。").titled(Title::Settings),
+    };
+
+    frontend.send_reply(reply).await
+}
+
+/// Returns the vacation mode commands.
+pub fn commands() -> Vec<Command> {
+    vec![pause_tracking(), resume_tracking()]
+}
@@ -0,0 +1,195 @@
+//! Push notifications instead of DMs, see `push`.
+
+use poise::serenity_prelude::futures::lock::Mutex;
+use tracing::warn;
+
+use crate::database::Database;
+use crate::frontend::{Frontend, Reply};
+use crate::notify::{Notification, NotifyChannel};
+use crate::push::{PushProviders, PushService};
+use crate::{Context, Error};
+
+use super::Command;
+
+/// Chooses where you receive notifications instead of DMs.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, subcommands("push_ntfy", "push_pushover", "push_off"))]
+pub async fn push(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new(
+        "使い方: push ntfy <トピック> / push pushover <ユーザーキー> / push off",
+    ))
+    .await
+}
+
+/// Receives notifications on an ntfy topic instead of DMs.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `topic` - The ntfy topic.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "ntfy")]
+pub async fn push_ntfy(ctx: Context<'_>, topic: String) -> Result<(), Error> {
+    subscribe_push(
+        &ctx,
+        &ctx.data().database,
+        &ctx.data().push,
+        &ctx.author().id.get().to_string(),
+        &ctx.author().name,
+        PushService::Ntfy,
+        &topic,
+    )
+    .await
+}
+
+/// Receives notifications through Pushover instead of DMs.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `user_key` - The Pushover user key.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "pushover")]
+pub async fn push_pushover(ctx: Context<'_>, user_key: String) -> Result<(), Error> {
+    subscribe_push(
+        &ctx,
+        &ctx.data().database,
+        &ctx.data().push,
+        &ctx.author().id.get().to_string(),
+        &ctx.author().name,
+        PushService::Pushover,
+        &user_key,
+    )
+    .await
+}
+
+/// Receives notifications as DMs again.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "off")]
+pub async fn push_off(ctx: Context<'_>) -> Result<(), Error> {
+    disable_push(
+        &ctx,
+        &ctx.data().database,
+        &ctx.author().id.get().to_string(),
+    )
+    .await
+}
+
+/// Sends a test notification to a push target and, if it arrives, makes its
+/// service the channel the user receives notifications on.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `providers` - The enabled push services.
+/// * `user_id` - The Discord ID of the user.
+/// * `username` - The current username of the user.
+/// * `service` - The push service.
+/// * `target` - The ntfy topic or Pushover user key.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn subscribe_push(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    providers: &PushProviders,
+    user_id: &str,
+    username: &str,
+    service: PushService,
+    target: &str,
+) -> Result<(), Error> {
+    let Some(provider) = providers.get(service) else {
+        return frontend
+            .send_reply(Reply::error(format!(
+                "このインスタンスでは{}が有効になっていません。",
+                service.display_name()
+            )))
+            .await;
+    };
+    if !service.is_valid_target(target) {
+        let message = match service {
+            PushService::Ntfy => "トピックは英数字、-、_ の64文字以内で指定してください。",
+            PushService::Pushover => "ユーザーキーは30文字の英数字です。",
+        };
+        return frontend.send_reply(Reply::error(message)).await;
+    }
+
+    let test = Notification {
+        title: "通知のテスト".to_string(),
+        message: "マイルストーンの通知はDMの代わりにここに届きます。".to_string(),
+    };
+    if let Err(e) = provider.push(target, &test).await {
+        warn!("Failed to push a test notification to {}: {}", user_id, e);
+        return frontend
+            .send_reply(Reply::error(format!(
+                "{}にテスト通知を送信できませんでした。",
+                service.display_name()
+            )))
+            .await;
+    }
+
+    {
+        let db = database.lock().await;
+        db.get_or_create_user(user_id, username).await?;
+        db.set_push_subscription(user_id, service.name(), target)
+            .await?;
+        db.set_notification_channel(user_id, Some(NotifyChannel::from(service).name()))
+            .await?;
+    }
+
+    frontend
+        .send_reply(Reply::new(format!(
+            "通知を{}で受け取るように設定しました。テスト通知が届いているか確認してください。",
+            service.display_name()
+        )))
+        .await
+}
+
+/// Makes a user receive notifications as DMs again and confirms it.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `user_id` - The Discord ID of the user.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn disable_push(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    user_id: &str,
+) -> Result<(), Error> {
+    let removed = {
+        let db = database.lock().await;
+        let removed = db.remove_push_subscription(user_id).await?;
+        if removed {
+            db.set_notification_channel(user_id, None).await?;
+        }
+        removed
+    };
+
+    let reply = if removed {
+        Reply::new("通知をDMで受け取るように戻しました。")
+    } else {
+        Reply::error("プッシュ通知は設定されていません。")
+    };
+
+    frontend.send_reply(reply).await
+}
+
+/// Returns the push notification commands.
+pub fn commands() -> Vec<Command> {
+    vec![push()]
+}
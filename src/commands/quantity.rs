@@ -0,0 +1,297 @@
+//! Logging several units at once from a command.
+//!
+//! `log <type> [quantity]` records a quantity without the panel, e.g. the
+//! cigarettes of an evening out. Quantities above `LARGE_QUANTITY_THRESHOLD`
+//! are more often typos (`200` for `20`) than real logs, so the logging
+//! service rejects them until confirmed; the command then asks with buttons
+//! that only its invoker can use. The HTTP API enforces the same threshold.
+
+use poise::serenity_prelude as serenity;
+
+use crate::embed::Title;
+use crate::format::{branding, format_count, format_date, format_summary_lines, Locale};
+use crate::frontend::{Frontend, InteractionFrontend, Reply};
+use crate::installation::interaction_guild;
+use crate::service::{LoggedSmoking, LoggingService, ServiceError};
+use crate::{Context, Data, Error};
+
+use super::Command;
+
+/// Prefix of the custom IDs of the confirmation buttons
+const QUANTITY_PREFIX: &str = "quantity:";
+
+/// A button of the confirmation of a large quantity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuantityId {
+    /// Logs the quantity
+    Confirm {
+        user_id: u64,
+        smoking_type_id: i32,
+        quantity: i32,
+    },
+    /// Drops the request
+    Cancel { user_id: u64 },
+}
+
+impl QuantityId {
+    /// Returns the user who asked to log the quantity.
+    pub fn user_id(&self) -> u64 {
+        match self {
+            Self::Confirm { user_id, .. } | Self::Cancel { user_id } => *user_id,
+        }
+    }
+
+    /// Encodes the custom ID of the button.
+    pub fn custom_id(&self) -> String {
+        match self {
+            Self::Confirm {
+                user_id,
+                smoking_type_id,
+                quantity,
+            } => format!(
+                "{}confirm:{}:{}:{}",
+                QUANTITY_PREFIX, user_id, smoking_type_id, quantity
+            ),
+            Self::Cancel { user_id } => format!("{}cancel:{}", QUANTITY_PREFIX, user_id),
+        }
+    }
+
+    /// Decodes the custom ID of a confirmation button.
+    ///
+    /// # Arguments
+    /// * `custom_id` - The custom ID of a pressed button.
+    ///
+    /// # Returns
+    /// The button, or `None` if it is not a confirmation button.
+    pub fn parse(custom_id: &str) -> Option<Self> {
+        let rest = custom_id.strip_prefix(QUANTITY_PREFIX)?;
+        let parts: Vec<&str> = rest.split(':').collect();
+
+        match parts.as_slice() {
+            ["confirm", user_id, smoking_type_id, quantity] => Some(Self::Confirm {
+                user_id: user_id.parse().ok()?,
+                smoking_type_id: smoking_type_id.parse().ok()?,
+                quantity: quantity.parse().ok()?,
+            }),
+            ["cancel", user_id] => Some(Self::Cancel {
+                user_id: user_id.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Builds the question whether a large quantity is correct.
+///
+/// # Arguments
+/// * `user_id` - The Discord ID of the user logging.
+/// * `smoking_type_id` - The ID of the smoking type.
+/// * `display_name` - The display name of the smoking type.
+/// * `quantity` - The requested quantity.
+/// * `threshold` - The largest quantity logged without confirmation.
+/// * `locale` - The locale of the user.
+///
+/// # Returns
+/// The question with confirm and cancel buttons.
+pub fn confirmation_prompt(
+    user_id: u64,
+    smoking_type_id: i32,
+    display_name: &str,
+    quantity: i32,
+    threshold: i32,
+    locale: Locale,
+) -> Reply {
+    Reply::new(format!(
+        "{}を{}記録しようとしています。1回の記録としては多いため（{}を超えています）、入力に間違いがないか確認してください。",
+        display_name,
+        format_count(i64::from(quantity), locale),
+        format_count(i64::from(threshold), locale)
+    ))
+    .button(
+        QuantityId::Confirm {
+            user_id,
+            smoking_type_id,
+            quantity,
+        }
+        .custom_id(),
+        "記録する",
+    )
+    .button(QuantityId::Cancel { user_id }.custom_id(), "キャンセル")
+}
+
+/// Builds the confirmation of a recorded quantity.
+fn logged_reply(logged: &LoggedSmoking, display_name: &str, locale: Locale) -> Reply {
+    Reply::new(format!(
+        "{}を{}記録しました。\n本日（{}）の累計本数\n{}",
+        display_name,
+        format_count(i64::from(logged.log.quantity), locale),
+        format_date(logged.date, locale),
+        format_summary_lines(&logged.daily_summary, locale).join("\n")
+    ))
+    .titled(Title::Confirmation)
+}
+
+/// Turns the outcome of logging into a reply; rejections become error messages.
+///
+/// # Arguments
+/// * `logging` - The logging service.
+/// * `user_id` - The Discord ID of the user logging.
+/// * `smoking_type_id` - The ID of the smoking type.
+/// * `result` - The outcome of logging.
+/// * `locale` - The locale of the user.
+///
+/// # Returns
+/// A Result containing the reply, or an `Error` for failures that are not rejections.
+async fn outcome_reply(
+    logging: &LoggingService,
+    user_id: u64,
+    smoking_type_id: i32,
+    result: Result<LoggedSmoking, ServiceError>,
+    locale: Locale,
+) -> Result<Reply, Error> {
+    let display_name = logging.display_name(smoking_type_id).await?;
+
+    Ok(match result {
+        Ok(logged) => logged_reply(&logged, &display_name, locale),
+        Err(ServiceError::UnconfirmedQuantity {
+            quantity,
+            threshold,
+        }) => confirmation_prompt(
+            user_id,
+            smoking_type_id,
+            &display_name,
+            quantity,
+            threshold,
+            locale,
+        ),
+        Err(ServiceError::InvalidQuantity) => Reply::error("本数は1以上で指定してください。"),
+        Err(ServiceError::ConsentRequired) => Reply::error(
+            "記録を始める前に、パネルのボタンを押してデータの取り扱いに同意してください。",
+        ),
+        Err(ServiceError::DailyCapReached { display_name, cap }) => Reply::error(format!(
+            "本日の{}はこのサーバーの上限（{}{}）に達しているため記録できません。上限を超えて記録する場合は ignore-caps on を使ってください。",
+            display_name,
+            cap,
+            branding().count_unit
+        )),
+        Err(e) => return Err(e.into()),
+    })
+}
+
+/// Logs a quantity of a smoking type, asking first if it is unusually large.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `type_name` - The type name of the smoking type (e.g. `iqos`).
+/// * `quantity` - The number of units; one if omitted.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command)]
+pub async fn log(ctx: Context<'_>, type_name: String, quantity: Option<i32>) -> Result<(), Error> {
+    let smoking_type = {
+        let db = ctx.data().database.lock().await;
+        db.find_smoking_type_by_name(&type_name).await?
+    };
+    let Some(smoking_type) = smoking_type else {
+        return ctx
+            .send_reply(Reply::error(format!(
+                "種類「{}」は存在しません。",
+                type_name
+            )))
+            .await;
+    };
+
+    let user_id = ctx.author().id.get();
+    let guild_id = ctx.guild_id().map(|guild_id| guild_id.to_string());
+    let logging = &ctx.data().logging;
+    let result = logging
+        .log_for_user(
+            &user_id.to_string(),
+            &ctx.author().name,
+            guild_id.as_deref(),
+            smoking_type.id,
+            quantity.unwrap_or(1),
+            false,
+            None,
+        )
+        .await;
+    let reply = outcome_reply(
+        logging,
+        user_id,
+        smoking_type.id,
+        result,
+        Frontend::locale(&ctx),
+    )
+    .await?;
+
+    ctx.send_reply(reply).await
+}
+
+/// Handles a press of a confirmation button, replacing the question with the outcome.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `mci` - The button press.
+/// * `control` - The pressed button.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub(crate) async fn handle_quantity(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &serenity::ComponentInteraction,
+    control: &QuantityId,
+) -> Result<(), Error> {
+    let frontend = InteractionFrontend::new(ctx, mci);
+    if mci.user.id.get() != control.user_id() {
+        return frontend
+            .respond(Reply::error("この確認は記録しようとした本人だけが操作できます。").ephemeral())
+            .await;
+    }
+
+    match control {
+        QuantityId::Cancel { .. } => {
+            frontend
+                .update(Reply::new("キャンセルしました。記録していません。"))
+                .await
+        }
+        QuantityId::Confirm {
+            user_id,
+            smoking_type_id,
+            quantity,
+        } => {
+            let guild_id = interaction_guild(mci).map(|guild_id| guild_id.to_string());
+            // Keyed by the question, so pressing the button twice logs once.
+            let idempotency_key = format!("quantity:{}", mci.message.id);
+            let result = data
+                .logging
+                .log_for_user(
+                    &user_id.to_string(),
+                    &mci.user.name,
+                    guild_id.as_deref(),
+                    *smoking_type_id,
+                    *quantity,
+                    true,
+                    Some(&idempotency_key),
+                )
+                .await;
+            let reply = outcome_reply(
+                &data.logging,
+                *user_id,
+                *smoking_type_id,
+                result,
+                frontend.locale(),
+            )
+            .await?;
+
+            frontend.update(reply).await
+        }
+    }
+}
+
+/// Returns the quantity logging commands.
+pub fn commands() -> Vec<Command> {
+    vec![log()]
+}
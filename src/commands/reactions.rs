@@ -0,0 +1,271 @@
+//! Per-guild confirmation settings for the panel: the reaction mode and the
+//! cleanup of old confirmation messages.
+
+use crate::confirmation_cleanup::{MAX_MAX_AGE_HOURS, MIN_MAX_AGE_HOURS};
+use crate::database::Database;
+use crate::embed::Title;
+use crate::frontend::{Frontend, Reply};
+use crate::{Context, Error};
+use poise::serenity_prelude::{futures::lock::Mutex, EmojiId, EmojiIdentifier};
+
+use super::Command;
+
+/// Manages how panel presses are confirmed in this guild.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("reaction_mode_on", "reaction_mode_off")
+)]
+pub async fn reaction_mode(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new(
+        "使い方: reaction_mode on <サーバーの絵文字> / reaction_mode off",
+    ))
+    .await
+}
+
+/// Confirms panel presses by reacting with one of this guild's emojis instead of sending a message.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `emoji` - The guild emoji to react with.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "on"
+)]
+pub async fn reaction_mode_on(ctx: Context<'_>, emoji: String) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?;
+    let guild_emojis: Vec<EmojiId> = guild_id
+        .emojis(ctx)
+        .await?
+        .into_iter()
+        .map(|emoji| emoji.id)
+        .collect();
+
+    update_confirmation_reaction(
+        &ctx,
+        &ctx.data().database,
+        &guild_id.to_string(),
+        Some(&emoji),
+        &guild_emojis,
+    )
+    .await
+}
+
+/// Confirms panel presses with a message again.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "off"
+)]
+pub async fn reaction_mode_off(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?;
+
+    update_confirmation_reaction(&ctx, &ctx.data().database, &guild_id.to_string(), None, &[]).await
+}
+
+/// Stores (or removes) a guild's confirmation reaction and confirms the change.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `guild_id` - The ID of the guild.
+/// * `emoji` - The custom emoji as typed in Discord, or `None` to confirm with messages.
+/// * `guild_emojis` - The IDs of the guild's emojis; the emoji must be one of them.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn update_confirmation_reaction(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    guild_id: &str,
+    emoji: Option<&str>,
+    guild_emojis: &[EmojiId],
+) -> Result<(), Error> {
+    let emoji = match emoji.map(|emoji| emoji.trim().parse::<EmojiIdentifier>()) {
+        Some(Ok(emoji)) if guild_emojis.contains(&emoji.id) => Some(emoji),
+        Some(_) => {
+            return frontend
+                .send_reply(Reply::error(
+                    "このサーバーのカスタム絵文字を指定してください。",
+                ))
+                .await;
+        }
+        None => None,
+    };
+
+    let reply = {
+        let db = database.lock().await;
+        let guild = db.guild(guild_id);
+        match emoji {
+            Some(emoji) => {
+                guild.set_confirmation_reaction(&emoji.to_string()).await?;
+                format!(
+                    "パネルの記録をメッセージの代わりに {} のリアクションで確認します。",
+                    emoji
+                )
+            }
+            None if guild.remove_confirmation_reaction().await? => {
+                "パネルの記録をメッセージで確認します。".to_string()
+            }
+            None => "リアクションでの確認は設定されていません。".to_string(),
+        }
+    };
+
+    frontend
+        .send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Manages the cleanup of old panel confirmations in this guild.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("confirmation_cleanup_on", "confirmation_cleanup_off")
+)]
+pub async fn confirmation_cleanup(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let max_age_hours = ctx
+        .data()
+        .database
+        .lock()
+        .await
+        .guild(&guild_id)
+        .get_confirmation_cleanup()
+        .await?;
+
+    let status = match max_age_hours {
+        Some(hours) => format!("パネルの確認メッセージを{}時間後に削除しています。", hours),
+        None => "パネルの確認メッセージは削除していません。".to_string(),
+    };
+    ctx.send_reply(Reply::new(format!(
+        "{}\n使い方: confirmation_cleanup on <時間> / confirmation_cleanup off",
+        status
+    )))
+    .await
+}
+
+/// Deletes the bot's panel confirmations once they are older than a number of hours.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `hours` - How long confirmations stay.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "on"
+)]
+pub async fn confirmation_cleanup_on(ctx: Context<'_>, hours: i32) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?;
+
+    update_confirmation_cleanup(
+        &ctx,
+        &ctx.data().database,
+        &guild_id.to_string(),
+        Some(hours),
+    )
+    .await
+}
+
+/// Keeps panel confirmations again.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "off"
+)]
+pub async fn confirmation_cleanup_off(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?;
+
+    update_confirmation_cleanup(&ctx, &ctx.data().database, &guild_id.to_string(), None).await
+}
+
+/// Stores (or removes) how long a guild's panel confirmations stay and confirms the change.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `guild_id` - The ID of the guild.
+/// * `max_age_hours` - How long confirmations stay, or `None` to keep them.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn update_confirmation_cleanup(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    guild_id: &str,
+    max_age_hours: Option<i32>,
+) -> Result<(), Error> {
+    if let Some(hours) = max_age_hours {
+        if !(MIN_MAX_AGE_HOURS..=MAX_MAX_AGE_HOURS).contains(&hours) {
+            return frontend
+                .send_reply(Reply::error(format!(
+                    "時間は{}〜{}の範囲で指定してください。",
+                    MIN_MAX_AGE_HOURS, MAX_MAX_AGE_HOURS
+                )))
+                .await;
+        }
+    }
+
+    let reply = {
+        let db = database.lock().await;
+        let guild = db.guild(guild_id);
+        match max_age_hours {
+            Some(hours) => {
+                guild.set_confirmation_cleanup(hours).await?;
+                format!(
+                    "これから送るパネルの確認メッセージを{}時間後に削除します。",
+                    hours
+                )
+            }
+            None if guild.remove_confirmation_cleanup().await? => {
+                "パネルの確認メッセージを削除しないようにしました。".to_string()
+            }
+            None => "確認メッセージの削除は設定されていません。".to_string(),
+        }
+    };
+
+    frontend
+        .send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Returns the panel confirmation commands.
+pub fn commands() -> Vec<Command> {
+    vec![reaction_mode(), confirmation_cleanup()]
+}
@@ -0,0 +1,159 @@
+//! Configuration of smoke-free milestone roles.
+
+use crate::embed::Title;
+use crate::format::format_number;
+use crate::frontend::{Frontend, Reply};
+use crate::milestones::Milestone;
+use crate::{Context, Error};
+use poise::serenity_prelude as serenity;
+
+use super::Command;
+
+/// Manages roles assigned automatically for smoke-free milestones.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_ROLES",
+    subcommands("roles_map", "roles_unmap", "roles_list")
+)]
+pub async fn roles(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new(
+        "使い方: roles map <日数>days @ロール / roles unmap @ロール / roles list",
+    ))
+    .await
+}
+
+/// Maps a role to a smoke-free milestone (e.g. `roles map 7days @SmokeFreeWeek`).
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `milestone` - The milestone, written as e.g. `7days`.
+/// * `role` - The role to assign when the milestone is reached.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_ROLES",
+    rename = "map"
+)]
+pub async fn roles_map(
+    ctx: Context<'_>,
+    milestone: Milestone,
+    role: serenity::Role,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?;
+
+    ctx.data()
+        .database
+        .lock()
+        .await
+        .guild(&guild_id.to_string())
+        .set_milestone_role(&role.id.to_string(), milestone.smoke_free_days)
+        .await?;
+
+    ctx.send_reply(
+        Reply::new(format!(
+            "禁煙{}日で「{}」を付与するように設定しました。",
+            format_number(milestone.smoke_free_days.into(), Frontend::locale(&ctx)),
+            role.name
+        ))
+        .titled(Title::Settings),
+    )
+    .await
+}
+
+/// Removes a milestone role mapping.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `role` - The mapped role.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_ROLES",
+    rename = "unmap"
+)]
+pub async fn roles_unmap(ctx: Context<'_>, role: serenity::Role) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?;
+
+    let removed = ctx
+        .data()
+        .database
+        .lock()
+        .await
+        .guild(&guild_id.to_string())
+        .remove_milestone_role(&role.id.to_string())
+        .await?;
+
+    let reply = if removed {
+        format!("「{}」の設定を削除しました。", role.name)
+    } else {
+        format!("「{}」は設定されていません。", role.name)
+    };
+    ctx.send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Lists the milestone role mappings of the guild.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_ROLES",
+    rename = "list"
+)]
+pub async fn roles_list(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?;
+
+    let milestone_roles = ctx
+        .data()
+        .database
+        .lock()
+        .await
+        .guild(&guild_id.to_string())
+        .get_milestone_roles()
+        .await?;
+
+    let reply = if milestone_roles.is_empty() {
+        "マイルストーンロールは設定されていません。".to_string()
+    } else {
+        milestone_roles
+            .into_iter()
+            .map(|milestone_role| {
+                format!(
+                    "\n禁煙{}日: <@&{}>",
+                    format_number(
+                        milestone_role.smoke_free_days.into(),
+                        Frontend::locale(&ctx)
+                    ),
+                    milestone_role.role_id
+                )
+            })
+            .fold("マイルストーンロール".to_string(), |acc, line| {
+                acc + line.as_str()
+            })
+    };
+    ctx.send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Returns the milestone role commands.
+pub fn commands() -> Vec<Command> {
+    vec![roles()]
+}
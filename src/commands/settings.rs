@@ -0,0 +1,480 @@
+//! User and guild settings.
+//!
+//! `settings` shows a member's own settings (daily goal, smoke-break prompt,
+//! ignoring guild caps, sharing logs in guild data exports, quiet
+//! confirmations) in one message, with a select menu and toggle buttons
+//! that change them in place instead of a separate command per setting. The
+//! controls only work for the member the panel was shown to.
+//!
+//! A guild can also count its days in its own time zone, separate from the
+//! bot's, with `settings timezone`. Guild-scoped date math (server statistics,
+//! daily caps) uses it; personal summaries keep using the bot's time zone.
+//! `settings setup` posts the onboarding wizard again.
+
+use chrono_tz::Tz;
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+
+use crate::database::Database;
+use crate::embed::Title;
+use crate::format::{format_count, Locale};
+use crate::frontend::{Frontend, InteractionFrontend, Reply};
+use crate::onboarding::render_wizard;
+use crate::service::StatsService;
+use crate::{Context, Data, Error};
+
+use super::Command;
+
+/// Argument of `settings timezone` going back to the bot's time zone
+const RESET_TIMEZONE: &str = "reset";
+
+/// Prefix of the custom IDs of settings controls
+const SETTINGS_PREFIX: &str = "settings:";
+
+/// Select-menu value clearing the daily goal
+const NO_GOAL: &str = "none";
+
+/// Daily goals offered in the select menu
+const GOAL_CHOICES: [i64; 6] = [3, 5, 10, 15, 20, 30];
+
+/// A user setting changed from the settings panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    /// The daily goal, chosen from a select menu
+    DailyGoal,
+    /// Whether smoke-break channels send a panel by DM, toggled by a button
+    SmokeBreakPrompt,
+    /// Whether guild daily caps are ignored, toggled by a button
+    IgnoreCaps,
+    /// Whether raw logs are included in guild data exports, toggled by a button
+    ShareLogs,
+    /// Whether later presses of a day edit the day's first confirmation, toggled by a button
+    QuietConfirmations,
+}
+
+impl Setting {
+    /// Returns the name of the setting in custom IDs.
+    fn name(self) -> &'static str {
+        match self {
+            Self::DailyGoal => "goal",
+            Self::SmokeBreakPrompt => "smoke_break",
+            Self::IgnoreCaps => "ignore_caps",
+            Self::ShareLogs => "share_logs",
+            Self::QuietConfirmations => "quiet",
+        }
+    }
+}
+
+/// A control of the settings panel: which setting it changes, and whose
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettingsId {
+    /// The Discord ID of the user the panel was shown to
+    pub owner_id: String,
+    /// The setting the control changes
+    pub setting: Setting,
+}
+
+impl SettingsId {
+    /// Encodes the custom ID of the control.
+    pub fn custom_id(&self) -> String {
+        format!(
+            "{}{}:{}",
+            SETTINGS_PREFIX,
+            self.owner_id,
+            self.setting.name()
+        )
+    }
+
+    /// Decodes the custom ID of a settings control.
+    ///
+    /// # Arguments
+    /// * `custom_id` - The custom ID of a used component.
+    ///
+    /// # Returns
+    /// The control, or `None` if the component is not a settings control.
+    pub fn parse(custom_id: &str) -> Option<Self> {
+        let (owner_id, setting) = custom_id.strip_prefix(SETTINGS_PREFIX)?.split_once(':')?;
+        if owner_id.is_empty() || !owner_id.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        let setting = [
+            Setting::DailyGoal,
+            Setting::SmokeBreakPrompt,
+            Setting::IgnoreCaps,
+            Setting::ShareLogs,
+            Setting::QuietConfirmations,
+        ]
+        .into_iter()
+        .find(|candidate| candidate.name() == setting)?;
+
+        Some(Self {
+            owner_id: owner_id.to_string(),
+            setting,
+        })
+    }
+}
+
+/// Shows your settings with controls to change them.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, subcommands("settings_timezone", "settings_setup"))]
+pub async fn settings(ctx: Context<'_>) -> Result<(), Error> {
+    let reply = render_user_settings(
+        &ctx.data().database,
+        &ctx.author().id.get().to_string(),
+        Frontend::locale(&ctx),
+    )
+    .await?;
+
+    ctx.send_reply(reply).await
+}
+
+/// Renders a user's settings panel.
+///
+/// # Arguments
+/// * `database` - The database.
+/// * `user_id` - The Discord ID of the user.
+/// * `locale` - The locale counts are formatted for.
+///
+/// # Returns
+/// A Result containing the ephemeral panel or an `Error`.
+pub async fn render_user_settings(
+    database: &Mutex<Database>,
+    user_id: &str,
+    locale: Locale,
+) -> Result<Reply, Error> {
+    let (goal, smoke_break_prompt, ignore_caps, share_logs, quiet) = {
+        let db = database.lock().await;
+        (
+            db.get_daily_goal(user_id).await?,
+            db.smoke_break_prompt_enabled(user_id).await?,
+            db.get_ignore_type_caps(user_id).await?,
+            db.get_share_logs_in_exports(user_id).await?,
+            db.get_quiet_confirmations(user_id).await?,
+        )
+    };
+
+    let on_off = |enabled: bool| if enabled { "オン" } else { "オフ" };
+    let content = [
+        "あなたの設定".to_string(),
+        format!(
+            "1日の目標: {}",
+            goal.map_or("なし".to_string(), |goal| format_count(
+                goal.into(),
+                locale
+            ))
+        ),
+        format!("喫煙所のパネル送信: {}", on_off(smoke_break_prompt)),
+        format!("サーバーの上限を無視: {}", on_off(ignore_caps)),
+        format!(
+            "サーバーのデータ書き出しに記録を含める: {}",
+            on_off(share_logs)
+        ),
+        format!("確認メッセージの簡潔表示: {}", on_off(quiet)),
+        "サーバーのタイムゾーンは settings timezone で変更できます。".to_string(),
+    ]
+    .join("\n");
+
+    let id = |setting| {
+        SettingsId {
+            owner_id: user_id.to_string(),
+            setting,
+        }
+        .custom_id()
+    };
+    let mut goals = vec![(NO_GOAL.to_string(), "目標なし".to_string())];
+    goals.extend(
+        GOAL_CHOICES
+            .iter()
+            .map(|goal| (goal.to_string(), format_count(*goal, locale))),
+    );
+
+    Ok(Reply::new(content)
+        .titled(Title::Settings)
+        .ephemeral()
+        .button(
+            id(Setting::SmokeBreakPrompt),
+            format!("喫煙所のパネル送信を{}にする", on_off(!smoke_break_prompt)),
+        )
+        .button(
+            id(Setting::IgnoreCaps),
+            format!("上限の無視を{}にする", on_off(!ignore_caps)),
+        )
+        .button(
+            id(Setting::ShareLogs),
+            format!("書き出しへの記録の提供を{}にする", on_off(!share_logs)),
+        )
+        .button(
+            id(Setting::QuietConfirmations),
+            format!("簡潔表示を{}にする", on_off(!quiet)),
+        )
+        .select(id(Setting::DailyGoal), "1日の目標を選ぶ", goals))
+}
+
+/// Handles a use of a settings control: changes the setting and updates the panel in place.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `mci` - The component interaction.
+/// * `control` - The control encoded in the component.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub(crate) async fn handle_settings(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &serenity::ComponentInteraction,
+    control: &SettingsId,
+) -> Result<(), Error> {
+    let value = match &mci.data.kind {
+        serenity::ComponentInteractionDataKind::StringSelect { values } => values.first(),
+        _ => None,
+    };
+    let updated = apply_user_setting(
+        &data.database,
+        &mci.user.id.get().to_string(),
+        &mci.user.name,
+        control,
+        value.map(String::as_str),
+        Locale::from_discord(Some(&mci.locale)),
+    )
+    .await?;
+
+    match updated {
+        Some(reply) => InteractionFrontend::new(ctx, mci).update(reply).await,
+        None => {
+            InteractionFrontend::new(ctx, mci)
+                .respond(
+                    Reply::error("この設定はコマンドを実行した人だけが変更できます。").ephemeral(),
+                )
+                .await
+        }
+    }
+}
+
+/// Changes the setting a control stands for, if the user may do so.
+///
+/// Toggles flip the stored value, so a stale panel still changes the current state.
+///
+/// # Arguments
+/// * `database` - The database.
+/// * `presser_id` - The Discord ID of the user who used the control.
+/// * `presser_name` - The current username of that user.
+/// * `control` - The control encoded in the component.
+/// * `value` - The chosen option of a select menu, `None` for buttons.
+/// * `locale` - The locale counts are formatted for.
+///
+/// # Returns
+/// A Result containing the updated panel, `None` if the user is not the panel's owner, or an `Error`.
+pub async fn apply_user_setting(
+    database: &Mutex<Database>,
+    presser_id: &str,
+    presser_name: &str,
+    control: &SettingsId,
+    value: Option<&str>,
+    locale: Locale,
+) -> Result<Option<Reply>, Error> {
+    if presser_id != control.owner_id {
+        return Ok(None);
+    }
+
+    {
+        let db = database.lock().await;
+        let user = db.get_or_create_user(presser_id, presser_name).await?;
+        match control.setting {
+            Setting::DailyGoal => match value {
+                Some(NO_GOAL) => db.set_daily_goal(&user.discord_id, None).await?,
+                Some(goal) => {
+                    // Anything but an offered option is a forged or outdated menu and is ignored.
+                    if let Some(goal) = goal
+                        .parse::<i64>()
+                        .ok()
+                        .filter(|goal| GOAL_CHOICES.contains(goal))
+                    {
+                        db.set_daily_goal(&user.discord_id, Some(goal as i32))
+                            .await?;
+                    }
+                }
+                None => {}
+            },
+            Setting::SmokeBreakPrompt => {
+                let enabled = db.smoke_break_prompt_enabled(&user.discord_id).await?;
+                db.set_smoke_break_prompt(&user.discord_id, !enabled)
+                    .await?;
+            }
+            Setting::IgnoreCaps => {
+                let ignore = db.get_ignore_type_caps(&user.discord_id).await?;
+                db.set_ignore_type_caps(&user.discord_id, !ignore).await?;
+            }
+            Setting::ShareLogs => {
+                let share = db.get_share_logs_in_exports(&user.discord_id).await?;
+                db.set_share_logs_in_exports(&user.discord_id, !share)
+                    .await?;
+            }
+            Setting::QuietConfirmations => {
+                let quiet = db.get_quiet_confirmations(&user.discord_id).await?;
+                db.set_quiet_confirmations(&user.discord_id, !quiet).await?;
+            }
+        }
+    }
+
+    render_user_settings(database, presser_id, locale)
+        .await
+        .map(Some)
+}
+
+/// Shows or sets the time zone this guild's days are counted in.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `timezone` - An IANA time zone name (e.g. `Asia/Tokyo`), `reset`, or `None` to show the current one.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "timezone"
+)]
+pub async fn settings_timezone(ctx: Context<'_>, timezone: Option<String>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let data = ctx.data();
+    let default = data.clock.timezone();
+
+    match timezone {
+        Some(timezone) => {
+            update_guild_timezone(
+                &ctx,
+                &data.database,
+                &data.stats,
+                &guild_id,
+                &timezone,
+                default,
+            )
+            .await
+        }
+        None => show_guild_timezone(&ctx, &data.database, &guild_id, default).await,
+    }
+}
+
+/// Posts the guild setup wizard in the channel.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "setup"
+)]
+pub async fn settings_setup(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let data = ctx.data();
+    let reply = render_wizard(&data.database, &guild_id, data.clock.timezone()).await?;
+
+    ctx.send_reply(reply).await
+}
+
+/// Tells which time zone a guild's days are counted in.
+///
+/// # Arguments
+/// * `frontend` - Where the reply is sent.
+/// * `database` - The database.
+/// * `guild_id` - The ID of the guild.
+/// * `default` - The bot's time zone.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn show_guild_timezone(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    guild_id: &str,
+    default: Tz,
+) -> Result<(), Error> {
+    let timezone = database.lock().await.guild(guild_id).get_timezone().await?;
+
+    let reply = match timezone {
+        Some(timezone) => format!("このサーバーのタイムゾーンは{}です。", timezone),
+        None => format!(
+            "このサーバーはボットのタイムゾーン（{}）を使っています。",
+            default
+        ),
+    };
+
+    frontend
+        .send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Stores a guild's time zone (or goes back to the bot's) and confirms the change.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `stats` - The statistics service whose cached guild aggregates are dropped.
+/// * `guild_id` - The ID of the guild.
+/// * `timezone` - An IANA time zone name, or `reset`.
+/// * `default` - The bot's time zone.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn update_guild_timezone(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    stats: &StatsService,
+    guild_id: &str,
+    timezone: &str,
+    default: Tz,
+) -> Result<(), Error> {
+    let timezone = timezone.trim();
+    let (timezone, reply) = if timezone.eq_ignore_ascii_case(RESET_TIMEZONE) {
+        (
+            None,
+            format!(
+                "サーバーのタイムゾーンをボットの設定（{}）に戻しました。",
+                default
+            ),
+        )
+    } else {
+        match timezone.parse::<Tz>() {
+            Ok(timezone) => (
+                Some(timezone),
+                format!("サーバーのタイムゾーンを{}にしました。", timezone),
+            ),
+            Err(_) => {
+                return frontend
+                    .send_reply(Reply::error(format!(
+                        "不明なタイムゾーンです: {}（例: Asia/Tokyo）",
+                        timezone
+                    )))
+                    .await;
+            }
+        }
+    };
+
+    database
+        .lock()
+        .await
+        .guild(guild_id)
+        .set_timezone(timezone)
+        .await?;
+    stats.forget_guild(guild_id);
+
+    frontend
+        .send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Returns the settings commands.
+pub fn commands() -> Vec<Command> {
+    vec![settings()]
+}
@@ -0,0 +1,180 @@
+//! Smoke-break prompts sent when joining a designated voice channel.
+
+use crate::database::Database;
+use crate::embed::Title;
+use crate::frontend::{Frontend, Reply};
+use crate::{Context, Error};
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+
+use super::Command;
+
+/// Manages smoke-break prompts sent when joining a designated voice channel.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    subcommands(
+        "smoke_break_on",
+        "smoke_break_off",
+        "smoke_break_channel",
+        "smoke_break_unset"
+    )
+)]
+pub async fn smoke_break(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new("使い方: smoke_break on / smoke_break off / smoke_break channel #VC / smoke_break unset #VC")).await
+}
+
+/// Opts the author in to smoke-break prompts.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "on")]
+pub async fn smoke_break_on(ctx: Context<'_>) -> Result<(), Error> {
+    update_smoke_break_prompt(
+        &ctx,
+        &ctx.data().database,
+        &ctx.author().id.get().to_string(),
+        &ctx.author().name,
+        true,
+    )
+    .await
+}
+
+/// Opts the author out of smoke-break prompts.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "off")]
+pub async fn smoke_break_off(ctx: Context<'_>) -> Result<(), Error> {
+    update_smoke_break_prompt(
+        &ctx,
+        &ctx.data().database,
+        &ctx.author().id.get().to_string(),
+        &ctx.author().name,
+        false,
+    )
+    .await
+}
+
+/// Stores a user's smoke-break prompt preference and confirms the change.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `user_id` - The Discord ID of the user.
+/// * `username` - The current username of the user.
+/// * `enabled` - Whether the user wants to be prompted.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn update_smoke_break_prompt(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    user_id: &str,
+    username: &str,
+    enabled: bool,
+) -> Result<(), Error> {
+    {
+        let db = database.lock().await;
+        let user = db.get_or_create_user(user_id, username).await?;
+        db.set_smoke_break_prompt(&user.discord_id, enabled).await?;
+    }
+
+    let reply = if enabled {
+        "喫煙所に入室したときにDMでパネルを送信します。"
+    } else {
+        "喫煙所のパネル送信を停止しました。"
+    };
+    frontend
+        .send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Designates a voice channel as a smoke-break channel.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `channel` - The voice channel.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_CHANNELS",
+    rename = "channel"
+)]
+pub async fn smoke_break_channel(
+    ctx: Context<'_>,
+    channel: serenity::GuildChannel,
+) -> Result<(), Error> {
+    if channel.kind != serenity::ChannelType::Voice {
+        return ctx
+            .send_reply(Reply::error("ボイスチャンネルを指定してください。"))
+            .await;
+    }
+
+    ctx.data()
+        .database
+        .lock()
+        .await
+        .guild(&channel.guild_id.to_string())
+        .add_smoke_break_channel(&channel.id.to_string())
+        .await?;
+
+    ctx.send_reply(
+        Reply::new(format!("{}を喫煙所に設定しました。", channel.name)).titled(Title::Settings),
+    )
+    .await
+}
+
+/// Removes a smoke-break channel designation.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `channel` - The voice channel.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_CHANNELS",
+    rename = "unset"
+)]
+pub async fn smoke_break_unset(
+    ctx: Context<'_>,
+    channel: serenity::GuildChannel,
+) -> Result<(), Error> {
+    let removed = ctx
+        .data()
+        .database
+        .lock()
+        .await
+        .guild(&channel.guild_id.to_string())
+        .remove_smoke_break_channel(&channel.id.to_string())
+        .await?;
+
+    let reply = if removed {
+        format!("{}の喫煙所設定を解除しました。", channel.name)
+    } else {
+        format!("{}は喫煙所に設定されていません。", channel.name)
+    };
+    ctx.send_reply(Reply::new(reply).titled(Title::Settings))
+        .await
+}
+
+/// Returns the smoke-break commands.
+pub fn commands() -> Vec<Command> {
+    vec![smoke_break()]
+}
@@ -0,0 +1,597 @@
+//! Monthly charts and guild and group DM aggregates, served from the
+//! statistics cache.
+//!
+//! Every view says how long ago it was computed and carries a 🔄 button that
+//! recomputes it in place. `compare-periods` compares a user's totals between
+//! two periods, and `stats weekly` / `stats monthly` the current week or month
+//! with the previous one; both are computed on every call.
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use poise::serenity_prelude as serenity;
+
+use crate::database::GuildTypeTotal;
+use crate::embed::Title;
+use crate::format::{branding, format_count, format_date, format_number, Locale};
+use crate::frontend::{Frontend, InteractionFrontend, Reply};
+use crate::periods::{
+    compare, parse_periods, Period, PeriodComparison, PeriodSummary, SummarySpan,
+};
+use crate::presets::format_price;
+use crate::service::StatsService;
+use crate::{Context, Data, Error};
+
+use super::Command;
+
+/// Prefix of the custom IDs of refresh buttons
+const REFRESH_PREFIX: &str = "stats:refresh:";
+
+/// Longest bar drawn in a monthly chart
+const MAX_BAR_LENGTH: i64 = 20;
+
+/// A statistics view that can be refreshed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatsView {
+    /// A user's daily totals over a month
+    Month {
+        discord_id: String,
+        month: NaiveDate,
+    },
+    /// A guild's totals per smoking type over a month
+    Guild { guild_id: String, month: NaiveDate },
+    /// The totals per smoking type of the logs recorded in a DM or group DM over a month
+    Channel {
+        channel_id: String,
+        month: NaiveDate,
+    },
+}
+
+impl StatsView {
+    /// Encodes the custom ID of the view's refresh button.
+    pub fn refresh_id(&self) -> String {
+        match self {
+            Self::Month { discord_id, month } => {
+                format!("{}month:{}:{}", REFRESH_PREFIX, discord_id, month)
+            }
+            Self::Guild { guild_id, month } => {
+                format!("{}guild:{}:{}", REFRESH_PREFIX, guild_id, month)
+            }
+            Self::Channel { channel_id, month } => {
+                format!("{}channel:{}:{}", REFRESH_PREFIX, channel_id, month)
+            }
+        }
+    }
+
+    /// Decodes the custom ID of a refresh button.
+    ///
+    /// # Arguments
+    /// * `custom_id` - The custom ID of a pressed button.
+    ///
+    /// # Returns
+    /// The view to refresh, or `None` if the button is not a refresh button.
+    pub fn parse_refresh_id(custom_id: &str) -> Option<Self> {
+        let mut parts = custom_id.strip_prefix(REFRESH_PREFIX)?.split(':');
+        let (kind, id, month) = (parts.next()?, parts.next()?, parts.next()?);
+        if parts.next().is_some() || id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let month: NaiveDate = month.parse().ok()?;
+        if month.day() != 1 {
+            return None;
+        }
+
+        match kind {
+            "month" => Some(Self::Month {
+                discord_id: id.to_string(),
+                month,
+            }),
+            "guild" => Some(Self::Guild {
+                guild_id: id.to_string(),
+                month,
+            }),
+            "channel" => Some(Self::Channel {
+                channel_id: id.to_string(),
+                month,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Shows statistics.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    subcommands(
+        "stats_month",
+        "stats_guild",
+        "stats_group",
+        "stats_weekly",
+        "stats_monthly"
+    )
+)]
+pub async fn stats(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new(
+        "使い方: stats month / stats guild / stats group / stats weekly / stats monthly",
+    ))
+    .await
+}
+
+/// Shows your totals this week against last week.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "weekly")]
+pub async fn stats_weekly(ctx: Context<'_>) -> Result<(), Error> {
+    show_period_summary(
+        &ctx,
+        &ctx.data().stats,
+        &ctx.author().id.get().to_string(),
+        SummarySpan::Week,
+    )
+    .await
+}
+
+/// Shows your totals this month against last month.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "monthly")]
+pub async fn stats_monthly(ctx: Context<'_>) -> Result<(), Error> {
+    show_period_summary(
+        &ctx,
+        &ctx.data().stats,
+        &ctx.author().id.get().to_string(),
+        SummarySpan::Month,
+    )
+    .await
+}
+
+/// Shows your daily totals over the current month.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "month")]
+pub async fn stats_month(ctx: Context<'_>) -> Result<(), Error> {
+    let stats = &ctx.data().stats;
+    let view = StatsView::Month {
+        discord_id: ctx.author().id.get().to_string(),
+        month: stats.current_month(),
+    };
+
+    show_stats(&ctx, stats, &view, ctx.data().clock.now()).await
+}
+
+/// Shows this guild's totals per smoking type over the current month in the guild's time zone.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, guild_only, rename = "guild")]
+pub async fn stats_guild(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?.to_string();
+    let stats = &ctx.data().stats;
+    let view = StatsView::Guild {
+        month: stats.guild_current_month(&guild_id).await?,
+        guild_id,
+    };
+
+    show_stats(&ctx, stats, &view, ctx.data().clock.now()).await
+}
+
+/// Shows the totals per smoking type of the panel presses in this DM or group
+/// DM over the current month.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "group")]
+pub async fn stats_group(ctx: Context<'_>) -> Result<(), Error> {
+    if ctx.guild_id().is_some() {
+        return ctx
+            .send_reply(Reply::error("サーバーでは stats guild を使ってください。"))
+            .await;
+    }
+    let stats = &ctx.data().stats;
+    let view = StatsView::Channel {
+        channel_id: ctx.channel_id().to_string(),
+        month: stats.current_month(),
+    };
+
+    show_stats(&ctx, stats, &view, ctx.data().clock.now()).await
+}
+
+/// Compares your consumption between two periods.
+///
+/// Each period is given as two dates (`2024-04-29 2024-05-05`) or as a
+/// shortcut such as `golden-week` or `last-month`.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `periods` - The two periods.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, rename = "compare-periods")]
+pub async fn compare_periods(ctx: Context<'_>, periods: Vec<String>) -> Result<(), Error> {
+    let args: Vec<&str> = periods.iter().map(String::as_str).collect();
+
+    show_period_comparison(
+        &ctx,
+        &ctx.data().stats,
+        &ctx.author().id.get().to_string(),
+        &args,
+    )
+    .await
+}
+
+/// Compares a user's totals between two periods and sends the comparison.
+///
+/// # Arguments
+/// * `frontend` - Where the comparison is sent.
+/// * `stats` - The statistics service.
+/// * `discord_id` - The Discord ID of the user.
+/// * `args` - The periods, each as a shortcut or as two dates.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn show_period_comparison(
+    frontend: &dyn Frontend,
+    stats: &StatsService,
+    discord_id: &str,
+    args: &[&str],
+) -> Result<(), Error> {
+    let (first, second) = match parse_periods(args, stats.today()) {
+        Ok(periods) => periods,
+        Err(message) => return frontend.send_reply(Reply::error(message)).await,
+    };
+    let comparison = compare(
+        &stats.period_type_totals(discord_id, first).await?,
+        &stats.period_type_totals(discord_id, second).await?,
+    );
+    let lines = comparison_lines(first, second, &comparison, frontend.locale());
+
+    frontend
+        .send_reply(Reply::new(lines.join("\n")).titled(Title::Stats))
+        .await
+}
+
+/// Renders a comparison of two periods.
+///
+/// # Arguments
+/// * `first` - The first period.
+/// * `second` - The second period.
+/// * `comparison` - The comparison of the periods.
+/// * `locale` - The locale numbers and dates are formatted for.
+///
+/// # Returns
+/// The lines.
+pub fn comparison_lines(
+    first: Period,
+    second: Period,
+    comparison: &PeriodComparison,
+    locale: Locale,
+) -> Vec<String> {
+    let period_line = |label: &str, period: Period| {
+        format!(
+            "{}: {}〜{}（{}日）",
+            label,
+            format_date(period.from, locale),
+            format_date(period.to, locale),
+            period.days()
+        )
+    };
+    let mut lines = vec![
+        period_line("期間A", first),
+        period_line("期間B", second),
+        format!(
+            "合計: {} → {}（{}{}）",
+            format_count(comparison.first_total, locale),
+            format_count(comparison.second_total, locale),
+            format_delta(comparison.second_total - comparison.first_total, locale),
+            branding().count_unit
+        ),
+        format!(
+            "1日平均: {:.1}{unit} → {:.1}{unit}",
+            comparison.first_total as f64 / first.days() as f64,
+            comparison.second_total as f64 / second.days() as f64,
+            unit = branding().count_unit
+        ),
+    ];
+    if comparison.types.is_empty() {
+        lines.push("どちらの期間にも記録はありません。".to_string());
+    }
+    for delta in &comparison.types {
+        lines.push(format!(
+            "- {}: {} → {}（{}{}）",
+            delta.name,
+            format_count(delta.first, locale),
+            format_count(delta.second, locale),
+            format_delta(delta.second - delta.first, locale),
+            branding().count_unit
+        ));
+    }
+    for cost in &comparison.costs {
+        let sign = if cost.second > cost.first { "+" } else { "" };
+        lines.push(format!(
+            "費用: {} → {}（{}{}）",
+            format_price(cost.first, &cost.currency),
+            format_price(cost.second, &cost.currency),
+            sign,
+            format_price(cost.second - cost.first, &cost.currency)
+        ));
+    }
+
+    lines
+}
+
+/// Summarizes a user's current week or month against the previous one and sends it.
+///
+/// # Arguments
+/// * `frontend` - Where the summary is sent.
+/// * `stats` - The statistics service.
+/// * `discord_id` - The Discord ID of the user.
+/// * `span` - Whether weeks or months are summarized.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn show_period_summary(
+    frontend: &dyn Frontend,
+    stats: &StatsService,
+    discord_id: &str,
+    span: SummarySpan,
+) -> Result<(), Error> {
+    let summary = stats.period_summary(discord_id, span).await?;
+    let lines = summary_lines(&summary, frontend.locale());
+
+    frontend
+        .send_reply(Reply::new(lines.join("\n")).titled(Title::Stats))
+        .await
+}
+
+/// Renders a summary of the current week or month against the previous one.
+///
+/// # Arguments
+/// * `summary` - The summary.
+/// * `locale` - The locale numbers and dates are formatted for.
+///
+/// # Returns
+/// The lines, e.g. `合計: 12本（先週比 +3本）`.
+pub fn summary_lines(summary: &PeriodSummary, locale: Locale) -> Vec<String> {
+    let (current, previous) = summary.span.labels();
+    let comparison = &summary.comparison;
+    let unit = &branding().count_unit;
+    let average = |total: i64, period: Period| total as f64 / period.days() as f64;
+
+    let mut lines = vec![
+        format!(
+            "{}: {}〜{}（{}日）",
+            current,
+            format_date(summary.current.from, locale),
+            format_date(summary.current.to, locale),
+            summary.current.days()
+        ),
+        format!(
+            "合計: {}（{}比 {}{}）",
+            format_count(comparison.second_total, locale),
+            previous,
+            format_delta(comparison.second_total - comparison.first_total, locale),
+            unit
+        ),
+        format!(
+            "1日平均: {:.1}{}（{} {:.1}{}）",
+            average(comparison.second_total, summary.current),
+            unit,
+            previous,
+            average(comparison.first_total, summary.previous),
+            unit
+        ),
+    ];
+    if comparison.types.is_empty() {
+        lines.push(format!("{}も{}も記録はありません。", current, previous));
+    }
+    for delta in &comparison.types {
+        lines.push(format!(
+            "- {}: {}（{}{}）",
+            delta.name,
+            format_count(delta.second, locale),
+            format_delta(delta.second - delta.first, locale),
+            unit
+        ));
+    }
+    for cost in &comparison.costs {
+        let sign = if cost.second > cost.first { "+" } else { "" };
+        lines.push(format!(
+            "費用: {}（{}比 {}{}）",
+            format_price(cost.second, &cost.currency),
+            previous,
+            sign,
+            format_price(cost.second - cost.first, &cost.currency)
+        ));
+    }
+
+    lines
+}
+
+/// Formats a difference with its sign.
+pub fn format_delta(delta: i64, locale: Locale) -> String {
+    match delta {
+        0 => "±0".to_string(),
+        delta if delta > 0 => format!("+{}", format_number(delta, locale)),
+        delta => format_number(delta, locale),
+    }
+}
+
+/// Sends a statistics view, computing it only if the cached one is stale.
+///
+/// # Arguments
+/// * `frontend` - Where the view is sent.
+/// * `stats` - The statistics service.
+/// * `view` - The view to show.
+/// * `now` - The current time, for the freshness line.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn show_stats(
+    frontend: &dyn Frontend,
+    stats: &StatsService,
+    view: &StatsView,
+    now: DateTime<Utc>,
+) -> Result<(), Error> {
+    let reply = render_view(stats, view, frontend.locale(), now, false).await?;
+
+    frontend.send_reply(reply).await
+}
+
+/// Handles a press of a refresh button: recomputes the view and replaces the message.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `mci` - The refresh button press.
+/// * `view` - The view to refresh.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub(crate) async fn refresh_stats(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &serenity::ComponentInteraction,
+    view: &StatsView,
+) -> Result<(), Error> {
+    let frontend = InteractionFrontend::new(ctx, mci);
+    let reply = render_view(&data.stats, view, frontend.locale(), data.clock.now(), true).await?;
+
+    frontend.update(reply).await
+}
+
+/// Renders a statistics view with its freshness line and refresh button.
+///
+/// # Arguments
+/// * `stats` - The statistics service.
+/// * `view` - The view to render.
+/// * `locale` - The locale numbers are formatted for.
+/// * `now` - The current time.
+/// * `refresh` - Whether to recompute the statistics even if cached.
+///
+/// # Returns
+/// A Result containing the reply or an `Error`.
+async fn render_view(
+    stats: &StatsService,
+    view: &StatsView,
+    locale: Locale,
+    now: DateTime<Utc>,
+    refresh: bool,
+) -> Result<Reply, Error> {
+    let (mut lines, generated_at) = match view {
+        StatsView::Month { discord_id, month } => {
+            let cached = stats.monthly_totals(discord_id, *month, refresh).await?;
+            let mut lines = vec![format!("{}年{}月の記録", month.year(), month.month())];
+            let mut total = 0;
+            let today = stats.today();
+            for day in month
+                .iter_days()
+                .take_while(|day| day.month() == month.month() && *day <= today)
+            {
+                let count = cached
+                    .value
+                    .iter()
+                    .find(|daily| daily.smoke_date == day)
+                    .map_or(0, |daily| daily.total_quantity);
+                total += count;
+                lines.push(format!(
+                    "{:>2}日 {} {}",
+                    day.day(),
+                    "█".repeat(count.clamp(0, MAX_BAR_LENGTH) as usize),
+                    format_count(count, locale)
+                ));
+            }
+            lines.push(format!("合計: {}", format_count(total, locale)));
+            (lines, cached.generated_at)
+        }
+        StatsView::Guild { guild_id, month } => {
+            let cached = stats.guild_totals(guild_id, *month, refresh).await?;
+            let heading = format!("{}年{}月のサーバー集計", month.year(), month.month());
+            (
+                type_total_lines(heading, &cached.value, locale),
+                cached.generated_at,
+            )
+        }
+        StatsView::Channel { channel_id, month } => {
+            let cached = stats.channel_totals(channel_id, *month, refresh).await?;
+            let heading = format!("{}年{}月のグループ集計", month.year(), month.month());
+            (
+                type_total_lines(heading, &cached.value, locale),
+                cached.generated_at,
+            )
+        }
+    };
+    lines.push(format_freshness(generated_at, now));
+
+    Ok(Reply::new(lines.join("\n"))
+        .titled(Title::Stats)
+        .button(view.refresh_id(), "🔄"))
+}
+
+/// Lists totals per smoking type under a heading.
+///
+/// # Arguments
+/// * `heading` - The first line.
+/// * `totals` - The totals of the logged types.
+/// * `locale` - The locale numbers are formatted for.
+///
+/// # Returns
+/// The lines.
+pub fn type_total_lines(heading: String, totals: &[GuildTypeTotal], locale: Locale) -> Vec<String> {
+    let mut lines = vec![heading];
+    if totals.is_empty() {
+        lines.push("記録はありません。".to_string());
+    }
+    for total in totals {
+        lines.push(format!(
+            "- {}: {}（{}人）",
+            total.description.as_deref().unwrap_or(&total.type_name),
+            format_count(total.total_quantity, locale),
+            total.member_count
+        ));
+    }
+
+    lines
+}
+
+/// Describes how long ago statistics were computed.
+///
+/// # Arguments
+/// * `generated_at` - When the statistics were computed.
+/// * `now` - The current time.
+///
+/// # Returns
+/// The freshness line.
+pub fn format_freshness(generated_at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    match (now - generated_at).num_minutes() {
+        minutes if minutes < 1 => "たった今集計しました（🔄で更新）".to_string(),
+        minutes => format!("{}分前に集計しました（🔄で更新）", minutes),
+    }
+}
+
+/// Returns the statistics commands.
+pub fn commands() -> Vec<Command> {
+    vec![stats(), compare_periods()]
+}
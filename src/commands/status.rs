@@ -0,0 +1,119 @@
+//! Commands reporting the health of the bot.
+//!
+//! `uptime` shows how long the bot has been running together with the
+//! values of the last heartbeat (see the `heartbeat` module), so a stalled
+//! database or job worker can be spotted from Discord.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::frontend::{Frontend, Reply};
+use crate::heartbeat::{self, HeartbeatReport};
+use crate::{Context, Error};
+
+use super::Command;
+
+/// Shows the uptime, gateway latency, last successful database query and scheduler lag.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command)]
+pub async fn uptime(ctx: Context<'_>) -> Result<(), Error> {
+    let mut report = heartbeat::report();
+    // The shard handling this command knows a fresher latency than the last heartbeat.
+    let latency = ctx.ping().await;
+    if !latency.is_zero() {
+        report.gateway_latency = Some(latency);
+    }
+
+    show_uptime(&ctx, &report, ctx.data().clock.now()).await
+}
+
+/// Formats a duration as days, hours, minutes and seconds, leaving out leading zero units.
+///
+/// # Arguments
+/// * `duration` - The duration.
+///
+/// # Returns
+/// The formatted duration (e.g. `2日 3時間 0分 5秒`).
+fn format_elapsed(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let units = [
+        (seconds / 86400, "日"),
+        (seconds % 86400 / 3600, "時間"),
+        (seconds % 3600 / 60, "分"),
+        (seconds % 60, "秒"),
+    ];
+
+    units
+        .iter()
+        .skip_while(|(value, unit)| *value == 0 && *unit != "秒")
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Renders a heartbeat report.
+///
+/// # Arguments
+/// * `report` - The report.
+/// * `now` - The current time, to say how long ago the database last answered.
+///
+/// # Returns
+/// The lines of the report.
+pub fn render_uptime(report: &HeartbeatReport, now: DateTime<Utc>) -> String {
+    let gateway = report
+        .gateway_latency
+        .map_or("不明".to_string(), |latency| {
+            format!("{}ms", latency.as_millis())
+        });
+    let database = report
+        .last_db_success
+        .map_or("まだありません".to_string(), |at| {
+            format!(
+                "{}前",
+                format_elapsed((now - at).to_std().unwrap_or_default())
+            )
+        });
+    let scheduler = match report.scheduler_lag {
+        None => "不明".to_string(),
+        Some(lag) if lag.is_zero() => "遅れなし".to_string(),
+        Some(lag) => format_elapsed(lag),
+    };
+
+    format!(
+        "稼働時間: {}\nGatewayの遅延: {}\n最後に成功したDBクエリ: {}\nスケジューラーの遅延: {}",
+        format_elapsed(report.uptime),
+        gateway,
+        database,
+        scheduler
+    )
+}
+
+/// Sends a heartbeat report.
+///
+/// # Arguments
+/// * `frontend` - Where the report is sent.
+/// * `report` - The report.
+/// * `now` - The current time.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn show_uptime(
+    frontend: &dyn Frontend,
+    report: &HeartbeatReport,
+    now: DateTime<Utc>,
+) -> Result<(), Error> {
+    frontend
+        .send_reply(Reply::new(render_uptime(report, now)))
+        .await
+}
+
+/// Returns the status commands.
+pub fn commands() -> Vec<Command> {
+    vec![uptime()]
+}
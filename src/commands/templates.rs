@@ -0,0 +1,260 @@
+//! Per-guild message template management.
+
+use crate::database::Database;
+use crate::embed::Title;
+use crate::frontend::{Frontend, Reply};
+use crate::templates::{Revision, Template, TemplateKey};
+use crate::{Context, Error};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+use super::{chunk_lines, Command, MESSAGE_CHUNK_LENGTH};
+
+/// Manages the guild's message templates.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("templates_set", "templates_reset", "templates_list")
+)]
+pub async fn templates(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new(
+        "使い方: templates set <キー> [v版] <テキスト> / templates reset <キー> [v版] / templates list",
+    ))
+    .await
+}
+
+/// Overrides a message template (e.g. `templates set confirmation v2 {user}さん、{total}目です`).
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `key` - The message to customize.
+/// * `revision` - The version shown by `templates list` the change is based on, if given.
+/// * `text` - The template text.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "set"
+)]
+pub async fn templates_set(
+    ctx: Context<'_>,
+    key: TemplateKey,
+    revision: Option<Revision>,
+    #[rest] text: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?;
+
+    update_message_template(
+        &ctx,
+        &ctx.data().database,
+        &guild_id.to_string(),
+        key,
+        Some(&text),
+        revision,
+    )
+    .await
+}
+
+/// Restores the default of a message template.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `key` - The customized message.
+/// * `revision` - The version shown by `templates list` the reset is based on, if given.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "reset"
+)]
+pub async fn templates_reset(
+    ctx: Context<'_>,
+    key: TemplateKey,
+    revision: Option<Revision>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?;
+
+    update_message_template(
+        &ctx,
+        &ctx.data().database,
+        &guild_id.to_string(),
+        key,
+        None,
+        revision,
+    )
+    .await
+}
+
+/// Validates and stores (or removes) a guild's message template and confirms the change.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `guild_id` - The ID of the guild.
+/// * `key` - The message to customize.
+/// * `text` - The template text, or `None` to restore the default.
+/// * `revision` - The version the change is based on; the change is refused if the
+///   template was changed since. `None` applies the change unconditionally.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn update_message_template(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    guild_id: &str,
+    key: TemplateKey,
+    text: Option<&str>,
+    revision: Option<Revision>,
+) -> Result<(), Error> {
+    let expected_version = revision.map(|revision| revision.0);
+    let reply = {
+        let db = database.lock().await;
+        let guild = db.guild(guild_id);
+
+        match text {
+            Some(text) => match Template::parse(key, text) {
+                Ok(_) => match guild
+                    .set_message_template(key.name(), text, expected_version)
+                    .await?
+                {
+                    Some(version) => Reply::new(format!(
+                        "テンプレート「{}」を設定しました（{}）。",
+                        key,
+                        Revision(version)
+                    ))
+                    .titled(Title::Settings),
+                    None => Reply::error(conflict_message(
+                        key,
+                        guild.get_message_template_version(key.name()).await?,
+                    )),
+                },
+                Err(e) => Reply::error(format!(
+                    "テンプレートが不正です: {}\n使用できる変数: {}",
+                    e,
+                    format_placeholders(key)
+                )),
+            },
+            None => {
+                if guild
+                    .remove_message_template(key.name(), expected_version)
+                    .await?
+                {
+                    Reply::new(format!("テンプレート「{}」を既定に戻しました。", key))
+                        .titled(Title::Settings)
+                } else {
+                    let current = guild.get_message_template_version(key.name()).await?;
+                    if expected_version.is_some_and(|version| version != current) {
+                        Reply::error(conflict_message(key, current))
+                    } else {
+                        Reply::new(format!("テンプレート「{}」は変更されていません。", key))
+                            .titled(Title::Settings)
+                    }
+                }
+            }
+        }
+    };
+
+    frontend.send_reply(reply).await
+}
+
+/// Builds the reply sent when a template was changed by someone else first.
+///
+/// # Arguments
+/// * `key` - The template key.
+/// * `current` - The version now stored.
+///
+/// # Returns
+/// The reply.
+fn conflict_message(key: TemplateKey, current: i32) -> String {
+    format!(
+        "テンプレート「{}」は他の管理者によって変更されています（現在 {}）。`templates list` で最新の内容を確認してから、もう一度お試しください。",
+        key,
+        Revision(current)
+    )
+}
+
+/// Lists the guild's message templates with their placeholders.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "list"
+)]
+pub async fn templates_list(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("guild only")?;
+
+    let overrides = ctx
+        .data()
+        .database
+        .lock()
+        .await
+        .guild(&guild_id.to_string())
+        .get_message_templates()
+        .await?;
+
+    let lines: Vec<String> = TemplateKey::ALL
+        .into_iter()
+        .map(|key| {
+            let (source, state, version) = match overrides
+                .iter()
+                .find(|template| template.template_key == key.name())
+            {
+                Some(template) => (template.template.as_str(), "カスタム", template.version),
+                None => (key.default_template(), "既定", 0),
+            };
+            format!(
+                "**{}**（{}・{}） 変数: {}\n```\n{}\n```",
+                key,
+                state,
+                Revision(version),
+                format_placeholders(key),
+                source
+            )
+        })
+        .collect();
+
+    for chunk in chunk_lines(&lines, MESSAGE_CHUNK_LENGTH) {
+        ctx.send_reply(Reply::new(chunk).titled(Title::Settings))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Formats the placeholders of a template key for display.
+///
+/// # Arguments
+/// * `key` - The template key.
+///
+/// # Returns
+/// The placeholders, e.g. `{user}, {date}`.
+fn format_placeholders(key: TemplateKey) -> String {
+    key.placeholders()
+        .iter()
+        .map(|placeholder| format!("{{{}}}", placeholder))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Returns the template commands.
+pub fn commands() -> Vec<Command> {
+    vec![templates()]
+}
@@ -0,0 +1,804 @@
+//! Owner-only management of the smoking types the bot knows.
+//!
+//! Types are shared by every guild, so only owners can add them. `type preset`
+//! lists the curated presets and applies one; see `crate::presets`. `type
+//! merge` folds a near-duplicate type (e.g. `メビウス` and `Mevius`) into
+//! another: its logs move to the kept type, it is archived, and its name
+//! keeps finding the kept type. Types counting in different units need a
+//! conversion factor to merge; `type conversion` manages the hints suggested
+//! for it, see `crate::units`. `type category` groups types into categories
+//! of habit (cigarettes, coffee, energy drinks) counted in one unit, so one
+//! deployment can track several habits and members can set a daily goal for
+//! each (see `category-goal`).
+
+use crate::database::{Database, SmokingType};
+use crate::frontend::{Frontend, Reply};
+use crate::presets::{default_preset, find_preset, format_price, PRESETS};
+use crate::service::StatsService;
+use crate::units::{describe_conversion, format_factor, is_valid_factor, unit_of, units_differ};
+use crate::{Context, Error};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+use super::Command;
+
+/// Manages the smoking types the bot knows.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    owners_only,
+    rename = "type",
+    subcommands("type_preset", "type_merge", "type_conversion", "type_category")
+)]
+pub async fn type_command(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new("使い方: type preset list / type preset apply [プリセット名] / type merge <統合する種類> <残す種類> [換算係数] / type conversion set <種類> <種類> <換算係数> / type conversion remove <種類> <種類> / type conversion list / type category add <カテゴリ> <単位> [絵文字] / type category remove <カテゴリ> / type category assign <種類> [カテゴリ] / type category list")).await
+}
+
+/// Lists or applies the curated type presets.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    owners_only,
+    rename = "preset",
+    subcommands("type_preset_list", "type_preset_apply")
+)]
+pub async fn type_preset(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new(
+        "使い方: type preset list / type preset apply [プリセット名]",
+    ))
+    .await
+}
+
+/// Lists the curated type presets.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "list")]
+pub async fn type_preset_list(ctx: Context<'_>) -> Result<(), Error> {
+    list_type_presets(&ctx).await
+}
+
+/// Adds the types of a preset, by default the one suggested for your locale.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `name` - The name of the preset.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "apply")]
+pub async fn type_preset_apply(ctx: Context<'_>, name: Option<String>) -> Result<(), Error> {
+    apply_preset(&ctx, &ctx.data().database, name.as_deref()).await
+}
+
+/// Merges a smoking type into another.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `from` - The type name of the type merged away.
+/// * `to` - The type name of the type kept.
+/// * `factor` - How many units of the kept type one unit of the merged type is,
+///   required when the units differ.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "merge")]
+pub async fn type_merge(
+    ctx: Context<'_>,
+    from: String,
+    to: String,
+    factor: Option<f64>,
+) -> Result<(), Error> {
+    merge_types(
+        &ctx,
+        &ctx.data().database,
+        &ctx.data().stats,
+        &ctx.author().id.get().to_string(),
+        &from,
+        &to,
+        factor,
+    )
+    .await
+}
+
+/// Manages the conversion hints between the units of two types.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    owners_only,
+    rename = "conversion",
+    subcommands(
+        "type_conversion_set",
+        "type_conversion_remove",
+        "type_conversion_list"
+    )
+)]
+pub async fn type_conversion(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new(
+        "使い方: type conversion set <種類> <種類> <換算係数> / type conversion remove <種類> <種類> / type conversion list",
+    ))
+    .await
+}
+
+/// Sets how many units of one type a unit of another type is.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `from` - The type name of the source type.
+/// * `to` - The type name of the target type.
+/// * `factor` - How many units of the target type one unit of the source type is.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "set")]
+pub async fn type_conversion_set(
+    ctx: Context<'_>,
+    from: String,
+    to: String,
+    factor: f64,
+) -> Result<(), Error> {
+    set_conversion(&ctx, &ctx.data().database, &from, &to, factor).await
+}
+
+/// Removes the conversion hint from one type to another.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `from` - The type name of the source type.
+/// * `to` - The type name of the target type.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "remove")]
+pub async fn type_conversion_remove(
+    ctx: Context<'_>,
+    from: String,
+    to: String,
+) -> Result<(), Error> {
+    remove_conversion(&ctx, &ctx.data().database, &from, &to).await
+}
+
+/// Lists the conversion hints.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "list")]
+pub async fn type_conversion_list(ctx: Context<'_>) -> Result<(), Error> {
+    list_conversions(&ctx, &ctx.data().database).await
+}
+
+/// Manages the categories types are grouped into.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(
+    prefix_command,
+    owners_only,
+    rename = "category",
+    subcommands(
+        "type_category_add",
+        "type_category_remove",
+        "type_category_assign",
+        "type_category_list"
+    )
+)]
+pub async fn type_category(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.send_reply(Reply::new(
+        "使い方: type category add <カテゴリ> <単位> [絵文字] / type category remove <カテゴリ> / type category assign <種類> [カテゴリ] / type category list",
+    ))
+    .await
+}
+
+/// Adds a category counted in a unit.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `name` - The name of the category.
+/// * `unit` - What one logged unit of its types is.
+/// * `emoji` - The emoji shown with the category, if any.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "add")]
+pub async fn type_category_add(
+    ctx: Context<'_>,
+    name: String,
+    unit: String,
+    emoji: Option<String>,
+) -> Result<(), Error> {
+    add_category(&ctx, &ctx.data().database, &name, &unit, emoji.as_deref()).await
+}
+
+/// Removes a category, leaving its types uncategorized.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `name` - The name of the category.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "remove")]
+pub async fn type_category_remove(ctx: Context<'_>, name: String) -> Result<(), Error> {
+    remove_category(&ctx, &ctx.data().database, &name).await
+}
+
+/// Moves a type into a category, or out of any when the category is omitted.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `type_name` - The type name of the type.
+/// * `category` - The name of the category, or omitted to leave the type uncategorized.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "assign")]
+pub async fn type_category_assign(
+    ctx: Context<'_>,
+    type_name: String,
+    category: Option<String>,
+) -> Result<(), Error> {
+    assign_category(&ctx, &ctx.data().database, &type_name, category.as_deref()).await
+}
+
+/// Lists the categories with their types.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, owners_only, rename = "list")]
+pub async fn type_category_list(ctx: Context<'_>) -> Result<(), Error> {
+    list_categories(&ctx, &ctx.data().database).await
+}
+
+/// Lists the presets with their types.
+///
+/// # Arguments
+/// * `frontend` - Where the list is sent.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn list_type_presets(frontend: &dyn Frontend) -> Result<(), Error> {
+    let mut lines = vec!["種類のプリセット:".to_string()];
+    for preset in PRESETS {
+        lines.push(format!("{} ({})", preset.name, preset.label));
+        lines.extend(preset.types.iter().map(|preset_type| {
+            format!(
+                "- {} {} ({}): 1{}あたり{}",
+                preset_type.emoji,
+                preset_type.description,
+                preset_type.type_name,
+                preset_type.unit,
+                format_price(preset_type.typical_price.into(), preset_type.currency)
+            )
+        }));
+    }
+
+    frontend.send_reply(Reply::new(lines.join("\n"))).await
+}
+
+/// Applies a preset and reports how many types were added.
+///
+/// # Arguments
+/// * `frontend` - Where the result is sent.
+/// * `database` - The database.
+/// * `name` - The name of the preset, or `None` for the one suggested for the frontend's locale.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn apply_preset(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    name: Option<&str>,
+) -> Result<(), Error> {
+    let preset = match name {
+        Some(name) => match find_preset(name) {
+            Some(preset) => preset,
+            None => {
+                let names: Vec<&str> = PRESETS.iter().map(|preset| preset.name).collect();
+                return frontend
+                    .send_reply(Reply::error(format!(
+                        "不明なプリセットです: {}（{}）",
+                        name,
+                        names.join(", ")
+                    )))
+                    .await;
+            }
+        },
+        None => default_preset(frontend.locale()),
+    };
+
+    let applied = database
+        .lock()
+        .await
+        .ensure_smoking_types(&preset.details())
+        .await?;
+
+    frontend
+        .send_reply(Reply::new(format!(
+            "プリセット「{}」を適用しました。{}種類を追加し、{}種類は既存の種類を使います。",
+            preset.label,
+            applied.added,
+            preset.types.len() - applied.added
+        )))
+        .await
+}
+
+/// Merges a smoking type into another, audits the merge and confirms it.
+///
+/// When the types count in different units, the merge needs a `factor` to
+/// convert the moved quantities; without one the owner is asked for it, with
+/// the configured conversion hint if there is one.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `stats` - The statistics service whose cached statistics are dropped.
+/// * `actor_id` - The Discord ID of the owner merging the types.
+/// * `from` - The type name of the type merged away.
+/// * `to` - The type name of the type kept.
+/// * `factor` - How many units of the kept type one unit of the merged type is.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn merge_types(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    stats: &StatsService,
+    actor_id: &str,
+    from: &str,
+    to: &str,
+    factor: Option<f64>,
+) -> Result<(), Error> {
+    if factor.is_some_and(|factor| !is_valid_factor(factor)) {
+        return frontend
+            .send_reply(Reply::error("換算係数は0より大きい数で指定してください。"))
+            .await;
+    }
+
+    let reply = {
+        let db = database.lock().await;
+        let Some((source, target)) = find_pair(&db, from, to).await? else {
+            drop(db);
+            return unknown_types(frontend, database, from, to).await;
+        };
+
+        if source.id == target.id {
+            Reply::error(format!("{}と{}はすでに同じ種類です。", from, to))
+        } else if factor.is_none() && units_differ(&source, &target) {
+            let hint = match db.get_unit_conversion(source.id, target.id).await? {
+                Some(factor) => format!(
+                    "換算の目安: {}",
+                    describe_conversion(unit_of(&source), unit_of(&target), factor)
+                ),
+                None => "換算の目安は type conversion set で登録できます。".to_string(),
+            };
+            Reply::error(format!(
+                "{}の単位は「{}」、{}の単位は「{}」です。換算係数（1{}あたりの{}）を指定して type merge {} {} <換算係数> を実行し直してください。\n{}",
+                display_name(&source),
+                unit_of(&source),
+                display_name(&target),
+                unit_of(&target),
+                unit_of(&source),
+                unit_of(&target),
+                from,
+                to,
+                hint
+            ))
+        } else {
+            let moved = db
+                .merge_smoking_types(actor_id, source.id, target.id, factor)
+                .await?;
+            stats.forget_all();
+            Reply::new(format!(
+                "{}を{}に統合しました（{}件の記録を移動）。",
+                display_name(&source),
+                display_name(&target),
+                moved
+            ))
+        }
+    };
+
+    frontend.send_reply(reply).await
+}
+
+/// Sets a conversion hint between two types and confirms it.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `from` - The type name of the source type.
+/// * `to` - The type name of the target type.
+/// * `factor` - How many units of the target type one unit of the source type is.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn set_conversion(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    from: &str,
+    to: &str,
+    factor: f64,
+) -> Result<(), Error> {
+    if !is_valid_factor(factor) {
+        return frontend
+            .send_reply(Reply::error("換算係数は0より大きい数で指定してください。"))
+            .await;
+    }
+
+    let reply = {
+        let db = database.lock().await;
+        let Some((source, target)) = find_pair(&db, from, to).await? else {
+            drop(db);
+            return unknown_types(frontend, database, from, to).await;
+        };
+
+        if source.id == target.id {
+            Reply::error(format!("{}と{}は同じ種類です。", from, to))
+        } else {
+            db.set_unit_conversion(source.id, target.id, factor).await?;
+            Reply::new(format!(
+                "{}から{}への換算の目安を登録しました: {}",
+                display_name(&source),
+                display_name(&target),
+                describe_conversion(unit_of(&source), unit_of(&target), factor)
+            ))
+        }
+    };
+
+    frontend.send_reply(reply).await
+}
+
+/// Removes a conversion hint between two types and confirms it.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `from` - The type name of the source type.
+/// * `to` - The type name of the target type.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn remove_conversion(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    from: &str,
+    to: &str,
+) -> Result<(), Error> {
+    let reply = {
+        let db = database.lock().await;
+        let Some((source, target)) = find_pair(&db, from, to).await? else {
+            drop(db);
+            return unknown_types(frontend, database, from, to).await;
+        };
+
+        if db.remove_unit_conversion(source.id, target.id).await? {
+            Reply::new(format!(
+                "{}から{}への換算の目安を削除しました。",
+                display_name(&source),
+                display_name(&target)
+            ))
+        } else {
+            Reply::error(format!(
+                "{}から{}への換算の目安は登録されていません。",
+                display_name(&source),
+                display_name(&target)
+            ))
+        }
+    };
+
+    frontend.send_reply(reply).await
+}
+
+/// Lists the conversion hints.
+///
+/// # Arguments
+/// * `frontend` - Where the list is sent.
+/// * `database` - The database.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn list_conversions(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+) -> Result<(), Error> {
+    let lines = {
+        let db = database.lock().await;
+        let types = db.get_smoking_types().await?;
+        let find = |id: i32| types.iter().find(|smoking_type| smoking_type.id == id);
+
+        db.get_unit_conversions()
+            .await?
+            .into_iter()
+            .filter_map(|conversion| {
+                let source = find(conversion.from_type_id)?;
+                let target = find(conversion.to_type_id)?;
+                Some(format!(
+                    "- {} → {}: {}（×{}）",
+                    display_name(source),
+                    display_name(target),
+                    describe_conversion(unit_of(source), unit_of(target), conversion.factor),
+                    format_factor(conversion.factor)
+                ))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    if lines.is_empty() {
+        return frontend
+            .send_reply(Reply::new("換算の目安は登録されていません。"))
+            .await;
+    }
+
+    frontend
+        .send_reply(Reply::new(format!("換算の目安\n{}", lines.join("\n"))))
+        .await
+}
+
+/// Adds a category and confirms it.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `name` - The name of the category.
+/// * `unit` - What one logged unit of its types is.
+/// * `emoji` - The emoji shown with the category, if any.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn add_category(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    name: &str,
+    unit: &str,
+    emoji: Option<&str>,
+) -> Result<(), Error> {
+    let added = database
+        .lock()
+        .await
+        .add_category(name, unit, emoji)
+        .await?;
+
+    let reply = match added {
+        Some(category) => Reply::new(format!(
+            "カテゴリ「{}」（単位: {}）を追加しました。type category assign で種類を追加してください。",
+            category.label(),
+            category.unit
+        )),
+        None => Reply::error(format!("カテゴリ「{}」はすでにあります。", name)),
+    };
+
+    frontend.send_reply(reply).await
+}
+
+/// Removes a category and confirms it.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `name` - The name of the category.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn remove_category(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    name: &str,
+) -> Result<(), Error> {
+    let reply = {
+        let db = database.lock().await;
+        match db.find_category_by_name(name).await? {
+            Some(category) if db.remove_category(category.id).await? => Reply::new(format!(
+                "カテゴリ「{}」を削除しました。その種類はカテゴリなしになり、カテゴリの目標も解除されました。",
+                category.label()
+            )),
+            _ => Reply::error(format!("不明なカテゴリです: {}", name)),
+        }
+    };
+
+    frontend.send_reply(reply).await
+}
+
+/// Moves a type into a category, or out of any, and confirms it.
+///
+/// # Arguments
+/// * `frontend` - Where the confirmation is sent.
+/// * `database` - The database.
+/// * `type_name` - The type name of the type.
+/// * `category` - The name of the category, or `None` to leave the type uncategorized.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn assign_category(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    type_name: &str,
+    category: Option<&str>,
+) -> Result<(), Error> {
+    let reply = {
+        let db = database.lock().await;
+        let Some(smoking_type) = db.find_smoking_type_by_name(type_name).await? else {
+            drop(db);
+            return frontend
+                .send_reply(Reply::error(format!("不明な種類です: {}", type_name)))
+                .await;
+        };
+
+        match category {
+            Some(name) => match db.find_category_by_name(name).await? {
+                Some(category) => {
+                    db.set_type_category(smoking_type.id, Some(category.id))
+                        .await?;
+                    Reply::new(format!(
+                        "{}をカテゴリ「{}」に追加しました。",
+                        display_name(&smoking_type),
+                        category.label()
+                    ))
+                }
+                None => Reply::error(format!("不明なカテゴリです: {}", name)),
+            },
+            None => {
+                db.set_type_category(smoking_type.id, None).await?;
+                Reply::new(format!(
+                    "{}をカテゴリなしにしました。",
+                    display_name(&smoking_type)
+                ))
+            }
+        }
+    };
+
+    frontend.send_reply(reply).await
+}
+
+/// Lists the categories with their types.
+///
+/// # Arguments
+/// * `frontend` - Where the list is sent.
+/// * `database` - The database.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn list_categories(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+) -> Result<(), Error> {
+    let lines = {
+        let db = database.lock().await;
+        let types = db.get_smoking_types().await?;
+        let type_categories = db.get_type_categories().await?;
+
+        db.get_categories()
+            .await?
+            .into_iter()
+            .map(|category| {
+                let names: Vec<&str> = type_categories
+                    .iter()
+                    .filter(|(_, category_id)| *category_id == category.id)
+                    .filter_map(|(type_id, _)| {
+                        types
+                            .iter()
+                            .find(|smoking_type| smoking_type.id == *type_id)
+                            .map(display_name)
+                    })
+                    .collect();
+                format!(
+                    "- {}（単位: {}）: {}",
+                    category.label(),
+                    category.unit,
+                    if names.is_empty() {
+                        "種類なし".to_string()
+                    } else {
+                        names.join(", ")
+                    }
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    if lines.is_empty() {
+        return frontend
+            .send_reply(Reply::new("カテゴリは登録されていません。"))
+            .await;
+    }
+
+    frontend
+        .send_reply(Reply::new(format!("カテゴリ\n{}", lines.join("\n"))))
+        .await
+}
+
+/// Returns the name of a type shown to owners.
+fn display_name(smoking_type: &SmokingType) -> &str {
+    smoking_type
+        .description
+        .as_deref()
+        .unwrap_or(&smoking_type.type_name)
+}
+
+/// Looks up two types by name.
+///
+/// # Arguments
+/// * `db` - The database.
+/// * `from` - The type name of the first type.
+/// * `to` - The type name of the second type.
+///
+/// # Returns
+/// A Result containing both types, `None` if either is unknown, or an `Error`.
+async fn find_pair(
+    db: &Database,
+    from: &str,
+    to: &str,
+) -> Result<Option<(SmokingType, SmokingType)>, Error> {
+    Ok(
+        match (
+            db.find_smoking_type_by_name(from).await?,
+            db.find_smoking_type_by_name(to).await?,
+        ) {
+            (Some(source), Some(target)) => Some((source, target)),
+            _ => None,
+        },
+    )
+}
+
+/// Reports that a type name is unknown, listing the known ones.
+///
+/// # Arguments
+/// * `frontend` - Where the error is sent.
+/// * `database` - The database.
+/// * `from` - The first type name given.
+/// * `to` - The second type name given.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+async fn unknown_types(
+    frontend: &dyn Frontend,
+    database: &Mutex<Database>,
+    from: &str,
+    to: &str,
+) -> Result<(), Error> {
+    let known: Vec<String> = database
+        .lock()
+        .await
+        .get_smoking_types()
+        .await?
+        .into_iter()
+        .map(|smoking_type| smoking_type.type_name)
+        .collect();
+
+    frontend
+        .send_reply(Reply::error(format!(
+            "不明な種類です: {} / {}（使用できる種類: {}）",
+            from,
+            to,
+            known.join(", ")
+        )))
+        .await
+}
+
+/// Returns the type commands.
+pub fn commands() -> Vec<Command> {
+    vec![type_command()]
+}
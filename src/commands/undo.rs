@@ -0,0 +1,167 @@
+//! Undoing the most recent log after an accidental press.
+//!
+//! Every confirmation carries a 取り消し button that deletes the pressing
+//! member's most recent log and shows the corrected daily summary; `undo`
+//! does the same for guilds that confirm with a reaction. A button only
+//! works for the member whose press it confirms.
+
+use poise::serenity_prelude as serenity;
+
+use crate::embed::Title;
+use crate::format::{format_count, format_summary_heading, format_summary_lines};
+use crate::frontend::{Frontend, InteractionFrontend, Reply};
+use crate::service::LoggingService;
+use crate::{Context, Data, Error};
+
+use super::Command;
+
+/// Prefix of the custom IDs of undo buttons
+const UNDO_PREFIX: &str = "undo:";
+
+/// Label of undo buttons
+pub const UNDO_LABEL: &str = "取り消し";
+
+/// An undo button and the member who may press it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoId {
+    /// The Discord ID of the member whose last log is undone
+    pub user_id: String,
+}
+
+impl UndoId {
+    /// Encodes the custom ID of the button.
+    pub fn custom_id(&self) -> String {
+        format!("{}{}", UNDO_PREFIX, self.user_id)
+    }
+
+    /// Decodes the custom ID of an undo button.
+    ///
+    /// # Arguments
+    /// * `custom_id` - The custom ID of a pressed button.
+    ///
+    /// # Returns
+    /// The undo to run, or `None` if the button is not an undo button.
+    pub fn parse(custom_id: &str) -> Option<Self> {
+        let user_id = custom_id.strip_prefix(UNDO_PREFIX)?;
+        if user_id.is_empty() || !user_id.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        Some(Self {
+            user_id: user_id.to_string(),
+        })
+    }
+}
+
+/// Deletes your most recent log and shows the corrected summary of its day.
+///
+/// # Arguments
+/// * `ctx` - The context.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command)]
+pub async fn undo(ctx: Context<'_>) -> Result<(), Error> {
+    let reply = undo_reply(
+        &ctx,
+        &ctx.data().logging,
+        &ctx.author().id.get().to_string(),
+    )
+    .await?;
+
+    ctx.send_reply(reply).await
+}
+
+/// Handles a press of an undo button.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `mci` - The button press.
+/// * `undo` - The undo encoded in the button.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub(crate) async fn handle_undo(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &serenity::ComponentInteraction,
+    undo: &UndoId,
+) -> Result<(), Error> {
+    apply_undo(
+        &InteractionFrontend::new(ctx, mci),
+        &data.logging,
+        &mci.user.id.get().to_string(),
+        undo,
+    )
+    .await
+}
+
+/// Undoes the last log of the button's member if the presser is that member,
+/// and responds with the corrected summary.
+///
+/// # Arguments
+/// * `frontend` - Where the response is sent.
+/// * `logging` - The logging service.
+/// * `presser_id` - The Discord ID of the user who pressed the button.
+/// * `undo` - The undo encoded in the button.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn apply_undo(
+    frontend: &dyn Frontend,
+    logging: &LoggingService,
+    presser_id: &str,
+    undo: &UndoId,
+) -> Result<(), Error> {
+    if presser_id != undo.user_id {
+        return frontend
+            .respond(Reply::error("このボタンは記録した人だけが使えます。").ephemeral())
+            .await;
+    }
+
+    let reply = undo_reply(frontend, logging, presser_id).await?;
+    frontend.respond(reply).await
+}
+
+/// Deletes a user's most recent log and builds the corrected summary of its day.
+///
+/// # Arguments
+/// * `frontend` - Where the reply is sent, for its locale.
+/// * `logging` - The logging service.
+/// * `user_id` - The Discord ID of the user.
+///
+/// # Returns
+/// A Result containing the `Reply`, or an `Error`.
+async fn undo_reply(
+    frontend: &dyn Frontend,
+    logging: &LoggingService,
+    user_id: &str,
+) -> Result<Reply, Error> {
+    let Some(undone) = logging.undo_last_log(user_id).await? else {
+        return Ok(Reply::error("取り消せる記録がありません。"));
+    };
+
+    let locale = frontend.locale();
+    let name = logging.display_name(undone.log.smoking_type_id).await?;
+    let mut lines = vec![
+        format!(
+            "{} {}の記録を取り消しました。",
+            name,
+            format_count(i64::from(undone.log.quantity), locale)
+        ),
+        format_summary_heading(undone.date, locale),
+    ];
+    let summary = format_summary_lines(&undone.daily_summary, locale);
+    if summary.is_empty() {
+        lines.push("記録はありません。".to_string());
+    }
+    lines.extend(summary);
+
+    Ok(Reply::new(lines.join("\n")).titled(Title::Confirmation))
+}
+
+/// Returns the undo commands.
+pub fn commands() -> Vec<Command> {
+    vec![undo()]
+}
@@ -0,0 +1,233 @@
+//! Friendly wagers between users; see `crate::wagers`.
+
+use poise::serenity_prelude as serenity;
+
+use crate::database::Wager;
+use crate::frontend::{Frontend, InteractionFrontend, Reply};
+use crate::wagers::{describe_terms, WagerMetric, WagerPeriod, PENDING_EXPIRY};
+use crate::{Context, Data, Error};
+
+use super::Command;
+
+/// Prefix of the custom IDs of the wager buttons
+const WAGER_PREFIX: &str = "wager:";
+
+/// A button answering a proposed wager
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WagerId {
+    /// Starts the wager
+    Accept { wager_id: i32 },
+    /// Turns the wager down
+    Decline { wager_id: i32 },
+}
+
+impl WagerId {
+    /// Returns the wager the button answers.
+    pub fn wager_id(&self) -> i32 {
+        match self {
+            Self::Accept { wager_id } | Self::Decline { wager_id } => *wager_id,
+        }
+    }
+
+    /// Encodes the custom ID of the button.
+    pub fn custom_id(&self) -> String {
+        match self {
+            Self::Accept { wager_id } => format!("{}accept:{}", WAGER_PREFIX, wager_id),
+            Self::Decline { wager_id } => format!("{}decline:{}", WAGER_PREFIX, wager_id),
+        }
+    }
+
+    /// Decodes the custom ID of a wager button.
+    ///
+    /// # Arguments
+    /// * `custom_id` - The custom ID of a pressed button.
+    ///
+    /// # Returns
+    /// The button, or `None` if it is not a wager button.
+    pub fn parse(custom_id: &str) -> Option<Self> {
+        let rest = custom_id.strip_prefix(WAGER_PREFIX)?;
+
+        match rest.split_once(':')? {
+            ("accept", wager_id) => Some(Self::Accept {
+                wager_id: wager_id.parse().ok()?,
+            }),
+            ("decline", wager_id) => Some(Self::Decline {
+                wager_id: wager_id.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Builds the proposal of a wager, with buttons for the opponent.
+///
+/// # Arguments
+/// * `wager` - The proposed wager.
+/// * `metric` - What decides the winner.
+/// * `period` - How long the wager runs.
+///
+/// # Returns
+/// The proposal with accept and decline buttons.
+pub fn proposal(wager: &Wager, metric: WagerMetric, period: WagerPeriod) -> Reply {
+    Reply::new(format!(
+        "<@{}> さん、<@{}> さんから勝負の申し込みです: {}（賭け金はありません）。{}時間以内に受けるかどうか選んでください。",
+        wager.opponent_id,
+        wager.challenger_id,
+        describe_terms(metric, period),
+        PENDING_EXPIRY.num_hours()
+    ))
+    .button(
+        WagerId::Accept { wager_id: wager.id }.custom_id(),
+        "受ける",
+    )
+    .button(
+        WagerId::Decline { wager_id: wager.id }.custom_id(),
+        "断る",
+    )
+}
+
+/// Proposes a friendly wager to another member, e.g. `bet @friend lowest-count week`.
+///
+/// # Arguments
+/// * `ctx` - The context.
+/// * `opponent` - The member challenged.
+/// * `metric` - What decides the winner; `lowest-count` if omitted.
+/// * `period` - How long the wager runs (`day` or `week`); a week if omitted.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[poise::command(prefix_command, guild_only)]
+pub async fn bet(
+    ctx: Context<'_>,
+    opponent: serenity::User,
+    metric: Option<String>,
+    period: Option<String>,
+) -> Result<(), Error> {
+    let Some(metric) = WagerMetric::from_name(metric.as_deref().unwrap_or("lowest-count")) else {
+        let names: Vec<&str> = WagerMetric::ALL
+            .iter()
+            .map(|metric| metric.name())
+            .collect();
+        return ctx
+            .send_reply(Reply::error(format!(
+                "勝負の種類を指定してください: {}",
+                names.join(", ")
+            )))
+            .await;
+    };
+    let Some(period) = WagerPeriod::from_name(period.as_deref().unwrap_or("week")) else {
+        let names: Vec<&str> = WagerPeriod::ALL
+            .iter()
+            .map(|period| period.name())
+            .collect();
+        return ctx
+            .send_reply(Reply::error(format!(
+                "期間を指定してください: {}",
+                names.join(", ")
+            )))
+            .await;
+    };
+    if opponent.bot || opponent.id == ctx.author().id {
+        return ctx
+            .send_reply(Reply::error(
+                "勝負を申し込めるのは自分以外のメンバーだけです。",
+            ))
+            .await;
+    }
+
+    let wager = ctx
+        .data()
+        .database
+        .lock()
+        .await
+        .create_wager(
+            &ctx.channel_id().to_string(),
+            &ctx.author().id.to_string(),
+            &opponent.id.to_string(),
+            metric.name(),
+            period.name(),
+        )
+        .await?;
+
+    ctx.send_reply(proposal(&wager, metric, period)).await
+}
+
+/// Handles a press of a wager button, replacing the proposal with the answer.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `mci` - The button press.
+/// * `control` - The pressed button.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub(crate) async fn handle_wager(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &serenity::ComponentInteraction,
+    control: &WagerId,
+) -> Result<(), Error> {
+    let frontend = InteractionFrontend::new(ctx, mci);
+    let wager = data
+        .database
+        .lock()
+        .await
+        .get_wager(control.wager_id())
+        .await?;
+    let Some(wager) = wager else {
+        return frontend
+            .respond(Reply::error("この勝負は見つかりません。").ephemeral())
+            .await;
+    };
+    if mci.user.id.to_string() != wager.opponent_id {
+        return frontend
+            .respond(Reply::error("この勝負は申し込まれた本人だけが答えられます。").ephemeral())
+            .await;
+    }
+
+    let now = data.clock.now();
+    let reply = match control {
+        WagerId::Decline { .. } => {
+            if data.database.lock().await.decline_wager(wager.id).await? {
+                Reply::new(format!(
+                    "<@{}> さんは <@{}> さんからの勝負を断りました。",
+                    wager.opponent_id, wager.challenger_id
+                ))
+            } else {
+                Reply::error("この申し込みはすでに締め切られています。")
+            }
+        }
+        WagerId::Accept { .. } => {
+            let period = WagerPeriod::from_name(&wager.period)
+                .ok_or_else(|| format!("unknown wager period: {}", wager.period))?;
+            let metric = WagerMetric::from_name(&wager.metric)
+                .ok_or_else(|| format!("unknown wager metric: {}", wager.metric))?;
+            let ends_at = now + period.duration();
+            let accepted = data
+                .database
+                .lock()
+                .await
+                .accept_wager(wager.id, now - PENDING_EXPIRY, now, ends_at)
+                .await?;
+            match accepted {
+                Some(_) => Reply::new(format!(
+                    "勝負 #{} が始まりました: <@{}> さん対 <@{}> さん、{}。<t:{}:f> に結果を発表します。",
+                    wager.id,
+                    wager.challenger_id,
+                    wager.opponent_id,
+                    describe_terms(metric, period),
+                    ends_at.timestamp()
+                )),
+                None => Reply::error("この申し込みはすでに締め切られています。"),
+            }
+        }
+    };
+
+    frontend.update(reply).await
+}
+
+/// Returns the wager commands.
+pub fn commands() -> Vec<Command> {
+    vec![bet()]
+}
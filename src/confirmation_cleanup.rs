@@ -0,0 +1,123 @@
+//! Deletion of old panel confirmations.
+//!
+//! Guilds that enable it with `confirmation_cleanup on <hours>` have the
+//! confirmations of panel presses remembered as they are sent (see
+//! `LoggingService::remember_confirmation`). A recurring job deletes those
+//! older than the guild's age and forgets them, keeping panel channels tidy
+//! without manual moderation. Confirmations sent before cleanup was enabled
+//! are left alone.
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+use tracing::{info, warn};
+
+use crate::clock::Clock;
+use crate::database::{ConfirmationMessage, Database, ScheduledJob};
+use crate::jobs::JobHandler;
+use crate::Error;
+
+/// Scheduled job kind deleting old confirmations
+pub const CONFIRMATION_CLEANUP_JOB: &str = "confirmation_cleanup";
+
+/// Interval between runs of the cleanup
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Most confirmations deleted in one run, so a backlog is spread over runs
+const MAX_DELETIONS_PER_RUN: i64 = 100;
+
+/// Shortest age, in hours, confirmations can be kept for
+pub const MIN_MAX_AGE_HOURS: i32 = 1;
+
+/// Longest age, in hours, confirmations can be kept for (30 days)
+pub const MAX_MAX_AGE_HOURS: i32 = 720;
+
+/// Job handler deleting expired confirmations every `CLEANUP_INTERVAL`
+pub struct ConfirmationCleanupJob {
+    http: Arc<serenity::Http>,
+    database: Arc<Mutex<Database>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl ConfirmationCleanupJob {
+    /// Creates the handler.
+    ///
+    /// # Arguments
+    /// * `http` - The Discord HTTP client.
+    /// * `database` - Database connection shared with the bot.
+    /// * `clock` - The clock deciding which confirmations expired.
+    pub fn new(
+        http: Arc<serenity::Http>,
+        database: Arc<Mutex<Database>>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            http,
+            database,
+            clock,
+        }
+    }
+
+    /// Deletes a confirmation from its channel.
+    ///
+    /// # Arguments
+    /// * `message` - The confirmation.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    async fn delete(&self, message: &ConfirmationMessage) -> Result<(), Error> {
+        serenity::ChannelId::new(message.channel_id.parse()?)
+            .delete_message(
+                &self.http,
+                serenity::MessageId::new(message.message_id.parse()?),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl JobHandler for ConfirmationCleanupJob {
+    async fn run(&self, _job: &ScheduledJob) -> Result<(), Error> {
+        let expired = self
+            .database
+            .lock()
+            .await
+            .get_expired_confirmations(self.clock.now(), MAX_DELETIONS_PER_RUN)
+            .await?;
+        if expired.is_empty() {
+            return Ok(());
+        }
+
+        for message in &expired {
+            // Confirmations deleted by hand, or in channels the bot lost access
+            // to, are forgotten as well so they are not retried forever.
+            if let Err(e) = self.delete(message).await {
+                warn!(
+                    "Failed to delete confirmation {} in guild {}: {}",
+                    message.message_id, message.guild_id, e
+                );
+            }
+        }
+
+        let message_ids: Vec<String> = expired
+            .into_iter()
+            .map(|message| message.message_id)
+            .collect();
+        let forgotten = self
+            .database
+            .lock()
+            .await
+            .forget_confirmations(&message_ids)
+            .await?;
+        info!("Cleaned up {} old confirmations", forgotten);
+
+        Ok(())
+    }
+
+    fn repeat_every(&self) -> Option<Duration> {
+        Some(CLEANUP_INTERVAL)
+    }
+}
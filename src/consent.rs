@@ -0,0 +1,102 @@
+//! The data policy users accept before their first log.
+//!
+//! Nothing is stored about a user until they accept the policy shown by
+//! `request_consent`; the accept button then logs the panel button that
+//! triggered the prompt. The accepted version is stored on the user, so
+//! bumping `POLICY_VERSION` after changing `POLICY_TEXT` prompts everyone
+//! again on their next log.
+
+use poise::serenity_prelude as serenity;
+
+use crate::commands::panel::log_button_press;
+use crate::{deferral, Data, Error};
+
+/// Current version of the data policy; bump it whenever `POLICY_TEXT` changes
+pub const POLICY_VERSION: i32 = 1;
+
+/// The data policy shown to users
+pub const POLICY_TEXT: &str = "記録を始める前に、データの取り扱いをご確認ください。\n\
+    - 記録した日時・種類・本数と、DiscordのユーザーID・ユーザー名を保存します。\n\
+    - 記録はサーバー内の集計や役職の付与に使われます。\n\
+    - データの削除を希望する場合は、ボットの管理者に連絡してください。\n\
+    同意する場合は下のボタンを押してください。押したボタンの記録はその後に行われます。";
+
+/// Prefix of the custom ID of the accept button, followed by the smoking type to log
+const ACCEPT_PREFIX: &str = "consent:accept:";
+
+/// Encodes the custom ID of the accept button.
+///
+/// # Arguments
+/// * `smoking_type_id` - The smoking type logged once the policy is accepted.
+///
+/// # Returns
+/// The custom ID.
+pub fn accept_id(smoking_type_id: i32) -> String {
+    format!("{}{}", ACCEPT_PREFIX, smoking_type_id)
+}
+
+/// Decodes the custom ID of an accept button.
+///
+/// # Arguments
+/// * `custom_id` - The custom ID of a pressed button.
+///
+/// # Returns
+/// The smoking type to log, or `None` if the button is not an accept button.
+pub fn parse_accept_id(custom_id: &str) -> Option<i32> {
+    let smoking_type_id = custom_id.strip_prefix(ACCEPT_PREFIX)?;
+    let parsed: i32 = smoking_type_id.parse().ok()?;
+
+    (parsed.to_string() == smoking_type_id).then_some(parsed)
+}
+
+/// Responds to a panel button press with an ephemeral prompt to accept the data policy.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `mci` - The button press that requires consent.
+/// * `smoking_type_id` - The smoking type of the pressed button, logged once the policy is accepted.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn request_consent(
+    ctx: &serenity::Context,
+    mci: &serenity::ComponentInteraction,
+    smoking_type_id: i32,
+) -> Result<(), Error> {
+    let button = serenity::CreateButton::new(accept_id(smoking_type_id))
+        .style(serenity::ButtonStyle::Success)
+        .label("同意する");
+    deferral::respond(
+        ctx,
+        mci,
+        POLICY_TEXT.to_string(),
+        vec![serenity::CreateActionRow::Buttons(vec![button])],
+        Vec::new(),
+        true,
+    )
+    .await
+}
+
+/// Handles a press of the accept button: records the consent, then logs the
+/// button the user originally pressed.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `mci` - The accept button press.
+/// * `smoking_type_id` - The smoking type to log.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn handle_accept(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &serenity::ComponentInteraction,
+    smoking_type_id: i32,
+) -> Result<(), Error> {
+    data.logging
+        .accept_policy(&mci.user.id.get().to_string(), &mci.user.name)
+        .await?;
+
+    log_button_press(ctx, data, mci, smoking_type_id).await
+}
@@ -0,0 +1,181 @@
+//! Codec for the `custom_id` of panel buttons.
+//!
+//! A button's custom ID is `<panel>:<smoking type ID>`, where the panel ID
+//! identifies the stored panel the press is recorded against, so presses on
+//! panels posted before a restart are still handled. Custom IDs come back from Discord untrusted (stale panels,
+//! modified clients), so decoding never panics and rejects anything that
+//! could not have been produced by `CustomId::encode`.
+//!
+//! The button offering to refresh a stale panel uses `RefreshId`, encoded as
+//! `refresh:<panel message ID>:<panel>`, which never decodes as a `CustomId`.
+
+use std::{fmt, str::FromStr};
+
+/// Maximum length of a custom ID accepted by Discord
+pub const MAX_LENGTH: usize = 100;
+
+/// Separator between the panel ID and the smoking type ID
+const SEPARATOR: char = ':';
+
+/// Errors that can occur while decoding a custom ID
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseCustomIdError {
+    #[error("Custom ID is longer than {MAX_LENGTH} characters")]
+    TooLong,
+    #[error("Custom ID has no panel ID")]
+    MissingPanel,
+    #[error("Invalid smoking type ID: {0}")]
+    InvalidSmokingTypeId(String),
+    #[error("Invalid message ID: {0}")]
+    InvalidMessageId(String),
+}
+
+/// Prefix of the custom ID of refresh buttons
+const REFRESH_PREFIX: &str = "refresh";
+
+/// The decoded custom ID of a panel button
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomId {
+    /// The ID of the panel the button belongs to
+    pub panel: String,
+    /// The smoking type logged by the button
+    pub smoking_type_id: i32,
+}
+
+impl CustomId {
+    /// Creates a custom ID for a panel button.
+    ///
+    /// # Arguments
+    /// * `panel` - The panel ID. Must not contain `:`.
+    /// * `smoking_type_id` - The smoking type logged by the button.
+    pub fn new(panel: impl Into<String>, smoking_type_id: i32) -> Self {
+        Self {
+            panel: panel.into(),
+            smoking_type_id,
+        }
+    }
+
+    /// Encodes the custom ID for use on a button.
+    ///
+    /// # Returns
+    /// The encoded custom ID.
+    pub fn encode(&self) -> String {
+        self.to_string()
+    }
+
+    /// Checks whether an encoded custom ID belongs to a panel, without fully decoding it.
+    ///
+    /// # Arguments
+    /// * `custom_id` - The encoded custom ID.
+    /// * `panel` - The panel ID.
+    ///
+    /// # Returns
+    /// `true` if the custom ID was produced for a button of the panel.
+    pub fn belongs_to(custom_id: &str, panel: &str) -> bool {
+        custom_id
+            .rsplit_once(SEPARATOR)
+            .is_some_and(|(prefix, _)| prefix == panel)
+    }
+}
+
+impl fmt::Display for CustomId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.panel, SEPARATOR, self.smoking_type_id)
+    }
+}
+
+impl FromStr for CustomId {
+    type Err = ParseCustomIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() > MAX_LENGTH {
+            return Err(ParseCustomIdError::TooLong);
+        }
+
+        let (panel, smoking_type_id) = s
+            .rsplit_once(SEPARATOR)
+            .ok_or(ParseCustomIdError::MissingPanel)?;
+        if panel.is_empty() || panel.contains(SEPARATOR) {
+            return Err(ParseCustomIdError::MissingPanel);
+        }
+
+        // Only the canonical form produced by `encode` is accepted, so e.g.
+        // `+1` or `01` do not alias a real button.
+        let invalid = || ParseCustomIdError::InvalidSmokingTypeId(smoking_type_id.to_string());
+        let parsed: i32 = smoking_type_id.parse().map_err(|_| invalid())?;
+        if parsed.to_string() != smoking_type_id {
+            return Err(invalid());
+        }
+
+        Ok(Self::new(panel, parsed))
+    }
+}
+
+/// The decoded custom ID of a button refreshing a panel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefreshId {
+    /// The ID of the panel message to refresh
+    pub message_id: u64,
+    /// The ID of the panel
+    pub panel: String,
+}
+
+impl RefreshId {
+    /// Creates the custom ID of a refresh button.
+    ///
+    /// # Arguments
+    /// * `message_id` - The ID of the panel message.
+    /// * `panel` - The panel ID. Must not contain `:`.
+    pub fn new(message_id: u64, panel: impl Into<String>) -> Self {
+        Self {
+            message_id,
+            panel: panel.into(),
+        }
+    }
+
+    /// Encodes the custom ID for use on a button.
+    ///
+    /// # Returns
+    /// The encoded custom ID.
+    pub fn encode(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for RefreshId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}{}{}",
+            REFRESH_PREFIX, SEPARATOR, self.message_id, SEPARATOR, self.panel
+        )
+    }
+}
+
+impl FromStr for RefreshId {
+    type Err = ParseCustomIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() > MAX_LENGTH {
+            return Err(ParseCustomIdError::TooLong);
+        }
+
+        let mut parts = s.splitn(3, SEPARATOR);
+        if parts.next() != Some(REFRESH_PREFIX) {
+            return Err(ParseCustomIdError::MissingPanel);
+        }
+        let message_id = parts.next().unwrap_or_default();
+        let panel = parts.next().unwrap_or_default();
+        if panel.is_empty() || panel.contains(SEPARATOR) {
+            return Err(ParseCustomIdError::MissingPanel);
+        }
+
+        let invalid = || ParseCustomIdError::InvalidMessageId(message_id.to_string());
+        let parsed: u64 = message_id.parse().map_err(|_| invalid())?;
+        if parsed.to_string() != message_id {
+            return Err(invalid());
+        }
+
+        Ok(Self::new(parsed, panel))
+    }
+}
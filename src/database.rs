@@ -1,8 +1,16 @@
 use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
-use sqlx::{postgres::PgPool, Error};
+use sqlx::{postgres::PgPool, Error, PgExecutor};
 use std::sync::Arc;
 
+use crate::anonymize::Anonymizer;
+use crate::explain::ParamValue;
+use crate::export::Granularity;
+use crate::latency::QueryTimer;
+use crate::log_notify::{self, LogChange};
+use crate::rollover;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct User {
     pub discord_id: String,
@@ -11,12 +19,97 @@ pub struct User {
     pub updated_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SmokingType {
     pub id: i32,
     pub type_name: String,
     pub description: Option<String>,
     pub created_at: Option<DateTime<Utc>>,
+    /// Emoji shown on the type's panel button
+    pub emoji: Option<String>,
+    /// What one logged unit is (e.g. `本`, `pod`)
+    pub unit: Option<String>,
+    /// Typical price of one unit, in the minor units of `currency`
+    pub typical_price: Option<i32>,
+    /// ISO 4217 code of the currency of `typical_price`
+    pub currency: Option<String>,
+}
+
+/// A conversion hint between the units of two smoking types
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnitConversion {
+    pub from_type_id: i32,
+    pub to_type_id: i32,
+    /// How many units of the target type one unit of the source type is
+    pub factor: f64,
+}
+
+/// A category of habit (e.g. cigarettes, coffee) grouping smoking types that count in one unit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Category {
+    pub id: i32,
+    pub name: String,
+    /// What one logged unit of the category's types is (e.g. `杯`)
+    pub unit: String,
+    pub emoji: Option<String>,
+}
+
+impl Category {
+    /// Returns the name of the category, preceded by its emoji if it has one.
+    pub fn label(&self) -> String {
+        match &self.emoji {
+            Some(emoji) => format!("{} {}", emoji, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// A user's total for a day in a category they set a daily goal for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CategoryProgress {
+    pub category: Category,
+    pub total: i64,
+    pub daily_goal: i32,
+}
+
+/// A log carried over from another bot instance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedLog {
+    pub discord_id: String,
+    pub smoking_type_id: i32,
+    pub quantity: i32,
+    pub smoked_at: DateTime<Utc>,
+}
+
+/// A log taken while the database was unreachable, waiting to be written
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BufferedLog {
+    pub discord_id: String,
+    pub guild_id: Option<String>,
+    pub smoking_type_id: i32,
+    pub quantity: i32,
+    pub smoked_at: DateTime<Utc>,
+    pub idempotency_key: Option<String>,
+}
+
+/// Details of a smoking type to add, or to complete a known type with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypeDetails<'a> {
+    pub type_name: &'a str,
+    pub description: Option<&'a str>,
+    pub emoji: Option<&'a str>,
+    pub unit: Option<&'a str>,
+    pub typical_price: Option<i32>,
+    pub currency: Option<&'a str>,
+}
+
+/// The result of making sure smoking types exist
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnsuredTypes {
+    /// The IDs of the types, in the order they were given
+    pub type_ids: Vec<i32>,
+    /// How many of the types were added
+    pub added: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -26,6 +119,9 @@ pub struct SmokingLog {
     pub smoking_type_id: i32,
     pub quantity: i32,
     pub smoked_at: DateTime<Utc>,
+    pub device_name: Option<String>,
+    pub guild_id: Option<String>,
+    pub idempotency_key: Option<String>,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
 }
@@ -41,8 +137,374 @@ pub struct DailySmokingSummary {
     pub total_quantity: Option<i64>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Device {
+    pub id: i32,
+    pub discord_id: String,
+    pub device_name: String,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReachedMilestone {
+    pub smoke_free_days: i32,
+    pub reached_on: NaiveDate,
+    pub reached_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmailSubscription {
+    pub discord_id: String,
+    pub email: String,
+    pub unsubscribe_token: String,
+    pub verified_at: Option<DateTime<Utc>>,
+    pub last_sent_week: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PushSubscription {
+    pub service: String,
+    pub target: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShortcutLink {
+    pub id: i32,
+    pub discord_id: String,
+    pub smoking_type_id: i32,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DailyTotal {
+    pub smoke_date: NaiveDate,
+    pub total_quantity: i64,
+}
+
+/// A user's total of one smoking type over a period, with the type's price
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserTypeTotal {
+    pub type_name: String,
+    pub description: Option<String>,
+    pub typical_price: Option<i32>,
+    pub currency: Option<String>,
+    pub total_quantity: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RoleConnection {
+    pub discord_id: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_at: DateTime<Utc>,
+    pub last_pushed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MilestoneRole {
+    pub guild_id: String,
+    pub role_id: String,
+    pub smoke_free_days: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageTemplate {
+    pub guild_id: String,
+    pub template_key: String,
+    pub template: String,
+    pub version: i32,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutboxMessage {
+    pub id: i32,
+    pub topic: String,
+    pub payload: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub dead_lettered_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledJob {
+    pub id: i32,
+    pub kind: String,
+    pub payload: String,
+    pub run_at: DateTime<Utc>,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuildSettings {
+    pub timezone: Option<String>,
+    pub panel_channel_id: Option<String>,
+    pub panel_smoking_type_ids: Option<Vec<i32>>,
+    pub onboarded_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Panel {
+    pub panel_id: String,
+    pub guild_id: Option<String>,
+    pub channel_id: String,
+    pub message_id: Option<String>,
+    pub smoking_type_ids: Option<Vec<i32>>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackingPause {
+    pub id: i32,
+    pub discord_id: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+}
+
+/// A user's total of one smoking type over a day, week or month
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeriodTypeTotal {
+    /// The first date of the period
+    pub period: NaiveDate,
+    pub type_name: String,
+    pub total_quantity: i64,
+}
+
+/// How much a user smoked on days with and without one of their event tags
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventCorrelation {
+    pub tag: String,
+    pub days_with: i64,
+    pub average_with: f64,
+    pub days_without: i64,
+    pub average_without: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuildTypeTotal {
+    pub type_name: String,
+    pub description: Option<String>,
+    pub total_quantity: i64,
+    pub member_count: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuildMonthlyTypeTotal {
+    pub month: NaiveDate,
+    pub type_name: String,
+    pub total_quantity: i64,
+    pub member_count: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedGuildLog {
+    pub discord_id: String,
+    pub username: String,
+    pub type_name: String,
+    pub quantity: i32,
+    pub smoked_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForumChannel {
+    pub guild_id: String,
+    pub channel_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForumThread {
+    pub week_start: NaiveDate,
+    pub thread_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuildChallenge {
+    pub guild_id: String,
+    pub channel_id: String,
+    pub target_percent: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChallengeWeek {
+    pub week_start: NaiveDate,
+    pub channel_id: String,
+    pub message_id: String,
+    pub baseline: i64,
+    pub target_percent: i32,
+    pub updated_on: NaiveDate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Wager {
+    pub id: i32,
+    pub channel_id: String,
+    pub challenger_id: String,
+    pub opponent_id: String,
+    pub metric: String,
+    pub period: String,
+    pub status: String,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub winner_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuildAchievement {
+    pub id: i32,
+    pub name: String,
+    pub condition: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EarnedAchievement {
+    pub achievement_id: i32,
+    pub name: String,
+    pub earned_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeaderboardSettings {
+    pub guild_id: String,
+    pub channel_id: String,
+    pub season_length: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeaderboardSeason {
+    pub id: i32,
+    pub number: i32,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+    pub archived_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemberTotal {
+    pub discord_id: String,
+    pub total: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemberPeriodTotals {
+    pub discord_id: String,
+    pub previous: i64,
+    pub current: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Standing {
+    pub discord_id: String,
+    pub rank: i32,
+    pub total: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmokeFreeMember {
+    pub discord_id: String,
+    pub last_smoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelCounter {
+    pub guild_id: String,
+    pub channel_id: String,
+    pub metric: String,
+    pub target: String,
+    pub last_text: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfirmationMessage {
+    pub message_id: String,
+    pub guild_id: String,
+    pub channel_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuildRemoval {
+    pub guild_id: String,
+    pub removed_at: DateTime<Utc>,
+    pub delete_after: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypeCap {
+    pub smoking_type_id: i32,
+    pub daily_cap: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdminAuditEntry {
+    pub id: i32,
+    pub actor_id: String,
+    pub action: String,
+    pub target_id: String,
+    pub details: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicateLog {
+    pub id: i32,
+    pub original_id: i32,
+    pub discord_id: String,
+    pub smoking_type_id: i32,
+    pub quantity: i32,
+    pub smoked_at: DateTime<Utc>,
+}
+
+/// How duplicate logs are cleaned up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateResolution {
+    /// Add the duplicates' quantities to the original logs, then remove the duplicates
+    Merge,
+    /// Remove the duplicates
+    Delete,
+}
+
+impl DuplicateResolution {
+    /// Returns the name recorded in the audit log.
+    pub fn action(self) -> &'static str {
+        match self {
+            Self::Merge => "merge_duplicates",
+            Self::Delete => "delete_duplicates",
+        }
+    }
+}
+
+/// Outbox topic of messages written for every new smoking log
+pub const LOG_CREATED_TOPIC: &str = "log_created";
+
+/// Indexes the hot queries rely on, checked at startup
+pub const EXPECTED_INDEXES: &[&str] = &[
+    "idx_smoking_logs_discord_id_smoked_at",
+    "idx_smoking_logs_guild_id_smoked_at",
+    "idx_smoking_logs_smoked_at",
+    "idx_devices_discord_id",
+    "idx_shortcut_links_discord_id",
+    "idx_smoke_break_channels_guild_id",
+    "idx_outbox_pending",
+    "idx_smoking_logs_idempotency_key",
+    "idx_scheduled_jobs_due",
+    "idx_wagers_due",
+    "idx_confirmation_messages_sent_at",
+];
+
 pub struct Database {
     pool: Arc<PgPool>,
+    outbox: bool,
+    notify_channel: Option<String>,
+    anonymizer: Anonymizer,
 }
 
 impl Database {
@@ -53,6 +515,51 @@ impl Database {
     pub fn new(pool: PgPool) -> Self {
         Self {
             pool: Arc::new(pool),
+            outbox: false,
+            notify_channel: None,
+            anonymizer: Anonymizer::default(),
+        }
+    }
+
+    /// Enables the outbox: every smoking log is written together with a
+    /// `log_created` outbox message, in the same transaction.
+    pub fn with_outbox(mut self) -> Self {
+        self.outbox = true;
+        self
+    }
+
+    /// Enables log notifications: every inserted or deleted smoking log sends a
+    /// `pg_notify` on a channel, in the same transaction (see `log_notify`).
+    ///
+    /// # Arguments
+    /// * `channel` - The channel consumers `LISTEN` on.
+    pub fn with_log_notifications(mut self, channel: &str) -> Self {
+        self.notify_channel = Some(channel.to_string());
+        self
+    }
+
+    /// Sets how Discord IDs are anonymized in outbox messages and log notifications.
+    ///
+    /// # Arguments
+    /// * `anonymizer` - The anonymizer applied to outgoing payloads.
+    pub fn with_anonymizer(mut self, anonymizer: Anonymizer) -> Self {
+        self.anonymizer = anonymizer;
+        self
+    }
+
+    /// Returns the anonymizer applied to data leaving the bot.
+    pub fn anonymizer(&self) -> &Anonymizer {
+        &self.anonymizer
+    }
+
+    /// Returns a handle limited to the data of one guild.
+    ///
+    /// # Arguments
+    /// * `guild_id` - The ID of the guild.
+    pub fn guild(&self, guild_id: &str) -> GuildScope<'_> {
+        GuildScope {
+            db: self,
+            guild_id: guild_id.to_string(),
         }
     }
 
@@ -65,6 +572,8 @@ impl Database {
     /// # Returns
     /// A Result containing the created `User` or an `Error`.
     pub async fn create_user(&self, discord_id: &str, username: &str) -> Result<User, Error> {
+        let _timer = QueryTimer::start("create_user");
+
         let user = sqlx::query_as!(
             User,
             r#"
@@ -98,6 +607,8 @@ impl Database {
         discord_id: &str,
         username: &str,
     ) -> Result<User, Error> {
+        let _timer = QueryTimer::start("get_or_create_user");
+
         let mut tx = self.pool.begin().await?;
 
         let user = sqlx::query_as!(
@@ -173,6 +684,8 @@ impl Database {
     /// # Returns
     /// A Result containing a boolean indicating whether the user exists or an `Error`.
     pub async fn user_exists(&self, discord_id: &str) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("user_exists");
+
         let exists = sqlx::query_scalar!(
             r#"
             SELECT EXISTS(SELECT 1 FROM users WHERE discord_id = $1) as "exists!"
@@ -189,6 +702,7 @@ impl Database {
     ///
     /// # Arguments
     /// * `discord_id` - The Discord ID of the user.
+    /// * `guild_id` - The ID of the guild the event was logged in, if any.
     /// * `smoking_type_id` - The ID of the smoking type.
     /// * `quantity` - The quantity of cigarettes smoked.
     ///
@@ -197,32 +711,28 @@ impl Database {
     pub async fn log_smoking(
         &self,
         discord_id: &str,
-
+        guild_id: Option<&str>,
         smoking_type_id: i32,
         quantity: i32,
     ) -> Result<SmokingLog, Error> {
-        let log = sqlx::query_as!(
-            SmokingLog,
-            r#"
-            INSERT INTO smoking_logs (discord_id, smoking_type_id, quantity)
-            VALUES ($1, $2, $3)
+        let _timer = QueryTimer::start("log_smoking");
 
-            RETURNING 
-                id as "id!", 
-                discord_id as "discord_id!", 
-                smoking_type_id as "smoking_type_id!", 
-                quantity as "quantity!",
-                smoked_at as "smoked_at!",
-                created_at,
-                updated_at
+        let mut tx = self.pool.begin().await?;
 
-            "#,
+        let log = insert_smoking_log(
+            &mut *tx,
             discord_id,
+            guild_id,
             smoking_type_id,
-            quantity
+            quantity,
+            None,
         )
-        .fetch_one(&*self.pool)
         .await?;
+        self.enqueue_log_created(&mut tx, &log).await?;
+        self.notify_log_change(&mut tx, LogChange::Created, &log)
+            .await?;
+
+        tx.commit().await?;
 
         Ok(log)
     }
@@ -231,7 +741,8 @@ impl Database {
     ///
     /// # Arguments
     /// * `discord_id` - The Discord ID of the user.
-    /// * `date` - The date for which to retrieve the summary.
+    /// * `date` - The local date for which to retrieve the summary.
+    /// * `timezone` - The time zone the date is counted in.
     ///
     /// # Returns
     /// A Result containing a vector of `DailySmokingSummary` or an `Error`.
@@ -239,36 +750,115 @@ impl Database {
         &self,
         discord_id: &str,
         date: NaiveDate,
+        timezone: Tz,
     ) -> Result<Vec<DailySmokingSummary>, Error> {
-        let summary = sqlx::query_as!(
-            DailySmokingSummary,
+        let _timer = QueryTimer::start("get_daily_summary");
+
+        select_daily_summary(&*self.pool, discord_id, date, timezone).await
+    }
+
+    /// Logs a smoking event and retrieves the resulting daily summary in one transaction.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `guild_id` - The ID of the guild the event was logged in, if any.
+    /// * `smoking_type_id` - The ID of the smoking type.
+    /// * `quantity` - The quantity of cigarettes smoked.
+    /// * `idempotency_key` - The key identifying the request, if any.
+    /// * `date` - The local date for which to retrieve the summary.
+    /// * `timezone` - The time zone the date is counted in.
+    ///
+    /// # Returns
+    /// A Result containing the logged `SmokingLog` and the `DailySmokingSummary` rows, or an `Error`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn log_smoking_with_summary(
+        &self,
+        discord_id: &str,
+        guild_id: Option<&str>,
+        smoking_type_id: i32,
+        quantity: i32,
+        idempotency_key: Option<&str>,
+        date: NaiveDate,
+        timezone: Tz,
+    ) -> Result<(SmokingLog, Vec<DailySmokingSummary>), Error> {
+        let _timer = QueryTimer::start("log_smoking_with_summary");
+
+        let mut tx = self.pool.begin().await?;
+
+        let log = insert_smoking_log(
+            &mut *tx,
+            discord_id,
+            guild_id,
+            smoking_type_id,
+            quantity,
+            idempotency_key,
+        )
+        .await?;
+        self.enqueue_log_created(&mut tx, &log).await?;
+        self.notify_log_change(&mut tx, LogChange::Created, &log)
+            .await?;
+        let summary = select_daily_summary(&mut *tx, discord_id, date, timezone).await?;
+
+        tx.commit().await?;
+
+        Ok((log, summary))
+    }
+
+    /// Writes a log that was buffered while the database was unreachable,
+    /// marking it as replayed.
+    ///
+    /// # Arguments
+    /// * `log` - The buffered log.
+    /// * `replayed_at` - When the log is written.
+    ///
+    /// # Returns
+    /// A Result containing the stored `SmokingLog`, `None` if a log with the same
+    /// idempotency key was already stored, or an `Error`.
+    pub async fn insert_replayed_log(
+        &self,
+        log: &BufferedLog,
+        replayed_at: DateTime<Utc>,
+    ) -> Result<Option<SmokingLog>, Error> {
+        let _timer = QueryTimer::start("insert_replayed_log");
+
+        let mut tx = self.pool.begin().await?;
+        let inserted = sqlx::query_as!(
+            SmokingLog,
             r#"
-            SELECT 
-                sl.discord_id as "discord_id!",
-                u.username as "username!",
-                DATE(sl.smoked_at) as "smoke_date!",
-                st.type_name as "type_name!",
-                st.description as "description!",
-                SUM(sl.quantity) as total_quantity
-            FROM smoking_logs sl
-            JOIN users u ON sl.discord_id = u.discord_id
-            JOIN smoking_types st ON sl.smoking_type_id = st.id
-            WHERE sl.discord_id = $1 
-            AND DATE(sl.smoked_at) = $2
-            GROUP BY 
-                sl.discord_id,
-                u.username,
-                DATE(sl.smoked_at),
-                st.type_name,
-                st.description
+            INSERT INTO smoking_logs
+                (discord_id, guild_id, smoking_type_id, quantity, smoked_at, idempotency_key, replayed_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (discord_id, idempotency_key) WHERE idempotency_key IS NOT NULL DO NOTHING
+            RETURNING
+                id as "id!",
+                discord_id as "discord_id!",
+                smoking_type_id as "smoking_type_id!",
+                quantity as "quantity!",
+                smoked_at as "smoked_at!",
+                device_name,
+                guild_id,
+                idempotency_key,
+                created_at,
+                updated_at
             "#,
-            discord_id,
-            date
+            log.discord_id,
+            log.guild_id,
+            log.smoking_type_id,
+            log.quantity,
+            log.smoked_at,
+            log.idempotency_key,
+            replayed_at
         )
-        .fetch_all(&*self.pool)
+        .fetch_optional(&mut *tx)
         .await?;
+        if let Some(inserted) = &inserted {
+            self.enqueue_log_created(&mut tx, inserted).await?;
+            self.notify_log_change(&mut tx, LogChange::Created, inserted)
+                .await?;
+        }
+        tx.commit().await?;
 
-        Ok(summary)
+        Ok(inserted)
     }
 
     /// Retrieves a smoking type by its ID.
@@ -279,6 +869,8 @@ impl Database {
     /// # Returns
     /// A Result containing the `SmokingType` or an `Error`.
     pub async fn get_smoking_type(&self, id: i32) -> Result<SmokingType, Error> {
+        let _timer = QueryTimer::start("get_smoking_type");
+
         let smoking_type = sqlx::query_as!(
             SmokingType,
             r#"
@@ -286,7 +878,11 @@ impl Database {
                 id as "id!", 
                 type_name as "type_name!", 
                 description,
-                created_at
+                created_at,
+                emoji,
+                unit,
+                typical_price,
+                currency
             FROM smoking_types
             WHERE id = $1
             "#,
@@ -298,11 +894,13 @@ impl Database {
         Ok(smoking_type)
     }
 
-    /// Retrieves all smoking types.
+    /// Retrieves all smoking types that were not archived.
     ///
     /// # Returns
     /// A Result containing a vector of `SmokingType` or an `Error`.
     pub async fn get_smoking_types(&self) -> Result<Vec<SmokingType>, Error> {
+        let _timer = QueryTimer::start("get_smoking_types");
+
         let types = sqlx::query_as!(
             SmokingType,
             r#"
@@ -310,8 +908,13 @@ impl Database {
                 id as "id!",
                 type_name as "type_name!",
                 description,
-                created_at
+                created_at,
+                emoji,
+                unit,
+                typical_price,
+                currency
             FROM smoking_types
+            WHERE archived_at IS NULL
             ORDER BY id
             "#
         )
@@ -321,17 +924,257 @@ impl Database {
         Ok(types)
     }
 
-    /// Checks if a smoking type exists in the database.
+    /// Finds a smoking type by its type name.
+    ///
+    /// The name of a type merged into another finds the type it was merged into.
+    ///
+    /// # Arguments
+    /// * `type_name` - The type name of the smoking type (e.g. `iqos`).
+    ///
+    /// # Returns
+    /// A Result containing the `SmokingType` if found, `None` otherwise, or an `Error`.
+    pub async fn find_smoking_type_by_name(
+        &self,
+        type_name: &str,
+    ) -> Result<Option<SmokingType>, Error> {
+        let _timer = QueryTimer::start("find_smoking_type_by_name");
+
+        let smoking_type = sqlx::query_as!(
+            SmokingType,
+            r#"
+            SELECT
+                t.id as "id!",
+                t.type_name as "type_name!",
+                t.description,
+                t.created_at,
+                t.emoji,
+                t.unit,
+                t.typical_price,
+                t.currency
+            FROM smoking_types s
+            JOIN smoking_types t ON t.id = COALESCE(s.merged_into, s.id)
+            WHERE s.type_name = $1
+            ORDER BY s.archived_at IS NOT NULL, s.id
+            LIMIT 1
+            "#,
+            type_name
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(smoking_type)
+    }
+
+    /// Adds the unknown types among the given ones, filling in the details missing from known types.
+    ///
+    /// Types are matched by type name, following merges; a known type keeps its
+    /// description, emoji, unit and price when it has them.
+    ///
+    /// # Arguments
+    /// * `types` - The types.
+    ///
+    /// # Returns
+    /// A Result containing the IDs of the types in the given order and how
+    /// many were added, or an `Error`.
+    pub async fn ensure_smoking_types(
+        &self,
+        types: &[TypeDetails<'_>],
+    ) -> Result<EnsuredTypes, Error> {
+        let _timer = QueryTimer::start("ensure_smoking_types");
+
+        let mut tx = self.pool.begin().await?;
+        let mut ensured = EnsuredTypes::default();
+        for details in types {
+            let known = sqlx::query_scalar!(
+                r#"
+                UPDATE smoking_types
+                SET description = COALESCE(NULLIF(TRIM(description), ''), $2),
+                    emoji = COALESCE(emoji, $3),
+                    unit = COALESCE(unit, $4),
+                    typical_price = COALESCE(typical_price, $5),
+                    currency = COALESCE(currency, $6)
+                WHERE id = (
+                    SELECT COALESCE(merged_into, id)
+                    FROM smoking_types
+                    WHERE type_name = $1
+                    ORDER BY archived_at IS NOT NULL, id
+                    LIMIT 1
+                )
+                RETURNING id
+                "#,
+                details.type_name,
+                details.description,
+                details.emoji,
+                details.unit,
+                details.typical_price,
+                details.currency
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            let id = match known {
+                Some(id) => id,
+                None => {
+                    ensured.added += 1;
+                    sqlx::query_scalar!(
+                        r#"
+                        INSERT INTO smoking_types
+                            (type_name, description, emoji, unit, typical_price, currency)
+                        VALUES ($1, $2, $3, $4, $5, $6)
+                        RETURNING id
+                        "#,
+                        details.type_name,
+                        details.description,
+                        details.emoji,
+                        details.unit,
+                        details.typical_price,
+                        details.currency
+                    )
+                    .fetch_one(&mut *tx)
+                    .await?
+                }
+            };
+            ensured.type_ids.push(id);
+        }
+        tx.commit().await?;
+
+        Ok(ensured)
+    }
+
+    /// Stores the configuration of a newly installed panel.
+    ///
+    /// # Arguments
+    /// * `panel_id` - The ID of the panel, used in its buttons' custom IDs.
+    /// * `guild_id` - The guild the panel was installed in, if any.
+    /// * `channel_id` - The channel the panel was installed in.
+    /// * `smoking_type_ids` - The smoking types shown on the panel, or `None` for all types.
+    ///
+    /// # Returns
+    /// A Result containing the `Panel` or an `Error`.
+    pub async fn create_panel(
+        &self,
+        panel_id: &str,
+        guild_id: Option<&str>,
+        channel_id: &str,
+        smoking_type_ids: Option<&[i32]>,
+    ) -> Result<Panel, Error> {
+        let _timer = QueryTimer::start("create_panel");
+
+        let panel = sqlx::query_as!(
+            Panel,
+            r#"
+            INSERT INTO panels (panel_id, guild_id, channel_id, smoking_type_ids)
+            VALUES ($1, $2, $3, $4)
+            RETURNING panel_id, guild_id, channel_id, message_id, smoking_type_ids, created_at
+            "#,
+            panel_id,
+            guild_id,
+            channel_id,
+            smoking_type_ids
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(panel)
+    }
+
+    /// Retrieves the configuration of a panel.
+    ///
+    /// # Arguments
+    /// * `panel_id` - The ID of the panel.
+    /// * `guild_id` - The guild the panel is used in, if any; panels of other guilds are not returned.
+    ///
+    /// # Returns
+    /// A Result containing the `Panel` if found, `None` otherwise, or an `Error`.
+    pub async fn get_panel(
+        &self,
+        panel_id: &str,
+        guild_id: Option<&str>,
+    ) -> Result<Option<Panel>, Error> {
+        let _timer = QueryTimer::start("get_panel");
+
+        let panel = sqlx::query_as!(
+            Panel,
+            r#"
+            SELECT panel_id, guild_id, channel_id, message_id, smoking_type_ids, created_at
+            FROM panels
+            WHERE panel_id = $1 AND guild_id IS NOT DISTINCT FROM $2
+            "#,
+            panel_id,
+            guild_id
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(panel)
+    }
+
+    /// Stores the ID of the message showing a panel.
+    ///
+    /// # Arguments
+    /// * `panel_id` - The ID of the panel.
+    /// * `message_id` - The ID of the message with the panel's buttons.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_panel_message(&self, panel_id: &str, message_id: &str) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_panel_message");
+
+        sqlx::query!(
+            r#"
+            UPDATE panels
+            SET message_id = $2
+            WHERE panel_id = $1
+            "#,
+            panel_id,
+            message_id
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves every panel whose message is known, across guilds.
+    ///
+    /// Panels of guilds the bot was removed from are left out.
+    ///
+    /// # Returns
+    /// A Result containing the panels ordered by creation, or an `Error`.
+    pub async fn get_posted_panels(&self) -> Result<Vec<Panel>, Error> {
+        let _timer = QueryTimer::start("get_posted_panels");
+
+        let panels = sqlx::query_as!(
+            Panel,
+            r#"
+            SELECT panel_id, guild_id, channel_id, message_id, smoking_type_ids, created_at
+            FROM panels
+            WHERE message_id IS NOT NULL
+                AND NOT EXISTS (SELECT 1 FROM guild_removals gr WHERE gr.guild_id = panels.guild_id)
+            ORDER BY created_at, panel_id
+            "#
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(panels)
+    }
+
+    /// Checks if a smoking type exists in the database and was not archived.
     ///
     /// # Arguments
     /// * `id` - The ID of the smoking type.
     ///
     /// # Returns
-    /// A Result containing a boolean indicating whether the smoking type exists or an `Error`.
+    /// A Result containing a boolean indicating whether the smoking type can be logged or an `Error`.
     pub async fn smoking_type_exists(&self, id: i32) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("smoking_type_exists");
+
         let exists = sqlx::query_scalar!(
             r#"
-            SELECT EXISTS(SELECT 1 FROM smoking_types WHERE id = $1) as "exists!"
+            SELECT EXISTS(
+                SELECT 1 FROM smoking_types WHERE id = $1 AND archived_at IS NULL
+            ) as "exists!"
             "#,
             id
         )
@@ -340,4 +1183,6539 @@ impl Database {
 
         Ok(exists)
     }
+
+    /// Registers a new device that may log smoking events through the HTTP API.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the device owner.
+    /// * `device_name` - A human-readable name for the device.
+    /// * `token_hash` - The SHA-256 hex digest of the device's API token.
+    ///
+    /// # Returns
+    /// A Result containing the created `Device` or an `Error`.
+    pub async fn create_device(
+        &self,
+        discord_id: &str,
+        device_name: &str,
+        token_hash: &str,
+    ) -> Result<Device, Error> {
+        let _timer = QueryTimer::start("create_device");
+
+        let device = sqlx::query_as!(
+            Device,
+            r#"
+            INSERT INTO devices (discord_id, device_name, token_hash)
+            VALUES ($1, $2, $3)
+            RETURNING
+                id,
+                discord_id,
+                device_name,
+                last_used_at,
+                created_at
+            "#,
+            discord_id,
+            device_name,
+            token_hash
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(device)
+    }
+
+    /// Looks up a device by the hash of its API token and marks it as used.
+    ///
+    /// # Arguments
+    /// * `token_hash` - The SHA-256 hex digest of the presented API token.
+    ///
+    /// # Returns
+    /// A Result containing the matching `Device`, `None` if the token is unknown, or an `Error`.
+    pub async fn authenticate_device(&self, token_hash: &str) -> Result<Option<Device>, Error> {
+        let _timer = QueryTimer::start("authenticate_device");
+
+        let device = sqlx::query_as!(
+            Device,
+            r#"
+            UPDATE devices
+            SET last_used_at = CURRENT_TIMESTAMP
+            WHERE token_hash = $1
+            RETURNING
+                id,
+                discord_id,
+                device_name,
+                last_used_at,
+                created_at
+            "#,
+            token_hash
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(device)
+    }
+
+    /// Logs a smoking event reported by a registered device.
+    ///
+    /// # Arguments
+    /// * `device` - The authenticated device reporting the event.
+    /// * `smoking_type_id` - The ID of the smoking type.
+    /// * `quantity` - The quantity of cigarettes smoked.
+    /// * `idempotency_key` - The client-chosen key of the request, if any.
+    ///
+    /// # Returns
+    /// A Result containing the logged `SmokingLog` or an `Error`.
+    pub async fn log_device_smoking(
+        &self,
+        device: &Device,
+        smoking_type_id: i32,
+        quantity: i32,
+        idempotency_key: Option<&str>,
+    ) -> Result<SmokingLog, Error> {
+        let _timer = QueryTimer::start("log_device_smoking");
+
+        let mut tx = self.pool.begin().await?;
+
+        let log = sqlx::query_as!(
+            SmokingLog,
+            r#"
+            INSERT INTO smoking_logs
+                (discord_id, smoking_type_id, quantity, device_name, idempotency_key)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING
+                id as "id!",
+                discord_id as "discord_id!",
+                smoking_type_id as "smoking_type_id!",
+                quantity as "quantity!",
+                smoked_at as "smoked_at!",
+                device_name,
+                guild_id,
+                idempotency_key,
+                created_at,
+                updated_at
+            "#,
+            device.discord_id,
+            smoking_type_id,
+            quantity,
+            device.device_name,
+            idempotency_key
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        self.enqueue_log_created(&mut tx, &log).await?;
+        self.notify_log_change(&mut tx, LogChange::Created, &log)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(log)
+    }
+
+    /// Logs one unit for the owner of a shortcut link.
+    ///
+    /// # Arguments
+    /// * `link` - The opened shortcut link.
+    /// * `idempotency_key` - The client-chosen key of the request, if any.
+    ///
+    /// # Returns
+    /// A Result containing the logged `SmokingLog` or an `Error`.
+    pub async fn log_shortcut_smoking(
+        &self,
+        link: &ShortcutLink,
+        idempotency_key: Option<&str>,
+    ) -> Result<SmokingLog, Error> {
+        let _timer = QueryTimer::start("log_shortcut_smoking");
+
+        let mut tx = self.pool.begin().await?;
+
+        let log = insert_smoking_log(
+            &mut *tx,
+            &link.discord_id,
+            None,
+            link.smoking_type_id,
+            1,
+            idempotency_key,
+        )
+        .await?;
+        self.enqueue_log_created(&mut tx, &log).await?;
+        self.notify_log_change(&mut tx, LogChange::Created, &log)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(log)
+    }
+
+    /// Finds the log a user created with an idempotency key.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `idempotency_key` - The key of the original request.
+    ///
+    /// # Returns
+    /// A Result containing the `SmokingLog`, `None` if the key has not been used, or an `Error`.
+    pub async fn find_log_by_idempotency_key(
+        &self,
+        discord_id: &str,
+        idempotency_key: &str,
+    ) -> Result<Option<SmokingLog>, Error> {
+        let _timer = QueryTimer::start("find_log_by_idempotency_key");
+
+        let log = sqlx::query_as!(
+            SmokingLog,
+            r#"
+            SELECT
+                id as "id!",
+                discord_id as "discord_id!",
+                smoking_type_id as "smoking_type_id!",
+                quantity as "quantity!",
+                smoked_at as "smoked_at!",
+                device_name,
+                guild_id,
+                idempotency_key,
+                created_at,
+                updated_at
+            FROM smoking_logs
+            WHERE discord_id = $1 AND idempotency_key = $2
+            "#,
+            discord_id,
+            idempotency_key
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(log)
+    }
+
+    /// Records the channel of a log created outside any guild, so the channel can be aggregated.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `idempotency_key` - The key the log was created with.
+    /// * `channel_id` - The ID of the DM or group DM channel.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn assign_log_channel(
+        &self,
+        discord_id: &str,
+        idempotency_key: &str,
+        channel_id: &str,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("assign_log_channel");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO channel_logs (log_id, channel_id)
+            SELECT id, $3
+            FROM smoking_logs
+            WHERE discord_id = $1 AND idempotency_key = $2 AND guild_id IS NULL
+            ON CONFLICT (log_id) DO NOTHING
+            "#,
+            discord_id,
+            idempotency_key,
+            channel_id
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Totals a channel's logs per smoking type over a period.
+    ///
+    /// # Arguments
+    /// * `channel_id` - The ID of the DM or group DM channel.
+    /// * `start` - The start of the period (inclusive).
+    /// * `end` - The end of the period (exclusive).
+    ///
+    /// # Returns
+    /// A Result containing the totals of the logged types ordered by type, or an `Error`.
+    pub async fn get_channel_type_totals(
+        &self,
+        channel_id: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<GuildTypeTotal>, Error> {
+        let _timer = QueryTimer::start("get_channel_type_totals");
+
+        let totals = sqlx::query_as!(
+            GuildTypeTotal,
+            r#"
+            SELECT
+                st.type_name as "type_name!",
+                st.description,
+                SUM(sl.quantity) as "total_quantity!",
+                COUNT(DISTINCT sl.discord_id) as "member_count!"
+            FROM channel_logs cl
+            JOIN smoking_logs sl ON cl.log_id = sl.id
+            JOIN smoking_types st ON sl.smoking_type_id = st.id
+            WHERE cl.channel_id = $1
+            AND sl.smoked_at >= $2
+            AND sl.smoked_at < $3
+            GROUP BY st.id, st.type_name, st.description
+            ORDER BY st.id
+            "#,
+            channel_id,
+            start,
+            end
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(totals)
+    }
+
+    /// Creates a shortcut link that logs one unit of a smoking type when opened.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the link owner.
+    /// * `smoking_type_id` - The ID of the smoking type logged by the link.
+    /// * `token_hash` - The SHA-256 hex digest of the link token.
+    ///
+    /// # Returns
+    /// A Result containing the created `ShortcutLink` or an `Error`.
+    pub async fn create_shortcut_link(
+        &self,
+        discord_id: &str,
+        smoking_type_id: i32,
+        token_hash: &str,
+    ) -> Result<ShortcutLink, Error> {
+        let _timer = QueryTimer::start("create_shortcut_link");
+
+        let link = sqlx::query_as!(
+            ShortcutLink,
+            r#"
+            INSERT INTO shortcut_links (discord_id, smoking_type_id, token_hash)
+            VALUES ($1, $2, $3)
+            RETURNING
+                id,
+                discord_id,
+                smoking_type_id,
+                last_used_at,
+                created_at
+            "#,
+            discord_id,
+            smoking_type_id,
+            token_hash
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(link)
+    }
+
+    /// Looks up a shortcut link by the hash of its token and marks it as used.
+    ///
+    /// # Arguments
+    /// * `token_hash` - The SHA-256 hex digest of the presented link token.
+    ///
+    /// # Returns
+    /// A Result containing the matching `ShortcutLink`, `None` if the token is unknown, or an `Error`.
+    pub async fn use_shortcut_link(&self, token_hash: &str) -> Result<Option<ShortcutLink>, Error> {
+        let _timer = QueryTimer::start("use_shortcut_link");
+
+        let link = sqlx::query_as!(
+            ShortcutLink,
+            r#"
+            UPDATE shortcut_links
+            SET last_used_at = CURRENT_TIMESTAMP
+            WHERE token_hash = $1
+            RETURNING
+                id,
+                discord_id,
+                smoking_type_id,
+                last_used_at,
+                created_at
+            "#,
+            token_hash
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(link)
+    }
+
+    /// Sets the token of a user's feed, replacing any previous one.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the feed owner.
+    /// * `token_hash` - The SHA-256 hex digest of the feed token.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_feed_token(&self, discord_id: &str, token_hash: &str) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_feed_token");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO feed_tokens (discord_id, token_hash)
+            VALUES ($1, $2)
+            ON CONFLICT (discord_id)
+            DO UPDATE SET token_hash = EXCLUDED.token_hash, created_at = CURRENT_TIMESTAMP
+            "#,
+            discord_id,
+            token_hash
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes the token of a user's feed.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the feed owner.
+    ///
+    /// # Returns
+    /// A Result containing whether the user had a feed, or an `Error`.
+    pub async fn remove_feed_token(&self, discord_id: &str) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remove_feed_token");
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM feed_tokens
+            WHERE discord_id = $1
+            "#,
+            discord_id
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Looks up the owner of a feed by the hash of its token.
+    ///
+    /// # Arguments
+    /// * `token_hash` - The SHA-256 hex digest of the presented feed token.
+    ///
+    /// # Returns
+    /// A Result containing the Discord ID of the owner, `None` if the token is unknown, or an `Error`.
+    pub async fn find_feed_owner(&self, token_hash: &str) -> Result<Option<String>, Error> {
+        let _timer = QueryTimer::start("find_feed_owner");
+
+        let discord_id = sqlx::query_scalar!(
+            r#"
+            SELECT discord_id
+            FROM feed_tokens
+            WHERE token_hash = $1
+            "#,
+            token_hash
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(discord_id)
+    }
+
+    /// Records that a user reached a smoke-free milestone.
+    ///
+    /// Reaching the same milestone again on the same day is ignored.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `smoke_free_days` - The number of smoke-free days of the milestone.
+    /// * `reached_on` - The local date the milestone was reached on.
+    /// * `reached_at` - When the milestone was reached.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn record_milestone(
+        &self,
+        discord_id: &str,
+        smoke_free_days: i32,
+        reached_on: NaiveDate,
+        reached_at: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("record_milestone");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO reached_milestones (discord_id, smoke_free_days, reached_on, reached_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT DO NOTHING
+            "#,
+            discord_id,
+            smoke_free_days,
+            reached_on,
+            reached_at
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the latest milestones a user reached.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `limit` - The maximum number of milestones to return.
+    ///
+    /// # Returns
+    /// A Result containing the milestones, newest first, or an `Error`.
+    pub async fn get_reached_milestones(
+        &self,
+        discord_id: &str,
+        limit: i64,
+    ) -> Result<Vec<ReachedMilestone>, Error> {
+        let _timer = QueryTimer::start("get_reached_milestones");
+
+        let milestones = sqlx::query_as!(
+            ReachedMilestone,
+            r#"
+            SELECT smoke_free_days, reached_on, reached_at
+            FROM reached_milestones
+            WHERE discord_id = $1
+            ORDER BY reached_at DESC, smoke_free_days DESC
+            LIMIT $2
+            "#,
+            discord_id,
+            limit
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(milestones)
+    }
+
+    /// Subscribes a user to the weekly email digest, replacing any previous address.
+    ///
+    /// The subscription stays unverified until `verify_email` is called with
+    /// the verification token.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `email` - The email address.
+    /// * `verification_token_hash` - The SHA-256 hex digest of the verification token.
+    /// * `unsubscribe_token` - The token of the unsubscribe links.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_email_subscription(
+        &self,
+        discord_id: &str,
+        email: &str,
+        verification_token_hash: &str,
+        unsubscribe_token: &str,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_email_subscription");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO email_subscriptions
+                (discord_id, email, verification_token_hash, unsubscribe_token)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (discord_id)
+            DO UPDATE SET
+                email = EXCLUDED.email,
+                verification_token_hash = EXCLUDED.verification_token_hash,
+                verified_at = NULL,
+                unsubscribe_token = EXCLUDED.unsubscribe_token,
+                last_sent_week = NULL,
+                created_at = CURRENT_TIMESTAMP
+            "#,
+            discord_id,
+            email,
+            verification_token_hash,
+            unsubscribe_token
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Verifies the email subscription a verification token was sent for.
+    ///
+    /// # Arguments
+    /// * `verification_token_hash` - The SHA-256 hex digest of the presented token.
+    /// * `verified_at` - When the address was verified.
+    ///
+    /// # Returns
+    /// A Result containing the verified address, `None` if the token is unknown or already used, or an `Error`.
+    pub async fn verify_email(
+        &self,
+        verification_token_hash: &str,
+        verified_at: DateTime<Utc>,
+    ) -> Result<Option<String>, Error> {
+        let _timer = QueryTimer::start("verify_email");
+
+        let email = sqlx::query_scalar!(
+            r#"
+            UPDATE email_subscriptions
+            SET verified_at = $2, verification_token_hash = NULL
+            WHERE verification_token_hash = $1
+            RETURNING email
+            "#,
+            verification_token_hash,
+            verified_at
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(email)
+    }
+
+    /// Removes the email subscription an unsubscribe link belongs to.
+    ///
+    /// # Arguments
+    /// * `unsubscribe_token` - The token of the opened unsubscribe link.
+    ///
+    /// # Returns
+    /// A Result containing the unsubscribed address, `None` if the token is unknown, or an `Error`.
+    pub async fn unsubscribe_email(
+        &self,
+        unsubscribe_token: &str,
+    ) -> Result<Option<String>, Error> {
+        let _timer = QueryTimer::start("unsubscribe_email");
+
+        let email = sqlx::query_scalar!(
+            r#"
+            DELETE FROM email_subscriptions
+            WHERE unsubscribe_token = $1
+            RETURNING email
+            "#,
+            unsubscribe_token
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(email)
+    }
+
+    /// Removes the email subscription of a user.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing whether the user was subscribed, or an `Error`.
+    pub async fn remove_email_subscription(&self, discord_id: &str) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remove_email_subscription");
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM email_subscriptions
+            WHERE discord_id = $1
+            "#,
+            discord_id
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves the verified email subscriptions that have not been sent a week's digest yet.
+    ///
+    /// # Arguments
+    /// * `week_start` - The Monday of the week the digest covers.
+    ///
+    /// # Returns
+    /// A Result containing the subscriptions, or an `Error`.
+    pub async fn get_due_email_digests(
+        &self,
+        week_start: NaiveDate,
+    ) -> Result<Vec<EmailSubscription>, Error> {
+        let _timer = QueryTimer::start("get_due_email_digests");
+
+        let subscriptions = sqlx::query_as!(
+            EmailSubscription,
+            r#"
+            SELECT discord_id, email, unsubscribe_token, verified_at, last_sent_week
+            FROM email_subscriptions
+            WHERE verified_at IS NOT NULL
+              AND (last_sent_week IS NULL OR last_sent_week < $1)
+            ORDER BY discord_id
+            "#,
+            week_start
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(subscriptions)
+    }
+
+    /// Records that a user was sent the digest of a week.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `week_start` - The Monday of the week the digest covered.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn mark_email_digest_sent(
+        &self,
+        discord_id: &str,
+        week_start: NaiveDate,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("mark_email_digest_sent");
+
+        sqlx::query!(
+            r#"
+            UPDATE email_subscriptions
+            SET last_sent_week = $2
+            WHERE discord_id = $1
+            "#,
+            discord_id,
+            week_start
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Sets where a user receives push notifications, replacing any previous target.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `service` - The push service, `ntfy` or `pushover`.
+    /// * `target` - The ntfy topic or Pushover user key.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_push_subscription(
+        &self,
+        discord_id: &str,
+        service: &str,
+        target: &str,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_push_subscription");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO push_subscriptions (discord_id, service, target)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (discord_id)
+            DO UPDATE SET
+                service = EXCLUDED.service,
+                target = EXCLUDED.target,
+                created_at = CURRENT_TIMESTAMP
+            "#,
+            discord_id,
+            service,
+            target
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves where a user receives push notifications.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing the subscription, `None` if the user has none, or an `Error`.
+    pub async fn get_push_subscription(
+        &self,
+        discord_id: &str,
+    ) -> Result<Option<PushSubscription>, Error> {
+        let _timer = QueryTimer::start("get_push_subscription");
+
+        let subscription = sqlx::query_as!(
+            PushSubscription,
+            r#"
+            SELECT service, target
+            FROM push_subscriptions
+            WHERE discord_id = $1
+            "#,
+            discord_id
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(subscription)
+    }
+
+    /// Removes the push notification target of a user.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing whether the user had a target, or an `Error`.
+    pub async fn remove_push_subscription(&self, discord_id: &str) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remove_push_subscription");
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM push_subscriptions
+            WHERE discord_id = $1
+            "#,
+            discord_id
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves the verified address a user's weekly digest is sent to.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing the address, `None` if the user has no verified address, or an `Error`.
+    pub async fn get_verified_email(&self, discord_id: &str) -> Result<Option<String>, Error> {
+        let _timer = QueryTimer::start("get_verified_email");
+
+        let email = sqlx::query_scalar!(
+            r#"
+            SELECT email
+            FROM email_subscriptions
+            WHERE discord_id = $1 AND verified_at IS NOT NULL
+            "#,
+            discord_id
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(email)
+    }
+
+    /// Sets or clears the channel a user prefers notifications on.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `channel` - The channel (`dm`, `email`, `ntfy` or `pushover`), or `None` for DMs.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_notification_channel(
+        &self,
+        discord_id: &str,
+        channel: Option<&str>,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_notification_channel");
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET notification_channel = $2
+            WHERE discord_id = $1
+            "#,
+            discord_id,
+            channel
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the channel a user prefers notifications on.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing the channel, `None` if the user has not chosen one, or an `Error`.
+    pub async fn get_notification_channel(&self, discord_id: &str) -> Result<Option<String>, Error> {
+        let _timer = QueryTimer::start("get_notification_channel");
+
+        let channel = sqlx::query_scalar!(
+            r#"
+            SELECT notification_channel
+            FROM users
+            WHERE discord_id = $1
+            "#,
+            discord_id
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(channel.flatten())
+    }
+
+    /// Sets or clears the daily goal of a user.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `daily_goal` - The maximum number of cigarettes per day, or `None` to clear the goal.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_daily_goal(
+        &self,
+        discord_id: &str,
+        daily_goal: Option<i32>,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_daily_goal");
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET daily_goal = $2
+            WHERE discord_id = $1
+            "#,
+            discord_id,
+            daily_goal
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the daily goal of a user.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing the daily goal if one is set, or an `Error`.
+    pub async fn get_daily_goal(&self, discord_id: &str) -> Result<Option<i32>, Error> {
+        let _timer = QueryTimer::start("get_daily_goal");
+
+        let goal = sqlx::query_scalar!(
+            r#"
+            SELECT daily_goal
+            FROM users
+            WHERE discord_id = $1
+            "#,
+            discord_id
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(goal.flatten())
+    }
+
+    /// Checks whether a user was created recently and has not logged anything yet.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `since` - How recently the user must have been created.
+    ///
+    /// # Returns
+    /// A Result containing whether the user is new, or an `Error`.
+    pub async fn is_new_user(&self, discord_id: &str, since: DateTime<Utc>) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("is_new_user");
+
+        let new = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM users u
+                WHERE u.discord_id = $1
+                  AND u.created_at >= $2
+                  AND NOT EXISTS (
+                      SELECT 1 FROM smoking_logs l WHERE l.discord_id = u.discord_id
+                  )
+            ) as "new!"
+            "#,
+            discord_id,
+            since
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(new)
+    }
+
+    /// Records that a user accepted a version of the data policy.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `version` - The accepted policy version.
+    /// * `consented_at` - When the policy was accepted.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn record_consent(
+        &self,
+        discord_id: &str,
+        version: i32,
+        consented_at: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("record_consent");
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET consent_version = $2, consented_at = $3
+            WHERE discord_id = $1
+            "#,
+            discord_id,
+            version,
+            consented_at
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the latest data policy version a user accepted.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing the accepted version, `None` if the user never accepted one, or an `Error`.
+    pub async fn get_consent_version(&self, discord_id: &str) -> Result<Option<i32>, Error> {
+        let _timer = QueryTimer::start("get_consent_version");
+
+        let version = sqlx::query_scalar!(
+            r#"
+            SELECT consent_version
+            FROM users
+            WHERE discord_id = $1
+            "#,
+            discord_id
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(version.flatten())
+    }
+
+    /// Sets whether a user logs past the daily caps set by guild admins.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `ignore` - Whether caps are ignored.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_ignore_type_caps(&self, discord_id: &str, ignore: bool) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_ignore_type_caps");
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET ignore_type_caps = $2
+            WHERE discord_id = $1
+            "#,
+            discord_id,
+            ignore
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Checks whether a user logs past the daily caps set by guild admins.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing whether caps are ignored (`false` for unknown users), or an `Error`.
+    pub async fn get_ignore_type_caps(&self, discord_id: &str) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("get_ignore_type_caps");
+
+        let ignore = sqlx::query_scalar!(
+            r#"
+            SELECT ignore_type_caps
+            FROM users
+            WHERE discord_id = $1
+            "#,
+            discord_id
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(ignore.unwrap_or(false))
+    }
+
+    /// Sets whether a user's raw logs are included in guild data exports.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `share` - Whether the logs are included.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_share_logs_in_exports(
+        &self,
+        discord_id: &str,
+        share: bool,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_share_logs_in_exports");
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET share_logs_in_guild_exports = $2
+            WHERE discord_id = $1
+            "#,
+            discord_id,
+            share
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Checks whether a user's raw logs are included in guild data exports.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing whether the logs are included (`false` for unknown users), or an `Error`.
+    pub async fn get_share_logs_in_exports(&self, discord_id: &str) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("get_share_logs_in_exports");
+
+        let share = sqlx::query_scalar!(
+            r#"
+            SELECT share_logs_in_guild_exports
+            FROM users
+            WHERE discord_id = $1
+            "#,
+            discord_id
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(share.unwrap_or(false))
+    }
+
+    /// Sets whether later presses of a day edit the day's first confirmation instead of sending another.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `quiet` - Whether confirmations are quiet.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_quiet_confirmations(
+        &self,
+        discord_id: &str,
+        quiet: bool,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_quiet_confirmations");
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET quiet_confirmations = $2
+            WHERE discord_id = $1
+            "#,
+            discord_id,
+            quiet
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Checks whether a user's confirmations are quiet.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing whether confirmations are quiet (`false` for unknown users), or an `Error`.
+    pub async fn get_quiet_confirmations(&self, discord_id: &str) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("get_quiet_confirmations");
+
+        let quiet = sqlx::query_scalar!(
+            r#"
+            SELECT quiet_confirmations
+            FROM users
+            WHERE discord_id = $1
+            "#,
+            discord_id
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(quiet.unwrap_or(false))
+    }
+
+    /// Stores the message confirming a user's presses of a day, replacing the one of any earlier day.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `date` - The local date.
+    /// * `message_id` - The ID of the confirmation message.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_daily_confirmation(
+        &self,
+        discord_id: &str,
+        date: NaiveDate,
+        message_id: &str,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_daily_confirmation");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO daily_confirmations (discord_id, smoke_date, message_id)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (discord_id) DO UPDATE
+            SET smoke_date = EXCLUDED.smoke_date, message_id = EXCLUDED.message_id
+            "#,
+            discord_id,
+            date,
+            message_id
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the message confirming a user's presses of a day.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `date` - The local date.
+    ///
+    /// # Returns
+    /// A Result containing the ID of the message, `None` if none was sent that day, or an `Error`.
+    pub async fn get_daily_confirmation(
+        &self,
+        discord_id: &str,
+        date: NaiveDate,
+    ) -> Result<Option<String>, Error> {
+        let _timer = QueryTimer::start("get_daily_confirmation");
+
+        let message_id = sqlx::query_scalar!(
+            r#"
+            SELECT message_id
+            FROM daily_confirmations
+            WHERE discord_id = $1 AND smoke_date = $2
+            "#,
+            discord_id,
+            date
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(message_id)
+    }
+
+    /// Retrieves how many units of one smoking type a user logged on a local date.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `smoking_type_id` - The ID of the smoking type.
+    /// * `date` - The local date.
+    /// * `timezone` - The time zone the date is counted in.
+    ///
+    /// # Returns
+    /// A Result containing the total quantity or an `Error`.
+    pub async fn get_daily_type_total(
+        &self,
+        discord_id: &str,
+        smoking_type_id: i32,
+        date: NaiveDate,
+        timezone: Tz,
+    ) -> Result<i64, Error> {
+        let _timer = QueryTimer::start("get_daily_type_total");
+        let (start, end) = rollover::day_bounds(timezone, date);
+
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COALESCE(SUM(quantity), 0) as "total!"
+            FROM smoking_logs
+            WHERE discord_id = $1
+            AND smoking_type_id = $2
+            AND smoked_at >= $3
+            AND smoked_at < $4
+            "#,
+            discord_id,
+            smoking_type_id,
+            start,
+            end
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(total)
+    }
+
+    /// Pauses tracking for a user over a range of local dates.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `start_date` - The first paused date.
+    /// * `end_date` - The last paused date.
+    ///
+    /// # Returns
+    /// A Result containing the created `TrackingPause` or an `Error`.
+    pub async fn pause_tracking(
+        &self,
+        discord_id: &str,
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+    ) -> Result<TrackingPause, Error> {
+        let _timer = QueryTimer::start("pause_tracking");
+
+        let pause = sqlx::query_as!(
+            TrackingPause,
+            r#"
+            INSERT INTO tracking_pauses (discord_id, start_date, end_date)
+            VALUES ($1, $2, $3)
+            RETURNING id, discord_id, start_date, end_date
+            "#,
+            discord_id,
+            start_date,
+            end_date
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(pause)
+    }
+
+    /// Ends a user's pauses so tracking resumes on a given date.
+    ///
+    /// Pauses covering the date end the day before; pauses starting on or after it are removed.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `date` - The first date tracked again.
+    ///
+    /// # Returns
+    /// A Result containing whether any pause was ended, or an `Error`.
+    pub async fn resume_tracking(&self, discord_id: &str, date: NaiveDate) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("resume_tracking");
+
+        let mut tx = self.pool.begin().await?;
+        let ended = sqlx::query!(
+            r#"
+            UPDATE tracking_pauses
+            SET end_date = $2::date - 1
+            WHERE discord_id = $1 AND start_date < $2 AND end_date >= $2
+            "#,
+            discord_id,
+            date
+        )
+        .execute(&mut *tx)
+        .await?;
+        let removed = sqlx::query!(
+            r#"
+            DELETE FROM tracking_pauses
+            WHERE discord_id = $1 AND start_date >= $2
+            "#,
+            discord_id,
+            date
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(ended.rows_affected() + removed.rows_affected() > 0)
+    }
+
+    /// Retrieves a user's pauses that end on or after a given date.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `since` - The first local date of interest.
+    ///
+    /// # Returns
+    /// A Result containing a vector of `TrackingPause` ordered by start date, or an `Error`.
+    pub async fn get_tracking_pauses(
+        &self,
+        discord_id: &str,
+        since: NaiveDate,
+    ) -> Result<Vec<TrackingPause>, Error> {
+        let _timer = QueryTimer::start("get_tracking_pauses");
+
+        let pauses = sqlx::query_as!(
+            TrackingPause,
+            r#"
+            SELECT id, discord_id, start_date, end_date
+            FROM tracking_pauses
+            WHERE discord_id = $1 AND end_date >= $2
+            ORDER BY start_date
+            "#,
+            discord_id,
+            since
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(pauses)
+    }
+
+    /// Checks whether tracking is paused for a user on a date.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `date` - The local date.
+    ///
+    /// # Returns
+    /// A Result containing whether the date is paused, or an `Error`.
+    pub async fn is_tracking_paused(
+        &self,
+        discord_id: &str,
+        date: NaiveDate,
+    ) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("is_tracking_paused");
+
+        let paused = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM tracking_pauses
+                WHERE discord_id = $1 AND start_date <= $2 AND end_date >= $2
+            ) as "paused!"
+            "#,
+            discord_id,
+            date
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(paused)
+    }
+
+    /// Tags a user's day with a custom event.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `tag` - The event tag.
+    /// * `date` - The local date of the event.
+    ///
+    /// # Returns
+    /// A Result containing whether the tag was added (`false` if the day already had it), or an `Error`.
+    pub async fn add_user_event(
+        &self,
+        discord_id: &str,
+        tag: &str,
+        date: NaiveDate,
+    ) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("add_user_event");
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_events (discord_id, tag, event_date)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (discord_id, tag, event_date) DO NOTHING
+            "#,
+            discord_id,
+            tag,
+            date
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Removes a custom event from a user's day.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `tag` - The event tag.
+    /// * `date` - The local date of the event.
+    ///
+    /// # Returns
+    /// A Result containing whether the tag was removed, or an `Error`.
+    pub async fn remove_user_event(
+        &self,
+        discord_id: &str,
+        tag: &str,
+        date: NaiveDate,
+    ) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remove_user_event");
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM user_events
+            WHERE discord_id = $1 AND tag = $2 AND event_date = $3
+            "#,
+            discord_id,
+            tag,
+            date
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Compares a user's daily totals on days with and without each of their event tags.
+    ///
+    /// Days without logs count as zero and paused days are left out.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `from` - The first local date to include.
+    /// * `to` - The last local date to include.
+    /// * `timezone` - The time zone dates are counted in.
+    ///
+    /// # Returns
+    /// A Result containing a vector of `EventCorrelation` ordered by tag, or an `Error`.
+    pub async fn get_event_correlations(
+        &self,
+        discord_id: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+        timezone: Tz,
+    ) -> Result<Vec<EventCorrelation>, Error> {
+        let _timer = QueryTimer::start("get_event_correlations");
+
+        let correlations = sqlx::query_as!(
+            EventCorrelation,
+            r#"
+            WITH days AS (
+                SELECT day::date AS day
+                FROM generate_series($2::date, $3::date, INTERVAL '1 day') AS day
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM tracking_pauses tp
+                    WHERE tp.discord_id = $1
+                    AND tp.start_date <= day::date AND tp.end_date >= day::date
+                )
+            ),
+            totals AS (
+                SELECT DATE(smoked_at AT TIME ZONE $4) AS day, SUM(quantity) AS total
+                FROM smoking_logs
+                WHERE discord_id = $1
+                AND smoked_at >= $5
+                AND smoked_at < $6
+                GROUP BY 1
+            ),
+            tags AS (
+                SELECT DISTINCT tag
+                FROM user_events
+                WHERE discord_id = $1 AND event_date BETWEEN $2 AND $3
+            )
+            SELECT
+                tags.tag as "tag!",
+                COUNT(ue.id) as "days_with!",
+                COALESCE(AVG(COALESCE(totals.total, 0)) FILTER (WHERE ue.id IS NOT NULL), 0)::float8
+                    as "average_with!",
+                COUNT(*) FILTER (WHERE ue.id IS NULL) as "days_without!",
+                COALESCE(AVG(COALESCE(totals.total, 0)) FILTER (WHERE ue.id IS NULL), 0)::float8
+                    as "average_without!"
+            FROM tags
+            CROSS JOIN days
+            LEFT JOIN user_events ue
+                ON ue.discord_id = $1 AND ue.tag = tags.tag AND ue.event_date = days.day
+            LEFT JOIN totals ON totals.day = days.day
+            GROUP BY tags.tag
+            ORDER BY tags.tag
+            "#,
+            discord_id,
+            from,
+            to,
+            timezone.name(),
+            rollover::start_of_day(timezone, from),
+            rollover::start_of_day(timezone, to + chrono::Duration::days(1))
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(correlations)
+    }
+
+    /// Retrieves the time of a user's most recent smoking event.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing the time of the last log, `None` if the user has never logged, or an `Error`.
+    pub async fn get_last_smoked_at(
+        &self,
+        discord_id: &str,
+    ) -> Result<Option<DateTime<Utc>>, Error> {
+        let _timer = QueryTimer::start("get_last_smoked_at");
+
+        let smoked_at = sqlx::query_scalar!(
+            r#"
+            SELECT MAX(smoked_at)
+            FROM smoking_logs
+            WHERE discord_id = $1
+            "#,
+            discord_id
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(smoked_at)
+    }
+
+    /// Retrieves a user's total quantity per day since a given date.
+    ///
+    /// Days without any logs are omitted.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `since` - The first local date to include.
+    /// * `timezone` - The time zone dates are counted in.
+    ///
+    /// # Returns
+    /// A Result containing a vector of `DailyTotal` ordered by date, or an `Error`.
+    pub async fn get_daily_totals(
+        &self,
+        discord_id: &str,
+        since: NaiveDate,
+        timezone: Tz,
+    ) -> Result<Vec<DailyTotal>, Error> {
+        let _timer = QueryTimer::start("get_daily_totals");
+
+        let totals = sqlx::query_as!(
+            DailyTotal,
+            r#"
+            SELECT
+                DATE(smoked_at AT TIME ZONE $3) as "smoke_date!",
+                SUM(quantity) as "total_quantity!"
+            FROM smoking_logs
+            WHERE discord_id = $1
+            AND smoked_at >= $2
+            GROUP BY 1
+            ORDER BY 1
+            "#,
+            discord_id,
+            rollover::start_of_day(timezone, since),
+            timezone.name()
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(totals)
+    }
+
+    /// Totals a user's logs per smoking type over a period.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `start` - The start of the period (inclusive).
+    /// * `end` - The end of the period (exclusive).
+    ///
+    /// # Returns
+    /// A Result containing the totals of the logged types ordered by type, or an `Error`.
+    pub async fn get_user_type_totals(
+        &self,
+        discord_id: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<UserTypeTotal>, Error> {
+        let _timer = QueryTimer::start("get_user_type_totals");
+
+        select_user_type_totals(&*self.pool, discord_id, start, end).await
+    }
+
+    /// Totals a user's logs per smoking type over the week starting on a date.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `week_start` - The local date the week starts on.
+    /// * `timezone` - The time zone the week is counted in.
+    ///
+    /// # Returns
+    /// A Result containing the totals of the logged types ordered by type, or an `Error`.
+    pub async fn get_weekly_summary(
+        &self,
+        discord_id: &str,
+        week_start: NaiveDate,
+        timezone: Tz,
+    ) -> Result<Vec<UserTypeTotal>, Error> {
+        let _timer = QueryTimer::start("get_weekly_summary");
+
+        select_user_type_totals(
+            &*self.pool,
+            discord_id,
+            rollover::start_of_day(timezone, week_start),
+            rollover::start_of_day(timezone, week_start + chrono::Duration::days(7)),
+        )
+        .await
+    }
+
+    /// Totals a user's logs per smoking type over a calendar month.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `month` - The first day of the month.
+    /// * `timezone` - The time zone the month is counted in.
+    ///
+    /// # Returns
+    /// A Result containing the totals of the logged types ordered by type, or an `Error`.
+    pub async fn get_monthly_summary(
+        &self,
+        discord_id: &str,
+        month: NaiveDate,
+        timezone: Tz,
+    ) -> Result<Vec<UserTypeTotal>, Error> {
+        let _timer = QueryTimer::start("get_monthly_summary");
+        let next_month = rollover::shift_months(month, 1);
+
+        select_user_type_totals(
+            &*self.pool,
+            discord_id,
+            rollover::start_of_day(timezone, month),
+            rollover::start_of_day(timezone, next_month),
+        )
+        .await
+    }
+
+    /// Totals a user's logs per period and smoking type over their whole history.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `granularity` - The length of the periods.
+    /// * `timezone` - The time zone periods are counted in.
+    ///
+    /// # Returns
+    /// A Result containing the totals ordered by period and type, or an `Error`.
+    pub async fn get_period_type_totals(
+        &self,
+        discord_id: &str,
+        granularity: Granularity,
+        timezone: Tz,
+    ) -> Result<Vec<PeriodTypeTotal>, Error> {
+        let _timer = QueryTimer::start("get_period_type_totals");
+
+        let totals = sqlx::query_as!(
+            PeriodTypeTotal,
+            r#"
+            SELECT
+                DATE_TRUNC($2, sl.smoked_at AT TIME ZONE $3)::date as "period!",
+                st.type_name as "type_name!",
+                SUM(sl.quantity) as "total_quantity!"
+            FROM smoking_logs sl
+            JOIN smoking_types st ON sl.smoking_type_id = st.id
+            WHERE sl.discord_id = $1
+            GROUP BY 1, st.id, st.type_name
+            ORDER BY 1, st.id
+            "#,
+            discord_id,
+            granularity.name(),
+            timezone.name()
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(totals)
+    }
+
+    /// Retrieves the date of a user's first smoking event.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `timezone` - The time zone the date is counted in.
+    ///
+    /// # Returns
+    /// A Result containing the local date of the first log, `None` if the user has never logged, or an `Error`.
+    pub async fn get_first_smoke_date(
+        &self,
+        discord_id: &str,
+        timezone: Tz,
+    ) -> Result<Option<NaiveDate>, Error> {
+        let _timer = QueryTimer::start("get_first_smoke_date");
+
+        let first = sqlx::query_scalar!(
+            r#"
+            SELECT MIN(smoked_at)
+            FROM smoking_logs
+            WHERE discord_id = $1
+            "#,
+            discord_id
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(first.map(|smoked_at| smoked_at.with_timezone(&timezone).date_naive()))
+    }
+
+    /// Stores or replaces the linked-roles OAuth tokens of a user.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `access_token` - The OAuth access token.
+    /// * `refresh_token` - The OAuth refresh token.
+    /// * `expires_at` - When the access token expires.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn upsert_role_connection(
+        &self,
+        discord_id: &str,
+        access_token: &str,
+        refresh_token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("upsert_role_connection");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO role_connections (discord_id, access_token, refresh_token, expires_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (discord_id) DO UPDATE
+            SET access_token = EXCLUDED.access_token,
+                refresh_token = EXCLUDED.refresh_token,
+                expires_at = EXCLUDED.expires_at
+            "#,
+            discord_id,
+            access_token,
+            refresh_token,
+            expires_at
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves all linked-roles connections.
+    ///
+    /// # Returns
+    /// A Result containing a vector of `RoleConnection` or an `Error`.
+    pub async fn get_role_connections(&self) -> Result<Vec<RoleConnection>, Error> {
+        let _timer = QueryTimer::start("get_role_connections");
+
+        let connections = sqlx::query_as!(
+            RoleConnection,
+            r#"
+            SELECT
+                discord_id,
+                access_token,
+                refresh_token,
+                expires_at,
+                last_pushed_at
+            FROM role_connections
+            ORDER BY discord_id
+            "#
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(connections)
+    }
+
+    /// Records that linked-roles metadata was pushed for a user.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn mark_role_connection_pushed(&self, discord_id: &str) -> Result<(), Error> {
+        let _timer = QueryTimer::start("mark_role_connection_pushed");
+
+        sqlx::query!(
+            r#"
+            UPDATE role_connections
+            SET last_pushed_at = CURRENT_TIMESTAMP
+            WHERE discord_id = $1
+            "#,
+            discord_id
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the IDs of all guilds with milestone role mappings.
+    ///
+    /// Spans all guilds; only for background tasks. Guilds the bot was removed from are left out.
+    ///
+    /// # Returns
+    /// A Result containing a vector of guild IDs or an `Error`.
+    pub async fn get_milestone_guilds(&self) -> Result<Vec<String>, Error> {
+        let _timer = QueryTimer::start("get_milestone_guilds");
+
+        let guilds = sqlx::query_scalar!(
+            r#"
+            SELECT DISTINCT guild_id
+            FROM milestone_roles
+            WHERE NOT EXISTS (SELECT 1 FROM guild_removals gr WHERE gr.guild_id = milestone_roles.guild_id)
+            ORDER BY guild_id
+            "#
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(guilds)
+    }
+
+    /// Retrieves the IDs of all guilds with custom achievements.
+    ///
+    /// Spans all guilds; only for background tasks. Guilds the bot was removed from are left out.
+    ///
+    /// # Returns
+    /// A Result containing a vector of guild IDs or an `Error`.
+    pub async fn get_achievement_guilds(&self) -> Result<Vec<String>, Error> {
+        let _timer = QueryTimer::start("get_achievement_guilds");
+
+        let guilds = sqlx::query_scalar!(
+            r#"
+            SELECT DISTINCT guild_id
+            FROM guild_achievements
+            WHERE NOT EXISTS (SELECT 1 FROM guild_removals gr WHERE gr.guild_id = guild_achievements.guild_id)
+            ORDER BY guild_id
+            "#
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(guilds)
+    }
+
+    /// Retrieves the leaderboard settings of all guilds running seasons.
+    ///
+    /// Spans all guilds; only for background tasks. Guilds the bot was removed from are left out.
+    ///
+    /// # Returns
+    /// A Result containing the settings ordered by guild, or an `Error`.
+    pub async fn get_leaderboard_settings(&self) -> Result<Vec<LeaderboardSettings>, Error> {
+        let _timer = QueryTimer::start("get_leaderboard_settings");
+
+        let settings = sqlx::query_as!(
+            LeaderboardSettings,
+            r#"
+            SELECT guild_id, channel_id, season_length
+            FROM leaderboard_settings
+            WHERE NOT EXISTS (SELECT 1 FROM guild_removals gr WHERE gr.guild_id = leaderboard_settings.guild_id)
+            ORDER BY guild_id
+            "#
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(settings)
+    }
+
+    /// Retrieves the channel counters of all guilds.
+    ///
+    /// Spans all guilds; only for background tasks. Guilds the bot was removed from are left out.
+    ///
+    /// # Returns
+    /// A Result containing the counters ordered by guild, or an `Error`.
+    pub async fn get_channel_counters(&self) -> Result<Vec<ChannelCounter>, Error> {
+        let _timer = QueryTimer::start("get_channel_counters");
+
+        let counters = sqlx::query_as!(
+            ChannelCounter,
+            r#"
+            SELECT guild_id, channel_id, metric, target, last_text, updated_at
+            FROM channel_counters
+            WHERE NOT EXISTS (SELECT 1 FROM guild_removals gr WHERE gr.guild_id = channel_counters.guild_id)
+            ORDER BY guild_id
+            "#
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(counters)
+    }
+
+    /// Retrieves panel confirmations older than their guild's cleanup age.
+    ///
+    /// Spans all guilds; only for background tasks. Guilds the bot was removed from are left out.
+    ///
+    /// # Arguments
+    /// * `now` - The current time.
+    /// * `limit` - The most confirmations returned.
+    ///
+    /// # Returns
+    /// A Result containing the oldest expired confirmations, or an `Error`.
+    pub async fn get_expired_confirmations(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<ConfirmationMessage>, Error> {
+        let _timer = QueryTimer::start("get_expired_confirmations");
+
+        let messages = sqlx::query_as!(
+            ConfirmationMessage,
+            r#"
+            SELECT cm.message_id, cm.guild_id, cm.channel_id
+            FROM confirmation_messages cm
+            JOIN confirmation_cleanup_settings ccs ON ccs.guild_id = cm.guild_id
+            WHERE cm.sent_at + make_interval(hours => ccs.max_age_hours) <= $1
+                AND NOT EXISTS (SELECT 1 FROM guild_removals gr WHERE gr.guild_id = cm.guild_id)
+            ORDER BY cm.sent_at
+            LIMIT $2
+            "#,
+            now,
+            limit
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(messages)
+    }
+
+    /// Forgets panel confirmations that were deleted.
+    ///
+    /// Spans all guilds; only for background tasks.
+    ///
+    /// # Arguments
+    /// * `message_ids` - The IDs of the deleted messages.
+    ///
+    /// # Returns
+    /// A Result containing the number of confirmations forgotten, or an `Error`.
+    pub async fn forget_confirmations(&self, message_ids: &[String]) -> Result<u64, Error> {
+        let _timer = QueryTimer::start("forget_confirmations");
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM confirmation_messages
+            WHERE message_id = ANY($1)
+            "#,
+            message_ids
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Retrieves the guilds whose data is due for deletion after the bot was removed.
+    ///
+    /// Spans all guilds; only for background tasks.
+    ///
+    /// # Arguments
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    /// A Result containing the guild IDs ordered by due time, or an `Error`.
+    pub async fn get_due_guild_deletions(&self, now: DateTime<Utc>) -> Result<Vec<String>, Error> {
+        let _timer = QueryTimer::start("get_due_guild_deletions");
+
+        let guilds = sqlx::query_scalar!(
+            r#"
+            SELECT guild_id
+            FROM guild_removals
+            WHERE delete_after <= $1
+            ORDER BY delete_after, guild_id
+            "#,
+            now
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(guilds)
+    }
+
+    /// Retrieves the forum channels weekly threads are opened in.
+    ///
+    /// Spans all guilds; only for background tasks. Guilds the bot was removed from are left out.
+    ///
+    /// # Returns
+    /// A Result containing the forum channels ordered by guild, or an `Error`.
+    pub async fn get_forum_channels(&self) -> Result<Vec<ForumChannel>, Error> {
+        let _timer = QueryTimer::start("get_forum_channels");
+
+        let channels = sqlx::query_as!(
+            ForumChannel,
+            r#"
+            SELECT guild_id, channel_id
+            FROM forum_channels
+            WHERE NOT EXISTS (SELECT 1 FROM guild_removals gr WHERE gr.guild_id = forum_channels.guild_id)
+            ORDER BY guild_id
+            "#
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(channels)
+    }
+
+    /// Retrieves the guilds taking part in the weekly challenge.
+    ///
+    /// Spans all guilds; only for background tasks. Guilds the bot was removed from are left out.
+    ///
+    /// # Returns
+    /// A Result containing the challenges ordered by guild, or an `Error`.
+    pub async fn get_guild_challenges(&self) -> Result<Vec<GuildChallenge>, Error> {
+        let _timer = QueryTimer::start("get_guild_challenges");
+
+        let challenges = sqlx::query_as!(
+            GuildChallenge,
+            r#"
+            SELECT guild_id, channel_id, target_percent
+            FROM guild_challenges
+            WHERE NOT EXISTS (SELECT 1 FROM guild_removals gr WHERE gr.guild_id = guild_challenges.guild_id)
+            ORDER BY guild_id
+            "#
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(challenges)
+    }
+
+    /// Creates a wager waiting for the opponent to accept it.
+    ///
+    /// # Arguments
+    /// * `channel_id` - The ID of the channel the result is announced in.
+    /// * `challenger_id` - The Discord ID of the user proposing the wager.
+    /// * `opponent_id` - The Discord ID of the user challenged.
+    /// * `metric` - The name of the metric deciding the winner.
+    /// * `period` - The name of the period the wager runs for.
+    ///
+    /// # Returns
+    /// A Result containing the created `Wager` or an `Error`.
+    pub async fn create_wager(
+        &self,
+        channel_id: &str,
+        challenger_id: &str,
+        opponent_id: &str,
+        metric: &str,
+        period: &str,
+    ) -> Result<Wager, Error> {
+        let _timer = QueryTimer::start("create_wager");
+
+        let wager = sqlx::query_as!(
+            Wager,
+            r#"
+            INSERT INTO wagers (channel_id, challenger_id, opponent_id, metric, period)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, channel_id, challenger_id, opponent_id, metric, period, status,
+                starts_at, ends_at, winner_id, created_at
+            "#,
+            channel_id,
+            challenger_id,
+            opponent_id,
+            metric,
+            period
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(wager)
+    }
+
+    /// Retrieves a wager.
+    ///
+    /// # Arguments
+    /// * `id` - The ID of the wager.
+    ///
+    /// # Returns
+    /// A Result containing the wager, `None` if it does not exist, or an `Error`.
+    pub async fn get_wager(&self, id: i32) -> Result<Option<Wager>, Error> {
+        let _timer = QueryTimer::start("get_wager");
+
+        let wager = sqlx::query_as!(
+            Wager,
+            r#"
+            SELECT id, channel_id, challenger_id, opponent_id, metric, period, status,
+                starts_at, ends_at, winner_id, created_at
+            FROM wagers
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(wager)
+    }
+
+    /// Starts a pending wager proposed after `proposed_after`.
+    ///
+    /// # Arguments
+    /// * `id` - The ID of the wager.
+    /// * `proposed_after` - Wagers proposed before this have expired.
+    /// * `starts_at` - The start of the wager's period.
+    /// * `ends_at` - The end of the wager's period.
+    ///
+    /// # Returns
+    /// A Result containing the started wager, `None` if it was no longer open, or an `Error`.
+    pub async fn accept_wager(
+        &self,
+        id: i32,
+        proposed_after: DateTime<Utc>,
+        starts_at: DateTime<Utc>,
+        ends_at: DateTime<Utc>,
+    ) -> Result<Option<Wager>, Error> {
+        let _timer = QueryTimer::start("accept_wager");
+
+        let wager = sqlx::query_as!(
+            Wager,
+            r#"
+            UPDATE wagers
+            SET status = 'accepted', starts_at = $3, ends_at = $4
+            WHERE id = $1 AND status = 'pending' AND created_at > $2
+            RETURNING id, channel_id, challenger_id, opponent_id, metric, period, status,
+                starts_at, ends_at, winner_id, created_at
+            "#,
+            id,
+            proposed_after,
+            starts_at,
+            ends_at
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(wager)
+    }
+
+    /// Declines a pending wager.
+    ///
+    /// # Arguments
+    /// * `id` - The ID of the wager.
+    ///
+    /// # Returns
+    /// A Result containing whether the wager was still pending, or an `Error`.
+    pub async fn decline_wager(&self, id: i32) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("decline_wager");
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE wagers
+            SET status = 'declined'
+            WHERE id = $1 AND status = 'pending'
+            "#,
+            id
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Expires the wagers nobody accepted in time.
+    ///
+    /// # Arguments
+    /// * `proposed_before` - Pending wagers proposed before this expire.
+    ///
+    /// # Returns
+    /// A Result containing the number of expired wagers or an `Error`.
+    pub async fn expire_pending_wagers(
+        &self,
+        proposed_before: DateTime<Utc>,
+    ) -> Result<u64, Error> {
+        let _timer = QueryTimer::start("expire_pending_wagers");
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE wagers
+            SET status = 'expired'
+            WHERE status = 'pending' AND created_at <= $1
+            "#,
+            proposed_before
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Retrieves the accepted wagers whose period is over.
+    ///
+    /// # Arguments
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    /// A Result containing the wagers ordered by end, or an `Error`.
+    pub async fn get_due_wagers(&self, now: DateTime<Utc>) -> Result<Vec<Wager>, Error> {
+        let _timer = QueryTimer::start("get_due_wagers");
+
+        let wagers = sqlx::query_as!(
+            Wager,
+            r#"
+            SELECT id, channel_id, challenger_id, opponent_id, metric, period, status,
+                starts_at, ends_at, winner_id, created_at
+            FROM wagers
+            WHERE status = 'accepted' AND ends_at <= $1
+            ORDER BY ends_at, id
+            "#,
+            now
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(wagers)
+    }
+
+    /// Records the result of a wager.
+    ///
+    /// # Arguments
+    /// * `id` - The ID of the wager.
+    /// * `challenger_total` - The challenger's total over the period.
+    /// * `opponent_total` - The opponent's total over the period.
+    /// * `winner_id` - The Discord ID of the winner, `None` for a draw.
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn settle_wager(
+        &self,
+        id: i32,
+        challenger_total: i64,
+        opponent_total: i64,
+        winner_id: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("settle_wager");
+
+        sqlx::query!(
+            r#"
+            UPDATE wagers
+            SET status = 'settled', challenger_total = $2, opponent_total = $3,
+                winner_id = $4, settled_at = $5
+            WHERE id = $1
+            "#,
+            id,
+            challenger_total,
+            opponent_total,
+            winner_id,
+            now
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Opts a user in or out of smoke-break prompts.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `enabled` - Whether the user wants to be prompted.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_smoke_break_prompt(
+        &self,
+        discord_id: &str,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_smoke_break_prompt");
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET smoke_break_prompt = $2
+            WHERE discord_id = $1
+            "#,
+            discord_id,
+            enabled
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Checks whether a user has opted in to smoke-break prompts.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing whether the user should be prompted or an `Error`.
+    pub async fn smoke_break_prompt_enabled(&self, discord_id: &str) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("smoke_break_prompt_enabled");
+
+        let enabled = sqlx::query_scalar!(
+            r#"
+            SELECT smoke_break_prompt
+            FROM users
+            WHERE discord_id = $1
+            "#,
+            discord_id
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(enabled.unwrap_or_default())
+    }
+
+    /// Changes the smoking type of a log and audits the change, in one transaction.
+    ///
+    /// # Arguments
+    /// * `actor_id` - The Discord ID of the owner making the change.
+    /// * `log_id` - The ID of the log.
+    /// * `smoking_type_id` - The ID of the new smoking type.
+    /// * `quantity` - The quantity converted to the new type's unit; kept if `None`.
+    ///
+    /// # Returns
+    /// A Result containing the updated `SmokingLog`, `None` if the log does not exist, or an `Error`.
+    pub async fn reassign_log_type(
+        &self,
+        actor_id: &str,
+        log_id: i32,
+        smoking_type_id: i32,
+        quantity: Option<i32>,
+    ) -> Result<Option<SmokingLog>, Error> {
+        let _timer = QueryTimer::start("reassign_log_type");
+
+        let mut tx = self.pool.begin().await?;
+        let Some(previous) = sqlx::query!(
+            r#"
+            SELECT smoking_type_id as "smoking_type_id!", quantity
+            FROM smoking_logs
+            WHERE id = $1
+            FOR UPDATE
+            "#,
+            log_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let log = sqlx::query_as!(
+            SmokingLog,
+            r#"
+            UPDATE smoking_logs
+            SET smoking_type_id = $2, quantity = COALESCE($3, quantity)
+            WHERE id = $1
+            RETURNING
+                id as "id!",
+                discord_id as "discord_id!",
+                smoking_type_id as "smoking_type_id!",
+                quantity as "quantity!",
+                smoked_at as "smoked_at!",
+                device_name,
+                guild_id,
+                idempotency_key,
+                created_at,
+                updated_at
+            "#,
+            log_id,
+            smoking_type_id,
+            quantity
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        let mut details = format!(
+            "log {}: type {} -> {}",
+            log_id, previous.smoking_type_id, smoking_type_id
+        );
+        if previous.quantity != log.quantity {
+            details.push_str(&format!(
+                ", quantity {} -> {}",
+                previous.quantity, log.quantity
+            ));
+        }
+        insert_admin_audit_entry(
+            &mut *tx,
+            actor_id,
+            "reassign_log_type",
+            &log.discord_id,
+            &details,
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(Some(log))
+    }
+
+    /// Shifts every log of a user by a number of hours and audits the change, in one transaction.
+    ///
+    /// # Arguments
+    /// * `actor_id` - The Discord ID of the owner making the change.
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `hours` - The number of hours to add (negative to move the logs earlier).
+    ///
+    /// # Returns
+    /// A Result containing the number of shifted logs or an `Error`.
+    pub async fn shift_user_logs(
+        &self,
+        actor_id: &str,
+        discord_id: &str,
+        hours: i32,
+    ) -> Result<u64, Error> {
+        let _timer = QueryTimer::start("shift_user_logs");
+
+        let mut tx = self.pool.begin().await?;
+        let shifted = sqlx::query!(
+            r#"
+            UPDATE smoking_logs
+            SET smoked_at = smoked_at + make_interval(hours => $2)
+            WHERE discord_id = $1
+            "#,
+            discord_id,
+            hours
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        insert_admin_audit_entry(
+            &mut *tx,
+            actor_id,
+            "shift_timestamps",
+            discord_id,
+            &format!("{} logs shifted by {} hours", shifted, hours),
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(shifted)
+    }
+
+    /// Merges one smoking type into another and audits the merge, in one transaction.
+    ///
+    /// Logs, shortcut links, daily caps and panel type lists are moved to the
+    /// target type, which takes over the source's details it lacks. The source
+    /// type is archived, and its name keeps finding the target type.
+    ///
+    /// # Arguments
+    /// * `actor_id` - The Discord ID of the owner making the change.
+    /// * `from_id` - The ID of the type merged away.
+    /// * `to_id` - The ID of the type kept.
+    /// * `factor` - The factor converting moved quantities to the kept type's unit; kept if `None`.
+    ///
+    /// # Returns
+    /// A Result containing the number of moved logs or an `Error`.
+    pub async fn merge_smoking_types(
+        &self,
+        actor_id: &str,
+        from_id: i32,
+        to_id: i32,
+        factor: Option<f64>,
+    ) -> Result<u64, Error> {
+        let _timer = QueryTimer::start("merge_smoking_types");
+
+        let mut tx = self.pool.begin().await?;
+        // Converted quantities are rounded, but a log never drops to zero.
+        let moved = sqlx::query!(
+            r#"
+            UPDATE smoking_logs
+            SET smoking_type_id = $2,
+                quantity = CASE
+                    WHEN $3::DOUBLE PRECISION IS NULL THEN quantity
+                    ELSE GREATEST(1, ROUND(quantity * $3::DOUBLE PRECISION))::INTEGER
+                END
+            WHERE smoking_type_id = $1
+            "#,
+            from_id,
+            to_id,
+            factor
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        sqlx::query!(
+            r#"
+            UPDATE shortcut_links
+            SET smoking_type_id = $2
+            WHERE smoking_type_id = $1
+            "#,
+            from_id,
+            to_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        // A guild that capped both types keeps the target's cap.
+        sqlx::query!(
+            r#"
+            INSERT INTO guild_type_caps (guild_id, smoking_type_id, daily_cap)
+            SELECT guild_id, $2, daily_cap
+            FROM guild_type_caps
+            WHERE smoking_type_id = $1
+            ON CONFLICT (guild_id, smoking_type_id) DO NOTHING
+            "#,
+            from_id,
+            to_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM guild_type_caps WHERE smoking_type_id = $1",
+            from_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            UPDATE panels
+            SET smoking_type_ids = CASE
+                WHEN $2 = ANY(smoking_type_ids) THEN array_remove(smoking_type_ids, $1)
+                ELSE array_replace(smoking_type_ids, $1, $2)
+            END
+            WHERE $1 = ANY(smoking_type_ids)
+            "#,
+            from_id,
+            to_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            UPDATE guild_settings
+            SET panel_smoking_type_ids = CASE
+                WHEN $2 = ANY(panel_smoking_type_ids)
+                    THEN array_remove(panel_smoking_type_ids, $1)
+                ELSE array_replace(panel_smoking_type_ids, $1, $2)
+            END,
+            updated_at = CURRENT_TIMESTAMP
+            WHERE $1 = ANY(panel_smoking_type_ids)
+            "#,
+            from_id,
+            to_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            UPDATE smoking_types t
+            SET description = COALESCE(NULLIF(TRIM(t.description), ''), s.description),
+                emoji = COALESCE(t.emoji, s.emoji),
+                unit = COALESCE(t.unit, s.unit),
+                typical_price = COALESCE(t.typical_price, s.typical_price),
+                currency = CASE
+                    WHEN t.typical_price IS NULL THEN s.currency
+                    ELSE t.currency
+                END
+            FROM smoking_types s
+            WHERE t.id = $2 AND s.id = $1
+            "#,
+            from_id,
+            to_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        // Types merged into the source earlier now point at the target as well.
+        sqlx::query!(
+            r#"
+            UPDATE smoking_types
+            SET merged_into = $2,
+                archived_at = COALESCE(archived_at, CURRENT_TIMESTAMP)
+            WHERE id = $1 OR merged_into = $1
+            "#,
+            from_id,
+            to_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        insert_admin_audit_entry(
+            &mut *tx,
+            actor_id,
+            "merge_types",
+            &from_id.to_string(),
+            &match factor {
+                Some(factor) => format!(
+                    "type {} -> {}: {} logs moved, quantities x{}",
+                    from_id, to_id, moved, factor
+                ),
+                None => format!("type {} -> {}: {} logs moved", from_id, to_id, moved),
+            },
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(moved)
+    }
+
+    /// Sets the conversion hint from one smoking type's unit to another's,
+    /// replacing any existing hint for the pair.
+    ///
+    /// # Arguments
+    /// * `from_type_id` - The ID of the source type.
+    /// * `to_type_id` - The ID of the target type.
+    /// * `factor` - How many units of the target type one unit of the source type is.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_unit_conversion(
+        &self,
+        from_type_id: i32,
+        to_type_id: i32,
+        factor: f64,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_unit_conversion");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO unit_conversions (from_type_id, to_type_id, factor)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (from_type_id, to_type_id) DO UPDATE SET factor = EXCLUDED.factor
+            "#,
+            from_type_id,
+            to_type_id,
+            factor
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes the conversion hint from one smoking type's unit to another's.
+    ///
+    /// # Arguments
+    /// * `from_type_id` - The ID of the source type.
+    /// * `to_type_id` - The ID of the target type.
+    ///
+    /// # Returns
+    /// A Result containing whether a hint was removed, or an `Error`.
+    pub async fn remove_unit_conversion(
+        &self,
+        from_type_id: i32,
+        to_type_id: i32,
+    ) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remove_unit_conversion");
+
+        let removed = sqlx::query!(
+            "DELETE FROM unit_conversions WHERE from_type_id = $1 AND to_type_id = $2",
+            from_type_id,
+            to_type_id
+        )
+        .execute(&*self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(removed > 0)
+    }
+
+    /// Retrieves the conversion factor from one smoking type's unit to
+    /// another's, inverting the hint of the opposite direction if only that one is set.
+    ///
+    /// # Arguments
+    /// * `from_type_id` - The ID of the source type.
+    /// * `to_type_id` - The ID of the target type.
+    ///
+    /// # Returns
+    /// A Result containing the factor, `None` if no hint is set for the pair, or an `Error`.
+    pub async fn get_unit_conversion(
+        &self,
+        from_type_id: i32,
+        to_type_id: i32,
+    ) -> Result<Option<f64>, Error> {
+        let _timer = QueryTimer::start("get_unit_conversion");
+
+        let factor = sqlx::query_scalar!(
+            r#"
+            SELECT factor as "factor!"
+            FROM (
+                SELECT factor, 0 AS priority
+                FROM unit_conversions
+                WHERE from_type_id = $1 AND to_type_id = $2
+                UNION ALL
+                SELECT 1.0 / factor, 1
+                FROM unit_conversions
+                WHERE from_type_id = $2 AND to_type_id = $1
+            ) hints
+            ORDER BY priority
+            LIMIT 1
+            "#,
+            from_type_id,
+            to_type_id
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(factor)
+    }
+
+    /// Retrieves every conversion hint.
+    ///
+    /// # Returns
+    /// A Result containing the hints, ordered by source and target type, or an `Error`.
+    pub async fn get_unit_conversions(&self) -> Result<Vec<UnitConversion>, Error> {
+        let _timer = QueryTimer::start("get_unit_conversions");
+
+        let conversions = sqlx::query_as!(
+            UnitConversion,
+            r#"
+            SELECT from_type_id, to_type_id, factor
+            FROM unit_conversions
+            ORDER BY from_type_id, to_type_id
+            "#
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(conversions)
+    }
+
+    /// Adds a category.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the category (e.g. `coffee`).
+    /// * `unit` - What one logged unit of its types is (e.g. `杯`).
+    /// * `emoji` - The emoji shown with the category, if any.
+    ///
+    /// # Returns
+    /// A Result containing the added `Category`, `None` if the name is taken, or an `Error`.
+    pub async fn add_category(
+        &self,
+        name: &str,
+        unit: &str,
+        emoji: Option<&str>,
+    ) -> Result<Option<Category>, Error> {
+        let _timer = QueryTimer::start("add_category");
+
+        let category = sqlx::query_as!(
+            Category,
+            r#"
+            INSERT INTO categories (name, unit, emoji)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (name) DO NOTHING
+            RETURNING id, name, unit, emoji
+            "#,
+            name,
+            unit,
+            emoji
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(category)
+    }
+
+    /// Removes a category; its types stay but no longer belong to a category.
+    ///
+    /// # Arguments
+    /// * `category_id` - The ID of the category.
+    ///
+    /// # Returns
+    /// A Result containing whether the category was removed, or an `Error`.
+    pub async fn remove_category(&self, category_id: i32) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remove_category");
+
+        let removed = sqlx::query!("DELETE FROM categories WHERE id = $1", category_id)
+            .execute(&*self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(removed > 0)
+    }
+
+    /// Retrieves every category.
+    ///
+    /// # Returns
+    /// A Result containing the categories, ordered by ID, or an `Error`.
+    pub async fn get_categories(&self) -> Result<Vec<Category>, Error> {
+        let _timer = QueryTimer::start("get_categories");
+
+        let categories = sqlx::query_as!(
+            Category,
+            "SELECT id, name, unit, emoji FROM categories ORDER BY id"
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(categories)
+    }
+
+    /// Finds a category by its name.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the category.
+    ///
+    /// # Returns
+    /// A Result containing the `Category` if found, `None` otherwise, or an `Error`.
+    pub async fn find_category_by_name(&self, name: &str) -> Result<Option<Category>, Error> {
+        let _timer = QueryTimer::start("find_category_by_name");
+
+        let category = sqlx::query_as!(
+            Category,
+            "SELECT id, name, unit, emoji FROM categories WHERE name = $1",
+            name
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(category)
+    }
+
+    /// Retrieves the category of every categorized smoking type.
+    ///
+    /// # Returns
+    /// A Result containing pairs of a type ID and its category ID, or an `Error`.
+    pub async fn get_type_categories(&self) -> Result<Vec<(i32, i32)>, Error> {
+        let _timer = QueryTimer::start("get_type_categories");
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, category_id as "category_id!"
+            FROM smoking_types
+            WHERE category_id IS NOT NULL AND archived_at IS NULL
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.id, row.category_id))
+            .collect())
+    }
+
+    /// Moves a smoking type into a category, or out of any.
+    ///
+    /// # Arguments
+    /// * `smoking_type_id` - The ID of the smoking type.
+    /// * `category_id` - The ID of the category, or `None` to leave the type uncategorized.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_type_category(
+        &self,
+        smoking_type_id: i32,
+        category_id: Option<i32>,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_type_category");
+
+        sqlx::query!(
+            "UPDATE smoking_types SET category_id = $2 WHERE id = $1",
+            smoking_type_id,
+            category_id
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Sets or clears a user's daily goal for a category.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `category_id` - The ID of the category.
+    /// * `daily_goal` - The maximum number of units per day, or `None` to clear the goal.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_category_goal(
+        &self,
+        discord_id: &str,
+        category_id: i32,
+        daily_goal: Option<i32>,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_category_goal");
+
+        match daily_goal {
+            Some(daily_goal) => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO category_goals (discord_id, category_id, daily_goal)
+                    VALUES ($1, $2, $3)
+                    ON CONFLICT (discord_id, category_id) DO UPDATE
+                    SET daily_goal = EXCLUDED.daily_goal, updated_at = CURRENT_TIMESTAMP
+                    "#,
+                    discord_id,
+                    category_id,
+                    daily_goal
+                )
+                .execute(&*self.pool)
+                .await?;
+            }
+            None => {
+                sqlx::query!(
+                    "DELETE FROM category_goals WHERE discord_id = $1 AND category_id = $2",
+                    discord_id,
+                    category_id
+                )
+                .execute(&*self.pool)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Retrieves a user's progress towards their goal for the category of a smoking type.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `smoking_type_id` - The ID of the smoking type.
+    /// * `date` - The local date to total.
+    /// * `timezone` - The time zone the date is counted in.
+    ///
+    /// # Returns
+    /// A Result containing the `CategoryProgress`, `None` if the type has no
+    /// category or the user set no goal for it, or an `Error`.
+    pub async fn get_category_progress(
+        &self,
+        discord_id: &str,
+        smoking_type_id: i32,
+        date: NaiveDate,
+        timezone: Tz,
+    ) -> Result<Option<CategoryProgress>, Error> {
+        let _timer = QueryTimer::start("get_category_progress");
+        let (start, end) = rollover::day_bounds(timezone, date);
+
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                c.id,
+                c.name,
+                c.unit,
+                c.emoji,
+                g.daily_goal,
+                COALESCE((
+                    SELECT SUM(sl.quantity)
+                    FROM smoking_logs sl
+                    JOIN smoking_types logged ON logged.id = sl.smoking_type_id
+                    WHERE sl.discord_id = $1
+                    AND logged.category_id = c.id
+                    AND sl.smoked_at >= $3
+                    AND sl.smoked_at < $4
+                ), 0) as "total!"
+            FROM smoking_types st
+            JOIN categories c ON c.id = st.category_id
+            JOIN category_goals g ON g.category_id = c.id AND g.discord_id = $1
+            WHERE st.id = $2
+            "#,
+            discord_id,
+            smoking_type_id,
+            start,
+            end
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(row.map(|row| CategoryProgress {
+            category: Category {
+                id: row.id,
+                name: row.name,
+                unit: row.unit,
+                emoji: row.emoji,
+            },
+            total: row.total,
+            daily_goal: row.daily_goal,
+        }))
+    }
+
+    /// Retrieves a log by its ID.
+    ///
+    /// # Arguments
+    /// * `log_id` - The ID of the log.
+    ///
+    /// # Returns
+    /// A Result containing the log if found, `None` otherwise, or an `Error`.
+    pub async fn get_log(&self, log_id: i32) -> Result<Option<SmokingLog>, Error> {
+        let _timer = QueryTimer::start("get_log");
+
+        let log = sqlx::query_as!(
+            SmokingLog,
+            r#"
+            SELECT
+                id as "id!",
+                discord_id as "discord_id!",
+                smoking_type_id as "smoking_type_id!",
+                quantity as "quantity!",
+                smoked_at as "smoked_at!",
+                device_name,
+                guild_id,
+                idempotency_key,
+                created_at,
+                updated_at
+            FROM smoking_logs
+            WHERE id = $1
+            "#,
+            log_id
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(log)
+    }
+
+    /// Records an owner's operational action in the audit log.
+    ///
+    /// # Arguments
+    /// * `actor_id` - The Discord ID of the owner.
+    /// * `action` - The name of the action.
+    /// * `target_id` - The Discord ID of the affected user.
+    /// * `details` - A description of what changed.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn record_admin_action(
+        &self,
+        actor_id: &str,
+        action: &str,
+        target_id: &str,
+        details: &str,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("record_admin_action");
+
+        insert_admin_audit_entry(&*self.pool, actor_id, action, target_id, details).await
+    }
+
+    /// Retrieves the most recent entries of the audit log.
+    ///
+    /// # Arguments
+    /// * `limit` - The maximum number of entries.
+    ///
+    /// # Returns
+    /// A Result containing a vector of `AdminAuditEntry`, newest first, or an `Error`.
+    pub async fn get_admin_audit_entries(&self, limit: i64) -> Result<Vec<AdminAuditEntry>, Error> {
+        let _timer = QueryTimer::start("get_admin_audit_entries");
+
+        let entries = sqlx::query_as!(
+            AdminAuditEntry,
+            r#"
+            SELECT id, actor_id, action, target_id, details, created_at
+            FROM admin_audit_log
+            ORDER BY id DESC
+            LIMIT $1
+            "#,
+            limit
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// Deletes a user's most recent log.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing the deleted `SmokingLog`, `None` if the user has no logs, or an `Error`.
+    pub async fn delete_last_log(&self, discord_id: &str) -> Result<Option<SmokingLog>, Error> {
+        let _timer = QueryTimer::start("delete_last_log");
+
+        let mut tx = self.pool.begin().await?;
+        let log = sqlx::query_as!(
+            SmokingLog,
+            r#"
+            DELETE FROM smoking_logs
+            WHERE id = (
+                SELECT id
+                FROM smoking_logs
+                WHERE discord_id = $1
+                ORDER BY smoked_at DESC, id DESC
+                LIMIT 1
+            )
+            RETURNING
+                id as "id!",
+                discord_id as "discord_id!",
+                smoking_type_id as "smoking_type_id!",
+                quantity as "quantity!",
+                smoked_at as "smoked_at!",
+                device_name,
+                guild_id,
+                idempotency_key,
+                created_at,
+                updated_at
+            "#,
+            discord_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(log) = &log {
+            self.notify_log_change(&mut tx, LogChange::Deleted, log)
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(log)
+    }
+
+    /// Finds logs that repeat an earlier log of the same user, type and quantity
+    /// within a short window, e.g. double presses from before requests were debounced.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user, or `None` for every user.
+    /// * `window` - The longest gap between two logs counted as duplicates.
+    ///
+    /// # Returns
+    /// A Result containing a vector of `DuplicateLog` ordered by user and time, or an `Error`.
+    pub async fn find_duplicate_logs(
+        &self,
+        discord_id: Option<&str>,
+        window: chrono::Duration,
+    ) -> Result<Vec<DuplicateLog>, Error> {
+        let _timer = QueryTimer::start("find_duplicate_logs");
+
+        select_duplicate_logs(&*self.pool, discord_id, window).await
+    }
+
+    /// Removes a user's duplicate logs and audits the change, in one transaction.
+    ///
+    /// # Arguments
+    /// * `actor_id` - The Discord ID of the user or owner requesting the cleanup.
+    /// * `discord_id` - The Discord ID of the user whose logs are cleaned up.
+    /// * `window` - The longest gap between two logs counted as duplicates.
+    /// * `resolution` - Whether the duplicates' quantities are kept on the original logs.
+    ///
+    /// # Returns
+    /// A Result containing the number of removed logs or an `Error`.
+    pub async fn resolve_duplicate_logs(
+        &self,
+        actor_id: &str,
+        discord_id: &str,
+        window: chrono::Duration,
+        resolution: DuplicateResolution,
+    ) -> Result<u64, Error> {
+        let _timer = QueryTimer::start("resolve_duplicate_logs");
+
+        let mut tx = self.pool.begin().await?;
+        let duplicates = select_duplicate_logs(&mut *tx, Some(discord_id), window).await?;
+        if duplicates.is_empty() {
+            return Ok(0);
+        }
+
+        if resolution == DuplicateResolution::Merge {
+            for duplicate in &duplicates {
+                sqlx::query!(
+                    r#"
+                    UPDATE smoking_logs
+                    SET quantity = quantity + $2
+                    WHERE id = $1
+                    "#,
+                    duplicate.original_id,
+                    duplicate.quantity
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        let ids: Vec<i32> = duplicates.iter().map(|duplicate| duplicate.id).collect();
+        let removed = sqlx::query_as!(
+            SmokingLog,
+            r#"
+            DELETE FROM smoking_logs
+            WHERE id = ANY($1)
+            RETURNING
+                id as "id!",
+                discord_id as "discord_id!",
+                smoking_type_id as "smoking_type_id!",
+                quantity as "quantity!",
+                smoked_at as "smoked_at!",
+                device_name,
+                guild_id,
+                idempotency_key,
+                created_at,
+                updated_at
+            "#,
+            &ids
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        for log in &removed {
+            self.notify_log_change(&mut tx, LogChange::Deleted, log)
+                .await?;
+        }
+        let ids: Vec<String> = ids.iter().map(i32::to_string).collect();
+        insert_admin_audit_entry(
+            &mut *tx,
+            actor_id,
+            resolution.action(),
+            discord_id,
+            &format!("removed logs {}", ids.join(", ")),
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(removed.len() as u64)
+    }
+
+    /// Runs `EXPLAIN ANALYZE` for a query inside a transaction that is always rolled back.
+    ///
+    /// # Arguments
+    /// * `sql` - The query to explain.
+    /// * `params` - Values bound to the query's parameters, in order.
+    ///
+    /// # Returns
+    /// A Result containing the lines of the query plan or an `Error`.
+    pub async fn explain_analyze(
+        &self,
+        sql: &str,
+        params: &[ParamValue],
+    ) -> Result<Vec<String>, Error> {
+        let _timer = QueryTimer::start("explain_analyze");
+
+        let mut tx = self.pool.begin().await?;
+
+        let explain = format!("EXPLAIN (ANALYZE, BUFFERS) {}", sql);
+        let mut query = sqlx::query_scalar::<_, String>(&explain);
+        for param in params {
+            query = match param {
+                ParamValue::Text(value) => query.bind(value),
+                ParamValue::Date(value) => query.bind(value),
+                ParamValue::Int(value) => query.bind(value),
+                ParamValue::Timestamp(value) => query.bind(value),
+            };
+        }
+        let plan = query.fetch_all(&mut *tx).await?;
+
+        tx.rollback().await?;
+
+        Ok(plan)
+    }
+
+    /// Finds which of the given indexes do not exist in the database.
+    ///
+    /// # Arguments
+    /// * `expected` - The names of the expected indexes.
+    ///
+    /// # Returns
+    /// A Result containing the names of the missing indexes or an `Error`.
+    pub async fn find_missing_indexes(&self, expected: &[&str]) -> Result<Vec<String>, Error> {
+        let _timer = QueryTimer::start("find_missing_indexes");
+
+        let expected: Vec<String> = expected.iter().map(|name| name.to_string()).collect();
+        let missing = sqlx::query_scalar!(
+            r#"
+            SELECT name as "name!"
+            FROM UNNEST($1::text[]) as name
+            WHERE NOT EXISTS (
+                SELECT 1 FROM pg_indexes
+                WHERE schemaname = current_schema()
+                AND indexname = name
+            )
+            ORDER BY name
+            "#,
+            &expected
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(missing)
+    }
+
+    /// Writes the `log_created` outbox message of a new log if the outbox is enabled.
+    ///
+    /// # Arguments
+    /// * `tx` - The transaction the log was inserted in.
+    /// * `log` - The new log.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    async fn enqueue_log_created(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        log: &SmokingLog,
+    ) -> Result<(), Error> {
+        if !self.outbox {
+            return Ok(());
+        }
+
+        let payload = serde_json::json!({
+            "id": log.id,
+            "discord_id": self.anonymizer.id(&log.discord_id),
+            "guild_id": self.anonymizer.optional_id(log.guild_id.as_deref()),
+            "smoking_type_id": log.smoking_type_id,
+            "quantity": log.quantity,
+            "smoked_at": log.smoked_at,
+            "device_name": log.device_name,
+        });
+        insert_outbox_message(&mut **tx, LOG_CREATED_TOPIC, &payload.to_string()).await?;
+
+        Ok(())
+    }
+
+    /// Notifies log consumers of an inserted or deleted log, if enabled.
+    ///
+    /// # Arguments
+    /// * `tx` - The transaction changing the log.
+    /// * `change` - What happened to the log.
+    /// * `log` - The log.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    async fn notify_log_change(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        change: LogChange,
+        log: &SmokingLog,
+    ) -> Result<(), Error> {
+        let Some(channel) = &self.notify_channel else {
+            return Ok(());
+        };
+
+        let payload = log_notify::payload(change, log, &self.anonymizer);
+        sqlx::query!(
+            "SELECT FROM pg_notify($1, $2)",
+            channel,
+            payload.to_string()
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Adds a message to the outbox.
+    ///
+    /// # Arguments
+    /// * `topic` - The kind of message.
+    /// * `payload` - The JSON payload delivered to the sinks.
+    ///
+    /// # Returns
+    /// A Result containing the queued `OutboxMessage` or an `Error`.
+    pub async fn enqueue_outbox_message(
+        &self,
+        topic: &str,
+        payload: &str,
+    ) -> Result<OutboxMessage, Error> {
+        let _timer = QueryTimer::start("enqueue_outbox_message");
+
+        insert_outbox_message(&*self.pool, topic, payload).await
+    }
+
+    /// Retrieves the outbox messages due for a delivery attempt.
+    ///
+    /// # Arguments
+    /// * `now` - The current time.
+    /// * `limit` - The maximum number of messages to return.
+    ///
+    /// # Returns
+    /// A Result containing the due messages, oldest first, or an `Error`.
+    pub async fn get_due_outbox_messages(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<OutboxMessage>, Error> {
+        let _timer = QueryTimer::start("get_due_outbox_messages");
+
+        let messages = sqlx::query_as!(
+            OutboxMessage,
+            r#"
+            SELECT id, topic, payload, attempts, next_attempt_at, last_error,
+                delivered_at, dead_lettered_at, created_at
+            FROM outbox
+            WHERE delivered_at IS NULL
+            AND dead_lettered_at IS NULL
+            AND next_attempt_at <= $1
+            ORDER BY id
+            LIMIT $2
+            "#,
+            now,
+            limit
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(messages)
+    }
+
+    /// Retrieves the outbox messages that were given up on.
+    ///
+    /// # Returns
+    /// A Result containing the dead-lettered messages, oldest first, or an `Error`.
+    pub async fn get_dead_lettered_outbox_messages(&self) -> Result<Vec<OutboxMessage>, Error> {
+        let _timer = QueryTimer::start("get_dead_lettered_outbox_messages");
+
+        let messages = sqlx::query_as!(
+            OutboxMessage,
+            r#"
+            SELECT id, topic, payload, attempts, next_attempt_at, last_error,
+                delivered_at, dead_lettered_at, created_at
+            FROM outbox
+            WHERE dead_lettered_at IS NOT NULL
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(messages)
+    }
+
+    /// Marks an outbox message as delivered.
+    ///
+    /// # Arguments
+    /// * `id` - The ID of the message.
+    /// * `now` - The time of delivery.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn mark_outbox_delivered(&self, id: i32, now: DateTime<Utc>) -> Result<(), Error> {
+        let _timer = QueryTimer::start("mark_outbox_delivered");
+
+        sqlx::query!(
+            r#"
+            UPDATE outbox
+            SET attempts = attempts + 1, delivered_at = $2, last_error = NULL
+            WHERE id = $1
+            "#,
+            id,
+            now
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Records a failed delivery attempt of an outbox message.
+    ///
+    /// # Arguments
+    /// * `id` - The ID of the message.
+    /// * `error` - Why the delivery failed.
+    /// * `now` - The time of the attempt.
+    /// * `retry_at` - When to try again, or `None` to dead-letter the message.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn record_outbox_failure(
+        &self,
+        id: i32,
+        error: &str,
+        now: DateTime<Utc>,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("record_outbox_failure");
+
+        sqlx::query!(
+            r#"
+            UPDATE outbox
+            SET attempts = attempts + 1,
+                last_error = $2,
+                next_attempt_at = COALESCE($4, next_attempt_at),
+                dead_lettered_at = CASE WHEN $4::timestamptz IS NULL THEN $3::timestamptz END
+            WHERE id = $1
+            "#,
+            id,
+            error,
+            now,
+            retry_at
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Schedules a job.
+    ///
+    /// # Arguments
+    /// * `kind` - The kind of job, selecting its handler.
+    /// * `payload` - The JSON payload passed to the handler.
+    /// * `run_at` - When the job is due.
+    ///
+    /// # Returns
+    /// A Result containing the scheduled `ScheduledJob` or an `Error`.
+    pub async fn schedule_job(
+        &self,
+        kind: &str,
+        payload: &str,
+        run_at: DateTime<Utc>,
+    ) -> Result<ScheduledJob, Error> {
+        let _timer = QueryTimer::start("schedule_job");
+
+        let job = sqlx::query_as!(
+            ScheduledJob,
+            r#"
+            INSERT INTO scheduled_jobs (kind, payload, run_at)
+            VALUES ($1, $2, $3)
+            RETURNING id, kind, payload, run_at, status, attempts, last_error, created_at
+            "#,
+            kind,
+            payload,
+            run_at
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(job)
+    }
+
+    /// Schedules a job of a kind unless one is already pending or running.
+    ///
+    /// # Arguments
+    /// * `kind` - The kind of job.
+    /// * `run_at` - When the job is due.
+    ///
+    /// # Returns
+    /// A Result containing whether a job was scheduled, or an `Error`.
+    pub async fn ensure_job_scheduled(
+        &self,
+        kind: &str,
+        run_at: DateTime<Utc>,
+    ) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("ensure_job_scheduled");
+
+        let scheduled = sqlx::query!(
+            r#"
+            INSERT INTO scheduled_jobs (kind, run_at)
+            SELECT $1::varchar, $2::timestamptz
+            WHERE NOT EXISTS (
+                SELECT 1 FROM scheduled_jobs
+                WHERE kind = $1::varchar AND status IN ('pending', 'running')
+            )
+            "#,
+            kind,
+            run_at
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(scheduled.rows_affected() > 0)
+    }
+
+    /// Marks due jobs as running and returns them. Jobs left running since
+    /// before `stale_before` (e.g. by a replica that stopped) are claimed again.
+    ///
+    /// # Arguments
+    /// * `now` - The current time.
+    /// * `stale_before` - Running jobs claimed before this time are due again.
+    /// * `limit` - The maximum number of jobs.
+    ///
+    /// # Returns
+    /// A Result containing the claimed jobs ordered by due time, or an `Error`.
+    pub async fn claim_due_jobs(
+        &self,
+        now: DateTime<Utc>,
+        stale_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<ScheduledJob>, Error> {
+        let _timer = QueryTimer::start("claim_due_jobs");
+
+        let mut jobs = sqlx::query_as!(
+            ScheduledJob,
+            r#"
+            UPDATE scheduled_jobs
+            SET status = 'running', attempts = attempts + 1, claimed_at = $1
+            WHERE id IN (
+                SELECT id FROM scheduled_jobs
+                WHERE (status = 'pending' AND run_at <= $1)
+                OR (status = 'running' AND claimed_at < $2)
+                ORDER BY run_at
+                LIMIT $3
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, kind, payload, run_at, status, attempts, last_error, created_at
+            "#,
+            now,
+            stale_before,
+            limit
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+        jobs.sort_by_key(|job| (job.run_at, job.id));
+
+        Ok(jobs)
+    }
+
+    /// Marks a job as done.
+    ///
+    /// # Arguments
+    /// * `id` - The ID of the job.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn complete_job(&self, id: i32) -> Result<(), Error> {
+        let _timer = QueryTimer::start("complete_job");
+
+        sqlx::query!(
+            r#"
+            UPDATE scheduled_jobs
+            SET status = 'done', last_error = NULL
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Records a failed run of a job.
+    ///
+    /// # Arguments
+    /// * `id` - The ID of the job.
+    /// * `error` - Why the run failed.
+    /// * `retry_at` - When to run the job again, or `None` to give up on it.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn fail_job(
+        &self,
+        id: i32,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("fail_job");
+
+        sqlx::query!(
+            r#"
+            UPDATE scheduled_jobs
+            SET status = CASE WHEN $3::timestamptz IS NULL THEN 'failed' ELSE 'pending' END,
+                last_error = $2,
+                run_at = COALESCE($3, run_at)
+            WHERE id = $1
+            "#,
+            id,
+            error,
+            retry_at
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Cancels a job that has not started.
+    ///
+    /// # Arguments
+    /// * `id` - The ID of the job.
+    ///
+    /// # Returns
+    /// A Result containing whether a pending job was cancelled, or an `Error`.
+    pub async fn cancel_job(&self, id: i32) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("cancel_job");
+
+        let cancelled = sqlx::query!(
+            r#"
+            UPDATE scheduled_jobs
+            SET status = 'cancelled'
+            WHERE id = $1 AND status = 'pending'
+            "#,
+            id
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(cancelled.rows_affected() > 0)
+    }
+
+    /// Retrieves the jobs that are pending, running or failed.
+    ///
+    /// # Arguments
+    /// * `limit` - The maximum number of jobs.
+    ///
+    /// # Returns
+    /// A Result containing the jobs ordered by due time, or an `Error`.
+    pub async fn get_open_jobs(&self, limit: i64) -> Result<Vec<ScheduledJob>, Error> {
+        let _timer = QueryTimer::start("get_open_jobs");
+
+        let jobs = sqlx::query_as!(
+            ScheduledJob,
+            r#"
+            SELECT id, kind, payload, run_at, status, attempts, last_error, created_at
+            FROM scheduled_jobs
+            WHERE status IN ('pending', 'running', 'failed')
+            ORDER BY run_at, id
+            LIMIT $1
+            "#,
+            limit
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(jobs)
+    }
+
+    /// Retrieves when the longest-overdue pending job was due.
+    ///
+    /// # Arguments
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    /// A Result containing the due time, `None` if no pending job is due, or an `Error`.
+    pub async fn get_oldest_due_job(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<Option<DateTime<Utc>>, Error> {
+        let _timer = QueryTimer::start("get_oldest_due_job");
+
+        let oldest = sqlx::query_scalar!(
+            r#"
+            SELECT MIN(run_at)
+            FROM scheduled_jobs
+            WHERE status = 'pending' AND run_at <= $1
+            "#,
+            now
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(oldest)
+    }
+}
+
+/// Access to the settings and data of a single guild
+///
+/// Every query on guild-scoped data (milestone roles, message templates,
+/// smoke-break channels, guild member lists) lives here and filters on the
+/// guild the scope was created for, so a command handling one guild cannot
+/// read or change another guild's rows by passing the wrong ID to a query.
+/// Only owner commands and background tasks use the few global queries on
+/// `Database` that span guilds.
+pub struct GuildScope<'a> {
+    db: &'a Database,
+    guild_id: String,
+}
+
+impl GuildScope<'_> {
+    /// Returns the ID of the guild this scope is limited to.
+    pub fn guild_id(&self) -> &str {
+        &self.guild_id
+    }
+
+    /// Maps a guild role to a smoke-free-days milestone.
+    ///
+    /// # Arguments
+    /// * `role_id` - The ID of the role to assign.
+    /// * `smoke_free_days` - The number of smoke-free days required for the role.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_milestone_role(
+        &self,
+        role_id: &str,
+        smoke_free_days: i32,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_milestone_role");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO milestone_roles (guild_id, role_id, smoke_free_days)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (guild_id, role_id) DO UPDATE
+            SET smoke_free_days = EXCLUDED.smoke_free_days
+            "#,
+            self.guild_id,
+            role_id,
+            smoke_free_days
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes a milestone role mapping.
+    ///
+    /// # Arguments
+    /// * `role_id` - The ID of the mapped role.
+    ///
+    /// # Returns
+    /// A Result containing whether a mapping was removed, or an `Error`.
+    pub async fn remove_milestone_role(&self, role_id: &str) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remove_milestone_role");
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM milestone_roles
+            WHERE guild_id = $1 AND role_id = $2
+            "#,
+            self.guild_id,
+            role_id
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves the guild's milestone role mappings.
+    ///
+    /// # Returns
+    /// A Result containing a vector of `MilestoneRole` ordered by milestone, or an `Error`.
+    pub async fn get_milestone_roles(&self) -> Result<Vec<MilestoneRole>, Error> {
+        let _timer = QueryTimer::start("get_milestone_roles");
+
+        let roles = sqlx::query_as!(
+            MilestoneRole,
+            r#"
+            SELECT guild_id, role_id, smoke_free_days
+            FROM milestone_roles
+            WHERE guild_id = $1
+            ORDER BY smoke_free_days, role_id
+            "#,
+            self.guild_id
+        )
+        .fetch_all(&*self.db.pool)
+        .await?;
+
+        Ok(roles)
+    }
+
+    /// Defines a custom achievement.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the achievement.
+    /// * `condition` - The condition members must meet, in the achievement DSL.
+    ///
+    /// # Returns
+    /// A Result containing whether it was created (`false` if the name is taken), or an `Error`.
+    pub async fn create_achievement(&self, name: &str, condition: &str) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("create_achievement");
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO guild_achievements (guild_id, name, condition)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (guild_id, name) DO NOTHING
+            "#,
+            self.guild_id,
+            name,
+            condition
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Deletes a custom achievement, together with who earned it.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the achievement.
+    ///
+    /// # Returns
+    /// A Result containing whether the achievement existed, or an `Error`.
+    pub async fn delete_achievement(&self, name: &str) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("delete_achievement");
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM guild_achievements
+            WHERE guild_id = $1 AND name = $2
+            "#,
+            self.guild_id,
+            name
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves the guild's custom achievements.
+    ///
+    /// # Returns
+    /// A Result containing the achievements in order of creation, or an `Error`.
+    pub async fn get_achievements(&self) -> Result<Vec<GuildAchievement>, Error> {
+        let _timer = QueryTimer::start("get_achievements");
+
+        let achievements = sqlx::query_as!(
+            GuildAchievement,
+            r#"
+            SELECT id, name, condition
+            FROM guild_achievements
+            WHERE guild_id = $1
+            ORDER BY id
+            "#,
+            self.guild_id
+        )
+        .fetch_all(&*self.db.pool)
+        .await?;
+
+        Ok(achievements)
+    }
+
+    /// Records that a member earned an achievement.
+    ///
+    /// # Arguments
+    /// * `achievement_id` - The ID of the achievement.
+    /// * `discord_id` - The Discord ID of the member.
+    /// * `earned_at` - When the condition was met.
+    ///
+    /// # Returns
+    /// A Result containing whether it was newly earned, or an `Error`.
+    pub async fn award_achievement(
+        &self,
+        achievement_id: i32,
+        discord_id: &str,
+        earned_at: DateTime<Utc>,
+    ) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("award_achievement");
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO earned_achievements (achievement_id, guild_id, discord_id, earned_at)
+            SELECT id, guild_id, $3, $4
+            FROM guild_achievements
+            WHERE guild_id = $1 AND id = $2
+            ON CONFLICT (achievement_id, discord_id) DO NOTHING
+            "#,
+            self.guild_id,
+            achievement_id,
+            discord_id,
+            earned_at
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves the achievements a member earned in the guild.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the member.
+    ///
+    /// # Returns
+    /// A Result containing the earned achievements in the order they were earned, or an `Error`.
+    pub async fn get_earned_achievements(
+        &self,
+        discord_id: &str,
+    ) -> Result<Vec<EarnedAchievement>, Error> {
+        let _timer = QueryTimer::start("get_earned_achievements");
+
+        let earned = sqlx::query_as!(
+            EarnedAchievement,
+            r#"
+            SELECT e.achievement_id, a.name, e.earned_at
+            FROM earned_achievements e
+            JOIN guild_achievements a ON a.id = e.achievement_id
+            WHERE e.guild_id = $1 AND e.discord_id = $2
+            ORDER BY e.earned_at, a.id
+            "#,
+            self.guild_id,
+            discord_id
+        )
+        .fetch_all(&*self.db.pool)
+        .await?;
+
+        Ok(earned)
+    }
+
+    /// Retrieves the IDs of all users who have logged in a guild.
+    ///
+    /// # Returns
+    /// A Result containing a vector of Discord IDs or an `Error`.
+    pub async fn get_user_ids(&self) -> Result<Vec<String>, Error> {
+        let _timer = QueryTimer::start("get_guild_user_ids");
+
+        let users = sqlx::query_scalar!(
+            r#"
+            SELECT DISTINCT discord_id as "discord_id!"
+            FROM smoking_logs
+            WHERE guild_id = $1
+            ORDER BY discord_id
+            "#,
+            self.guild_id
+        )
+        .fetch_all(&*self.db.pool)
+        .await?;
+
+        Ok(users)
+    }
+
+    /// Inserts logs carried over from another bot instance, all or none.
+    ///
+    /// # Arguments
+    /// * `logs` - The logs, with the IDs of their users and smoking types on this instance.
+    ///
+    /// # Returns
+    /// A Result containing the number of inserted logs or an `Error`.
+    pub async fn import_logs(&self, logs: &[ImportedLog]) -> Result<u64, Error> {
+        let _timer = QueryTimer::start("import_guild_logs");
+
+        let discord_ids: Vec<String> = logs.iter().map(|log| log.discord_id.clone()).collect();
+        let type_ids: Vec<i32> = logs.iter().map(|log| log.smoking_type_id).collect();
+        let quantities: Vec<i32> = logs.iter().map(|log| log.quantity).collect();
+        let smoked_at: Vec<DateTime<Utc>> = logs.iter().map(|log| log.smoked_at).collect();
+
+        let mut tx = self.db.pool.begin().await?;
+        let inserted = sqlx::query_as!(
+            SmokingLog,
+            r#"
+            INSERT INTO smoking_logs (discord_id, smoking_type_id, quantity, smoked_at, guild_id)
+            SELECT log.discord_id, log.smoking_type_id, log.quantity, log.smoked_at, $5
+            FROM UNNEST($1::varchar[], $2::int[], $3::int[], $4::timestamptz[])
+                AS log(discord_id, smoking_type_id, quantity, smoked_at)
+            RETURNING
+                id as "id!",
+                discord_id as "discord_id!",
+                smoking_type_id as "smoking_type_id!",
+                quantity as "quantity!",
+                smoked_at as "smoked_at!",
+                device_name,
+                guild_id,
+                idempotency_key,
+                created_at,
+                updated_at
+            "#,
+            &discord_ids,
+            &type_ids,
+            &quantities,
+            &smoked_at,
+            self.guild_id
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        for log in &inserted {
+            self.db
+                .notify_log_change(&mut tx, LogChange::Created, log)
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(inserted.len() as u64)
+    }
+
+    /// Stores a guild's override of a message template.
+    ///
+    /// # Arguments
+    /// * `template_key` - The name of the template key.
+    /// * `template` - The validated template text.
+    /// * `expected_version` - The version the change is based on (0 for the default), or
+    ///   `None` to overwrite whatever is stored.
+    ///
+    /// # Returns
+    /// A Result containing the new version, `None` if the stored version is not the
+    /// expected one, or an `Error`.
+    pub async fn set_message_template(
+        &self,
+        template_key: &str,
+        template: &str,
+        expected_version: Option<i32>,
+    ) -> Result<Option<i32>, Error> {
+        let _timer = QueryTimer::start("set_message_template");
+
+        let version = match expected_version {
+            Some(expected_version) if expected_version > 0 => {
+                sqlx::query_scalar!(
+                    r#"
+                    UPDATE message_templates
+                    SET template = $3,
+                        version = version + 1,
+                        updated_at = CURRENT_TIMESTAMP
+                    WHERE guild_id = $1 AND template_key = $2 AND version = $4
+                    RETURNING version
+                    "#,
+                    self.guild_id,
+                    template_key,
+                    template,
+                    expected_version
+                )
+                .fetch_optional(&*self.db.pool)
+                .await?
+            }
+            // Expecting the default only succeeds if there is no override yet.
+            _ => {
+                sqlx::query_scalar!(
+                    r#"
+                    INSERT INTO message_templates (guild_id, template_key, template)
+                    VALUES ($1, $2, $3)
+                    ON CONFLICT (guild_id, template_key) DO UPDATE
+                    SET template = EXCLUDED.template,
+                        version = message_templates.version + 1,
+                        updated_at = CURRENT_TIMESTAMP
+                    WHERE $4
+                    RETURNING version
+                    "#,
+                    self.guild_id,
+                    template_key,
+                    template,
+                    expected_version.is_none()
+                )
+                .fetch_optional(&*self.db.pool)
+                .await?
+            }
+        };
+
+        Ok(version)
+    }
+
+    /// Removes a guild's override of a message template.
+    ///
+    /// # Arguments
+    /// * `template_key` - The name of the template key.
+    /// * `expected_version` - The version the removal is based on, or `None` to remove
+    ///   whatever is stored.
+    ///
+    /// # Returns
+    /// A Result containing whether an override was removed, or an `Error`.
+    pub async fn remove_message_template(
+        &self,
+        template_key: &str,
+        expected_version: Option<i32>,
+    ) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remove_message_template");
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM message_templates
+            WHERE guild_id = $1 AND template_key = $2
+            AND ($3::int IS NULL OR version = $3)
+            "#,
+            self.guild_id,
+            template_key,
+            expected_version
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves the version of a guild's override of a message template.
+    ///
+    /// # Arguments
+    /// * `template_key` - The name of the template key.
+    ///
+    /// # Returns
+    /// A Result containing the version (0 if the guild uses the default) or an `Error`.
+    pub async fn get_message_template_version(&self, template_key: &str) -> Result<i32, Error> {
+        let _timer = QueryTimer::start("get_message_template_version");
+
+        let version = sqlx::query_scalar!(
+            r#"
+            SELECT version
+            FROM message_templates
+            WHERE guild_id = $1 AND template_key = $2
+            "#,
+            self.guild_id,
+            template_key
+        )
+        .fetch_optional(&*self.db.pool)
+        .await?;
+
+        Ok(version.unwrap_or_default())
+    }
+
+    /// Retrieves a guild's override of a message template.
+    ///
+    /// # Arguments
+    /// * `template_key` - The name of the template key.
+    ///
+    /// # Returns
+    /// A Result containing the template text, `None` if the guild uses the default, or an `Error`.
+    pub async fn get_message_template(&self, template_key: &str) -> Result<Option<String>, Error> {
+        let _timer = QueryTimer::start("get_message_template");
+
+        let template = sqlx::query_scalar!(
+            r#"
+            SELECT template
+            FROM message_templates
+            WHERE guild_id = $1 AND template_key = $2
+            "#,
+            self.guild_id,
+            template_key
+        )
+        .fetch_optional(&*self.db.pool)
+        .await?;
+
+        Ok(template)
+    }
+
+    /// Retrieves all message template overrides of a guild.
+    ///
+    /// # Returns
+    /// A Result containing a vector of `MessageTemplate` ordered by key, or an `Error`.
+    pub async fn get_message_templates(&self) -> Result<Vec<MessageTemplate>, Error> {
+        let _timer = QueryTimer::start("get_message_templates");
+
+        let templates = sqlx::query_as!(
+            MessageTemplate,
+            r#"
+            SELECT guild_id, template_key, template, version, updated_at
+            FROM message_templates
+            WHERE guild_id = $1
+            ORDER BY template_key
+            "#,
+            self.guild_id
+        )
+        .fetch_all(&*self.db.pool)
+        .await?;
+
+        Ok(templates)
+    }
+
+    /// Designates a voice channel as a smoke-break channel.
+    ///
+    /// # Arguments
+    /// * `channel_id` - The ID of the voice channel.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn add_smoke_break_channel(&self, channel_id: &str) -> Result<(), Error> {
+        let _timer = QueryTimer::start("add_smoke_break_channel");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO smoke_break_channels (channel_id, guild_id)
+            VALUES ($1, $2)
+            ON CONFLICT (channel_id) DO NOTHING
+            "#,
+            channel_id,
+            self.guild_id
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes a smoke-break channel designation.
+    ///
+    /// # Arguments
+    /// * `channel_id` - The ID of the voice channel.
+    ///
+    /// # Returns
+    /// A Result containing whether a designation was removed, or an `Error`.
+    pub async fn remove_smoke_break_channel(&self, channel_id: &str) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remove_smoke_break_channel");
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM smoke_break_channels
+            WHERE guild_id = $1 AND channel_id = $2
+            "#,
+            self.guild_id,
+            channel_id
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves the guild's smoke-break channels.
+    ///
+    /// # Returns
+    /// A Result containing the IDs of the voice channels or an `Error`.
+    pub async fn get_smoke_break_channels(&self) -> Result<Vec<String>, Error> {
+        let _timer = QueryTimer::start("get_smoke_break_channels");
+
+        let channels = sqlx::query_scalar!(
+            r#"
+            SELECT channel_id
+            FROM smoke_break_channels
+            WHERE guild_id = $1
+            ORDER BY channel_id
+            "#,
+            self.guild_id
+        )
+        .fetch_all(&*self.db.pool)
+        .await?;
+
+        Ok(channels)
+    }
+
+    /// Checks whether a voice channel is one of the guild's smoke-break channels.
+    ///
+    /// # Arguments
+    /// * `channel_id` - The ID of the voice channel.
+    ///
+    /// # Returns
+    /// A Result containing whether the channel is designated or an `Error`.
+    pub async fn is_smoke_break_channel(&self, channel_id: &str) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("is_smoke_break_channel");
+
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM smoke_break_channels
+                WHERE guild_id = $1 AND channel_id = $2
+            ) as "exists!"
+            "#,
+            self.guild_id,
+            channel_id
+        )
+        .fetch_one(&*self.db.pool)
+        .await?;
+
+        Ok(exists)
+    }
+
+    /// Sets the forum channel the guild's weekly threads are opened in.
+    ///
+    /// # Arguments
+    /// * `channel_id` - The ID of the forum channel.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_forum_channel(&self, channel_id: &str) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_forum_channel");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO forum_channels (guild_id, channel_id)
+            VALUES ($1, $2)
+            ON CONFLICT (guild_id) DO UPDATE
+            SET channel_id = EXCLUDED.channel_id
+            "#,
+            self.guild_id,
+            channel_id
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Stops opening weekly threads in the guild.
+    ///
+    /// # Returns
+    /// A Result containing whether a forum channel was set, or an `Error`.
+    pub async fn remove_forum_channel(&self) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remove_forum_channel");
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM forum_channels
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves the forum channel the guild's weekly threads are opened in.
+    ///
+    /// # Returns
+    /// A Result containing the ID of the channel, `None` if weekly threads are off, or an `Error`.
+    pub async fn get_forum_channel(&self) -> Result<Option<String>, Error> {
+        let _timer = QueryTimer::start("get_forum_channel");
+
+        let channel_id = sqlx::query_scalar!(
+            r#"
+            SELECT channel_id
+            FROM forum_channels
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .fetch_optional(&*self.db.pool)
+        .await?;
+
+        Ok(channel_id)
+    }
+
+    /// Retrieves the thread opened for a week.
+    ///
+    /// # Arguments
+    /// * `week_start` - The Monday of the week.
+    ///
+    /// # Returns
+    /// A Result containing the ID of the thread, `None` if none was opened, or an `Error`.
+    pub async fn get_forum_thread(&self, week_start: NaiveDate) -> Result<Option<String>, Error> {
+        let _timer = QueryTimer::start("get_forum_thread");
+
+        let thread_id = sqlx::query_scalar!(
+            r#"
+            SELECT thread_id
+            FROM forum_threads
+            WHERE guild_id = $1 AND week_start = $2
+            "#,
+            self.guild_id,
+            week_start
+        )
+        .fetch_optional(&*self.db.pool)
+        .await?;
+
+        Ok(thread_id)
+    }
+
+    /// Records the thread opened for a week.
+    ///
+    /// # Arguments
+    /// * `week_start` - The Monday of the week.
+    /// * `thread_id` - The ID of the thread.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn record_forum_thread(
+        &self,
+        week_start: NaiveDate,
+        thread_id: &str,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("record_forum_thread");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO forum_threads (guild_id, week_start, thread_id)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (guild_id, week_start) DO NOTHING
+            "#,
+            self.guild_id,
+            week_start,
+            thread_id
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the threads of earlier weeks that were not archived yet.
+    ///
+    /// # Arguments
+    /// * `week_start` - The Monday of the current week.
+    ///
+    /// # Returns
+    /// A Result containing the threads ordered by week, or an `Error`.
+    pub async fn get_open_forum_threads_before(
+        &self,
+        week_start: NaiveDate,
+    ) -> Result<Vec<ForumThread>, Error> {
+        let _timer = QueryTimer::start("get_open_forum_threads_before");
+
+        let threads = sqlx::query_as!(
+            ForumThread,
+            r#"
+            SELECT week_start, thread_id
+            FROM forum_threads
+            WHERE guild_id = $1 AND week_start < $2 AND archived_at IS NULL
+            ORDER BY week_start
+            "#,
+            self.guild_id,
+            week_start
+        )
+        .fetch_all(&*self.db.pool)
+        .await?;
+
+        Ok(threads)
+    }
+
+    /// Marks the thread of a week as archived.
+    ///
+    /// # Arguments
+    /// * `week_start` - The Monday of the week.
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn mark_forum_thread_archived(
+        &self,
+        week_start: NaiveDate,
+        now: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("mark_forum_thread_archived");
+
+        sqlx::query!(
+            r#"
+            UPDATE forum_threads
+            SET archived_at = $3
+            WHERE guild_id = $1 AND week_start = $2
+            "#,
+            self.guild_id,
+            week_start,
+            now
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Opts the guild in to the weekly challenge, or changes its target or channel.
+    ///
+    /// # Arguments
+    /// * `channel_id` - The ID of the channel progress is posted in.
+    /// * `target_percent` - The reduction against the week before aimed for, in percent.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_challenge(&self, channel_id: &str, target_percent: i32) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_challenge");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO guild_challenges (guild_id, channel_id, target_percent)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (guild_id) DO UPDATE
+            SET channel_id = EXCLUDED.channel_id, target_percent = EXCLUDED.target_percent
+            "#,
+            self.guild_id,
+            channel_id,
+            target_percent
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Opts the guild out of the weekly challenge; past weeks are kept.
+    ///
+    /// # Returns
+    /// A Result containing whether the guild took part, or an `Error`.
+    pub async fn remove_challenge(&self) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remove_challenge");
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM guild_challenges
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves the guild's weekly challenge.
+    ///
+    /// # Returns
+    /// A Result containing the challenge, `None` if the guild does not take part, or an `Error`.
+    pub async fn get_challenge(&self) -> Result<Option<GuildChallenge>, Error> {
+        let _timer = QueryTimer::start("get_challenge");
+
+        let challenge = sqlx::query_as!(
+            GuildChallenge,
+            r#"
+            SELECT guild_id, channel_id, target_percent
+            FROM guild_challenges
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .fetch_optional(&*self.db.pool)
+        .await?;
+
+        Ok(challenge)
+    }
+
+    /// Retrieves the challenge of a week.
+    ///
+    /// # Arguments
+    /// * `week_start` - The Monday of the week.
+    ///
+    /// # Returns
+    /// A Result containing the week, `None` if no progress was posted for it, or an `Error`.
+    pub async fn get_challenge_week(
+        &self,
+        week_start: NaiveDate,
+    ) -> Result<Option<ChallengeWeek>, Error> {
+        let _timer = QueryTimer::start("get_challenge_week");
+
+        let week = sqlx::query_as!(
+            ChallengeWeek,
+            r#"
+            SELECT week_start, channel_id, message_id, baseline, target_percent, updated_on
+            FROM guild_challenge_weeks
+            WHERE guild_id = $1 AND week_start = $2
+            "#,
+            self.guild_id,
+            week_start
+        )
+        .fetch_optional(&*self.db.pool)
+        .await?;
+
+        Ok(week)
+    }
+
+    /// Records the progress message posted for a challenge week.
+    ///
+    /// # Arguments
+    /// * `week` - The week, with its baseline and target.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn record_challenge_week(&self, week: &ChallengeWeek) -> Result<(), Error> {
+        let _timer = QueryTimer::start("record_challenge_week");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO guild_challenge_weeks
+                (guild_id, week_start, channel_id, message_id, baseline, target_percent, updated_on)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (guild_id, week_start) DO NOTHING
+            "#,
+            self.guild_id,
+            week.week_start,
+            week.channel_id,
+            week.message_id,
+            week.baseline,
+            week.target_percent,
+            week.updated_on
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Records the day a challenge week's progress message was last updated.
+    ///
+    /// # Arguments
+    /// * `week_start` - The Monday of the week.
+    /// * `date` - The local date of the update.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn mark_challenge_week_updated(
+        &self,
+        week_start: NaiveDate,
+        date: NaiveDate,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("mark_challenge_week_updated");
+
+        sqlx::query!(
+            r#"
+            UPDATE guild_challenge_weeks
+            SET updated_on = $3
+            WHERE guild_id = $1 AND week_start = $2
+            "#,
+            self.guild_id,
+            week_start,
+            date
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the challenge weeks before a week that were not settled yet.
+    ///
+    /// # Arguments
+    /// * `week_start` - The Monday of the current week.
+    ///
+    /// # Returns
+    /// A Result containing the weeks ordered by week, or an `Error`.
+    pub async fn get_unsettled_challenge_weeks_before(
+        &self,
+        week_start: NaiveDate,
+    ) -> Result<Vec<ChallengeWeek>, Error> {
+        let _timer = QueryTimer::start("get_unsettled_challenge_weeks_before");
+
+        let weeks = sqlx::query_as!(
+            ChallengeWeek,
+            r#"
+            SELECT week_start, channel_id, message_id, baseline, target_percent, updated_on
+            FROM guild_challenge_weeks
+            WHERE guild_id = $1 AND week_start < $2 AND settled_at IS NULL
+            ORDER BY week_start
+            "#,
+            self.guild_id,
+            week_start
+        )
+        .fetch_all(&*self.db.pool)
+        .await?;
+
+        Ok(weeks)
+    }
+
+    /// Records the outcome of a finished challenge week.
+    ///
+    /// # Arguments
+    /// * `week_start` - The Monday of the week.
+    /// * `achieved` - Whether the guild reached the target.
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn settle_challenge_week(
+        &self,
+        week_start: NaiveDate,
+        achieved: bool,
+        now: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("settle_challenge_week");
+
+        sqlx::query!(
+            r#"
+            UPDATE guild_challenge_weeks
+            SET settled_at = $3, achieved = $4
+            WHERE guild_id = $1 AND week_start = $2
+            "#,
+            self.guild_id,
+            week_start,
+            now,
+            achieved
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Starts leaderboard seasons in the guild, or changes their settings.
+    ///
+    /// # Arguments
+    /// * `channel_id` - The channel season results are announced in.
+    /// * `season_length` - The length of the seasons (`month` or `quarter`).
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_leaderboard(
+        &self,
+        channel_id: &str,
+        season_length: &str,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_leaderboard");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO leaderboard_settings (guild_id, channel_id, season_length)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (guild_id) DO UPDATE
+            SET channel_id = EXCLUDED.channel_id, season_length = EXCLUDED.season_length
+            "#,
+            self.guild_id,
+            channel_id,
+            season_length
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Stops leaderboard seasons in the guild; archived seasons are kept.
+    ///
+    /// # Returns
+    /// A Result containing whether seasons were running, or an `Error`.
+    pub async fn remove_leaderboard(&self) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remove_leaderboard");
+
+        let mut tx = self.db.pool.begin().await?;
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM leaderboard_settings
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        // The running season ends unarchived; a restart opens a new one.
+        sqlx::query!(
+            r#"
+            DELETE FROM leaderboard_seasons
+            WHERE guild_id = $1 AND archived_at IS NULL
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves the guild's leaderboard settings.
+    ///
+    /// # Returns
+    /// A Result containing the settings, `None` if the guild runs no seasons, or an `Error`.
+    pub async fn get_leaderboard(&self) -> Result<Option<LeaderboardSettings>, Error> {
+        let _timer = QueryTimer::start("get_leaderboard");
+
+        let settings = sqlx::query_as!(
+            LeaderboardSettings,
+            r#"
+            SELECT guild_id, channel_id, season_length
+            FROM leaderboard_settings
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .fetch_optional(&*self.db.pool)
+        .await?;
+
+        Ok(settings)
+    }
+
+    /// Opens the guild's next season.
+    ///
+    /// # Arguments
+    /// * `starts_on` - The first day of the season.
+    /// * `ends_on` - The first day after the season.
+    ///
+    /// # Returns
+    /// A Result containing the season, numbered after the guild's last one, or an `Error`.
+    pub async fn open_season(
+        &self,
+        starts_on: NaiveDate,
+        ends_on: NaiveDate,
+    ) -> Result<LeaderboardSeason, Error> {
+        let _timer = QueryTimer::start("open_season");
+
+        let season = sqlx::query_as!(
+            LeaderboardSeason,
+            r#"
+            INSERT INTO leaderboard_seasons (guild_id, number, starts_on, ends_on)
+            SELECT $1::varchar, COALESCE(MAX(number), 0) + 1, $2, $3
+            FROM leaderboard_seasons
+            WHERE guild_id = $1
+            RETURNING id, number, starts_on, ends_on, archived_at
+            "#,
+            self.guild_id,
+            starts_on,
+            ends_on
+        )
+        .fetch_one(&*self.db.pool)
+        .await?;
+
+        Ok(season)
+    }
+
+    /// Retrieves the guild's running season.
+    ///
+    /// # Returns
+    /// A Result containing the season, `None` if none is running, or an `Error`.
+    pub async fn get_open_season(&self) -> Result<Option<LeaderboardSeason>, Error> {
+        let _timer = QueryTimer::start("get_open_season");
+
+        let season = sqlx::query_as!(
+            LeaderboardSeason,
+            r#"
+            SELECT id, number, starts_on, ends_on, archived_at
+            FROM leaderboard_seasons
+            WHERE guild_id = $1 AND archived_at IS NULL
+            ORDER BY number DESC
+            LIMIT 1
+            "#,
+            self.guild_id
+        )
+        .fetch_optional(&*self.db.pool)
+        .await?;
+
+        Ok(season)
+    }
+
+    /// Retrieves one of the guild's seasons by number.
+    ///
+    /// # Arguments
+    /// * `number` - The number of the season within the guild.
+    ///
+    /// # Returns
+    /// A Result containing the season, `None` if the guild has no such season, or an `Error`.
+    pub async fn get_season(&self, number: i32) -> Result<Option<LeaderboardSeason>, Error> {
+        let _timer = QueryTimer::start("get_season");
+
+        let season = sqlx::query_as!(
+            LeaderboardSeason,
+            r#"
+            SELECT id, number, starts_on, ends_on, archived_at
+            FROM leaderboard_seasons
+            WHERE guild_id = $1 AND number = $2
+            "#,
+            self.guild_id,
+            number
+        )
+        .fetch_optional(&*self.db.pool)
+        .await?;
+
+        Ok(season)
+    }
+
+    /// Retrieves the guild's archived seasons.
+    ///
+    /// # Returns
+    /// A Result containing the seasons, most recent first, or an `Error`.
+    pub async fn get_archived_seasons(&self) -> Result<Vec<LeaderboardSeason>, Error> {
+        let _timer = QueryTimer::start("get_archived_seasons");
+
+        let seasons = sqlx::query_as!(
+            LeaderboardSeason,
+            r#"
+            SELECT id, number, starts_on, ends_on, archived_at
+            FROM leaderboard_seasons
+            WHERE guild_id = $1 AND archived_at IS NOT NULL
+            ORDER BY number DESC
+            "#,
+            self.guild_id
+        )
+        .fetch_all(&*self.db.pool)
+        .await?;
+
+        Ok(seasons)
+    }
+
+    /// Archives a season's final standings, all or none.
+    ///
+    /// # Arguments
+    /// * `season_id` - The ID of the season.
+    /// * `standings` - The final standings.
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    /// A Result containing whether the season was archived (`false` if it already was), or an `Error`.
+    pub async fn archive_season(
+        &self,
+        season_id: i32,
+        standings: &[Standing],
+        now: DateTime<Utc>,
+    ) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("archive_season");
+
+        let discord_ids: Vec<String> = standings
+            .iter()
+            .map(|standing| standing.discord_id.clone())
+            .collect();
+        let ranks: Vec<i32> = standings.iter().map(|standing| standing.rank).collect();
+        let totals: Vec<i64> = standings.iter().map(|standing| standing.total).collect();
+
+        let mut tx = self.db.pool.begin().await?;
+        let result = sqlx::query!(
+            r#"
+            UPDATE leaderboard_seasons
+            SET archived_at = $3
+            WHERE guild_id = $1 AND id = $2 AND archived_at IS NULL
+            "#,
+            self.guild_id,
+            season_id,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        sqlx::query!(
+            r#"
+            INSERT INTO season_standings (season_id, guild_id, discord_id, rank, total)
+            SELECT $2, $1, standing.discord_id, standing.rank, standing.total
+            FROM UNNEST($3::varchar[], $4::int[], $5::bigint[])
+                AS standing(discord_id, rank, total)
+            "#,
+            self.guild_id,
+            season_id,
+            &discord_ids,
+            &ranks,
+            &totals
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Retrieves the archived standings of a season.
+    ///
+    /// # Arguments
+    /// * `season_id` - The ID of the season.
+    ///
+    /// # Returns
+    /// A Result containing the standings ordered by rank, or an `Error`.
+    pub async fn get_season_standings(&self, season_id: i32) -> Result<Vec<Standing>, Error> {
+        let _timer = QueryTimer::start("get_season_standings");
+
+        let standings = sqlx::query_as!(
+            Standing,
+            r#"
+            SELECT discord_id, rank, total
+            FROM season_standings
+            WHERE guild_id = $1 AND season_id = $2
+            ORDER BY rank, discord_id
+            "#,
+            self.guild_id,
+            season_id
+        )
+        .fetch_all(&*self.db.pool)
+        .await?;
+
+        Ok(standings)
+    }
+
+    /// Totals the logs of every member who logged in the guild over a period.
+    ///
+    /// # Arguments
+    /// * `start` - The start of the period (inclusive).
+    /// * `end` - The end of the period (exclusive).
+    ///
+    /// # Returns
+    /// A Result containing the totals, zero for members who logged nothing in the
+    /// period, ordered from the fewest units, or an `Error`.
+    pub async fn get_member_totals(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<MemberTotal>, Error> {
+        let _timer = QueryTimer::start("get_member_totals");
+
+        let totals = sqlx::query_as!(
+            MemberTotal,
+            r#"
+            SELECT
+                discord_id as "discord_id!",
+                COALESCE(SUM(quantity) FILTER (WHERE smoked_at >= $2 AND smoked_at < $3), 0)
+                    as "total!"
+            FROM smoking_logs
+            WHERE guild_id = $1
+            GROUP BY discord_id
+            ORDER BY 2, 1
+            "#,
+            self.guild_id,
+            start,
+            end
+        )
+        .fetch_all(&*self.db.pool)
+        .await?;
+
+        Ok(totals)
+    }
+
+    /// Totals each member's logs in the guild over two consecutive periods.
+    ///
+    /// # Arguments
+    /// * `previous_start` - The start of the earlier period (inclusive).
+    /// * `current_start` - The end of the earlier period and start of the later one.
+    /// * `current_end` - The end of the later period (exclusive).
+    ///
+    /// # Returns
+    /// A Result containing the totals of the members who logged in either
+    /// period, ordered by member, or an `Error`.
+    pub async fn get_member_period_totals(
+        &self,
+        previous_start: DateTime<Utc>,
+        current_start: DateTime<Utc>,
+        current_end: DateTime<Utc>,
+    ) -> Result<Vec<MemberPeriodTotals>, Error> {
+        let _timer = QueryTimer::start("get_member_period_totals");
+
+        let totals = sqlx::query_as!(
+            MemberPeriodTotals,
+            r#"
+            SELECT
+                discord_id as "discord_id!",
+                COALESCE(SUM(quantity) FILTER (WHERE smoked_at < $3), 0) as "previous!",
+                COALESCE(SUM(quantity) FILTER (WHERE smoked_at >= $3), 0) as "current!"
+            FROM smoking_logs
+            WHERE guild_id = $1
+            AND smoked_at >= $2
+            AND smoked_at < $4
+            GROUP BY discord_id
+            ORDER BY discord_id
+            "#,
+            self.guild_id,
+            previous_start,
+            current_start,
+            current_end
+        )
+        .fetch_all(&*self.db.pool)
+        .await?;
+
+        Ok(totals)
+    }
+
+    /// Adds a member to the guild's smoke-free board.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the member.
+    ///
+    /// # Returns
+    /// A Result containing whether the member was newly added, or an `Error`.
+    pub async fn join_smoke_free_board(&self, discord_id: &str) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("join_smoke_free_board");
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO smoke_free_board_members (guild_id, discord_id)
+            VALUES ($1, $2)
+            ON CONFLICT (guild_id, discord_id) DO NOTHING
+            "#,
+            self.guild_id,
+            discord_id
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Removes a member from the guild's smoke-free board.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the member.
+    ///
+    /// # Returns
+    /// A Result containing whether the member was on the board, or an `Error`.
+    pub async fn leave_smoke_free_board(&self, discord_id: &str) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("leave_smoke_free_board");
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM smoke_free_board_members
+            WHERE guild_id = $1 AND discord_id = $2
+            "#,
+            self.guild_id,
+            discord_id
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves the members of the guild's smoke-free board with their last log.
+    ///
+    /// The last log counts every log of the member, in any guild or DM, since
+    /// the streak is personal.
+    ///
+    /// # Returns
+    /// A Result containing the members ordered by ID, or an `Error`.
+    pub async fn get_smoke_free_board(&self) -> Result<Vec<SmokeFreeMember>, Error> {
+        let _timer = QueryTimer::start("get_smoke_free_board");
+
+        let members = sqlx::query_as!(
+            SmokeFreeMember,
+            r#"
+            SELECT
+                m.discord_id,
+                (
+                    SELECT MAX(sl.smoked_at)
+                    FROM smoking_logs sl
+                    WHERE sl.discord_id = m.discord_id
+                ) as last_smoked_at
+            FROM smoke_free_board_members m
+            WHERE m.guild_id = $1
+            ORDER BY m.discord_id
+            "#,
+            self.guild_id
+        )
+        .fetch_all(&*self.db.pool)
+        .await?;
+
+        Ok(members)
+    }
+
+    /// Shows a counter in a channel of the guild, replacing any earlier counter.
+    ///
+    /// # Arguments
+    /// * `channel_id` - The channel showing the counter.
+    /// * `metric` - What the counter shows (`daily-total` or `days-since`).
+    /// * `target` - What is edited (`topic`, or `name` for voice channels).
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_channel_counter(
+        &self,
+        channel_id: &str,
+        metric: &str,
+        target: &str,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_channel_counter");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO channel_counters (guild_id, channel_id, metric, target)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (guild_id) DO UPDATE
+            SET channel_id = EXCLUDED.channel_id,
+                metric = EXCLUDED.metric,
+                target = EXCLUDED.target,
+                last_text = NULL,
+                updated_at = NULL
+            "#,
+            self.guild_id,
+            channel_id,
+            metric,
+            target
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Stops the guild's channel counter; the channel keeps its last text.
+    ///
+    /// # Returns
+    /// A Result containing whether a counter was shown, or an `Error`.
+    pub async fn remove_channel_counter(&self) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remove_channel_counter");
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM channel_counters
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves the guild's channel counter.
+    ///
+    /// # Returns
+    /// A Result containing the counter, `None` if the guild shows none, or an `Error`.
+    pub async fn get_channel_counter(&self) -> Result<Option<ChannelCounter>, Error> {
+        let _timer = QueryTimer::start("get_channel_counter");
+
+        let counter = sqlx::query_as!(
+            ChannelCounter,
+            r#"
+            SELECT guild_id, channel_id, metric, target, last_text, updated_at
+            FROM channel_counters
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .fetch_optional(&*self.db.pool)
+        .await?;
+
+        Ok(counter)
+    }
+
+    /// Records the text last written to the counter's channel.
+    ///
+    /// # Arguments
+    /// * `text` - The text written.
+    /// * `now` - When the channel was edited.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn record_channel_counter_update(
+        &self,
+        text: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("record_channel_counter_update");
+
+        sqlx::query!(
+            r#"
+            UPDATE channel_counters
+            SET last_text = $2, updated_at = $3
+            WHERE guild_id = $1
+            "#,
+            self.guild_id,
+            text,
+            now
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves when anyone last logged in the guild.
+    ///
+    /// # Returns
+    /// A Result containing the time of the guild's latest log, `None` if there is none, or an `Error`.
+    pub async fn get_last_smoked_at(&self) -> Result<Option<DateTime<Utc>>, Error> {
+        let _timer = QueryTimer::start("get_guild_last_smoked_at");
+
+        let smoked_at = sqlx::query_scalar!(
+            r#"
+            SELECT MAX(smoked_at)
+            FROM smoking_logs
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .fetch_one(&*self.db.pool)
+        .await?;
+
+        Ok(smoked_at)
+    }
+
+    /// Deletes the bot's panel confirmations in the guild once they are older than a number of hours.
+    ///
+    /// # Arguments
+    /// * `max_age_hours` - How long confirmations stay, in hours.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_confirmation_cleanup(&self, max_age_hours: i32) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_confirmation_cleanup");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO confirmation_cleanup_settings (guild_id, max_age_hours)
+            VALUES ($1, $2)
+            ON CONFLICT (guild_id) DO UPDATE
+            SET max_age_hours = EXCLUDED.max_age_hours
+            "#,
+            self.guild_id,
+            max_age_hours
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Stops deleting the guild's panel confirmations; those sent so far are kept.
+    ///
+    /// # Returns
+    /// A Result containing whether cleanup was enabled, or an `Error`.
+    pub async fn remove_confirmation_cleanup(&self) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remove_confirmation_cleanup");
+
+        let mut tx = self.db.pool.begin().await?;
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM confirmation_cleanup_settings
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM confirmation_messages
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves how long the guild's panel confirmations stay.
+    ///
+    /// # Returns
+    /// A Result containing the age in hours, `None` if confirmations are kept, or an `Error`.
+    pub async fn get_confirmation_cleanup(&self) -> Result<Option<i32>, Error> {
+        let _timer = QueryTimer::start("get_confirmation_cleanup");
+
+        let max_age_hours = sqlx::query_scalar!(
+            r#"
+            SELECT max_age_hours
+            FROM confirmation_cleanup_settings
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .fetch_optional(&*self.db.pool)
+        .await?;
+
+        Ok(max_age_hours)
+    }
+
+    /// Remembers a panel confirmation so it can be deleted later; does nothing
+    /// unless the guild cleans up its confirmations.
+    ///
+    /// # Arguments
+    /// * `channel_id` - The channel the confirmation was sent in.
+    /// * `message_id` - The confirmation message.
+    /// * `sent_at` - When the confirmation was sent.
+    ///
+    /// # Returns
+    /// A Result containing whether the confirmation was remembered, or an `Error`.
+    pub async fn remember_confirmation(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        sent_at: DateTime<Utc>,
+    ) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remember_confirmation");
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO confirmation_messages (message_id, guild_id, channel_id, sent_at)
+            SELECT $2, guild_id, $3, $4
+            FROM confirmation_cleanup_settings
+            WHERE guild_id = $1
+            ON CONFLICT (message_id) DO NOTHING
+            "#,
+            self.guild_id,
+            message_id,
+            channel_id,
+            sent_at
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Marks the guild as removed, pausing its background work.
+    ///
+    /// # Arguments
+    /// * `removed_at` - When the bot was removed from the guild.
+    /// * `delete_after` - When the guild's data is deleted, or `None` to keep it.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn mark_removed(
+        &self,
+        removed_at: DateTime<Utc>,
+        delete_after: Option<DateTime<Utc>>,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("mark_removed");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO guild_removals (guild_id, removed_at, delete_after)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (guild_id)
+            DO UPDATE SET removed_at = $2, delete_after = $3
+            "#,
+            self.guild_id,
+            removed_at,
+            delete_after
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Clears the guild's removal, resuming its background work and cancelling the deletion of its data.
+    ///
+    /// # Returns
+    /// A Result containing whether the guild was marked as removed, or an `Error`.
+    pub async fn unmark_removed(&self) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("unmark_removed");
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM guild_removals
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves the guild's removal.
+    ///
+    /// # Returns
+    /// A Result containing the removal, `None` if the bot is in the guild, or an `Error`.
+    pub async fn get_removal(&self) -> Result<Option<GuildRemoval>, Error> {
+        let _timer = QueryTimer::start("get_removal");
+
+        let removal = sqlx::query_as!(
+            GuildRemoval,
+            r#"
+            SELECT guild_id, removed_at, delete_after
+            FROM guild_removals
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .fetch_optional(&*self.db.pool)
+        .await?;
+
+        Ok(removal)
+    }
+
+    /// Deletes the guild's settings, panels and other data once its removal is due for deletion.
+    ///
+    /// Members' logs are kept but no longer count towards the guild.
+    ///
+    /// # Arguments
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    /// A Result containing whether the data was deleted, `false` if the guild
+    /// is not removed or not due yet, or an `Error`.
+    pub async fn delete_removed_data(&self, now: DateTime<Utc>) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("delete_removed_data");
+
+        let mut tx = self.db.pool.begin().await?;
+        let due = sqlx::query!(
+            r#"
+            DELETE FROM guild_removals
+            WHERE guild_id = $1 AND delete_after <= $2
+            "#,
+            self.guild_id,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        if due.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+            DELETE FROM guild_settings WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM panels WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM milestone_roles WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM message_templates WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM smoke_break_channels WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM guild_acknowledgment_policies WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM guild_acknowledgments WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM guild_type_caps WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM guild_confirmation_reactions WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM forum_threads WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM forum_channels WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM guild_challenge_weeks WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM guild_challenges WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        // Earned achievements and season standings go with their achievements and seasons.
+        sqlx::query!(
+            r#"
+            DELETE FROM guild_achievements WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM leaderboard_seasons WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM leaderboard_settings WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM smoke_free_board_members WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM channel_counters WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM confirmation_cleanup_settings WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM confirmation_messages WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            UPDATE smoking_logs SET guild_id = NULL WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Requires members to acknowledge a message before using the panel.
+    ///
+    /// Earlier acknowledgments are discarded, so members acknowledge the new message.
+    ///
+    /// # Arguments
+    /// * `message` - The message members acknowledge.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_acknowledgment_policy(&self, message: &str) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_acknowledgment_policy");
+
+        let mut tx = self.db.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO guild_acknowledgment_policies (guild_id, message)
+            VALUES ($1, $2)
+            ON CONFLICT (guild_id) DO UPDATE
+            SET message = EXCLUDED.message, updated_at = CURRENT_TIMESTAMP
+            "#,
+            self.guild_id,
+            message
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM guild_acknowledgments
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Stops requiring an acknowledgment and discards the recorded ones.
+    ///
+    /// # Returns
+    /// A Result containing whether an acknowledgment was required, or an `Error`.
+    pub async fn remove_acknowledgment_policy(&self) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remove_acknowledgment_policy");
+
+        let mut tx = self.db.pool.begin().await?;
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM guild_acknowledgment_policies
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM guild_acknowledgments
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves the message members must acknowledge.
+    ///
+    /// # Returns
+    /// A Result containing the message, `None` if no acknowledgment is required, or an `Error`.
+    pub async fn get_acknowledgment_policy(&self) -> Result<Option<String>, Error> {
+        let _timer = QueryTimer::start("get_acknowledgment_policy");
+
+        let message = sqlx::query_scalar!(
+            r#"
+            SELECT message
+            FROM guild_acknowledgment_policies
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .fetch_optional(&*self.db.pool)
+        .await?;
+
+        Ok(message)
+    }
+
+    /// Records that a member acknowledged the guild's message.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the member.
+    /// * `acknowledged_at` - When the message was acknowledged.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn acknowledge(
+        &self,
+        discord_id: &str,
+        acknowledged_at: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let _timer = QueryTimer::start("acknowledge");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO guild_acknowledgments (guild_id, discord_id, acknowledged_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (guild_id, discord_id) DO UPDATE
+            SET acknowledged_at = EXCLUDED.acknowledged_at
+            "#,
+            self.guild_id,
+            discord_id,
+            acknowledged_at
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Checks whether a member acknowledged the guild's current message.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the member.
+    ///
+    /// # Returns
+    /// A Result containing whether the member acknowledged it, or an `Error`.
+    pub async fn has_acknowledged(&self, discord_id: &str) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("has_acknowledged");
+
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM guild_acknowledgments
+                WHERE guild_id = $1 AND discord_id = $2
+            ) as "exists!"
+            "#,
+            self.guild_id,
+            discord_id
+        )
+        .fetch_one(&*self.db.pool)
+        .await?;
+
+        Ok(exists)
+    }
+
+    /// Sets the daily cap of a smoking type, replacing any existing cap.
+    ///
+    /// # Arguments
+    /// * `smoking_type_id` - The ID of the smoking type.
+    /// * `daily_cap` - The most units a member may log per day.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_type_cap(&self, smoking_type_id: i32, daily_cap: i32) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_type_cap");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO guild_type_caps (guild_id, smoking_type_id, daily_cap)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (guild_id, smoking_type_id) DO UPDATE
+            SET daily_cap = EXCLUDED.daily_cap, updated_at = CURRENT_TIMESTAMP
+            "#,
+            self.guild_id,
+            smoking_type_id,
+            daily_cap
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes the daily cap of a smoking type.
+    ///
+    /// # Arguments
+    /// * `smoking_type_id` - The ID of the smoking type.
+    ///
+    /// # Returns
+    /// A Result containing whether a cap was removed, or an `Error`.
+    pub async fn remove_type_cap(&self, smoking_type_id: i32) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remove_type_cap");
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM guild_type_caps
+            WHERE guild_id = $1 AND smoking_type_id = $2
+            "#,
+            self.guild_id,
+            smoking_type_id
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves every daily cap of the guild.
+    ///
+    /// # Returns
+    /// A Result containing the caps ordered by smoking type, or an `Error`.
+    pub async fn get_type_caps(&self) -> Result<Vec<TypeCap>, Error> {
+        let _timer = QueryTimer::start("get_type_caps");
+
+        let caps = sqlx::query_as!(
+            TypeCap,
+            r#"
+            SELECT smoking_type_id, daily_cap
+            FROM guild_type_caps
+            WHERE guild_id = $1
+            ORDER BY smoking_type_id
+            "#,
+            self.guild_id
+        )
+        .fetch_all(&*self.db.pool)
+        .await?;
+
+        Ok(caps)
+    }
+
+    /// Retrieves the daily cap of a smoking type.
+    ///
+    /// # Arguments
+    /// * `smoking_type_id` - The ID of the smoking type.
+    ///
+    /// # Returns
+    /// A Result containing the cap, `None` if the type is not capped, or an `Error`.
+    pub async fn get_type_cap(&self, smoking_type_id: i32) -> Result<Option<i32>, Error> {
+        let _timer = QueryTimer::start("get_type_cap");
+
+        let cap = sqlx::query_scalar!(
+            r#"
+            SELECT daily_cap
+            FROM guild_type_caps
+            WHERE guild_id = $1 AND smoking_type_id = $2
+            "#,
+            self.guild_id,
+            smoking_type_id
+        )
+        .fetch_optional(&*self.db.pool)
+        .await?;
+
+        Ok(cap)
+    }
+
+    /// Totals the guild's logs per smoking type over a period.
+    ///
+    /// # Arguments
+    /// * `start` - The start of the period (inclusive).
+    /// * `end` - The end of the period (exclusive).
+    ///
+    /// # Returns
+    /// A Result containing the totals of the logged types ordered by type, or an `Error`.
+    pub async fn get_type_totals(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<GuildTypeTotal>, Error> {
+        let _timer = QueryTimer::start("get_type_totals");
+
+        let totals = sqlx::query_as!(
+            GuildTypeTotal,
+            r#"
+            SELECT
+                st.type_name as "type_name!",
+                st.description,
+                SUM(sl.quantity) as "total_quantity!",
+                COUNT(DISTINCT sl.discord_id) as "member_count!"
+            FROM smoking_logs sl
+            JOIN smoking_types st ON sl.smoking_type_id = st.id
+            WHERE sl.guild_id = $1
+            AND sl.smoked_at >= $2
+            AND sl.smoked_at < $3
+            GROUP BY st.id, st.type_name, st.description
+            ORDER BY st.id
+            "#,
+            self.guild_id,
+            start,
+            end
+        )
+        .fetch_all(&*self.db.pool)
+        .await?;
+
+        Ok(totals)
+    }
+
+    /// Totals the guild's logs per month and smoking type over its whole history.
+    ///
+    /// # Arguments
+    /// * `timezone` - The time zone months are counted in.
+    ///
+    /// # Returns
+    /// A Result containing the totals ordered by month and type, or an `Error`.
+    pub async fn get_monthly_type_totals(
+        &self,
+        timezone: Tz,
+    ) -> Result<Vec<GuildMonthlyTypeTotal>, Error> {
+        let _timer = QueryTimer::start("get_monthly_type_totals");
+
+        let totals = sqlx::query_as!(
+            GuildMonthlyTypeTotal,
+            r#"
+            SELECT
+                DATE_TRUNC('month', sl.smoked_at AT TIME ZONE $2)::date as "month!",
+                st.type_name as "type_name!",
+                SUM(sl.quantity) as "total_quantity!",
+                COUNT(DISTINCT sl.discord_id) as "member_count!"
+            FROM smoking_logs sl
+            JOIN smoking_types st ON sl.smoking_type_id = st.id
+            WHERE sl.guild_id = $1
+            GROUP BY 1, st.id, st.type_name
+            ORDER BY 1, st.id
+            "#,
+            self.guild_id,
+            timezone.name()
+        )
+        .fetch_all(&*self.db.pool)
+        .await?;
+
+        Ok(totals)
+    }
+
+    /// Retrieves the guild's logs of the members who share them in guild data exports.
+    ///
+    /// # Returns
+    /// A Result containing the logs ordered by member and time, or an `Error`.
+    pub async fn get_shared_logs(&self) -> Result<Vec<SharedGuildLog>, Error> {
+        let _timer = QueryTimer::start("get_shared_guild_logs");
+
+        let logs = sqlx::query_as!(
+            SharedGuildLog,
+            r#"
+            SELECT
+                u.discord_id as "discord_id!",
+                u.username as "username!",
+                st.type_name as "type_name!",
+                sl.quantity as "quantity!",
+                sl.smoked_at as "smoked_at!"
+            FROM smoking_logs sl
+            JOIN users u ON sl.discord_id = u.discord_id
+            JOIN smoking_types st ON sl.smoking_type_id = st.id
+            WHERE sl.guild_id = $1
+            AND u.share_logs_in_guild_exports
+            ORDER BY u.discord_id, sl.smoked_at, sl.id
+            "#,
+            self.guild_id
+        )
+        .fetch_all(&*self.db.pool)
+        .await?;
+
+        Ok(logs)
+    }
+
+    /// Sets the emoji the bot reacts with instead of sending a confirmation message.
+    ///
+    /// # Arguments
+    /// * `emoji` - The emoji in mention form (e.g. `<:name:123>`).
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_confirmation_reaction(&self, emoji: &str) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_confirmation_reaction");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO guild_confirmation_reactions (guild_id, emoji)
+            VALUES ($1, $2)
+            ON CONFLICT (guild_id) DO UPDATE
+            SET emoji = EXCLUDED.emoji, updated_at = CURRENT_TIMESTAMP
+            "#,
+            self.guild_id,
+            emoji
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Goes back to confirming logs with a message.
+    ///
+    /// # Returns
+    /// A Result containing whether a reaction was configured, or an `Error`.
+    pub async fn remove_confirmation_reaction(&self) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("remove_confirmation_reaction");
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM guild_confirmation_reactions
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves the emoji the bot reacts with instead of sending a confirmation message.
+    ///
+    /// # Returns
+    /// A Result containing the emoji, `None` if logs are confirmed with a message, or an `Error`.
+    pub async fn get_confirmation_reaction(&self) -> Result<Option<String>, Error> {
+        let _timer = QueryTimer::start("get_confirmation_reaction");
+
+        let emoji = sqlx::query_scalar!(
+            r#"
+            SELECT emoji
+            FROM guild_confirmation_reactions
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .fetch_optional(&*self.db.pool)
+        .await?;
+
+        Ok(emoji)
+    }
+
+    /// Retrieves the guild's settings.
+    ///
+    /// # Returns
+    /// A Result containing the settings (all unset if the guild has none), or an `Error`.
+    pub async fn get_settings(&self) -> Result<GuildSettings, Error> {
+        let _timer = QueryTimer::start("get_guild_settings");
+
+        let settings = sqlx::query_as!(
+            GuildSettings,
+            r#"
+            SELECT timezone, panel_channel_id, panel_smoking_type_ids, onboarded_at
+            FROM guild_settings
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .fetch_optional(&*self.db.pool)
+        .await?;
+
+        Ok(settings.unwrap_or_default())
+    }
+
+    /// Sets the channel the onboarding wizard installs the panel in.
+    ///
+    /// # Arguments
+    /// * `channel_id` - The ID of the channel.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_panel_channel(&self, channel_id: &str) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_panel_channel");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO guild_settings (guild_id, panel_channel_id)
+            VALUES ($1, $2)
+            ON CONFLICT (guild_id) DO UPDATE
+            SET panel_channel_id = EXCLUDED.panel_channel_id, updated_at = CURRENT_TIMESTAMP
+            "#,
+            self.guild_id,
+            channel_id
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Sets the smoking types panels installed without a type filter show.
+    ///
+    /// # Arguments
+    /// * `smoking_type_ids` - The IDs of the types, or `None` for all types.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_panel_types(&self, smoking_type_ids: Option<&[i32]>) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_panel_types");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO guild_settings (guild_id, panel_smoking_type_ids)
+            VALUES ($1, $2)
+            ON CONFLICT (guild_id) DO UPDATE
+            SET panel_smoking_type_ids = EXCLUDED.panel_smoking_type_ids,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+            self.guild_id,
+            smoking_type_ids
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Records that the guild finished the onboarding wizard.
+    ///
+    /// # Returns
+    /// A Result containing whether this finished the wizard (`false` if it already was), or an `Error`.
+    pub async fn finish_onboarding(&self) -> Result<bool, Error> {
+        let _timer = QueryTimer::start("finish_onboarding");
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO guild_settings (guild_id, onboarded_at)
+            VALUES ($1, CURRENT_TIMESTAMP)
+            ON CONFLICT (guild_id) DO UPDATE
+            SET onboarded_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+            WHERE guild_settings.onboarded_at IS NULL
+            "#,
+            self.guild_id
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Retrieves the time zone the guild's days are counted in.
+    ///
+    /// # Returns
+    /// A Result containing the time zone, `None` if the guild uses the bot's time zone, or an `Error`.
+    pub async fn get_timezone(&self) -> Result<Option<Tz>, Error> {
+        let _timer = QueryTimer::start("get_guild_timezone");
+
+        let timezone = sqlx::query_scalar!(
+            r#"
+            SELECT timezone
+            FROM guild_settings
+            WHERE guild_id = $1
+            "#,
+            self.guild_id
+        )
+        .fetch_optional(&*self.db.pool)
+        .await?
+        .flatten();
+
+        // Only valid names are stored; one a newer time zone database dropped falls back too.
+        Ok(timezone.and_then(|name| name.parse().ok()))
+    }
+
+    /// Sets the time zone the guild's days are counted in.
+    ///
+    /// # Arguments
+    /// * `timezone` - The time zone, or `None` to use the bot's time zone.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn set_timezone(&self, timezone: Option<Tz>) -> Result<(), Error> {
+        let _timer = QueryTimer::start("set_guild_timezone");
+
+        sqlx::query!(
+            r#"
+            INSERT INTO guild_settings (guild_id, timezone)
+            VALUES ($1, $2)
+            ON CONFLICT (guild_id) DO UPDATE
+            SET timezone = EXCLUDED.timezone, updated_at = CURRENT_TIMESTAMP
+            "#,
+            self.guild_id,
+            timezone.map(|timezone| timezone.name())
+        )
+        .execute(&*self.db.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Inserts an outbox message row.
+///
+/// # Arguments
+/// * `executor` - The pool or transaction to run the query on.
+/// * `topic` - The kind of message.
+/// * `payload` - The JSON payload.
+///
+/// # Returns
+/// A Result containing the queued `OutboxMessage` or an `Error`.
+async fn insert_outbox_message<'e>(
+    executor: impl PgExecutor<'e>,
+    topic: &str,
+    payload: &str,
+) -> Result<OutboxMessage, Error> {
+    let message = sqlx::query_as!(
+        OutboxMessage,
+        r#"
+        INSERT INTO outbox (topic, payload)
+        VALUES ($1, $2)
+        RETURNING id, topic, payload, attempts, next_attempt_at, last_error,
+            delivered_at, dead_lettered_at, created_at
+        "#,
+        topic,
+        payload
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(message)
+}
+
+/// Inserts an audit log row.
+///
+/// # Arguments
+/// * `executor` - The pool or transaction to run the query on.
+/// * `actor_id` - The Discord ID of the owner.
+/// * `action` - The name of the action.
+/// * `target_id` - The Discord ID of the affected user.
+/// * `details` - A description of what changed.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+async fn insert_admin_audit_entry<'e>(
+    executor: impl PgExecutor<'e>,
+    actor_id: &str,
+    action: &str,
+    target_id: &str,
+    details: &str,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO admin_audit_log (actor_id, action, target_id, details)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        actor_id,
+        action,
+        target_id,
+        details
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Selects logs that repeat an earlier log of the same user, type and quantity within a window.
+///
+/// Logs closer than the window to the previous one form a run; every log of
+/// a run after the first is a duplicate of the first.
+///
+/// # Arguments
+/// * `executor` - The pool or transaction to run the query on.
+/// * `discord_id` - The Discord ID of the user, or `None` for every user.
+/// * `window` - The longest gap between two logs counted as duplicates.
+///
+/// # Returns
+/// A Result containing a vector of `DuplicateLog` ordered by user and time, or an `Error`.
+async fn select_duplicate_logs<'e>(
+    executor: impl PgExecutor<'e>,
+    discord_id: Option<&str>,
+    window: chrono::Duration,
+) -> Result<Vec<DuplicateLog>, Error> {
+    let duplicates = sqlx::query_as!(
+        DuplicateLog,
+        r#"
+        WITH ordered AS (
+            SELECT
+                id, discord_id, smoking_type_id, quantity, smoked_at,
+                CASE WHEN smoked_at - LAG(smoked_at) OVER runs <= make_interval(secs => $2) THEN 0 ELSE 1 END AS starts_run
+            FROM smoking_logs
+            WHERE $1::varchar IS NULL OR discord_id = $1
+            WINDOW runs AS (PARTITION BY discord_id, smoking_type_id, quantity ORDER BY smoked_at, id)
+        ), numbered AS (
+            SELECT
+                *,
+                SUM(starts_run) OVER (
+                    PARTITION BY discord_id, smoking_type_id, quantity ORDER BY smoked_at, id
+                ) AS run
+            FROM ordered
+        ), rooted AS (
+            SELECT
+                *,
+                FIRST_VALUE(id) OVER (
+                    PARTITION BY discord_id, smoking_type_id, quantity, run ORDER BY smoked_at, id
+                ) AS original_id
+            FROM numbered
+        )
+        SELECT
+            id as "id!",
+            original_id as "original_id!",
+            discord_id as "discord_id!",
+            smoking_type_id as "smoking_type_id!",
+            quantity as "quantity!",
+            smoked_at as "smoked_at!"
+        FROM rooted
+        WHERE id <> original_id
+        ORDER BY discord_id, smoked_at, id
+        "#,
+        discord_id,
+        window.num_milliseconds() as f64 / 1000.0
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(duplicates)
+}
+
+/// Inserts a smoking log row.
+///
+/// # Arguments
+/// * `executor` - The pool or transaction to run the query on.
+/// * `discord_id` - The Discord ID of the user.
+/// * `guild_id` - The ID of the guild the event was logged in, if any.
+/// * `smoking_type_id` - The ID of the smoking type.
+/// * `quantity` - The quantity of cigarettes smoked.
+/// * `idempotency_key` - The client-chosen key of the request, if any.
+///
+/// # Returns
+/// A Result containing the logged `SmokingLog` or an `Error`.
+async fn insert_smoking_log<'e>(
+    executor: impl PgExecutor<'e>,
+    discord_id: &str,
+    guild_id: Option<&str>,
+    smoking_type_id: i32,
+    quantity: i32,
+    idempotency_key: Option<&str>,
+) -> Result<SmokingLog, Error> {
+    let log = sqlx::query_as!(
+        SmokingLog,
+        r#"
+        INSERT INTO smoking_logs (discord_id, smoking_type_id, quantity, guild_id, idempotency_key)
+        VALUES ($1, $2, $3, $4, $5)
+
+        RETURNING 
+            id as "id!", 
+            discord_id as "discord_id!", 
+            smoking_type_id as "smoking_type_id!", 
+            quantity as "quantity!",
+            smoked_at as "smoked_at!",
+            device_name,
+            guild_id,
+            idempotency_key,
+            created_at,
+            updated_at
+
+        "#,
+        discord_id,
+        smoking_type_id,
+        quantity,
+        guild_id,
+        idempotency_key
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(log)
+}
+
+/// Totals a user's logs per smoking type over a period.
+///
+/// # Arguments
+/// * `executor` - The pool or transaction to run the query on.
+/// * `discord_id` - The Discord ID of the user.
+/// * `start` - The start of the period (inclusive).
+/// * `end` - The end of the period (exclusive).
+///
+/// # Returns
+/// A Result containing the totals of the logged types ordered by type, or an `Error`.
+async fn select_user_type_totals<'e>(
+    executor: impl PgExecutor<'e>,
+    discord_id: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<UserTypeTotal>, Error> {
+    let totals = sqlx::query_as!(
+        UserTypeTotal,
+        r#"
+        SELECT
+            st.type_name as "type_name!",
+            st.description,
+            st.typical_price,
+            st.currency,
+            SUM(sl.quantity) as "total_quantity!"
+        FROM smoking_logs sl
+        JOIN smoking_types st ON sl.smoking_type_id = st.id
+        WHERE sl.discord_id = $1
+        AND sl.smoked_at >= $2
+        AND sl.smoked_at < $3
+        GROUP BY st.id, st.type_name, st.description, st.typical_price, st.currency
+        ORDER BY st.id
+        "#,
+        discord_id,
+        start,
+        end
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(totals)
+}
+
+/// Selects the daily smoking summary of a user.
+///
+/// # Arguments
+/// * `executor` - The pool or transaction to run the query on.
+/// * `discord_id` - The Discord ID of the user.
+/// * `date` - The local date for which to retrieve the summary.
+/// * `timezone` - The time zone the date is counted in.
+///
+/// # Returns
+/// A Result containing a vector of `DailySmokingSummary` or an `Error`.
+async fn select_daily_summary<'e>(
+    executor: impl PgExecutor<'e>,
+    discord_id: &str,
+    date: NaiveDate,
+    timezone: Tz,
+) -> Result<Vec<DailySmokingSummary>, Error> {
+    let (start, end) = rollover::day_bounds(timezone, date);
+
+    let summary = sqlx::query_as!(
+        DailySmokingSummary,
+        r#"
+        SELECT 
+            sl.discord_id as "discord_id!",
+            u.username as "username!",
+            $4::date as "smoke_date!",
+            st.type_name as "type_name!",
+            st.description as "description!",
+            SUM(sl.quantity) as total_quantity
+        FROM smoking_logs sl
+        JOIN users u ON sl.discord_id = u.discord_id
+        JOIN smoking_types st ON sl.smoking_type_id = st.id
+        WHERE sl.discord_id = $1 
+        AND sl.smoked_at >= $2
+        AND sl.smoked_at < $3
+        GROUP BY 
+            sl.discord_id,
+            u.username,
+            st.type_name,
+            st.description
+        "#,
+        discord_id,
+        start,
+        end,
+        date
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(summary)
 }
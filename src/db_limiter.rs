@@ -0,0 +1,77 @@
+//! Concurrency limiting for database access.
+//!
+//! Requests acquire a permit before touching the database. When all permits
+//! are taken for longer than the acquire timeout, the request fails fast so
+//! the caller can answer 「混雑中です」 instead of letting Discord's
+//! interaction window expire.
+
+use std::{
+    sync::atomic::{AtomicI64, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Number of requests currently waiting for a permit
+static QUEUE_DEPTH: AtomicI64 = AtomicI64::new(0);
+
+/// Number of requests rejected because no permit became available in time
+static REJECTIONS_TOTAL: AtomicU64 = AtomicU64::new(0);
+
+/// Error returned when the database is saturated
+#[derive(Debug, thiserror::Error)]
+#[error("Database is saturated")]
+pub struct Saturated;
+
+/// Semaphore-based limiter for concurrent database operations
+pub struct DbLimiter {
+    semaphore: Semaphore,
+    acquire_timeout: Duration,
+}
+
+impl DbLimiter {
+    /// Creates a new limiter.
+    ///
+    /// # Arguments
+    /// * `max_concurrency` - The maximum number of concurrent database operations.
+    /// * `acquire_timeout` - How long to wait for a permit before failing.
+    pub fn new(max_concurrency: u32, acquire_timeout: Duration) -> Self {
+        Self {
+            semaphore: Semaphore::new(max_concurrency as usize),
+            acquire_timeout,
+        }
+    }
+
+    /// Waits for a permit to access the database.
+    ///
+    /// # Returns
+    /// A Result containing the permit, or `Saturated` if none became available in time.
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, Saturated> {
+        QUEUE_DEPTH.fetch_add(1, Ordering::Relaxed);
+        let permit = tokio::time::timeout(self.acquire_timeout, self.semaphore.acquire()).await;
+        QUEUE_DEPTH.fetch_sub(1, Ordering::Relaxed);
+
+        match permit {
+            Ok(Ok(permit)) => Ok(permit),
+            _ => {
+                REJECTIONS_TOTAL.fetch_add(1, Ordering::Relaxed);
+                Err(Saturated)
+            }
+        }
+    }
+}
+
+/// Renders the limiter metrics in the Prometheus text exposition format.
+///
+/// # Returns
+/// The metrics text.
+pub fn render_metrics() -> String {
+    format!(
+        "# TYPE cigarette_counter_db_queue_depth gauge\n\
+         cigarette_counter_db_queue_depth {}\n\
+         # TYPE cigarette_counter_db_rejections_total counter\n\
+         cigarette_counter_db_rejections_total {}\n",
+        QUEUE_DEPTH.load(Ordering::Relaxed),
+        REJECTIONS_TOTAL.load(Ordering::Relaxed)
+    )
+}
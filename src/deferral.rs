@@ -0,0 +1,279 @@
+//! Automatic deferral of slow interaction responses.
+//!
+//! Discord fails an interaction that is not responded to within three
+//! seconds, even if the bot completes it (and writes the log) later. Handlers
+//! run through `run` get `DEFER_AFTER` to respond; if they have not by then,
+//! the interaction is acknowledged with a deferred update and the handler's
+//! response is sent as a follow-up once it is ready. Handlers respond through
+//! `respond` and `update`, which pick the right endpoint.
+
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use poise::serenity_prelude::{self as serenity, CreateInteractionResponseMessage};
+use tracing::debug;
+
+use crate::Error;
+
+/// How long a handler may take before its interaction is deferred
+pub const DEFER_AFTER: Duration = Duration::from_millis(2000);
+
+/// The interaction has not been responded to yet
+const PENDING: u8 = 0;
+
+/// The handler responded to the interaction
+const RESPONDED: u8 = 1;
+
+/// The interaction was deferred; responses are sent as follow-ups
+const DEFERRED: u8 = 2;
+
+tokio::task_local! {
+    /// Response state of the interaction handled by the current task
+    static STATE: Arc<AtomicU8>;
+}
+
+/// How a handler must deliver its response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// Respond to the interaction directly
+    Response,
+    /// The interaction was deferred; send a follow-up
+    FollowUp,
+}
+
+/// Runs a handler, calling `defer` if it has not responded within `threshold`.
+///
+/// # Arguments
+/// * `threshold` - How long the handler may take before the interaction is deferred.
+/// * `work` - The handler; it must claim its response with `begin_response`.
+/// * `defer` - Acknowledges the interaction without responding.
+///
+/// # Returns
+/// The result of the handler, or the error of `defer`.
+pub async fn with_deferral<W, D, DF>(threshold: Duration, work: W, defer: D) -> Result<(), Error>
+where
+    W: Future<Output = Result<(), Error>>,
+    D: FnOnce() -> DF,
+    DF: Future<Output = Result<(), Error>>,
+{
+    let state = Arc::new(AtomicU8::new(PENDING));
+    let work = STATE.scope(state.clone(), work);
+    tokio::pin!(work);
+
+    tokio::select! {
+        result = &mut work => return result,
+        _ = tokio::time::sleep(threshold) => {}
+    }
+
+    // The handler is suspended while deferring, so it cannot respond concurrently.
+    if state
+        .compare_exchange(PENDING, DEFERRED, Ordering::SeqCst, Ordering::SeqCst)
+        .is_ok()
+    {
+        debug!("Deferring interaction after {:?}", threshold);
+        defer().await?;
+    }
+
+    work.await
+}
+
+/// Claims the response of the current interaction.
+///
+/// # Returns
+/// `Delivery::FollowUp` if the interaction was deferred, `Delivery::Response`
+/// otherwise (including outside `with_deferral`).
+pub fn begin_response() -> Delivery {
+    let state = STATE
+        .try_with(|state| {
+            state
+                .compare_exchange(PENDING, RESPONDED, Ordering::SeqCst, Ordering::SeqCst)
+                .unwrap_or_else(|current| current)
+        })
+        .unwrap_or(PENDING);
+
+    if state == DEFERRED {
+        Delivery::FollowUp
+    } else {
+        Delivery::Response
+    }
+}
+
+/// Runs an interaction handler, deferring the interaction if it takes longer than `DEFER_AFTER`.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `mci` - The component interaction.
+/// * `work` - The handler.
+///
+/// # Returns
+/// The result of the handler, or an `Error` if deferring failed.
+pub async fn run<W>(
+    ctx: &serenity::Context,
+    mci: &serenity::ComponentInteraction,
+    work: W,
+) -> Result<(), Error>
+where
+    W: Future<Output = Result<(), Error>>,
+{
+    with_deferral(DEFER_AFTER, work, || async {
+        mci.defer(ctx).await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Responds to a component interaction with a new message, or sends it as a
+/// follow-up if the interaction was deferred.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `mci` - The component interaction.
+/// * `content` - The message content.
+/// * `components` - The message components.
+/// * `embeds` - The message embeds.
+/// * `ephemeral` - Whether only the invoking user can see the message.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn respond(
+    ctx: &serenity::Context,
+    mci: &serenity::ComponentInteraction,
+    content: String,
+    components: Vec<serenity::CreateActionRow>,
+    embeds: Vec<serenity::CreateEmbed>,
+    ephemeral: bool,
+) -> Result<(), Error> {
+    deliver(ctx, mci, content, components, embeds, ephemeral).await?;
+
+    Ok(())
+}
+
+/// Responds like `respond` and returns the ID of the sent message, so it can be edited later.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `mci` - The component interaction.
+/// * `content` - The message content.
+/// * `components` - The message components.
+/// * `embeds` - The message embeds.
+///
+/// # Returns
+/// A Result containing the ID of the message or an `Error`.
+pub async fn respond_message(
+    ctx: &serenity::Context,
+    mci: &serenity::ComponentInteraction,
+    content: String,
+    components: Vec<serenity::CreateActionRow>,
+    embeds: Vec<serenity::CreateEmbed>,
+) -> Result<serenity::MessageId, Error> {
+    match deliver(ctx, mci, content, components, embeds, false).await? {
+        Some(followup) => Ok(followup.id),
+        None => Ok(mci.get_response(ctx).await?.id),
+    }
+}
+
+/// Sends a response or follow-up, depending on whether the interaction was deferred.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `mci` - The component interaction.
+/// * `content` - The message content.
+/// * `components` - The message components.
+/// * `embeds` - The message embeds.
+/// * `ephemeral` - Whether only the invoking user can see the message.
+///
+/// # Returns
+/// A Result containing the follow-up message if one was sent, or an `Error`.
+async fn deliver(
+    ctx: &serenity::Context,
+    mci: &serenity::ComponentInteraction,
+    content: String,
+    components: Vec<serenity::CreateActionRow>,
+    embeds: Vec<serenity::CreateEmbed>,
+    ephemeral: bool,
+) -> Result<Option<serenity::Message>, Error> {
+    match begin_response() {
+        Delivery::Response => {
+            mci.create_response(
+                ctx,
+                serenity::CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .components(components)
+                        .embeds(embeds)
+                        .ephemeral(ephemeral),
+                ),
+            )
+            .await?;
+
+            Ok(None)
+        }
+        Delivery::FollowUp => {
+            let followup = mci
+                .create_followup(
+                    ctx,
+                    serenity::CreateInteractionResponseFollowup::new()
+                        .content(content)
+                        .components(components)
+                        .embeds(embeds)
+                        .ephemeral(ephemeral),
+                )
+                .await?;
+
+            Ok(Some(followup))
+        }
+    }
+}
+
+/// Replaces the message a component belongs to, editing it through the
+/// deferred response if the interaction was deferred.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `mci` - The component interaction.
+/// * `content` - The new content.
+/// * `components` - The new components, replacing the current ones.
+/// * `embeds` - The new embeds, replacing the current ones.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn update(
+    ctx: &serenity::Context,
+    mci: &serenity::ComponentInteraction,
+    content: String,
+    components: Vec<serenity::CreateActionRow>,
+    embeds: Vec<serenity::CreateEmbed>,
+) -> Result<(), Error> {
+    match begin_response() {
+        Delivery::Response => {
+            mci.create_response(
+                ctx,
+                serenity::CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .components(components)
+                        .embeds(embeds),
+                ),
+            )
+            .await?;
+        }
+        Delivery::FollowUp => {
+            mci.edit_response(
+                ctx,
+                serenity::EditInteractionResponse::new()
+                    .content(content)
+                    .components(components)
+                    .embeds(embeds),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
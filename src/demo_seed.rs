@@ -0,0 +1,227 @@
+//! Synthetic demo data for screenshots, load tests and UI development.
+//!
+//! `cigarette-counter seed-demo` fills a demo guild with made-up members and
+//! months of logs, so nobody needs real data to try the bot. A profile picks
+//! the volume, and `--users` / `--months` override it:
+//!
+//! | Profile  | Users | Months |
+//! |----------|-------|--------|
+//! | `small`  | 5     | 1      |
+//! | `demo`   | 20    | 3      |
+//! | `load`   | 500   | 12     |
+//!
+//! Every member gets a baseline daily count that slowly drifts down over the
+//! period (people trying to cut down), smokes a little more on weekends and
+//! skips some days entirely. Logs fall between 7:00 and 24:00 in the bot's
+//! time zone and mostly use one favourite type. Passing `--seed` makes the
+//! data reproducible.
+//!
+//! Demo IDs are 20-digit snowflakes starting with `DEMO_ID_PREFIX`, whose
+//! timestamps lie decades in the future, so they cannot collide with real
+//! users or guilds. Seeding refuses to touch a guild that already has logs.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use clap::ValueEnum;
+use poise::serenity_prelude::futures::lock::Mutex;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::database::{Database, ImportedLog};
+
+/// Guild demo data is seeded into unless another one is given
+pub const DEMO_GUILD_ID: &str = "10000000000000000000";
+
+/// Prefix of the IDs of demo members
+pub const DEMO_ID_PREFIX: &str = "100000000000";
+
+/// Share of days a demo member logs nothing
+const SKIP_DAY_CHANCE: f64 = 0.08;
+
+/// Share of logs using a type other than the member's favourite
+const OTHER_TYPE_CHANCE: f64 = 0.15;
+
+/// How much of their baseline members cut down by the end of the period
+const MAX_REDUCTION: f64 = 0.4;
+
+/// How much of the data `seed-demo` generates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SeedProfile {
+    /// A handful of members for quick checks
+    Small,
+    /// Enough members and history for screenshots
+    #[default]
+    Demo,
+    /// A large guild for load tests
+    Load,
+}
+
+impl SeedProfile {
+    /// Returns the number of members and months of history.
+    pub fn volume(self) -> (u32, u32) {
+        match self {
+            Self::Small => (5, 1),
+            Self::Demo => (20, 3),
+            Self::Load => (500, 12),
+        }
+    }
+}
+
+/// What to seed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeedPlan {
+    pub guild_id: String,
+    pub users: u32,
+    pub months: u32,
+    /// Seed of the random generator; random data when `None`
+    pub seed: Option<u64>,
+}
+
+/// A synthetic member and their logs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DemoMember {
+    pub discord_id: String,
+    pub username: String,
+    pub logs: Vec<ImportedLog>,
+}
+
+/// Result of seeding demo data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeedSummary {
+    pub guild_id: String,
+    pub members: usize,
+    pub logs: u64,
+}
+
+/// Error returned when demo data cannot be seeded
+#[derive(Debug, thiserror::Error)]
+pub enum SeedError {
+    #[error("Guild {0} already has logs; seed another guild with --guild")]
+    GuildNotEmpty(String),
+    #[error("There are no smoking types to log")]
+    NoSmokingTypes,
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+}
+
+/// Generates synthetic members and their logs.
+///
+/// # Arguments
+/// * `plan` - What to generate.
+/// * `type_ids` - The smoking types logs may use.
+/// * `today` - The last day of the generated history.
+/// * `timezone` - The time zone days are counted in.
+///
+/// # Returns
+/// The members, ordered by ID, with their logs in time order.
+pub fn generate_members(
+    plan: &SeedPlan,
+    type_ids: &[i32],
+    today: NaiveDate,
+    timezone: Tz,
+) -> Vec<DemoMember> {
+    let mut rng = match plan.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let start = today - Duration::days(i64::from(plan.months) * 30);
+    let days = (today - start).num_days().max(1);
+
+    (1..=plan.users)
+        .map(|number| {
+            let discord_id = format!("{}{:08}", DEMO_ID_PREFIX, number);
+            let baseline: f64 = rng.gen_range(3.0..20.0);
+            let favourite = type_ids[rng.gen_range(0..type_ids.len())];
+            let mut logs = Vec::new();
+
+            for offset in 0..=days {
+                let date = start + Duration::days(offset);
+                if rng.gen_bool(SKIP_DAY_CHANCE) {
+                    continue;
+                }
+
+                let progress = offset as f64 / days as f64;
+                let weekend = matches!(date.weekday(), Weekday::Sat | Weekday::Sun);
+                let expected =
+                    baseline * (1.0 - MAX_REDUCTION * progress) * if weekend { 1.2 } else { 1.0 };
+                let count = (expected + rng.gen_range(-2.0..2.0)).round().max(0.0) as u32;
+
+                let mut times: Vec<DateTime<Utc>> = (0..count)
+                    .filter_map(|_| {
+                        let seconds = rng.gen_range(7 * 3600..24 * 3600);
+                        let time = NaiveTime::from_num_seconds_from_midnight_opt(seconds, 0)?;
+                        timezone
+                            .from_local_datetime(&date.and_time(time))
+                            .earliest()
+                            .map(|local| local.with_timezone(&Utc))
+                    })
+                    .collect();
+                times.sort();
+
+                logs.extend(times.into_iter().map(|smoked_at| ImportedLog {
+                    discord_id: discord_id.clone(),
+                    smoking_type_id: if rng.gen_bool(OTHER_TYPE_CHANCE) {
+                        type_ids[rng.gen_range(0..type_ids.len())]
+                    } else {
+                        favourite
+                    },
+                    quantity: 1,
+                    smoked_at,
+                }));
+            }
+
+            DemoMember {
+                discord_id,
+                username: format!("demo-user-{:03}", number),
+                logs,
+            }
+        })
+        .collect()
+}
+
+/// Seeds a guild with synthetic members and logs.
+///
+/// # Arguments
+/// * `database` - The database.
+/// * `plan` - What to seed.
+/// * `today` - The last day of the generated history.
+/// * `timezone` - The time zone days are counted in.
+///
+/// # Returns
+/// A Result containing what was seeded, or a `SeedError`.
+pub async fn seed_demo(
+    database: &Mutex<Database>,
+    plan: &SeedPlan,
+    today: NaiveDate,
+    timezone: Tz,
+) -> Result<SeedSummary, SeedError> {
+    let db = database.lock().await;
+    let guild = db.guild(&plan.guild_id);
+    if !guild.get_user_ids().await?.is_empty() {
+        return Err(SeedError::GuildNotEmpty(plan.guild_id.clone()));
+    }
+
+    let type_ids: Vec<i32> = db
+        .get_smoking_types()
+        .await?
+        .into_iter()
+        .map(|smoking_type| smoking_type.id)
+        .collect();
+    if type_ids.is_empty() {
+        return Err(SeedError::NoSmokingTypes);
+    }
+
+    let members = generate_members(plan, &type_ids, today, timezone);
+    // One insert per member keeps the load profile's statements small.
+    let mut inserted = 0;
+    for member in &members {
+        db.get_or_create_user(&member.discord_id, &member.username)
+            .await?;
+        inserted += guild.import_logs(&member.logs).await?;
+    }
+
+    Ok(SeedSummary {
+        guild_id: plan.guild_id.clone(),
+        members: members.len(),
+        logs: inserted,
+    })
+}
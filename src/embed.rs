@@ -0,0 +1,328 @@
+//! Embeds attached to replies.
+//!
+//! Every reply is shown as an embed in the bot's style: the brand color (red
+//! for errors), the bot's name in the footer (both from the deployment's
+//! `Branding`), the time it was sent and a title in the user's language. The
+//! body stays Japanese like the rest of the bot's messages. Handlers describe
+//! embeds with `ReplyEmbed`, which the frontends turn into Discord embeds.
+//!
+//! Confirmations of users with a daily goal also carry a goal embed: a
+//! unicode progress bar toward the goal, colored green, amber or red by how
+//! close the day's total is, and the budget left for the day. A press on a
+//! type whose category the user set a goal for adds the same embed for the
+//! category, counted in its unit.
+
+use poise::serenity_prelude as serenity;
+
+use crate::database::CategoryProgress;
+use crate::format::{branding, format_quantity, Locale};
+
+/// Color of the side bar of the bot's embeds, unless the branding sets another
+pub const BRAND_COLOR: u32 = 0xC8A165;
+
+/// Color of the side bar of error embeds
+pub const ERROR_COLOR: u32 = 0xE74C3C;
+
+/// Text in the footer of the bot's embeds and on panels, unless the branding sets another
+pub const FOOTER_TEXT: &str = "喫煙カウント";
+
+/// Number of cells in a progress bar
+pub const PROGRESS_BAR_WIDTH: i64 = 10;
+
+/// Share of the goal from which the progress is shown in amber, in percent
+const CLOSE_TO_GOAL_PERCENT: i64 = 80;
+
+/// Cell of a progress bar covered by the total
+const FILLED_CELL: char = '▰';
+
+/// Cell of a progress bar not covered by the total
+const EMPTY_CELL: char = '▱';
+
+/// What a reply is about, shown as the title of its embed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Title {
+    /// A recorded press
+    Confirmation,
+    /// A statistics view
+    Stats,
+    /// Progress toward the daily goal
+    Goal,
+    /// A changed or listed setting
+    Settings,
+    /// A help page
+    Help,
+    /// Progress of the guild's weekly challenge
+    Challenge,
+    /// Standings of a leaderboard season
+    Leaderboard,
+    /// A rejected or failed request
+    Error,
+}
+
+impl Title {
+    /// Returns the title in the locale's language.
+    ///
+    /// # Arguments
+    /// * `locale` - The locale of the user.
+    ///
+    /// # Returns
+    /// The title (e.g. `記録` or `Logged`).
+    pub fn text(self, locale: Locale) -> &'static str {
+        match (self, locale) {
+            (Title::Confirmation, Locale::Japanese) => "記録",
+            (Title::Confirmation, Locale::EnglishUs | Locale::EnglishGb) => "Logged",
+            (Title::Confirmation, Locale::German) => "Erfasst",
+            (Title::Confirmation, Locale::French) => "Enregistré",
+            (Title::Stats, Locale::Japanese) => "統計",
+            (Title::Stats, Locale::EnglishUs | Locale::EnglishGb) => "Statistics",
+            (Title::Stats, Locale::German) => "Statistik",
+            (Title::Stats, Locale::French) => "Statistiques",
+            (Title::Goal, Locale::Japanese) => "今日の目標",
+            (Title::Goal, Locale::EnglishUs | Locale::EnglishGb) => "Today's goal",
+            (Title::Goal, Locale::German) => "Tagesziel",
+            (Title::Goal, Locale::French) => "Objectif du jour",
+            (Title::Settings, Locale::Japanese) => "設定",
+            (Title::Settings, Locale::EnglishUs | Locale::EnglishGb) => "Settings",
+            (Title::Settings, Locale::German) => "Einstellungen",
+            (Title::Settings, Locale::French) => "Paramètres",
+            (Title::Help, Locale::Japanese) => "ヘルプ",
+            (Title::Help, Locale::EnglishUs | Locale::EnglishGb) => "Help",
+            (Title::Help, Locale::German) => "Hilfe",
+            (Title::Help, Locale::French) => "Aide",
+            (Title::Challenge, Locale::Japanese) => "今週のチャレンジ",
+            (Title::Challenge, Locale::EnglishUs | Locale::EnglishGb) => "Weekly challenge",
+            (Title::Challenge, Locale::German) => "Wochen-Challenge",
+            (Title::Challenge, Locale::French) => "Défi de la semaine",
+            (Title::Leaderboard, Locale::Japanese) => "ランキング",
+            (Title::Leaderboard, Locale::EnglishUs | Locale::EnglishGb) => "Leaderboard",
+            (Title::Leaderboard, Locale::German) => "Bestenliste",
+            (Title::Leaderboard, Locale::French) => "Classement",
+            (Title::Error, Locale::Japanese) => "エラー",
+            (Title::Error, Locale::EnglishUs | Locale::EnglishGb) => "Error",
+            (Title::Error, Locale::German) => "Fehler",
+            (Title::Error, Locale::French) => "Erreur",
+        }
+    }
+}
+
+/// An embed shown with a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplyEmbed {
+    /// The embed title
+    pub title: Option<String>,
+    /// The embed body
+    pub description: String,
+    /// The color of the embed's side bar, as RGB
+    pub color: u32,
+    /// The small text below the body
+    pub footer: Option<String>,
+    /// Whether the embed shows the time it was sent
+    pub timestamped: bool,
+}
+
+impl ReplyEmbed {
+    /// Creates an embed in the bot's style: the branding's color, or red for
+    /// errors, the bot's name in the footer and the time it is sent.
+    ///
+    /// # Arguments
+    /// * `title` - What the embed is about, if anything.
+    /// * `description` - The embed body.
+    /// * `locale` - The locale the title is shown in.
+    pub fn styled(title: Option<Title>, description: impl Into<String>, locale: Locale) -> Self {
+        Self {
+            title: title.map(|title| title.text(locale).to_string()),
+            description: description.into(),
+            color: if title == Some(Title::Error) {
+                ERROR_COLOR
+            } else {
+                branding().color
+            },
+            footer: Some(branding().name.clone()),
+            timestamped: true,
+        }
+    }
+
+    /// Creates an error embed in the bot's style.
+    ///
+    /// # Arguments
+    /// * `description` - What went wrong.
+    /// * `locale` - The locale the title is shown in.
+    pub fn error(description: impl Into<String>, locale: Locale) -> Self {
+        Self::styled(Some(Title::Error), description, locale)
+    }
+
+    /// Replaces the color of the embed's side bar.
+    ///
+    /// # Arguments
+    /// * `color` - The color, as RGB.
+    pub fn color(mut self, color: u32) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Replaces the footer.
+    ///
+    /// # Arguments
+    /// * `footer` - The small text below the body.
+    pub fn footer(mut self, footer: impl Into<String>) -> Self {
+        self.footer = Some(footer.into());
+        self
+    }
+
+    /// Builds the Discord embed, stamped with the current time if it is timestamped.
+    pub fn to_discord(&self) -> serenity::CreateEmbed {
+        let mut embed = serenity::CreateEmbed::new()
+            .description(&self.description)
+            .color(self.color);
+        if let Some(title) = &self.title {
+            embed = embed.title(title);
+        }
+        if let Some(footer) = &self.footer {
+            embed = embed.footer(serenity::CreateEmbedFooter::new(footer));
+        }
+        if self.timestamped {
+            embed = embed.timestamp(serenity::Timestamp::now());
+        }
+
+        embed
+    }
+}
+
+/// How a day's total stands against the daily goal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GoalStatus {
+    /// Well below the goal
+    OnTrack,
+    /// At least `CLOSE_TO_GOAL_PERCENT` of the goal, but not above it
+    Close,
+    /// Above the goal
+    Exceeded,
+}
+
+impl GoalStatus {
+    /// Determines the status of a day's total.
+    ///
+    /// # Arguments
+    /// * `total` - The day's total.
+    /// * `goal` - The daily goal.
+    ///
+    /// # Returns
+    /// The `GoalStatus`.
+    pub fn of(total: i64, goal: i32) -> Self {
+        let goal = i64::from(goal);
+        if total > goal {
+            GoalStatus::Exceeded
+        } else if total * 100 >= goal * CLOSE_TO_GOAL_PERCENT {
+            GoalStatus::Close
+        } else {
+            GoalStatus::OnTrack
+        }
+    }
+
+    /// Returns the embed color of the status: green, amber or red.
+    pub fn color(self) -> u32 {
+        match self {
+            GoalStatus::OnTrack => 0x2ECC71,
+            GoalStatus::Close => 0xF1C40F,
+            GoalStatus::Exceeded => 0xE74C3C,
+        }
+    }
+}
+
+/// Draws a progress bar of `PROGRESS_BAR_WIDTH` cells toward a goal.
+///
+/// # Arguments
+/// * `total` - The day's total.
+/// * `goal` - The daily goal.
+///
+/// # Returns
+/// The bar (e.g. `▰▰▰▰▰▰▰▱▱▱` for 7 of 10); it is full once the goal is reached.
+pub fn progress_bar(total: i64, goal: i32) -> String {
+    let goal = i64::from(goal);
+    let filled = if goal <= 0 {
+        PROGRESS_BAR_WIDTH
+    } else {
+        // Rounded to the nearest cell, but a started goal never shows as empty.
+        let cells = (total.max(0) * PROGRESS_BAR_WIDTH + goal / 2) / goal;
+        if total > 0 {
+            cells.clamp(1, PROGRESS_BAR_WIDTH)
+        } else {
+            0
+        }
+    };
+
+    (0..PROGRESS_BAR_WIDTH)
+        .map(|cell| {
+            if cell < filled {
+                FILLED_CELL
+            } else {
+                EMPTY_CELL
+            }
+        })
+        .collect()
+}
+
+/// Builds the embed showing a day's progress toward the daily goal.
+///
+/// # Arguments
+/// * `total` - The day's total.
+/// * `goal` - The daily goal.
+/// * `locale` - The locale to format for.
+///
+/// # Returns
+/// The embed, e.g. `▰▰▰▰▰▰▰▱▱▱ 7本 / 10本` with the footer `残り 3本`.
+pub fn goal_embed(total: i64, goal: i32, locale: Locale) -> ReplyEmbed {
+    progress_embed(None, total, goal, &branding().count_unit, locale)
+}
+
+/// Builds the embed showing a day's progress toward the goal of a category.
+///
+/// # Arguments
+/// * `progress` - The day's total in the category and its goal.
+/// * `locale` - The locale to format for.
+///
+/// # Returns
+/// The embed, e.g. `☕ coffee ▰▰▰▰▰▰▰▱▱▱ 2杯 / 3杯` with the footer `残り 1杯`.
+pub fn category_goal_embed(progress: &CategoryProgress, locale: Locale) -> ReplyEmbed {
+    progress_embed(
+        Some(&progress.category.label()),
+        progress.total,
+        progress.daily_goal,
+        &progress.category.unit,
+        locale,
+    )
+}
+
+/// Builds a progress embed toward a goal counted in `unit`, optionally labelled.
+fn progress_embed(
+    label: Option<&str>,
+    total: i64,
+    goal: i32,
+    unit: &str,
+    locale: Locale,
+) -> ReplyEmbed {
+    let remaining = i64::from(goal) - total;
+    let footer = if remaining >= 0 {
+        format!("残り {}", format_quantity(remaining, unit, locale))
+    } else {
+        format!(
+            "目標を{}超えています",
+            format_quantity(-remaining, unit, locale)
+        )
+    };
+
+    let progress = format!(
+        "{} {} / {}",
+        progress_bar(total, goal),
+        format_quantity(total, unit, locale),
+        format_quantity(goal.into(), unit, locale)
+    );
+    let description = match label {
+        Some(label) => format!("{} {}", label, progress),
+        None => progress,
+    };
+
+    ReplyEmbed::styled(Some(Title::Goal), description, locale)
+        .color(GoalStatus::of(total, goal).color())
+        .footer(footer)
+}
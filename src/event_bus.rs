@@ -0,0 +1,127 @@
+//! In-process bus of domain events.
+//!
+//! Services publish what happened (a log was created, a goal was exceeded,
+//! a milestone was reached) and subsystems subscribe to the events they care
+//! about, so the code recording a log does not need to know about everything
+//! that reacts to it. Delivery is best effort: events published while nobody
+//! subscribes are dropped, and a subscriber that falls more than `CAPACITY`
+//! events behind skips the oldest ones.
+
+use std::future::Future;
+
+use chrono::NaiveDate;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, warn};
+
+/// Number of events buffered per subscriber
+pub const CAPACITY: usize = 1024;
+
+/// Something that happened in the domain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DomainEvent {
+    /// A smoking event was recorded
+    LogCreated {
+        log_id: i32,
+        discord_id: String,
+        guild_id: Option<String>,
+        smoking_type_id: i32,
+        quantity: i32,
+        date: NaiveDate,
+        today_total: i64,
+    },
+    /// A smoking event was removed
+    LogDeleted { log_id: i32, discord_id: String },
+    /// A log took the user's daily total above their goal
+    GoalExceeded {
+        discord_id: String,
+        guild_id: Option<String>,
+        date: NaiveDate,
+        goal: i32,
+        today_total: i64,
+    },
+    /// A member was granted a smoke-free milestone role
+    MilestoneReached {
+        discord_id: String,
+        guild_id: String,
+        role_id: String,
+        smoke_free_days: i32,
+    },
+}
+
+impl DomainEvent {
+    /// Returns the name of the event kind, for logs and metrics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::LogCreated { .. } => "log_created",
+            Self::LogDeleted { .. } => "log_deleted",
+            Self::GoalExceeded { .. } => "goal_exceeded",
+            Self::MilestoneReached { .. } => "milestone_reached",
+        }
+    }
+}
+
+/// Broadcast channel of domain events
+pub struct EventBus {
+    sender: broadcast::Sender<DomainEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(CAPACITY)
+    }
+}
+
+impl EventBus {
+    /// Creates an event bus.
+    ///
+    /// # Arguments
+    /// * `capacity` - The number of events buffered per subscriber.
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+
+        Self { sender }
+    }
+
+    /// Publishes an event to all current subscribers.
+    ///
+    /// # Arguments
+    /// * `event` - The event.
+    pub fn publish(&self, event: DomainEvent) {
+        debug!("Publishing {} event", event.name());
+        // Sending only fails when there are no subscribers, which is fine.
+        let _ = self.sender.send(event);
+    }
+
+    /// Subscribes to events published from now on.
+    ///
+    /// # Returns
+    /// The receiving end of the subscription.
+    pub fn subscribe(&self) -> broadcast::Receiver<DomainEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Spawns a background task handling every published event in order.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the subscriber, used in logs.
+    /// * `handler` - Called for each event; the next event is handled once it completes.
+    pub fn spawn_subscriber<F, Fut>(&self, name: &'static str, handler: F)
+    where
+        F: Fn(DomainEvent) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let mut receiver = self.subscribe();
+
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => handler(event).await,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Subscriber {} skipped {} events", name, skipped)
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,94 @@
+//! Gateway event dispatch for features that react to Discord events.
+
+use poise::serenity_prelude as serenity;
+
+use crate::commands::cleanup::{handle_cleanup, CleanupId};
+use crate::commands::help::{handle_help, HelpId};
+use crate::commands::panel::{handle_interaction, parse_panel_press, refresh_panel};
+use crate::commands::quantity::{handle_quantity, QuantityId};
+use crate::commands::settings::{handle_settings, SettingsId};
+use crate::commands::stats::{refresh_stats, StatsView};
+use crate::commands::undo::{handle_undo, UndoId};
+use crate::commands::wagers::{handle_wager, WagerId};
+use crate::custom_id::RefreshId;
+use crate::guild_allowlist::admit_guild;
+use crate::guild_removal::{handle_guild_delete, handle_guild_return};
+use crate::onboarding::{handle_guild_create, handle_onboarding, OnboardingId};
+use crate::voice::handle_voice_state_update;
+use crate::{acknowledgment, consent, deferral};
+use crate::{Data, Error};
+
+/// Dispatches a gateway event to the relevant feature handlers.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `event` - The received event.
+/// * `data` - The shared application state.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn handle_event(
+    ctx: &serenity::Context,
+    event: &serenity::FullEvent,
+    data: &Data,
+) -> Result<(), Error> {
+    match event {
+        serenity::FullEvent::VoiceStateUpdate { old, new } => {
+            handle_voice_state_update(ctx, data, old.as_ref(), new).await?;
+        }
+        serenity::FullEvent::GuildCreate { guild, is_new } => {
+            if !admit_guild(ctx, data, guild).await? {
+                return Ok(());
+            }
+            handle_guild_return(data, guild).await?;
+            handle_guild_create(ctx, data, guild, *is_new).await?;
+        }
+        serenity::FullEvent::GuildDelete { incomplete, .. } => {
+            handle_guild_delete(data, incomplete).await?;
+        }
+        serenity::FullEvent::InteractionCreate {
+            interaction: serenity::Interaction::Component(mci),
+        } => {
+            if let Some(smoking_type_id) = consent::parse_accept_id(&mci.data.custom_id) {
+                deferral::run(
+                    ctx,
+                    mci,
+                    consent::handle_accept(ctx, data, mci, smoking_type_id),
+                )
+                .await?;
+            } else if let Some(smoking_type_id) =
+                acknowledgment::parse_accept_id(&mci.data.custom_id)
+            {
+                deferral::run(
+                    ctx,
+                    mci,
+                    acknowledgment::handle_accept(ctx, data, mci, smoking_type_id),
+                )
+                .await?;
+            } else if let Ok(refresh) = mci.data.custom_id.parse::<RefreshId>() {
+                deferral::run(ctx, mci, refresh_panel(ctx, data, mci, &refresh)).await?;
+            } else if let Some(view) = StatsView::parse_refresh_id(&mci.data.custom_id) {
+                deferral::run(ctx, mci, refresh_stats(ctx, data, mci, &view)).await?;
+            } else if let Some(cleanup) = CleanupId::parse(&mci.data.custom_id) {
+                deferral::run(ctx, mci, handle_cleanup(ctx, data, mci, &cleanup)).await?;
+            } else if let Some(undo) = UndoId::parse(&mci.data.custom_id) {
+                deferral::run(ctx, mci, handle_undo(ctx, data, mci, &undo)).await?;
+            } else if let Some(control) = SettingsId::parse(&mci.data.custom_id) {
+                deferral::run(ctx, mci, handle_settings(ctx, data, mci, &control)).await?;
+            } else if let Some(control) = QuantityId::parse(&mci.data.custom_id) {
+                deferral::run(ctx, mci, handle_quantity(ctx, data, mci, &control)).await?;
+            } else if let Some(control) = WagerId::parse(&mci.data.custom_id) {
+                deferral::run(ctx, mci, handle_wager(ctx, data, mci, &control)).await?;
+            } else if let Some(control) = HelpId::parse(&mci.data.custom_id) {
+                deferral::run(ctx, mci, handle_help(ctx, data, mci, &control)).await?;
+            } else if let Some(control) = OnboardingId::parse(&mci.data.custom_id) {
+                deferral::run(ctx, mci, handle_onboarding(ctx, data, mci, &control)).await?;
+            } else if let Some(panel) = parse_panel_press(&mci.data.custom_id) {
+                handle_interaction(ctx, data, mci, &panel).await?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
@@ -0,0 +1,210 @@
+//! Named query catalog for on-demand `EXPLAIN ANALYZE`.
+//!
+//! Each entry mirrors a hot query in `database.rs` so its plan can be checked
+//! against the live database with `admin explain <query-name>`. Keep the SQL
+//! here in sync when the corresponding `Database` method changes.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+
+use crate::rollover;
+
+/// The kind of value bound to a query parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamKind {
+    /// The Discord ID of the invoking user
+    DiscordId,
+    /// The ID of the current guild (or an empty string outside guilds)
+    GuildId,
+    /// Today's date
+    Today,
+    /// The first instant of today
+    TodayStart,
+    /// The first instant of tomorrow
+    TomorrowStart,
+    /// The first instant of the same day one year ago
+    YearAgoStart,
+    /// The name of the bot's time zone
+    Timezone,
+    /// The ID of the first smoking type
+    SmokingTypeId,
+}
+
+/// A bound parameter value
+#[derive(Debug, Clone)]
+pub enum ParamValue {
+    Text(String),
+    Date(NaiveDate),
+    Int(i32),
+    Timestamp(DateTime<Utc>),
+}
+
+/// The values query parameters are resolved from
+pub struct ParamSource {
+    pub discord_id: String,
+    pub guild_id: String,
+    pub smoking_type_id: i32,
+    pub today: NaiveDate,
+    pub timezone: Tz,
+}
+
+impl ParamSource {
+    /// Resolves parameter kinds to values.
+    ///
+    /// # Arguments
+    /// * `kinds` - The parameter kinds of a query.
+    ///
+    /// # Returns
+    /// The values to bind, in order.
+    pub fn resolve(&self, kinds: &[ParamKind]) -> Vec<ParamValue> {
+        kinds
+            .iter()
+            .map(|kind| match kind {
+                ParamKind::DiscordId => ParamValue::Text(self.discord_id.clone()),
+                ParamKind::GuildId => ParamValue::Text(self.guild_id.clone()),
+                ParamKind::Today => ParamValue::Date(self.today),
+                ParamKind::TodayStart => {
+                    ParamValue::Timestamp(rollover::day_bounds(self.timezone, self.today).0)
+                }
+                ParamKind::TomorrowStart => {
+                    ParamValue::Timestamp(rollover::day_bounds(self.timezone, self.today).1)
+                }
+                ParamKind::YearAgoStart => ParamValue::Timestamp(rollover::start_of_day(
+                    self.timezone,
+                    rollover::year_ago(self.today),
+                )),
+                ParamKind::Timezone => ParamValue::Text(self.timezone.name().to_string()),
+                ParamKind::SmokingTypeId => ParamValue::Int(self.smoking_type_id),
+            })
+            .collect()
+    }
+}
+
+/// A query that can be explained
+pub struct NamedQuery {
+    pub name: &'static str,
+    pub sql: &'static str,
+    pub params: &'static [ParamKind],
+}
+
+/// Hot queries from `database.rs`
+pub const QUERIES: &[NamedQuery] = &[
+    NamedQuery {
+        name: "get_daily_summary",
+        sql: r#"
+            SELECT
+                sl.discord_id,
+                u.username,
+                $4::date,
+                st.type_name,
+                st.description,
+                SUM(sl.quantity)
+            FROM smoking_logs sl
+            JOIN users u ON sl.discord_id = u.discord_id
+            JOIN smoking_types st ON sl.smoking_type_id = st.id
+            WHERE sl.discord_id = $1
+            AND sl.smoked_at >= $2
+            AND sl.smoked_at < $3
+            GROUP BY
+                sl.discord_id,
+                u.username,
+                st.type_name,
+                st.description
+        "#,
+        params: &[
+            ParamKind::DiscordId,
+            ParamKind::TodayStart,
+            ParamKind::TomorrowStart,
+            ParamKind::Today,
+        ],
+    },
+    NamedQuery {
+        name: "get_daily_totals",
+        sql: r#"
+            SELECT DATE(smoked_at AT TIME ZONE $3), SUM(quantity)
+            FROM smoking_logs
+            WHERE discord_id = $1
+            AND smoked_at >= $2
+            GROUP BY 1
+            ORDER BY 1
+        "#,
+        params: &[
+            ParamKind::DiscordId,
+            ParamKind::YearAgoStart,
+            ParamKind::Timezone,
+        ],
+    },
+    NamedQuery {
+        name: "get_last_smoked_at",
+        sql: r#"
+            SELECT MAX(smoked_at)
+            FROM smoking_logs
+            WHERE discord_id = $1
+        "#,
+        params: &[ParamKind::DiscordId],
+    },
+    NamedQuery {
+        name: "get_first_smoke_date",
+        sql: r#"
+            SELECT MIN(smoked_at)
+            FROM smoking_logs
+            WHERE discord_id = $1
+        "#,
+        params: &[ParamKind::DiscordId],
+    },
+    NamedQuery {
+        name: "get_guild_user_ids",
+        sql: r#"
+            SELECT DISTINCT discord_id
+            FROM smoking_logs
+            WHERE guild_id = $1
+            ORDER BY discord_id
+        "#,
+        params: &[ParamKind::GuildId],
+    },
+    NamedQuery {
+        name: "get_smoking_types",
+        sql: r#"
+            SELECT id, type_name, description, created_at
+            FROM smoking_types
+            ORDER BY id
+        "#,
+        params: &[],
+    },
+    NamedQuery {
+        name: "log_smoking",
+        sql: r#"
+            INSERT INTO smoking_logs (discord_id, smoking_type_id, quantity, guild_id)
+            VALUES ($1, $2, 1, NULLIF($3, ''))
+            RETURNING id
+        "#,
+        params: &[
+            ParamKind::DiscordId,
+            ParamKind::SmokingTypeId,
+            ParamKind::GuildId,
+        ],
+    },
+];
+
+/// Finds a named query.
+///
+/// # Arguments
+/// * `name` - The query name.
+///
+/// # Returns
+/// The matching `NamedQuery`, or `None` if there is no query with that name.
+pub fn find(name: &str) -> Option<&'static NamedQuery> {
+    QUERIES.iter().find(|query| query.name == name)
+}
+
+/// Lists the names of all explainable queries.
+///
+/// # Returns
+/// A comma-separated list of query names.
+pub fn query_names() -> String {
+    QUERIES
+        .iter()
+        .map(|query| query.name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,240 @@
+//! Personal exports of aggregated statistics.
+//!
+//! `export aggregates` DMs a user a compact CSV with one row per period and
+//! smoking type, ready to chart in a spreadsheet, instead of every raw log.
+//! Periods are days, weeks (starting on Monday) or months in the bot's time
+//! zone, identified by their first date.
+//!
+//! Exports are CSV by default. With the `parquet` feature enabled they can
+//! also be written as Parquet, for loading into pandas or DuckDB. A Parquet
+//! export is a single row group with the columns:
+//!
+//! | Column     | Arrow type | Parquet type            | Nullable |
+//! |------------|------------|-------------------------|----------|
+//! | `period`   | `Date32`   | `INT32` (`DATE`)        | no       |
+//! | `type`     | `Utf8`     | `BYTE_ARRAY` (`STRING`) | no       |
+//! | `quantity` | `Int64`    | `INT64`                 | no       |
+//!
+//! Columns are Snappy-compressed.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::database::PeriodTypeTotal;
+
+/// Header row of an aggregate export
+pub const CSV_HEADER: &str = "period,type,quantity";
+
+/// The length of the periods an export totals logs over
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Granularity {
+    Day,
+    Week,
+    Month,
+}
+
+impl Granularity {
+    /// Every granularity
+    pub const ALL: [Self; 3] = [Self::Day, Self::Week, Self::Month];
+
+    /// Returns the name used in commands and file names, which is also the
+    /// unit Postgres' `DATE_TRUNC` takes.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+        }
+    }
+}
+
+impl fmt::Display for Granularity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Error returned when a granularity argument is unknown
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown granularity: {0} (expected day, week or month)")]
+pub struct ParseGranularityError(String);
+
+impl FromStr for Granularity {
+    type Err = ParseGranularityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|granularity| granularity.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| ParseGranularityError(s.to_string()))
+    }
+}
+
+/// The file format of an export
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Parquet,
+}
+
+impl ExportFormat {
+    /// Every format
+    pub const ALL: [Self; 2] = [Self::Csv, Self::Parquet];
+
+    /// Returns the name used in commands, which is also the file extension.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Parquet => "parquet",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Error returned when an export format argument is unknown
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown export format: {0} (expected csv or parquet)")]
+pub struct ParseExportFormatError(String);
+
+impl FromStr for ExportFormat {
+    type Err = ParseExportFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| ParseExportFormatError(s.to_string()))
+    }
+}
+
+/// Error returned when an export cannot be written
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+    #[error("Parquet exports need the bot to be built with the `parquet` feature")]
+    ParquetDisabled,
+    #[error("Failed to write Parquet: {0}")]
+    Parquet(String),
+}
+
+/// Writes aggregated totals in an export format.
+///
+/// # Arguments
+/// * `totals` - The totals per period and type.
+/// * `format` - The file format.
+///
+/// # Returns
+/// The file contents, or an `ExportError` if the format is unavailable.
+pub fn encode_aggregates(
+    totals: &[PeriodTypeTotal],
+    format: ExportFormat,
+) -> Result<Vec<u8>, ExportError> {
+    match format {
+        ExportFormat::Csv => Ok(aggregate_csv(totals).into_bytes()),
+        ExportFormat::Parquet => aggregate_parquet(totals),
+    }
+}
+
+/// Writes aggregated totals as CSV.
+///
+/// # Arguments
+/// * `totals` - The totals per period and type.
+///
+/// # Returns
+/// The CSV document, with a header row and one row per total.
+pub fn aggregate_csv(totals: &[PeriodTypeTotal]) -> String {
+    let mut csv = format!("{}\n", CSV_HEADER);
+
+    for total in totals {
+        csv.push_str(&format!(
+            "{},{},{}\n",
+            total.period,
+            csv_field(&total.type_name),
+            total.total_quantity
+        ));
+    }
+
+    csv
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Writes aggregated totals as Parquet, with the schema described in the
+/// module documentation.
+///
+/// # Arguments
+/// * `totals` - The totals per period and type.
+///
+/// # Returns
+/// The Parquet file, or an `ExportError` if it cannot be written.
+#[cfg(feature = "parquet")]
+pub fn aggregate_parquet(totals: &[PeriodTypeTotal]) -> Result<Vec<u8>, ExportError> {
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, Date32Array, Int64Array, RecordBatch, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use chrono::NaiveDate;
+    use parquet::arrow::ArrowWriter;
+    use parquet::basic::Compression;
+    use parquet::file::properties::WriterProperties;
+
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).expect("valid date");
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("period", DataType::Date32, false),
+        Field::new("type", DataType::Utf8, false),
+        Field::new("quantity", DataType::Int64, false),
+    ]));
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Date32Array::from_iter_values(totals.iter().map(|total| {
+            // Dates within Date32's range of days since the epoch
+            (total.period - epoch).num_days() as i32
+        }))),
+        Arc::new(StringArray::from_iter_values(
+            totals.iter().map(|total| total.type_name.as_str()),
+        )),
+        Arc::new(Int64Array::from_iter_values(
+            totals.iter().map(|total| total.total_quantity),
+        )),
+    ];
+    let batch = RecordBatch::try_new(schema.clone(), columns)
+        .map_err(|e| ExportError::Parquet(e.to_string()))?;
+
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut buffer = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut buffer, schema, Some(properties))
+        .map_err(|e| ExportError::Parquet(e.to_string()))?;
+    writer
+        .write(&batch)
+        .map_err(|e| ExportError::Parquet(e.to_string()))?;
+    writer
+        .close()
+        .map_err(|e| ExportError::Parquet(e.to_string()))?;
+
+    Ok(buffer)
+}
+
+/// Writes aggregated totals as Parquet.
+///
+/// # Arguments
+/// * `totals` - The totals per period and type.
+///
+/// # Returns
+/// Always `ExportError::ParquetDisabled`, since the `parquet` feature is not enabled.
+#[cfg(not(feature = "parquet"))]
+pub fn aggregate_parquet(_totals: &[PeriodTypeTotal]) -> Result<Vec<u8>, ExportError> {
+    Err(ExportError::ParquetDisabled)
+}
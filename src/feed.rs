@@ -0,0 +1,309 @@
+//! Personal Atom feeds of milestones and weekly summaries.
+//!
+//! `feed url` issues a member a secret feed URL served by the HTTP module at
+//! `/feed/{token}`, so they can pipe their progress into a dashboard or blog.
+//! The feed lists the smoke-free milestones the member reached, recorded by a
+//! subscriber of `MilestoneReached` events, and a summary of each of the last
+//! `FEED_WEEKS` completed weeks. The weekly summaries are also what the
+//! email digest sends.
+
+use std::{cmp::Reverse, sync::Arc};
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use poise::serenity_prelude::futures::lock::Mutex;
+use tracing::warn;
+
+use crate::clock::Clock;
+use crate::commands::stats::format_delta;
+use crate::database::{Database, ReachedMilestone, UserTypeTotal};
+use crate::event_bus::{DomainEvent, EventBus};
+use crate::format::{branding, format_count, format_date, Locale};
+use crate::periods::{compare, SummarySpan};
+use crate::presets::format_price;
+use crate::rollover;
+
+/// Number of completed weeks summarized in a feed
+pub const FEED_WEEKS: i64 = 8;
+
+/// Maximum number of milestones listed in a feed
+const FEED_MILESTONES: i64 = 20;
+
+/// Namespace of the URNs identifying feeds and their entries
+const URN_PREFIX: &str = "urn:cigarette-counter";
+
+/// An entry of a member's feed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedEntry {
+    /// The permanent ID of the entry
+    pub id: String,
+    /// The entry title
+    pub title: String,
+    /// When the entry was last updated
+    pub updated: DateTime<Utc>,
+    /// The lines of the entry text
+    pub lines: Vec<String>,
+}
+
+/// Builds the entries of a member's feed.
+///
+/// # Arguments
+/// * `database` - Database connection shared with the bot.
+/// * `discord_id` - The Discord ID of the feed owner.
+/// * `today` - The current local date.
+/// * `timezone` - The time zone weeks are counted in.
+///
+/// # Returns
+/// A Result containing the entries, newest first, or an `Error`.
+pub async fn feed_entries(
+    database: &Mutex<Database>,
+    discord_id: &str,
+    today: NaiveDate,
+    timezone: Tz,
+) -> Result<Vec<FeedEntry>, sqlx::Error> {
+    let locale = Locale::default();
+    let db = database.lock().await;
+    let mut entries: Vec<FeedEntry> = db
+        .get_reached_milestones(discord_id, FEED_MILESTONES)
+        .await?
+        .iter()
+        .map(|milestone| milestone_entry(discord_id, milestone, locale))
+        .collect();
+
+    // The oldest week is only fetched to compare the one after it with.
+    let this_week = SummarySpan::Week.start(today);
+    let mut weeks = Vec::new();
+    for weeks_ago in 1..=FEED_WEEKS + 1 {
+        let week_start = this_week - Duration::weeks(weeks_ago);
+        let totals = db
+            .get_weekly_summary(discord_id, week_start, timezone)
+            .await?;
+        weeks.push((week_start, totals));
+    }
+    drop(db);
+
+    for pair in weeks.windows(2) {
+        let [(week_start, totals), (_, previous)] = pair else {
+            continue;
+        };
+        entries.extend(week_entry(
+            discord_id,
+            *week_start,
+            previous,
+            totals,
+            timezone,
+            locale,
+        ));
+    }
+
+    entries.sort_by_key(|entry| Reverse(entry.updated));
+    Ok(entries)
+}
+
+/// Builds the summary of a completed week, as listed in feeds and sent in email digests.
+///
+/// # Arguments
+/// * `db` - The database.
+/// * `discord_id` - The Discord ID of the member.
+/// * `week_start` - The Monday of the week.
+/// * `timezone` - The time zone weeks are counted in.
+///
+/// # Returns
+/// A Result containing the summary, `None` if neither the week nor the week before it had logs, or an `Error`.
+pub async fn week_summary(
+    db: &Database,
+    discord_id: &str,
+    week_start: NaiveDate,
+    timezone: Tz,
+) -> Result<Option<FeedEntry>, sqlx::Error> {
+    let totals = db
+        .get_weekly_summary(discord_id, week_start, timezone)
+        .await?;
+    let previous = db
+        .get_weekly_summary(discord_id, week_start - Duration::weeks(1), timezone)
+        .await?;
+
+    Ok(week_entry(
+        discord_id,
+        week_start,
+        &previous,
+        &totals,
+        timezone,
+        Locale::default(),
+    ))
+}
+
+/// Builds the feed entry of a reached milestone.
+fn milestone_entry(discord_id: &str, milestone: &ReachedMilestone, locale: Locale) -> FeedEntry {
+    FeedEntry {
+        id: format!(
+            "{}:milestone:{}:{}:{}",
+            URN_PREFIX, discord_id, milestone.smoke_free_days, milestone.reached_on
+        ),
+        title: format!("禁煙{}日を達成しました", milestone.smoke_free_days),
+        updated: milestone.reached_at,
+        lines: vec![format!(
+            "{}に{}日間の禁煙を達成しました。",
+            format_date(milestone.reached_on, locale),
+            milestone.smoke_free_days
+        )],
+    }
+}
+
+/// Builds the feed entry summarizing a completed week against the week before it,
+/// or `None` if neither week had logs.
+fn week_entry(
+    discord_id: &str,
+    week_start: NaiveDate,
+    previous: &[UserTypeTotal],
+    totals: &[UserTypeTotal],
+    timezone: Tz,
+    locale: Locale,
+) -> Option<FeedEntry> {
+    let comparison = compare(previous, totals);
+    if comparison.types.is_empty() {
+        return None;
+    }
+    let updated = rollover::start_of_day(timezone, week_start + Duration::weeks(1));
+    let unit = &branding().count_unit;
+    let week_end = week_start + Duration::days(6);
+
+    let mut lines = vec![
+        format!(
+            "合計: {}（前週比 {}{}）",
+            format_count(comparison.second_total, locale),
+            format_delta(comparison.second_total - comparison.first_total, locale),
+            unit
+        ),
+        format!(
+            "1日平均: {:.1}{}",
+            comparison.second_total as f64 / 7.0,
+            unit
+        ),
+    ];
+    for delta in &comparison.types {
+        lines.push(format!(
+            "- {}: {}（{}{}）",
+            delta.name,
+            format_count(delta.second, locale),
+            format_delta(delta.second - delta.first, locale),
+            unit
+        ));
+    }
+    for cost in &comparison.costs {
+        lines.push(format!(
+            "費用: {}",
+            format_price(cost.second, &cost.currency)
+        ));
+    }
+
+    Some(FeedEntry {
+        id: format!("{}:week:{}:{}", URN_PREFIX, discord_id, week_start),
+        title: format!(
+            "{}〜{}の週: {}",
+            format_date(week_start, locale),
+            format_date(week_end, locale),
+            format_count(comparison.second_total, locale)
+        ),
+        updated,
+        lines,
+    })
+}
+
+/// Escapes text for inclusion in an XML document.
+///
+/// # Arguments
+/// * `text` - The text to escape.
+///
+/// # Returns
+/// The escaped text.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Renders a member's feed as an Atom document.
+///
+/// # Arguments
+/// * `discord_id` - The Discord ID of the feed owner.
+/// * `entries` - The entries, newest first.
+/// * `now` - The current time, used as the update time of an empty feed.
+///
+/// # Returns
+/// The Atom XML.
+pub fn render_atom(discord_id: &str, entries: &[FeedEntry], now: DateTime<Utc>) -> String {
+    let name = escape_xml(&branding().name);
+    let updated = entries.first().map_or(now, |entry| entry.updated);
+
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+         <id>{}:feed:{}</id>\n\
+         <title>{}</title>\n\
+         <updated>{}</updated>\n\
+         <author><name>{}</name></author>\n",
+        URN_PREFIX,
+        discord_id,
+        name,
+        updated.to_rfc3339(),
+        name
+    );
+    for entry in entries {
+        xml.push_str(&format!(
+            "<entry>\n\
+             <id>{}</id>\n\
+             <title>{}</title>\n\
+             <updated>{}</updated>\n\
+             <content type=\"text\">{}</content>\n\
+             </entry>\n",
+            escape_xml(&entry.id),
+            escape_xml(&entry.title),
+            entry.updated.to_rfc3339(),
+            escape_xml(&entry.lines.join("\n"))
+        ));
+    }
+    xml.push_str("</feed>\n");
+
+    xml
+}
+
+/// Subscribes the task recording reached milestones for the feeds to the event bus.
+///
+/// # Arguments
+/// * `events` - The event bus.
+/// * `database` - Database connection shared with the bot.
+/// * `clock` - The clock determining the date milestones are reached on.
+pub fn spawn_milestone_recorder(
+    events: &EventBus,
+    database: Arc<Mutex<Database>>,
+    clock: Arc<dyn Clock>,
+) {
+    events.spawn_subscriber("feed milestones", move |event| {
+        let database = database.clone();
+        let clock = clock.clone();
+
+        async move {
+            let DomainEvent::MilestoneReached {
+                discord_id,
+                smoke_free_days,
+                ..
+            } = event
+            else {
+                return;
+            };
+            let now = clock.now();
+
+            if let Err(e) = database
+                .lock()
+                .await
+                .record_milestone(&discord_id, smoke_free_days, clock.local_date(now), now)
+                .await
+            {
+                warn!("Failed to record milestone of {}: {}", discord_id, e);
+            }
+        }
+    });
+}
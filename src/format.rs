@@ -0,0 +1,240 @@
+//! Locale-aware formatting of numbers and dates shown to users.
+//!
+//! Message text is Japanese throughout, but numbers and dates follow the
+//! locale of the user's Discord client so that e.g. `2024/05/01` reads as
+//! `May 1, 2024` for English users. Every command, page and digest that
+//! shows a count or a date formats it through this module.
+//!
+//! The deployment's `Branding` lives here too: the bot's name shown on
+//! panels and in embed footers, the unit counts are shown with, the embed
+//! color and the fallback emoji of panel buttons. Forks counting coffee or
+//! energy drinks set them in the environment (see `Config`) instead of
+//! editing the code.
+
+use std::sync::OnceLock;
+
+use chrono::{Datelike, NaiveDate, NaiveTime};
+
+use crate::database::DailySmokingSummary;
+use crate::embed::{BRAND_COLOR, FOOTER_TEXT};
+
+/// The branding set at startup
+static BRANDING: OnceLock<Branding> = OnceLock::new();
+
+/// How a deployment presents the bot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Branding {
+    /// The bot's name, shown on panels and in embed footers
+    pub name: String,
+    /// The unit counts are shown with (e.g. `本` in `3本`)
+    pub count_unit: String,
+    /// The color of the side bar of the bot's embeds, as RGB
+    pub color: u32,
+    /// The emoji shown on panel buttons of types without their own, if any
+    pub emoji: Option<String>,
+}
+
+impl Default for Branding {
+    fn default() -> Self {
+        Self {
+            name: FOOTER_TEXT.to_string(),
+            count_unit: "本".to_string(),
+            color: BRAND_COLOR,
+            emoji: None,
+        }
+    }
+}
+
+/// Sets the deployment's branding. Only the first call has an effect.
+///
+/// # Arguments
+/// * `branding` - The branding.
+pub fn set_branding(branding: Branding) {
+    let _ = BRANDING.set(branding);
+}
+
+/// Returns the deployment's branding, the default if none was set.
+pub fn branding() -> &'static Branding {
+    BRANDING.get_or_init(Branding::default)
+}
+
+/// A locale numbers and dates can be formatted for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    /// Japanese: `2024/05/01`, `1,234`
+    #[default]
+    Japanese,
+    /// American English: `May 1, 2024`, `1,234`
+    EnglishUs,
+    /// British English: `1 May 2024`, `1,234`
+    EnglishGb,
+    /// German: `01.05.2024`, `1.234`
+    German,
+    /// French: `01/05/2024`, `1 234`
+    French,
+}
+
+/// English month names
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+impl Locale {
+    /// Resolves a Discord locale tag (e.g. `ja`, `en-US`).
+    ///
+    /// # Arguments
+    /// * `tag` - The locale tag reported by Discord, if any.
+    ///
+    /// # Returns
+    /// The matching `Locale`, or the default (Japanese) for unknown or missing tags.
+    pub fn from_discord(tag: Option<&str>) -> Self {
+        match tag {
+            Some("en-US") => Self::EnglishUs,
+            Some("en-GB") => Self::EnglishGb,
+            Some("de") => Self::German,
+            Some("fr") => Self::French,
+            _ => Self::Japanese,
+        }
+    }
+
+    /// Returns the thousands separator of the locale.
+    fn thousands_separator(self) -> &'static str {
+        match self {
+            Self::Japanese | Self::EnglishUs | Self::EnglishGb => ",",
+            Self::German => ".",
+            Self::French => "\u{202F}",
+        }
+    }
+}
+
+/// Formats an integer with the locale's thousands separator.
+///
+/// # Arguments
+/// * `value` - The number to format.
+/// * `locale` - The locale to format for.
+///
+/// # Returns
+/// The formatted number (e.g. `12,345`).
+pub fn format_number(value: i64, locale: Locale) -> String {
+    let digits = value.unsigned_abs().to_string();
+    let mut grouped = String::new();
+
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push_str(locale.thousands_separator());
+        }
+        grouped.push(digit);
+    }
+
+    if value < 0 {
+        format!("-{}", grouped)
+    } else {
+        grouped
+    }
+}
+
+/// Formats a calendar date in the locale's conventional short form.
+///
+/// # Arguments
+/// * `date` - The date to format.
+/// * `locale` - The locale to format for.
+///
+/// # Returns
+/// The formatted date (e.g. `2024/05/01` or `May 1, 2024`).
+pub fn format_date(date: NaiveDate, locale: Locale) -> String {
+    let month_name = MONTHS[date.month0() as usize];
+
+    match locale {
+        Locale::Japanese => date.format("%Y/%m/%d").to_string(),
+        Locale::EnglishUs => format!("{} {}, {}", month_name, date.day(), date.year()),
+        Locale::EnglishGb => format!("{} {} {}", date.day(), month_name, date.year()),
+        Locale::German => date.format("%d.%m.%Y").to_string(),
+        Locale::French => date.format("%d/%m/%Y").to_string(),
+    }
+}
+
+/// Formats a cigarette count with the branding's unit.
+///
+/// # Arguments
+/// * `count` - The number of cigarettes.
+/// * `locale` - The locale to format for.
+///
+/// # Returns
+/// The formatted count (e.g. `1,234本`).
+pub fn format_count(count: i64, locale: Locale) -> String {
+    format_quantity(count, &branding().count_unit, locale)
+}
+
+/// Formats a quantity counted in a given unit.
+///
+/// # Arguments
+/// * `count` - The quantity.
+/// * `unit` - The unit (e.g. a category's `杯`).
+/// * `locale` - The locale to format for.
+///
+/// # Returns
+/// The formatted quantity (e.g. `3杯`).
+pub fn format_quantity(count: i64, unit: &str, locale: Locale) -> String {
+    format!("{}{}", format_number(count, locale), unit)
+}
+
+/// Formats the short confirmation a quiet user's presses edit into the day's first confirmation.
+///
+/// # Arguments
+/// * `today_total` - The user's total for the day.
+/// * `last_at` - The local time of the latest press.
+/// * `locale` - The locale to format for.
+///
+/// # Returns
+/// The confirmation (e.g. `今日: 8本, 最終 14:32`).
+pub fn format_quiet_confirmation(today_total: i64, last_at: NaiveTime, locale: Locale) -> String {
+    format!(
+        "今日: {}, 最終 {}",
+        format_count(today_total, locale),
+        last_at.format("%H:%M")
+    )
+}
+
+/// Formats the heading of a daily summary.
+///
+/// # Arguments
+/// * `date` - The date of the summary.
+/// * `locale` - The locale to format for.
+///
+/// # Returns
+/// The heading (e.g. `本日（2024/05/01）の累計本数`).
+pub fn format_summary_heading(date: NaiveDate, locale: Locale) -> String {
+    format!("本日（{}）の累計本数", format_date(date, locale))
+}
+
+/// Formats the lines of a daily summary, one per smoking type.
+///
+/// # Arguments
+/// * `daily_summary` - The summary rows.
+/// * `locale` - The locale to format for.
+///
+/// # Returns
+/// One line per row (e.g. `紙タバコ: 3本`).
+pub fn format_summary_lines(daily_summary: &[DailySmokingSummary], locale: Locale) -> Vec<String> {
+    daily_summary
+        .iter()
+        .map(|summary| {
+            format!(
+                "{}: {}",
+                summary.description,
+                format_count(summary.total_quantity.unwrap_or_default(), locale)
+            )
+        })
+        .collect()
+}
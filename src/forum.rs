@@ -0,0 +1,277 @@
+//! Weekly log threads in a forum channel.
+//!
+//! Guilds that set a forum channel get a thread per week, opened by a
+//! recurring job. Its first post is the digest of the week before, ending
+//! with the smoke-free board if members joined it, followed by a panel;
+//! threads of earlier weeks are archived once the new one is open, so
+//! long-running servers keep a single active log thread. Weeks start on
+//! Monday in the guild's time zone.
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{Datelike, NaiveDate};
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+use tracing::warn;
+
+use crate::clock::Clock;
+use crate::commands::panel::create_cigarette_buttons;
+use crate::commands::stats::type_total_lines;
+use crate::database::{Database, ForumChannel, GuildTypeTotal, ScheduledJob};
+use crate::format::{branding, format_date, Locale};
+use crate::jobs::JobHandler;
+use crate::rollover::start_of_day;
+use crate::smoke_free::{digest_section, rank_streaks};
+use crate::Error;
+
+/// Scheduled job kind opening and archiving weekly threads
+pub const WEEKLY_THREAD_JOB: &str = "forum_weekly_threads";
+
+/// Interval between checks for a new week
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Prefix of the IDs of panels posted in weekly threads
+pub const FORUM_PANEL_PREFIX: &str = "forum-";
+
+/// Returns the Monday of a date's week.
+///
+/// # Arguments
+/// * `date` - The date.
+///
+/// # Returns
+/// The first day of the week.
+pub fn week_start(date: NaiveDate) -> NaiveDate {
+    date - chrono::Duration::days(date.weekday().num_days_from_monday().into())
+}
+
+/// Returns the name of a week's thread.
+///
+/// # Arguments
+/// * `week_start` - The Monday of the week.
+///
+/// # Returns
+/// The thread name, with the ISO week number (e.g. `2024年 第23週の喫煙ログ`).
+pub fn thread_name(week_start: NaiveDate) -> String {
+    let week = week_start.iso_week();
+
+    format!("{}年 第{}週の喫煙ログ", week.year(), week.week())
+}
+
+/// Returns the ID of the panel posted in a weekly thread.
+///
+/// # Arguments
+/// * `thread_id` - The ID of the thread.
+///
+/// # Returns
+/// The panel ID.
+pub fn forum_panel_id(thread_id: &str) -> String {
+    format!("{}{}", FORUM_PANEL_PREFIX, thread_id)
+}
+
+/// Renders the digest opening a week's thread.
+///
+/// # Arguments
+/// * `week_start` - The Monday of the new week.
+/// * `totals` - The guild's totals per smoking type over the week before.
+///
+/// # Returns
+/// The digest.
+pub fn weekly_digest(week_start: NaiveDate, totals: &[GuildTypeTotal]) -> String {
+    let heading = format!(
+        "先週（{}〜{}）の集計",
+        format_date(week_start - chrono::Duration::days(7), Locale::Japanese),
+        format_date(week_start - chrono::Duration::days(1), Locale::Japanese)
+    );
+
+    type_total_lines(heading, totals, Locale::Japanese).join("\n")
+}
+
+/// Job handler opening each week's thread and archiving the earlier ones every `CHECK_INTERVAL`
+pub struct WeeklyThreadJob {
+    http: Arc<serenity::Http>,
+    database: Arc<Mutex<Database>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl WeeklyThreadJob {
+    /// Creates the handler.
+    ///
+    /// # Arguments
+    /// * `http` - The Discord HTTP client.
+    /// * `database` - Database connection shared with the bot.
+    /// * `clock` - The clock deciding the current week.
+    pub fn new(
+        http: Arc<serenity::Http>,
+        database: Arc<Mutex<Database>>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            http,
+            database,
+            clock,
+        }
+    }
+
+    /// Opens the current week's thread of a guild if needed and archives the earlier ones.
+    ///
+    /// # Arguments
+    /// * `forum` - The guild's forum channel.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    async fn update_guild(&self, forum: &ForumChannel) -> Result<(), Error> {
+        let timezone = {
+            let db = self.database.lock().await;
+            db.guild(&forum.guild_id).get_timezone().await?
+        }
+        .unwrap_or(self.clock.timezone());
+        let week = week_start(self.clock.now().with_timezone(&timezone).date_naive());
+
+        let existing = {
+            let db = self.database.lock().await;
+            db.guild(&forum.guild_id).get_forum_thread(week).await?
+        };
+        let thread_id = match existing {
+            Some(thread_id) => thread_id,
+            None => {
+                let (totals, board) = {
+                    let db = self.database.lock().await;
+                    let guild = db.guild(&forum.guild_id);
+                    (
+                        guild
+                            .get_type_totals(
+                                start_of_day(timezone, week - chrono::Duration::days(7)),
+                                start_of_day(timezone, week),
+                            )
+                            .await?,
+                        guild.get_smoke_free_board().await?,
+                    )
+                };
+                let today = self.clock.now().with_timezone(&timezone).date_naive();
+                let mut digest = weekly_digest(week, &totals);
+                if let Some(section) = digest_section(&rank_streaks(&board, today, timezone)) {
+                    digest = format!("{}\n\n{}", digest, section);
+                }
+                let thread = serenity::ChannelId::new(forum.channel_id.parse()?)
+                    .create_forum_post(
+                        &self.http,
+                        serenity::CreateForumPost::new(
+                            thread_name(week),
+                            // The board mentions its members without pinging them every week.
+                            serenity::CreateMessage::new()
+                                .content(digest)
+                                .allowed_mentions(serenity::CreateAllowedMentions::new()),
+                        )
+                        .auto_archive_duration(serenity::AutoArchiveDuration::OneWeek),
+                    )
+                    .await?;
+                let thread_id = thread.id.to_string();
+                self.database
+                    .lock()
+                    .await
+                    .guild(&forum.guild_id)
+                    .record_forum_thread(week, &thread_id)
+                    .await?;
+                thread_id
+            }
+        };
+        // Checked apart from the thread, so a failed post is retried in the same thread.
+        self.post_panel(&forum.guild_id, &thread_id).await?;
+
+        let earlier = {
+            let db = self.database.lock().await;
+            db.guild(&forum.guild_id)
+                .get_open_forum_threads_before(week)
+                .await?
+        };
+        for thread in earlier {
+            if let Err(e) = serenity::ChannelId::new(thread.thread_id.parse()?)
+                .edit_thread(&self.http, serenity::EditThread::new().archived(true))
+                .await
+            {
+                warn!(
+                    "Failed to archive weekly thread {} in {}: {}",
+                    thread.thread_id, forum.guild_id, e
+                );
+                continue;
+            }
+            self.database
+                .lock()
+                .await
+                .guild(&forum.guild_id)
+                .mark_forum_thread_archived(thread.week_start, self.clock.now())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Posts the panel of a weekly thread unless it was posted already.
+    ///
+    /// # Arguments
+    /// * `guild_id` - The ID of the guild.
+    /// * `thread_id` - The ID of the thread.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    async fn post_panel(&self, guild_id: &str, thread_id: &str) -> Result<(), Error> {
+        let panel_id = forum_panel_id(thread_id);
+        let (buttons, smoking_type_ids) = {
+            let db = self.database.lock().await;
+            if db.get_panel(&panel_id, Some(guild_id)).await?.is_some() {
+                return Ok(());
+            }
+            let smoking_type_ids = db
+                .guild(guild_id)
+                .get_settings()
+                .await?
+                .panel_smoking_type_ids;
+            (
+                create_cigarette_buttons(&db, &panel_id, smoking_type_ids.as_deref()).await?,
+                smoking_type_ids,
+            )
+        };
+
+        let message = serenity::ChannelId::new(thread_id.parse()?)
+            .send_message(
+                &self.http,
+                serenity::CreateMessage::new()
+                    .content(&branding().name)
+                    .components(vec![serenity::CreateActionRow::Buttons(buttons)]),
+            )
+            .await?;
+        let db = self.database.lock().await;
+        db.create_panel(
+            &panel_id,
+            Some(guild_id),
+            thread_id,
+            smoking_type_ids.as_deref(),
+        )
+        .await?;
+        db.set_panel_message(&panel_id, &message.id.to_string())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl JobHandler for WeeklyThreadJob {
+    async fn run(&self, _job: &ScheduledJob) -> Result<(), Error> {
+        let forums = self.database.lock().await.get_forum_channels().await?;
+        for forum in forums {
+            if let Err(e) = self.update_guild(&forum).await {
+                warn!(
+                    "Failed to update the weekly thread of {}: {}",
+                    forum.guild_id, e
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn repeat_every(&self) -> Option<Duration> {
+        Some(CHECK_INTERVAL)
+    }
+}
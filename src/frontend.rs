@@ -0,0 +1,536 @@
+//! The Discord reply surface used by command and interaction handlers.
+//!
+//! Handlers talk to Discord only through the `Frontend` trait, so they can be
+//! exercised with a recording fake instead of a live gateway. Commands use the
+//! implementation for the poise `Context`; component interactions use
+//! `InteractionFrontend`. Both send a reply's content as an embed in the
+//! bot's style (see `embed`), followed by the embeds attached to it.
+
+use async_trait::async_trait;
+use poise::serenity_prelude as serenity;
+use poise::CreateReply;
+
+use crate::deferral::{self, Delivery};
+use crate::embed::{ReplyEmbed, Title};
+use crate::format::Locale;
+use crate::{Context, Error};
+
+/// A button attached to a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplyButton {
+    /// The custom ID reported when the button is pressed
+    pub custom_id: String,
+    /// The button label
+    pub label: String,
+}
+
+/// What a select menu offers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplySelectKind {
+    /// Fixed options as pairs of value and label
+    String(Vec<(String, String)>),
+    /// The guild's text channels
+    Channel,
+}
+
+/// A select menu attached to a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplySelect {
+    /// The custom ID reported when an option is chosen
+    pub custom_id: String,
+    /// The text shown while nothing is chosen
+    pub placeholder: String,
+    /// What the menu offers
+    pub kind: ReplySelectKind,
+    /// How many options can be chosen at once
+    pub max_values: u8,
+}
+
+/// A message shown to the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reply {
+    /// The message content, shown as the body of the reply's embed
+    pub content: String,
+    /// What the reply is about, shown as the title of its embed
+    pub title: Option<Title>,
+    /// Whether only the invoking user can see the message
+    pub ephemeral: bool,
+    /// Buttons shown below the message
+    pub buttons: Vec<ReplyButton>,
+    /// Select menus shown below the buttons
+    pub selects: Vec<ReplySelect>,
+    /// Embeds shown below the content
+    pub embeds: Vec<ReplyEmbed>,
+}
+
+impl Reply {
+    /// Creates a reply visible to everyone in the channel.
+    ///
+    /// # Arguments
+    /// * `content` - The message content.
+    pub fn new(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+            title: None,
+            ephemeral: false,
+            buttons: Vec::new(),
+            selects: Vec::new(),
+            embeds: Vec::new(),
+        }
+    }
+
+    /// Creates a reply telling the user that their request was rejected or failed.
+    ///
+    /// # Arguments
+    /// * `content` - What went wrong.
+    pub fn error(content: impl Into<String>) -> Self {
+        Self::new(content).titled(Title::Error)
+    }
+
+    /// Sets what the reply is about.
+    ///
+    /// # Arguments
+    /// * `title` - The title of the reply's embed.
+    pub fn titled(mut self, title: Title) -> Self {
+        self.title = Some(title);
+        self
+    }
+
+    /// Makes the reply visible only to the invoking user, where Discord supports it.
+    pub fn ephemeral(mut self) -> Self {
+        self.ephemeral = true;
+        self
+    }
+
+    /// Shows an embed below the content.
+    ///
+    /// # Arguments
+    /// * `embed` - The embed.
+    pub fn embed(mut self, embed: ReplyEmbed) -> Self {
+        self.embeds.push(embed);
+        self
+    }
+
+    /// Adds a button below the message.
+    ///
+    /// # Arguments
+    /// * `custom_id` - The custom ID reported when the button is pressed.
+    /// * `label` - The button label.
+    pub fn button(mut self, custom_id: impl Into<String>, label: impl Into<String>) -> Self {
+        self.buttons.push(ReplyButton {
+            custom_id: custom_id.into(),
+            label: label.into(),
+        });
+        self
+    }
+
+    /// Adds a select menu of one option below the message.
+    ///
+    /// # Arguments
+    /// * `custom_id` - The custom ID reported when an option is chosen.
+    /// * `placeholder` - The text shown while nothing is chosen.
+    /// * `options` - The options as pairs of value and label.
+    pub fn select(
+        self,
+        custom_id: impl Into<String>,
+        placeholder: impl Into<String>,
+        options: Vec<(String, String)>,
+    ) -> Self {
+        self.add_select(custom_id, placeholder, ReplySelectKind::String(options), 1)
+    }
+
+    /// Adds a select menu below the message in which any number of options can be chosen.
+    ///
+    /// # Arguments
+    /// * `custom_id` - The custom ID reported when options are chosen.
+    /// * `placeholder` - The text shown while nothing is chosen.
+    /// * `options` - The options as pairs of value and label.
+    pub fn multi_select(
+        self,
+        custom_id: impl Into<String>,
+        placeholder: impl Into<String>,
+        options: Vec<(String, String)>,
+    ) -> Self {
+        let max_values = u8::try_from(options.len()).unwrap_or(u8::MAX).max(1);
+        self.add_select(
+            custom_id,
+            placeholder,
+            ReplySelectKind::String(options),
+            max_values,
+        )
+    }
+
+    /// Adds a select menu of one of the guild's text channels below the message.
+    ///
+    /// # Arguments
+    /// * `custom_id` - The custom ID reported when a channel is chosen.
+    /// * `placeholder` - The text shown while nothing is chosen.
+    pub fn channel_select(
+        self,
+        custom_id: impl Into<String>,
+        placeholder: impl Into<String>,
+    ) -> Self {
+        self.add_select(custom_id, placeholder, ReplySelectKind::Channel, 1)
+    }
+
+    /// Adds a select menu below the message.
+    fn add_select(
+        mut self,
+        custom_id: impl Into<String>,
+        placeholder: impl Into<String>,
+        kind: ReplySelectKind,
+        max_values: u8,
+    ) -> Self {
+        self.selects.push(ReplySelect {
+            custom_id: custom_id.into(),
+            placeholder: placeholder.into(),
+            kind,
+            max_values,
+        });
+        self
+    }
+
+    /// Builds the Discord components of the reply: one row of buttons, then one row per select menu.
+    pub fn components(&self) -> Vec<serenity::CreateActionRow> {
+        let mut rows = Vec::new();
+        if !self.buttons.is_empty() {
+            let buttons = self
+                .buttons
+                .iter()
+                .map(|button| {
+                    serenity::CreateButton::new(&button.custom_id)
+                        .style(serenity::ButtonStyle::Secondary)
+                        .label(&button.label)
+                })
+                .collect();
+            rows.push(serenity::CreateActionRow::Buttons(buttons));
+        }
+        for select in &self.selects {
+            let kind = match &select.kind {
+                ReplySelectKind::String(options) => serenity::CreateSelectMenuKind::String {
+                    options: options
+                        .iter()
+                        .map(|(value, label)| serenity::CreateSelectMenuOption::new(label, value))
+                        .collect(),
+                },
+                ReplySelectKind::Channel => serenity::CreateSelectMenuKind::Channel {
+                    channel_types: Some(vec![serenity::ChannelType::Text]),
+                    default_channels: None,
+                },
+            };
+            rows.push(serenity::CreateActionRow::SelectMenu(
+                serenity::CreateSelectMenu::new(&select.custom_id, kind)
+                    .placeholder(&select.placeholder)
+                    .max_values(select.max_values),
+            ));
+        }
+
+        rows
+    }
+
+    /// Lists the embeds of the reply: its content in the bot's style, then the attached embeds.
+    ///
+    /// # Arguments
+    /// * `locale` - The locale the titles are shown in.
+    pub fn styled_embeds(&self, locale: Locale) -> Vec<ReplyEmbed> {
+        let body = (!self.content.is_empty())
+            .then(|| ReplyEmbed::styled(self.title, self.content.clone(), locale));
+
+        body.into_iter()
+            .chain(self.embeds.iter().cloned())
+            .collect()
+    }
+
+    /// Builds the Discord embeds of the reply.
+    ///
+    /// # Arguments
+    /// * `locale` - The locale the titles are shown in.
+    pub fn embeds(&self, locale: Locale) -> Vec<serenity::CreateEmbed> {
+        self.styled_embeds(locale)
+            .iter()
+            .map(ReplyEmbed::to_discord)
+            .collect()
+    }
+}
+
+/// Where handlers send their output
+#[async_trait]
+pub trait Frontend: Send + Sync {
+    /// Returns the locale numbers and dates should be formatted for.
+    fn locale(&self) -> Locale;
+
+    /// Returns the channel of the current command or interaction, if it has one.
+    fn channel_id(&self) -> Option<serenity::ChannelId>;
+
+    /// Sends a message in reply to the current command or interaction.
+    ///
+    /// # Arguments
+    /// * `reply` - The message to send.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    async fn send_reply(&self, reply: Reply) -> Result<(), Error>;
+
+    /// Responds to the current interaction. Outside interactions this is the same as `send_reply`.
+    ///
+    /// # Arguments
+    /// * `reply` - The response message.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    async fn respond(&self, reply: Reply) -> Result<(), Error>;
+
+    /// Responds like `respond` with a message visible to everyone and returns its ID.
+    ///
+    /// # Arguments
+    /// * `reply` - The response message.
+    ///
+    /// # Returns
+    /// A Result containing the ID of the message, `None` if it cannot be edited later, or an `Error`.
+    async fn respond_editable(&self, reply: Reply) -> Result<Option<serenity::MessageId>, Error>;
+
+    /// Acknowledges the current interaction without sending a message.
+    /// Outside interactions this does nothing.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    async fn acknowledge(&self) -> Result<(), Error>;
+
+    /// Confirms the current command or interaction with a reaction instead of a message.
+    ///
+    /// # Arguments
+    /// * `emoji` - The emoji to react with.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    async fn react(&self, emoji: serenity::ReactionType) -> Result<(), Error>;
+
+    /// Replaces the embeds of a message previously sent in the current channel.
+    ///
+    /// # Arguments
+    /// * `message_id` - The message to edit.
+    /// * `reply` - The new message; its buttons and visibility are ignored.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    async fn edit_message(
+        &self,
+        message_id: serenity::MessageId,
+        reply: Reply,
+    ) -> Result<(), Error>;
+}
+
+#[async_trait]
+impl Frontend for Context<'_> {
+    fn locale(&self) -> Locale {
+        Locale::from_discord(poise::Context::locale(*self))
+    }
+
+    fn channel_id(&self) -> Option<serenity::ChannelId> {
+        Some(poise::Context::channel_id(*self))
+    }
+
+    async fn send_reply(&self, reply: Reply) -> Result<(), Error> {
+        self.send(
+            CreateReply {
+                embeds: reply.embeds(Frontend::locale(self)),
+                ..Default::default()
+            }
+            .components(reply.components())
+            .ephemeral(reply.ephemeral),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn respond(&self, reply: Reply) -> Result<(), Error> {
+        self.send_reply(reply).await
+    }
+
+    async fn respond_editable(&self, reply: Reply) -> Result<Option<serenity::MessageId>, Error> {
+        let handle = self
+            .send(
+                CreateReply {
+                    embeds: reply.embeds(Frontend::locale(self)),
+                    ..Default::default()
+                }
+                .components(reply.components()),
+            )
+            .await?;
+
+        Ok(Some(handle.message().await?.id))
+    }
+
+    async fn acknowledge(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Reacts to the invoking message; application commands have none, so the emoji is sent instead.
+    async fn react(&self, emoji: serenity::ReactionType) -> Result<(), Error> {
+        match self {
+            poise::Context::Prefix(prefix) => {
+                prefix.msg.react(self, emoji).await?;
+                Ok(())
+            }
+            poise::Context::Application(_) => {
+                self.send_reply(Reply::new(emoji.to_string()).ephemeral())
+                    .await
+            }
+        }
+    }
+
+    async fn edit_message(
+        &self,
+        message_id: serenity::MessageId,
+        reply: Reply,
+    ) -> Result<(), Error> {
+        poise::Context::channel_id(*self)
+            .edit_message(
+                self,
+                message_id,
+                serenity::EditMessage::new()
+                    .content("")
+                    .embeds(reply.embeds(Frontend::locale(self))),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Frontend for a component interaction (e.g. a panel button press)
+pub struct InteractionFrontend<'a> {
+    ctx: &'a serenity::Context,
+    interaction: &'a serenity::ComponentInteraction,
+}
+
+impl<'a> InteractionFrontend<'a> {
+    /// Creates a frontend for a component interaction.
+    ///
+    /// # Arguments
+    /// * `ctx` - The serenity context.
+    /// * `interaction` - The component interaction being handled.
+    pub fn new(
+        ctx: &'a serenity::Context,
+        interaction: &'a serenity::ComponentInteraction,
+    ) -> Self {
+        Self { ctx, interaction }
+    }
+
+    /// Replaces the message the component belongs to, e.g. to redraw a view in place.
+    ///
+    /// # Arguments
+    /// * `reply` - The new message; its visibility is ignored.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn update(&self, reply: Reply) -> Result<(), Error> {
+        let components = reply.components();
+        let embeds = reply.embeds(self.locale());
+
+        deferral::update(
+            self.ctx,
+            self.interaction,
+            String::new(),
+            components,
+            embeds,
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl Frontend for InteractionFrontend<'_> {
+    fn locale(&self) -> Locale {
+        Locale::from_discord(Some(&self.interaction.locale))
+    }
+
+    fn channel_id(&self) -> Option<serenity::ChannelId> {
+        Some(self.interaction.channel_id)
+    }
+
+    /// Sends a follow-up message. The interaction must already have been responded to.
+    async fn send_reply(&self, reply: Reply) -> Result<(), Error> {
+        self.interaction
+            .create_followup(
+                self.ctx,
+                serenity::CreateInteractionResponseFollowup::new()
+                    .embeds(reply.embeds(self.locale()))
+                    .components(reply.components())
+                    .ephemeral(reply.ephemeral),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Responds to the interaction, or sends a follow-up if it was deferred.
+    async fn respond(&self, reply: Reply) -> Result<(), Error> {
+        let components = reply.components();
+        let embeds = reply.embeds(self.locale());
+
+        deferral::respond(
+            self.ctx,
+            self.interaction,
+            String::new(),
+            components,
+            embeds,
+            reply.ephemeral,
+        )
+        .await
+    }
+
+    async fn respond_editable(&self, reply: Reply) -> Result<Option<serenity::MessageId>, Error> {
+        let components = reply.components();
+        let embeds = reply.embeds(self.locale());
+        let message_id = deferral::respond_message(
+            self.ctx,
+            self.interaction,
+            String::new(),
+            components,
+            embeds,
+        )
+        .await?;
+
+        Ok(Some(message_id))
+    }
+
+    /// Acknowledges the interaction unless it was already deferred.
+    async fn acknowledge(&self) -> Result<(), Error> {
+        if deferral::begin_response() == Delivery::Response {
+            self.interaction
+                .create_response(self.ctx, serenity::CreateInteractionResponse::Acknowledge)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Acknowledges the interaction without a message and reacts to the message holding the component.
+    async fn react(&self, emoji: serenity::ReactionType) -> Result<(), Error> {
+        self.acknowledge().await?;
+        self.interaction.message.react(self.ctx, emoji).await?;
+
+        Ok(())
+    }
+
+    async fn edit_message(
+        &self,
+        message_id: serenity::MessageId,
+        reply: Reply,
+    ) -> Result<(), Error> {
+        self.interaction
+            .channel_id
+            .edit_message(
+                self.ctx,
+                message_id,
+                serenity::EditMessage::new()
+                    .content("")
+                    .embeds(reply.embeds(self.locale())),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+//! Restricting the bot to an allowlist of guilds.
+//!
+//! With `ALLOWED_GUILDS` set, the bot only operates in the listed guilds, so
+//! a personal instance invited by strangers does not collect their data.
+//! When it joins (or finds itself in, at startup) any other guild it posts
+//! `NOT_ALLOWED_MESSAGE` in the guild's system channel and leaves. Commands
+//! used in such guilds, e.g. through a user install, are refused with the
+//! same notice. Without the variable every guild is allowed.
+
+use poise::serenity_prelude::{self as serenity, CreateMessage};
+use tracing::{info, warn};
+
+use crate::{Data, Error};
+
+/// Notice posted in, and replied to commands from, guilds that are not allowed
+pub const NOT_ALLOWED_MESSAGE: &str =
+    "このボットは許可されたサーバーでのみ利用できます。導入したい場合はボットの管理者に連絡してください。";
+
+/// Error returned for commands used in a guild that is not allowed
+#[derive(Debug, thiserror::Error)]
+#[error("Guild is not on the allowlist")]
+pub struct GuildNotAllowed;
+
+/// Checks whether the bot may operate in a guild.
+///
+/// # Arguments
+/// * `allowed_guilds` - The allowed guild IDs; empty to allow every guild.
+/// * `guild_id` - The guild, or `None` outside guilds (which are always allowed).
+///
+/// # Returns
+/// `Ok` if the bot may operate there, or `GuildNotAllowed`.
+pub fn check(
+    allowed_guilds: &[u64],
+    guild_id: Option<serenity::GuildId>,
+) -> Result<(), GuildNotAllowed> {
+    match guild_id {
+        Some(guild_id)
+            if !allowed_guilds.is_empty() && !allowed_guilds.contains(&guild_id.get()) =>
+        {
+            Err(GuildNotAllowed)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Leaves a guild the bot joined unless it is allowed, posting a notice first.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `guild` - The guild.
+///
+/// # Returns
+/// A Result containing whether the bot stays in the guild, or an `Error`.
+pub(crate) async fn admit_guild(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild: &serenity::Guild,
+) -> Result<bool, Error> {
+    if check(&data.config.allowed_guilds, Some(guild.id)).is_ok() {
+        return Ok(true);
+    }
+
+    if let Some(channel_id) = guild.system_channel_id {
+        if let Err(e) = channel_id
+            .send_message(ctx, CreateMessage::new().content(NOT_ALLOWED_MESSAGE))
+            .await
+        {
+            warn!(
+                "Failed to post the allowlist notice in guild {}: {}",
+                guild.id, e
+            );
+        }
+    }
+    guild.id.leave(ctx).await?;
+    info!("Left guild {}, which is not on the allowlist", guild.id);
+
+    Ok(false)
+}
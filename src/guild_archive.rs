@@ -0,0 +1,522 @@
+//! Guild data archives, for moving a guild to another bot instance.
+//!
+//! `guild export` writes everything the bot stores about a guild into one
+//! versioned JSON document: its settings, the smoking types it can use, and
+//! its statistics aggregated per month and type. Raw logs are included only
+//! for members who turned on sharing in `settings`; everyone else is only
+//! part of the aggregates. Types are referred to by type name, so the
+//! archive does not depend on the IDs of the instance that wrote it.
+//!
+//! Like other data leaving the bot, user and guild IDs are anonymized when
+//! `ANONYMIZE_SALT` is set; such archives cannot be imported.
+//!
+//! `admin import` reads an archive into another instance. The whole archive
+//! is checked first: every type it uses must be included, IDs must be Discord
+//! IDs, and the shared logs must fit within the monthly totals. Types are
+//! matched by name and added when missing, so the IDs of the writing
+//! instance never leak into the new one. Only a guild without logs can be
+//! imported into, so importing twice cannot duplicate logs. The aggregates
+//! of members who did not share their logs cannot be turned back into logs
+//! and are only reported.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use poise::serenity_prelude::futures::lock::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::database::{Database, ImportedLog, TypeDetails};
+use crate::rollover;
+use crate::templates::{Template, TemplateKey};
+use crate::Error;
+
+/// Value of the `format` field identifying a guild archive
+pub const ARCHIVE_FORMAT: &str = "cigarette-counter/guild";
+
+/// Version of the archive layout written by this build
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// Everything the bot stores about a guild
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuildArchive {
+    /// Always `ARCHIVE_FORMAT`
+    pub format: String,
+    /// The layout version, `ARCHIVE_VERSION` when written by this build
+    pub version: u32,
+    /// The ID of the exported guild
+    pub guild_id: String,
+    /// When the archive was written
+    pub exported_at: DateTime<Utc>,
+    /// The guild's settings
+    pub settings: ArchivedSettings,
+    /// The smoking types known to the instance
+    pub types: Vec<ArchivedType>,
+    /// The time zone the monthly totals were counted in
+    pub totals_timezone: String,
+    /// The guild's logs totalled per month and type
+    pub monthly_totals: Vec<ArchivedMonthlyTotal>,
+    /// The members who share their raw logs, with those logs
+    pub members: Vec<ArchivedMember>,
+}
+
+/// A guild's settings
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedSettings {
+    /// The guild's time zone, if it chose one
+    pub timezone: Option<String>,
+    /// The channel chosen for the panel in the setup wizard
+    pub panel_channel_id: Option<String>,
+    /// The type names shown on panels by default, or `None` for all types
+    pub panel_types: Option<Vec<String>>,
+    /// The age-gate message members must acknowledge, if enabled
+    pub acknowledgment_policy: Option<String>,
+    /// The emoji confirming logs instead of a message, if set
+    pub confirmation_reaction: Option<String>,
+    /// The smoke-break voice channels
+    pub smoke_break_channels: Vec<String>,
+    /// The milestone roles
+    pub milestone_roles: Vec<ArchivedMilestoneRole>,
+    /// The daily caps per type
+    pub type_caps: Vec<ArchivedTypeCap>,
+    /// The overridden message templates
+    pub message_templates: Vec<ArchivedTemplate>,
+}
+
+/// A smoking type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedType {
+    pub type_name: String,
+    pub description: Option<String>,
+    pub emoji: Option<String>,
+    pub unit: Option<String>,
+    pub typical_price: Option<i32>,
+    pub currency: Option<String>,
+}
+
+/// A role granted after a number of smoke-free days
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedMilestoneRole {
+    pub role_id: String,
+    pub smoke_free_days: i32,
+}
+
+/// The daily cap of a type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedTypeCap {
+    pub type_name: String,
+    pub daily_cap: i32,
+}
+
+/// An overridden message template
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedTemplate {
+    pub template_key: String,
+    pub template: String,
+}
+
+/// The total of one type in one month
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedMonthlyTotal {
+    /// The first day of the month
+    pub month: NaiveDate,
+    pub type_name: String,
+    pub total_quantity: i64,
+    pub member_count: i64,
+}
+
+/// A member who shares their raw logs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedMember {
+    pub discord_id: String,
+    /// The username, left out when IDs are anonymized
+    pub username: Option<String>,
+    pub logs: Vec<ArchivedLog>,
+}
+
+/// A raw log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedLog {
+    pub type_name: String,
+    pub quantity: i32,
+    pub smoked_at: DateTime<Utc>,
+}
+
+/// Collects a guild's data into an archive.
+///
+/// # Arguments
+/// * `database` - The database.
+/// * `guild_id` - The ID of the guild.
+/// * `default` - The bot's time zone, used for the monthly totals while the guild chose none.
+/// * `now` - The time the archive is written.
+///
+/// # Returns
+/// A Result containing the archive or an `Error`.
+pub async fn export_guild(
+    database: &Mutex<Database>,
+    guild_id: &str,
+    default: Tz,
+    now: DateTime<Utc>,
+) -> Result<GuildArchive, Error> {
+    let db = database.lock().await;
+    let guild = db.guild(guild_id);
+    let anonymizer = db.anonymizer();
+
+    let stored = guild.get_settings().await?;
+    let timezone = guild.get_timezone().await?;
+    let panel_types = match stored.panel_smoking_type_ids {
+        Some(ids) => {
+            let mut names = Vec::with_capacity(ids.len());
+            for id in ids {
+                names.push(db.get_smoking_type(id).await?.type_name);
+            }
+            Some(names)
+        }
+        None => None,
+    };
+    let mut type_caps = Vec::new();
+    for cap in guild.get_type_caps().await? {
+        type_caps.push(ArchivedTypeCap {
+            type_name: db.get_smoking_type(cap.smoking_type_id).await?.type_name,
+            daily_cap: cap.daily_cap,
+        });
+    }
+    let settings = ArchivedSettings {
+        timezone: timezone.map(|timezone| timezone.name().to_string()),
+        panel_channel_id: stored.panel_channel_id,
+        panel_types,
+        acknowledgment_policy: guild.get_acknowledgment_policy().await?,
+        confirmation_reaction: guild.get_confirmation_reaction().await?,
+        smoke_break_channels: guild.get_smoke_break_channels().await?,
+        milestone_roles: guild
+            .get_milestone_roles()
+            .await?
+            .into_iter()
+            .map(|role| ArchivedMilestoneRole {
+                role_id: role.role_id,
+                smoke_free_days: role.smoke_free_days,
+            })
+            .collect(),
+        type_caps,
+        message_templates: guild
+            .get_message_templates()
+            .await?
+            .into_iter()
+            .map(|template| ArchivedTemplate {
+                template_key: template.template_key,
+                template: template.template,
+            })
+            .collect(),
+    };
+
+    let types = db
+        .get_smoking_types()
+        .await?
+        .into_iter()
+        .map(|smoking_type| ArchivedType {
+            type_name: smoking_type.type_name,
+            description: smoking_type.description,
+            emoji: smoking_type.emoji,
+            unit: smoking_type.unit,
+            typical_price: smoking_type.typical_price,
+            currency: smoking_type.currency,
+        })
+        .collect();
+    let totals_timezone = timezone.unwrap_or(default);
+    let monthly_totals = guild
+        .get_monthly_type_totals(totals_timezone)
+        .await?
+        .into_iter()
+        .map(|total| ArchivedMonthlyTotal {
+            month: total.month,
+            type_name: total.type_name,
+            total_quantity: total.total_quantity,
+            member_count: total.member_count,
+        })
+        .collect();
+
+    let mut members: Vec<ArchivedMember> = Vec::new();
+    for log in guild.get_shared_logs().await? {
+        let discord_id = anonymizer.id(&log.discord_id);
+        let archived = ArchivedLog {
+            type_name: log.type_name,
+            quantity: log.quantity,
+            smoked_at: log.smoked_at,
+        };
+        // Logs are ordered by member, so a member's logs are adjacent.
+        match members.last_mut() {
+            Some(member) if member.discord_id == discord_id => member.logs.push(archived),
+            _ => members.push(ArchivedMember {
+                discord_id,
+                username: (!anonymizer.is_enabled()).then_some(log.username),
+                logs: vec![archived],
+            }),
+        }
+    }
+
+    Ok(GuildArchive {
+        format: ARCHIVE_FORMAT.to_string(),
+        version: ARCHIVE_VERSION,
+        guild_id: anonymizer.id(guild_id),
+        exported_at: now,
+        settings,
+        types,
+        totals_timezone: totals_timezone.name().to_string(),
+        monthly_totals,
+        members,
+    })
+}
+
+/// Why an archive cannot be imported
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("Not a guild archive")]
+    UnknownFormat,
+    #[error("Unsupported archive version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Invalid Discord ID {0} (archives with anonymized IDs cannot be imported)")]
+    InvalidId(String),
+    #[error("Unknown time zone: {0}")]
+    UnknownTimezone(String),
+    #[error("Invalid message template {0}")]
+    InvalidTemplate(String),
+    #[error("Smoking type {0} is used but not included in the archive")]
+    MissingType(String),
+    #[error("Shared logs of {type_name} in {month} exceed the month's total")]
+    TotalsMismatch { type_name: String, month: NaiveDate },
+    #[error("Guild {0} already has logs on this instance")]
+    GuildNotEmpty(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// What an import carried over
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportSummary {
+    /// The ID of the imported guild
+    pub guild_id: String,
+    /// How many smoking types were added to this instance
+    pub types_added: usize,
+    /// How many members' logs were imported
+    pub members: usize,
+    /// How many logs were imported
+    pub logs: u64,
+    /// The units in the monthly totals not covered by imported logs
+    pub aggregate_only_quantity: i64,
+}
+
+/// Checks whether an ID is a Discord snowflake rather than an anonymized digest.
+fn is_discord_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 20 && id.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Checks that an archive can be imported without leaving dangling references.
+///
+/// # Arguments
+/// * `archive` - The archive.
+///
+/// # Returns
+/// `Ok` if the archive is consistent, or the first problem found.
+pub fn check_archive(archive: &GuildArchive) -> Result<(), ImportError> {
+    if archive.format != ARCHIVE_FORMAT {
+        return Err(ImportError::UnknownFormat);
+    }
+    if archive.version != ARCHIVE_VERSION {
+        return Err(ImportError::UnsupportedVersion(archive.version));
+    }
+
+    let settings = &archive.settings;
+    let ids = std::iter::once(&archive.guild_id)
+        .chain(&settings.panel_channel_id)
+        .chain(&settings.smoke_break_channels)
+        .chain(settings.milestone_roles.iter().map(|role| &role.role_id))
+        .chain(archive.members.iter().map(|member| &member.discord_id));
+    for id in ids {
+        if !is_discord_id(id) {
+            return Err(ImportError::InvalidId(id.clone()));
+        }
+    }
+    for timezone in settings.timezone.iter().chain([&archive.totals_timezone]) {
+        if timezone.parse::<Tz>().is_err() {
+            return Err(ImportError::UnknownTimezone(timezone.clone()));
+        }
+    }
+    for template in &settings.message_templates {
+        let valid = template
+            .template_key
+            .parse::<TemplateKey>()
+            .is_ok_and(|key| Template::parse(key, &template.template).is_ok());
+        if !valid {
+            return Err(ImportError::InvalidTemplate(template.template_key.clone()));
+        }
+    }
+
+    let used = settings
+        .panel_types
+        .iter()
+        .flatten()
+        .chain(settings.type_caps.iter().map(|cap| &cap.type_name))
+        .chain(archive.monthly_totals.iter().map(|total| &total.type_name))
+        .chain(
+            archive
+                .members
+                .iter()
+                .flat_map(|member| member.logs.iter().map(|log| &log.type_name)),
+        );
+    for type_name in used {
+        if !archive
+            .types
+            .iter()
+            .any(|archived| &archived.type_name == type_name)
+        {
+            return Err(ImportError::MissingType(type_name.clone()));
+        }
+    }
+
+    let timezone = archive.totals_timezone.parse::<Tz>().unwrap_or(Tz::UTC);
+    for ((month, type_name), shared) in shared_monthly_totals(archive, timezone) {
+        let total = archive
+            .monthly_totals
+            .iter()
+            .find(|total| total.month == month && total.type_name == type_name)
+            .map_or(0, |total| total.total_quantity);
+        if shared > total {
+            return Err(ImportError::TotalsMismatch { type_name, month });
+        }
+    }
+
+    Ok(())
+}
+
+/// Totals the shared logs of an archive per month and type.
+///
+/// # Arguments
+/// * `archive` - The archive.
+/// * `timezone` - The time zone the archive's months are counted in.
+///
+/// # Returns
+/// The totals keyed by the first day of the month and the type name.
+fn shared_monthly_totals(
+    archive: &GuildArchive,
+    timezone: Tz,
+) -> HashMap<(NaiveDate, String), i64> {
+    let mut totals = HashMap::new();
+    for log in archive.members.iter().flat_map(|member| &member.logs) {
+        let date = log.smoked_at.with_timezone(&timezone).date_naive();
+        let month = rollover::month_start(date);
+        *totals.entry((month, log.type_name.clone())).or_default() += i64::from(log.quantity);
+    }
+
+    totals
+}
+
+/// Imports a checked archive into this instance.
+///
+/// # Arguments
+/// * `database` - The database.
+/// * `archive` - The archive.
+///
+/// # Returns
+/// A Result containing what was imported, or an `ImportError` if the archive
+/// is inconsistent or the guild already has logs here.
+pub async fn import_guild(
+    database: &Mutex<Database>,
+    archive: &GuildArchive,
+) -> Result<ImportSummary, ImportError> {
+    check_archive(archive)?;
+
+    let db = database.lock().await;
+    let guild = db.guild(&archive.guild_id);
+    if !guild.get_user_ids().await?.is_empty() {
+        return Err(ImportError::GuildNotEmpty(archive.guild_id.clone()));
+    }
+
+    let details: Vec<TypeDetails<'_>> = archive
+        .types
+        .iter()
+        .map(|archived| TypeDetails {
+            type_name: &archived.type_name,
+            description: archived.description.as_deref(),
+            emoji: archived.emoji.as_deref(),
+            unit: archived.unit.as_deref(),
+            typical_price: archived.typical_price,
+            currency: archived.currency.as_deref(),
+        })
+        .collect();
+    let ensured = db.ensure_smoking_types(&details).await?;
+    let type_ids: HashMap<&str, i32> = archive
+        .types
+        .iter()
+        .map(|archived| archived.type_name.as_str())
+        .zip(ensured.type_ids.iter().copied())
+        .collect();
+    // Every used type was checked to be in the archive.
+    let type_id = |type_name: &str| type_ids[type_name];
+
+    let settings = &archive.settings;
+    if let Some(timezone) = &settings.timezone {
+        guild.set_timezone(timezone.parse::<Tz>().ok()).await?;
+    }
+    if let Some(channel_id) = &settings.panel_channel_id {
+        guild.set_panel_channel(channel_id).await?;
+    }
+    if let Some(panel_types) = &settings.panel_types {
+        let ids: Vec<i32> = panel_types.iter().map(|name| type_id(name)).collect();
+        guild.set_panel_types(Some(&ids)).await?;
+    }
+    if let Some(policy) = &settings.acknowledgment_policy {
+        guild.set_acknowledgment_policy(policy).await?;
+    }
+    if let Some(emoji) = &settings.confirmation_reaction {
+        guild.set_confirmation_reaction(emoji).await?;
+    }
+    for channel_id in &settings.smoke_break_channels {
+        guild.add_smoke_break_channel(channel_id).await?;
+    }
+    for role in &settings.milestone_roles {
+        guild
+            .set_milestone_role(&role.role_id, role.smoke_free_days)
+            .await?;
+    }
+    for cap in &settings.type_caps {
+        guild
+            .set_type_cap(type_id(&cap.type_name), cap.daily_cap)
+            .await?;
+    }
+    for template in &settings.message_templates {
+        guild
+            .set_message_template(&template.template_key, &template.template, None)
+            .await?;
+    }
+
+    let mut logs = Vec::new();
+    for member in &archive.members {
+        db.get_or_create_user(
+            &member.discord_id,
+            member.username.as_deref().unwrap_or(&member.discord_id),
+        )
+        .await?;
+        logs.extend(member.logs.iter().map(|log| ImportedLog {
+            discord_id: member.discord_id.clone(),
+            smoking_type_id: type_id(&log.type_name),
+            quantity: log.quantity,
+            smoked_at: log.smoked_at,
+        }));
+    }
+    let imported = guild.import_logs(&logs).await?;
+
+    let total: i64 = archive
+        .monthly_totals
+        .iter()
+        .map(|total| total.total_quantity)
+        .sum();
+    let shared: i64 = logs.iter().map(|log| i64::from(log.quantity)).sum();
+
+    Ok(ImportSummary {
+        guild_id: archive.guild_id.clone(),
+        types_added: ensured.added,
+        members: archive.members.len(),
+        logs: imported,
+        aggregate_only_quantity: total - shared,
+    })
+}
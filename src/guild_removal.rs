@@ -0,0 +1,171 @@
+//! Handling of guilds the bot is removed from.
+//!
+//! When the bot is kicked from a guild (or the guild is deleted), Discord
+//! sends a `GuildDelete` that is not marked unavailable; outages send one
+//! that is, and are ignored. The guild is then marked as removed, which
+//! leaves its panels, counters, challenges and other scheduled work out of
+//! the background tasks. With `GUILD_DATA_RETENTION_DAYS` set, a recurring
+//! job deletes the guild's data once that grace period passes; members'
+//! logs are kept but no longer count towards the guild. Rejoining the guild
+//! before then clears the removal and resumes everything as it was.
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+use tracing::info;
+
+use crate::clock::Clock;
+use crate::database::{Database, ScheduledJob};
+use crate::jobs::JobHandler;
+use crate::{Data, Error};
+
+/// Scheduled job kind deleting the data of removed guilds
+pub const GUILD_DATA_DELETION_JOB: &str = "guild_data_deletion";
+
+/// Interval between runs of the deletion
+const DELETION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Marks a guild as removed, scheduling the deletion of its data if retention is limited.
+///
+/// # Arguments
+/// * `database` - The database.
+/// * `guild_id` - The ID of the guild.
+/// * `now` - When the bot was removed.
+/// * `retention` - How long the guild's data is kept, or `None` to keep it.
+///
+/// # Returns
+/// A Result containing when the data will be deleted, if ever, or an `Error`.
+pub async fn mark_guild_removed(
+    database: &Mutex<Database>,
+    guild_id: &str,
+    now: DateTime<Utc>,
+    retention: Option<Duration>,
+) -> Result<Option<DateTime<Utc>>, Error> {
+    let delete_after = retention
+        .map(chrono::Duration::from_std)
+        .transpose()?
+        .map(|retention| now + retention);
+    database
+        .lock()
+        .await
+        .guild(guild_id)
+        .mark_removed(now, delete_after)
+        .await?;
+
+    Ok(delete_after)
+}
+
+/// Deletes the data of every removed guild whose grace period passed.
+///
+/// # Arguments
+/// * `database` - The database.
+/// * `now` - The current time.
+///
+/// # Returns
+/// A Result containing the IDs of the guilds whose data was deleted, or an `Error`.
+pub async fn delete_due_guilds(
+    database: &Mutex<Database>,
+    now: DateTime<Utc>,
+) -> Result<Vec<String>, Error> {
+    let db = database.lock().await;
+    let mut deleted = Vec::new();
+    for guild_id in db.get_due_guild_deletions(now).await? {
+        if db.guild(&guild_id).delete_removed_data(now).await? {
+            deleted.push(guild_id);
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// Marks the guild of a `GuildDelete` as removed, unless it is only unavailable.
+///
+/// # Arguments
+/// * `data` - The shared application state.
+/// * `incomplete` - The guild the event is about.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub(crate) async fn handle_guild_delete(
+    data: &Data,
+    incomplete: &serenity::UnavailableGuild,
+) -> Result<(), Error> {
+    if incomplete.unavailable {
+        return Ok(());
+    }
+
+    let delete_after = mark_guild_removed(
+        &data.database,
+        &incomplete.id.to_string(),
+        data.clock.now(),
+        data.config.guild_data_retention,
+    )
+    .await?;
+    match delete_after {
+        Some(at) => info!(
+            "Removed from guild {}; its data will be deleted after {}",
+            incomplete.id,
+            at.to_rfc3339()
+        ),
+        None => info!("Removed from guild {}; its data is kept", incomplete.id),
+    }
+
+    Ok(())
+}
+
+/// Resumes a guild the bot was removed from when it is back.
+///
+/// # Arguments
+/// * `data` - The shared application state.
+/// * `guild` - The guild.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub(crate) async fn handle_guild_return(data: &Data, guild: &serenity::Guild) -> Result<(), Error> {
+    if data
+        .database
+        .lock()
+        .await
+        .guild(&guild.id.to_string())
+        .unmark_removed()
+        .await?
+    {
+        info!("Back in guild {}; its background work resumes", guild.id);
+    }
+
+    Ok(())
+}
+
+/// Job handler deleting the data of removed guilds every `DELETION_INTERVAL`
+pub struct GuildDataDeletionJob {
+    database: Arc<Mutex<Database>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl GuildDataDeletionJob {
+    /// Creates the handler.
+    ///
+    /// # Arguments
+    /// * `database` - Database connection shared with the bot.
+    /// * `clock` - The clock deciding which grace periods passed.
+    pub fn new(database: Arc<Mutex<Database>>, clock: Arc<dyn Clock>) -> Self {
+        Self { database, clock }
+    }
+}
+
+#[async_trait]
+impl JobHandler for GuildDataDeletionJob {
+    async fn run(&self, _job: &ScheduledJob) -> Result<(), Error> {
+        for guild_id in delete_due_guilds(&self.database, self.clock.now()).await? {
+            info!("Deleted the data of removed guild {}", guild_id);
+        }
+
+        Ok(())
+    }
+
+    fn repeat_every(&self) -> Option<Duration> {
+        Some(DELETION_INTERVAL)
+    }
+}
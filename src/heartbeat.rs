@@ -0,0 +1,219 @@
+//! Periodic liveness reporting.
+//!
+//! A background task probes the database every `HEARTBEAT_INTERVAL`, reads
+//! the gateway latency of the shards and measures how far the scheduled job
+//! worker is behind. Each beat is logged and exposed on `/metrics`, so
+//! operators can alert on silent stalls: a dead job worker shows up as a
+//! growing scheduler lag, a hung database as a stale last successful query.
+//! The `uptime` command shows the same report.
+
+use std::{
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::{futures::lock::Mutex, ShardManager};
+use tracing::{info, warn};
+
+use crate::clock::Clock;
+use crate::database::Database;
+
+/// Interval between heartbeats
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Scheduler lag above which a heartbeat is logged as a warning
+const STALL_THRESHOLD: Duration = Duration::from_secs(5 * 60);
+
+/// Marker stored in the gauges below while a value is unknown
+const UNKNOWN: i64 = -1;
+
+/// When the process started
+static STARTED_AT: OnceLock<Instant> = OnceLock::new();
+
+/// Unix time of the last successful database probe, in seconds
+static LAST_DB_SUCCESS: AtomicI64 = AtomicI64::new(UNKNOWN);
+
+/// Average gateway latency of the shards at the last heartbeat, in milliseconds
+static GATEWAY_LATENCY_MS: AtomicI64 = AtomicI64::new(UNKNOWN);
+
+/// How long the longest-overdue job had been waiting at the last heartbeat, in seconds
+static SCHEDULER_LAG_SECS: AtomicI64 = AtomicI64::new(UNKNOWN);
+
+/// A snapshot of the bot's liveness
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeartbeatReport {
+    /// How long the process has been running
+    pub uptime: Duration,
+    /// The gateway latency, or `None` if no heartbeat has been acknowledged yet
+    pub gateway_latency: Option<Duration>,
+    /// When a database query last succeeded, or `None` if none has yet
+    pub last_db_success: Option<DateTime<Utc>>,
+    /// How long the longest-overdue job has been waiting, or `None` if unknown
+    pub scheduler_lag: Option<Duration>,
+}
+
+/// Records the start of the process. Only the first call has an effect.
+pub fn mark_started() {
+    let _ = STARTED_AT.set(Instant::now());
+}
+
+/// Returns how long the process has been running.
+pub fn uptime() -> Duration {
+    STARTED_AT.get_or_init(Instant::now).elapsed()
+}
+
+/// Probes the database and measures the scheduler lag, recording both.
+///
+/// # Arguments
+/// * `database` - The database.
+/// * `clock` - The clock the lag is measured with.
+///
+/// # Returns
+/// A Result containing the scheduler lag, or an `Error` if the database is unreachable.
+pub async fn probe(database: &Mutex<Database>, clock: &dyn Clock) -> Result<Duration, sqlx::Error> {
+    let now = clock.now();
+    let oldest_due = database.lock().await.get_oldest_due_job(now).await?;
+
+    let lag = oldest_due
+        .and_then(|run_at| (now - run_at).to_std().ok())
+        .unwrap_or_default();
+    LAST_DB_SUCCESS.store(now.timestamp(), Ordering::Relaxed);
+    SCHEDULER_LAG_SECS.store(lag.as_secs() as i64, Ordering::Relaxed);
+
+    Ok(lag)
+}
+
+/// Records the gateway latency measured at a heartbeat.
+///
+/// # Arguments
+/// * `latency` - The latency, or `None` if no shard has reported one.
+pub fn record_gateway_latency(latency: Option<Duration>) {
+    let millis = latency.map_or(UNKNOWN, |latency| latency.as_millis() as i64);
+    GATEWAY_LATENCY_MS.store(millis, Ordering::Relaxed);
+}
+
+/// Returns the values recorded at the last heartbeat.
+pub fn report() -> HeartbeatReport {
+    let known = |value: i64| (value != UNKNOWN).then_some(value);
+
+    HeartbeatReport {
+        uptime: uptime(),
+        gateway_latency: known(GATEWAY_LATENCY_MS.load(Ordering::Relaxed))
+            .map(|millis| Duration::from_millis(millis as u64)),
+        last_db_success: known(LAST_DB_SUCCESS.load(Ordering::Relaxed))
+            .and_then(|seconds| DateTime::from_timestamp(seconds, 0)),
+        scheduler_lag: known(SCHEDULER_LAG_SECS.load(Ordering::Relaxed))
+            .map(|seconds| Duration::from_secs(seconds as u64)),
+    }
+}
+
+/// Returns the average gateway latency of the shards that have reported one.
+///
+/// # Arguments
+/// * `shard_manager` - The shard manager of the client.
+///
+/// # Returns
+/// The latency, or `None` if no shard has reported one.
+async fn gateway_latency(shard_manager: &ShardManager) -> Option<Duration> {
+    let runners = shard_manager.runners.lock().await;
+    let latencies: Vec<Duration> = runners
+        .values()
+        .filter_map(|runner| runner.latency)
+        .collect();
+    if latencies.is_empty() {
+        return None;
+    }
+
+    Some(latencies.iter().sum::<Duration>() / latencies.len() as u32)
+}
+
+/// Spawns the background task recording and logging a heartbeat every `HEARTBEAT_INTERVAL`.
+///
+/// # Arguments
+/// * `database` - Database connection shared with the bot.
+/// * `clock` - Source of the current time.
+/// * `shard_manager` - The shard manager whose gateway latency is reported.
+pub fn spawn(
+    database: Arc<Mutex<Database>>,
+    clock: Arc<dyn Clock>,
+    shard_manager: Arc<ShardManager>,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+        loop {
+            interval.tick().await;
+            record_gateway_latency(gateway_latency(&shard_manager).await);
+            let probed = probe(&database, clock.as_ref()).await;
+            let report = report();
+            let gateway = report
+                .gateway_latency
+                .map_or("unknown".to_string(), |latency| {
+                    format!("{}ms", latency.as_millis())
+                });
+
+            match probed {
+                Ok(lag) if lag > STALL_THRESHOLD => warn!(
+                    "Heartbeat: scheduler is {}s behind (uptime {}s, gateway latency {})",
+                    lag.as_secs(),
+                    report.uptime.as_secs(),
+                    gateway
+                ),
+                Ok(lag) => info!(
+                    "Heartbeat: uptime {}s, gateway latency {}, scheduler lag {}s",
+                    report.uptime.as_secs(),
+                    gateway,
+                    lag.as_secs()
+                ),
+                Err(e) => warn!(
+                    "Heartbeat: database probe failed, last success {}: {}",
+                    report
+                        .last_db_success
+                        .map_or("never".to_string(), |at| at.to_rfc3339()),
+                    e
+                ),
+            }
+        }
+    });
+}
+
+/// Renders the heartbeat gauges in the Prometheus text exposition format.
+///
+/// Gauges whose value is not known yet are left out.
+///
+/// # Returns
+/// The metrics text.
+pub fn render_metrics() -> String {
+    let report = report();
+    let mut metrics = format!(
+        "# TYPE cigarette_counter_uptime_seconds gauge\n\
+         cigarette_counter_uptime_seconds {}\n",
+        report.uptime.as_secs()
+    );
+    if let Some(latency) = report.gateway_latency {
+        metrics.push_str(&format!(
+            "# TYPE cigarette_counter_gateway_latency_seconds gauge\n\
+             cigarette_counter_gateway_latency_seconds {:.3}\n",
+            latency.as_secs_f64()
+        ));
+    }
+    if let Some(at) = report.last_db_success {
+        metrics.push_str(&format!(
+            "# TYPE cigarette_counter_last_db_success_timestamp_seconds gauge\n\
+             cigarette_counter_last_db_success_timestamp_seconds {}\n",
+            at.timestamp()
+        ));
+    }
+    if let Some(lag) = report.scheduler_lag {
+        metrics.push_str(&format!(
+            "# TYPE cigarette_counter_scheduler_lag_seconds gauge\n\
+             cigarette_counter_scheduler_lag_seconds {}\n",
+            lag.as_secs()
+        ));
+    }
+
+    metrics
+}
@@ -0,0 +1,728 @@
+//! Inbound HTTP API for logging smoking events from external devices.
+//!
+//! Devices (e.g. a hardware "smoke button") authenticate with a per-device
+//! bearer token issued by the `register_device` command. Shortcut links
+//! (e.g. a QR code stuck on a lighter) created by `create_shortcut` log one
+//! unit when opened and show a confirmation page. Both accept an
+//! `Idempotency-Key` header so that a client retrying a request after a
+//! network error does not record the event twice. Quantities above
+//! `LARGE_QUANTITY_THRESHOLD` are rejected with 422 unless the request sets
+//! `confirm_large_quantity`, to catch typos. The Discord Linked Roles
+//! OAuth flow is served under `/linked-roles` when configured, members' Atom
+//! feeds issued by `feed url` at `/feed/{token}`, the verification and
+//! unsubscribe links of email digests under `/email`, and request latency
+//! counters are exposed at `/metrics`.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use poise::serenity_prelude::futures::lock::Mutex;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::circuit_breaker::{self, CircuitBreaker, CircuitOpen};
+use crate::clock::Clock;
+use crate::database::{Database, SmokingLog};
+use crate::db_limiter::{self, DbLimiter, Saturated};
+use crate::feed;
+use crate::format::{format_summary_heading, format_summary_lines, Locale};
+use crate::heartbeat;
+use crate::latency;
+use crate::linked_roles::LinkedRoles;
+use crate::service::{LoggingService, ServiceError};
+use crate::shards;
+
+/// Length of generated device, shortcut, feed and email tokens
+const TOKEN_LENGTH: usize = 40;
+
+/// Header carrying the client-chosen key of a log-creation request
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Maximum length of an idempotency key
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 128;
+
+/// Shared state for HTTP handlers
+#[derive(Clone)]
+struct ApiState {
+    database: Arc<Mutex<Database>>,
+    linked_roles: Option<Arc<LinkedRoles>>,
+    db_limiter: Arc<DbLimiter>,
+    db_breaker: Arc<CircuitBreaker>,
+    logging: Arc<LoggingService>,
+    clock: Arc<dyn Clock>,
+}
+
+/// Request body for `POST /api/log`
+#[derive(Debug, Deserialize)]
+struct LogRequest {
+    smoking_type_id: i32,
+    #[serde(default = "default_quantity")]
+    quantity: i32,
+    /// Set to log a quantity above `LARGE_QUANTITY_THRESHOLD`
+    #[serde(default)]
+    confirm_large_quantity: bool,
+}
+
+fn default_quantity() -> i32 {
+    1
+}
+
+/// Query parameters of the linked-roles OAuth callback
+#[derive(Debug, Deserialize)]
+struct LinkedRolesCallback {
+    code: String,
+    state: String,
+}
+
+/// Error body returned by the API
+#[derive(Debug, Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+/// Errors returned by API handlers
+#[derive(Debug, thiserror::Error)]
+enum ApiError {
+    #[error("Missing or invalid device token")]
+    Unauthorized,
+    #[error("Quantity must be positive")]
+    InvalidQuantity,
+    #[error("Quantity {quantity} is more than {threshold}; resend with confirm_large_quantity set to true if it is correct")]
+    UnconfirmedQuantity { quantity: i32, threshold: i32 },
+    #[error("Unknown smoking type: {0}")]
+    UnknownSmokingType(i32),
+    #[error("Idempotency-Key must be 1 to {MAX_IDEMPOTENCY_KEY_LENGTH} visible ASCII characters")]
+    InvalidIdempotencyKey,
+    #[error("Idempotency-Key was already used for a different request")]
+    IdempotencyKeyReused,
+    #[error("The user has not accepted the current data policy")]
+    ConsentRequired,
+    #[error("Daily cap of {cap} reached for smoking type {display_name}")]
+    DailyCapReached { display_name: String, cap: i32 },
+    #[error("Server is busy, please retry later")]
+    Busy(#[from] Saturated),
+    #[error("Database is unavailable, please retry later")]
+    Unavailable(#[from] CircuitOpen),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl From<ServiceError> for ApiError {
+    fn from(error: ServiceError) -> Self {
+        match error {
+            ServiceError::InvalidQuantity => ApiError::InvalidQuantity,
+            ServiceError::UnconfirmedQuantity {
+                quantity,
+                threshold,
+            } => ApiError::UnconfirmedQuantity {
+                quantity,
+                threshold,
+            },
+            ServiceError::UnknownSmokingType(id) => ApiError::UnknownSmokingType(id),
+            ServiceError::IdempotencyKeyReused => ApiError::IdempotencyKeyReused,
+            ServiceError::ConsentRequired => ApiError::ConsentRequired,
+            ServiceError::DailyCapReached { display_name, cap } => {
+                ApiError::DailyCapReached { display_name, cap }
+            }
+            ServiceError::Database(e) => ApiError::Database(e),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::InvalidQuantity => StatusCode::BAD_REQUEST,
+            ApiError::UnconfirmedQuantity { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::UnknownSmokingType(_) => StatusCode::NOT_FOUND,
+            ApiError::InvalidIdempotencyKey => StatusCode::BAD_REQUEST,
+            ApiError::IdempotencyKeyReused => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::ConsentRequired => StatusCode::FORBIDDEN,
+            ApiError::DailyCapReached { .. } => StatusCode::CONFLICT,
+            ApiError::Busy(_) | ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Database(ref e) => {
+                tracing::error!("HTTP API database error: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+
+        (
+            status,
+            Json(ErrorBody {
+                error: self.to_string(),
+            }),
+        )
+            .into_response()
+    }
+}
+
+/// Generates a new random device, shortcut, feed or email token.
+///
+/// # Returns
+/// A tuple of the plain-text token (shown to the user once) and its hash (stored in the database).
+pub fn generate_token() -> (String, String) {
+    let token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(TOKEN_LENGTH)
+        .map(char::from)
+        .collect();
+    let hash = hash_token(&token);
+
+    (token, hash)
+}
+
+/// Hashes a token for storage and lookup.
+///
+/// # Arguments
+/// * `token` - The plain-text token.
+///
+/// # Returns
+/// The SHA-256 digest of the token as a hex string.
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Extracts the bearer token from the `Authorization` header.
+///
+/// # Arguments
+/// * `headers` - The request headers.
+///
+/// # Returns
+/// The token if present, otherwise `None`.
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// Extracts the idempotency key from the `Idempotency-Key` header.
+///
+/// # Arguments
+/// * `headers` - The request headers.
+///
+/// # Returns
+/// A Result containing the key if present, or `ApiError::InvalidIdempotencyKey` if it is malformed.
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<&str>, ApiError> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+    let key = value
+        .to_str()
+        .map_err(|_| ApiError::InvalidIdempotencyKey)?
+        .trim();
+
+    if key.is_empty()
+        || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH
+        || !key.chars().all(|c| c.is_ascii_graphic())
+    {
+        return Err(ApiError::InvalidIdempotencyKey);
+    }
+
+    Ok(Some(key))
+}
+
+/// Handles `POST /api/log`.
+///
+/// # Arguments
+/// * `state` - The shared API state.
+/// * `headers` - The request headers, carrying the device token and optional idempotency key.
+/// * `request` - The log request body.
+///
+/// # Returns
+/// A Result containing the created `SmokingLog` as JSON (200 instead of 201 when an
+/// earlier request with the same idempotency key created it) or an `ApiError`.
+async fn post_log(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    Json(request): Json<LogRequest>,
+) -> Result<(StatusCode, Json<SmokingLog>), ApiError> {
+    let token = bearer_token(&headers).ok_or(ApiError::Unauthorized)?;
+    let idempotency_key = idempotency_key(&headers)?;
+
+    if request.quantity <= 0 {
+        return Err(ApiError::InvalidQuantity);
+    }
+
+    state.db_breaker.allow()?;
+    let _permit = state.db_limiter.acquire().await?;
+    let logged = state
+        .logging
+        .log_for_device(
+            &hash_token(token),
+            request.smoking_type_id,
+            request.quantity,
+            request.confirm_large_quantity,
+            idempotency_key,
+        )
+        .await;
+    state
+        .db_breaker
+        .record_outcome(logged.as_ref().err().map(|e| e as _));
+    let (device, logged) = logged?.ok_or(ApiError::Unauthorized)?;
+    let log = logged.log;
+
+    if logged.replayed {
+        return Ok((StatusCode::OK, Json(log)));
+    }
+
+    tracing::info!(
+        "Logged {} x type {} from device '{}' ({})",
+        log.quantity,
+        log.smoking_type_id,
+        device.device_name,
+        device.discord_id
+    );
+
+    Ok((StatusCode::CREATED, Json(log)))
+}
+
+/// Handles `GET /metrics`, exposing counters and gauges in the Prometheus text format.
+///
+/// # Arguments
+/// * `state` - The shared API state.
+///
+/// # Returns
+/// The metrics text.
+async fn get_metrics(State(state): State<ApiState>) -> String {
+    latency::render_metrics()
+        + db_limiter::render_metrics().as_str()
+        + circuit_breaker::render_metrics(&state.db_breaker).as_str()
+        + heartbeat::render_metrics().as_str()
+        + shards::render_metrics(&shards::statuses()).as_str()
+}
+
+/// Escapes text for inclusion in an HTML page.
+///
+/// # Arguments
+/// * `text` - The text to escape.
+///
+/// # Returns
+/// The escaped text.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Renders a minimal HTML page.
+///
+/// # Arguments
+/// * `status` - The HTTP status of the response.
+/// * `title` - The page heading.
+/// * `lines` - Body lines, escaped before rendering.
+///
+/// # Returns
+/// The HTML response.
+fn render_page(status: StatusCode, title: &str, lines: &[String]) -> Response {
+    let body: String = lines
+        .iter()
+        .map(|line| format!("<p>{}</p>", escape_html(line)))
+        .collect();
+
+    (
+        status,
+        Html(format!(
+            "<!DOCTYPE html><html lang=\"ja\"><head><meta charset=\"utf-8\">\
+             <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\
+             <title>{0}</title></head><body><h1>{0}</h1>{1}</body></html>",
+            escape_html(title),
+            body
+        )),
+    )
+        .into_response()
+}
+
+/// Handles `GET /s/{token}`, logging one unit for the shortcut link owner.
+///
+/// # Arguments
+/// * `state` - The shared API state.
+/// * `token` - The shortcut link token from the URL.
+/// * `headers` - The request headers, carrying the optional idempotency key.
+///
+/// # Returns
+/// An HTML confirmation or error page.
+async fn get_shortcut(
+    State(state): State<ApiState>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let result = match idempotency_key(&headers) {
+        Ok(idempotency_key) => log_shortcut(&state, &token, idempotency_key).await,
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(Some(lines)) => render_page(StatusCode::OK, "記録しました", &lines),
+        Ok(None) => render_page(
+            StatusCode::NOT_FOUND,
+            "無効なリンクです",
+            &["このショートカットリンクは存在しません。".to_string()],
+        ),
+        Err(e @ (ApiError::InvalidIdempotencyKey | ApiError::IdempotencyKeyReused)) => render_page(
+            StatusCode::BAD_REQUEST,
+            "不正なリクエストです",
+            &[e.to_string()],
+        ),
+        Err(ApiError::ConsentRequired) => render_page(
+            StatusCode::FORBIDDEN,
+            "同意が必要です",
+            &[
+                "Discordでパネルのボタンを押し、データの取り扱いに同意してから再度お試しください。"
+                    .to_string(),
+            ],
+        ),
+        Err(ApiError::Busy(_)) => render_page(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "混雑中です",
+            &["しばらくしてからもう一度お試しください。".to_string()],
+        ),
+        Err(e) => {
+            tracing::error!("Shortcut link error: {}", e);
+            render_page(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "エラー",
+                &["記録できませんでした。時間をおいて再度お試しください。".to_string()],
+            )
+        }
+    }
+}
+
+/// Logs one unit for a shortcut link and builds the confirmation page lines.
+///
+/// # Arguments
+/// * `state` - The shared API state.
+/// * `token` - The shortcut link token.
+/// * `idempotency_key` - The client-chosen key of the request, if any.
+///
+/// # Returns
+/// A Result containing the page lines, `None` if the token is unknown, or an `ApiError`.
+async fn log_shortcut(
+    state: &ApiState,
+    token: &str,
+    idempotency_key: Option<&str>,
+) -> Result<Option<Vec<String>>, ApiError> {
+    state.db_breaker.allow()?;
+    let _permit = state.db_limiter.acquire().await?;
+    let logged = state
+        .logging
+        .log_for_shortcut(&hash_token(token), idempotency_key)
+        .await;
+    state
+        .db_breaker
+        .record_outcome(logged.as_ref().err().map(|e| e as _));
+    let Some(logged) = logged? else {
+        return Ok(None);
+    };
+
+    let locale = Locale::default();
+    let mut lines = vec![format_summary_heading(logged.date, locale)];
+    lines.extend(format_summary_lines(&logged.daily_summary, locale));
+    lines.extend(logged.script_lines);
+
+    Ok(Some(lines))
+}
+
+/// Handles `GET /feed/{token}`, serving the feed owner's Atom feed.
+///
+/// # Arguments
+/// * `state` - The shared API state.
+/// * `token` - The feed token from the URL.
+///
+/// # Returns
+/// The Atom feed, or an HTML error page.
+async fn get_feed(State(state): State<ApiState>, Path(token): Path<String>) -> Response {
+    match render_feed(&state, &token).await {
+        Ok(Some(xml)) => (
+            [(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+            xml,
+        )
+            .into_response(),
+        Ok(None) => render_page(
+            StatusCode::NOT_FOUND,
+            "無効なリンクです",
+            &["このフィードは存在しないか、無効になっています。".to_string()],
+        ),
+        Err(ApiError::Busy(_) | ApiError::Unavailable(_)) => render_page(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "混雑中です",
+            &["しばらくしてからもう一度お試しください。".to_string()],
+        ),
+        Err(e) => {
+            tracing::error!("Feed error: {}", e);
+            render_page(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "エラー",
+                &["フィードを表示できませんでした。時間をおいて再度お試しください。".to_string()],
+            )
+        }
+    }
+}
+
+/// Looks up a feed by its token and renders it.
+///
+/// # Arguments
+/// * `state` - The shared API state.
+/// * `token` - The feed token.
+///
+/// # Returns
+/// A Result containing the Atom XML, `None` if the token is unknown, or an `ApiError`.
+async fn render_feed(state: &ApiState, token: &str) -> Result<Option<String>, ApiError> {
+    state.db_breaker.allow()?;
+    let _permit = state.db_limiter.acquire().await?;
+    let entries = async {
+        let owner = state
+            .database
+            .lock()
+            .await
+            .find_feed_owner(&hash_token(token))
+            .await?;
+        let Some(discord_id) = owner else {
+            return Ok(None);
+        };
+        let entries = feed::feed_entries(
+            &state.database,
+            &discord_id,
+            state.clock.today(),
+            state.clock.timezone(),
+        )
+        .await?;
+
+        Ok::<_, sqlx::Error>(Some((discord_id, entries)))
+    }
+    .await;
+    state
+        .db_breaker
+        .record_outcome(entries.as_ref().err().map(|e| e as _));
+
+    Ok(entries?
+        .map(|(discord_id, entries)| feed::render_atom(&discord_id, &entries, state.clock.now())))
+}
+
+/// Handles `GET /email/verify/{token}`, verifying the address of an email digest subscription.
+///
+/// # Arguments
+/// * `state` - The shared API state.
+/// * `token` - The verification token from the URL.
+///
+/// # Returns
+/// An HTML confirmation or error page.
+async fn get_email_verify(State(state): State<ApiState>, Path(token): Path<String>) -> Response {
+    match verify_email(&state, &token).await {
+        Ok(Some(email)) => render_page(
+            StatusCode::OK,
+            "登録しました",
+            &[format!(
+                "{} に毎週月曜日、前週のまとめをお送りします。",
+                email
+            )],
+        ),
+        Ok(None) => render_page(
+            StatusCode::NOT_FOUND,
+            "無効なリンクです",
+            &["このリンクは存在しないか、使用済みです。".to_string()],
+        ),
+        Err(e) => email_error_page(e),
+    }
+}
+
+/// Verifies the email digest subscription a verification token was sent for.
+///
+/// # Arguments
+/// * `state` - The shared API state.
+/// * `token` - The verification token.
+///
+/// # Returns
+/// A Result containing the verified address, `None` if the token is unknown or used, or an `ApiError`.
+async fn verify_email(state: &ApiState, token: &str) -> Result<Option<String>, ApiError> {
+    state.db_breaker.allow()?;
+    let _permit = state.db_limiter.acquire().await?;
+    let verified = state
+        .database
+        .lock()
+        .await
+        .verify_email(&hash_token(token), state.clock.now())
+        .await;
+    state
+        .db_breaker
+        .record_outcome(verified.as_ref().err().map(|e| e as _));
+
+    Ok(verified?)
+}
+
+/// Handles `GET /email/unsubscribe/{token}`, stopping an email digest.
+///
+/// # Arguments
+/// * `state` - The shared API state.
+/// * `token` - The unsubscribe token from the URL.
+///
+/// # Returns
+/// An HTML confirmation or error page.
+async fn get_email_unsubscribe(
+    State(state): State<ApiState>,
+    Path(token): Path<String>,
+) -> Response {
+    match unsubscribe_email(&state, &token).await {
+        Ok(Some(email)) => render_page(
+            StatusCode::OK,
+            "配信を停止しました",
+            &[format!("{} へのまとめメールの配信を停止しました。", email)],
+        ),
+        Ok(None) => render_page(
+            StatusCode::NOT_FOUND,
+            "無効なリンクです",
+            &["この配信は既に停止されています。".to_string()],
+        ),
+        Err(e) => email_error_page(e),
+    }
+}
+
+/// Removes the email digest subscription an unsubscribe token belongs to.
+///
+/// # Arguments
+/// * `state` - The shared API state.
+/// * `token` - The unsubscribe token.
+///
+/// # Returns
+/// A Result containing the unsubscribed address, `None` if the token is unknown, or an `ApiError`.
+async fn unsubscribe_email(state: &ApiState, token: &str) -> Result<Option<String>, ApiError> {
+    state.db_breaker.allow()?;
+    let _permit = state.db_limiter.acquire().await?;
+    let removed = state.database.lock().await.unsubscribe_email(token).await;
+    state
+        .db_breaker
+        .record_outcome(removed.as_ref().err().map(|e| e as _));
+
+    Ok(removed?)
+}
+
+/// Renders the page shown when an email link cannot be handled.
+fn email_error_page(error: ApiError) -> Response {
+    match error {
+        ApiError::Busy(_) | ApiError::Unavailable(_) => render_page(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "混雑中です",
+            &["しばらくしてからもう一度お試しください。".to_string()],
+        ),
+        e => {
+            tracing::error!("Email link error: {}", e);
+            render_page(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "エラー",
+                &["処理できませんでした。時間をおいて再度お試しください。".to_string()],
+            )
+        }
+    }
+}
+
+/// Handles `GET /linked-roles`, redirecting the user to Discord's OAuth consent screen.
+///
+/// # Arguments
+/// * `state` - The shared API state.
+///
+/// # Returns
+/// A redirect, or a 404 page if linked roles are not configured.
+async fn get_linked_roles(State(state): State<ApiState>) -> Response {
+    match &state.linked_roles {
+        Some(linked_roles) => Redirect::to(&linked_roles.authorize_url()).into_response(),
+        None => linked_roles_disabled(),
+    }
+}
+
+/// Handles `GET /linked-roles/callback`, completing the OAuth flow.
+///
+/// # Arguments
+/// * `state` - The shared API state.
+/// * `params` - The authorization code and state returned by Discord.
+///
+/// # Returns
+/// An HTML confirmation or error page.
+async fn get_linked_roles_callback(
+    State(state): State<ApiState>,
+    Query(params): Query<LinkedRolesCallback>,
+) -> Response {
+    let Some(linked_roles) = &state.linked_roles else {
+        return linked_roles_disabled();
+    };
+
+    match linked_roles
+        .complete_authorization(&state.database, &params.code, &params.state)
+        .await
+    {
+        Ok(username) => render_page(
+            StatusCode::OK,
+            "連携しました",
+            &[
+                format!("{} さんのアカウントを連携しました。", username),
+                "Discordに戻ってロールを確認してください。".to_string(),
+            ],
+        ),
+        Err(e) => {
+            tracing::warn!("Linked roles authorization failed: {}", e);
+            render_page(
+                StatusCode::BAD_REQUEST,
+                "連携に失敗しました",
+                &["もう一度最初からやり直してください。".to_string()],
+            )
+        }
+    }
+}
+
+/// Renders the page shown when linked roles are not configured.
+fn linked_roles_disabled() -> Response {
+    render_page(
+        StatusCode::NOT_FOUND,
+        "利用できません",
+        &["このインスタンスではリンクロールが有効になっていません。".to_string()],
+    )
+}
+
+/// Builds the HTTP API router.
+///
+/// # Arguments
+/// * `database` - Database connection shared with the bot.
+/// * `linked_roles` - The linked-roles client, if configured.
+/// * `db_limiter` - Database concurrency limiter shared with the bot.
+/// * `db_breaker` - Database circuit breaker shared with the bot.
+/// * `logging` - The logging service shared with the bot.
+/// * `clock` - The clock feeds are dated with.
+///
+/// # Returns
+/// The configured `Router`.
+pub fn router(
+    database: Arc<Mutex<Database>>,
+    linked_roles: Option<Arc<LinkedRoles>>,
+    db_limiter: Arc<DbLimiter>,
+    db_breaker: Arc<CircuitBreaker>,
+    logging: Arc<LoggingService>,
+    clock: Arc<dyn Clock>,
+) -> Router {
+    Router::new()
+        .route("/api/log", post(post_log))
+        .route("/metrics", get(get_metrics))
+        .route("/s/:token", get(get_shortcut))
+        .route("/feed/:token", get(get_feed))
+        .route("/email/verify/:token", get(get_email_verify))
+        .route("/email/unsubscribe/:token", get(get_email_unsubscribe))
+        .route("/linked-roles", get(get_linked_roles))
+        .route("/linked-roles/callback", get(get_linked_roles_callback))
+        .with_state(ApiState {
+            database,
+            linked_roles,
+            db_limiter,
+            db_breaker,
+            logging,
+            clock,
+        })
+}
@@ -0,0 +1,191 @@
+//! Leaderboards by reduction percentage instead of raw counts.
+//!
+//! `leaderboard most-improved` and `leaderboard least-improved` rank the
+//! guild's members by how much less they logged in the last completed week
+//! or month than in the one before, which is fairer to heavy smokers cutting
+//! down than ranking by count. Members who logged nothing in the earlier
+//! period have no baseline and are left out.
+
+use chrono::{Datelike, Months, NaiveDate};
+
+use crate::challenge::reduction_percent;
+use crate::database::MemberPeriodTotals;
+use crate::format::{format_count, format_date, Locale};
+use crate::forum::week_start;
+
+/// The periods compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImprovementPeriod {
+    Week,
+    Month,
+}
+
+impl ImprovementPeriod {
+    /// Every period, in the order shown to users
+    pub const ALL: [ImprovementPeriod; 2] = [ImprovementPeriod::Week, ImprovementPeriod::Month];
+
+    /// Returns the name used in commands.
+    pub fn name(self) -> &'static str {
+        match self {
+            ImprovementPeriod::Week => "week",
+            ImprovementPeriod::Month => "month",
+        }
+    }
+
+    /// Looks up a period by name.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the period (e.g. `week`).
+    ///
+    /// # Returns
+    /// The period, or `None` for unknown names.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|period| period.name() == name)
+    }
+
+    /// Returns the last two completed periods before a date.
+    ///
+    /// # Arguments
+    /// * `today` - The current local date.
+    ///
+    /// # Returns
+    /// The first day of the earlier period, the first day of the later one,
+    /// and the first day after it.
+    pub fn completed(self, today: NaiveDate) -> (NaiveDate, NaiveDate, NaiveDate) {
+        match self {
+            ImprovementPeriod::Week => {
+                let end = week_start(today);
+                (
+                    end - chrono::Duration::days(14),
+                    end - chrono::Duration::days(7),
+                    end,
+                )
+            }
+            ImprovementPeriod::Month => {
+                let end = today.with_day(1).unwrap_or(today);
+                (end - Months::new(2), end - Months::new(1), end)
+            }
+        }
+    }
+}
+
+/// Which end of the ranking comes first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImprovementOrder {
+    /// The largest reductions first
+    Most,
+    /// The smallest reductions (or largest increases) first
+    Least,
+}
+
+/// A member's change between two periods
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Improvement {
+    pub discord_id: String,
+    pub rank: i32,
+    pub previous: i64,
+    pub current: i64,
+    /// The reduction in percent, negative for an increase
+    pub percent: i64,
+}
+
+/// Ranks members by their reduction between two periods.
+///
+/// Members with equal reductions share a rank, like in season standings.
+///
+/// # Arguments
+/// * `totals` - The members' totals of both periods.
+/// * `order` - Which end of the ranking comes first.
+///
+/// # Returns
+/// The ranking of the members with a baseline.
+pub fn rank_improvements(
+    totals: &[MemberPeriodTotals],
+    order: ImprovementOrder,
+) -> Vec<Improvement> {
+    let mut improvements: Vec<Improvement> = totals
+        .iter()
+        .filter_map(|totals| {
+            Some(Improvement {
+                discord_id: totals.discord_id.clone(),
+                rank: 0,
+                previous: totals.previous,
+                current: totals.current,
+                percent: reduction_percent(totals.previous, totals.current)?,
+            })
+        })
+        .collect();
+    improvements.sort_by(|a, b| {
+        let by_percent = match order {
+            ImprovementOrder::Most => b.percent.cmp(&a.percent),
+            ImprovementOrder::Least => a.percent.cmp(&b.percent),
+        };
+        by_percent.then(a.discord_id.cmp(&b.discord_id))
+    });
+
+    for position in 0..improvements.len() {
+        improvements[position].rank = match position.checked_sub(1) {
+            Some(previous) if improvements[previous].percent == improvements[position].percent => {
+                improvements[previous].rank
+            }
+            _ => i32::try_from(position + 1).unwrap_or(i32::MAX),
+        };
+    }
+
+    improvements
+}
+
+/// Describes the compared periods.
+///
+/// # Arguments
+/// * `period` - The kind of period.
+/// * `today` - The current local date.
+///
+/// # Returns
+/// The heading, e.g. `2024/06/03〜2024/06/09 と前の週の比較`.
+pub fn comparison_heading(period: ImprovementPeriod, today: NaiveDate) -> String {
+    let locale = Locale::Japanese;
+    let (_, start, end) = period.completed(today);
+    let earlier = match period {
+        ImprovementPeriod::Week => "前の週",
+        ImprovementPeriod::Month => "前の月",
+    };
+
+    format!(
+        "{}〜{} と{}の比較",
+        format_date(start, locale),
+        format_date(end - chrono::Duration::days(1), locale),
+        earlier
+    )
+}
+
+/// Renders a ranking, one line per member.
+///
+/// # Arguments
+/// * `improvements` - The ranking.
+/// * `limit` - The most members shown.
+///
+/// # Returns
+/// The lines, e.g. `1位 <@1> 40%減（10本 → 6本）`.
+pub fn improvement_lines(improvements: &[Improvement], limit: usize) -> Vec<String> {
+    let locale = Locale::Japanese;
+    improvements
+        .iter()
+        .take(limit)
+        .map(|improvement| {
+            let change = if improvement.percent >= 0 {
+                format!("{}%減", improvement.percent)
+            } else {
+                format!("{}%増", -improvement.percent)
+            };
+            format!(
+                "{}位 <@{}> {}（{} → {}）",
+                improvement.rank,
+                improvement.discord_id,
+                change,
+                format_count(improvement.previous, locale),
+                format_count(improvement.current, locale)
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,49 @@
+//! Guild and user installs of the bot.
+//!
+//! Besides being added to a server, the bot can be installed by a user.
+//! Its personal commands (the panel, logging and statistics) are then
+//! available in DMs, in group DMs and in servers the bot is not a member of.
+//! Interactions there still carry the server's ID, but the bot cannot act in
+//! that server (post, react or assign roles) and the server never agreed to
+//! its settings applying, so such logs are recorded like DM logs: without a
+//! guild.
+
+use poise::serenity_prelude::{
+    AuthorizingIntegrationOwner, AuthorizingIntegrationOwners, ComponentInteraction, GuildId,
+};
+
+/// Returns the guild an interaction belongs to, if the bot is installed there.
+///
+/// # Arguments
+/// * `guild_id` - The guild the interaction was created in, if any.
+/// * `owners` - The installations that authorized the interaction.
+///
+/// # Returns
+/// The guild, or `None` in DMs and in guilds reached only through a user install.
+pub fn installed_guild(
+    guild_id: Option<GuildId>,
+    owners: &AuthorizingIntegrationOwners,
+) -> Option<GuildId> {
+    let guild_id = guild_id?;
+    // Interactions without installation details predate user installs.
+    if owners.0.is_empty() {
+        return Some(guild_id);
+    }
+
+    owners
+        .0
+        .iter()
+        .any(|owner| matches!(owner, AuthorizingIntegrationOwner::GuildInstall(Some(id)) if *id == guild_id))
+        .then_some(guild_id)
+}
+
+/// Returns the guild a component interaction is recorded in.
+///
+/// # Arguments
+/// * `mci` - The component interaction.
+///
+/// # Returns
+/// The guild, or `None` if the bot is not installed in the interaction's guild.
+pub fn interaction_guild(mci: &ComponentInteraction) -> Option<GuildId> {
+    installed_guild(mci.guild_id, &mci.authorizing_integration_owners)
+}
@@ -0,0 +1,187 @@
+//! Scheduled background work stored in the `scheduled_jobs` table.
+//!
+//! Work that must happen later (or again and again) is written to the
+//! database instead of being kept in a tokio timer, so it survives restarts
+//! and owners can inspect and cancel it with `admin jobs`. A worker on the
+//! leader replica polls for due jobs and runs the handler registered for
+//! each job's kind. Failed runs are retried with backoff; a job is given up
+//! on after `MAX_ATTEMPTS` runs. Handlers of recurring work schedule the
+//! next run when a run finishes, and a pending run of every recurring kind
+//! is ensured when a replica first becomes the leader.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use poise::serenity_prelude::futures::lock::Mutex;
+use tracing::{error, warn};
+
+use crate::clock::Clock;
+use crate::database::{Database, ScheduledJob};
+use crate::leader::LeaderElection;
+use crate::outbox::backoff;
+use crate::Error;
+
+/// Interval between polls for due jobs
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Maximum number of jobs run per poll
+const BATCH_SIZE: i64 = 10;
+
+/// Runs after which a failing job is given up on
+pub const MAX_ATTEMPTS: i32 = 5;
+
+/// How long a job may stay running before another worker claims it again
+const LEASE: Duration = Duration::from_secs(60 * 60);
+
+/// Runs the jobs of one kind
+#[async_trait]
+pub trait JobHandler: Send + Sync {
+    /// Runs a job.
+    ///
+    /// # Arguments
+    /// * `job` - The job, including its payload.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    async fn run(&self, job: &ScheduledJob) -> Result<(), Error>;
+
+    /// Returns the interval of recurring work, or `None` for one-off jobs.
+    fn repeat_every(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Runs due jobs with the handlers registered for their kinds
+pub struct JobWorker {
+    database: Arc<Mutex<Database>>,
+    clock: Arc<dyn Clock>,
+    handlers: HashMap<String, Arc<dyn JobHandler>>,
+}
+
+impl JobWorker {
+    /// Creates a worker without handlers.
+    ///
+    /// # Arguments
+    /// * `database` - Database connection shared with the bot.
+    /// * `clock` - The clock deciding which jobs are due.
+    pub fn new(database: Arc<Mutex<Database>>, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            database,
+            clock,
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Registers the handler of a kind of job.
+    ///
+    /// # Arguments
+    /// * `kind` - The kind of job.
+    /// * `handler` - The handler running jobs of the kind.
+    pub fn register(mut self, kind: &str, handler: Arc<dyn JobHandler>) -> Self {
+        self.handlers.insert(kind.to_string(), handler);
+        self
+    }
+
+    /// Schedules a run of every recurring kind that has none pending, due now.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn schedule_recurring(&self) -> Result<(), sqlx::Error> {
+        let now = self.clock.now();
+        let db = self.database.lock().await;
+        for (kind, handler) in &self.handlers {
+            if handler.repeat_every().is_some() {
+                db.ensure_job_scheduled(kind, now).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs every due job once.
+    ///
+    /// # Returns
+    /// A Result containing the number of jobs that succeeded, or an `Error`.
+    pub async fn run_due(&self) -> Result<usize, sqlx::Error> {
+        let now = self.clock.now();
+        let jobs = self
+            .database
+            .lock()
+            .await
+            .claim_due_jobs(
+                now,
+                now - chrono::Duration::from_std(LEASE).unwrap_or_default(),
+                BATCH_SIZE,
+            )
+            .await?;
+
+        let mut succeeded = 0;
+        for job in jobs {
+            let Some(handler) = self.handlers.get(&job.kind) else {
+                warn!("No handler for job {} of kind {}", job.id, job.kind);
+                self.database
+                    .lock()
+                    .await
+                    .fail_job(job.id, "no handler for this kind", None)
+                    .await?;
+                continue;
+            };
+
+            let result = handler.run(&job).await;
+            let db = self.database.lock().await;
+            let finished = match result {
+                Ok(()) => {
+                    db.complete_job(job.id).await?;
+                    succeeded += 1;
+                    true
+                }
+                Err(e) if job.attempts < MAX_ATTEMPTS => {
+                    warn!("Job {} of kind {} failed: {}", job.id, job.kind, e);
+                    let retry_at = self.clock.now()
+                        + chrono::Duration::from_std(backoff(job.attempts)).unwrap_or_default();
+                    db.fail_job(job.id, &e.to_string(), Some(retry_at)).await?;
+                    false
+                }
+                Err(e) => {
+                    error!("Giving up on job {} of kind {}: {}", job.id, job.kind, e);
+                    db.fail_job(job.id, &e.to_string(), None).await?;
+                    true
+                }
+            };
+            if let Some(every) = handler.repeat_every().filter(|_| finished) {
+                let next_run =
+                    self.clock.now() + chrono::Duration::from_std(every).unwrap_or_default();
+                db.ensure_job_scheduled(&job.kind, next_run).await?;
+            }
+        }
+
+        Ok(succeeded)
+    }
+
+    /// Spawns the background task running due jobs while this replica is the leader.
+    ///
+    /// # Arguments
+    /// * `leader` - The election deciding whether this replica runs jobs.
+    pub fn spawn(self, leader: Arc<LeaderElection>) {
+        tokio::spawn(async move {
+            let mut scheduled_recurring = false;
+            let mut interval = tokio::time::interval(POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                if !leader.is_leader().await {
+                    continue;
+                }
+                // Only the leader schedules, so replicas starting together do not race.
+                if !scheduled_recurring {
+                    match self.schedule_recurring().await {
+                        Ok(()) => scheduled_recurring = true,
+                        Err(e) => error!("Failed to schedule recurring jobs: {}", e),
+                    }
+                }
+                if let Err(e) = self.run_due().await {
+                    error!("Failed to run scheduled jobs: {}", e);
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,161 @@
+//! Latency measurement for commands and interactions.
+//!
+//! A request is tracked per tokio task from `begin` until `end`. Database
+//! methods record their duration with a `QueryTimer`, so a request exceeding
+//! the slow threshold is logged together with a per-query breakdown.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock, Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::task;
+use tracing::warn;
+
+/// Threshold used when none has been configured
+const DEFAULT_SLOW_THRESHOLD: Duration = Duration::from_millis(1000);
+
+/// Requests in flight, keyed by the task executing them
+static REQUESTS: LazyLock<Mutex<HashMap<task::Id, Request>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Configured slow-request threshold
+static SLOW_THRESHOLD: OnceLock<Duration> = OnceLock::new();
+
+/// Total number of measured requests
+static REQUESTS_TOTAL: AtomicU64 = AtomicU64::new(0);
+
+/// Number of requests that exceeded the slow threshold
+static SLOW_REQUESTS_TOTAL: AtomicU64 = AtomicU64::new(0);
+
+/// A request being measured
+struct Request {
+    label: String,
+    start: Instant,
+    queries: Vec<(&'static str, Duration)>,
+}
+
+/// Sets the slow-request threshold. Only the first call has an effect.
+///
+/// # Arguments
+/// * `threshold` - Requests taking longer than this are reported.
+pub fn set_slow_threshold(threshold: Duration) {
+    let _ = SLOW_THRESHOLD.set(threshold);
+}
+
+/// Starts measuring a request on the current task.
+///
+/// # Arguments
+/// * `label` - A description of the request (e.g. the command name).
+pub fn begin(label: impl Into<String>) {
+    let Some(id) = task::try_id() else {
+        return;
+    };
+
+    REQUESTS.lock().unwrap().insert(
+        id,
+        Request {
+            label: label.into(),
+            start: Instant::now(),
+            queries: Vec::new(),
+        },
+    );
+}
+
+/// Finishes measuring the request on the current task, reporting it if it was slow.
+pub fn end() {
+    let Some(request) = task::try_id().and_then(|id| REQUESTS.lock().unwrap().remove(&id)) else {
+        return;
+    };
+
+    let elapsed = request.start.elapsed();
+    REQUESTS_TOTAL.fetch_add(1, Ordering::Relaxed);
+
+    if elapsed <= *SLOW_THRESHOLD.get().unwrap_or(&DEFAULT_SLOW_THRESHOLD) {
+        return;
+    }
+
+    SLOW_REQUESTS_TOTAL.fetch_add(1, Ordering::Relaxed);
+    let breakdown = request
+        .queries
+        .iter()
+        .map(|(name, duration)| format!("{} {}ms", name, duration.as_millis()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    warn!(
+        "Slow request '{}' took {}ms (queries: [{}])",
+        request.label,
+        elapsed.as_millis(),
+        breakdown
+    );
+}
+
+/// Measures a request for as long as the guard is alive.
+pub struct RequestGuard(());
+
+impl RequestGuard {
+    /// Starts measuring a request on the current task.
+    ///
+    /// # Arguments
+    /// * `label` - A description of the request.
+    pub fn begin(label: impl Into<String>) -> Self {
+        begin(label);
+        Self(())
+    }
+}
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        end();
+    }
+}
+
+/// Records the duration of a database query into the current request, if any.
+pub struct QueryTimer {
+    name: &'static str,
+    start: Instant,
+}
+
+impl QueryTimer {
+    /// Starts timing a query.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the query (usually the `Database` method name).
+    pub fn start(name: &'static str) -> Self {
+        Self {
+            name,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for QueryTimer {
+    fn drop(&mut self) {
+        let Some(id) = task::try_id() else {
+            return;
+        };
+
+        if let Some(request) = REQUESTS.lock().unwrap().get_mut(&id) {
+            request.queries.push((self.name, self.start.elapsed()));
+        }
+    }
+}
+
+/// Renders the latency counters in the Prometheus text exposition format.
+///
+/// # Returns
+/// The metrics text.
+pub fn render_metrics() -> String {
+    format!(
+        "# TYPE cigarette_counter_requests_total counter\n\
+         cigarette_counter_requests_total {}\n\
+         # TYPE cigarette_counter_slow_requests_total counter\n\
+         cigarette_counter_slow_requests_total {}\n",
+        REQUESTS_TOTAL.load(Ordering::Relaxed),
+        SLOW_REQUESTS_TOTAL.load(Ordering::Relaxed)
+    )
+}
@@ -0,0 +1,100 @@
+//! Leader election between bot replicas.
+//!
+//! Operators may run several replicas of the bot for availability. All of
+//! them serve interactions, but periodic background work (outbox delivery,
+//! linked-roles pushes, milestone role synchronization) must run on exactly
+//! one. The replica holding a Postgres session-level advisory lock is the
+//! leader; the lock lives on a connection detached from the pool, so it is
+//! released as soon as that connection closes (e.g. when the replica dies).
+//! Background tasks ask `is_leader` on every tick and skip it otherwise, so
+//! another replica takes over at its next tick.
+
+use poise::serenity_prelude::futures::lock::Mutex;
+use sqlx::{PgConnection, PgPool};
+use tracing::{info, warn};
+
+/// Advisory lock key held by the leader (the ASCII bytes of `cigarett`)
+pub const LEADER_LOCK_KEY: i64 = 0x6369_6761_7265_7474;
+
+/// Decides which replica runs the background tasks
+pub struct LeaderElection {
+    pool: PgPool,
+    connection: Mutex<Option<PgConnection>>,
+}
+
+impl LeaderElection {
+    /// Creates an election that has not acquired leadership yet.
+    ///
+    /// # Arguments
+    /// * `pool` - The connection pool the lock connection is taken from.
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Checks whether this replica is the leader, trying to become it if no replica is.
+    ///
+    /// Errors are logged and count as not being the leader.
+    ///
+    /// # Returns
+    /// Whether this replica holds the lock.
+    pub async fn is_leader(&self) -> bool {
+        let mut connection = self.connection.lock().await;
+
+        if let Some(held) = connection.as_mut() {
+            match sqlx::query!("SELECT 1 as one").fetch_one(held).await {
+                Ok(_) => return true,
+                Err(e) => {
+                    // Dropping the connection closes the session, releasing the lock if it survived.
+                    warn!("Lost leadership: {}", e);
+                    *connection = None;
+                }
+            }
+        }
+
+        match self.try_acquire().await {
+            Ok(Some(acquired)) => {
+                info!("This replica is now the leader");
+                *connection = Some(acquired);
+                true
+            }
+            Ok(None) => false,
+            Err(e) => {
+                warn!("Failed to run leader election: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Gives up leadership, e.g. before shutting down, so another replica takes over at once.
+    pub async fn resign(&self) {
+        let Some(mut held) = self.connection.lock().await.take() else {
+            return;
+        };
+
+        if let Err(e) = sqlx::query!("SELECT pg_advisory_unlock($1)", LEADER_LOCK_KEY)
+            .fetch_one(&mut held)
+            .await
+        {
+            warn!("Failed to release leadership: {}", e);
+        }
+        info!("This replica resigned as the leader");
+    }
+
+    /// Tries to take the advisory lock on a connection from the pool.
+    ///
+    /// # Returns
+    /// A Result containing the connection holding the lock, `None` if another replica holds it, or an `Error`.
+    async fn try_acquire(&self) -> Result<Option<PgConnection>, sqlx::Error> {
+        let mut connection = self.pool.acquire().await?;
+        let acquired = sqlx::query_scalar!("SELECT pg_try_advisory_lock($1)", LEADER_LOCK_KEY)
+            .fetch_one(&mut *connection)
+            .await?
+            .unwrap_or_default();
+
+        // Only the lock connection leaves the pool; a failed attempt returns it unlocked.
+        Ok(acquired.then(|| connection.detach()))
+    }
+}
@@ -0,0 +1,116 @@
+//! Cigarette Counter Discord bot.
+//!
+//! The binary in `main.rs` wires these modules together; they are exposed as
+//! a library so integration tests can exercise them directly.
+
+pub mod achievements;
+pub mod acknowledgment;
+pub mod anonymize;
+pub mod challenge;
+pub mod channel_counter;
+pub mod circuit_breaker;
+pub mod cli;
+pub mod clock;
+pub mod command_sync;
+pub mod commands;
+pub mod config;
+pub mod confirmation_cleanup;
+pub mod consent;
+pub mod custom_id;
+pub mod database;
+pub mod db_limiter;
+pub mod deferral;
+pub mod demo_seed;
+pub mod email;
+pub mod embed;
+pub mod event_bus;
+pub mod events;
+pub mod explain;
+pub mod export;
+pub mod feed;
+pub mod format;
+pub mod forum;
+pub mod frontend;
+pub mod guild_allowlist;
+pub mod guild_archive;
+pub mod guild_removal;
+pub mod heartbeat;
+pub mod http;
+pub mod improvement;
+pub mod installation;
+pub mod jobs;
+pub mod latency;
+pub mod leader;
+pub mod linked_roles;
+pub mod log_notify;
+pub mod milestones;
+pub mod notify;
+pub mod onboarding;
+pub mod outbox;
+pub mod panel_health;
+pub mod pauses;
+pub mod periods;
+pub mod presets;
+pub mod push;
+pub mod rollover;
+pub mod scripting;
+pub mod seasons;
+pub mod service;
+pub mod shards;
+pub mod smoke_free;
+pub mod systemd;
+pub mod templates;
+pub mod tutorial;
+pub mod units;
+mod voice;
+pub mod wagers;
+pub mod write_buffer;
+
+use std::sync::Arc;
+
+use circuit_breaker::CircuitBreaker;
+use clock::Clock;
+use config::Config;
+use database::Database;
+use db_limiter::DbLimiter;
+use email::Mailer;
+use event_bus::EventBus;
+use poise::serenity_prelude::futures::lock::Mutex;
+use push::PushProviders;
+use scripting::ScriptHooks;
+use service::{LoggingService, StatsService};
+use write_buffer::WriteBuffer;
+
+/// Shared application state containing the database connection and configuration
+pub struct Data {
+    /// Thread-safe, async database connection wrapped in Arc<Mutex>
+    pub database: Arc<Mutex<Database>>,
+    /// Loaded bot configuration
+    pub config: Arc<Config>,
+    /// Limits concurrent database access and fails fast when saturated
+    pub db_limiter: Arc<DbLimiter>,
+    /// Fails database requests fast while the database is unreachable
+    pub db_breaker: Arc<CircuitBreaker>,
+    /// Source of the current time and date
+    pub clock: Arc<dyn Clock>,
+    /// Self-hosted scripts hooked into events
+    pub scripts: Arc<ScriptHooks>,
+    /// Broadcasts domain events to internal subscribers
+    pub events: Arc<EventBus>,
+    /// Records smoking events
+    pub logging: Arc<LoggingService>,
+    /// Computes statistics
+    pub stats: Arc<StatsService>,
+    /// Holds panel presses taken while the database is unreachable, if enabled
+    pub write_buffer: Option<Arc<WriteBuffer>>,
+    /// Sends the weekly email digests, if SMTP is configured
+    pub mailer: Option<Arc<dyn Mailer>>,
+    /// Push services members can receive notifications from instead of DMs
+    pub push: Arc<PushProviders>,
+}
+
+/// Type alias for boxed errors that can be sent between threads
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Type alias for command context containing application state
+pub type Context<'a> = poise::Context<'a, Data, Error>;
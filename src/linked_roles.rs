@@ -0,0 +1,494 @@
+//! Discord Linked Roles integration.
+//!
+//! Users authorize the bot through an OAuth flow served by the HTTP API, after
+//! which their role-connection metadata (days smoke-free, under-goal streak) is
+//! pushed periodically so guilds can gate roles such as 「禁煙30日」 on it.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex as StdMutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use poise::serenity_prelude::futures::lock::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{error, info, warn};
+
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::database::{DailyTotal, Database, RoleConnection, ScheduledJob, TrackingPause};
+use crate::http::generate_token;
+use crate::jobs::JobHandler;
+use crate::milestones::days_smoke_free;
+use crate::pauses::is_paused;
+use crate::service::StatsService;
+
+/// Base URL of the Discord REST API
+const API_BASE: &str = "https://discord.com/api/v10";
+
+/// Platform name shown on the user's profile
+const PLATFORM_NAME: &str = "Cigarette Counter";
+
+/// Interval between metadata pushes for all connected users
+const PUSH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How long an OAuth `state` value stays valid
+const STATE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Maximum number of days counted for the under-goal streak
+const STREAK_WINDOW_DAYS: i64 = 365;
+
+/// Errors that can occur in the linked-roles integration
+#[derive(Debug, thiserror::Error)]
+pub enum LinkedRolesError {
+    #[error("Discord API request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("Invalid or expired OAuth state")]
+    InvalidState,
+}
+
+/// Role-connection metadata published for a user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoleMetadata {
+    pub days_smoke_free: i64,
+    pub under_goal_streak: i64,
+}
+
+/// OAuth token response from Discord
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: String,
+    expires_in: i64,
+}
+
+/// Subset of the Discord user object returned by `/users/@me`
+#[derive(Debug, Deserialize)]
+struct DiscordUser {
+    id: String,
+    username: String,
+}
+
+/// A single metadata field definition registered with Discord
+#[derive(Debug, Serialize)]
+struct MetadataField {
+    #[serde(rename = "type")]
+    kind: u8,
+    key: &'static str,
+    name: &'static str,
+    description: &'static str,
+}
+
+/// Metadata type `INTEGER_GREATER_THAN_OR_EQUAL`
+const INTEGER_GREATER_THAN_OR_EQUAL: u8 = 2;
+
+/// Discord Linked Roles client
+pub struct LinkedRoles {
+    client: reqwest::Client,
+    client_id: String,
+    client_secret: String,
+    bot_token: String,
+    redirect_uri: String,
+    pending_states: StdMutex<HashMap<String, Instant>>,
+    clock: Arc<dyn Clock>,
+    stats: Arc<StatsService>,
+}
+
+impl LinkedRoles {
+    /// Creates the linked-roles client if the required configuration is present.
+    ///
+    /// # Arguments
+    /// * `config` - Loaded bot configuration.
+    /// * `clock` - The clock used for token expiry.
+    /// * `stats` - The statistics service computing the metadata.
+    ///
+    /// # Returns
+    /// `Some(LinkedRoles)` when `DISCORD_CLIENT_ID`, `DISCORD_CLIENT_SECRET` and `PUBLIC_URL` are set, otherwise `None`.
+    pub fn from_config(
+        config: &Config,
+        clock: Arc<dyn Clock>,
+        stats: Arc<StatsService>,
+    ) -> Option<Self> {
+        Some(Self {
+            client: reqwest::Client::new(),
+            client_id: config.discord_client_id.clone()?,
+            client_secret: config.discord_client_secret.clone()?,
+            bot_token: config.bot_token.clone(),
+            redirect_uri: format!("{}/linked-roles/callback", config.public_url.as_ref()?),
+            pending_states: StdMutex::new(HashMap::new()),
+            clock,
+            stats,
+        })
+    }
+
+    /// Registers the role-connection metadata schema with Discord.
+    ///
+    /// # Returns
+    /// A Result indicating success or a `LinkedRolesError`.
+    pub async fn register_metadata(&self) -> Result<(), LinkedRolesError> {
+        let fields = [
+            MetadataField {
+                kind: INTEGER_GREATER_THAN_OR_EQUAL,
+                key: "days_smoke_free",
+                name: "禁煙日数",
+                description: "最後の喫煙からの日数",
+            },
+            MetadataField {
+                kind: INTEGER_GREATER_THAN_OR_EQUAL,
+                key: "under_goal_streak",
+                name: "目標達成連続日数",
+                description: "1日の目標本数以下を連続で達成した日数",
+            },
+        ];
+
+        self.client
+            .put(format!(
+                "{}/applications/{}/role-connections/metadata",
+                API_BASE, self.client_id
+            ))
+            .header("Authorization", format!("Bot {}", self.bot_token))
+            .json(&fields)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    /// Builds the Discord authorization URL and records a fresh `state` value.
+    ///
+    /// # Returns
+    /// The URL the user should be redirected to.
+    pub fn authorize_url(&self) -> String {
+        let (state, _) = generate_token();
+
+        {
+            let mut pending = self.pending_states.lock().unwrap();
+            pending.retain(|_, created| created.elapsed() < STATE_TTL);
+            pending.insert(state.clone(), Instant::now());
+        }
+
+        reqwest::Url::parse_with_params(
+            "https://discord.com/oauth2/authorize",
+            &[
+                ("client_id", self.client_id.as_str()),
+                ("redirect_uri", self.redirect_uri.as_str()),
+                ("response_type", "code"),
+                ("scope", "role_connections.write identify"),
+                ("state", state.as_str()),
+                ("prompt", "consent"),
+            ],
+        )
+        .expect("authorize URL is valid")
+        .to_string()
+    }
+
+    /// Completes the OAuth flow, stores the user's tokens, and pushes their metadata.
+    ///
+    /// # Arguments
+    /// * `database` - Database connection shared with the bot.
+    /// * `code` - The authorization code returned by Discord.
+    /// * `state` - The `state` value returned by Discord.
+    ///
+    /// # Returns
+    /// A Result containing the username of the connected user or a `LinkedRolesError`.
+    pub async fn complete_authorization(
+        &self,
+        database: &Mutex<Database>,
+        code: &str,
+        state: &str,
+    ) -> Result<String, LinkedRolesError> {
+        let valid_state = self
+            .pending_states
+            .lock()
+            .unwrap()
+            .remove(state)
+            .is_some_and(|created| created.elapsed() < STATE_TTL);
+        if !valid_state {
+            return Err(LinkedRolesError::InvalidState);
+        }
+
+        let tokens = self
+            .request_tokens(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.redirect_uri),
+            ])
+            .await?;
+
+        let user: DiscordUser = self
+            .client
+            .get(format!("{}/users/@me", API_BASE))
+            .bearer_auth(&tokens.access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let connection = {
+            let db = database.lock().await;
+            db.get_or_create_user(&user.id, &user.username).await?;
+            let expires_at = self.clock.now() + chrono::Duration::seconds(tokens.expires_in);
+            db.upsert_role_connection(
+                &user.id,
+                &tokens.access_token,
+                &tokens.refresh_token,
+                expires_at,
+            )
+            .await?;
+
+            RoleConnection {
+                discord_id: user.id,
+                access_token: tokens.access_token,
+                refresh_token: tokens.refresh_token,
+                expires_at,
+                last_pushed_at: None,
+            }
+        };
+
+        self.push_metadata(database, connection).await?;
+
+        Ok(user.username)
+    }
+
+    /// Pushes metadata for every connected user.
+    ///
+    /// Failures for individual users are logged and do not stop the remaining pushes.
+    ///
+    /// # Arguments
+    /// * `database` - Database connection shared with the bot.
+    pub async fn push_all(&self, database: &Mutex<Database>) {
+        let connections = match database.lock().await.get_role_connections().await {
+            Ok(connections) => connections,
+            Err(e) => {
+                error!("Failed to load role connections: {}", e);
+                return;
+            }
+        };
+
+        for connection in connections {
+            let discord_id = connection.discord_id.clone();
+            if let Err(e) = self.push_metadata(database, connection).await {
+                warn!("Failed to push role metadata for {}: {}", discord_id, e);
+            }
+        }
+    }
+
+    /// Pushes the current metadata of a single user, refreshing their token if needed.
+    ///
+    /// # Arguments
+    /// * `database` - Database connection shared with the bot.
+    /// * `connection` - The user's stored connection.
+    ///
+    /// # Returns
+    /// A Result indicating success or a `LinkedRolesError`.
+    async fn push_metadata(
+        &self,
+        database: &Mutex<Database>,
+        mut connection: RoleConnection,
+    ) -> Result<(), LinkedRolesError> {
+        if connection.expires_at <= self.clock.now() + chrono::Duration::minutes(5) {
+            let tokens = self
+                .request_tokens(&[
+                    ("grant_type", "refresh_token"),
+                    ("refresh_token", &connection.refresh_token),
+                ])
+                .await?;
+            connection.access_token = tokens.access_token;
+            connection.refresh_token = tokens.refresh_token;
+            connection.expires_at = self.clock.now() + chrono::Duration::seconds(tokens.expires_in);
+
+            database
+                .lock()
+                .await
+                .upsert_role_connection(
+                    &connection.discord_id,
+                    &connection.access_token,
+                    &connection.refresh_token,
+                    connection.expires_at,
+                )
+                .await?;
+        }
+
+        let metadata = self.stats.role_metadata(&connection.discord_id).await?;
+
+        self.client
+            .put(format!(
+                "{}/users/@me/applications/{}/role-connection",
+                API_BASE, self.client_id
+            ))
+            .bearer_auth(&connection.access_token)
+            .json(&json!({
+                "platform_name": PLATFORM_NAME,
+                "metadata": {
+                    "days_smoke_free": metadata.days_smoke_free.to_string(),
+                    "under_goal_streak": metadata.under_goal_streak.to_string(),
+                },
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        database
+            .lock()
+            .await
+            .mark_role_connection_pushed(&connection.discord_id)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Calls the OAuth token endpoint.
+    ///
+    /// # Arguments
+    /// * `params` - Grant-specific form parameters.
+    ///
+    /// # Returns
+    /// A Result containing the `TokenResponse` or a `LinkedRolesError`.
+    async fn request_tokens(
+        &self,
+        params: &[(&str, &str)],
+    ) -> Result<TokenResponse, LinkedRolesError> {
+        let mut form = vec![
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+        ];
+        form.extend_from_slice(params);
+
+        let tokens = self
+            .client
+            .post(format!("{}/oauth2/token", API_BASE))
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(tokens)
+    }
+}
+
+/// Computes the role-connection metadata of a user.
+///
+/// # Arguments
+/// * `db` - The database.
+/// * `clock` - The clock determining the current date.
+/// * `discord_id` - The Discord ID of the user.
+///
+/// # Returns
+/// A Result containing the `RoleMetadata` or an `Error`.
+pub async fn compute_metadata(
+    db: &Database,
+    clock: &dyn Clock,
+    discord_id: &str,
+) -> Result<RoleMetadata, sqlx::Error> {
+    let today = clock.today();
+    let days_smoke_free = days_smoke_free(db, clock, discord_id).await?;
+
+    let under_goal_streak = match (
+        db.get_daily_goal(discord_id).await?,
+        db.get_first_smoke_date(discord_id, clock.timezone())
+            .await?,
+    ) {
+        (Some(goal), Some(first_date)) => {
+            let since = first_date.max(today - chrono::Duration::days(STREAK_WINDOW_DAYS));
+            let totals = db
+                .get_daily_totals(discord_id, since, clock.timezone())
+                .await?;
+            let pauses = db.get_tracking_pauses(discord_id, since).await?;
+            under_goal_streak(&totals, &pauses, goal, since, today)
+        }
+        _ => 0,
+    };
+
+    Ok(RoleMetadata {
+        days_smoke_free,
+        under_goal_streak,
+    })
+}
+
+/// Counts consecutive days ending today on which the total stayed within the goal.
+///
+/// Paused days are skipped: they neither count nor break the streak.
+///
+/// # Arguments
+/// * `totals` - Daily totals; days without logs count as zero.
+/// * `pauses` - The user's tracking pauses.
+/// * `goal` - The daily goal.
+/// * `since` - The earliest date that may be counted.
+/// * `today` - The current local date.
+///
+/// # Returns
+/// The length of the streak in days.
+fn under_goal_streak(
+    totals: &[DailyTotal],
+    pauses: &[TrackingPause],
+    goal: i32,
+    since: NaiveDate,
+    today: NaiveDate,
+) -> i64 {
+    let by_date: HashMap<NaiveDate, i64> = totals
+        .iter()
+        .map(|total| (total.smoke_date, total.total_quantity))
+        .collect();
+
+    let mut streak = 0;
+    let mut date = today;
+    while date >= since {
+        if !is_paused(pauses, date) {
+            if by_date.get(&date).copied().unwrap_or_default() > i64::from(goal) {
+                break;
+            }
+            streak += 1;
+        }
+        match date.pred_opt() {
+            Some(previous) => date = previous,
+            None => break,
+        }
+    }
+
+    streak
+}
+
+/// Scheduled job kind of the push of every connected user's metadata
+pub const PUSH_JOB: &str = "linked_roles_push";
+
+/// Job handler pushing metadata for all connected users every `PUSH_INTERVAL`
+pub struct PushJob {
+    linked_roles: Arc<LinkedRoles>,
+    database: Arc<Mutex<Database>>,
+}
+
+impl PushJob {
+    /// Creates the handler.
+    ///
+    /// # Arguments
+    /// * `linked_roles` - The linked-roles client.
+    /// * `database` - Database connection shared with the bot.
+    pub fn new(linked_roles: Arc<LinkedRoles>, database: Arc<Mutex<Database>>) -> Self {
+        Self {
+            linked_roles,
+            database,
+        }
+    }
+}
+
+#[async_trait]
+impl JobHandler for PushJob {
+    async fn run(&self, _job: &ScheduledJob) -> Result<(), crate::Error> {
+        self.linked_roles.push_all(&self.database).await;
+        info!("Pushed linked-roles metadata");
+        Ok(())
+    }
+
+    fn repeat_every(&self) -> Option<Duration> {
+        Some(PUSH_INTERVAL)
+    }
+}
@@ -0,0 +1,60 @@
+//! Postgres notifications about smoking logs for external consumers.
+//!
+//! With `LOG_NOTIFY_CHANNEL` set, every inserted or deleted log sends a
+//! `pg_notify` on that channel from within the transaction that changes it,
+//! so sidecar services (an alerter, a custom exporter) can `LISTEN` instead
+//! of polling. Postgres only delivers the notification once the transaction
+//! commits, and drops it on rollback.
+//!
+//! The payload is a JSON object:
+//!
+//! ```json
+//! {
+//!   "event": "created",
+//!   "id": 42,
+//!   "discord_id": "123456789012345678",
+//!   "guild_id": "876543210987654321",
+//!   "smoking_type_id": 1,
+//!   "quantity": 1,
+//!   "smoked_at": "2025-02-10T12:34:56.789Z"
+//! }
+//! ```
+//!
+//! `event` is `created` or `deleted`, `guild_id` is `null` for logs made
+//! outside a guild, and Discord IDs are hashed when `ANONYMIZE_SALT` is set.
+//! Unlike the outbox, notifications are not stored: a consumer that is not
+//! listening when a log changes misses it.
+
+use serde::Serialize;
+
+use crate::anonymize::Anonymizer;
+use crate::database::SmokingLog;
+
+/// What happened to a log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogChange {
+    Created,
+    Deleted,
+}
+
+/// Builds the notification payload for a changed log.
+///
+/// # Arguments
+/// * `change` - What happened to the log.
+/// * `log` - The log.
+/// * `anonymizer` - The anonymizer applied to Discord IDs.
+///
+/// # Returns
+/// The JSON payload.
+pub fn payload(change: LogChange, log: &SmokingLog, anonymizer: &Anonymizer) -> serde_json::Value {
+    serde_json::json!({
+        "event": change,
+        "id": log.id,
+        "discord_id": anonymizer.id(&log.discord_id),
+        "guild_id": anonymizer.optional_id(log.guild_id.as_deref()),
+        "smoking_type_id": log.smoking_type_id,
+        "quantity": log.quantity,
+        "smoked_at": log.smoked_at,
+    })
+}
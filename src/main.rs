@@ -6,33 +6,56 @@
 //! - Command framework setup
 //! - Discord client creation
 
-mod commands;
-mod config;
-mod database;
-
 use std::sync::Arc;
 
-use config::{Config, ConfigError};
-use commands::create_cigarette_ui;
-use database::Database;
+use cigarette_counter::{
+    achievements::{AchievementJob, ACHIEVEMENT_JOB},
+    anonymize::Anonymizer,
+    challenge::{ChallengeJob, CHALLENGE_JOB},
+    channel_counter::{ChannelCounterJob, CHANNEL_COUNTER_JOB},
+    circuit_breaker::{CircuitBreaker, CircuitOpen, UNAVAILABLE_MESSAGE},
+    cli::{self, Cli},
+    clock::{Clock, SystemClock},
+    command_sync,
+    commands::{self, help},
+    config::{Config, ConfigError},
+    confirmation_cleanup::{ConfirmationCleanupJob, CONFIRMATION_CLEANUP_JOB},
+    database::{Database, EXPECTED_INDEXES},
+    db_limiter::DbLimiter,
+    email::{EmailDigestJob, EmailError, Mailer, SmtpMailer, EMAIL_DIGEST_JOB},
+    event_bus::EventBus,
+    events, feed, format,
+    forum::{WeeklyThreadJob, WEEKLY_THREAD_JOB},
+    frontend::{Frontend, Reply},
+    guild_allowlist::{self, GuildNotAllowed, NOT_ALLOWED_MESSAGE},
+    guild_removal::{GuildDataDeletionJob, GUILD_DATA_DELETION_JOB},
+    heartbeat, http,
+    jobs::JobWorker,
+    latency,
+    leader::LeaderElection,
+    linked_roles::{LinkedRoles, PushJob, PUSH_JOB},
+    milestones::{self, SyncJob, SYNC_JOB},
+    notify::{
+        DmNotifier, EmailNotifier, NotificationRouter, NotifyChannel, PushNotifier, RateLimit,
+    },
+    outbox::{self, WebhookSink},
+    panel_health,
+    push::{NtfyProvider, PushProviders, PushService, PushoverProvider},
+    scripting::{ScriptError, ScriptHooks},
+    seasons::{SeasonJob, SEASON_JOB},
+    service::{LoggingService, StatsService},
+    shards, systemd,
+    wagers::{WagerJob, WAGER_JOB},
+    write_buffer::WriteBuffer,
+    Data, Error,
+};
+use clap::Parser;
 use poise::{
     serenity_prelude::{self as serenity, futures::lock::Mutex},
     PrefixFrameworkOptions,
 };
-use sqlx::PgPool;
-use tracing::{error, info};
-
-/// Shared application state containing the database connection
-pub struct Data {
-    /// Thread-safe, async database connection wrapped in Arc<Mutex>
-    pub database: Arc<Mutex<Database>>,
-}
-
-/// Type alias for boxed errors that can be sent between threads
-pub type Error = Box<dyn std::error::Error + Send + Sync>;
-
-/// Type alias for command context containing application state
-pub type Context<'a> = poise::Context<'a, Data, Error>;
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use tracing::{error, info, warn};
 
 /// Main error type for the bot application
 #[derive(Debug, thiserror::Error)]
@@ -40,44 +63,215 @@ pub enum BotError {
     /// Error occurred while loading or parsing configuration
     #[error("Configuration error: {0}")]
     Config(#[from] ConfigError),
-    
+
     /// Error occurred during database operations
     #[error("Database connection error: {0}")]
     Database(#[from] sqlx::Error),
-    
+
     /// Error occurred in the Discord client
     #[error("Client error: {0}")]
-    Client(#[from] serenity::Error),
+    Client(Box<serenity::Error>),
+
+    /// Error occurred while applying database migrations
+    #[error("Migration error: {0}")]
+    Migration(#[from] sqlx::migrate::MigrateError),
+
+    /// Error occurred while starting the HTTP API
+    #[error("HTTP server error: {0}")]
+    Http(#[from] std::io::Error),
+
+    /// Error occurred while loading hook scripts
+    #[error("Scripting error: {0}")]
+    Scripting(#[from] ScriptError),
+
+    /// Error occurred while opening the write buffer
+    #[error("Write buffer error: {0}")]
+    WriteBuffer(std::io::Error),
+
+    /// Error occurred while setting up email
+    #[error("Email error: {0}")]
+    Email(#[from] EmailError),
+
+    /// Error occurred while running a maintenance task
+    #[error("Maintenance task failed: {0}")]
+    Task(Error),
+}
+
+impl From<serenity::Error> for BotError {
+    fn from(error: serenity::Error) -> Self {
+        Self::Client(Box::new(error))
+    }
 }
 
 /// Sets up the command framework with bot configuration and commands
 ///
 /// # Arguments
 /// * `config` - Loaded bot configuration
-/// * `db` - Database connection to be shared across commands
+/// * `data` - Application state shared across commands
 ///
 /// # Returns
 /// Configured Poise framework instance
-async fn setup_framework(config: &Config, db: Database) -> poise::Framework<Data, Error> {
+async fn setup_framework(config: &Config, data: Data) -> poise::Framework<Data, Error> {
+    let registration = config
+        .register_commands
+        .then_some(config.command_guild_id.map(serenity::GuildId::new));
+
     poise::Framework::builder()
         .options(poise::FrameworkOptions {
-            commands: vec![create_cigarette_ui()],
-            prefix_options: PrefixFrameworkOptions {
-                prefix: Some(config.command_prefix.clone()),
-                ..Default::default()
+            commands: commands::enabled_commands(&config.disabled_modules),
+            event_handler: |ctx, event, _framework, data| {
+                Box::pin(events::handle_event(ctx, event, data))
+            },
+            // Without the message content intent only mentions would reach the
+            // prefix parser, so interaction-only deployments parse no messages.
+            prefix_options: if config.interaction_only {
+                PrefixFrameworkOptions {
+                    prefix: None,
+                    mention_as_prefix: false,
+                    ..Default::default()
+                }
+            } else {
+                PrefixFrameworkOptions {
+                    prefix: Some(config.command_prefix.clone()),
+                    ..Default::default()
+                }
+            },
+            pre_command: |ctx| {
+                Box::pin(async move { latency::begin(format!("command {}", ctx.command().name)) })
+            },
+            command_check: Some(|ctx| {
+                Box::pin(async move {
+                    guild_allowlist::check(&ctx.data().config.allowed_guilds, ctx.guild_id())?;
+                    ctx.data().db_breaker.allow()?;
+                    Ok(true)
+                })
+            }),
+            post_command: |ctx| {
+                Box::pin(async move {
+                    latency::end();
+                    ctx.data().db_breaker.record_success();
+                })
+            },
+            on_error: |error| {
+                Box::pin(async move {
+                    latency::end();
+                    if let Err(e) = handle_framework_error(error).await {
+                        error!("Error while handling error: {}", e);
+                    }
+                })
             },
             ..Default::default()
         })
-        .setup(|_ctx, _ready, _framework| {
+        .setup(move |ctx, _ready, framework| {
             Box::pin(async move {
-                Ok(Data {
-                    database: Arc::new(Mutex::new(db)),
-                })
+                if let Some(guild_id) = registration {
+                    register_commands(&ctx.http, &framework.options().commands, guild_id).await;
+                }
+                systemd::notify_ready();
+                Ok(data)
             })
         })
         .build()
 }
 
+/// Registers the slash commands at startup, logging what changed
+///
+/// A failure is only logged; owners can retry with `register`.
+///
+/// # Arguments
+/// * `http` - The HTTP client
+/// * `commands` - The enabled commands of the framework
+/// * `guild_id` - The guild to register in, or `None` to register globally
+async fn register_commands(
+    http: &serenity::Http,
+    commands: &[poise::Command<Data, Error>],
+    guild_id: Option<serenity::GuildId>,
+) {
+    let scope = guild_id.map_or("globally".to_string(), |guild_id| {
+        format!("in guild {}", guild_id)
+    });
+    match command_sync::sync_commands(http, commands, guild_id).await {
+        Ok(changes) => info!(
+            "Registered commands {}: {} added, {} updated, {} removed, {} unchanged",
+            scope,
+            changes.added.len(),
+            changes.updated.len(),
+            changes.removed.len(),
+            changes.unchanged
+        ),
+        Err(e) => error!("Failed to register commands {}: {}", scope, e),
+    }
+}
+
+/// Handles an error raised while running a command
+///
+/// Command failures are reported to the database circuit breaker and
+/// answered with an error embed, as are commands rejected because the
+/// circuit is open or the guild is not on the allowlist; failures and
+/// unparsable arguments carry a button opening
+/// the command's help. Everything else is handled by poise's default handler.
+///
+/// # Arguments
+/// * `error` - The framework error
+///
+/// # Returns
+/// Result indicating success or an error if the reply could not be sent
+async fn handle_framework_error(
+    error: poise::FrameworkError<'_, Data, Error>,
+) -> Result<(), Error> {
+    match error {
+        poise::FrameworkError::Command { error, ctx, .. } => {
+            ctx.data()
+                .db_breaker
+                .record_outcome(Some(error.as_ref() as _));
+            error!("Command {} failed: {}", ctx.command().qualified_name, error);
+            return ctx
+                .send_reply(
+                    help::with_help_button(
+                        Reply::error(format!("コマンドを実行できませんでした: {}", error)),
+                        &ctx.command().qualified_name,
+                        Frontend::locale(&ctx),
+                    )
+                    .ephemeral(),
+                )
+                .await;
+        }
+        poise::FrameworkError::ArgumentParse { error, ctx, .. } => {
+            return ctx
+                .send_reply(
+                    help::with_help_button(
+                        Reply::error(format!("引数を読み取れませんでした: {}", error)),
+                        &ctx.command().qualified_name,
+                        Frontend::locale(&ctx),
+                    )
+                    .ephemeral(),
+                )
+                .await;
+        }
+        poise::FrameworkError::CommandCheckFailed {
+            error: Some(ref error),
+            ctx,
+            ..
+        } if error.is::<CircuitOpen>() => {
+            return ctx
+                .send_reply(Reply::error(UNAVAILABLE_MESSAGE).ephemeral())
+                .await;
+        }
+        poise::FrameworkError::CommandCheckFailed {
+            error: Some(ref error),
+            ctx,
+            ..
+        } if error.is::<GuildNotAllowed>() => {
+            return ctx
+                .send_reply(Reply::error(NOT_ALLOWED_MESSAGE).ephemeral())
+                .await;
+        }
+        _ => {}
+    }
+
+    Ok(poise::builtins::on_error(error).await?)
+}
+
 /// Creates and configures the Discord client with the command framework
 ///
 /// # Arguments
@@ -86,10 +280,11 @@ async fn setup_framework(config: &Config, db: Database) -> poise::Framework<Data
 ///
 /// # Returns
 /// Result containing the initialized Discord client or a BotError
-async fn create_client(config: &Config, framework: poise::Framework<Data, Error>) -> Result<serenity::Client, BotError> {
-    let intents = serenity::GatewayIntents::non_privileged() | serenity::GatewayIntents::all();
-    
-    serenity::ClientBuilder::new(&config.bot_token, intents)
+async fn create_client(
+    config: &Config,
+    framework: poise::Framework<Data, Error>,
+) -> Result<serenity::Client, BotError> {
+    serenity::ClientBuilder::new(&config.bot_token, config.gateway_intents())
         .framework(framework)
         .await
         .map_err(BotError::from)
@@ -103,36 +298,496 @@ async fn create_client(config: &Config, framework: poise::Framework<Data, Error>
 /// # Returns
 /// Result containing the database connection pool or a BotError
 async fn connect_database(config: &Config) -> Result<PgPool, BotError> {
-    PgPool::connect(&config.database_url)
+    PgPoolOptions::new()
+        .max_connections(config.db_max_concurrency)
+        .connect(&config.database_url)
         .await
         .map_err(BotError::from)
 }
 
+/// Warns about expected indexes that are missing from the database
+///
+/// Missing indexes do not prevent startup but make the hot queries degrade
+/// as the log tables grow.
+///
+/// # Arguments
+/// * `database` - Database connection to check
+async fn check_indexes(database: &Database) {
+    match database.find_missing_indexes(EXPECTED_INDEXES).await {
+        Ok(missing) if missing.is_empty() => {}
+        Ok(missing) => warn!(
+            "Expected database indexes are missing: {}. Run the migrations to create them.",
+            missing.join(", ")
+        ),
+        Err(e) => warn!("Failed to check database indexes: {}", e),
+    }
+}
+
+/// Starts the inbound HTTP API in the background if `HTTP_BIND` is configured
+///
+/// # Arguments
+/// * `config` - Loaded bot configuration containing the bind address
+/// * `database` - Database connection shared with the bot
+/// * `linked_roles` - Linked-roles client, if configured
+/// * `db_limiter` - Database concurrency limiter shared with the bot
+/// * `db_breaker` - Database circuit breaker shared with the bot
+/// * `logging` - Logging service shared with the bot
+/// * `clock` - Clock shared with the bot
+///
+/// # Returns
+/// Result indicating success or a BotError if the listener could not be bound
+async fn start_http_server(
+    config: &Config,
+    database: Arc<Mutex<Database>>,
+    linked_roles: Option<Arc<LinkedRoles>>,
+    db_limiter: Arc<DbLimiter>,
+    db_breaker: Arc<CircuitBreaker>,
+    logging: Arc<LoggingService>,
+    clock: Arc<dyn Clock>,
+) -> Result<(), BotError> {
+    let Some(bind) = &config.http_bind else {
+        return Ok(());
+    };
+
+    let listener = tokio::net::TcpListener::bind(bind).await?;
+    info!("HTTP API listening on {}", bind);
+
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(
+            listener,
+            http::router(
+                database,
+                linked_roles,
+                db_limiter,
+                db_breaker,
+                logging,
+                clock,
+            ),
+        )
+        .await
+        {
+            error!("HTTP API stopped: {}", e);
+        }
+    });
+
+    Ok(())
+}
+
+/// Loads the hook scripts if `SCRIPTS_DIR` is configured
+///
+/// # Arguments
+/// * `config` - Loaded bot configuration containing the scripts directory
+///
+/// # Returns
+/// Result containing the loaded scripts (empty if not configured) or a BotError
+fn load_scripts(config: &Config) -> Result<Arc<ScriptHooks>, BotError> {
+    let Some(dir) = &config.scripts_dir else {
+        return Ok(Arc::new(ScriptHooks::default()));
+    };
+
+    let scripts = ScriptHooks::load(dir)?;
+    info!(
+        "Loaded {} hook scripts from {}",
+        scripts.len(),
+        dir.display()
+    );
+
+    Ok(Arc::new(scripts))
+}
+
+/// Starts delivering outbox messages if `WEBHOOK_URL` is configured
+///
+/// # Arguments
+/// * `config` - Loaded bot configuration containing the webhook URL
+/// * `database` - Database connection shared with the bot
+/// * `clock` - Source of the current time shared with the bot
+/// * `leader` - Election deciding whether this replica delivers
+fn start_outbox(
+    config: &Config,
+    database: Arc<Mutex<Database>>,
+    clock: Arc<dyn Clock>,
+    leader: Arc<LeaderElection>,
+) {
+    let Some(url) = &config.webhook_url else {
+        return;
+    };
+
+    outbox::spawn_delivery_task(
+        database,
+        clock,
+        Arc::new(WebhookSink::new(url.clone())),
+        config.outbox_max_attempts,
+        leader,
+    );
+    info!("Outbox delivery enabled");
+}
+
+/// Opens the write buffer if `WRITE_BUFFER_PATH` is configured and starts replaying it
+///
+/// # Arguments
+/// * `config` - Loaded bot configuration containing the buffer path and capacity
+/// * `logging` - Logging service writing the buffered logs
+/// * `db_breaker` - Database circuit breaker deciding when to replay
+///
+/// # Returns
+/// Result containing the buffer, `None` if buffering is disabled, or a BotError
+fn start_write_buffer(
+    config: &Config,
+    logging: Arc<LoggingService>,
+    db_breaker: Arc<CircuitBreaker>,
+) -> Result<Option<Arc<WriteBuffer>>, BotError> {
+    let Some(path) = &config.write_buffer_path else {
+        return Ok(None);
+    };
+
+    let buffer = Arc::new(
+        WriteBuffer::open(path, config.write_buffer_capacity).map_err(BotError::WriteBuffer)?,
+    );
+    buffer.clone().spawn_replay_task(logging, db_breaker);
+    info!("Write buffer enabled at {}", path.display());
+
+    Ok(Some(buffer))
+}
+
+/// Creates the mailer of the email digests if `SMTP_URL` is configured
+///
+/// # Arguments
+/// * `config` - Loaded bot configuration containing the SMTP server and sender
+///
+/// # Returns
+/// Result containing the mailer, `None` if email is disabled, or a BotError
+fn setup_mailer(config: &Config) -> Result<Option<Arc<dyn Mailer>>, BotError> {
+    let (Some(url), Some(from)) = (&config.smtp_url, &config.smtp_from) else {
+        return Ok(None);
+    };
+    if config.public_url.is_none() {
+        warn!("SMTP_URL is set but PUBLIC_URL is not; email digests are disabled");
+        return Ok(None);
+    }
+
+    let mailer = SmtpMailer::new(url, from)?;
+    info!("Email digests enabled");
+
+    Ok(Some(Arc::new(mailer)))
+}
+
+/// Enables the push services configured with `NTFY_URL` and `PUSHOVER_TOKEN`
+///
+/// # Arguments
+/// * `config` - Loaded bot configuration containing the push service settings
+///
+/// # Returns
+/// The enabled push services
+fn setup_push(config: &Config) -> PushProviders {
+    let mut push = PushProviders::default();
+    if let Some(url) = &config.ntfy_url {
+        push = push.with(PushService::Ntfy, Arc::new(NtfyProvider::new(url.clone())));
+        info!("ntfy notifications enabled");
+    }
+    if let Some(token) = &config.pushover_token {
+        push = push.with(
+            PushService::Pushover,
+            Arc::new(PushoverProvider::new(token.clone())),
+        );
+        info!("Pushover notifications enabled");
+    }
+
+    push
+}
+
+/// Builds the router delivering personal notifications on every enabled channel
+///
+/// # Arguments
+/// * `database` - Database connection shared with the bot
+/// * `http` - Discord HTTP client sending DMs
+/// * `mailer` - The mailer, if email is enabled
+/// * `push` - The enabled push services
+///
+/// # Returns
+/// The notification router
+fn setup_notifications(
+    database: Arc<Mutex<Database>>,
+    http: Arc<serenity::Http>,
+    mailer: Option<Arc<dyn Mailer>>,
+    push: &PushProviders,
+) -> NotificationRouter {
+    let limit = |channel: NotifyChannel| RateLimit::per_minute(channel.default_rate_limit());
+    let mut router = NotificationRouter::new(database.clone()).with(
+        NotifyChannel::Dm,
+        Arc::new(DmNotifier::new(http)),
+        limit(NotifyChannel::Dm),
+    );
+    if let Some(mailer) = mailer {
+        router = router.with(
+            NotifyChannel::Email,
+            Arc::new(EmailNotifier::new(database.clone(), mailer)),
+            limit(NotifyChannel::Email),
+        );
+    }
+    for service in [PushService::Ntfy, PushService::Pushover] {
+        if let Some(provider) = push.get(service) {
+            router = router.with(
+                service.into(),
+                Arc::new(PushNotifier::new(database.clone(), service, provider)),
+                limit(service.into()),
+            );
+        }
+    }
+
+    router
+}
+
+/// Sets up the Discord Linked Roles integration if it is configured
+///
+/// Registers the metadata schema. The periodic metadata push runs as a scheduled job.
+///
+/// # Arguments
+/// * `config` - Loaded bot configuration containing the OAuth credentials
+/// * `clock` - Source of the current time shared with the bot
+/// * `stats` - Statistics service computing the metadata
+///
+/// # Returns
+/// The linked-roles client, or `None` if the integration is not configured
+async fn setup_linked_roles(
+    config: &Config,
+    clock: Arc<dyn Clock>,
+    stats: Arc<StatsService>,
+) -> Option<Arc<LinkedRoles>> {
+    let linked_roles = Arc::new(LinkedRoles::from_config(config, clock, stats)?);
+
+    if let Err(e) = linked_roles.register_metadata().await {
+        error!("Failed to register linked-roles metadata: {}", e);
+    }
+    info!("Linked roles enabled");
+
+    Some(linked_roles)
+}
+
 /// Main entry point for the bot application
 ///
-/// Initializes the bot by:
+/// When a maintenance subcommand is given, runs it and exits (see `cli`).
+/// Otherwise initializes the bot by:
 /// 1. Setting up logging
-/// 2. Loading configuration
+/// 2. Loading configuration and hook scripts
 /// 3. Connecting to the database
-/// 4. Setting up the command framework
-/// 5. Creating and starting the Discord client
+/// 4. Starting outbox delivery, linked roles and the inbound HTTP API
+/// 5. Setting up the command framework
+/// 6. Creating the Discord client and starting the heartbeat, systemd watchdog, panel health
+///    checks and scheduled job worker
+/// 7. Starting the Discord client
 ///
 /// # Returns
 /// Result indicating success or a BotError
 #[tokio::main]
 async fn main() -> Result<(), BotError> {
     tracing_subscriber::fmt::init();
+    let cli = Cli::parse();
+    if let Some(task) = cli.task {
+        let config = Config::load()?;
+        let pool = connect_database(&config).await?;
+        return cli::run(task, &config, pool).await.map_err(BotError::Task);
+    }
+
     info!("Starting cigarette counter bot...");
+    heartbeat::mark_started();
 
     let config = Config::load()?;
+    latency::set_slow_threshold(config.slow_request_threshold);
+    format::set_branding(config.branding.clone());
+    if config.interaction_only {
+        info!("Interaction-only mode: prefix commands and the message content intent are disabled");
+    }
     let pool = connect_database(&config).await?;
-    let db = Database::new(pool);
-    
-    let framework = setup_framework(&config, db).await;
+    if config.auto_migrate {
+        let applied = cli::migrate(&pool).await?;
+        info!("Applied {} pending database migrations", applied);
+    }
+    let leader = Arc::new(LeaderElection::new(pool.clone()));
+    let mut database =
+        Database::new(pool).with_anonymizer(Anonymizer::new(config.anonymize_salt.clone()));
+    if config.webhook_url.is_some() {
+        database = database.with_outbox();
+    }
+    if let Some(channel) = &config.log_notify_channel {
+        database = database.with_log_notifications(channel);
+    }
+    check_indexes(&database).await;
+    let database = Arc::new(Mutex::new(database));
+    let db_limiter = Arc::new(DbLimiter::new(
+        config.db_max_concurrency,
+        config.db_acquire_timeout,
+    ));
+    let db_breaker = Arc::new(CircuitBreaker::new(
+        config.db_breaker_failures,
+        config.db_breaker_open_for,
+    ));
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock::new(config.timezone));
+    let scripts = load_scripts(&config)?;
+    start_outbox(&config, database.clone(), clock.clone(), leader.clone());
+    let event_bus = Arc::new(EventBus::default());
+
+    let logging = Arc::new(LoggingService::new(
+        database.clone(),
+        clock.clone(),
+        scripts.clone(),
+        event_bus.clone(),
+    ));
+    let stats = Arc::new(
+        StatsService::new(database.clone(), clock.clone()).with_cache_ttl(config.stats_cache_ttl),
+    );
+    let write_buffer = start_write_buffer(&config, logging.clone(), db_breaker.clone())?;
+    stats.spawn_invalidation_task(&event_bus);
+
+    let linked_roles = setup_linked_roles(&config, clock.clone(), stats.clone()).await;
+    let mut jobs = JobWorker::new(database.clone(), clock.clone());
+    if let Some(linked_roles) = &linked_roles {
+        jobs = jobs.register(
+            PUSH_JOB,
+            Arc::new(PushJob::new(linked_roles.clone(), database.clone())),
+        );
+    }
+    let mailer = setup_mailer(&config)?;
+    if let (Some(mailer), Some(public_url)) = (&mailer, &config.public_url) {
+        jobs = jobs.register(
+            EMAIL_DIGEST_JOB,
+            Arc::new(EmailDigestJob::new(
+                database.clone(),
+                clock.clone(),
+                mailer.clone(),
+                public_url.clone(),
+            )),
+        );
+    }
+    start_http_server(
+        &config,
+        database.clone(),
+        linked_roles,
+        db_limiter.clone(),
+        db_breaker.clone(),
+        logging.clone(),
+        clock.clone(),
+    )
+    .await?;
+
+    let push = Arc::new(setup_push(&config));
+    let data = Data {
+        database: database.clone(),
+        config: Arc::new(config.clone()),
+        db_limiter,
+        db_breaker,
+        clock: clock.clone(),
+        scripts: scripts.clone(),
+        events: event_bus.clone(),
+        logging,
+        stats: stats.clone(),
+        write_buffer,
+        mailer: mailer.clone(),
+        push: push.clone(),
+    };
+    let framework = setup_framework(&config, data).await;
     let mut client = create_client(&config, framework).await?;
+    heartbeat::spawn(
+        database.clone(),
+        clock.clone(),
+        client.shard_manager.clone(),
+    );
+    shards::spawn(
+        client.cache.clone(),
+        client.shard_manager.clone(),
+        clock.clone(),
+    );
+    systemd::spawn_watchdog(
+        database.clone(),
+        clock.clone(),
+        client.shard_manager.clone(),
+    );
+    panel_health::spawn(client.http.clone(), database.clone(), clock.clone());
+    feed::spawn_milestone_recorder(&event_bus, database.clone(), clock.clone());
+    let notifications = Arc::new(setup_notifications(
+        database.clone(),
+        client.http.clone(),
+        mailer,
+        &push,
+    ));
+    milestones::spawn_congratulation_task(
+        &event_bus,
+        client.http.clone(),
+        database.clone(),
+        scripts,
+        notifications,
+    );
+    jobs.register(
+        SYNC_JOB,
+        Arc::new(SyncJob::new(
+            client.http.clone(),
+            database.clone(),
+            stats,
+            event_bus,
+        )),
+    )
+    .register(
+        WEEKLY_THREAD_JOB,
+        Arc::new(WeeklyThreadJob::new(
+            client.http.clone(),
+            database.clone(),
+            clock.clone(),
+        )),
+    )
+    .register(
+        CHALLENGE_JOB,
+        Arc::new(ChallengeJob::new(
+            client.http.clone(),
+            database.clone(),
+            clock.clone(),
+        )),
+    )
+    .register(
+        WAGER_JOB,
+        Arc::new(WagerJob::new(
+            client.http.clone(),
+            database.clone(),
+            clock.clone(),
+        )),
+    )
+    .register(
+        ACHIEVEMENT_JOB,
+        Arc::new(AchievementJob::new(database.clone(), clock.clone())),
+    )
+    .register(
+        SEASON_JOB,
+        Arc::new(SeasonJob::new(
+            client.http.clone(),
+            database.clone(),
+            clock.clone(),
+        )),
+    )
+    .register(
+        CHANNEL_COUNTER_JOB,
+        Arc::new(ChannelCounterJob::new(
+            client.http.clone(),
+            database.clone(),
+            clock.clone(),
+        )),
+    )
+    .register(
+        CONFIRMATION_CLEANUP_JOB,
+        Arc::new(ConfirmationCleanupJob::new(
+            client.http.clone(),
+            database.clone(),
+            clock.clone(),
+        )),
+    )
+    .register(
+        GUILD_DATA_DELETION_JOB,
+        Arc::new(GuildDataDeletionJob::new(database, clock)),
+    )
+    .spawn(leader.clone());
 
     info!("Bot is running!");
-    client.start().await?;
+    let result = client.start().await;
+    systemd::notify_stopping();
+    leader.resign().await;
+    result?;
 
     Ok(())
 }
@@ -0,0 +1,371 @@
+//! Milestone engine assigning guild roles for smoke-free streaks.
+//!
+//! Guild admins map roles to a number of smoke-free days with `roles map`.
+//! Members who reach the milestone receive the role; it is removed again as
+//! soon as they log a cigarette. Granting a role publishes a
+//! `MilestoneReached` event; the congratulation task reacts to it with a DM
+//! using the guild's `milestone` template followed by the output of
+//! `on_milestone_reached` scripts, delivered on the member's preferred
+//! notification channel (see `notify`).
+
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+use tracing::{error, warn};
+
+use crate::clock::Clock;
+use crate::database::{Database, ScheduledJob};
+use crate::event_bus::{DomainEvent, EventBus};
+use crate::format::{format_number, Locale};
+use crate::jobs::JobHandler;
+use crate::notify::{Notification, NotificationRouter};
+use crate::scripting::{ScriptEvent, ScriptHooks};
+use crate::service::StatsService;
+use crate::templates::{self, TemplateKey};
+
+/// Interval between full milestone role synchronizations
+const SYNC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Audit log reason attached to role changes
+const AUDIT_REASON: &str = "Smoke-free milestone";
+
+/// A milestone expressed as a number of smoke-free days, written as e.g. `7days`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Milestone {
+    pub smoke_free_days: i32,
+}
+
+/// Error returned when a milestone argument cannot be parsed
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid milestone: {0} (expected e.g. 7days)")]
+pub struct ParseMilestoneError(String);
+
+impl FromStr for Milestone {
+    type Err = ParseMilestoneError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let days = s
+            .strip_suffix("days")
+            .or_else(|| s.strip_suffix("day"))
+            .or_else(|| s.strip_suffix('d'))
+            .unwrap_or(s);
+
+        match days.parse::<i32>() {
+            Ok(smoke_free_days) if smoke_free_days > 0 => Ok(Self { smoke_free_days }),
+            _ => Err(ParseMilestoneError(s.to_string())),
+        }
+    }
+}
+
+/// Computes how many full days have passed since the user's last smoking event.
+///
+/// # Arguments
+/// * `db` - The database.
+/// * `clock` - The clock determining the current date.
+/// * `discord_id` - The Discord ID of the user.
+///
+/// # Returns
+/// A Result containing the number of smoke-free days (0 if the user has never logged) or an `Error`.
+pub async fn days_smoke_free(
+    db: &Database,
+    clock: &dyn Clock,
+    discord_id: &str,
+) -> Result<i64, sqlx::Error> {
+    Ok(db
+        .get_last_smoked_at(discord_id)
+        .await?
+        .map(|smoked_at| {
+            let last_date = clock.local_date(smoked_at);
+            (clock.today() - last_date).num_days().max(0)
+        })
+        .unwrap_or_default())
+}
+
+/// Assigns or removes a member's milestone roles in a guild.
+///
+/// # Arguments
+/// * `http` - The Discord HTTP client.
+/// * `database` - Database connection shared with the bot.
+/// * `stats` - The statistics service.
+/// * `events` - The event bus notified of granted milestones.
+/// * `guild_id` - The guild to synchronize.
+/// * `user_id` - The member to synchronize.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn sync_member_roles(
+    http: &serenity::Http,
+    database: &Mutex<Database>,
+    stats: &StatsService,
+    events: &EventBus,
+    guild_id: serenity::GuildId,
+    user_id: serenity::UserId,
+) -> Result<(), crate::Error> {
+    let milestone_roles = database
+        .lock()
+        .await
+        .guild(&guild_id.to_string())
+        .get_milestone_roles()
+        .await?;
+    // Paused members keep their roles until tracking resumes.
+    if milestone_roles.is_empty() || stats.is_tracking_paused(&user_id.to_string()).await? {
+        return Ok(());
+    }
+    let days = stats.days_smoke_free(&user_id.to_string()).await?;
+
+    let member = match guild_id.member(http, user_id).await {
+        Ok(member) => member,
+        // The user has left the guild; nothing to update.
+        Err(serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(response)))
+            if response.status_code == serenity::StatusCode::NOT_FOUND =>
+        {
+            return Ok(())
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    for milestone_role in milestone_roles {
+        let role_id = serenity::RoleId::new(milestone_role.role_id.parse()?);
+        let reached = days >= i64::from(milestone_role.smoke_free_days);
+        let has_role = member.roles.contains(&role_id);
+
+        if reached && !has_role {
+            http.add_member_role(guild_id, user_id, role_id, Some(AUDIT_REASON))
+                .await?;
+            events.publish(DomainEvent::MilestoneReached {
+                discord_id: user_id.to_string(),
+                guild_id: guild_id.to_string(),
+                role_id: milestone_role.role_id,
+                smoke_free_days: milestone_role.smoke_free_days,
+            });
+        } else if !reached && has_role {
+            http.remove_member_role(guild_id, user_id, role_id, Some(AUDIT_REASON))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Congratulates a member on a milestone role they just received.
+///
+/// # Arguments
+/// * `http` - The Discord HTTP client.
+/// * `database` - Database connection shared with the bot.
+/// * `scripts` - The hook scripts.
+/// * `notifications` - The router delivering the congratulation.
+/// * `guild_id` - The guild the role was granted in.
+/// * `user_id` - The member.
+/// * `role_id` - The granted role.
+/// * `smoke_free_days` - The reached milestone.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+#[allow(clippy::too_many_arguments)]
+async fn congratulate(
+    http: &serenity::Http,
+    database: &Mutex<Database>,
+    scripts: &ScriptHooks,
+    notifications: &NotificationRouter,
+    guild_id: serenity::GuildId,
+    user_id: serenity::UserId,
+    role_id: serenity::RoleId,
+    smoke_free_days: i32,
+) -> Result<(), crate::Error> {
+    let guild = guild_id.to_string();
+    let template = {
+        let db = database.lock().await;
+        templates::load_template(&db, Some(&guild), TemplateKey::Milestone).await?
+    };
+    let member = guild_id.member(http, user_id).await?;
+    let role_name = guild_id
+        .roles(http)
+        .await?
+        .get(&role_id)
+        .map(|role| role.name.clone())
+        .unwrap_or_default();
+
+    let mut lines = vec![template.render(&[
+        ("user", member.display_name()),
+        (
+            "days",
+            &format_number(smoke_free_days.into(), Locale::default()),
+        ),
+        ("role", &role_name),
+    ])];
+    lines.extend(scripts.run(&ScriptEvent::MilestoneReached {
+        user_id: user_id.to_string(),
+        guild_id: guild,
+        days: smoke_free_days,
+        role: role_name,
+    }));
+
+    let notification = Notification {
+        title: format!("禁煙{}日を達成しました", smoke_free_days),
+        message: lines.join("\n"),
+    };
+    if notifications
+        .send(&user_id.to_string(), &notification)
+        .await?
+        .is_none()
+    {
+        warn!("No notification channel reached {}", user_id);
+    }
+
+    Ok(())
+}
+
+/// Subscribes the task congratulating members on their milestones to the event bus.
+///
+/// # Arguments
+/// * `events` - The event bus.
+/// * `http` - The Discord HTTP client.
+/// * `database` - Database connection shared with the bot.
+/// * `scripts` - The hook scripts.
+/// * `notifications` - The router delivering congratulations.
+pub fn spawn_congratulation_task(
+    events: &EventBus,
+    http: Arc<serenity::Http>,
+    database: Arc<Mutex<Database>>,
+    scripts: Arc<ScriptHooks>,
+    notifications: Arc<NotificationRouter>,
+) {
+    events.spawn_subscriber("milestone congratulations", move |event| {
+        let http = http.clone();
+        let database = database.clone();
+        let scripts = scripts.clone();
+        let notifications = notifications.clone();
+
+        async move {
+            let DomainEvent::MilestoneReached {
+                discord_id,
+                guild_id,
+                role_id,
+                smoke_free_days,
+            } = event
+            else {
+                return;
+            };
+            let (Ok(guild), Ok(user), Ok(role)) = (
+                guild_id.parse().map(serenity::GuildId::new),
+                discord_id.parse().map(serenity::UserId::new),
+                role_id.parse().map(serenity::RoleId::new),
+            ) else {
+                return;
+            };
+
+            if let Err(e) = congratulate(
+                &http,
+                &database,
+                &scripts,
+                &notifications,
+                guild,
+                user,
+                role,
+                smoke_free_days,
+            )
+            .await
+            {
+                warn!("Failed to send milestone message to {}: {}", discord_id, e);
+            }
+        }
+    });
+}
+
+/// Synchronizes milestone roles for every known member of every configured guild.
+///
+/// Failures for individual members are logged and do not stop the remaining updates.
+///
+/// # Arguments
+/// * `http` - The Discord HTTP client.
+/// * `database` - Database connection shared with the bot.
+/// * `stats` - The statistics service.
+/// * `events` - The event bus notified of granted milestones.
+pub async fn sync_all(
+    http: &serenity::Http,
+    database: &Mutex<Database>,
+    stats: &StatsService,
+    events: &EventBus,
+) {
+    let guilds = match database.lock().await.get_milestone_guilds().await {
+        Ok(guilds) => guilds,
+        Err(e) => {
+            error!("Failed to load milestone guilds: {}", e);
+            return;
+        }
+    };
+
+    for guild in guilds {
+        let Ok(guild_id) = guild.parse().map(serenity::GuildId::new) else {
+            continue;
+        };
+        let user_ids = match database.lock().await.guild(&guild).get_user_ids().await {
+            Ok(user_ids) => user_ids,
+            Err(e) => {
+                error!("Failed to load members of guild {}: {}", guild, e);
+                continue;
+            }
+        };
+
+        for user in user_ids {
+            let Ok(user_id) = user.parse().map(serenity::UserId::new) else {
+                continue;
+            };
+            if let Err(e) =
+                sync_member_roles(http, database, stats, events, guild_id, user_id).await
+            {
+                warn!(
+                    "Failed to sync milestone roles for {} in {}: {}",
+                    user, guild, e
+                );
+            }
+        }
+    }
+}
+
+/// Scheduled job kind of the full milestone role synchronization
+pub const SYNC_JOB: &str = "milestone_sync";
+
+/// Job handler synchronizing milestone roles every `SYNC_INTERVAL`
+pub struct SyncJob {
+    http: Arc<serenity::Http>,
+    database: Arc<Mutex<Database>>,
+    stats: Arc<StatsService>,
+    events: Arc<EventBus>,
+}
+
+impl SyncJob {
+    /// Creates the handler.
+    ///
+    /// # Arguments
+    /// * `http` - The Discord HTTP client.
+    /// * `database` - Database connection shared with the bot.
+    /// * `stats` - The statistics service.
+    /// * `events` - The event bus notified of granted milestones.
+    pub fn new(
+        http: Arc<serenity::Http>,
+        database: Arc<Mutex<Database>>,
+        stats: Arc<StatsService>,
+        events: Arc<EventBus>,
+    ) -> Self {
+        Self {
+            http,
+            database,
+            stats,
+            events,
+        }
+    }
+}
+
+#[async_trait]
+impl JobHandler for SyncJob {
+    async fn run(&self, _job: &ScheduledJob) -> Result<(), crate::Error> {
+        sync_all(&self.http, &self.database, &self.stats, &self.events).await;
+        Ok(())
+    }
+
+    fn repeat_every(&self) -> Option<Duration> {
+        Some(SYNC_INTERVAL)
+    }
+}
@@ -0,0 +1,547 @@
+//! Delivery of personal notifications over the channel each member prefers.
+//!
+//! A `Notifier` delivers to one channel: Discord DMs, the member's verified
+//! email address, or their ntfy topic or Pushover key. The
+//! `NotificationRouter` tries the member's preferred channel (chosen with
+//! `notify`) and falls back to a DM when the member has no target on it, the
+//! channel is over its rate limit or delivery fails. Transient failures, such
+//! as timeouts and 5xx responses, are retried with exponential backoff before
+//! falling back; permanent ones, such as closed DMs, are not.
+
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, Mutex as StdMutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+use tracing::warn;
+
+use crate::database::Database;
+use crate::email::{Email, EmailError, Mailer};
+use crate::push::{PushError, PushProvider, PushService};
+
+/// Attempts per channel before falling back to the next one
+const DEFAULT_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry; doubled on every further failure
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Window of the per-channel rate limits
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// A channel members can receive notifications on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NotifyChannel {
+    Dm,
+    Email,
+    Ntfy,
+    Pushover,
+}
+
+impl NotifyChannel {
+    /// Every channel, in the order shown to users
+    pub const ALL: [NotifyChannel; 4] = [
+        NotifyChannel::Dm,
+        NotifyChannel::Email,
+        NotifyChannel::Ntfy,
+        NotifyChannel::Pushover,
+    ];
+
+    /// Returns the name stored in the database and typed in commands.
+    pub fn name(self) -> &'static str {
+        match self {
+            NotifyChannel::Dm => "dm",
+            NotifyChannel::Email => "email",
+            NotifyChannel::Ntfy => "ntfy",
+            NotifyChannel::Pushover => "pushover",
+        }
+    }
+
+    /// Returns the name shown to members.
+    pub fn display_name(self) -> &'static str {
+        match self {
+            NotifyChannel::Dm => "DM",
+            NotifyChannel::Email => "メール",
+            NotifyChannel::Ntfy => "ntfy",
+            NotifyChannel::Pushover => "Pushover",
+        }
+    }
+
+    /// Returns how many notifications the channel delivers per minute by default.
+    pub fn default_rate_limit(self) -> u32 {
+        match self {
+            NotifyChannel::Dm => 30,
+            NotifyChannel::Email => 20,
+            NotifyChannel::Ntfy => 60,
+            NotifyChannel::Pushover => 30,
+        }
+    }
+}
+
+impl From<PushService> for NotifyChannel {
+    fn from(service: PushService) -> Self {
+        match service {
+            PushService::Ntfy => NotifyChannel::Ntfy,
+            PushService::Pushover => NotifyChannel::Pushover,
+        }
+    }
+}
+
+/// Error returned when a notification channel name cannot be parsed
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown notification channel: {0}")]
+pub struct ParseNotifyChannelError(String);
+
+impl FromStr for NotifyChannel {
+    type Err = ParseNotifyChannelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|channel| channel.name() == s)
+            .ok_or_else(|| ParseNotifyChannelError(s.to_string()))
+    }
+}
+
+/// A personal notification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    /// The notification title, used where the channel has one
+    pub title: String,
+    /// The notification text
+    pub message: String,
+}
+
+/// Outcome of a delivery attempt that did not fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// The notification was delivered
+    Sent,
+    /// The member has no target on the channel
+    Unavailable,
+}
+
+/// Error returned when a notifier fails to deliver a notification
+#[derive(Debug, thiserror::Error)]
+pub enum NotifyError {
+    #[error("Temporary failure: {0}")]
+    Transient(String),
+    #[error("{0}")]
+    Permanent(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl NotifyError {
+    /// Returns whether the delivery may succeed if retried.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, NotifyError::Transient(_))
+    }
+}
+
+impl From<PushError> for NotifyError {
+    fn from(error: PushError) -> Self {
+        let transient = match &error {
+            PushError::Request(e) => e.is_timeout() || e.is_connect(),
+            PushError::Status(status) => {
+                status.is_server_error() || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+        };
+        if transient {
+            NotifyError::Transient(error.to_string())
+        } else {
+            NotifyError::Permanent(error.to_string())
+        }
+    }
+}
+
+impl From<EmailError> for NotifyError {
+    fn from(error: EmailError) -> Self {
+        match &error {
+            EmailError::Transport(e) if e.is_transient() => {
+                NotifyError::Transient(error.to_string())
+            }
+            _ => NotifyError::Permanent(error.to_string()),
+        }
+    }
+}
+
+impl From<serenity::Error> for NotifyError {
+    fn from(error: serenity::Error) -> Self {
+        let transient = match &error {
+            serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(response)) => {
+                response.status_code.is_server_error()
+            }
+            serenity::Error::Http(serenity::HttpError::Request(_)) => true,
+            _ => false,
+        };
+        if transient {
+            NotifyError::Transient(error.to_string())
+        } else {
+            NotifyError::Permanent(error.to_string())
+        }
+    }
+}
+
+/// A channel notifications are delivered on
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Delivers a notification to a member.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the member.
+    /// * `notification` - The notification.
+    ///
+    /// # Returns
+    /// A Result containing whether the member could be reached on the channel, or a `NotifyError`.
+    async fn notify(
+        &self,
+        discord_id: &str,
+        notification: &Notification,
+    ) -> Result<Delivery, NotifyError>;
+}
+
+/// Notifier sending Discord DMs
+pub struct DmNotifier {
+    http: Arc<serenity::Http>,
+}
+
+impl DmNotifier {
+    /// Creates a DM notifier.
+    ///
+    /// # Arguments
+    /// * `http` - The Discord HTTP client.
+    pub fn new(http: Arc<serenity::Http>) -> Self {
+        Self { http }
+    }
+}
+
+#[async_trait]
+impl Notifier for DmNotifier {
+    async fn notify(
+        &self,
+        discord_id: &str,
+        notification: &Notification,
+    ) -> Result<Delivery, NotifyError> {
+        let Ok(user_id) = discord_id.parse().map(serenity::UserId::new) else {
+            return Ok(Delivery::Unavailable);
+        };
+
+        user_id
+            .direct_message(
+                &self.http,
+                serenity::CreateMessage::new().content(&notification.message),
+            )
+            .await?;
+
+        Ok(Delivery::Sent)
+    }
+}
+
+/// Notifier emailing members' verified digest addresses
+pub struct EmailNotifier {
+    database: Arc<Mutex<Database>>,
+    mailer: Arc<dyn Mailer>,
+}
+
+impl EmailNotifier {
+    /// Creates an email notifier.
+    ///
+    /// # Arguments
+    /// * `database` - Database connection shared with the bot.
+    /// * `mailer` - The mailer.
+    pub fn new(database: Arc<Mutex<Database>>, mailer: Arc<dyn Mailer>) -> Self {
+        Self { database, mailer }
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    async fn notify(
+        &self,
+        discord_id: &str,
+        notification: &Notification,
+    ) -> Result<Delivery, NotifyError> {
+        let address = self
+            .database
+            .lock()
+            .await
+            .get_verified_email(discord_id)
+            .await?;
+        let Some(address) = address else {
+            return Ok(Delivery::Unavailable);
+        };
+
+        let email = Email {
+            subject: notification.title.clone(),
+            body: notification.message.clone(),
+        };
+        self.mailer.send(&address, &email).await?;
+
+        Ok(Delivery::Sent)
+    }
+}
+
+/// Notifier pushing to members' ntfy topics or Pushover keys
+pub struct PushNotifier {
+    database: Arc<Mutex<Database>>,
+    service: PushService,
+    provider: Arc<dyn PushProvider>,
+}
+
+impl PushNotifier {
+    /// Creates a push notifier.
+    ///
+    /// # Arguments
+    /// * `database` - Database connection shared with the bot.
+    /// * `service` - The push service.
+    /// * `provider` - The provider delivering through the service.
+    pub fn new(
+        database: Arc<Mutex<Database>>,
+        service: PushService,
+        provider: Arc<dyn PushProvider>,
+    ) -> Self {
+        Self {
+            database,
+            service,
+            provider,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for PushNotifier {
+    async fn notify(
+        &self,
+        discord_id: &str,
+        notification: &Notification,
+    ) -> Result<Delivery, NotifyError> {
+        let subscription = self
+            .database
+            .lock()
+            .await
+            .get_push_subscription(discord_id)
+            .await?;
+        let Some(subscription) = subscription.filter(|s| s.service == self.service.name()) else {
+            return Ok(Delivery::Unavailable);
+        };
+
+        self.provider
+            .push(&subscription.target, notification)
+            .await?;
+
+        Ok(Delivery::Sent)
+    }
+}
+
+/// A fixed-window limit of deliveries on one channel
+pub struct RateLimit {
+    limit: u32,
+    window: Duration,
+    state: StdMutex<(Instant, u32)>,
+}
+
+impl RateLimit {
+    /// Creates a rate limit.
+    ///
+    /// # Arguments
+    /// * `limit` - The maximum number of deliveries per window.
+    /// * `window` - The length of the window.
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            state: StdMutex::new((Instant::now(), 0)),
+        }
+    }
+
+    /// Creates a rate limit of deliveries per minute.
+    ///
+    /// # Arguments
+    /// * `limit` - The maximum number of deliveries per minute.
+    pub fn per_minute(limit: u32) -> Self {
+        Self::new(limit, RATE_LIMIT_WINDOW)
+    }
+
+    /// Takes one delivery from the current window.
+    ///
+    /// # Returns
+    /// `false` if the window's deliveries are used up.
+    pub fn try_acquire(&self) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        if now.duration_since(state.0) >= self.window {
+            *state = (now, 0);
+        }
+        if state.1 >= self.limit {
+            return false;
+        }
+        state.1 += 1;
+
+        true
+    }
+}
+
+/// A notifier and the limit of its channel
+struct Route {
+    notifier: Arc<dyn Notifier>,
+    rate_limit: RateLimit,
+}
+
+/// Routes notifications to the channel each member prefers
+pub struct NotificationRouter {
+    database: Arc<Mutex<Database>>,
+    routes: HashMap<NotifyChannel, Route>,
+    attempts: u32,
+    retry_delay: Duration,
+}
+
+impl NotificationRouter {
+    /// Creates a router without channels.
+    ///
+    /// # Arguments
+    /// * `database` - Database connection shared with the bot.
+    pub fn new(database: Arc<Mutex<Database>>) -> Self {
+        Self {
+            database,
+            routes: HashMap::new(),
+            attempts: DEFAULT_ATTEMPTS,
+            retry_delay: DEFAULT_RETRY_DELAY,
+        }
+    }
+
+    /// Enables a channel.
+    ///
+    /// # Arguments
+    /// * `channel` - The channel.
+    /// * `notifier` - The notifier delivering on it.
+    /// * `rate_limit` - The limit of deliveries on it.
+    pub fn with(
+        mut self,
+        channel: NotifyChannel,
+        notifier: Arc<dyn Notifier>,
+        rate_limit: RateLimit,
+    ) -> Self {
+        self.routes.insert(
+            channel,
+            Route {
+                notifier,
+                rate_limit,
+            },
+        );
+        self
+    }
+
+    /// Sets how often transient failures are attempted on one channel.
+    ///
+    /// # Arguments
+    /// * `attempts` - Attempts per channel, including the first.
+    /// * `retry_delay` - The delay before the first retry, doubled on every further one.
+    pub fn with_retries(mut self, attempts: u32, retry_delay: Duration) -> Self {
+        self.attempts = attempts.max(1);
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Returns the channels a member's notifications are tried on, in order.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the member.
+    ///
+    /// # Returns
+    /// A Result containing the preferred channel followed by DMs, or an `Error`.
+    pub async fn channels_for(&self, discord_id: &str) -> Result<Vec<NotifyChannel>, sqlx::Error> {
+        let preferred = self
+            .database
+            .lock()
+            .await
+            .get_notification_channel(discord_id)
+            .await?
+            .and_then(|channel| channel.parse().ok())
+            .unwrap_or(NotifyChannel::Dm);
+
+        let mut channels = vec![preferred];
+        if preferred != NotifyChannel::Dm {
+            channels.push(NotifyChannel::Dm);
+        }
+
+        Ok(channels)
+    }
+
+    /// Delivers a notification to a member on the first channel that reaches them.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the member.
+    /// * `notification` - The notification.
+    ///
+    /// # Returns
+    /// A Result containing the channel the notification was delivered on,
+    /// `None` if no channel reached the member, or an `Error`.
+    pub async fn send(
+        &self,
+        discord_id: &str,
+        notification: &Notification,
+    ) -> Result<Option<NotifyChannel>, sqlx::Error> {
+        for channel in self.channels_for(discord_id).await? {
+            let Some(route) = self.routes.get(&channel) else {
+                continue;
+            };
+            if !route.rate_limit.try_acquire() {
+                warn!(
+                    "Notification channel {} is over its rate limit",
+                    channel.name()
+                );
+                continue;
+            }
+            if self.deliver(route, channel, discord_id, notification).await {
+                return Ok(Some(channel));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Delivers a notification on one channel, retrying transient failures.
+    ///
+    /// # Returns
+    /// Whether the notification was delivered.
+    async fn deliver(
+        &self,
+        route: &Route,
+        channel: NotifyChannel,
+        discord_id: &str,
+        notification: &Notification,
+    ) -> bool {
+        let mut delay = self.retry_delay;
+        for attempt in 1..=self.attempts {
+            match route.notifier.notify(discord_id, notification).await {
+                Ok(Delivery::Sent) => return true,
+                Ok(Delivery::Unavailable) => return false,
+                Err(e) if e.is_transient() && attempt < self.attempts => {
+                    warn!(
+                        "Retrying notification to {} via {}: {}",
+                        discord_id,
+                        channel.name(),
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay = delay.saturating_mul(2);
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to notify {} via {}: {}",
+                        discord_id,
+                        channel.name(),
+                        e
+                    );
+                    return false;
+                }
+            }
+        }
+
+        false
+    }
+}
@@ -0,0 +1,550 @@
+//! Setup wizard posted when the bot joins a guild.
+//!
+//! The wizard is posted in the guild's system channel (or with
+//! `settings setup`) and lets members who can manage the guild choose the
+//! guild's time zone, the channel and smoking types of the panel (or a type
+//! preset, which also becomes the panel's types), and whether members must
+//! acknowledge the age gate. Every choice is stored in
+//! `guild_settings` right away, so the wizard can be picked up again later;
+//! finishing it installs the panel in the chosen channel.
+
+use chrono_tz::Tz;
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+use tracing::info;
+
+use crate::acknowledgment::DEFAULT_MESSAGE;
+use crate::commands::panel::install_panel;
+use crate::database::Database;
+use crate::embed::Title;
+use crate::format::branding;
+use crate::frontend::{Frontend, InteractionFrontend, Reply};
+use crate::presets::{find_preset, PRESETS};
+use crate::service::StatsService;
+use crate::{Data, Error};
+
+/// Prefix of the custom IDs of wizard controls
+const ONBOARDING_PREFIX: &str = "onboarding:";
+
+/// Time zones offered by the wizard; others can be set with `settings timezone`
+pub const TIMEZONE_CHOICES: [Tz; 8] = [
+    Tz::Asia__Tokyo,
+    Tz::Asia__Seoul,
+    Tz::Asia__Shanghai,
+    Tz::Europe__London,
+    Tz::Europe__Berlin,
+    Tz::America__New_York,
+    Tz::America__Los_Angeles,
+    Tz::UTC,
+];
+
+/// Most smoking types offered in the type menu (Discord's limit of options)
+const MAX_TYPE_CHOICES: usize = 25;
+
+/// A step of the wizard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnboardingStep {
+    /// Choosing the guild's time zone
+    Timezone,
+    /// Choosing the channel of the panel
+    PanelChannel,
+    /// Choosing the smoking types of the panel
+    PanelTypes,
+    /// Applying a type preset and showing its types on the panel
+    Preset,
+    /// Turning the age gate on or off
+    AgeGate,
+    /// Finishing the wizard
+    Finish,
+}
+
+impl OnboardingStep {
+    /// Every step, in the order of the wizard
+    const ALL: [Self; 6] = [
+        Self::Timezone,
+        Self::PanelChannel,
+        Self::PanelTypes,
+        Self::Preset,
+        Self::AgeGate,
+        Self::Finish,
+    ];
+
+    /// Returns the name of the step in custom IDs.
+    fn name(self) -> &'static str {
+        match self {
+            Self::Timezone => "timezone",
+            Self::PanelChannel => "channel",
+            Self::PanelTypes => "types",
+            Self::Preset => "preset",
+            Self::AgeGate => "age_gate",
+            Self::Finish => "finish",
+        }
+    }
+}
+
+/// A control of the wizard: which step it belongs to, and for which guild
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnboardingId {
+    /// The ID of the guild being set up
+    pub guild_id: String,
+    /// The step the control belongs to
+    pub step: OnboardingStep,
+}
+
+impl OnboardingId {
+    /// Encodes the custom ID of the control.
+    pub fn custom_id(&self) -> String {
+        format!(
+            "{}{}:{}",
+            ONBOARDING_PREFIX,
+            self.guild_id,
+            self.step.name()
+        )
+    }
+
+    /// Decodes the custom ID of a wizard control.
+    ///
+    /// # Arguments
+    /// * `custom_id` - The custom ID of a used component.
+    ///
+    /// # Returns
+    /// The control, or `None` if the component is not a wizard control.
+    pub fn parse(custom_id: &str) -> Option<Self> {
+        let (guild_id, step) = custom_id.strip_prefix(ONBOARDING_PREFIX)?.split_once(':')?;
+        if guild_id.is_empty() || !guild_id.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let step = OnboardingStep::ALL
+            .into_iter()
+            .find(|candidate| candidate.name() == step)?;
+
+        Some(Self {
+            guild_id: guild_id.to_string(),
+            step,
+        })
+    }
+}
+
+/// The result of using a wizard control
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OnboardingOutcome {
+    /// The member may not manage the guild; nothing changed
+    Forbidden,
+    /// A choice was stored; the wizard shows the new state
+    Updated(Reply),
+    /// The wizard was finished
+    Finished {
+        /// The final message replacing the wizard
+        reply: Reply,
+        /// The channel to install the panel in, if this use finished the wizard and one was chosen
+        panel_channel_id: Option<String>,
+    },
+}
+
+/// Posts the wizard in the system channel of a guild the bot just joined.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `guild` - The guild.
+/// * `is_new` - Whether the bot just joined the guild, as reported by Discord.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub(crate) async fn handle_guild_create(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild: &serenity::Guild,
+    is_new: Option<bool>,
+) -> Result<(), Error> {
+    if is_new != Some(true) {
+        return Ok(());
+    }
+    let Some(channel_id) = guild.system_channel_id else {
+        info!(
+            "Joined guild {} without a system channel; setup is left to settings setup",
+            guild.id
+        );
+        return Ok(());
+    };
+
+    let guild_id = guild.id.to_string();
+    if data
+        .database
+        .lock()
+        .await
+        .guild(&guild_id)
+        .get_settings()
+        .await?
+        .onboarded_at
+        .is_some()
+    {
+        return Ok(());
+    }
+
+    let reply = render_wizard(&data.database, &guild_id, data.clock.timezone()).await?;
+    channel_id
+        .send_message(
+            ctx,
+            serenity::CreateMessage::new()
+                .content(&reply.content)
+                .components(reply.components()),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Renders the wizard with the guild's current choices.
+///
+/// # Arguments
+/// * `database` - The database.
+/// * `guild_id` - The ID of the guild.
+/// * `default` - The bot's time zone, used while the guild chose none.
+///
+/// # Returns
+/// A Result containing the wizard or an `Error`.
+pub async fn render_wizard(
+    database: &Mutex<Database>,
+    guild_id: &str,
+    default: Tz,
+) -> Result<Reply, Error> {
+    let (mut lines, age_gate, smoking_types) = {
+        let db = database.lock().await;
+        let guild = db.guild(guild_id);
+        (
+            describe_choices(&db, guild_id, default).await?,
+            guild.get_acknowledgment_policy().await?.is_some(),
+            db.get_smoking_types().await?,
+        )
+    };
+    lines.insert(
+        0,
+        format!(
+            "{}を追加していただきありがとうございます。サーバーの管理権限があるメンバーは、下のメニューで初期設定ができます。",
+            branding().name
+        ),
+    );
+
+    let id = |step| {
+        OnboardingId {
+            guild_id: guild_id.to_string(),
+            step,
+        }
+        .custom_id()
+    };
+    let timezones = TIMEZONE_CHOICES
+        .iter()
+        .map(|timezone| (timezone.name().to_string(), timezone.name().to_string()))
+        .collect();
+    let types = smoking_types
+        .into_iter()
+        .take(MAX_TYPE_CHOICES)
+        .map(|smoking_type| {
+            (
+                smoking_type.id.to_string(),
+                smoking_type.description.unwrap_or(smoking_type.type_name),
+            )
+        })
+        .collect();
+    let presets = PRESETS
+        .iter()
+        .map(|preset| (preset.name.to_string(), preset.label.to_string()))
+        .collect();
+
+    Ok(Reply::new(lines.join("\n"))
+        .titled(Title::Settings)
+        .button(
+            id(OnboardingStep::AgeGate),
+            if age_gate {
+                "年齢確認をオフにする"
+            } else {
+                "年齢確認をオンにする"
+            },
+        )
+        .button(id(OnboardingStep::Finish), "設定を完了")
+        .select(
+            id(OnboardingStep::Timezone),
+            "タイムゾーンを選ぶ",
+            timezones,
+        )
+        .channel_select(id(OnboardingStep::PanelChannel), "パネルのチャンネルを選ぶ")
+        .multi_select(id(OnboardingStep::PanelTypes), "パネルの種類を選ぶ", types)
+        .select(
+            id(OnboardingStep::Preset),
+            "種類のプリセットを使う",
+            presets,
+        ))
+}
+
+/// Describes the guild's current choices, one line each.
+///
+/// # Arguments
+/// * `db` - The database.
+/// * `guild_id` - The ID of the guild.
+/// * `default` - The bot's time zone, used while the guild chose none.
+///
+/// # Returns
+/// A Result containing the lines or an `Error`.
+async fn describe_choices(
+    db: &Database,
+    guild_id: &str,
+    default: Tz,
+) -> Result<Vec<String>, Error> {
+    let guild = db.guild(guild_id);
+    let settings = guild.get_settings().await?;
+    let timezone = guild.get_timezone().await?;
+
+    let types = match &settings.panel_smoking_type_ids {
+        Some(ids) => {
+            let mut names = Vec::with_capacity(ids.len());
+            for id in ids {
+                let smoking_type = db.get_smoking_type(*id).await?;
+                names.push(smoking_type.description.unwrap_or(smoking_type.type_name));
+            }
+            names.join(", ")
+        }
+        None => "すべて".to_string(),
+    };
+
+    Ok(vec![
+        match timezone {
+            Some(timezone) => format!("タイムゾーン: {}", timezone),
+            None => format!("タイムゾーン: {}（ボットの設定）", default),
+        },
+        format!(
+            "パネルのチャンネル: {}",
+            settings
+                .panel_channel_id
+                .map_or("未選択".to_string(), |channel_id| format!(
+                    "<#{}>",
+                    channel_id
+                ))
+        ),
+        format!("パネルの種類: {}", types),
+        format!(
+            "年齢確認: {}",
+            if guild.get_acknowledgment_policy().await?.is_some() {
+                "オン"
+            } else {
+                "オフ"
+            }
+        ),
+    ])
+}
+
+/// Handles a use of a wizard control and, when it finishes the wizard, installs the panel.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `mci` - The component interaction.
+/// * `control` - The control encoded in the component.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub(crate) async fn handle_onboarding(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &serenity::ComponentInteraction,
+    control: &OnboardingId,
+) -> Result<(), Error> {
+    let may_configure = mci.guild_id.map(|guild_id| guild_id.to_string())
+        == Some(control.guild_id.clone())
+        && mci
+            .member
+            .as_ref()
+            .and_then(|member| member.permissions)
+            .is_some_and(|permissions| permissions.manage_guild());
+    let values: Vec<String> = match &mci.data.kind {
+        serenity::ComponentInteractionDataKind::StringSelect { values } => values.clone(),
+        serenity::ComponentInteractionDataKind::ChannelSelect { values } => values
+            .iter()
+            .map(|channel_id| channel_id.to_string())
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let outcome = apply_onboarding(
+        &data.database,
+        &data.stats,
+        control,
+        &values,
+        may_configure,
+        data.clock.timezone(),
+    )
+    .await?;
+
+    match outcome {
+        OnboardingOutcome::Forbidden => {
+            InteractionFrontend::new(ctx, mci)
+                .respond(
+                    Reply::error("初期設定はサーバーの管理権限があるメンバーだけが行えます。")
+                        .ephemeral(),
+                )
+                .await
+        }
+        OnboardingOutcome::Updated(reply) => InteractionFrontend::new(ctx, mci).update(reply).await,
+        OnboardingOutcome::Finished {
+            reply,
+            panel_channel_id,
+        } => {
+            InteractionFrontend::new(ctx, mci).update(reply).await?;
+            match panel_channel_id {
+                Some(channel_id) => post_panel(ctx, data, mci, control, &channel_id).await,
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+/// Stores the choice made with a wizard control, if the member may configure the guild.
+///
+/// # Arguments
+/// * `database` - The database.
+/// * `stats` - The statistics service whose cached guild aggregates are dropped.
+/// * `control` - The control encoded in the component.
+/// * `values` - The chosen options of a select menu, empty for buttons.
+/// * `may_configure` - Whether the member can manage the guild.
+/// * `default` - The bot's time zone.
+///
+/// # Returns
+/// A Result containing what happened, or an `Error`.
+pub async fn apply_onboarding(
+    database: &Mutex<Database>,
+    stats: &StatsService,
+    control: &OnboardingId,
+    values: &[String],
+    may_configure: bool,
+    default: Tz,
+) -> Result<OnboardingOutcome, Error> {
+    if !may_configure {
+        return Ok(OnboardingOutcome::Forbidden);
+    }
+
+    let guild_id = control.guild_id.as_str();
+    {
+        let db = database.lock().await;
+        let guild = db.guild(guild_id);
+        // Values that were never offered come from forged or outdated menus and are ignored.
+        match control.step {
+            OnboardingStep::Timezone => {
+                let timezone = values.first().and_then(|name| {
+                    TIMEZONE_CHOICES
+                        .into_iter()
+                        .find(|timezone| timezone.name() == name)
+                });
+                if let Some(timezone) = timezone {
+                    guild.set_timezone(Some(timezone)).await?;
+                    stats.forget_guild(guild_id);
+                }
+            }
+            OnboardingStep::PanelChannel => {
+                if let Some(channel_id) = values
+                    .first()
+                    .filter(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()))
+                {
+                    guild.set_panel_channel(channel_id).await?;
+                }
+            }
+            OnboardingStep::PanelTypes => {
+                let known: Vec<i32> = db
+                    .get_smoking_types()
+                    .await?
+                    .into_iter()
+                    .map(|smoking_type| smoking_type.id)
+                    .collect();
+                let ids: Vec<i32> = values
+                    .iter()
+                    .filter_map(|value| value.parse().ok())
+                    .filter(|id| known.contains(id))
+                    .collect();
+                guild
+                    .set_panel_types((!ids.is_empty()).then_some(ids.as_slice()))
+                    .await?;
+            }
+            OnboardingStep::Preset => {
+                if let Some(preset) = values.first().and_then(|name| find_preset(name)) {
+                    let applied = db.ensure_smoking_types(&preset.details()).await?;
+                    guild.set_panel_types(Some(&applied.type_ids)).await?;
+                }
+            }
+            OnboardingStep::AgeGate => {
+                if !guild.remove_acknowledgment_policy().await? {
+                    guild.set_acknowledgment_policy(DEFAULT_MESSAGE).await?;
+                }
+            }
+            OnboardingStep::Finish => {
+                let finished = guild.finish_onboarding().await?;
+                let mut lines = vec!["初期設定が完了しました。".to_string()];
+                lines.extend(describe_choices(&db, guild_id, default).await?);
+                let panel_channel_id = guild
+                    .get_settings()
+                    .await?
+                    .panel_channel_id
+                    .filter(|_| finished);
+                lines.push(match &panel_channel_id {
+                    Some(channel_id) => format!("<#{}> にパネルを設置します。", channel_id),
+                    None => "パネルは panel install で設置できます。".to_string(),
+                });
+
+                return Ok(OnboardingOutcome::Finished {
+                    reply: Reply::new(lines.join("\n")).titled(Title::Settings),
+                    panel_channel_id,
+                });
+            }
+        }
+    }
+
+    render_wizard(database, guild_id, default)
+        .await
+        .map(OnboardingOutcome::Updated)
+}
+
+/// Installs the panel in the channel chosen in the wizard.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `mci` - The interaction that finished the wizard; problems with the panel are reported to it.
+/// * `control` - The finishing control.
+/// * `channel_id` - The ID of the channel.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+async fn post_panel(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &serenity::ComponentInteraction,
+    control: &OnboardingId,
+    channel_id: &str,
+) -> Result<(), Error> {
+    let uuid = mci.id.to_string();
+    let Some(buttons) = install_panel(
+        &InteractionFrontend::new(ctx, mci),
+        &data.database,
+        &uuid,
+        Some(&control.guild_id),
+        channel_id,
+        None,
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+
+    let message = serenity::ChannelId::new(channel_id.parse()?)
+        .send_message(
+            ctx,
+            serenity::CreateMessage::new()
+                .content(&branding().name)
+                .components(vec![serenity::CreateActionRow::Buttons(buttons)]),
+        )
+        .await?;
+    data.database
+        .lock()
+        .await
+        .set_panel_message(&uuid, &message.id.to_string())
+        .await?;
+
+    Ok(())
+}
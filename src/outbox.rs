@@ -0,0 +1,194 @@
+//! Reliable delivery of notifications to external services.
+//!
+//! A delivery that fails after the log was committed would otherwise be lost,
+//! so notifications are written to the `outbox` table in the same transaction
+//! as the change they describe. A background worker delivers due messages to
+//! the configured sink, retries failures with exponential backoff and
+//! dead-letters a message once it has failed `OUTBOX_MAX_ATTEMPTS` times.
+//! Sinks may see a message more than once (e.g. when the bot stops between
+//! delivering and recording it) and should deduplicate on the message ID.
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use poise::serenity_prelude::futures::lock::Mutex;
+use tracing::{error, warn};
+
+use crate::clock::Clock;
+use crate::database::{Database, OutboxMessage};
+use crate::leader::LeaderElection;
+
+/// Interval between polls for due messages
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Maximum number of messages delivered per poll
+const BATCH_SIZE: i64 = 50;
+
+/// Delay before the first retry; doubled on every further failure
+const BASE_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Upper bound of the delay between retries
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// Error returned when a sink fails to deliver a message
+#[derive(Debug, thiserror::Error)]
+pub enum DeliveryError {
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Sink responded with status {0}")]
+    Status(reqwest::StatusCode),
+}
+
+/// An external service outbox messages are delivered to
+#[async_trait]
+pub trait OutboxSink: Send + Sync {
+    /// Delivers a message.
+    ///
+    /// # Arguments
+    /// * `message` - The message.
+    ///
+    /// # Returns
+    /// A Result indicating success or a `DeliveryError`.
+    async fn deliver(&self, message: &OutboxMessage) -> Result<(), DeliveryError>;
+}
+
+/// Sink posting each message as JSON to a webhook URL
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookSink {
+    /// Creates a webhook sink.
+    ///
+    /// # Arguments
+    /// * `url` - The URL messages are posted to.
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+}
+
+#[async_trait]
+impl OutboxSink for WebhookSink {
+    async fn deliver(&self, message: &OutboxMessage) -> Result<(), DeliveryError> {
+        let response = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Outbox-Id", message.id)
+            .header("X-Outbox-Topic", &message.topic)
+            .body(message.payload.clone())
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(DeliveryError::Status(response.status()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Computes the delay before retrying a message.
+///
+/// # Arguments
+/// * `attempts` - The number of failed attempts so far, including the latest.
+///
+/// # Returns
+/// The delay, doubling from `BASE_BACKOFF` up to `MAX_BACKOFF`.
+pub fn backoff(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+
+    BASE_BACKOFF.saturating_mul(1 << exponent).min(MAX_BACKOFF)
+}
+
+/// Delivers every due outbox message once.
+///
+/// # Arguments
+/// * `database` - Database connection shared with the bot.
+/// * `clock` - The clock deciding which messages are due.
+/// * `sink` - The sink to deliver to.
+/// * `max_attempts` - The number of failed attempts after which a message is dead-lettered.
+///
+/// # Returns
+/// A Result containing the number of delivered messages or an `Error`.
+pub async fn deliver_due(
+    database: &Mutex<Database>,
+    clock: &dyn Clock,
+    sink: &dyn OutboxSink,
+    max_attempts: i32,
+) -> Result<usize, sqlx::Error> {
+    let messages = database
+        .lock()
+        .await
+        .get_due_outbox_messages(clock.now(), BATCH_SIZE)
+        .await?;
+
+    let mut delivered = 0;
+    for message in messages {
+        match sink.deliver(&message).await {
+            Ok(()) => {
+                database
+                    .lock()
+                    .await
+                    .mark_outbox_delivered(message.id, clock.now())
+                    .await?;
+                delivered += 1;
+            }
+            Err(e) => {
+                let attempts = message.attempts + 1;
+                let retry_at = (attempts < max_attempts).then(|| {
+                    clock.now() + chrono::Duration::from_std(backoff(attempts)).unwrap_or_default()
+                });
+                if retry_at.is_none() {
+                    warn!(
+                        "Dead-lettering outbox message {} after {} attempts: {}",
+                        message.id, attempts, e
+                    );
+                }
+
+                database
+                    .lock()
+                    .await
+                    .record_outbox_failure(message.id, &e.to_string(), clock.now(), retry_at)
+                    .await?;
+            }
+        }
+    }
+
+    Ok(delivered)
+}
+
+/// Spawns the background task delivering outbox messages.
+///
+/// # Arguments
+/// * `database` - Database connection shared with the bot.
+/// * `clock` - The clock deciding which messages are due.
+/// * `sink` - The sink to deliver to.
+/// * `max_attempts` - The number of failed attempts after which a message is dead-lettered.
+/// * `leader` - The election deciding whether this replica delivers.
+pub fn spawn_delivery_task(
+    database: Arc<Mutex<Database>>,
+    clock: Arc<dyn Clock>,
+    sink: Arc<dyn OutboxSink>,
+    max_attempts: i32,
+    leader: Arc<LeaderElection>,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            if !leader.is_leader().await {
+                continue;
+            }
+            if let Err(e) =
+                deliver_due(&database, clock.as_ref(), sink.as_ref(), max_attempts).await
+            {
+                error!("Failed to deliver outbox messages: {}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,226 @@
+//! Disabling panels while the database is unreachable.
+//!
+//! Every `CHECK_INTERVAL` the database is probed (see `heartbeat::probe`).
+//! While it answers, the posted panels and smoking types are remembered.
+//! After `FAILURES_BEFORE_DISABLE` failed checks in a row the remembered
+//! panels are edited to show `UNAVAILABLE_CONTENT` with disabled buttons, so
+//! members see at once that logging is down instead of every tap timing out.
+//! The first successful check afterwards restores them from the database.
+
+use std::{mem, sync::Arc, time::Duration};
+
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+use tracing::{info, warn};
+
+use crate::clock::Clock;
+use crate::commands::panel::cigarette_buttons;
+use crate::database::{Database, Panel, SmokingType};
+use crate::format::branding;
+use crate::heartbeat;
+use crate::Error;
+
+/// Interval between database checks
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How long a check may take before the database counts as unreachable
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Failed checks in a row after which panels are disabled
+pub const FAILURES_BEFORE_DISABLE: u32 = 2;
+
+/// Text shown on disabled panels
+pub const UNAVAILABLE_CONTENT: &str = "⚠️ 一時的に記録できません";
+
+/// What to do with the panels after a check
+#[derive(Debug, Clone, PartialEq)]
+pub enum PanelAction {
+    /// Nothing changes
+    None,
+    /// Show these panels as disabled
+    Disable(Vec<Panel>),
+    /// Show these panels as usable again
+    Restore(Vec<Panel>),
+}
+
+/// Tracks the database's health and the panels that were disabled
+#[derive(Debug, Default)]
+pub struct PanelHealth {
+    failures: u32,
+    panels: Vec<Panel>,
+    smoking_types: Vec<SmokingType>,
+    disabled: Vec<Panel>,
+}
+
+impl PanelHealth {
+    /// Records a successful check.
+    ///
+    /// # Arguments
+    /// * `panels` - The posted panels.
+    /// * `smoking_types` - The smoking types that can be logged.
+    ///
+    /// # Returns
+    /// `PanelAction::Restore` with the disabled panels, if any were.
+    pub fn healthy(&mut self, panels: Vec<Panel>, smoking_types: Vec<SmokingType>) -> PanelAction {
+        self.failures = 0;
+        self.panels = panels;
+        self.smoking_types = smoking_types;
+
+        let disabled = mem::take(&mut self.disabled);
+        if disabled.is_empty() {
+            return PanelAction::None;
+        }
+        // Panels removed while the database was down are not restored.
+        PanelAction::Restore(
+            disabled
+                .into_iter()
+                .filter_map(|panel| {
+                    self.panels
+                        .iter()
+                        .find(|posted| posted.panel_id == panel.panel_id)
+                        .cloned()
+                })
+                .collect(),
+        )
+    }
+
+    /// Records a failed check.
+    ///
+    /// # Returns
+    /// `PanelAction::Disable` with the remembered panels once `FAILURES_BEFORE_DISABLE` checks in
+    /// a row have failed.
+    pub fn unhealthy(&mut self) -> PanelAction {
+        self.failures += 1;
+        if self.failures != FAILURES_BEFORE_DISABLE || self.panels.is_empty() {
+            return PanelAction::None;
+        }
+
+        self.disabled = self.panels.clone();
+        PanelAction::Disable(self.disabled.clone())
+    }
+
+    /// Builds a panel's buttons from the remembered smoking types.
+    ///
+    /// # Arguments
+    /// * `panel` - The panel.
+    /// * `enabled` - Whether the buttons can be pressed.
+    ///
+    /// # Returns
+    /// The panel's buttons, or an `Error` if a type cannot be labelled.
+    pub fn buttons(
+        &self,
+        panel: &Panel,
+        enabled: bool,
+    ) -> Result<Vec<serenity::CreateButton>, Error> {
+        Ok(cigarette_buttons(
+            self.smoking_types.clone(),
+            &panel.panel_id,
+            panel.smoking_type_ids.as_deref(),
+        )?
+        .into_iter()
+        .map(|button| button.disabled(!enabled))
+        .collect())
+    }
+}
+
+/// Probes the database and loads the panels and smoking types.
+///
+/// # Arguments
+/// * `database` - The database.
+/// * `clock` - The clock the heartbeat is measured with.
+///
+/// # Returns
+/// A Result containing the posted panels and the smoking types, or an `Error`.
+async fn check(
+    database: &Mutex<Database>,
+    clock: &dyn Clock,
+) -> Result<(Vec<Panel>, Vec<SmokingType>), Error> {
+    heartbeat::probe(database, clock).await?;
+    let db = database.lock().await;
+
+    Ok((db.get_posted_panels().await?, db.get_smoking_types().await?))
+}
+
+/// Edits a panel's message.
+///
+/// # Arguments
+/// * `http` - The Discord HTTP client.
+/// * `panel` - The panel.
+/// * `content` - The new text of the message.
+/// * `buttons` - The new buttons.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+async fn edit_panel(
+    http: &serenity::Http,
+    panel: &Panel,
+    content: &str,
+    buttons: Vec<serenity::CreateButton>,
+) -> Result<(), Error> {
+    let Some(message_id) = &panel.message_id else {
+        return Ok(());
+    };
+
+    serenity::ChannelId::new(panel.channel_id.parse()?)
+        .edit_message(
+            http,
+            serenity::MessageId::new(message_id.parse()?),
+            serenity::EditMessage::new()
+                .content(content)
+                .components(vec![serenity::CreateActionRow::Buttons(buttons)]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Spawns the background task disabling and restoring panels with the database's health.
+///
+/// # Arguments
+/// * `http` - The Discord HTTP client.
+/// * `database` - Database connection shared with the bot.
+/// * `clock` - Source of the current time.
+pub fn spawn(http: Arc<serenity::Http>, database: Arc<Mutex<Database>>, clock: Arc<dyn Clock>) {
+    tokio::spawn(async move {
+        let mut health = PanelHealth::default();
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let action =
+                match tokio::time::timeout(CHECK_TIMEOUT, check(&database, clock.as_ref())).await {
+                    Ok(Ok((panels, smoking_types))) => health.healthy(panels, smoking_types),
+                    Ok(Err(e)) => {
+                        warn!("Database check for panels failed: {}", e);
+                        health.unhealthy()
+                    }
+                    Err(_) => {
+                        warn!("Database check for panels timed out");
+                        health.unhealthy()
+                    }
+                };
+            let (panels, content, enabled) = match action {
+                PanelAction::None => continue,
+                PanelAction::Disable(panels) => {
+                    warn!("Database unreachable, disabling {} panels", panels.len());
+                    (panels, UNAVAILABLE_CONTENT, false)
+                }
+                PanelAction::Restore(panels) => {
+                    info!(
+                        "Database reachable again, restoring {} panels",
+                        panels.len()
+                    );
+                    (panels, branding().name.as_str(), true)
+                }
+            };
+
+            for panel in &panels {
+                let result = match health.buttons(panel, enabled) {
+                    Ok(buttons) => edit_panel(&http, panel, content, buttons).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    warn!("Failed to edit panel {}: {}", panel.panel_id, e);
+                }
+            }
+        }
+    });
+}
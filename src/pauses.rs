@@ -0,0 +1,40 @@
+//! Vacation mode: periods in which a user's tracking is paused.
+//!
+//! Users pause tracking with `pause-tracking` (e.g. for a hospital stay).
+//! While paused they receive no smoke-break prompts, their milestone roles
+//! are left untouched, and paused days neither count towards nor break the
+//! under-goal streak. Paused days are also left out of daily averages.
+
+use chrono::NaiveDate;
+
+use crate::database::TrackingPause;
+
+/// Checks whether a date falls into any of the pauses.
+///
+/// # Arguments
+/// * `pauses` - The user's pauses.
+/// * `date` - The local date.
+///
+/// # Returns
+/// `true` if tracking is paused on the date.
+pub fn is_paused(pauses: &[TrackingPause], date: NaiveDate) -> bool {
+    pauses
+        .iter()
+        .any(|pause| pause.start_date <= date && date <= pause.end_date)
+}
+
+/// Counts the dates in a range on which tracking was not paused.
+///
+/// # Arguments
+/// * `pauses` - The user's pauses.
+/// * `from` - The first date of the range.
+/// * `to` - The last date of the range.
+///
+/// # Returns
+/// The number of tracked dates.
+pub fn tracked_days(pauses: &[TrackingPause], from: NaiveDate, to: NaiveDate) -> i64 {
+    from.iter_days()
+        .take_while(|date| *date <= to)
+        .filter(|date| !is_paused(pauses, *date))
+        .count() as i64
+}
@@ -0,0 +1,297 @@
+//! Comparisons of a user's consumption between two periods.
+//!
+//! `compare-periods` takes each period either as two dates (`2024-04-29
+//! 2024-05-05`) or as one of the named shortcuts in `SHORTCUTS`, and shows
+//! the totals, the deltas per smoking type and the difference in cost of the
+//! types with a known price. Periods are inclusive local date ranges.
+//!
+//! `stats weekly` and `stats monthly` summarize the current week or month so
+//! far against the whole previous one, see `SummarySpan`.
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+use crate::database::UserTypeTotal;
+use crate::rollover::{month_start, shift_months};
+
+/// Longest period that can be compared, in days
+pub const MAX_PERIOD_DAYS: i64 = 366;
+
+/// The names of the period shortcuts
+pub const SHORTCUTS: &[&str] = &[
+    "this-week",
+    "last-week",
+    "this-month",
+    "last-month",
+    "golden-week",
+    "normal-week",
+];
+
+/// An inclusive range of local dates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Period {
+    /// The first date of the period
+    pub from: NaiveDate,
+    /// The last date of the period
+    pub to: NaiveDate,
+}
+
+impl Period {
+    /// Returns the number of days in the period.
+    pub fn days(&self) -> i64 {
+        (self.to - self.from).num_days() + 1
+    }
+}
+
+/// Resolves a period shortcut.
+///
+/// Weeks start on Monday. `golden-week` is the latest Golden Week (April 29
+/// to May 5) that has started by today, and `normal-week` the week before it.
+///
+/// # Arguments
+/// * `name` - The shortcut, compared case-insensitively.
+/// * `today` - The user's current local date.
+///
+/// # Returns
+/// The period, or `None` if no shortcut has the name.
+pub fn resolve_shortcut(name: &str, today: NaiveDate) -> Option<Period> {
+    let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    let first_of_month = today.with_day(1)?;
+    let golden_week = |year: i32| NaiveDate::from_ymd_opt(year, 4, 29);
+    let golden_week_start = match golden_week(today.year())? {
+        start if start <= today => start,
+        _ => golden_week(today.year() - 1)?,
+    };
+
+    let (from, to) = match name.to_ascii_lowercase().as_str() {
+        "this-week" => (monday, today),
+        "last-week" => (monday - Duration::days(7), monday - Duration::days(1)),
+        "this-month" => (first_of_month, today),
+        "last-month" => (
+            first_of_month.checked_sub_months(chrono::Months::new(1))?,
+            first_of_month - Duration::days(1),
+        ),
+        "golden-week" => (golden_week_start, golden_week_start + Duration::days(6)),
+        "normal-week" => (
+            golden_week_start - Duration::days(7),
+            golden_week_start - Duration::days(1),
+        ),
+        _ => return None,
+    };
+
+    Some(Period { from, to })
+}
+
+/// Parses the two periods given to `compare-periods`.
+///
+/// # Arguments
+/// * `args` - The arguments: each period as a shortcut or as two dates.
+/// * `today` - The user's current local date.
+///
+/// # Returns
+/// The two periods, or the message explaining why the arguments are invalid.
+pub fn parse_periods(args: &[&str], today: NaiveDate) -> Result<(Period, Period), String> {
+    let mut periods = Vec::new();
+    let mut rest = args;
+
+    while let Some((first, tail)) = rest.split_first() {
+        if let Some(period) = resolve_shortcut(first, today) {
+            periods.push(period);
+            rest = tail;
+            continue;
+        }
+        let Some((second, tail)) = tail.split_first() else {
+            return Err(format!("「{}」は期間として読めません。", first));
+        };
+        let parse = |arg: &str| {
+            arg.parse::<NaiveDate>()
+                .map_err(|_| format!("「{}」は日付（YYYY-MM-DD）として読めません。", arg))
+        };
+        let period = Period {
+            from: parse(first)?,
+            to: parse(second)?,
+        };
+        if period.from > period.to {
+            return Err("期間の終了日は開始日以降にしてください。".to_string());
+        }
+        if period.days() > MAX_PERIOD_DAYS {
+            return Err(format!("比較できる期間は{}日までです。", MAX_PERIOD_DAYS));
+        }
+        periods.push(period);
+        rest = tail;
+    }
+
+    match periods[..] {
+        [first, second] => Ok((first, second)),
+        _ => Err(format!(
+            "期間を2つ指定してください（開始日と終了日、または {}）。",
+            SHORTCUTS.join(" / ")
+        )),
+    }
+}
+
+/// The totals of one smoking type in both periods
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeDelta {
+    /// The name shown for the type
+    pub name: String,
+    /// The total in the first period
+    pub first: i64,
+    /// The total in the second period
+    pub second: i64,
+}
+
+/// The cost in one currency in both periods
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CostDelta {
+    /// The ISO 4217 code of the currency
+    pub currency: String,
+    /// The cost in the first period, in minor units
+    pub first: i64,
+    /// The cost in the second period, in minor units
+    pub second: i64,
+}
+
+/// A comparison of two periods
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeriodComparison {
+    /// The total in the first period
+    pub first_total: i64,
+    /// The total in the second period
+    pub second_total: i64,
+    /// The types logged in either period, in the order they were first seen
+    pub types: Vec<TypeDelta>,
+    /// The costs per currency of the types with a known price
+    pub costs: Vec<CostDelta>,
+}
+
+/// Compares the totals per smoking type of two periods.
+///
+/// # Arguments
+/// * `first` - The totals of the first period.
+/// * `second` - The totals of the second period.
+///
+/// # Returns
+/// The comparison.
+pub fn compare(first: &[UserTypeTotal], second: &[UserTypeTotal]) -> PeriodComparison {
+    let mut comparison = PeriodComparison {
+        first_total: first.iter().map(|total| total.total_quantity).sum(),
+        second_total: second.iter().map(|total| total.total_quantity).sum(),
+        types: Vec::new(),
+        costs: Vec::new(),
+    };
+
+    for (in_second, total) in first
+        .iter()
+        .map(|total| (false, total))
+        .chain(second.iter().map(|total| (true, total)))
+    {
+        let name = total.description.as_deref().unwrap_or(&total.type_name);
+        let index = match comparison.types.iter().position(|delta| delta.name == name) {
+            Some(index) => index,
+            None => {
+                comparison.types.push(TypeDelta {
+                    name: name.to_string(),
+                    first: 0,
+                    second: 0,
+                });
+                comparison.types.len() - 1
+            }
+        };
+        let delta = &mut comparison.types[index];
+        if in_second {
+            delta.second += total.total_quantity;
+        } else {
+            delta.first += total.total_quantity;
+        }
+
+        let (Some(price), Some(currency)) = (total.typical_price, total.currency.as_deref()) else {
+            continue;
+        };
+        let cost = total.total_quantity * price as i64;
+        let index = match comparison.costs.iter().position(|c| c.currency == currency) {
+            Some(index) => index,
+            None => {
+                comparison.costs.push(CostDelta {
+                    currency: currency.to_string(),
+                    first: 0,
+                    second: 0,
+                });
+                comparison.costs.len() - 1
+            }
+        };
+        let cost_delta = &mut comparison.costs[index];
+        if in_second {
+            cost_delta.second += cost;
+        } else {
+            cost_delta.first += cost;
+        }
+    }
+
+    comparison
+}
+
+/// The span of a `stats weekly` or `stats monthly` summary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummarySpan {
+    /// A week starting on Monday
+    Week,
+    /// A calendar month
+    Month,
+}
+
+impl SummarySpan {
+    /// Returns the first day of the span a date falls in.
+    ///
+    /// # Arguments
+    /// * `date` - The date.
+    pub fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            Self::Month => month_start(date),
+        }
+    }
+
+    /// Returns the current span up to today and the whole span before it.
+    ///
+    /// # Arguments
+    /// * `today` - The user's current local date.
+    ///
+    /// # Returns
+    /// The current period and the previous period.
+    pub fn periods(self, today: NaiveDate) -> (Period, Period) {
+        let from = self.start(today);
+        let previous_from = match self {
+            Self::Week => from - Duration::days(7),
+            Self::Month => shift_months(from, -1),
+        };
+
+        (
+            Period { from, to: today },
+            Period {
+                from: previous_from,
+                to: from - Duration::days(1),
+            },
+        )
+    }
+
+    /// Returns the names of the current and the previous span (e.g. `今週`, `先週`).
+    pub fn labels(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Week => ("今週", "先週"),
+            Self::Month => ("今月", "先月"),
+        }
+    }
+}
+
+/// A user's current week or month so far against the whole previous one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeriodSummary {
+    /// The span summarized
+    pub span: SummarySpan,
+    /// The current span up to today
+    pub current: Period,
+    /// The whole previous span
+    pub previous: Period,
+    /// The comparison of the previous span (first) with the current one (second)
+    pub comparison: PeriodComparison,
+}
@@ -0,0 +1,238 @@
+//! Curated sets of smoking types a deployment can start from.
+//!
+//! A preset lists types with their descriptions, emoji, units and typical
+//! prices. Applying one adds the types the bot does not know yet and fills in
+//! the details missing from those it already knows, matching them by type
+//! name; existing types and their logs are never replaced. Presets are
+//! applied with `type preset apply` or from the setup wizard, which also puts
+//! the preset's types on the guild's panel. Each preset fits in one row of
+//! panel buttons.
+
+use crate::database::TypeDetails;
+use crate::format::Locale;
+
+/// A smoking type in a preset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PresetType {
+    /// The type name, used to match types the bot already knows
+    pub type_name: &'static str,
+    /// The description shown on panel buttons
+    pub description: &'static str,
+    /// The emoji shown on panel buttons
+    pub emoji: &'static str,
+    /// What one logged unit is
+    pub unit: &'static str,
+    /// Typical price of one unit, in the minor units of `currency`
+    pub typical_price: i32,
+    /// ISO 4217 code of the currency of `typical_price`
+    pub currency: &'static str,
+}
+
+impl PresetType {
+    /// Returns the details stored for the type.
+    pub fn details(&self) -> TypeDetails<'static> {
+        TypeDetails {
+            type_name: self.type_name,
+            description: Some(self.description),
+            emoji: Some(self.emoji),
+            unit: Some(self.unit),
+            typical_price: Some(self.typical_price),
+            currency: Some(self.currency),
+        }
+    }
+}
+
+/// A named set of smoking types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypePreset {
+    /// The name used with `type preset apply`
+    pub name: &'static str,
+    /// The label shown in lists and menus
+    pub label: &'static str,
+    /// The types of the preset, in panel order
+    pub types: &'static [PresetType],
+}
+
+/// Japanese brands, matching the types the bot is installed with
+const JAPANESE: TypePreset = TypePreset {
+    name: "japanese",
+    label: "日本の銘柄",
+    types: &[
+        PresetType {
+            type_name: "traditional",
+            description: "紙タバコ",
+            emoji: "🚬",
+            unit: "本",
+            typical_price: 30,
+            currency: "JPY",
+        },
+        PresetType {
+            type_name: "iqos",
+            description: "IQOS",
+            emoji: "🔥",
+            unit: "本",
+            typical_price: 29,
+            currency: "JPY",
+        },
+        PresetType {
+            type_name: "ploom",
+            description: "プルーム",
+            emoji: "🟤",
+            unit: "本",
+            typical_price: 28,
+            currency: "JPY",
+        },
+        PresetType {
+            type_name: "glo",
+            description: "グロー",
+            emoji: "🟠",
+            unit: "本",
+            typical_price: 25,
+            currency: "JPY",
+        },
+        PresetType {
+            type_name: "other",
+            description: "その他",
+            emoji: "❔",
+            unit: "本",
+            typical_price: 30,
+            currency: "JPY",
+        },
+    ],
+};
+
+/// Generic English types
+const ENGLISH: TypePreset = TypePreset {
+    name: "english",
+    label: "English (generic)",
+    types: &[
+        PresetType {
+            type_name: "cigarette",
+            description: "Cigarette",
+            emoji: "🚬",
+            unit: "cigarette",
+            typical_price: 50,
+            currency: "USD",
+        },
+        PresetType {
+            type_name: "roll_up",
+            description: "Roll-up",
+            emoji: "🌿",
+            unit: "roll-up",
+            typical_price: 25,
+            currency: "USD",
+        },
+        PresetType {
+            type_name: "heated",
+            description: "Heated tobacco",
+            emoji: "🔥",
+            unit: "stick",
+            typical_price: 45,
+            currency: "USD",
+        },
+        PresetType {
+            type_name: "cigar",
+            description: "Cigar",
+            emoji: "🟫",
+            unit: "cigar",
+            typical_price: 800,
+            currency: "USD",
+        },
+        PresetType {
+            type_name: "pipe",
+            description: "Pipe",
+            emoji: "🍂",
+            unit: "bowl",
+            typical_price: 100,
+            currency: "USD",
+        },
+    ],
+};
+
+/// Vaping products
+const VAPE: TypePreset = TypePreset {
+    name: "vape",
+    label: "Vape",
+    types: &[
+        PresetType {
+            type_name: "vape_disposable",
+            description: "Disposable vape",
+            emoji: "💨",
+            unit: "device",
+            typical_price: 1000,
+            currency: "USD",
+        },
+        PresetType {
+            type_name: "vape_pod",
+            description: "Pod vape",
+            emoji: "🫧",
+            unit: "pod",
+            typical_price: 500,
+            currency: "USD",
+        },
+        PresetType {
+            type_name: "vape_liquid",
+            description: "E-liquid",
+            emoji: "💧",
+            unit: "ml",
+            typical_price: 50,
+            currency: "USD",
+        },
+    ],
+};
+
+impl TypePreset {
+    /// Returns the details stored for the preset's types.
+    pub fn details(&self) -> Vec<TypeDetails<'static>> {
+        self.types.iter().map(PresetType::details).collect()
+    }
+}
+
+/// Every preset, in the order they are listed
+pub const PRESETS: &[TypePreset] = &[JAPANESE, ENGLISH, VAPE];
+
+/// Finds a preset by name.
+///
+/// # Arguments
+/// * `name` - The name of the preset, compared case-insensitively.
+///
+/// # Returns
+/// The preset, or `None` if no preset has the name.
+pub fn find_preset(name: &str) -> Option<&'static TypePreset> {
+    PRESETS
+        .iter()
+        .find(|preset| preset.name.eq_ignore_ascii_case(name.trim()))
+}
+
+/// Returns the preset suggested for a locale.
+///
+/// # Arguments
+/// * `locale` - The locale.
+///
+/// # Returns
+/// The Japanese brands for Japanese, the generic English types otherwise.
+pub fn default_preset(locale: Locale) -> &'static TypePreset {
+    match locale {
+        Locale::Japanese => &PRESETS[0],
+        Locale::EnglishUs | Locale::EnglishGb | Locale::German | Locale::French => &PRESETS[1],
+    }
+}
+
+/// Formats a price given in the minor units of its currency.
+///
+/// # Arguments
+/// * `price` - The price, in minor units.
+/// * `currency` - The ISO 4217 code of the currency.
+///
+/// # Returns
+/// The price with its currency code (e.g. `30 JPY`, `0.50 USD`).
+pub fn format_price(price: i64, currency: &str) -> String {
+    // Currencies without minor units
+    if matches!(currency, "JPY" | "KRW") {
+        return format!("{} {}", price, currency);
+    }
+    let sign = if price < 0 { "-" } else { "" };
+    let price = price.unsigned_abs();
+
+    format!("{}{}.{:02} {}", sign, price / 100, price % 100, currency)
+}
@@ -0,0 +1,222 @@
+//! Push notifications through ntfy and Pushover as an alternative to Discord DMs.
+//!
+//! A member who does not read DMs can register an ntfy topic (`push ntfy`) or
+//! a Pushover user key (`push pushover`); personal notifications such as
+//! milestone congratulations are then pushed there instead, see `notify`.
+//! ntfy is enabled by `NTFY_URL` and Pushover by `PUSHOVER_TOKEN`, the
+//! operator's application token.
+
+use std::{str::FromStr, sync::Arc};
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use crate::notify::Notification;
+
+/// Endpoint of the Pushover message API
+const PUSHOVER_API_URL: &str = "https://api.pushover.net/1/messages.json";
+
+/// Maximum length of an ntfy topic
+const MAX_TOPIC_LENGTH: usize = 64;
+
+/// Length of a Pushover user key
+const PUSHOVER_KEY_LENGTH: usize = 30;
+
+/// A service members can receive push notifications through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PushService {
+    Ntfy,
+    Pushover,
+}
+
+impl PushService {
+    /// Returns the name stored in the database.
+    pub fn name(self) -> &'static str {
+        match self {
+            PushService::Ntfy => "ntfy",
+            PushService::Pushover => "pushover",
+        }
+    }
+
+    /// Returns the name shown to members.
+    pub fn display_name(self) -> &'static str {
+        match self {
+            PushService::Ntfy => "ntfy",
+            PushService::Pushover => "Pushover",
+        }
+    }
+
+    /// Checks whether a target is a well-formed ntfy topic or Pushover user key.
+    ///
+    /// # Arguments
+    /// * `target` - The topic or user key.
+    pub fn is_valid_target(self, target: &str) -> bool {
+        let allowed = |b: u8| b.is_ascii_alphanumeric() || b == b'-' || b == b'_';
+        match self {
+            PushService::Ntfy => {
+                (1..=MAX_TOPIC_LENGTH).contains(&target.len()) && target.bytes().all(allowed)
+            }
+            PushService::Pushover => {
+                target.len() == PUSHOVER_KEY_LENGTH
+                    && target.bytes().all(|b| b.is_ascii_alphanumeric())
+            }
+        }
+    }
+}
+
+/// Error returned when a push service name cannot be parsed
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown push service: {0}")]
+pub struct ParsePushServiceError(String);
+
+impl FromStr for PushService {
+    type Err = ParsePushServiceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ntfy" => Ok(PushService::Ntfy),
+            "pushover" => Ok(PushService::Pushover),
+            _ => Err(ParsePushServiceError(s.to_string())),
+        }
+    }
+}
+
+/// Error returned when a push service fails to deliver a notification
+#[derive(Debug, thiserror::Error)]
+pub enum PushError {
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Push service responded with status {0}")]
+    Status(reqwest::StatusCode),
+}
+
+/// A push service notifications are delivered through
+#[async_trait]
+pub trait PushProvider: Send + Sync {
+    /// Pushes a notification.
+    ///
+    /// # Arguments
+    /// * `target` - The ntfy topic or Pushover user key of the recipient.
+    /// * `notification` - The notification.
+    ///
+    /// # Returns
+    /// A Result indicating success or a `PushError`.
+    async fn push(&self, target: &str, notification: &Notification) -> Result<(), PushError>;
+}
+
+/// Provider publishing to topics of an ntfy server
+pub struct NtfyProvider {
+    client: reqwest::Client,
+    server_url: String,
+}
+
+impl NtfyProvider {
+    /// Creates an ntfy provider.
+    ///
+    /// # Arguments
+    /// * `server_url` - The base URL of the ntfy server, e.g. `https://ntfy.sh`.
+    pub fn new(server_url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            server_url,
+        }
+    }
+}
+
+#[async_trait]
+impl PushProvider for NtfyProvider {
+    async fn push(&self, target: &str, notification: &Notification) -> Result<(), PushError> {
+        // Publishing as JSON keeps non-ASCII titles out of HTTP headers.
+        let response = self
+            .client
+            .post(&self.server_url)
+            .json(&json!({
+                "topic": target,
+                "title": notification.title,
+                "message": notification.message,
+            }))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(PushError::Status(response.status()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Provider sending through the Pushover API
+pub struct PushoverProvider {
+    client: reqwest::Client,
+    app_token: String,
+}
+
+impl PushoverProvider {
+    /// Creates a Pushover provider.
+    ///
+    /// # Arguments
+    /// * `app_token` - The API token of the operator's Pushover application.
+    pub fn new(app_token: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            app_token,
+        }
+    }
+}
+
+#[async_trait]
+impl PushProvider for PushoverProvider {
+    async fn push(&self, target: &str, notification: &Notification) -> Result<(), PushError> {
+        let response = self
+            .client
+            .post(PUSHOVER_API_URL)
+            .json(&json!({
+                "token": self.app_token,
+                "user": target,
+                "title": notification.title,
+                "message": notification.message,
+            }))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(PushError::Status(response.status()));
+        }
+
+        Ok(())
+    }
+}
+
+/// The enabled push services
+#[derive(Clone, Default)]
+pub struct PushProviders {
+    ntfy: Option<Arc<dyn PushProvider>>,
+    pushover: Option<Arc<dyn PushProvider>>,
+}
+
+impl PushProviders {
+    /// Enables a service.
+    ///
+    /// # Arguments
+    /// * `service` - The service.
+    /// * `provider` - The provider delivering through it.
+    pub fn with(mut self, service: PushService, provider: Arc<dyn PushProvider>) -> Self {
+        match service {
+            PushService::Ntfy => self.ntfy = Some(provider),
+            PushService::Pushover => self.pushover = Some(provider),
+        }
+        self
+    }
+
+    /// Returns the provider of a service, or `None` if it is not enabled.
+    ///
+    /// # Arguments
+    /// * `service` - The service.
+    pub fn get(&self, service: PushService) -> Option<Arc<dyn PushProvider>> {
+        match service {
+            PushService::Ntfy => self.ntfy.clone(),
+            PushService::Pushover => self.pushover.clone(),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Calendar rules for daily and monthly statistics.
+//!
+//! Logs are stored as UTC instants; the day a log counts towards is the
+//! calendar date of that instant in the bot's time zone (see `Clock`).
+//! This makes the DST edge cases explicit:
+//!
+//! - A day runs from local midnight to the next local midnight, so days are
+//!   23 or 25 hours long around DST transitions. Every instant belongs to
+//!   exactly one day, so no log is lost or counted twice.
+//! - Logs made during a repeated hour (fall back) all count towards that
+//!   day, in the order they happened.
+//! - The skipped hour (spring forward) has no local times; no log can fall
+//!   into it, and the day is simply an hour shorter.
+//! - In zones where midnight itself is skipped, the day starts at the first
+//!   local time that exists.
+//!
+//! Month arithmetic clamps to the end of the month, so one month before
+//! March 31 is February 28 (or 29), and one year before February 29 is
+//! February 28. Monthly comparisons therefore compare equally long spans
+//! where possible without ever skipping or repeating days.
+
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Returns the instant a local calendar day starts.
+///
+/// # Arguments
+/// * `timezone` - The time zone of the calendar.
+/// * `date` - The local date.
+///
+/// # Returns
+/// The first instant of the day.
+pub fn start_of_day(timezone: Tz, date: NaiveDate) -> DateTime<Utc> {
+    let mut time = date.and_time(NaiveTime::MIN);
+
+    // If midnight is skipped by a DST transition, the day starts at the
+    // first local time after the gap. Gaps are at most a few hours long.
+    loop {
+        if let Some(start) = timezone.from_local_datetime(&time).earliest() {
+            return start.with_timezone(&Utc);
+        }
+        time += Duration::minutes(15);
+    }
+}
+
+/// Returns the half-open range of instants belonging to a local calendar day.
+///
+/// # Arguments
+/// * `timezone` - The time zone of the calendar.
+/// * `date` - The local date.
+///
+/// # Returns
+/// The first instant of the day and the first instant of the next day.
+pub fn day_bounds(timezone: Tz, date: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    let end = date
+        .succ_opt()
+        .map(|next| start_of_day(timezone, next))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC);
+
+    (start_of_day(timezone, date), end)
+}
+
+/// Moves a date by a number of months, clamping to the end of the target month.
+///
+/// # Arguments
+/// * `date` - The date to move.
+/// * `months` - The number of months to move; negative values move backwards.
+///
+/// # Returns
+/// The moved date, or `date` itself if the result would be out of range.
+pub fn shift_months(date: NaiveDate, months: i32) -> NaiveDate {
+    let shifted = if months >= 0 {
+        date.checked_add_months(Months::new(months.unsigned_abs()))
+    } else {
+        date.checked_sub_months(Months::new(months.unsigned_abs()))
+    };
+
+    shifted.unwrap_or(date)
+}
+
+/// Returns the same calendar day one year earlier (February 29 maps to February 28).
+///
+/// # Arguments
+/// * `date` - The date.
+pub fn year_ago(date: NaiveDate) -> NaiveDate {
+    shift_months(date, -12)
+}
+
+/// Returns the first day of the month a date falls in.
+///
+/// # Arguments
+/// * `date` - The date.
+pub fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(date)
+}
+
+/// Returns the span of the previous month comparable to the current month to date.
+///
+/// On March 31 this is February 1 through February 28 (or 29); on March 15
+/// it is February 1 through February 15.
+///
+/// # Arguments
+/// * `today` - The current local date.
+///
+/// # Returns
+/// The first and last day (inclusive) of the comparable span.
+pub fn previous_month_to_date(today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let end = shift_months(today, -1);
+
+    (month_start(end), end)
+}
@@ -0,0 +1,331 @@
+//! Optional scripting hooks for self-hosters.
+//!
+//! With the `scripting` feature enabled and `SCRIPTS_DIR` set, every `*.rhai`
+//! file in the directory is compiled at startup. A script subscribes to an
+//! event by defining a function named after the hook, taking the event as an
+//! object map:
+//!
+//! ```text
+//! fn on_log_created(event) {
+//!     if event.today_total >= 10 {
+//!         `今日はもう${event.today_total}本です`
+//!     }
+//! }
+//! ```
+//!
+//! A hook returning a value adds a line to the message sent for the event
+//! (e.g. a custom metric); returning nothing leaves the message unchanged.
+//! Scripts are sandboxed: they cannot touch files, the network or the bot,
+//! and are aborted when they exceed an operation or time limit. A failing
+//! script is logged and skipped.
+
+use std::path::Path;
+
+use chrono::NaiveDate;
+
+/// An event scripts can react to
+#[derive(Debug, Clone)]
+pub enum ScriptEvent {
+    /// A cigarette was recorded (`on_log_created`)
+    LogCreated {
+        user_id: String,
+        username: String,
+        guild_id: Option<String>,
+        smoking_type_id: i32,
+        date: NaiveDate,
+        today_total: i64,
+    },
+    /// A member reached a smoke-free milestone (`on_milestone_reached`)
+    MilestoneReached {
+        user_id: String,
+        guild_id: String,
+        days: i32,
+        role: String,
+    },
+}
+
+impl ScriptEvent {
+    /// Returns the name of the script function handling the event.
+    pub fn hook(&self) -> &'static str {
+        match self {
+            Self::LogCreated { .. } => "on_log_created",
+            Self::MilestoneReached { .. } => "on_milestone_reached",
+        }
+    }
+}
+
+/// Error returned when scripts cannot be loaded
+#[derive(Debug, thiserror::Error)]
+pub enum ScriptError {
+    #[error("SCRIPTS_DIR is set but the bot was built without the `scripting` feature")]
+    Disabled,
+    #[error("Failed to read scripts: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to compile {path}: {message}")]
+    Compile { path: String, message: String },
+}
+
+/// The loaded scripts; empty when scripting is not configured
+#[derive(Default)]
+pub struct ScriptHooks {
+    #[cfg(feature = "scripting")]
+    runtime: Option<engine::Runtime>,
+}
+
+impl ScriptHooks {
+    /// Compiles every `*.rhai` file in a directory.
+    ///
+    /// # Arguments
+    /// * `dir` - The directory containing the scripts.
+    ///
+    /// # Returns
+    /// The hooks, or a `ScriptError` if a script cannot be read or compiled.
+    #[cfg(feature = "scripting")]
+    pub fn load(dir: &Path) -> Result<Self, ScriptError> {
+        Ok(Self {
+            runtime: Some(engine::Runtime::load(dir)?),
+        })
+    }
+
+    /// Compiles every `*.rhai` file in a directory.
+    ///
+    /// # Arguments
+    /// * `dir` - The directory containing the scripts.
+    ///
+    /// # Returns
+    /// Always `ScriptError::Disabled`, since the `scripting` feature is not enabled.
+    #[cfg(not(feature = "scripting"))]
+    pub fn load(_dir: &Path) -> Result<Self, ScriptError> {
+        Err(ScriptError::Disabled)
+    }
+
+    /// Returns the number of loaded scripts.
+    pub fn len(&self) -> usize {
+        #[cfg(feature = "scripting")]
+        if let Some(runtime) = &self.runtime {
+            return runtime.len();
+        }
+
+        0
+    }
+
+    /// Returns whether no scripts are loaded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Runs every script hooking an event.
+    ///
+    /// # Arguments
+    /// * `event` - The event.
+    ///
+    /// # Returns
+    /// The lines returned by the scripts, in file name order.
+    #[cfg_attr(not(feature = "scripting"), allow(unused_variables))]
+    pub fn run(&self, event: &ScriptEvent) -> Vec<String> {
+        #[cfg(feature = "scripting")]
+        if let Some(runtime) = &self.runtime {
+            return runtime.run(event);
+        }
+
+        Vec::new()
+    }
+}
+
+#[cfg(feature = "scripting")]
+mod engine {
+    use std::{
+        cell::Cell,
+        path::Path,
+        time::{Duration, Instant},
+    };
+
+    use rhai::{CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+    use tracing::{info, warn};
+
+    use super::{ScriptError, ScriptEvent};
+
+    /// Maximum number of operations a single hook call may perform
+    const MAX_OPERATIONS: u64 = 100_000;
+
+    /// Maximum wall-clock time a single hook call may take
+    const TIME_LIMIT: Duration = Duration::from_millis(50);
+
+    /// Maximum function call depth
+    const MAX_CALL_LEVELS: usize = 32;
+
+    /// Maximum length of strings, arrays and maps created by scripts
+    const MAX_SIZE: usize = 2000;
+
+    thread_local! {
+        /// Deadline of the hook call running on this thread
+        static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+    }
+
+    /// A compiled script
+    struct Script {
+        name: String,
+        ast: AST,
+    }
+
+    /// The sandboxed engine and the compiled scripts
+    pub(super) struct Runtime {
+        engine: Engine,
+        scripts: Vec<Script>,
+    }
+
+    impl Runtime {
+        /// Compiles every `*.rhai` file in a directory.
+        ///
+        /// # Arguments
+        /// * `dir` - The directory containing the scripts.
+        ///
+        /// # Returns
+        /// The runtime, or a `ScriptError` if a script cannot be read or compiled.
+        pub(super) fn load(dir: &Path) -> Result<Self, ScriptError> {
+            let engine = sandboxed_engine();
+
+            let mut paths = Vec::new();
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path
+                    .extension()
+                    .is_some_and(|extension| extension == "rhai")
+                {
+                    paths.push(path);
+                }
+            }
+            paths.sort();
+
+            let mut scripts = Vec::new();
+            for path in paths {
+                let source = std::fs::read_to_string(&path)?;
+                let ast = engine.compile(&source).map_err(|e| ScriptError::Compile {
+                    path: path.display().to_string(),
+                    message: e.to_string(),
+                })?;
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+
+                scripts.push(Script { name, ast });
+            }
+
+            Ok(Self { engine, scripts })
+        }
+
+        /// Returns the number of loaded scripts.
+        pub(super) fn len(&self) -> usize {
+            self.scripts.len()
+        }
+
+        /// Runs every script hooking an event.
+        ///
+        /// # Arguments
+        /// * `event` - The event.
+        ///
+        /// # Returns
+        /// The lines returned by the scripts.
+        pub(super) fn run(&self, event: &ScriptEvent) -> Vec<String> {
+            let hook = event.hook();
+            let argument = Dynamic::from_map(event_map(event));
+            let mut lines = Vec::new();
+
+            for script in &self.scripts {
+                let hooked = script
+                    .ast
+                    .iter_functions()
+                    .any(|function| function.name == hook && function.params.len() == 1);
+                if !hooked {
+                    continue;
+                }
+
+                DEADLINE.with(|deadline| deadline.set(Some(Instant::now() + TIME_LIMIT)));
+                let result = self.engine.call_fn_with_options::<Dynamic>(
+                    CallFnOptions::new().eval_ast(false),
+                    &mut Scope::new(),
+                    &script.ast,
+                    hook,
+                    (argument.clone(),),
+                );
+                DEADLINE.with(|deadline| deadline.set(None));
+
+                match result {
+                    Ok(value) if value.is_unit() => {}
+                    Ok(value) if value.is_string() => {
+                        lines.push(value.into_string().unwrap_or_default())
+                    }
+                    Ok(value) => lines.push(value.to_string()),
+                    Err(e) => warn!("Script {} failed in {}: {}", script.name, hook, e),
+                }
+            }
+
+            lines
+        }
+    }
+
+    /// Creates an engine with the sandbox limits applied.
+    fn sandboxed_engine() -> Engine {
+        let mut engine = Engine::new();
+
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(MAX_CALL_LEVELS)
+            .set_max_string_size(MAX_SIZE)
+            .set_max_array_size(MAX_SIZE)
+            .set_max_map_size(MAX_SIZE)
+            .disable_symbol("eval")
+            .on_print(|message| info!(target: "script", "{}", message))
+            .on_debug(|message, _, _| info!(target: "script", "{}", message))
+            .on_progress(|_| {
+                let expired = DEADLINE.with(|deadline| {
+                    deadline
+                        .get()
+                        .is_some_and(|deadline| Instant::now() > deadline)
+                });
+                expired.then_some(Dynamic::UNIT)
+            });
+
+        engine
+    }
+
+    /// Converts an event into the object map passed to scripts.
+    fn event_map(event: &ScriptEvent) -> Map {
+        let mut map = Map::new();
+
+        match event {
+            ScriptEvent::LogCreated {
+                user_id,
+                username,
+                guild_id,
+                smoking_type_id,
+                date,
+                today_total,
+            } => {
+                map.insert("user_id".into(), user_id.clone().into());
+                map.insert("username".into(), username.clone().into());
+                map.insert(
+                    "guild_id".into(),
+                    guild_id.clone().map(Dynamic::from).unwrap_or(Dynamic::UNIT),
+                );
+                map.insert("smoking_type_id".into(), i64::from(*smoking_type_id).into());
+                map.insert("date".into(), date.to_string().into());
+                map.insert("today_total".into(), (*today_total).into());
+            }
+            ScriptEvent::MilestoneReached {
+                user_id,
+                guild_id,
+                days,
+                role,
+            } => {
+                map.insert("user_id".into(), user_id.clone().into());
+                map.insert("guild_id".into(), guild_id.clone().into());
+                map.insert("days".into(), i64::from(*days).into());
+                map.insert("role".into(), role.clone().into());
+            }
+        }
+
+        map
+    }
+}
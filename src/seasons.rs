@@ -0,0 +1,322 @@
+//! Leaderboard seasons: monthly or quarterly standings that start fresh.
+//!
+//! Guilds that opt in with `leaderboard start` rank their members by the
+//! units logged in the running season, fewest first; members who logged
+//! nothing in the season share the top spot. A recurring job archives the
+//! standings when a season ends, announces the winners in the chosen channel
+//! and opens the next season. Seasons follow the calendar in the guild's time
+//! zone and are numbered per guild, so `leaderboard season:3` shows the
+//! guild's third season.
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{Datelike, Months, NaiveDate};
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+use tracing::warn;
+
+use crate::clock::Clock;
+use crate::database::{
+    Database, LeaderboardSeason, LeaderboardSettings, MemberTotal, ScheduledJob, Standing,
+};
+use crate::format::{format_count, format_date, Locale};
+use crate::jobs::JobHandler;
+use crate::rollover::start_of_day;
+use crate::Error;
+
+/// Scheduled job kind archiving finished seasons and opening new ones
+pub const SEASON_JOB: &str = "leaderboard_seasons";
+
+/// Interval between checks for a finished season
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Most members shown in the standings
+pub const MAX_SHOWN: usize = 10;
+
+/// Lowest rank announced as a winner
+const WINNING_RANK: i32 = 3;
+
+/// How long a season runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeasonLength {
+    Month,
+    Quarter,
+}
+
+impl SeasonLength {
+    /// Every length, in the order shown to users
+    pub const ALL: [SeasonLength; 2] = [SeasonLength::Month, SeasonLength::Quarter];
+
+    /// Returns the name used in commands and stored with the settings.
+    pub fn name(self) -> &'static str {
+        match self {
+            SeasonLength::Month => "month",
+            SeasonLength::Quarter => "quarter",
+        }
+    }
+
+    /// Returns the length shown to users.
+    pub fn label(self) -> &'static str {
+        match self {
+            SeasonLength::Month => "1か月",
+            SeasonLength::Quarter => "3か月",
+        }
+    }
+
+    /// Looks up a length by name.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the length (e.g. `quarter`).
+    ///
+    /// # Returns
+    /// The length, or `None` for unknown names.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|length| length.name() == name)
+    }
+
+    /// Returns the calendar season containing a date.
+    ///
+    /// # Arguments
+    /// * `date` - A local date.
+    ///
+    /// # Returns
+    /// The first day of the season and the first day after it.
+    pub fn bounds(self, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let months = match self {
+            SeasonLength::Month => 1,
+            SeasonLength::Quarter => 3,
+        };
+        let first_month = date.month0() / months * months + 1;
+        let start = NaiveDate::from_ymd_opt(date.year(), first_month, 1).unwrap_or(date);
+
+        (start, start + Months::new(months))
+    }
+}
+
+/// Ranks members by their totals, fewest first.
+///
+/// Members with equal totals share a rank, and the next rank skips the
+/// shared places (1, 2, 2, 4).
+///
+/// # Arguments
+/// * `totals` - The members' totals over the season.
+///
+/// # Returns
+/// The standings ordered by rank.
+pub fn rank(totals: &[MemberTotal]) -> Vec<Standing> {
+    let mut totals = totals.to_vec();
+    totals.sort_by(|a, b| a.total.cmp(&b.total).then(a.discord_id.cmp(&b.discord_id)));
+
+    let mut standings: Vec<Standing> = Vec::with_capacity(totals.len());
+    for (position, member) in totals.into_iter().enumerate() {
+        let rank = match standings.last() {
+            Some(previous) if previous.total == member.total => previous.rank,
+            _ => i32::try_from(position + 1).unwrap_or(i32::MAX),
+        };
+        standings.push(Standing {
+            discord_id: member.discord_id,
+            rank,
+            total: member.total,
+        });
+    }
+
+    standings
+}
+
+/// Describes a season's number and dates.
+///
+/// # Arguments
+/// * `season` - The season.
+///
+/// # Returns
+/// The heading, e.g. `シーズン #3（2024/06/01〜2024/06/30）`.
+pub fn season_heading(season: &LeaderboardSeason) -> String {
+    let locale = Locale::Japanese;
+    format!(
+        "シーズン #{}（{}〜{}）",
+        season.number,
+        format_date(season.starts_on, locale),
+        format_date(season.ends_on - chrono::Duration::days(1), locale)
+    )
+}
+
+/// Renders the top of the standings, one line per member.
+///
+/// # Arguments
+/// * `standings` - The standings ordered by rank.
+///
+/// # Returns
+/// The lines of the first `MAX_SHOWN` members.
+pub fn standing_lines(standings: &[Standing]) -> Vec<String> {
+    standings
+        .iter()
+        .take(MAX_SHOWN)
+        .map(|standing| {
+            format!(
+                "{}位 <@{}> {}",
+                standing.rank,
+                standing.discord_id,
+                format_count(standing.total, Locale::Japanese)
+            )
+        })
+        .collect()
+}
+
+/// Renders the announcement of a finished season.
+///
+/// # Arguments
+/// * `season` - The finished season.
+/// * `standings` - Its final standings.
+///
+/// # Returns
+/// The announcement mentioning the winners.
+pub fn announcement(season: &LeaderboardSeason, standings: &[Standing]) -> String {
+    let winners: Vec<String> = standings
+        .iter()
+        .filter(|standing| standing.rank <= WINNING_RANK)
+        .map(|standing| {
+            format!(
+                "{}位 <@{}>（{}）",
+                standing.rank,
+                standing.discord_id,
+                format_count(standing.total, Locale::Japanese)
+            )
+        })
+        .collect();
+
+    if winners.is_empty() {
+        return format!(
+            "🏁 {}が終了しました。記録したメンバーはいませんでした。",
+            season_heading(season)
+        );
+    }
+    format!(
+        "🏆 {}が終了しました！ 記録した本数が少なかったメンバーは:\n{}\n新しいシーズンが始まります。",
+        season_heading(season),
+        winners.join("\n")
+    )
+}
+
+/// Job handler archiving finished seasons and opening new ones every `CHECK_INTERVAL`
+pub struct SeasonJob {
+    http: Arc<serenity::Http>,
+    database: Arc<Mutex<Database>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl SeasonJob {
+    /// Creates the handler.
+    ///
+    /// # Arguments
+    /// * `http` - The Discord HTTP client.
+    /// * `database` - Database connection shared with the bot.
+    /// * `clock` - The clock deciding the current season.
+    pub fn new(
+        http: Arc<serenity::Http>,
+        database: Arc<Mutex<Database>>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            http,
+            database,
+            clock,
+        }
+    }
+
+    /// Archives and announces the guild's finished season, then opens the current one.
+    ///
+    /// # Arguments
+    /// * `settings` - The guild's leaderboard settings.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    async fn update_guild(&self, settings: &LeaderboardSettings) -> Result<(), Error> {
+        let guild_id = &settings.guild_id;
+        let length = SeasonLength::from_name(&settings.season_length)
+            .ok_or_else(|| format!("unknown season length: {}", settings.season_length))?;
+        let timezone = {
+            let db = self.database.lock().await;
+            db.guild(guild_id).get_timezone().await?
+        }
+        .unwrap_or(self.clock.timezone());
+        let now = self.clock.now();
+        let today = now.with_timezone(&timezone).date_naive();
+
+        let open = {
+            let db = self.database.lock().await;
+            db.guild(guild_id).get_open_season().await?
+        };
+        let mut earliest_start = None;
+        match open {
+            Some(season) if season.ends_on <= today => {
+                let standings = {
+                    let db = self.database.lock().await;
+                    let guild = db.guild(guild_id);
+                    let standings = rank(
+                        &guild
+                            .get_member_totals(
+                                start_of_day(timezone, season.starts_on),
+                                start_of_day(timezone, season.ends_on),
+                            )
+                            .await?,
+                    );
+                    // Archived first, so a failed announcement is not posted twice.
+                    if !guild.archive_season(season.id, &standings, now).await? {
+                        return Ok(());
+                    }
+                    standings
+                };
+                if let Err(e) = serenity::ChannelId::new(settings.channel_id.parse()?)
+                    .say(&self.http, announcement(&season, &standings))
+                    .await
+                {
+                    warn!(
+                        "Failed to announce season {} of {}: {}",
+                        season.number, guild_id, e
+                    );
+                }
+                earliest_start = Some(season.ends_on);
+            }
+            Some(_) => return Ok(()),
+            None => {}
+        }
+
+        // A season following one of another length starts where that one ended.
+        let (start, end) = length.bounds(today);
+        let start = earliest_start.map_or(start, |earliest| start.max(earliest));
+        self.database
+            .lock()
+            .await
+            .guild(guild_id)
+            .open_season(start, end)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl JobHandler for SeasonJob {
+    async fn run(&self, _job: &ScheduledJob) -> Result<(), Error> {
+        let settings = self
+            .database
+            .lock()
+            .await
+            .get_leaderboard_settings()
+            .await?;
+        for settings in settings {
+            if let Err(e) = self.update_guild(&settings).await {
+                warn!(
+                    "Failed to update the leaderboard season of {}: {}",
+                    settings.guild_id, e
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn repeat_every(&self) -> Option<Duration> {
+        Some(CHECK_INTERVAL)
+    }
+}
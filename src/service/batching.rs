@@ -0,0 +1,108 @@
+//! Collapsing of repeated panel presses into one confirmation.
+//!
+//! Every press is still recorded as its own log; only the confirmation is
+//! batched. The first press of a type sends a confirmation message, and
+//! presses of the same type by the same user that follow within the window
+//! edit that message instead of sending another one.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Utc};
+use poise::serenity_prelude::MessageId;
+
+/// Longest gap, in seconds, between presses whose confirmations are collapsed
+pub const PRESS_BATCH_SECONDS: i64 = 5;
+
+/// Identifies the presses that share a confirmation
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PressKey {
+    /// The Discord ID of the user
+    pub user_id: String,
+    /// The guild the presses were made in, if any
+    pub guild_id: Option<String>,
+    /// The smoking type pressed
+    pub smoking_type_id: i32,
+}
+
+/// A confirmation message and the presses it covers so far
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PressBatch {
+    /// The confirmation message to edit
+    pub message_id: MessageId,
+    /// The number of presses the message confirms
+    pub count: u32,
+    last_press_at: DateTime<Utc>,
+}
+
+/// The open confirmation batches, one per user, guild and smoking type
+pub struct PressBatcher {
+    window: Duration,
+    batches: Mutex<HashMap<PressKey, PressBatch>>,
+}
+
+impl Default for PressBatcher {
+    fn default() -> Self {
+        Self::new(Duration::seconds(PRESS_BATCH_SECONDS))
+    }
+}
+
+impl PressBatcher {
+    /// Creates a batcher without open batches.
+    ///
+    /// # Arguments
+    /// * `window` - The longest gap between presses whose confirmations are collapsed.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            batches: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Adds a press to the open batch of its key, if the previous press was within the window.
+    ///
+    /// # Arguments
+    /// * `key` - The key of the press.
+    /// * `now` - When the press was made.
+    ///
+    /// # Returns
+    /// The batch including the press, or `None` if the press must send a new confirmation.
+    pub fn extend(&self, key: &PressKey, now: DateTime<Utc>) -> Option<PressBatch> {
+        let mut batches = self.batches.lock().unwrap();
+        let batch = batches
+            .get_mut(key)
+            .filter(|batch| now - batch.last_press_at <= self.window)?;
+        batch.count += 1;
+        batch.last_press_at = now;
+
+        Some(*batch)
+    }
+
+    /// Opens a batch for a press whose confirmation was just sent.
+    ///
+    /// # Arguments
+    /// * `key` - The key of the press.
+    /// * `message_id` - The confirmation message.
+    /// * `now` - When the press was made.
+    pub fn open(&self, key: PressKey, message_id: MessageId, now: DateTime<Utc>) {
+        let mut batches = self.batches.lock().unwrap();
+        // Closed batches of other keys are dropped here instead of by a background task.
+        batches.retain(|_, batch| now - batch.last_press_at <= self.window);
+        batches.insert(
+            key,
+            PressBatch {
+                message_id,
+                count: 1,
+                last_press_at: now,
+            },
+        );
+    }
+
+    /// Closes the batch of a key, so the next press sends a new confirmation.
+    ///
+    /// # Arguments
+    /// * `key` - The key of the batch.
+    pub fn close(&self, key: &PressKey) {
+        self.batches.lock().unwrap().remove(key);
+    }
+}
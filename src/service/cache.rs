@@ -0,0 +1,91 @@
+//! Time-limited caches of computed statistics.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+
+/// A computed value and when it was computed
+#[derive(Debug)]
+pub struct Cached<V> {
+    /// The computed value
+    pub value: Arc<V>,
+    /// When the value was computed
+    pub generated_at: DateTime<Utc>,
+}
+
+impl<V> Clone for Cached<V> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            generated_at: self.generated_at,
+        }
+    }
+}
+
+/// Values computed per key, reused until they are older than the time to live
+pub struct StatsCache<K, V> {
+    ttl: Duration,
+    entries: Mutex<HashMap<K, Cached<V>>>,
+}
+
+impl<K: Eq + Hash, V> StatsCache<K, V> {
+    /// Creates an empty cache.
+    ///
+    /// # Arguments
+    /// * `ttl` - How long a computed value is reused.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the value cached for a key, if it is still fresh.
+    ///
+    /// # Arguments
+    /// * `key` - The key.
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    /// The cached value, or `None` if it is missing or expired.
+    pub fn get(&self, key: &K, now: DateTime<Utc>) -> Option<Cached<V>> {
+        let entries = self.entries.lock().unwrap();
+
+        entries
+            .get(key)
+            .filter(|cached| now - cached.generated_at < self.ttl)
+            .cloned()
+    }
+
+    /// Stores a freshly computed value.
+    ///
+    /// # Arguments
+    /// * `key` - The key.
+    /// * `value` - The computed value.
+    /// * `now` - When the value was computed.
+    ///
+    /// # Returns
+    /// The cached value.
+    pub fn insert(&self, key: K, value: V, now: DateTime<Utc>) -> Cached<V> {
+        let cached = Cached {
+            value: Arc::new(value),
+            generated_at: now,
+        };
+        let mut entries = self.entries.lock().unwrap();
+        // Expired entries of other keys are dropped here instead of by a background task.
+        entries.retain(|_, entry| now - entry.generated_at < self.ttl);
+        entries.insert(key, cached.clone());
+
+        cached
+    }
+
+    /// Removes every entry whose key matches a predicate.
+    ///
+    /// # Arguments
+    /// * `matches` - Returns whether an entry must be removed.
+    pub fn invalidate(&self, matches: impl Fn(&K) -> bool) {
+        self.entries.lock().unwrap().retain(|key, _| !matches(key));
+    }
+}
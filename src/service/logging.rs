@@ -0,0 +1,867 @@
+//! Recording smoking events.
+
+use std::sync::Arc;
+
+use chrono::{NaiveDate, NaiveTime};
+use poise::serenity_prelude::{futures::lock::Mutex, ChannelId, MessageId};
+
+use super::batching::{PressBatch, PressBatcher, PressKey};
+use super::ServiceError;
+use crate::clock::Clock;
+use crate::consent::POLICY_VERSION;
+use crate::database::{
+    BufferedLog, CategoryProgress, DailySmokingSummary, Database, Device, SmokingLog,
+};
+use crate::event_bus::{DomainEvent, EventBus};
+use crate::scripting::{ScriptEvent, ScriptHooks};
+use crate::templates::{self, Template, TemplateKey};
+use crate::tutorial::NEW_USER_WINDOW;
+
+/// Largest quantity logged at once without confirmation; larger ones are
+/// more likely typos (`200` for `20`) than real logs
+pub const LARGE_QUANTITY_THRESHOLD: i32 = 20;
+
+/// The outcome of recording a smoking event
+#[derive(Debug)]
+pub struct LoggedSmoking {
+    /// The stored log
+    pub log: SmokingLog,
+    /// The local date the log counts towards
+    pub date: NaiveDate,
+    /// The user's totals for that date, per smoking type
+    pub daily_summary: Vec<DailySmokingSummary>,
+    /// The user's total for that date
+    pub today_total: i64,
+    /// The user's daily goal, if set
+    pub goal: Option<i32>,
+    /// The user's progress in the category of the logged type, if they set a goal for it
+    pub category_progress: Option<CategoryProgress>,
+    /// Lines added by `on_log_created` scripts
+    pub script_lines: Vec<String>,
+    /// Whether the log was created by an earlier request with the same idempotency key
+    pub replayed: bool,
+}
+
+/// The outcome of undoing a user's most recent log
+#[derive(Debug)]
+pub struct UndoneLog {
+    /// The deleted log
+    pub log: SmokingLog,
+    /// The local date the log counted towards
+    pub date: NaiveDate,
+    /// The user's totals for that date without the log, per smoking type
+    pub daily_summary: Vec<DailySmokingSummary>,
+}
+
+/// Records smoking events from panels, devices and shortcut links
+pub struct LoggingService {
+    database: Arc<Mutex<Database>>,
+    clock: Arc<dyn Clock>,
+    scripts: Arc<ScriptHooks>,
+    events: Arc<EventBus>,
+    batches: PressBatcher,
+}
+
+impl LoggingService {
+    /// Creates a logging service.
+    ///
+    /// # Arguments
+    /// * `database` - Database connection shared with the bot.
+    /// * `clock` - The clock determining the current date.
+    /// * `scripts` - The hook scripts run for every recorded event.
+    /// * `events` - The event bus notified of every recorded event.
+    pub fn new(
+        database: Arc<Mutex<Database>>,
+        clock: Arc<dyn Clock>,
+        scripts: Arc<ScriptHooks>,
+        events: Arc<EventBus>,
+    ) -> Self {
+        Self {
+            database,
+            clock,
+            scripts,
+            events,
+            batches: PressBatcher::default(),
+        }
+    }
+
+    /// Checks whether a user accepted the current data policy.
+    ///
+    /// # Arguments
+    /// * `user_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing whether the user may log, or an `Error`.
+    pub async fn has_consented(&self, user_id: &str) -> Result<bool, sqlx::Error> {
+        let db = self.database.lock().await;
+        has_consented(&db, user_id).await
+    }
+
+    /// Records that a user accepted the current data policy, creating the user if needed.
+    ///
+    /// # Arguments
+    /// * `user_id` - The Discord ID of the user.
+    /// * `username` - The current username of the user.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn accept_policy(&self, user_id: &str, username: &str) -> Result<(), sqlx::Error> {
+        let db = self.database.lock().await;
+        db.get_or_create_user(user_id, username).await?;
+
+        db.record_consent(user_id, POLICY_VERSION, self.clock.now())
+            .await
+    }
+
+    /// Checks whether a user is about to log for the first time, having
+    /// started using the bot within `NEW_USER_WINDOW`.
+    ///
+    /// # Arguments
+    /// * `user_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing whether the user should get the tutorial, or an `Error`.
+    pub async fn is_new_user(&self, user_id: &str) -> Result<bool, sqlx::Error> {
+        let db = self.database.lock().await;
+        db.is_new_user(user_id, self.clock.now() - NEW_USER_WINDOW)
+            .await
+    }
+
+    /// Records a smoking event for a Discord user, creating the user if needed.
+    ///
+    /// The user must have accepted the current data policy.
+    ///
+    /// # Arguments
+    /// * `user_id` - The Discord ID of the user.
+    /// * `username` - The current username of the user.
+    /// * `guild_id` - The guild the event was logged in, if any.
+    /// * `smoking_type_id` - The ID of the smoking type.
+    /// * `quantity` - The number of cigarettes; rejected if it would exceed the guild's
+    ///   daily cap of the type, unless the user chose to ignore caps.
+    /// * `confirmed` - Whether the user confirmed a quantity above `LARGE_QUANTITY_THRESHOLD`.
+    /// * `idempotency_key` - The key of the request (e.g. the Discord interaction); a
+    ///   retried request with the same key returns the original log instead of recording another one.
+    ///
+    /// # Returns
+    /// A Result containing the `LoggedSmoking` or a `ServiceError`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn log_for_user(
+        &self,
+        user_id: &str,
+        username: &str,
+        guild_id: Option<&str>,
+        smoking_type_id: i32,
+        quantity: i32,
+        confirmed: bool,
+        idempotency_key: Option<&str>,
+    ) -> Result<LoggedSmoking, ServiceError> {
+        let date = self.clock.today();
+        let (log, replayed, daily_summary, goal, category_progress) = {
+            let db = self.database.lock().await;
+            validate(&db, smoking_type_id, quantity, confirmed).await?;
+            require_consent(&db, user_id).await?;
+            let user = db.get_or_create_user(user_id, username).await?;
+
+            let replay = find_replay(
+                &db,
+                &user.discord_id,
+                idempotency_key,
+                smoking_type_id,
+                quantity,
+            )
+            .await?;
+            let replayed = replay.is_some();
+            let (log, daily_summary) = match replay {
+                Some(log) => {
+                    let daily_summary = db
+                        .get_daily_summary(&user.discord_id, date, self.clock.timezone())
+                        .await?;
+                    (log, daily_summary)
+                }
+                None => {
+                    if let Some(guild_id) = guild_id {
+                        enforce_type_cap(
+                            &db,
+                            &user.discord_id,
+                            guild_id,
+                            smoking_type_id,
+                            quantity,
+                            self.clock.as_ref(),
+                        )
+                        .await?;
+                    }
+                    db.log_smoking_with_summary(
+                        &user.discord_id,
+                        guild_id,
+                        smoking_type_id,
+                        quantity,
+                        idempotency_key,
+                        date,
+                        self.clock.timezone(),
+                    )
+                    .await?
+                }
+            };
+            let goal = db.get_daily_goal(&user.discord_id).await?;
+            let category_progress = db
+                .get_category_progress(
+                    &user.discord_id,
+                    log.smoking_type_id,
+                    date,
+                    self.clock.timezone(),
+                )
+                .await?;
+
+            (log, replayed, daily_summary, goal, category_progress)
+        };
+
+        Ok(self.finish(
+            log,
+            replayed,
+            date,
+            daily_summary,
+            goal,
+            category_progress,
+            username,
+        ))
+    }
+
+    /// Records a smoking event reported by a registered device.
+    ///
+    /// # Arguments
+    /// * `token_hash` - The SHA-256 hex digest of the presented device token.
+    /// * `smoking_type_id` - The ID of the smoking type.
+    /// * `quantity` - The number of cigarettes.
+    /// * `confirmed` - Whether the client confirmed a quantity above `LARGE_QUANTITY_THRESHOLD`.
+    /// * `idempotency_key` - The client-chosen key of the request; a retried request
+    ///   with the same key returns the original log instead of recording another one.
+    ///
+    /// # Returns
+    /// A Result containing the device and the `LoggedSmoking`, `None` if the token is unknown,
+    /// or a `ServiceError`.
+    pub async fn log_for_device(
+        &self,
+        token_hash: &str,
+        smoking_type_id: i32,
+        quantity: i32,
+        confirmed: bool,
+        idempotency_key: Option<&str>,
+    ) -> Result<Option<(Device, LoggedSmoking)>, ServiceError> {
+        let date = self.clock.today();
+        let (device, log, replayed, daily_summary, goal, category_progress) = {
+            let db = self.database.lock().await;
+            let Some(device) = db.authenticate_device(token_hash).await? else {
+                return Ok(None);
+            };
+            validate(&db, smoking_type_id, quantity, confirmed).await?;
+            require_consent(&db, &device.discord_id).await?;
+
+            let replay = find_replay(
+                &db,
+                &device.discord_id,
+                idempotency_key,
+                smoking_type_id,
+                quantity,
+            )
+            .await?;
+            let replayed = replay.is_some();
+            let log = match replay {
+                Some(log) => log,
+                None => {
+                    db.log_device_smoking(&device, smoking_type_id, quantity, idempotency_key)
+                        .await?
+                }
+            };
+            let daily_summary = db
+                .get_daily_summary(&device.discord_id, date, self.clock.timezone())
+                .await?;
+            let goal = db.get_daily_goal(&device.discord_id).await?;
+            let category_progress = db
+                .get_category_progress(
+                    &device.discord_id,
+                    log.smoking_type_id,
+                    date,
+                    self.clock.timezone(),
+                )
+                .await?;
+
+            (
+                device,
+                log,
+                replayed,
+                daily_summary,
+                goal,
+                category_progress,
+            )
+        };
+
+        let username = summary_username(&daily_summary);
+        let logged = self.finish(
+            log,
+            replayed,
+            date,
+            daily_summary,
+            goal,
+            category_progress,
+            &username,
+        );
+
+        Ok(Some((device, logged)))
+    }
+
+    /// Records one unit for the owner of a shortcut link.
+    ///
+    /// # Arguments
+    /// * `token_hash` - The SHA-256 hex digest of the presented link token.
+    /// * `idempotency_key` - The client-chosen key of the request; a retried request
+    ///   with the same key returns the original log instead of recording another one.
+    ///
+    /// # Returns
+    /// A Result containing the `LoggedSmoking`, `None` if the token is unknown, or a `ServiceError`.
+    pub async fn log_for_shortcut(
+        &self,
+        token_hash: &str,
+        idempotency_key: Option<&str>,
+    ) -> Result<Option<LoggedSmoking>, ServiceError> {
+        let date = self.clock.today();
+        let (log, replayed, daily_summary, goal, category_progress) = {
+            let db = self.database.lock().await;
+            let Some(link) = db.use_shortcut_link(token_hash).await? else {
+                return Ok(None);
+            };
+            require_consent(&db, &link.discord_id).await?;
+
+            let replay = find_replay(
+                &db,
+                &link.discord_id,
+                idempotency_key,
+                link.smoking_type_id,
+                1,
+            )
+            .await?;
+            let replayed = replay.is_some();
+            let log = match replay {
+                Some(log) => log,
+                None => db.log_shortcut_smoking(&link, idempotency_key).await?,
+            };
+            let daily_summary = db
+                .get_daily_summary(&link.discord_id, date, self.clock.timezone())
+                .await?;
+            let goal = db.get_daily_goal(&link.discord_id).await?;
+            let category_progress = db
+                .get_category_progress(
+                    &link.discord_id,
+                    link.smoking_type_id,
+                    date,
+                    self.clock.timezone(),
+                )
+                .await?;
+
+            (log, replayed, daily_summary, goal, category_progress)
+        };
+
+        let username = summary_username(&daily_summary);
+
+        Ok(Some(self.finish(
+            log,
+            replayed,
+            date,
+            daily_summary,
+            goal,
+            category_progress,
+            &username,
+        )))
+    }
+
+    /// Writes a log taken while the database was unreachable.
+    ///
+    /// The type and the user's consent are checked now, as they could not be
+    /// when the log was taken; daily caps are not enforced. Statistics are
+    /// refreshed, but hook scripts and goal notifications are not run for a
+    /// press that happened long ago.
+    ///
+    /// # Arguments
+    /// * `log` - The buffered log.
+    ///
+    /// # Returns
+    /// A Result containing whether the log was written (`false` if it already was), or a
+    /// `ServiceError`.
+    pub async fn replay_buffered(&self, log: &BufferedLog) -> Result<bool, ServiceError> {
+        let timezone = self.clock.timezone();
+        let date = log.smoked_at.with_timezone(&timezone).date_naive();
+        let (log, daily_summary) = {
+            let db = self.database.lock().await;
+            // The press was already accepted when it was taken.
+            validate(&db, log.smoking_type_id, log.quantity, true).await?;
+            require_consent(&db, &log.discord_id).await?;
+            let Some(log) = db.insert_replayed_log(log, self.clock.now()).await? else {
+                return Ok(false);
+            };
+            let daily_summary = db
+                .get_daily_summary(&log.discord_id, date, timezone)
+                .await?;
+
+            (log, daily_summary)
+        };
+
+        self.events.publish(DomainEvent::LogCreated {
+            log_id: log.id,
+            discord_id: log.discord_id,
+            guild_id: log.guild_id,
+            smoking_type_id: log.smoking_type_id,
+            quantity: log.quantity,
+            date,
+            today_total: daily_summary
+                .iter()
+                .filter_map(|summary| summary.total_quantity)
+                .sum(),
+        });
+
+        Ok(true)
+    }
+
+    /// Deletes a user's most recent log, e.g. after an accidental double press.
+    ///
+    /// # Arguments
+    /// * `user_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing the `UndoneLog`, `None` if the user has no logs, or an `Error`.
+    pub async fn undo_last_log(&self, user_id: &str) -> Result<Option<UndoneLog>, sqlx::Error> {
+        let (log, date, daily_summary) = {
+            let db = self.database.lock().await;
+            let Some(log) = db.delete_last_log(user_id).await? else {
+                return Ok(None);
+            };
+            let date = self.clock.local_date(log.smoked_at);
+            let daily_summary = db
+                .get_daily_summary(user_id, date, self.clock.timezone())
+                .await?;
+
+            (log, date, daily_summary)
+        };
+
+        self.events.publish(DomainEvent::LogDeleted {
+            log_id: log.id,
+            discord_id: log.discord_id.clone(),
+        });
+
+        Ok(Some(UndoneLog {
+            log,
+            date,
+            daily_summary,
+        }))
+    }
+
+    /// Loads the template used to confirm a recorded event.
+    ///
+    /// # Arguments
+    /// * `guild_id` - The guild the event was logged in, if any.
+    ///
+    /// # Returns
+    /// A Result containing the template or an `Error`.
+    pub async fn confirmation_template(
+        &self,
+        guild_id: Option<&str>,
+    ) -> Result<Template, sqlx::Error> {
+        let db = self.database.lock().await;
+        templates::load_template(&db, guild_id, TemplateKey::Confirmation).await
+    }
+
+    /// Loads the emoji a guild confirms recorded events with instead of a message.
+    ///
+    /// # Arguments
+    /// * `guild_id` - The guild the event was logged in, if any.
+    ///
+    /// # Returns
+    /// A Result containing the emoji, `None` if events are confirmed with a message, or an `Error`.
+    pub async fn confirmation_reaction(
+        &self,
+        guild_id: Option<&str>,
+    ) -> Result<Option<String>, sqlx::Error> {
+        let Some(guild_id) = guild_id else {
+            return Ok(None);
+        };
+
+        let db = self.database.lock().await;
+        db.guild(guild_id).get_confirmation_reaction().await
+    }
+
+    /// Loads the name a smoking type is shown with.
+    ///
+    /// # Arguments
+    /// * `smoking_type_id` - The ID of the smoking type.
+    ///
+    /// # Returns
+    /// A Result containing the description of the type (its type name if it has none), or an `Error`.
+    pub async fn display_name(&self, smoking_type_id: i32) -> Result<String, sqlx::Error> {
+        let smoking_type = self
+            .database
+            .lock()
+            .await
+            .get_smoking_type(smoking_type_id)
+            .await?;
+
+        Ok(smoking_type.description.unwrap_or(smoking_type.type_name))
+    }
+
+    /// Checks whether a user's later presses of a day edit the day's first confirmation.
+    ///
+    /// # Arguments
+    /// * `user_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing whether the user's confirmations are quiet, or an `Error`.
+    pub async fn quiet_confirmations(&self, user_id: &str) -> Result<bool, sqlx::Error> {
+        let db = self.database.lock().await;
+        db.get_quiet_confirmations(user_id).await
+    }
+
+    /// Loads the message confirming a user's presses of a day.
+    ///
+    /// # Arguments
+    /// * `user_id` - The Discord ID of the user.
+    /// * `date` - The local date.
+    ///
+    /// # Returns
+    /// A Result containing the message, `None` if none was sent that day, or an `Error`.
+    pub async fn daily_confirmation(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+    ) -> Result<Option<MessageId>, sqlx::Error> {
+        let db = self.database.lock().await;
+        let message_id = db.get_daily_confirmation(user_id, date).await?;
+
+        Ok(message_id
+            .and_then(|id| id.parse().ok())
+            .map(MessageId::new))
+    }
+
+    /// Remembers the message confirming a user's presses of a day, so later presses edit it.
+    ///
+    /// # Arguments
+    /// * `user_id` - The Discord ID of the user.
+    /// * `date` - The local date.
+    /// * `message_id` - The confirmation message.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn remember_daily_confirmation(
+        &self,
+        user_id: &str,
+        date: NaiveDate,
+        message_id: MessageId,
+    ) -> Result<(), sqlx::Error> {
+        let db = self.database.lock().await;
+        db.set_daily_confirmation(user_id, date, &message_id.to_string())
+            .await
+    }
+
+    /// Remembers a confirmation sent in a guild, so it is deleted once it is
+    /// older than the guild's cleanup age. Does nothing outside guilds or if
+    /// the guild keeps its confirmations.
+    ///
+    /// # Arguments
+    /// * `guild_id` - The guild the event was logged in, if any.
+    /// * `channel_id` - The channel the confirmation was sent in, if known.
+    /// * `message_id` - The confirmation message.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    pub async fn remember_confirmation(
+        &self,
+        guild_id: Option<&str>,
+        channel_id: Option<ChannelId>,
+        message_id: MessageId,
+    ) -> Result<(), sqlx::Error> {
+        let (Some(guild_id), Some(channel_id)) = (guild_id, channel_id) else {
+            return Ok(());
+        };
+
+        let db = self.database.lock().await;
+        db.guild(guild_id)
+            .remember_confirmation(
+                &channel_id.to_string(),
+                &message_id.to_string(),
+                self.clock.now(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns the local time of a recorded event.
+    ///
+    /// # Arguments
+    /// * `log` - The stored log.
+    pub fn local_time(&self, log: &SmokingLog) -> NaiveTime {
+        log.smoked_at.with_timezone(&self.clock.timezone()).time()
+    }
+
+    /// Adds a press to the open confirmation batch of its key, if the previous press was recent.
+    ///
+    /// # Arguments
+    /// * `key` - The key of the press.
+    ///
+    /// # Returns
+    /// The batch including the press, or `None` if the press must send a new confirmation.
+    pub fn extend_press_batch(&self, key: &PressKey) -> Option<PressBatch> {
+        self.batches.extend(key, self.clock.now())
+    }
+
+    /// Opens a confirmation batch for a press whose confirmation was just sent.
+    ///
+    /// # Arguments
+    /// * `key` - The key of the press.
+    /// * `message_id` - The confirmation message.
+    pub fn open_press_batch(&self, key: PressKey, message_id: MessageId) {
+        self.batches.open(key, message_id, self.clock.now());
+    }
+
+    /// Closes the confirmation batch of a key, so the next press sends a new confirmation.
+    ///
+    /// # Arguments
+    /// * `key` - The key of the batch.
+    pub fn close_press_batch(&self, key: &PressKey) {
+        self.batches.close(key);
+    }
+
+    /// Completes a recorded event by totalling the day, publishing its events and
+    /// running the script hooks. Replayed events were completed by the original
+    /// request, so only the day is totalled.
+    ///
+    /// # Arguments
+    /// * `log` - The stored log.
+    /// * `replayed` - Whether the log was created by an earlier request.
+    /// * `date` - The local date the log counts towards.
+    /// * `daily_summary` - The user's summary for that date.
+    /// * `goal` - The user's daily goal, if set.
+    /// * `category_progress` - The user's progress in the category of the logged type, if any.
+    /// * `username` - The username passed to scripts.
+    ///
+    /// # Returns
+    /// The `LoggedSmoking`.
+    #[allow(clippy::too_many_arguments)]
+    fn finish(
+        &self,
+        log: SmokingLog,
+        replayed: bool,
+        date: NaiveDate,
+        daily_summary: Vec<DailySmokingSummary>,
+        goal: Option<i32>,
+        category_progress: Option<CategoryProgress>,
+        username: &str,
+    ) -> LoggedSmoking {
+        let today_total: i64 = daily_summary
+            .iter()
+            .filter_map(|summary| summary.total_quantity)
+            .sum();
+        if replayed {
+            return LoggedSmoking {
+                log,
+                date,
+                daily_summary,
+                today_total,
+                goal,
+                category_progress,
+                script_lines: Vec::new(),
+                replayed,
+            };
+        }
+
+        self.events.publish(DomainEvent::LogCreated {
+            log_id: log.id,
+            discord_id: log.discord_id.clone(),
+            guild_id: log.guild_id.clone(),
+            smoking_type_id: log.smoking_type_id,
+            quantity: log.quantity,
+            date,
+            today_total,
+        });
+        // Only the log crossing the goal counts, not every log after it.
+        if let Some(goal) = goal {
+            let before = today_total - i64::from(log.quantity);
+            if before <= i64::from(goal) && today_total > i64::from(goal) {
+                self.events.publish(DomainEvent::GoalExceeded {
+                    discord_id: log.discord_id.clone(),
+                    guild_id: log.guild_id.clone(),
+                    date,
+                    goal,
+                    today_total,
+                });
+            }
+        }
+
+        let script_lines = self.scripts.run(&ScriptEvent::LogCreated {
+            user_id: log.discord_id.clone(),
+            username: username.to_string(),
+            guild_id: log.guild_id.clone(),
+            smoking_type_id: log.smoking_type_id,
+            date,
+            today_total,
+        });
+
+        LoggedSmoking {
+            log,
+            date,
+            daily_summary,
+            today_total,
+            goal,
+            category_progress,
+            script_lines,
+            replayed,
+        }
+    }
+}
+
+/// Checks a requested log against the business rules.
+///
+/// # Arguments
+/// * `db` - The database.
+/// * `smoking_type_id` - The ID of the smoking type.
+/// * `quantity` - The number of cigarettes.
+/// * `confirmed` - Whether a quantity above `LARGE_QUANTITY_THRESHOLD` was confirmed.
+///
+/// # Returns
+/// A Result indicating whether the request is valid, or a `ServiceError`.
+async fn validate(
+    db: &Database,
+    smoking_type_id: i32,
+    quantity: i32,
+    confirmed: bool,
+) -> Result<(), ServiceError> {
+    if quantity <= 0 {
+        return Err(ServiceError::InvalidQuantity);
+    }
+    if quantity > LARGE_QUANTITY_THRESHOLD && !confirmed {
+        return Err(ServiceError::UnconfirmedQuantity {
+            quantity,
+            threshold: LARGE_QUANTITY_THRESHOLD,
+        });
+    }
+    if !db.smoking_type_exists(smoking_type_id).await? {
+        return Err(ServiceError::UnknownSmokingType(smoking_type_id));
+    }
+
+    Ok(())
+}
+
+/// Checks whether a user accepted the current data policy.
+///
+/// # Arguments
+/// * `db` - The database.
+/// * `discord_id` - The Discord ID of the user.
+///
+/// # Returns
+/// A Result containing whether the accepted version is current, or an `Error`.
+async fn has_consented(db: &Database, discord_id: &str) -> Result<bool, sqlx::Error> {
+    let version = db.get_consent_version(discord_id).await?;
+
+    Ok(version.is_some_and(|version| version >= POLICY_VERSION))
+}
+
+/// Rejects logging for a user who has not accepted the current data policy.
+///
+/// # Arguments
+/// * `db` - The database.
+/// * `discord_id` - The Discord ID of the user.
+///
+/// # Returns
+/// A Result indicating consent, or `ServiceError::ConsentRequired`.
+async fn require_consent(db: &Database, discord_id: &str) -> Result<(), ServiceError> {
+    if !has_consented(db, discord_id).await? {
+        return Err(ServiceError::ConsentRequired);
+    }
+
+    Ok(())
+}
+
+/// Rejects a log that would take a user past a guild's daily cap of the smoking type.
+///
+/// # Arguments
+/// * `db` - The database.
+/// * `discord_id` - The Discord ID of the user.
+/// * `guild_id` - The guild the event is logged in.
+/// * `smoking_type_id` - The ID of the smoking type.
+/// * `quantity` - The number of units being logged.
+/// * `clock` - The clock; the day is counted in the guild's time zone, falling back to the clock's.
+///
+/// # Returns
+/// A Result indicating the log is allowed, or `ServiceError::DailyCapReached`.
+async fn enforce_type_cap(
+    db: &Database,
+    discord_id: &str,
+    guild_id: &str,
+    smoking_type_id: i32,
+    quantity: i32,
+    clock: &dyn Clock,
+) -> Result<(), ServiceError> {
+    let guild = db.guild(guild_id);
+    let Some(cap) = guild.get_type_cap(smoking_type_id).await? else {
+        return Ok(());
+    };
+    if db.get_ignore_type_caps(discord_id).await? {
+        return Ok(());
+    }
+
+    let timezone = guild.get_timezone().await?.unwrap_or(clock.timezone());
+    let date = clock.now().with_timezone(&timezone).date_naive();
+
+    let total = db
+        .get_daily_type_total(discord_id, smoking_type_id, date, timezone)
+        .await?;
+    if total + i64::from(quantity) > i64::from(cap) {
+        let smoking_type = db.get_smoking_type(smoking_type_id).await?;
+        return Err(ServiceError::DailyCapReached {
+            display_name: smoking_type.description.unwrap_or(smoking_type.type_name),
+            cap,
+        });
+    }
+
+    Ok(())
+}
+
+/// Looks up the log created by an earlier request with the same idempotency key.
+///
+/// # Arguments
+/// * `db` - The database.
+/// * `discord_id` - The Discord ID of the user the log is for.
+/// * `idempotency_key` - The key of the request, if any.
+/// * `smoking_type_id` - The requested smoking type.
+/// * `quantity` - The requested number of cigarettes.
+///
+/// # Returns
+/// A Result containing the earlier log, `None` if the key is new or absent, or
+/// `ServiceError::IdempotencyKeyReused` if the earlier request asked for something else.
+async fn find_replay(
+    db: &Database,
+    discord_id: &str,
+    idempotency_key: Option<&str>,
+    smoking_type_id: i32,
+    quantity: i32,
+) -> Result<Option<SmokingLog>, ServiceError> {
+    let Some(idempotency_key) = idempotency_key else {
+        return Ok(None);
+    };
+    let Some(log) = db
+        .find_log_by_idempotency_key(discord_id, idempotency_key)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    if log.smoking_type_id != smoking_type_id || log.quantity != quantity {
+        return Err(ServiceError::IdempotencyKeyReused);
+    }
+
+    Ok(Some(log))
+}
+
+/// Returns the username recorded in a daily summary.
+fn summary_username(daily_summary: &[DailySmokingSummary]) -> String {
+    daily_summary
+        .first()
+        .map(|summary| summary.username.clone())
+        .unwrap_or_default()
+}
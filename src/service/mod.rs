@@ -0,0 +1,36 @@
+//! Domain services shared by every frontend.
+//!
+//! Commands, the HTTP API and background tasks call these services instead
+//! of the `Database` directly, so business rules (validation, which day a
+//! log counts towards, script hooks) are implemented once and behave the
+//! same no matter where a request comes from. Frontends only translate
+//! between their transport and the service types.
+
+mod batching;
+mod cache;
+mod logging;
+mod stats;
+
+pub use batching::{PressBatch, PressBatcher, PressKey, PRESS_BATCH_SECONDS};
+pub use cache::{Cached, StatsCache};
+pub use logging::{LoggedSmoking, LoggingService, UndoneLog, LARGE_QUANTITY_THRESHOLD};
+pub use stats::{StatsService, DEFAULT_CACHE_TTL};
+
+/// Errors returned by the domain services
+#[derive(Debug, thiserror::Error)]
+pub enum ServiceError {
+    #[error("Quantity must be positive")]
+    InvalidQuantity,
+    #[error("Quantity {quantity} is more than {threshold} and must be confirmed")]
+    UnconfirmedQuantity { quantity: i32, threshold: i32 },
+    #[error("Unknown smoking type: {0}")]
+    UnknownSmokingType(i32),
+    #[error("Idempotency key was already used for a different request")]
+    IdempotencyKeyReused,
+    #[error("The user has not accepted the current data policy")]
+    ConsentRequired,
+    #[error("Daily cap of {cap} reached for smoking type {display_name}")]
+    DailyCapReached { display_name: String, cap: i32 },
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
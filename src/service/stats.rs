@@ -0,0 +1,462 @@
+//! Read-side statistics.
+//!
+//! Heavy statistics (monthly charts, guild and group DM aggregates) are
+//! cached per key for `DEFAULT_CACHE_TTL` or the configured time to live, and
+//! returned together with when they were computed so frontends can show how
+//! fresh they are.
+//! New and deleted logs invalidate the affected entries through the event bus.
+
+use std::sync::Arc;
+
+use chrono::{Datelike, NaiveDate};
+use chrono_tz::Tz;
+use poise::serenity_prelude::futures::lock::Mutex;
+
+use super::cache::{Cached, StatsCache};
+use crate::clock::Clock;
+use crate::database::{DailyTotal, Database, EventCorrelation, GuildTypeTotal, UserTypeTotal};
+use crate::event_bus::{DomainEvent, EventBus};
+use crate::linked_roles::{compute_metadata, RoleMetadata};
+use crate::milestones::days_smoke_free;
+use crate::pauses::{is_paused, tracked_days};
+use crate::periods::{compare, Period, PeriodSummary, SummarySpan};
+use crate::rollover::start_of_day;
+
+/// How long heavy statistics are reused when no time to live is configured
+pub const DEFAULT_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Computes statistics about users' consumption
+pub struct StatsService {
+    database: Arc<Mutex<Database>>,
+    clock: Arc<dyn Clock>,
+    monthly: StatsCache<(String, NaiveDate), Vec<DailyTotal>>,
+    guilds: StatsCache<(String, NaiveDate), Vec<GuildTypeTotal>>,
+    channels: StatsCache<(String, NaiveDate), Vec<GuildTypeTotal>>,
+}
+
+impl StatsService {
+    /// Creates a statistics service caching heavy statistics for `DEFAULT_CACHE_TTL`.
+    ///
+    /// # Arguments
+    /// * `database` - Database connection shared with the bot.
+    /// * `clock` - The clock determining the current date.
+    pub fn new(database: Arc<Mutex<Database>>, clock: Arc<dyn Clock>) -> Self {
+        let ttl = chrono::Duration::from_std(DEFAULT_CACHE_TTL).unwrap();
+
+        Self {
+            database,
+            clock,
+            monthly: StatsCache::new(ttl),
+            guilds: StatsCache::new(ttl),
+            channels: StatsCache::new(ttl),
+        }
+    }
+
+    /// Sets how long heavy statistics are reused.
+    ///
+    /// # Arguments
+    /// * `ttl` - The time to live of cached statistics.
+    pub fn with_cache_ttl(mut self, ttl: std::time::Duration) -> Self {
+        let ttl = chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX);
+        self.monthly = StatsCache::new(ttl);
+        self.guilds = StatsCache::new(ttl);
+        self.channels = StatsCache::new(ttl);
+        self
+    }
+
+    /// Returns the current local date.
+    pub fn today(&self) -> NaiveDate {
+        self.clock.today()
+    }
+
+    /// Returns the first day of the current month.
+    pub fn current_month(&self) -> NaiveDate {
+        self.clock.today().with_day(1).unwrap()
+    }
+
+    /// Retrieves the time zone a guild's days are counted in.
+    ///
+    /// # Arguments
+    /// * `guild_id` - The ID of the guild.
+    ///
+    /// # Returns
+    /// A Result containing the guild's time zone, or the bot's if the guild set none, or an `Error`.
+    pub async fn guild_timezone(&self, guild_id: &str) -> Result<Tz, sqlx::Error> {
+        let db = self.database.lock().await;
+        let timezone = db.guild(guild_id).get_timezone().await?;
+
+        Ok(timezone.unwrap_or(self.clock.timezone()))
+    }
+
+    /// Retrieves the first day of the current month in a guild's time zone.
+    ///
+    /// # Arguments
+    /// * `guild_id` - The ID of the guild.
+    ///
+    /// # Returns
+    /// A Result containing the date or an `Error`.
+    pub async fn guild_current_month(&self, guild_id: &str) -> Result<NaiveDate, sqlx::Error> {
+        let timezone = self.guild_timezone(guild_id).await?;
+        let today = self.clock.now().with_timezone(&timezone).date_naive();
+
+        Ok(today.with_day(1).unwrap())
+    }
+
+    /// Retrieves a user's daily totals over a month, from the cache if fresh.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `month` - The first day of the month.
+    /// * `refresh` - Whether to recompute the totals even if cached.
+    ///
+    /// # Returns
+    /// A Result containing the totals of the days with logs ordered by date, or an `Error`.
+    pub async fn monthly_totals(
+        &self,
+        discord_id: &str,
+        month: NaiveDate,
+        refresh: bool,
+    ) -> Result<Cached<Vec<DailyTotal>>, sqlx::Error> {
+        let key = (discord_id.to_string(), month);
+        if !refresh {
+            if let Some(cached) = self.monthly.get(&key, self.clock.now()) {
+                return Ok(cached);
+            }
+        }
+
+        let end = next_month(month);
+        let totals = {
+            let db = self.database.lock().await;
+            db.get_daily_totals(discord_id, month, self.clock.timezone())
+                .await?
+        };
+        let totals = totals
+            .into_iter()
+            .filter(|total| total.smoke_date < end)
+            .collect();
+
+        Ok(self.monthly.insert(key, totals, self.clock.now()))
+    }
+
+    /// Retrieves a guild's totals per smoking type over a month, from the cache if fresh.
+    ///
+    /// The month is counted in the guild's time zone.
+    ///
+    /// # Arguments
+    /// * `guild_id` - The ID of the guild.
+    /// * `month` - The first day of the month.
+    /// * `refresh` - Whether to recompute the totals even if cached.
+    ///
+    /// # Returns
+    /// A Result containing the totals of the logged types, or an `Error`.
+    pub async fn guild_totals(
+        &self,
+        guild_id: &str,
+        month: NaiveDate,
+        refresh: bool,
+    ) -> Result<Cached<Vec<GuildTypeTotal>>, sqlx::Error> {
+        let key = (guild_id.to_string(), month);
+        if !refresh {
+            if let Some(cached) = self.guilds.get(&key, self.clock.now()) {
+                return Ok(cached);
+            }
+        }
+
+        let timezone = self.guild_timezone(guild_id).await?;
+        let totals = {
+            let db = self.database.lock().await;
+            db.guild(guild_id)
+                .get_type_totals(
+                    start_of_day(timezone, month),
+                    start_of_day(timezone, next_month(month)),
+                )
+                .await?
+        };
+
+        Ok(self.guilds.insert(key, totals, self.clock.now()))
+    }
+
+    /// Retrieves the totals per smoking type of the logs recorded in a DM or
+    /// group DM over a month, from the cache if fresh.
+    ///
+    /// The month is counted in the bot's time zone.
+    ///
+    /// # Arguments
+    /// * `channel_id` - The ID of the channel.
+    /// * `month` - The first day of the month.
+    /// * `refresh` - Whether to recompute the totals even if cached.
+    ///
+    /// # Returns
+    /// A Result containing the totals of the logged types, or an `Error`.
+    pub async fn channel_totals(
+        &self,
+        channel_id: &str,
+        month: NaiveDate,
+        refresh: bool,
+    ) -> Result<Cached<Vec<GuildTypeTotal>>, sqlx::Error> {
+        let key = (channel_id.to_string(), month);
+        if !refresh {
+            if let Some(cached) = self.channels.get(&key, self.clock.now()) {
+                return Ok(cached);
+            }
+        }
+
+        let timezone = self.clock.timezone();
+        let totals = {
+            let db = self.database.lock().await;
+            db.get_channel_type_totals(
+                channel_id,
+                start_of_day(timezone, month),
+                start_of_day(timezone, next_month(month)),
+            )
+            .await?
+        };
+
+        Ok(self.channels.insert(key, totals, self.clock.now()))
+    }
+
+    /// Totals a user's logs per smoking type over a period.
+    ///
+    /// The period is counted in the bot's time zone and is not cached.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `period` - The period.
+    ///
+    /// # Returns
+    /// A Result containing the totals of the logged types, or an `Error`.
+    pub async fn period_type_totals(
+        &self,
+        discord_id: &str,
+        period: Period,
+    ) -> Result<Vec<UserTypeTotal>, sqlx::Error> {
+        let timezone = self.clock.timezone();
+        let db = self.database.lock().await;
+
+        db.get_user_type_totals(
+            discord_id,
+            start_of_day(timezone, period.from),
+            start_of_day(timezone, period.to + chrono::Duration::days(1)),
+        )
+        .await
+    }
+
+    /// Summarizes a user's current week or month so far against the previous one.
+    ///
+    /// The spans are counted in the bot's time zone and are not cached.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `span` - Whether weeks or months are summarized.
+    ///
+    /// # Returns
+    /// A Result containing the `PeriodSummary`, or an `Error`.
+    pub async fn period_summary(
+        &self,
+        discord_id: &str,
+        span: SummarySpan,
+    ) -> Result<PeriodSummary, sqlx::Error> {
+        let timezone = self.clock.timezone();
+        let (current, previous) = span.periods(self.today());
+        let db = self.database.lock().await;
+
+        let (current_totals, previous_totals) = match span {
+            SummarySpan::Week => (
+                db.get_weekly_summary(discord_id, current.from, timezone)
+                    .await?,
+                db.get_weekly_summary(discord_id, previous.from, timezone)
+                    .await?,
+            ),
+            SummarySpan::Month => (
+                db.get_monthly_summary(discord_id, current.from, timezone)
+                    .await?,
+                db.get_monthly_summary(discord_id, previous.from, timezone)
+                    .await?,
+            ),
+        };
+
+        Ok(PeriodSummary {
+            span,
+            current,
+            previous,
+            comparison: compare(&previous_totals, &current_totals),
+        })
+    }
+
+    /// Compares a user's daily totals on days with and without each of their
+    /// event tags over the last days.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `days` - The number of days ending today to analyse.
+    ///
+    /// # Returns
+    /// A Result containing the correlation of each tag used in the period, or an `Error`.
+    pub async fn event_correlations(
+        &self,
+        discord_id: &str,
+        days: i64,
+    ) -> Result<Vec<EventCorrelation>, sqlx::Error> {
+        let today = self.clock.today();
+        let since = today - chrono::Duration::days(days.max(1) - 1);
+        let db = self.database.lock().await;
+
+        db.get_event_correlations(discord_id, since, today, self.clock.timezone())
+            .await
+    }
+
+    /// Drops the cached statistics a domain event makes stale.
+    ///
+    /// # Arguments
+    /// * `event` - The published event.
+    pub fn invalidate(&self, event: &DomainEvent) {
+        match event {
+            DomainEvent::LogCreated {
+                discord_id,
+                guild_id,
+                ..
+            } => {
+                self.monthly.invalidate(|(id, _)| id == discord_id);
+                match guild_id {
+                    Some(guild_id) => self.guilds.invalidate(|(id, _)| id == guild_id),
+                    // The channel of a log outside guilds is not part of the event.
+                    None => self.channels.invalidate(|_| true),
+                }
+            }
+            DomainEvent::LogDeleted { discord_id, .. } => {
+                self.monthly.invalidate(|(id, _)| id == discord_id);
+                // The guild of a deleted log is not known, so every aggregate may be stale.
+                self.guilds.invalidate(|_| true);
+                self.channels.invalidate(|_| true);
+            }
+            _ => {}
+        }
+    }
+
+    /// Drops every cached statistic a user's logs contribute to, after their logs were edited directly.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    pub fn forget_user(&self, discord_id: &str) {
+        self.monthly.invalidate(|(id, _)| id == discord_id);
+        // The user may have logged in any guild or channel.
+        self.guilds.invalidate(|_| true);
+        self.channels.invalidate(|_| true);
+    }
+
+    /// Drops a guild's cached aggregates, after its time zone changed.
+    ///
+    /// # Arguments
+    /// * `guild_id` - The ID of the guild.
+    pub fn forget_guild(&self, guild_id: &str) {
+        self.guilds.invalidate(|(id, _)| id == guild_id);
+    }
+
+    /// Drops a DM or group DM channel's cached aggregates, after a log was recorded there.
+    ///
+    /// # Arguments
+    /// * `channel_id` - The ID of the channel.
+    pub fn forget_channel(&self, channel_id: &str) {
+        self.channels.invalidate(|(id, _)| id == channel_id);
+    }
+
+    /// Drops every cached statistic, after smoking types were merged.
+    pub fn forget_all(&self) {
+        self.monthly.invalidate(|_| true);
+        self.guilds.invalidate(|_| true);
+        self.channels.invalidate(|_| true);
+    }
+
+    /// Subscribes cache invalidation to the event bus.
+    ///
+    /// # Arguments
+    /// * `events` - The event bus.
+    pub fn spawn_invalidation_task(self: &Arc<Self>, events: &EventBus) {
+        let stats = self.clone();
+
+        events.spawn_subscriber("stats cache invalidation", move |event| {
+            stats.invalidate(&event);
+            async {}
+        });
+    }
+
+    /// Returns how many full days have passed since the user's last smoking event.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing the number of smoke-free days (0 if the user has never logged) or an `Error`.
+    pub async fn days_smoke_free(&self, discord_id: &str) -> Result<i64, sqlx::Error> {
+        let db = self.database.lock().await;
+        days_smoke_free(&db, self.clock.as_ref(), discord_id).await
+    }
+
+    /// Checks whether the user paused tracking for today.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing whether tracking is paused, or an `Error`.
+    pub async fn is_tracking_paused(&self, discord_id: &str) -> Result<bool, sqlx::Error> {
+        let db = self.database.lock().await;
+        db.is_tracking_paused(discord_id, self.clock.today()).await
+    }
+
+    /// Computes the user's average per day over the last days, leaving out paused days.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    /// * `days` - The number of days ending today to average over.
+    ///
+    /// # Returns
+    /// A Result containing the average, `None` if every day was paused, or an `Error`.
+    pub async fn daily_average(
+        &self,
+        discord_id: &str,
+        days: i64,
+    ) -> Result<Option<f64>, sqlx::Error> {
+        let today = self.clock.today();
+        let since = today - chrono::Duration::days(days.max(1) - 1);
+        let (totals, pauses) = {
+            let db = self.database.lock().await;
+            (
+                db.get_daily_totals(discord_id, since, self.clock.timezone())
+                    .await?,
+                db.get_tracking_pauses(discord_id, since).await?,
+            )
+        };
+
+        let tracked = tracked_days(&pauses, since, today);
+        if tracked == 0 {
+            return Ok(None);
+        }
+        let total: i64 = totals
+            .iter()
+            .filter(|total| total.smoke_date <= today && !is_paused(&pauses, total.smoke_date))
+            .map(|total| total.total_quantity)
+            .sum();
+
+        Ok(Some(total as f64 / tracked as f64))
+    }
+
+    /// Computes the role-connection metadata of a user.
+    ///
+    /// # Arguments
+    /// * `discord_id` - The Discord ID of the user.
+    ///
+    /// # Returns
+    /// A Result containing the `RoleMetadata` or an `Error`.
+    pub async fn role_metadata(&self, discord_id: &str) -> Result<RoleMetadata, sqlx::Error> {
+        let db = self.database.lock().await;
+        compute_metadata(&db, self.clock.as_ref(), discord_id).await
+    }
+}
+
+/// Returns the first day of the month after the given month.
+fn next_month(month: NaiveDate) -> NaiveDate {
+    month
+        .checked_add_months(chrono::Months::new(1))
+        .unwrap_or(NaiveDate::MAX)
+}
@@ -0,0 +1,298 @@
+//! Per-shard health: gateway latency, guild counts and heartbeats.
+//!
+//! A background task looks at every shard of the client each
+//! `CHECK_INTERVAL`. Serenity measures a shard's latency anew at every
+//! acknowledged heartbeat, so a changed latency counts as a heartbeat. A
+//! shard that stays disconnected, or stays connected without a heartbeat,
+//! for `WEDGED_AFTER` is restarted. The last check is exposed on `/metrics`
+//! and listed by `admin shards`; with a single shard it covers that one.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::{self as serenity, ConnectionStage, ShardManager};
+use tracing::warn;
+
+use crate::clock::Clock;
+
+/// Interval between checks of the shards
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long a shard may go without a heartbeat, or without connecting, before it is restarted
+pub const WEDGED_AFTER: chrono::Duration = chrono::Duration::minutes(3);
+
+/// The shards as seen at the last check, ordered by ID
+static STATUSES: Mutex<Vec<ShardStatus>> = Mutex::new(Vec::new());
+
+/// A shard as seen at a check
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShardStatus {
+    pub id: u32,
+    pub stage: ConnectionStage,
+    /// The latency of the last acknowledged heartbeat, if any
+    pub latency: Option<Duration>,
+    /// The number of cached guilds the shard handles
+    pub guilds: usize,
+    /// When a heartbeat was last seen, if one has been yet
+    pub last_heartbeat: Option<DateTime<Utc>>,
+    /// How often the shard was restarted for being wedged
+    pub restarts: u32,
+}
+
+/// What is remembered of a shard between checks
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShardTracker {
+    latency: Option<Duration>,
+    last_heartbeat: Option<DateTime<Utc>>,
+    connected: bool,
+    /// When the shard connected or disconnected, or was last restarted
+    since: DateTime<Utc>,
+    restarts: u32,
+}
+
+impl ShardTracker {
+    /// Starts tracking a shard seen for the first time.
+    ///
+    /// # Arguments
+    /// * `now` - The current time.
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            latency: None,
+            last_heartbeat: None,
+            connected: false,
+            since: now,
+            restarts: 0,
+        }
+    }
+
+    /// Records what a check saw of the shard.
+    ///
+    /// # Arguments
+    /// * `stage` - The shard's connection stage.
+    /// * `latency` - The latency of its last acknowledged heartbeat, if any.
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    /// `true` if the shard is wedged and should be restarted.
+    pub fn observe(
+        &mut self,
+        stage: ConnectionStage,
+        latency: Option<Duration>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        if latency.is_some() && latency != self.latency {
+            self.last_heartbeat = Some(now);
+        }
+        self.latency = latency;
+        let connected = stage == ConnectionStage::Connected;
+        if connected != self.connected {
+            self.connected = connected;
+            self.since = now;
+        }
+
+        let last_progress = match self.last_heartbeat {
+            Some(heartbeat) if self.connected => heartbeat.max(self.since),
+            _ => self.since,
+        };
+        now - last_progress >= WEDGED_AFTER
+    }
+
+    /// Records a restart, giving the shard `WEDGED_AFTER` to reconnect.
+    ///
+    /// # Arguments
+    /// * `now` - The current time.
+    pub fn restarted(&mut self, now: DateTime<Utc>) {
+        self.restarts += 1;
+        self.connected = false;
+        self.since = now;
+    }
+
+    /// Returns the shard's status.
+    ///
+    /// # Arguments
+    /// * `id` - The ID of the shard.
+    /// * `stage` - The shard's connection stage.
+    /// * `guilds` - The number of guilds the shard handles.
+    pub fn status(&self, id: u32, stage: ConnectionStage, guilds: usize) -> ShardStatus {
+        ShardStatus {
+            id,
+            stage,
+            latency: self.latency,
+            guilds,
+            last_heartbeat: self.last_heartbeat,
+            restarts: self.restarts,
+        }
+    }
+}
+
+/// Counts the guilds each shard handles.
+///
+/// # Arguments
+/// * `guilds` - The cached guilds.
+/// * `shard_count` - The total number of shards.
+///
+/// # Returns
+/// The number of guilds per shard ID; shards without guilds are left out.
+pub fn guild_counts(guilds: &[serenity::GuildId], shard_count: u32) -> HashMap<u32, usize> {
+    let mut counts = HashMap::new();
+    for guild_id in guilds {
+        *counts
+            .entry(serenity::utils::shard_id(*guild_id, shard_count.max(1)))
+            .or_default() += 1;
+    }
+
+    counts
+}
+
+/// Returns the shards as seen at the last check, ordered by ID.
+pub fn statuses() -> Vec<ShardStatus> {
+    STATUSES
+        .lock()
+        .map(|statuses| statuses.clone())
+        .unwrap_or_default()
+}
+
+/// Checks every shard once, restarting the wedged ones and recording their statuses.
+///
+/// # Arguments
+/// * `trackers` - What is remembered of each shard.
+/// * `cache` - The cache the guilds are counted from.
+/// * `shard_manager` - The shard manager of the client.
+/// * `now` - The current time.
+async fn check(
+    trackers: &mut HashMap<u32, ShardTracker>,
+    cache: &serenity::Cache,
+    shard_manager: &ShardManager,
+    now: DateTime<Utc>,
+) {
+    let counts = guild_counts(&cache.guilds(), cache.shard_count());
+    let shards: Vec<(u32, ConnectionStage, Option<Duration>)> = shard_manager
+        .runners
+        .lock()
+        .await
+        .iter()
+        .map(|(id, runner)| (id.0, runner.stage, runner.latency))
+        .collect();
+
+    let mut statuses = Vec::with_capacity(shards.len());
+    for (id, stage, latency) in shards {
+        let tracker = trackers.entry(id).or_insert_with(|| ShardTracker::new(now));
+        if tracker.observe(stage, latency, now) {
+            warn!(
+                "Restarting shard {}: no heartbeat since {} (stage {})",
+                id,
+                tracker
+                    .last_heartbeat
+                    .map_or("never".to_string(), |at| at.to_rfc3339()),
+                stage
+            );
+            shard_manager.restart(serenity::ShardId(id)).await;
+            tracker.restarted(now);
+        }
+        statuses.push(tracker.status(id, stage, counts.get(&id).copied().unwrap_or_default()));
+    }
+    statuses.sort_by_key(|status| status.id);
+
+    if let Ok(mut recorded) = STATUSES.lock() {
+        *recorded = statuses;
+    }
+}
+
+/// Spawns the background task checking the shards every `CHECK_INTERVAL`.
+///
+/// # Arguments
+/// * `cache` - The cache of the client.
+/// * `shard_manager` - The shard manager of the client.
+/// * `clock` - Source of the current time.
+pub fn spawn(cache: Arc<serenity::Cache>, shard_manager: Arc<ShardManager>, clock: Arc<dyn Clock>) {
+    tokio::spawn(async move {
+        let mut trackers = HashMap::new();
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            check(&mut trackers, &cache, &shard_manager, clock.now()).await;
+        }
+    });
+}
+
+/// Renders one metric with a sample per shard.
+///
+/// # Arguments
+/// * `name` - The name of the metric after `cigarette_counter_shard_`.
+/// * `kind` - The metric type, `gauge` or `counter`.
+/// * `samples` - The value of each shard; shards without a value are left out.
+///
+/// # Returns
+/// The metric text, empty if no shard has a value.
+fn render_metric(name: &str, kind: &str, samples: Vec<(u32, String)>) -> String {
+    if samples.is_empty() {
+        return String::new();
+    }
+
+    let mut metric = format!("# TYPE cigarette_counter_shard_{} {}\n", name, kind);
+    for (id, value) in samples {
+        metric.push_str(&format!(
+            "cigarette_counter_shard_{}{{shard=\"{}\"}} {}\n",
+            name, id, value
+        ));
+    }
+
+    metric
+}
+
+/// Renders the per-shard metrics in the Prometheus text exposition format.
+///
+/// Latencies and heartbeats that are not known yet are left out.
+///
+/// # Arguments
+/// * `statuses` - The shards as seen at the last check.
+///
+/// # Returns
+/// The metrics text.
+pub fn render_metrics(statuses: &[ShardStatus]) -> String {
+    let samples = |value: fn(&ShardStatus) -> Option<String>| {
+        statuses
+            .iter()
+            .filter_map(|status| Some((status.id, value(status)?)))
+            .collect()
+    };
+
+    [
+        render_metric(
+            "connected",
+            "gauge",
+            samples(|status| {
+                Some(u8::from(status.stage == ConnectionStage::Connected).to_string())
+            }),
+        ),
+        render_metric(
+            "latency_seconds",
+            "gauge",
+            samples(|status| {
+                status
+                    .latency
+                    .map(|latency| format!("{:.3}", latency.as_secs_f64()))
+            }),
+        ),
+        render_metric(
+            "guilds",
+            "gauge",
+            samples(|status| Some(status.guilds.to_string())),
+        ),
+        render_metric(
+            "last_heartbeat_timestamp_seconds",
+            "gauge",
+            samples(|status| status.last_heartbeat.map(|at| at.timestamp().to_string())),
+        ),
+        render_metric(
+            "restarts_total",
+            "counter",
+            samples(|status| Some(status.restarts.to_string())),
+        ),
+    ]
+    .concat()
+}
@@ -0,0 +1,102 @@
+//! The opt-in smoke-free board of a guild.
+//!
+//! Members who join with `leaderboard smoke-free join` are ranked by their
+//! current streak of smoke-free days, counted like the milestone roles: full
+//! local days since the member's last log anywhere. The board is shown with
+//! `leaderboard smoke-free` and at the end of the weekly forum digest; members
+//! leave it with `leaderboard smoke-free leave`.
+
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+
+use crate::database::SmokeFreeMember;
+
+/// Most members shown on the board
+pub const MAX_SHOWN: usize = 10;
+
+/// A member's place on the smoke-free board
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Streak {
+    pub discord_id: String,
+    pub rank: i32,
+    pub days: i64,
+}
+
+/// Ranks the board's members by their smoke-free streak, longest first.
+///
+/// Members with equal streaks share a rank, like in season standings; those
+/// who have never logged have no streak yet.
+///
+/// # Arguments
+/// * `members` - The members of the board with their last log.
+/// * `today` - The current local date.
+/// * `timezone` - The time zone days are counted in.
+///
+/// # Returns
+/// The board ordered by rank.
+pub fn rank_streaks(members: &[SmokeFreeMember], today: NaiveDate, timezone: Tz) -> Vec<Streak> {
+    let mut streaks: Vec<Streak> = members
+        .iter()
+        .map(|member| Streak {
+            discord_id: member.discord_id.clone(),
+            rank: 0,
+            days: member
+                .last_smoked_at
+                .map(|smoked_at| {
+                    (today - smoked_at.with_timezone(&timezone).date_naive())
+                        .num_days()
+                        .max(0)
+                })
+                .unwrap_or_default(),
+        })
+        .collect();
+    streaks.sort_by(|a, b| b.days.cmp(&a.days).then(a.discord_id.cmp(&b.discord_id)));
+
+    let mut previous: Option<(i64, i32)> = None;
+    for (position, streak) in streaks.iter_mut().enumerate() {
+        streak.rank = match previous {
+            Some((days, rank)) if days == streak.days => rank,
+            _ => i32::try_from(position + 1).unwrap_or(i32::MAX),
+        };
+        previous = Some((streak.days, streak.rank));
+    }
+
+    streaks
+}
+
+/// Renders the top of the board, one line per member.
+///
+/// # Arguments
+/// * `streaks` - The board ordered by rank.
+///
+/// # Returns
+/// The lines of the first `MAX_SHOWN` members, e.g. `1位 <@1> 12日`.
+pub fn streak_lines(streaks: &[Streak]) -> Vec<String> {
+    streaks
+        .iter()
+        .take(MAX_SHOWN)
+        .map(|streak| {
+            format!(
+                "{}位 <@{}> {}日",
+                streak.rank, streak.discord_id, streak.days
+            )
+        })
+        .collect()
+}
+
+/// Renders the board's section of the weekly digest.
+///
+/// # Arguments
+/// * `streaks` - The board ordered by rank.
+///
+/// # Returns
+/// The section, or `None` if nobody joined the board.
+pub fn digest_section(streaks: &[Streak]) -> Option<String> {
+    if streaks.is_empty() {
+        return None;
+    }
+
+    let mut lines = vec!["禁煙ボード（連続で記録のない日数）".to_string()];
+    lines.extend(streak_lines(streaks));
+    Some(lines.join("\n"))
+}
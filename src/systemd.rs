@@ -0,0 +1,138 @@
+//! systemd service notifications.
+//!
+//! When the bot runs as a `Type=notify` unit it reports `READY=1` once the
+//! gateway has connected and `STOPPING=1` when it shuts down. If the unit also
+//! sets `WatchdogSec=`, a task checks the gateway and the database twice per
+//! watchdog interval and sends `WATCHDOG=1` only while both are healthy, so
+//! systemd restarts a bot that is still running but no longer able to serve.
+//! Outside systemd (`NOTIFY_SOCKET` unset) all of this does nothing.
+
+use std::{sync::Arc, time::Duration};
+
+use poise::serenity_prelude::{futures::lock::Mutex, ConnectionStage, ShardManager};
+use sd_notify::NotifyState;
+use tracing::{info, warn};
+
+use crate::clock::Clock;
+use crate::database::Database;
+use crate::heartbeat;
+
+/// Time allowed for the database probe of a watchdog check
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sends notifications to the service manager, logging failures.
+///
+/// # Arguments
+/// * `state` - The notifications.
+fn notify(state: &[NotifyState]) {
+    if let Err(e) = sd_notify::notify(false, state) {
+        warn!("Failed to notify systemd: {}", e);
+    }
+}
+
+/// Tells the service manager that startup has finished.
+pub fn notify_ready() {
+    notify(&[NotifyState::Ready]);
+}
+
+/// Tells the service manager that the bot is shutting down.
+pub fn notify_stopping() {
+    notify(&[NotifyState::Stopping]);
+}
+
+/// Resets the watchdog timer of the service manager.
+pub fn feed_watchdog() {
+    notify(&[NotifyState::Watchdog]);
+}
+
+/// Returns the watchdog interval configured for this process.
+///
+/// # Returns
+/// The interval, or `None` if the watchdog is disabled or meant for another process.
+pub fn watchdog_interval() -> Option<Duration> {
+    let mut usec = 0;
+    sd_notify::watchdog_enabled(false, &mut usec).then(|| Duration::from_micros(usec))
+}
+
+/// Returns whether the gateway is connected.
+///
+/// # Arguments
+/// * `stages` - The connection stages of all shards.
+///
+/// # Returns
+/// `true` if there is at least one shard and every shard is connected.
+pub fn gateway_healthy(stages: impl IntoIterator<Item = ConnectionStage>) -> bool {
+    let mut stages = stages.into_iter().peekable();
+    stages.peek().is_some() && stages.all(|stage| stage == ConnectionStage::Connected)
+}
+
+/// Checks the gateway and the database.
+///
+/// # Arguments
+/// * `database` - The database.
+/// * `clock` - Source of the current time.
+/// * `shard_manager` - The shard manager of the client.
+///
+/// # Returns
+/// `true` if all shards are connected and the database answered in time.
+async fn healthy(
+    database: &Mutex<Database>,
+    clock: &dyn Clock,
+    shard_manager: &ShardManager,
+) -> bool {
+    let stages: Vec<ConnectionStage> = shard_manager
+        .runners
+        .lock()
+        .await
+        .values()
+        .map(|runner| runner.stage)
+        .collect();
+    if !gateway_healthy(stages) {
+        warn!("Watchdog: gateway is not connected");
+        return false;
+    }
+
+    match tokio::time::timeout(PROBE_TIMEOUT, heartbeat::probe(database, clock)).await {
+        Ok(Ok(_)) => true,
+        Ok(Err(e)) => {
+            warn!("Watchdog: database probe failed: {}", e);
+            false
+        }
+        Err(_) => {
+            warn!("Watchdog: database probe timed out");
+            false
+        }
+    }
+}
+
+/// Spawns the task feeding the watchdog while the bot is healthy.
+///
+/// Does nothing unless the service manager enabled the watchdog.
+///
+/// # Arguments
+/// * `database` - Database connection shared with the bot.
+/// * `clock` - Source of the current time.
+/// * `shard_manager` - The shard manager whose connection is checked.
+pub fn spawn_watchdog(
+    database: Arc<Mutex<Database>>,
+    clock: Arc<dyn Clock>,
+    shard_manager: Arc<ShardManager>,
+) {
+    let Some(timeout) = watchdog_interval() else {
+        return;
+    };
+    info!(
+        "systemd watchdog enabled with a {}s timeout",
+        timeout.as_secs()
+    );
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(timeout / 2);
+        loop {
+            interval.tick().await;
+            if healthy(&database, clock.as_ref(), &shard_manager).await {
+                feed_watchdog();
+            }
+        }
+    });
+}
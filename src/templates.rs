@@ -0,0 +1,269 @@
+//! Per-guild message templates.
+//!
+//! Guild admins can override a few key messages with `templates set`. A
+//! template is plain text with `{placeholder}` variables; `{{` and `}}`
+//! produce literal braces. Templates are validated against the placeholders
+//! of their key when they are set, so rendering never fails. Guilds without
+//! an override (and DMs) use the built-in default.
+//!
+//! Every stored override has a version that is bumped on each change. An
+//! admin can pass the version they last saw (e.g. `v3`) to `templates set`
+//! or `templates reset`; the change is then refused if another admin has
+//! changed the template in the meantime instead of silently overwriting it.
+
+use std::{fmt, str::FromStr};
+
+use tracing::warn;
+
+use crate::database::Database;
+
+/// Maximum length of a template in characters
+pub const MAX_LENGTH: usize = 1000;
+
+/// A message that can be customized per guild
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateKey {
+    /// Sent when a cigarette is recorded from a panel
+    Confirmation,
+    /// Sent when a member reaches a smoke-free milestone
+    Milestone,
+    /// Periodic summary of a member's consumption
+    Digest,
+}
+
+impl TemplateKey {
+    /// All template keys, in display order
+    pub const ALL: [Self; 3] = [Self::Confirmation, Self::Milestone, Self::Digest];
+
+    /// Returns the name used in commands and storage.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Confirmation => "confirmation",
+            Self::Milestone => "milestone",
+            Self::Digest => "digest",
+        }
+    }
+
+    /// Returns the placeholders a template for this key may use.
+    pub fn placeholders(self) -> &'static [&'static str] {
+        match self {
+            Self::Confirmation => &["user", "date", "summary", "total"],
+            Self::Milestone => &["user", "days", "role"],
+            Self::Digest => &["user", "period", "summary", "total"],
+        }
+    }
+
+    /// Returns the built-in template used when a guild has no override.
+    pub fn default_template(self) -> &'static str {
+        match self {
+            Self::Confirmation => "記録しました。\n本日（{date}）の累計本数\n{summary}",
+            Self::Milestone => "{user} 禁煙{days}日を達成しました！「{role}」を付与しました。",
+            Self::Digest => "{user} の{period}のまとめ\n合計: {total}\n{summary}",
+        }
+    }
+}
+
+impl fmt::Display for TemplateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Error returned when a template key argument is unknown
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown template key: {0} (expected confirmation, milestone or digest)")]
+pub struct ParseTemplateKeyError(String);
+
+impl FromStr for TemplateKey {
+    type Err = ParseTemplateKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|key| key.name() == s)
+            .ok_or_else(|| ParseTemplateKeyError(s.to_string()))
+    }
+}
+
+/// The version of a guild's template a change is based on, written as e.g. `v3`
+///
+/// Version 0 stands for the built-in default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Revision(pub i32);
+
+impl fmt::Display for Revision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.0)
+    }
+}
+
+/// Error returned when a revision argument cannot be parsed
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid revision: {0} (expected e.g. v3)")]
+pub struct ParseRevisionError(String);
+
+impl FromStr for Revision {
+    type Err = ParseRevisionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.strip_prefix(['v', 'V'])
+            .and_then(|version| version.parse::<i32>().ok())
+            .filter(|version| *version >= 0)
+            .map(Self)
+            .ok_or_else(|| ParseRevisionError(s.to_string()))
+    }
+}
+
+/// Error returned when a template fails validation
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum TemplateError {
+    #[error("Template is empty")]
+    Empty,
+    #[error("Template is longer than {MAX_LENGTH} characters")]
+    TooLong,
+    #[error("Unclosed placeholder (use {{{{ for a literal brace)")]
+    UnclosedPlaceholder,
+    #[error("Unmatched }} (use }}}} for a literal brace)")]
+    UnmatchedBrace,
+    #[error("Unknown placeholder: {{{0}}}")]
+    UnknownPlaceholder(String),
+}
+
+/// A piece of a parsed template
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Placeholder(&'static str),
+}
+
+/// A validated message template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    /// Parses and validates a template.
+    ///
+    /// # Arguments
+    /// * `key` - The key the template is for, determining the allowed placeholders.
+    /// * `source` - The template text.
+    ///
+    /// # Returns
+    /// The parsed template, or a `TemplateError` describing the first problem found.
+    pub fn parse(key: TemplateKey, source: &str) -> Result<Self, TemplateError> {
+        if source.trim().is_empty() {
+            return Err(TemplateError::Empty);
+        }
+        if source.chars().count() > MAX_LENGTH {
+            return Err(TemplateError::TooLong);
+        }
+
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '}' => return Err(TemplateError::UnmatchedBrace),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some('{') | None => return Err(TemplateError::UnclosedPlaceholder),
+                            Some(c) => name.push(c),
+                        }
+                    }
+                    let placeholder = key
+                        .placeholders()
+                        .iter()
+                        .find(|placeholder| **placeholder == name.trim())
+                        .ok_or(TemplateError::UnknownPlaceholder(name))?;
+
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(Segment::Placeholder(placeholder));
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Returns the parsed built-in template of a key.
+    ///
+    /// # Arguments
+    /// * `key` - The message.
+    pub fn default_for(key: TemplateKey) -> Self {
+        Self::parse(key, key.default_template())
+            .expect("built-in templates use only known placeholders")
+    }
+
+    /// Renders the template.
+    ///
+    /// # Arguments
+    /// * `values` - The placeholder values; placeholders without a value render as empty text.
+    ///
+    /// # Returns
+    /// The rendered message.
+    pub fn render(&self, values: &[(&str, &str)]) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.as_str(),
+                Segment::Placeholder(name) => values
+                    .iter()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| *value)
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }
+}
+
+/// Loads the template a guild uses for a message.
+///
+/// Stored templates that no longer validate (e.g. after a placeholder was
+/// renamed) are logged and replaced by the default.
+///
+/// # Arguments
+/// * `db` - The database.
+/// * `guild_id` - The guild the message is sent in, or `None` outside guilds.
+/// * `key` - The message.
+///
+/// # Returns
+/// A Result containing the template or an `Error`.
+pub async fn load_template(
+    db: &Database,
+    guild_id: Option<&str>,
+    key: TemplateKey,
+) -> Result<Template, sqlx::Error> {
+    let Some(guild_id) = guild_id else {
+        return Ok(Template::default_for(key));
+    };
+    let Some(source) = db.guild(guild_id).get_message_template(key.name()).await? else {
+        return Ok(Template::default_for(key));
+    };
+
+    Ok(Template::parse(key, &source).unwrap_or_else(|e| {
+        warn!(
+            "Ignoring invalid {} template of guild {}: {}",
+            key, guild_id, e
+        );
+        Template::default_for(key)
+    }))
+}
@@ -0,0 +1,31 @@
+//! A short tutorial for users pressing a panel button for the first time.
+//!
+//! A user who started using the bot (accepted the data policy or otherwise
+//! got a `users` row) within `NEW_USER_WINDOW` and has no logs yet gets this
+//! tutorial as an ephemeral follow-up to the confirmation of their first
+//! press. Gating on the user's `created_at` keeps existing users, who are
+//! asked to accept the policy again when it changes, from seeing it.
+
+use chrono::Duration;
+
+use crate::embed::Title;
+use crate::frontend::Reply;
+
+/// How recently a user must have been created to get the tutorial
+pub const NEW_USER_WINDOW: Duration = Duration::hours(1);
+
+/// The tutorial shown after the first press
+pub const TUTORIAL_TEXT: &str = "はじめての記録です。使い方を簡単にご紹介します。\n\
+    - **記録されるもの**: ボタンを押すたびに、押した日時・種類・本数（1本）が記録されます。\n\
+    - **統計を見る**: `stats month` で今月の日ごとの合計、`stats guild` でサーバーの合計を確認できます。\n\
+    - **取り消す**: 押し間違えた場合は記録の「取り消し」ボタンか `undo` で直前の記録を消せます。古い重複は `cleanup` で整理できます。\n\
+    - **データを削除する**: 記録の削除を希望する場合は、ボットの管理者に連絡してください。\n\
+    ほかのコマンドは `help` で確認できます。この案内はあなたにだけ表示されています。";
+
+/// Builds the tutorial, visible only to the new user.
+///
+/// # Returns
+/// The tutorial `Reply`.
+pub fn tutorial() -> Reply {
+    Reply::new(TUTORIAL_TEXT).titled(Title::Help).ephemeral()
+}
@@ -0,0 +1,88 @@
+//! Units of smoking types and conversions between them.
+//!
+//! A log's quantity counts units of its type (e.g. `本` for cigarettes,
+//! `pod` for a vape), so moving a log to a type with another unit must
+//! convert the quantity rather than carry the number over. Owners can store
+//! a conversion hint per type pair (`type conversion set`); re-typing
+//! commands show it when asking for the converted quantity.
+
+use crate::database::SmokingType;
+
+/// The unit of types that do not name one
+pub const DEFAULT_UNIT: &str = "本";
+
+/// Returns the unit of a smoking type.
+///
+/// # Arguments
+/// * `smoking_type` - The smoking type.
+///
+/// # Returns
+/// The unit, `DEFAULT_UNIT` if the type does not name one.
+pub fn unit_of(smoking_type: &SmokingType) -> &str {
+    smoking_type.unit.as_deref().unwrap_or(DEFAULT_UNIT)
+}
+
+/// Returns whether two smoking types count in different units.
+///
+/// # Arguments
+/// * `from` - The current type.
+/// * `to` - The new type.
+///
+/// # Returns
+/// `true` if quantities must be converted between the types.
+pub fn units_differ(from: &SmokingType, to: &SmokingType) -> bool {
+    unit_of(from) != unit_of(to)
+}
+
+/// Converts a quantity with a conversion factor.
+///
+/// # Arguments
+/// * `quantity` - The quantity in the source unit.
+/// * `factor` - How many target units one source unit is.
+///
+/// # Returns
+/// The rounded quantity in the target unit, at least one.
+pub fn convert(quantity: i32, factor: f64) -> i32 {
+    (f64::from(quantity) * factor)
+        .round()
+        .clamp(1.0, f64::from(i32::MAX)) as i32
+}
+
+/// Returns whether a conversion factor can be used.
+///
+/// # Arguments
+/// * `factor` - The factor given by an owner.
+///
+/// # Returns
+/// `true` if the factor is finite and positive.
+pub fn is_valid_factor(factor: f64) -> bool {
+    factor.is_finite() && factor > 0.0
+}
+
+/// Formats a conversion factor without trailing zeros.
+///
+/// # Arguments
+/// * `factor` - The factor.
+///
+/// # Returns
+/// The factor with at most three decimals (e.g. `20`, `0.05`).
+pub fn format_factor(factor: f64) -> String {
+    let formatted = format!("{:.3}", factor);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// Describes a conversion hint between two units.
+///
+/// # Arguments
+/// * `from_unit` - The source unit.
+/// * `to_unit` - The target unit.
+/// * `factor` - How many target units one source unit is.
+///
+/// # Returns
+/// The hint, e.g. `1本 = 20回`.
+pub fn describe_conversion(from_unit: &str, to_unit: &str, factor: f64) -> String {
+    format!("1{} = {}{}", from_unit, format_factor(factor), to_unit)
+}
@@ -0,0 +1,112 @@
+//! Opt-in smoke-break prompts for designated voice channels.
+//!
+//! When an opted-in user joins a voice channel designated as a smoking area
+//! (「喫煙所」), the bot DMs them the cigarette panel so logging during the
+//! break takes a single tap. Unlike other panels the prompt only accepts
+//! presses for `PANEL_TIMEOUT`, so its presses are handled by a collector and
+//! told apart from those of persistent panels by `SMOKE_BREAK_PANEL_PREFIX`.
+
+use std::time::Duration;
+
+use poise::serenity_prelude as serenity;
+
+use crate::commands::panel::{create_cigarette_buttons, handle_interaction};
+use crate::{Data, Error};
+
+/// How long the DM panel keeps accepting button presses
+const PANEL_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Prefix of the IDs of the panels sent by DM
+pub const SMOKE_BREAK_PANEL_PREFIX: &str = "smoke-break-";
+
+/// Handles a voice state update, prompting the user if they joined a smoke-break channel.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `old` - The previous voice state, if known.
+/// * `new` - The new voice state.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+pub async fn handle_voice_state_update(
+    ctx: &serenity::Context,
+    data: &Data,
+    old: Option<&serenity::VoiceState>,
+    new: &serenity::VoiceState,
+) -> Result<(), Error> {
+    let (Some(guild_id), Some(channel_id)) = (new.guild_id, new.channel_id) else {
+        return Ok(());
+    };
+    // Ignore mute/deafen changes within the same channel.
+    if old.and_then(|old| old.channel_id) == Some(channel_id) {
+        return Ok(());
+    }
+
+    {
+        let db = data.database.lock().await;
+        if !db
+            .guild(&guild_id.to_string())
+            .is_smoke_break_channel(&channel_id.to_string())
+            .await?
+            || !db
+                .smoke_break_prompt_enabled(&new.user_id.to_string())
+                .await?
+            || db
+                .is_tracking_paused(&new.user_id.to_string(), data.clock.today())
+                .await?
+        {
+            return Ok(());
+        }
+    }
+
+    send_panel_dm(ctx, data, new.user_id).await
+}
+
+/// Sends the cigarette panel to a user by DM and handles presses until it times out.
+///
+/// # Arguments
+/// * `ctx` - The serenity context.
+/// * `data` - The shared application state.
+/// * `user_id` - The user to prompt.
+///
+/// # Returns
+/// A Result indicating success or an `Error`.
+async fn send_panel_dm(
+    ctx: &serenity::Context,
+    data: &Data,
+    user_id: serenity::UserId,
+) -> Result<(), Error> {
+    let uuid = format!(
+        "{}{}-{}",
+        SMOKE_BREAK_PANEL_PREFIX,
+        user_id,
+        data.clock.now().timestamp_millis()
+    );
+
+    let buttons = {
+        let db = data.database.lock().await;
+        create_cigarette_buttons(&db, &uuid, None).await?
+    };
+
+    let message = user_id
+        .create_dm_channel(ctx)
+        .await?
+        .send_message(
+            ctx,
+            serenity::CreateMessage::new()
+                .content("喫煙所に入室しました。記録しますか？")
+                .components(vec![serenity::CreateActionRow::Buttons(buttons)]),
+        )
+        .await?;
+
+    while let Some(mci) = serenity::ComponentInteractionCollector::new(ctx)
+        .message_id(message.id)
+        .timeout(PANEL_TIMEOUT)
+        .await
+    {
+        handle_interaction(ctx, data, &mci, &uuid).await?;
+    }
+
+    Ok(())
+}
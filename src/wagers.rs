@@ -0,0 +1,291 @@
+//! Friendly wagers between two users; no money or stakes are involved.
+//!
+//! `bet @friend lowest-count week` proposes a wager that the friend accepts
+//! or declines with buttons. Once accepted, the wager runs for its period
+//! from that moment, and a recurring job announces the winner by the tracked
+//! counts in the channel it was proposed in. Wagers nobody answers within
+//! `PENDING_EXPIRY` expire.
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use poise::serenity_prelude::{self as serenity, futures::lock::Mutex};
+use tracing::warn;
+
+use crate::clock::Clock;
+use crate::database::{Database, ScheduledJob, Wager};
+use crate::format::{format_count, Locale};
+use crate::jobs::JobHandler;
+use crate::Error;
+
+/// Scheduled job kind settling finished wagers
+pub const WAGER_JOB: &str = "settle_wagers";
+
+/// Interval between checks for finished wagers
+const CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// How long a proposed wager waits for the opponent
+pub const PENDING_EXPIRY: chrono::Duration = chrono::Duration::hours(24);
+
+/// What decides the winner of a wager
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WagerMetric {
+    /// Whoever logs fewer units wins
+    LowestCount,
+}
+
+impl WagerMetric {
+    /// Every metric, in the order shown to users
+    pub const ALL: [WagerMetric; 1] = [WagerMetric::LowestCount];
+
+    /// Returns the name used in commands and stored with wagers.
+    pub fn name(self) -> &'static str {
+        match self {
+            WagerMetric::LowestCount => "lowest-count",
+        }
+    }
+
+    /// Returns the rule shown to users.
+    pub fn label(self) -> &'static str {
+        match self {
+            WagerMetric::LowestCount => "記録した本数が少なかった方が勝ち",
+        }
+    }
+
+    /// Looks up a metric by name.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the metric (e.g. `lowest-count`).
+    ///
+    /// # Returns
+    /// The metric, or `None` for unknown names.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|metric| metric.name() == name)
+    }
+
+    /// Decides the winner of a wager.
+    ///
+    /// # Arguments
+    /// * `challenger_total` - The challenger's total over the period.
+    /// * `opponent_total` - The opponent's total over the period.
+    ///
+    /// # Returns
+    /// The winning side, `None` for a draw.
+    pub fn winner(self, challenger_total: i64, opponent_total: i64) -> Option<Side> {
+        match self {
+            WagerMetric::LowestCount => match challenger_total.cmp(&opponent_total) {
+                std::cmp::Ordering::Less => Some(Side::Challenger),
+                std::cmp::Ordering::Greater => Some(Side::Opponent),
+                std::cmp::Ordering::Equal => None,
+            },
+        }
+    }
+}
+
+/// How long a wager runs once accepted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WagerPeriod {
+    Day,
+    Week,
+}
+
+impl WagerPeriod {
+    /// Every period, in the order shown to users
+    pub const ALL: [WagerPeriod; 2] = [WagerPeriod::Day, WagerPeriod::Week];
+
+    /// Returns the name used in commands and stored with wagers.
+    pub fn name(self) -> &'static str {
+        match self {
+            WagerPeriod::Day => "day",
+            WagerPeriod::Week => "week",
+        }
+    }
+
+    /// Returns the length shown to users.
+    pub fn label(self) -> &'static str {
+        match self {
+            WagerPeriod::Day => "1日",
+            WagerPeriod::Week => "1週間",
+        }
+    }
+
+    /// Returns the length of the period.
+    pub fn duration(self) -> chrono::Duration {
+        match self {
+            WagerPeriod::Day => chrono::Duration::days(1),
+            WagerPeriod::Week => chrono::Duration::weeks(1),
+        }
+    }
+
+    /// Looks up a period by name.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the period (e.g. `week`).
+    ///
+    /// # Returns
+    /// The period, or `None` for unknown names.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|period| period.name() == name)
+    }
+}
+
+/// A party of a wager
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Challenger,
+    Opponent,
+}
+
+/// Describes the terms of a wager.
+///
+/// # Arguments
+/// * `metric` - What decides the winner.
+/// * `period` - How long the wager runs.
+///
+/// # Returns
+/// The terms, e.g. `1週間で記録した本数が少なかった方が勝ち`.
+pub fn describe_terms(metric: WagerMetric, period: WagerPeriod) -> String {
+    format!("{}で{}", period.label(), metric.label())
+}
+
+/// Renders the announcement of a settled wager.
+///
+/// # Arguments
+/// * `wager` - The wager.
+/// * `challenger_total` - The challenger's total over the period.
+/// * `opponent_total` - The opponent's total over the period.
+/// * `winner` - The winning side, `None` for a draw.
+///
+/// # Returns
+/// The announcement mentioning both users.
+pub fn announcement(
+    wager: &Wager,
+    challenger_total: i64,
+    opponent_total: i64,
+    winner: Option<Side>,
+) -> String {
+    let locale = Locale::Japanese;
+    let result = match winner {
+        Some(Side::Challenger) => format!("<@{}> さんの勝ちです！", wager.challenger_id),
+        Some(Side::Opponent) => format!("<@{}> さんの勝ちです！", wager.opponent_id),
+        None => "引き分けです！".to_string(),
+    };
+
+    format!(
+        "🏁 勝負 #{} の結果: {}\n<@{}>: {}\n<@{}>: {}",
+        wager.id,
+        result,
+        wager.challenger_id,
+        format_count(challenger_total, locale),
+        wager.opponent_id,
+        format_count(opponent_total, locale)
+    )
+}
+
+/// Job handler expiring unanswered wagers and settling finished ones every `CHECK_INTERVAL`
+pub struct WagerJob {
+    http: Arc<serenity::Http>,
+    database: Arc<Mutex<Database>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl WagerJob {
+    /// Creates the handler.
+    ///
+    /// # Arguments
+    /// * `http` - The Discord HTTP client.
+    /// * `database` - Database connection shared with the bot.
+    /// * `clock` - The clock deciding which wagers are over.
+    pub fn new(
+        http: Arc<serenity::Http>,
+        database: Arc<Mutex<Database>>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            http,
+            database,
+            clock,
+        }
+    }
+
+    /// Totals both parties over the wager's period, records the result and announces it.
+    ///
+    /// # Arguments
+    /// * `wager` - The finished wager.
+    ///
+    /// # Returns
+    /// A Result indicating success or an `Error`.
+    async fn settle(&self, wager: &Wager) -> Result<(), Error> {
+        let (Some(starts_at), Some(ends_at)) = (wager.starts_at, wager.ends_at) else {
+            return Err(format!("wager {} has no period", wager.id).into());
+        };
+        let metric = WagerMetric::from_name(&wager.metric)
+            .ok_or_else(|| format!("unknown wager metric: {}", wager.metric))?;
+
+        let (challenger_total, opponent_total) = {
+            let db = self.database.lock().await;
+            let mut totals = [0; 2];
+            for (total, user_id) in totals
+                .iter_mut()
+                .zip([&wager.challenger_id, &wager.opponent_id])
+            {
+                *total = db
+                    .get_user_type_totals(user_id, starts_at, ends_at)
+                    .await?
+                    .iter()
+                    .map(|total| total.total_quantity)
+                    .sum();
+            }
+            (totals[0], totals[1])
+        };
+        let winner = metric.winner(challenger_total, opponent_total);
+        let winner_id = winner.map(|side| match side {
+            Side::Challenger => wager.challenger_id.as_str(),
+            Side::Opponent => wager.opponent_id.as_str(),
+        });
+
+        // Recorded first, so a failed announcement is not posted twice.
+        self.database
+            .lock()
+            .await
+            .settle_wager(
+                wager.id,
+                challenger_total,
+                opponent_total,
+                winner_id,
+                self.clock.now(),
+            )
+            .await?;
+        serenity::ChannelId::new(wager.channel_id.parse()?)
+            .say(
+                &self.http,
+                announcement(wager, challenger_total, opponent_total, winner),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl JobHandler for WagerJob {
+    async fn run(&self, _job: &ScheduledJob) -> Result<(), Error> {
+        let now = self.clock.now();
+        let due = {
+            let db = self.database.lock().await;
+            db.expire_pending_wagers(now - PENDING_EXPIRY).await?;
+            db.get_due_wagers(now).await?
+        };
+        for wager in due {
+            if let Err(e) = self.settle(&wager).await {
+                warn!("Failed to settle wager {}: {}", wager.id, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn repeat_every(&self) -> Option<Duration> {
+        Some(CHECK_INTERVAL)
+    }
+}
@@ -0,0 +1,249 @@
+//! Buffering of panel presses during short database outages.
+//!
+//! When `WRITE_BUFFER_PATH` is set, presses arriving while the database
+//! circuit is open are kept in memory and appended to that file as JSON
+//! lines, so they survive a restart of the bot too. A background task
+//! replays them once the database answers again; replayed rows get
+//! `replayed_at` set. The buffer holds at most `WRITE_BUFFER_CAPACITY` logs;
+//! presses beyond that are rejected like any other request during an outage.
+//! Each log keeps the idempotency key of its interaction, so a press that
+//! was written before the outage was noticed is not recorded twice.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tracing::{info, warn};
+
+use crate::circuit_breaker::CircuitBreaker;
+use crate::database::BufferedLog;
+use crate::service::{LoggingService, ServiceError};
+
+/// Interval between attempts to replay buffered logs
+const REPLAY_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Errors returned when a log cannot be buffered
+#[derive(Debug, thiserror::Error)]
+pub enum BufferError {
+    #[error("Write buffer is full")]
+    Full,
+    #[error("Failed to persist the write buffer: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// The outcome of a replay
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReplaySummary {
+    /// Logs written to the database
+    pub written: usize,
+    /// Logs that had already been written
+    pub duplicates: usize,
+    /// Logs dropped because they can no longer be recorded (e.g. a deleted type)
+    pub dropped: usize,
+    /// Logs still waiting because the database failed again
+    pub remaining: usize,
+}
+
+/// Bounded buffer of logs, persisted to a file
+pub struct WriteBuffer {
+    path: PathBuf,
+    capacity: usize,
+    logs: Mutex<Vec<BufferedLog>>,
+}
+
+impl WriteBuffer {
+    /// Opens a buffer, loading the logs left in its file by an earlier run.
+    ///
+    /// Lines that cannot be parsed are logged and skipped.
+    ///
+    /// # Arguments
+    /// * `path` - The file the buffer is persisted to; created when needed.
+    /// * `capacity` - The maximum number of buffered logs.
+    ///
+    /// # Returns
+    /// A Result containing the buffer, or an `io::Error` if the file cannot be read.
+    pub fn open(path: impl Into<PathBuf>, capacity: usize) -> Result<Self, io::Error> {
+        let path = path.into();
+        let logs = match File::open(&path) {
+            Ok(file) => {
+                let mut logs = Vec::new();
+                for line in BufReader::new(file).lines() {
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    match serde_json::from_str(&line) {
+                        Ok(log) => logs.push(log),
+                        Err(e) => warn!("Skipping unreadable buffered log: {}", e),
+                    }
+                }
+                logs
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        if !logs.is_empty() {
+            info!(
+                "Loaded {} buffered logs from {}",
+                logs.len(),
+                path.display()
+            );
+        }
+
+        Ok(Self {
+            path,
+            capacity,
+            logs: Mutex::new(logs),
+        })
+    }
+
+    /// Returns the number of buffered logs.
+    pub fn len(&self) -> usize {
+        self.logs.lock().unwrap().len()
+    }
+
+    /// Returns whether no logs are buffered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Buffers a log, appending it to the file before accepting it.
+    ///
+    /// # Arguments
+    /// * `log` - The log.
+    ///
+    /// # Returns
+    /// A Result indicating success, or `BufferError::Full` or `BufferError::Io`.
+    pub fn push(&self, log: BufferedLog) -> Result<(), BufferError> {
+        let mut logs = self.logs.lock().unwrap();
+        if logs.len() >= self.capacity {
+            return Err(BufferError::Full);
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(
+            file,
+            "{}",
+            serde_json::to_string(&log).map_err(io::Error::from)?
+        )?;
+        file.sync_data()?;
+        logs.push(log);
+
+        Ok(())
+    }
+
+    /// Writes the buffered logs to the database in the order they were taken.
+    ///
+    /// Replay stops at the first database error; the logs not written yet
+    /// stay buffered for the next attempt.
+    ///
+    /// # Arguments
+    /// * `logging` - The logging service writing the logs.
+    ///
+    /// # Returns
+    /// A Result containing the `ReplaySummary`, or a `ServiceError` with the database error
+    /// that stopped the replay.
+    pub async fn replay(&self, logging: &LoggingService) -> Result<ReplaySummary, ServiceError> {
+        let pending = self.logs.lock().unwrap().clone();
+        let mut summary = ReplaySummary::default();
+        let mut failure = None;
+
+        let mut handled = 0;
+        for log in &pending {
+            match logging.replay_buffered(log).await {
+                Ok(true) => summary.written += 1,
+                Ok(false) => summary.duplicates += 1,
+                Err(ServiceError::Database(e)) => {
+                    failure = Some(ServiceError::Database(e));
+                    break;
+                }
+                Err(e) => {
+                    warn!(
+                        "Dropping buffered log of {} for type {}: {}",
+                        log.discord_id, log.smoking_type_id, e
+                    );
+                    summary.dropped += 1;
+                }
+            }
+            handled += 1;
+        }
+
+        // Logs buffered while replaying were appended after the handled ones.
+        let mut logs = self.logs.lock().unwrap();
+        logs.drain(..handled);
+        if let Err(e) = rewrite(&self.path, &logs) {
+            warn!("Failed to rewrite the write buffer: {}", e);
+        }
+        summary.remaining = logs.len();
+
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(summary),
+        }
+    }
+
+    /// Spawns the background task replaying buffered logs whenever the circuit allows it.
+    ///
+    /// # Arguments
+    /// * `logging` - The logging service writing the logs.
+    /// * `breaker` - The database circuit breaker, told how each replay went.
+    pub fn spawn_replay_task(
+        self: Arc<Self>,
+        logging: Arc<LoggingService>,
+        breaker: Arc<CircuitBreaker>,
+    ) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(REPLAY_INTERVAL);
+            loop {
+                interval.tick().await;
+                if self.is_empty() || breaker.allow().is_err() {
+                    continue;
+                }
+
+                let result = self.replay(&logging).await;
+                breaker.record_outcome(result.as_ref().err().map(|e| e as _));
+                match result {
+                    Ok(summary) => info!(
+                        "Replayed buffered logs: {} written, {} already written, {} dropped",
+                        summary.written, summary.duplicates, summary.dropped
+                    ),
+                    Err(e) => warn!(
+                        "Failed to replay buffered logs, {} still buffered: {}",
+                        self.len(),
+                        e
+                    ),
+                }
+            }
+        });
+    }
+}
+
+/// Replaces the contents of the buffer file.
+///
+/// # Arguments
+/// * `path` - The buffer file.
+/// * `logs` - The logs still buffered.
+///
+/// # Returns
+/// A Result indicating success or an `io::Error`.
+fn rewrite(path: &Path, logs: &[BufferedLog]) -> Result<(), io::Error> {
+    let temporary = path.with_extension("tmp");
+    let mut file = File::create(&temporary)?;
+    for log in logs {
+        writeln!(
+            file,
+            "{}",
+            serde_json::to_string(log).map_err(io::Error::from)?
+        )?;
+    }
+    file.sync_data()?;
+
+    fs::rename(&temporary, path)
+}
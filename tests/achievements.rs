@@ -0,0 +1,161 @@
+//! Tests for custom guild achievements and their condition DSL.
+
+mod common;
+
+use chrono::NaiveDate;
+use chrono_tz::Asia::Tokyo;
+use cigarette_counter::{
+    achievements::{
+        completed_periods, evaluate_guild, Comparison, Condition, ConditionError, Metric, Metrics,
+    },
+    clock::{Clock, MockClock},
+    commands::achievements::strip_condition,
+    database::Database,
+};
+use common::{create_user, set_smoked_at, setup};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+fn date(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2025, month, day).unwrap()
+}
+
+#[test]
+fn conditions_parse_and_normalize() {
+    let condition: Condition = "weekly_total <= 10&&smoke_free_days>2".parse().unwrap();
+
+    assert_eq!(condition.clauses.len(), 2);
+    assert_eq!(condition.clauses[0].metric, Metric::WeeklyTotal);
+    assert_eq!(condition.clauses[0].comparison, Comparison::LessOrEqual);
+    assert_eq!(condition.clauses[1].comparison, Comparison::Greater);
+    assert_eq!(
+        condition.to_string(),
+        "weekly_total<=10 && smoke_free_days>2"
+    );
+    assert_eq!(
+        strip_condition(r#"condition:"monthly_total==0""#),
+        "monthly_total==0"
+    );
+}
+
+#[test]
+fn invalid_conditions_are_rejected() {
+    assert_eq!("  ".parse::<Condition>(), Err(ConditionError::Empty));
+    assert_eq!(
+        "weekly_total".parse::<Condition>(),
+        Err(ConditionError::MissingOperator("weekly_total".to_string()))
+    );
+    assert_eq!(
+        "yearly_total<5".parse::<Condition>(),
+        Err(ConditionError::UnknownMetric("yearly_total".to_string()))
+    );
+    assert_eq!(
+        "daily_total<=ten".parse::<Condition>(),
+        Err(ConditionError::InvalidNumber("ten".to_string()))
+    );
+    assert_eq!(
+        ["daily_total>=0"; 6].join("&&").parse::<Condition>(),
+        Err(ConditionError::TooManyClauses)
+    );
+}
+
+#[test]
+fn conditions_need_every_clause() {
+    let condition: Condition = "weekly_total<=10 && smoke_free_days>=2".parse().unwrap();
+    let metrics = Metrics {
+        weekly_total: 8,
+        smoke_free_days: 2,
+        ..Metrics::default()
+    };
+
+    assert!(condition.evaluate(&metrics));
+    assert!(!condition.evaluate(&Metrics {
+        smoke_free_days: 1,
+        ..metrics
+    }));
+    assert!(!condition.evaluate(&Metrics {
+        weekly_total: 11,
+        ..metrics
+    }));
+}
+
+#[test]
+fn totals_cover_the_last_completed_periods() {
+    // Wednesday, January 15th
+    assert_eq!(
+        completed_periods(date(1, 15)),
+        [
+            (date(1, 14), date(1, 15)),
+            (date(1, 6), date(1, 13)),
+            (NaiveDate::from_ymd_opt(2024, 12, 1).unwrap(), date(1, 1)),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn achievements_are_defined_per_guild_and_earned_once() {
+    let test = setup().await;
+    let guild = test.db.guild("10");
+
+    assert!(guild
+        .create_achievement("節煙週間", "weekly_total<=10")
+        .await
+        .unwrap());
+    assert!(!guild
+        .create_achievement("節煙週間", "weekly_total<=5")
+        .await
+        .unwrap());
+    assert!(guild
+        .create_achievement("禁煙5日", "smoke_free_days>=5")
+        .await
+        .unwrap());
+    assert!(test
+        .db
+        .guild("20")
+        .create_achievement("節煙週間", "weekly_total<=1")
+        .await
+        .unwrap());
+
+    let clock = MockClock::at_local(Tokyo, 2025, 1, 15, 12, 0).unwrap();
+    for (user_id, quantity) in [("1", 3), ("2", 20)] {
+        create_user(&test, user_id).await;
+        test.db
+            .log_smoking(user_id, Some("10"), 1, quantity)
+            .await
+            .unwrap();
+        set_smoked_at(&test, user_id, clock.now() - chrono::Duration::days(7)).await;
+    }
+
+    let database = Mutex::new(Database::new(test.pool.clone()));
+    assert_eq!(evaluate_guild(&database, &clock, "10").await.unwrap(), 3);
+    assert_eq!(evaluate_guild(&database, &clock, "10").await.unwrap(), 0);
+    assert_eq!(evaluate_guild(&database, &clock, "20").await.unwrap(), 0);
+
+    let earned: Vec<String> = guild
+        .get_earned_achievements("1")
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|earned| earned.name)
+        .collect();
+    assert_eq!(earned, vec!["節煙週間", "禁煙5日"]);
+    assert_eq!(guild.get_earned_achievements("2").await.unwrap().len(), 1);
+    assert!(test
+        .db
+        .guild("20")
+        .get_earned_achievements("1")
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Deleting an achievement removes who earned it.
+    assert!(guild.delete_achievement("節煙週間").await.unwrap());
+    assert!(!guild.delete_achievement("節煙週間").await.unwrap());
+    assert_eq!(guild.get_achievements().await.unwrap().len(), 1);
+    assert_eq!(guild.get_earned_achievements("1").await.unwrap().len(), 1);
+    assert_eq!(
+        test.db.get_achievement_guilds().await.unwrap(),
+        vec!["10", "20"]
+    );
+
+    test.teardown().await;
+}
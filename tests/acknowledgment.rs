@@ -0,0 +1,95 @@
+//! Tests for the per-guild acknowledgment required before using the panel.
+
+mod common;
+
+use cigarette_counter::{
+    acknowledgment::{accept_id, parse_accept_id},
+    commands::age_gate::update_age_gate,
+    database::Database,
+    embed::Title,
+    frontend::Reply,
+};
+use common::{create_user, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+#[test]
+fn accept_ids_round_trip() {
+    assert_eq!(parse_accept_id(&accept_id(1)), Some(1));
+    assert_eq!(parse_accept_id("acknowledgment:accept:x"), None);
+    assert_eq!(parse_accept_id("consent:accept:1"), None);
+}
+
+#[tokio::test]
+async fn acknowledgments_are_tracked_per_member_and_guild() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let (guild, other) = (test.db.guild("10"), test.db.guild("20"));
+
+    guild
+        .set_acknowledgment_policy("18歳以上です")
+        .await
+        .unwrap();
+    assert_eq!(
+        guild.get_acknowledgment_policy().await.unwrap().as_deref(),
+        Some("18歳以上です")
+    );
+    assert_eq!(other.get_acknowledgment_policy().await.unwrap(), None);
+
+    guild.acknowledge("1", chrono::Utc::now()).await.unwrap();
+    assert!(guild.has_acknowledged("1").await.unwrap());
+    assert!(!other.has_acknowledged("1").await.unwrap());
+
+    // A new message has to be acknowledged again.
+    guild
+        .set_acknowledgment_policy("20歳以上です")
+        .await
+        .unwrap();
+    assert!(!guild.has_acknowledged("1").await.unwrap());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn age_gate_command_sets_and_clears_the_message() {
+    let test = setup().await;
+    let database = Mutex::new(Database::new(test.pool.clone()));
+    let frontend = RecordingFrontend::default();
+
+    update_age_gate(&frontend, &database, "10", Some("  "))
+        .await
+        .unwrap();
+    update_age_gate(&frontend, &database, "10", Some("18歳以上です"))
+        .await
+        .unwrap();
+    update_age_gate(&frontend, &database, "10", None)
+        .await
+        .unwrap();
+    update_age_gate(&frontend, &database, "10", None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        [
+            Recorded::SendReply(Reply::error("確認メッセージを入力してください。")),
+            Recorded::SendReply(
+                Reply::new(
+                    "パネルの利用前に次の内容の確認を求めます。メンバーは改めて確認が必要です。\n> 18歳以上です"
+                )
+                .titled(Title::Settings)
+            ),
+            Recorded::SendReply(Reply::new("確認の要求を解除しました。").titled(Title::Settings)),
+            Recorded::SendReply(Reply::new("確認は要求されていません。").titled(Title::Settings)),
+        ]
+    );
+    assert_eq!(
+        test.db
+            .guild("10")
+            .get_acknowledgment_policy()
+            .await
+            .unwrap(),
+        None
+    );
+
+    test.teardown().await;
+}
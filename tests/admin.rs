@@ -0,0 +1,246 @@
+//! Tests for the owners' data-fix commands and their audit log.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono::{TimeZone, Utc};
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::MockClock,
+    commands::admin::{reassign_log, recompute_aggregates, shift_timestamps, show_audit_log},
+    database::Database,
+    frontend::Reply,
+    service::StatsService,
+};
+use common::{create_user, log_at, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+#[tokio::test]
+async fn reassigning_a_log_changes_its_type_and_is_audited() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let log = test.db.log_smoking("1", None, 1, 1).await.unwrap();
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 3, 12, 0).unwrap());
+    let stats = StatsService::new(database.clone(), clock);
+    let frontend = RecordingFrontend::default();
+
+    reassign_log(&frontend, &database, &stats, "99", log.id, "iqos", None)
+        .await
+        .unwrap();
+    reassign_log(&frontend, &database, &stats, "99", log.id + 1, "iqos", None)
+        .await
+        .unwrap();
+    reassign_log(&frontend, &database, &stats, "99", log.id, "cigar", None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        vec![
+            Recorded::SendReply(Reply::new(format!(
+                "ログ #{} の種類をIQOSに変更しました。",
+                log.id
+            ))),
+            Recorded::SendReply(Reply::error(format!(
+                "ログ #{} は見つかりません。",
+                log.id + 1
+            ))),
+            Recorded::SendReply(Reply::error(
+                "不明な種類です: cigar（使用できる種類: traditional, iqos, ploom, glo, other）"
+            )),
+        ]
+    );
+    let type_id: i32 = sqlx::query_scalar("SELECT smoking_type_id FROM smoking_logs WHERE id = $1")
+        .bind(log.id)
+        .fetch_one(&test.pool)
+        .await
+        .unwrap();
+    assert_eq!(type_id, 2);
+
+    let entries = test.db.get_admin_audit_entries(10).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].actor_id, "99");
+    assert_eq!(entries[0].action, "reassign_log_type");
+    assert_eq!(entries[0].target_id, "1");
+    assert_eq!(entries[0].details, format!("log {}: type 1 -> 2", log.id));
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn reassigning_to_another_unit_asks_for_a_converted_quantity() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    sqlx::query("UPDATE smoking_types SET unit = '回' WHERE id = 2")
+        .execute(&test.pool)
+        .await
+        .unwrap();
+    let log = test.db.log_smoking("1", None, 1, 2).await.unwrap();
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 3, 12, 0).unwrap());
+    let stats = StatsService::new(database.clone(), clock);
+    let frontend = RecordingFrontend::default();
+
+    reassign_log(&frontend, &database, &stats, "99", log.id, "iqos", None)
+        .await
+        .unwrap();
+    // The hint set for the opposite direction is inverted.
+    test.db.set_unit_conversion(2, 1, 0.05).await.unwrap();
+    reassign_log(&frontend, &database, &stats, "99", log.id, "iqos", None)
+        .await
+        .unwrap();
+    reassign_log(&frontend, &database, &stats, "99", log.id, "iqos", Some(40))
+        .await
+        .unwrap();
+
+    let calls = frontend.calls();
+    let prompts: Vec<&str> = calls[..2]
+        .iter()
+        .map(|call| match call {
+            Recorded::SendReply(reply) => reply.content.as_str(),
+            _ => panic!("expected a reply"),
+        })
+        .collect();
+    for prompt in &prompts {
+        assert!(prompt.starts_with(&format!(
+            "ログ #{} の数量は2本ですが、変更先の単位は「回」です。換算した数量を指定して admin reassign {} iqos <数量> を実行し直してください。",
+            log.id, log.id
+        )));
+    }
+    assert!(prompts[0].ends_with("換算の目安は type conversion set で登録できます。"));
+    assert!(prompts[1].ends_with("換算の目安: 1本 = 20回（2本 → 40回）"));
+    assert_eq!(
+        calls[2],
+        Recorded::SendReply(Reply::new(format!(
+            "ログ #{} の種類をIQOSに変更しました（数量 2本 → 40回）。",
+            log.id
+        )))
+    );
+
+    let entries = test.db.get_admin_audit_entries(10).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries[0].details,
+        format!("log {}: type 1 -> 2, quantity 2 -> 40", log.id)
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn shifting_moves_only_the_users_logs() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    create_user(&test, "2").await;
+    let smoked_at = Utc.with_ymd_and_hms(2024, 5, 3, 23, 0, 0).unwrap();
+    log_at(&test, "1", smoked_at).await;
+    log_at(&test, "1", smoked_at).await;
+    log_at(&test, "2", smoked_at).await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 4, 12, 0).unwrap());
+    let stats = StatsService::new(database.clone(), clock);
+    let frontend = RecordingFrontend::default();
+
+    shift_timestamps(&frontend, &database, &stats, "99", "1", -9)
+        .await
+        .unwrap();
+    shift_timestamps(&frontend, &database, &stats, "99", "1", 0)
+        .await
+        .unwrap();
+    shift_timestamps(&frontend, &database, &stats, "99", "1", 49)
+        .await
+        .unwrap();
+
+    let rejection = Reply::error("時間は0以外の-48から48の範囲で指定してください。");
+    assert_eq!(
+        frontend.calls(),
+        vec![
+            Recorded::SendReply(Reply::new("<@1> のログ2件を-9時間ずらしました。")),
+            Recorded::SendReply(rejection.clone()),
+            Recorded::SendReply(rejection),
+        ]
+    );
+    let times: Vec<(String, chrono::DateTime<Utc>)> =
+        sqlx::query_as("SELECT discord_id, smoked_at FROM smoking_logs ORDER BY id")
+            .fetch_all(&test.pool)
+            .await
+            .unwrap();
+    let shifted = Utc.with_ymd_and_hms(2024, 5, 3, 14, 0, 0).unwrap();
+    assert_eq!(
+        times,
+        vec![
+            ("1".to_string(), shifted),
+            ("1".to_string(), shifted),
+            ("2".to_string(), smoked_at),
+        ]
+    );
+
+    let entries = test.db.get_admin_audit_entries(10).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].action, "shift_timestamps");
+    assert_eq!(entries[0].details, "2 logs shifted by -9 hours");
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn recomputing_reports_fresh_aggregates() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 4, 12, 0).unwrap());
+    let stats = StatsService::new(database.clone(), clock);
+    let frontend = RecordingFrontend::default();
+    // The cached month predates the manual edit below.
+    stats
+        .monthly_totals("1", stats.current_month(), false)
+        .await
+        .unwrap();
+    log_at(
+        &test,
+        "1",
+        Utc.with_ymd_and_hms(2024, 5, 2, 8, 0, 0).unwrap(),
+    )
+    .await;
+    log_at(
+        &test,
+        "1",
+        Utc.with_ymd_and_hms(2024, 5, 2, 9, 0, 0).unwrap(),
+    )
+    .await;
+
+    recompute_aggregates(&frontend, &database, &stats, "99", "1")
+        .await
+        .unwrap();
+    show_audit_log(&frontend, &database).await.unwrap();
+
+    let calls = frontend.calls();
+    assert_eq!(
+        calls[0],
+        Recorded::SendReply(Reply::new(
+            "<@1> の集計を再計算しました（今月の合計: 2本、連続禁煙日数: 2日）。"
+        ))
+    );
+    let Recorded::SendReply(audit) = &calls[1] else {
+        panic!("unexpected call: {:?}", calls[1]);
+    };
+    assert!(audit
+        .content
+        .ends_with("<@99> recompute_aggregates <@1>: month total 2, 2 smoke-free days"));
+    let cached = stats
+        .monthly_totals("1", stats.current_month(), false)
+        .await
+        .unwrap();
+    assert_eq!(
+        cached
+            .value
+            .iter()
+            .map(|daily| daily.total_quantity)
+            .sum::<i64>(),
+        2
+    );
+
+    test.teardown().await;
+}
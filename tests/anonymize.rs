@@ -0,0 +1,44 @@
+//! Tests for the anonymization of Discord IDs in outgoing data.
+
+mod common;
+
+use cigarette_counter::{anonymize::Anonymizer, database::Database};
+use common::{create_user, setup};
+
+#[test]
+fn ids_are_hashed_only_when_a_salt_is_configured() {
+    let disabled = Anonymizer::default();
+    assert!(!disabled.is_enabled());
+    assert_eq!(disabled.id("123"), "123");
+
+    let anonymizer = Anonymizer::new(Some("pepper".to_string()));
+    let hashed = anonymizer.id("123");
+    assert_eq!(hashed.len(), 64);
+    assert_ne!(hashed, "123");
+    assert_eq!(anonymizer.id("123"), hashed);
+    assert_ne!(anonymizer.id("124"), hashed);
+    assert_ne!(Anonymizer::new(Some("salt".to_string())).id("123"), hashed);
+    assert_eq!(anonymizer.optional_id(None), None);
+}
+
+#[tokio::test]
+async fn outbox_payloads_carry_anonymized_ids() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let anonymizer = Anonymizer::new(Some("pepper".to_string()));
+    let db = Database::new(test.pool.clone())
+        .with_outbox()
+        .with_anonymizer(anonymizer.clone());
+
+    db.log_smoking("1", Some("10"), 1, 1).await.unwrap();
+    let messages = db
+        .get_due_outbox_messages(chrono::Utc::now(), 10)
+        .await
+        .unwrap();
+    let payload: serde_json::Value = serde_json::from_str(&messages[0].payload).unwrap();
+    assert_eq!(payload["discord_id"], anonymizer.id("1"));
+    assert_eq!(payload["guild_id"], anonymizer.id("10"));
+    assert!(!messages[0].payload.contains("\"1\""));
+
+    test.teardown().await;
+}
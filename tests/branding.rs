@@ -0,0 +1,63 @@
+//! Tests for per-deployment branding.
+//!
+//! The branding is set once per process, so these tests live in their own
+//! binary and share one branding.
+
+use cigarette_counter::{
+    commands::panel::cigarette_buttons,
+    database::SmokingType,
+    embed::Title,
+    format::{branding, format_count, set_branding, Branding, Locale},
+    frontend::Reply,
+};
+
+fn coffee() -> Branding {
+    Branding {
+        name: "コーヒーカウント".to_string(),
+        count_unit: "杯".to_string(),
+        color: 0x6F4E37,
+        emoji: Some("☕".to_string()),
+    }
+}
+
+#[test]
+fn counts_and_embeds_use_the_deployments_branding() {
+    set_branding(coffee());
+    // Only the first branding is kept.
+    set_branding(Branding::default());
+    assert_eq!(branding(), &coffee());
+
+    assert_eq!(format_count(1234, Locale::Japanese), "1,234杯");
+    let embeds = Reply::new("コーヒー: 2杯")
+        .titled(Title::Confirmation)
+        .styled_embeds(Locale::Japanese);
+    assert_eq!(embeds[0].color, 0x6F4E37);
+    assert_eq!(embeds[0].footer.as_deref(), Some("コーヒーカウント"));
+}
+
+#[test]
+fn types_without_an_emoji_show_the_brandings() {
+    set_branding(coffee());
+    let smoking_type = |id: i32, emoji: Option<&str>| SmokingType {
+        id,
+        type_name: "coffee".to_string(),
+        description: Some("コーヒー".to_string()),
+        created_at: None,
+        emoji: emoji.map(str::to_string),
+        unit: None,
+        typical_price: None,
+        currency: None,
+    };
+
+    let buttons = cigarette_buttons(
+        vec![smoking_type(1, None), smoking_type(2, Some("🥤"))],
+        "panel",
+        None,
+    )
+    .unwrap();
+    let emoji: Vec<serde_json::Value> = buttons
+        .iter()
+        .map(|button| serde_json::to_value(button).unwrap()["emoji"]["name"].clone())
+        .collect();
+    assert_eq!(emoji, [serde_json::json!("☕"), serde_json::json!("🥤")]);
+}
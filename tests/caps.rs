@@ -0,0 +1,156 @@
+//! Tests for the per-type daily caps set by guild admins.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::SystemClock,
+    commands::{
+        caps::{list_type_caps, update_ignore_type_caps, update_type_cap},
+        panel::record_cigarette,
+    },
+    database::{Database, TypeCap},
+    embed::Title,
+    frontend::Reply,
+};
+use common::{create_user, logging_service, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+const REJECTION: &str = "本日の紙タバコはこのサーバーの上限（2本）に達しているため記録できません。上限を超えて記録する場合は ignore-caps on を使ってください。";
+
+#[tokio::test]
+async fn logs_past_the_cap_are_rejected_in_the_guild() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    test.db.guild("10").set_type_cap(1, 2).await.unwrap();
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let logging = logging_service(&database, Arc::new(SystemClock::new(Tz::UTC)));
+    let frontend = RecordingFrontend::default();
+
+    for _ in 0..3 {
+        record_cigarette(&frontend, &logging, "1", "alice", Some("10"), 1, None)
+            .await
+            .unwrap();
+    }
+    // Other types and guilds without caps are not limited.
+    record_cigarette(&frontend, &logging, "1", "alice", Some("10"), 2, None)
+        .await
+        .unwrap();
+    record_cigarette(&frontend, &logging, "1", "alice", Some("20"), 1, None)
+        .await
+        .unwrap();
+
+    let calls = frontend.calls();
+    // The second press edits the first confirmation.
+    assert_eq!(calls.len(), 6);
+    assert_eq!(
+        calls[3],
+        Recorded::Respond(Reply::error(REJECTION).ephemeral())
+    );
+    assert!(calls
+        .iter()
+        .enumerate()
+        .all(|(i, call)| i == 3 || !matches!(call, Recorded::Respond(reply) if reply.ephemeral)));
+    assert_eq!(
+        test.db
+            .get_daily_type_total("1", 1, test.today(), Tz::UTC)
+            .await
+            .unwrap(),
+        3
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn users_can_choose_to_ignore_caps() {
+    let test = setup().await;
+    test.db.guild("10").set_type_cap(1, 0).await.unwrap();
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    create_user(&test, "1").await;
+    let logging = logging_service(&database, Arc::new(SystemClock::new(Tz::UTC)));
+    let frontend = RecordingFrontend::default();
+
+    update_ignore_type_caps(&frontend, &database, "1", "alice", true)
+        .await
+        .unwrap();
+    record_cigarette(&frontend, &logging, "1", "alice", Some("10"), 1, None)
+        .await
+        .unwrap();
+    update_ignore_type_caps(&frontend, &database, "1", "alice", false)
+        .await
+        .unwrap();
+    record_cigarette(&frontend, &logging, "1", "alice", Some("10"), 1, None)
+        .await
+        .unwrap();
+
+    let calls = frontend.calls();
+    assert_eq!(
+        calls[0],
+        Recorded::SendReply(
+            Reply::new("サーバーで設定された1日の上限を超えても記録します。")
+                .titled(Title::Settings)
+        )
+    );
+    assert!(matches!(&calls[1], Recorded::Respond(reply) if !reply.ephemeral));
+    assert!(matches!(&calls[3], Recorded::Respond(reply) if reply.ephemeral));
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn cap_commands_set_list_and_remove_caps() {
+    let test = setup().await;
+    let database = Mutex::new(Database::new(test.pool.clone()));
+    let frontend = RecordingFrontend::default();
+
+    update_type_cap(&frontend, &database, "10", "iqos", Some(-1))
+        .await
+        .unwrap();
+    update_type_cap(&frontend, &database, "10", "cigar", Some(1))
+        .await
+        .unwrap();
+    update_type_cap(&frontend, &database, "10", "iqos", Some(2))
+        .await
+        .unwrap();
+    list_type_caps(&frontend, &database, "10").await.unwrap();
+    list_type_caps(&frontend, &database, "20").await.unwrap();
+    assert_eq!(
+        test.db.guild("10").get_type_caps().await.unwrap(),
+        [TypeCap {
+            smoking_type_id: 2,
+            daily_cap: 2
+        }]
+    );
+    update_type_cap(&frontend, &database, "10", "iqos", None)
+        .await
+        .unwrap();
+    update_type_cap(&frontend, &database, "10", "iqos", None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        [
+            Recorded::SendReply(Reply::error("上限は0以上の本数で指定してください。")),
+            Recorded::SendReply(Reply::error(
+                "不明な種類です: cigar（使用できる種類: traditional, iqos, ploom, glo, other）"
+            )),
+            Recorded::SendReply(
+                Reply::new("IQOSの1日の上限を2本にしました。").titled(Title::Settings)
+            ),
+            Recorded::SendReply(Reply::new("1日の上限:\n- IQOS: 2本").titled(Title::Settings)),
+            Recorded::SendReply(
+                Reply::new("1日の上限は設定されていません。").titled(Title::Settings)
+            ),
+            Recorded::SendReply(Reply::new("IQOSの上限を解除しました。").titled(Title::Settings)),
+            Recorded::SendReply(
+                Reply::new("IQOSに上限は設定されていません。").titled(Title::Settings)
+            ),
+        ]
+    );
+
+    test.teardown().await;
+}
@@ -0,0 +1,154 @@
+//! Tests for categories of habits and their daily goals.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono::Utc;
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::MockClock,
+    commands::{
+        goals::update_category_goal,
+        types::{add_category, assign_category, list_categories},
+    },
+    database::{Category, CategoryProgress, Database},
+    embed::{category_goal_embed, GoalStatus, Title},
+    format::Locale,
+    frontend::Reply,
+};
+use common::{create_user, logging_service, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+#[tokio::test]
+async fn presses_show_the_goal_of_the_types_category() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::new(Tz::UTC, Utc::now()));
+    let logging = logging_service(&database, clock);
+    let frontend = RecordingFrontend::default();
+    create_user(&test, "1").await;
+
+    add_category(&frontend, &database, "coffee", "杯", Some("☕"))
+        .await
+        .unwrap();
+    add_category(&frontend, &database, "coffee", "本", None)
+        .await
+        .unwrap();
+    assign_category(&frontend, &database, "iqos", Some("coffee"))
+        .await
+        .unwrap();
+    update_category_goal(&frontend, &database, "1", "alice", "coffee", Some(3))
+        .await
+        .unwrap();
+    update_category_goal(&frontend, &database, "1", "alice", "tea", Some(3))
+        .await
+        .unwrap();
+    list_categories(&frontend, &database).await.unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        [
+            Recorded::SendReply(Reply::new(
+                "カテゴリ「☕ coffee」（単位: 杯）を追加しました。type category assign で種類を追加してください。"
+            )),
+            Recorded::SendReply(Reply::error("カテゴリ「coffee」はすでにあります。")),
+            Recorded::SendReply(Reply::new("IQOSをカテゴリ「☕ coffee」に追加しました。")),
+            Recorded::SendReply(
+                Reply::new("☕ coffeeの1日の目標を3杯に設定しました。").titled(Title::Settings)
+            ),
+            Recorded::SendReply(Reply::error(
+                "不明なカテゴリです: tea（使用できるカテゴリ: coffee）"
+            )),
+            Recorded::SendReply(Reply::new("カテゴリ\n- ☕ coffee（単位: 杯）: IQOS")),
+        ]
+    );
+
+    let logged = logging
+        .log_for_user("1", "alice", None, 2, 2, false, None)
+        .await
+        .unwrap();
+    let coffee = Category {
+        id: 1,
+        name: "coffee".to_string(),
+        unit: "杯".to_string(),
+        emoji: Some("☕".to_string()),
+    };
+    assert_eq!(
+        logged.category_progress,
+        Some(CategoryProgress {
+            category: coffee,
+            total: 2,
+            daily_goal: 3,
+        })
+    );
+    // Types outside the category do not count towards its goal.
+    let logged = logging
+        .log_for_user("1", "alice", None, 1, 1, false, None)
+        .await
+        .unwrap();
+    assert_eq!(logged.category_progress, None);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn uncategorized_types_and_cleared_goals_show_no_category_goal() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::new(Tz::UTC, Utc::now()));
+    let logging = logging_service(&database, clock);
+    let frontend = RecordingFrontend::default();
+    create_user(&test, "1").await;
+
+    add_category(&frontend, &database, "coffee", "杯", None)
+        .await
+        .unwrap();
+    assign_category(&frontend, &database, "iqos", Some("coffee"))
+        .await
+        .unwrap();
+    update_category_goal(&frontend, &database, "1", "alice", "coffee", Some(3))
+        .await
+        .unwrap();
+    update_category_goal(&frontend, &database, "1", "alice", "coffee", None)
+        .await
+        .unwrap();
+    let logged = logging
+        .log_for_user("1", "alice", None, 2, 1, false, None)
+        .await
+        .unwrap();
+    assert_eq!(logged.category_progress, None);
+
+    update_category_goal(&frontend, &database, "1", "alice", "coffee", Some(3))
+        .await
+        .unwrap();
+    assign_category(&frontend, &database, "iqos", None)
+        .await
+        .unwrap();
+    let logged = logging
+        .log_for_user("1", "alice", None, 2, 1, false, None)
+        .await
+        .unwrap();
+    assert_eq!(logged.category_progress, None);
+
+    test.teardown().await;
+}
+
+#[test]
+fn category_goal_embeds_count_in_the_categorys_unit() {
+    let progress = CategoryProgress {
+        category: Category {
+            id: 1,
+            name: "coffee".to_string(),
+            unit: "杯".to_string(),
+            emoji: Some("☕".to_string()),
+        },
+        total: 4,
+        daily_goal: 3,
+    };
+
+    let embed = category_goal_embed(&progress, Locale::Japanese);
+    assert_eq!(embed.description, "☕ coffee ▰▰▰▰▰▰▰▰▰▰ 4杯 / 3杯");
+    assert_eq!(embed.color, GoalStatus::Exceeded.color());
+    assert_eq!(embed.footer.as_deref(), Some("目標を1杯超えています"));
+}
@@ -0,0 +1,153 @@
+//! Tests for the weekly guild challenge.
+
+mod common;
+
+use chrono::NaiveDate;
+use cigarette_counter::{
+    challenge::{celebration, progress_embed, reduction_percent, target_total},
+    database::{ChallengeWeek, GuildChallenge},
+    embed::GoalStatus,
+};
+use common::setup;
+
+fn date(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+}
+
+fn week(baseline: i64) -> ChallengeWeek {
+    ChallengeWeek {
+        week_start: date(6, 3),
+        channel_id: "500".to_string(),
+        message_id: "900".to_string(),
+        baseline,
+        target_percent: 20,
+        updated_on: date(6, 3),
+    }
+}
+
+#[test]
+fn targets_are_a_reduction_of_the_week_before() {
+    assert_eq!(target_total(50, 20), 40);
+    assert_eq!(target_total(7, 20), 5);
+    assert_eq!(target_total(0, 20), 0);
+    assert_eq!(reduction_percent(50, 35), Some(30));
+    assert_eq!(reduction_percent(50, 60), Some(-20));
+    assert_eq!(reduction_percent(0, 0), None);
+}
+
+#[test]
+fn progress_shows_the_week_against_its_target() {
+    let embed = progress_embed(&week(50), 35, false);
+
+    assert_eq!(
+        embed.description,
+        "2024/06/03〜2024/06/09のチャレンジ\n先週の合計: 50本\n目標: 40本以下（20%削減）\n▰▰▰▰▰▰▰▰▰▱ 35本 / 40本"
+    );
+    assert_eq!(embed.footer.as_deref(), Some("残り 5本"));
+    assert_eq!(embed.color, GoalStatus::Close.color());
+
+    let finished = progress_embed(&week(50), 45, true);
+    assert!(finished
+        .description
+        .starts_with("2024/06/03〜2024/06/09のチャレンジ（終了）\n"));
+    assert_eq!(finished.footer.as_deref(), Some("目標まであと5本でした"));
+    assert_eq!(finished.color, GoalStatus::Exceeded.color());
+}
+
+#[test]
+fn celebrations_report_the_reduction() {
+    assert_eq!(
+        celebration(&week(50), 35),
+        "🎉 2024/06/03からの週のチャレンジを達成しました！ サーバー全体の合計は35本で、前の週の50本から30%減りました。"
+    );
+    assert_eq!(
+        celebration(&week(0), 0),
+        "🎉 2024/06/03からの週のチャレンジを達成しました！ サーバー全体で記録はありませんでした。"
+    );
+}
+
+#[tokio::test]
+async fn challenge_weeks_are_recorded_and_settled_per_guild() {
+    let test = setup().await;
+    let guild = test.db.guild("10");
+    let now = chrono::Utc::now();
+
+    assert_eq!(guild.get_challenge().await.unwrap(), None);
+    guild.set_challenge("500", 10).await.unwrap();
+    guild.set_challenge("500", 20).await.unwrap();
+    test.db.guild("20").set_challenge("600", 5).await.unwrap();
+    assert_eq!(
+        test.db.get_guild_challenges().await.unwrap(),
+        vec![
+            GuildChallenge {
+                guild_id: "10".to_string(),
+                channel_id: "500".to_string(),
+                target_percent: 20,
+            },
+            GuildChallenge {
+                guild_id: "20".to_string(),
+                channel_id: "600".to_string(),
+                target_percent: 5,
+            },
+        ]
+    );
+
+    guild.record_challenge_week(&week(50)).await.unwrap();
+    guild
+        .record_challenge_week(&ChallengeWeek {
+            week_start: date(6, 10),
+            baseline: 45,
+            ..week(50)
+        })
+        .await
+        .unwrap();
+    guild
+        .mark_challenge_week_updated(date(6, 3), date(6, 5))
+        .await
+        .unwrap();
+    assert_eq!(
+        guild.get_challenge_week(date(6, 3)).await.unwrap(),
+        Some(ChallengeWeek {
+            updated_on: date(6, 5),
+            ..week(50)
+        })
+    );
+    assert_eq!(
+        test.db
+            .guild("20")
+            .get_challenge_week(date(6, 3))
+            .await
+            .unwrap(),
+        None
+    );
+
+    let unsettled = guild
+        .get_unsettled_challenge_weeks_before(date(6, 17))
+        .await
+        .unwrap();
+    assert_eq!(
+        unsettled
+            .iter()
+            .map(|week| week.week_start)
+            .collect::<Vec<_>>(),
+        vec![date(6, 3), date(6, 10)]
+    );
+    guild
+        .settle_challenge_week(date(6, 3), true, now)
+        .await
+        .unwrap();
+    assert_eq!(
+        guild
+            .get_unsettled_challenge_weeks_before(date(6, 17))
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    assert!(guild.remove_challenge().await.unwrap());
+    assert!(!guild.remove_challenge().await.unwrap());
+    assert_eq!(test.db.get_guild_challenges().await.unwrap().len(), 1);
+
+    test.teardown().await;
+}
@@ -0,0 +1,148 @@
+//! Tests for the channel topic counter.
+
+mod common;
+
+use chrono::{Duration, TimeZone, Utc};
+use cigarette_counter::{
+    channel_counter::{counter_text, should_update, CounterMetric, CounterTarget},
+    database::ChannelCounter,
+};
+use common::{create_user, log_at, setup};
+use poise::serenity_prelude::ChannelType;
+
+#[test]
+fn counters_render_the_guilds_value() {
+    assert_eq!(
+        counter_text(CounterMetric::DailyTotal, Some(12)),
+        "今日の合計: 12本"
+    );
+    assert_eq!(
+        counter_text(CounterMetric::DaysSince, Some(3)),
+        "最後の1本から3日"
+    );
+    assert_eq!(
+        counter_text(CounterMetric::DaysSince, None),
+        "まだ記録はありません"
+    );
+    assert_eq!(CounterMetric::from_name("weekly-total"), None);
+    assert_eq!(
+        CounterTarget::for_channel(ChannelType::Voice),
+        Some(CounterTarget::Name)
+    );
+    assert_eq!(CounterTarget::for_channel(ChannelType::Forum), None);
+}
+
+#[test]
+fn channels_are_edited_only_when_the_text_changed_and_the_rate_limit_allows() {
+    let now = Utc.with_ymd_and_hms(2024, 6, 10, 12, 0, 0).unwrap();
+    let mut counter = ChannelCounter {
+        guild_id: "10".to_string(),
+        channel_id: "100".to_string(),
+        metric: "daily-total".to_string(),
+        target: "topic".to_string(),
+        last_text: None,
+        updated_at: None,
+    };
+    assert!(should_update(&counter, "今日の合計: 1本", now));
+
+    counter.last_text = Some("今日の合計: 1本".to_string());
+    counter.updated_at = Some(now - Duration::minutes(3));
+    assert!(!should_update(&counter, "今日の合計: 1本", now));
+    assert!(!should_update(&counter, "今日の合計: 2本", now));
+
+    counter.updated_at = Some(now - Duration::minutes(10));
+    assert!(!should_update(&counter, "今日の合計: 1本", now));
+    assert!(should_update(&counter, "今日の合計: 2本", now));
+}
+
+#[tokio::test]
+async fn a_guild_shows_one_counter() {
+    let test = setup().await;
+    let guild = test.db.guild("10");
+    let now = Utc.with_ymd_and_hms(2024, 6, 10, 12, 0, 0).unwrap();
+
+    guild
+        .set_channel_counter("100", "daily-total", "topic")
+        .await
+        .unwrap();
+    guild
+        .record_channel_counter_update("今日の合計: 1本", now)
+        .await
+        .unwrap();
+    test.db
+        .guild("20")
+        .set_channel_counter("200", "days-since", "name")
+        .await
+        .unwrap();
+    assert_eq!(
+        guild.get_channel_counter().await.unwrap(),
+        Some(ChannelCounter {
+            guild_id: "10".to_string(),
+            channel_id: "100".to_string(),
+            metric: "daily-total".to_string(),
+            target: "topic".to_string(),
+            last_text: Some("今日の合計: 1本".to_string()),
+            updated_at: Some(now),
+        })
+    );
+
+    // Moving the counter forgets the last edit, so the new channel is updated right away.
+    guild
+        .set_channel_counter("101", "days-since", "name")
+        .await
+        .unwrap();
+    let counter = guild.get_channel_counter().await.unwrap().unwrap();
+    assert_eq!(
+        (counter.channel_id.as_str(), counter.last_text),
+        ("101", None)
+    );
+    assert_eq!(test.db.get_channel_counters().await.unwrap().len(), 2);
+
+    assert!(guild.remove_channel_counter().await.unwrap());
+    assert!(!guild.remove_channel_counter().await.unwrap());
+    assert_eq!(guild.get_channel_counter().await.unwrap(), None);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn days_since_counts_from_the_guilds_latest_log() {
+    let test = setup().await;
+    let guild = test.db.guild("10");
+
+    for user_id in ["1", "2"] {
+        create_user(&test, user_id).await;
+    }
+    assert_eq!(guild.get_last_smoked_at().await.unwrap(), None);
+
+    log_at(
+        &test,
+        "1",
+        Utc.with_ymd_and_hms(2024, 6, 5, 12, 0, 0).unwrap(),
+    )
+    .await;
+    log_at(
+        &test,
+        "2",
+        Utc.with_ymd_and_hms(2024, 6, 7, 12, 0, 0).unwrap(),
+    )
+    .await;
+    sqlx::query("UPDATE smoking_logs SET guild_id = '10'")
+        .execute(&test.pool)
+        .await
+        .unwrap();
+    // Logs of other guilds do not count.
+    log_at(
+        &test,
+        "1",
+        Utc.with_ymd_and_hms(2024, 6, 9, 12, 0, 0).unwrap(),
+    )
+    .await;
+
+    assert_eq!(
+        guild.get_last_smoked_at().await.unwrap(),
+        Some(Utc.with_ymd_and_hms(2024, 6, 7, 12, 0, 0).unwrap())
+    );
+
+    test.teardown().await;
+}
@@ -0,0 +1,76 @@
+//! Tests for the database circuit breaker.
+
+use std::time::Duration;
+
+use cigarette_counter::{
+    circuit_breaker::{is_outage, CircuitBreaker, CircuitState},
+    service::ServiceError,
+    Error,
+};
+
+fn outage() -> Result<(), Error> {
+    Err(sqlx::Error::PoolTimedOut.into())
+}
+
+#[test]
+fn the_circuit_opens_after_consecutive_outages() {
+    let breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+
+    breaker.record(&outage());
+    breaker.record(&outage());
+    // A success in between resets the count.
+    breaker.record(&Ok(()));
+    breaker.record(&outage());
+    breaker.record(&outage());
+    assert_eq!(breaker.state(), CircuitState::Closed { failures: 2 });
+    assert!(breaker.allow().is_ok());
+
+    breaker.record(&outage());
+
+    assert!(matches!(breaker.state(), CircuitState::Open { .. }));
+    assert!(breaker.allow().is_err());
+}
+
+#[test]
+fn a_half_open_circuit_lets_one_probe_through() {
+    let breaker = CircuitBreaker::new(1, Duration::ZERO);
+    breaker.record(&outage());
+
+    assert!(breaker.allow().is_ok());
+    assert!(matches!(breaker.state(), CircuitState::HalfOpen { .. }));
+
+    // A failed probe opens the circuit again.
+    breaker.record(&outage());
+    assert!(matches!(breaker.state(), CircuitState::Open { .. }));
+
+    assert!(breaker.allow().is_ok());
+    breaker.record(&Ok(()));
+    assert_eq!(breaker.state(), CircuitState::Closed { failures: 0 });
+}
+
+#[test]
+fn only_requests_probed_during_the_cooldown_are_rejected() {
+    let breaker = CircuitBreaker::new(1, Duration::from_millis(50));
+    breaker.record(&outage());
+    assert!(breaker.allow().is_err());
+
+    std::thread::sleep(Duration::from_millis(60));
+
+    assert!(breaker.allow().is_ok());
+    // The probe has not reported back yet.
+    assert!(breaker.allow().is_err());
+}
+
+#[test]
+fn only_connection_errors_are_outages() {
+    let database_down: Error = sqlx::Error::PoolTimedOut.into();
+    let wrapped = ServiceError::Database(sqlx::Error::PoolClosed);
+    let answered: Error = sqlx::Error::RowNotFound.into();
+    let unrelated: Error = "boom".into();
+
+    assert!(is_outage(database_down.as_ref()));
+    assert!(is_outage(&wrapped));
+    assert!(!is_outage(answered.as_ref()));
+    assert!(!is_outage(unrelated.as_ref()));
+    assert!(!is_outage(&ServiceError::InvalidQuantity));
+}
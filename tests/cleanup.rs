@@ -0,0 +1,201 @@
+//! Tests for duplicate log detection and cleanup.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono::{Duration, TimeZone, Utc};
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::MockClock,
+    commands::cleanup::{apply_cleanup, duplicate_window, suggest_cleanup, CleanupId},
+    database::{Database, DuplicateResolution},
+    frontend::Reply,
+    service::StatsService,
+};
+use common::{create_user, log_at, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+fn cleanup_id(resolution: DuplicateResolution) -> CleanupId {
+    CleanupId {
+        resolution,
+        target_id: "1".to_string(),
+        actor_id: "1".to_string(),
+    }
+}
+
+async fn total_quantity(test: &common::TestDatabase, discord_id: &str) -> i64 {
+    sqlx::query_scalar("SELECT COALESCE(SUM(quantity), 0) FROM smoking_logs WHERE discord_id = $1")
+        .bind(discord_id)
+        .fetch_one(&test.pool)
+        .await
+        .unwrap()
+}
+
+#[test]
+fn cleanup_ids_round_trip() {
+    for resolution in [DuplicateResolution::Merge, DuplicateResolution::Delete] {
+        let cleanup = CleanupId {
+            resolution,
+            target_id: "1".to_string(),
+            actor_id: "2".to_string(),
+        };
+        assert_eq!(CleanupId::parse(&cleanup.custom_id()), Some(cleanup));
+    }
+
+    for custom_id in [
+        "cleanup:merge:1",
+        "cleanup:undo:1:2",
+        "cleanup:delete:x:2",
+        "cleanup:delete:1:2:3",
+        "merge:1:2",
+    ] {
+        assert_eq!(CleanupId::parse(custom_id), None, "{}", custom_id);
+    }
+}
+
+#[tokio::test]
+async fn runs_of_close_identical_logs_are_duplicates_of_their_first_log() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let start = Utc.with_ymd_and_hms(2024, 5, 3, 12, 0, 0).unwrap();
+    // A run of three presses, each within the window of the previous one.
+    for seconds in [0, 3, 6] {
+        log_at(&test, "1", start + Duration::seconds(seconds)).await;
+    }
+    // Far enough apart to be separate cigarettes.
+    log_at(&test, "1", start + Duration::minutes(10)).await;
+    test.db.log_smoking("1", None, 2, 1).await.unwrap();
+
+    let duplicates = test
+        .db
+        .find_duplicate_logs(Some("1"), duplicate_window())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        duplicates
+            .iter()
+            .map(|duplicate| (duplicate.id, duplicate.original_id))
+            .collect::<Vec<_>>(),
+        vec![(2, 1), (3, 1)]
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn merging_keeps_the_total_and_deleting_drops_the_duplicates() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let start = Utc.with_ymd_and_hms(2024, 5, 3, 12, 0, 0).unwrap();
+    for seconds in [0, 2] {
+        log_at(&test, "1", start + Duration::seconds(seconds)).await;
+    }
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 4, 12, 0).unwrap());
+    let stats = StatsService::new(database.clone(), clock);
+    let frontend = RecordingFrontend::default();
+
+    suggest_cleanup(&frontend, &database, Tz::UTC, "1", "1")
+        .await
+        .unwrap();
+    apply_cleanup(
+        &frontend,
+        &database,
+        &stats,
+        "1",
+        &cleanup_id(DuplicateResolution::Merge),
+    )
+    .await
+    .unwrap();
+    assert_eq!(total_quantity(&test, "1").await, 2);
+
+    for seconds in [60, 61] {
+        log_at(&test, "1", start + Duration::seconds(seconds)).await;
+    }
+    apply_cleanup(
+        &frontend,
+        &database,
+        &stats,
+        "1",
+        &cleanup_id(DuplicateResolution::Delete),
+    )
+    .await
+    .unwrap();
+    assert_eq!(total_quantity(&test, "1").await, 3);
+    apply_cleanup(
+        &frontend,
+        &database,
+        &stats,
+        "1",
+        &cleanup_id(DuplicateResolution::Delete),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        vec![
+            Recorded::SendReply(
+                Reply::new(
+                    "5秒以内に同じ内容で記録された重複らしき記録が1件あります。\n\
+                     - #2（#1の重複）2024/05/03 12:00:02 紙タバコ 1本\n\
+                     「統合」は本数を元の記録に加えて重複を消し、「削除」は重複をそのまま消します。"
+                )
+                .button("cleanup:merge:1:1", "統合")
+                .button("cleanup:delete:1:1", "削除")
+            ),
+            Recorded::Respond(
+                Reply::new("重複した記録1件を元の記録に統合しました。").ephemeral()
+            ),
+            Recorded::Respond(Reply::new("重複した記録1件を削除しました。").ephemeral()),
+            Recorded::Respond(Reply::new("重複した記録はもうありません。").ephemeral()),
+        ]
+    );
+    let actions: Vec<String> = test
+        .db
+        .get_admin_audit_entries(10)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.action)
+        .collect();
+    assert_eq!(actions, vec!["delete_duplicates", "merge_duplicates"]);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn only_the_person_who_ran_the_command_can_clean_up() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let start = Utc.with_ymd_and_hms(2024, 5, 3, 12, 0, 0).unwrap();
+    for seconds in [0, 1] {
+        log_at(&test, "1", start + Duration::seconds(seconds)).await;
+    }
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 4, 12, 0).unwrap());
+    let stats = StatsService::new(database.clone(), clock);
+    let frontend = RecordingFrontend::default();
+
+    apply_cleanup(
+        &frontend,
+        &database,
+        &stats,
+        "2",
+        &cleanup_id(DuplicateResolution::Delete),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        vec![Recorded::Respond(
+            Reply::error("このボタンはコマンドを実行した人だけが使えます。").ephemeral()
+        )]
+    );
+    assert_eq!(total_quantity(&test, "1").await, 2);
+
+    test.teardown().await;
+}
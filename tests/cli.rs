@@ -0,0 +1,83 @@
+//! Tests for the maintenance subcommands of the binary.
+
+mod common;
+
+use std::path::PathBuf;
+
+use cigarette_counter::{
+    cli::{migrate, Cli, Task},
+    demo_seed::{SeedProfile, DEMO_GUILD_ID},
+};
+use clap::Parser;
+use common::setup;
+
+#[test]
+fn subcommands_are_parsed() {
+    assert_eq!(
+        Cli::try_parse_from(["cigarette-counter"]).unwrap().task,
+        None
+    );
+    assert_eq!(
+        Cli::try_parse_from(["cigarette-counter", "migrate"])
+            .unwrap()
+            .task,
+        Some(Task::Migrate)
+    );
+    assert_eq!(
+        Cli::try_parse_from(["cigarette-counter", "export-guild", "10", "-o", "out.json"])
+            .unwrap()
+            .task,
+        Some(Task::ExportGuild {
+            guild_id: "10".to_string(),
+            output: Some(PathBuf::from("out.json")),
+        })
+    );
+    assert_eq!(
+        Cli::try_parse_from(["cigarette-counter", "recompute-aggregates", "1", "2"])
+            .unwrap()
+            .task,
+        Some(Task::RecomputeAggregates {
+            user_ids: vec!["1".to_string(), "2".to_string()],
+        })
+    );
+    assert_eq!(
+        Cli::try_parse_from(["cigarette-counter", "register-commands", "--guild", "10"])
+            .unwrap()
+            .task,
+        Some(Task::RegisterCommands { guild: Some(10) })
+    );
+    assert_eq!(
+        Cli::try_parse_from([
+            "cigarette-counter",
+            "seed-demo",
+            "--profile",
+            "load",
+            "--months",
+            "1"
+        ])
+        .unwrap()
+        .task,
+        Some(Task::SeedDemo {
+            profile: SeedProfile::Load,
+            users: None,
+            months: Some(1),
+            guild: DEMO_GUILD_ID.to_string(),
+            seed: None,
+        })
+    );
+
+    // Recomputing needs at least one user, and guild IDs are numeric.
+    assert!(Cli::try_parse_from(["cigarette-counter", "recompute-aggregates"]).is_err());
+    assert!(
+        Cli::try_parse_from(["cigarette-counter", "register-commands", "--guild", "x"]).is_err()
+    );
+}
+
+#[tokio::test]
+async fn migrating_an_up_to_date_database_applies_nothing() {
+    let test = setup().await;
+
+    assert_eq!(migrate(&test.pool).await.unwrap(), 0);
+
+    test.teardown().await;
+}
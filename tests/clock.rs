@@ -0,0 +1,75 @@
+//! Day-boundary behavior driven by a `MockClock`.
+
+mod common;
+
+use chrono::Duration;
+use chrono_tz::{America::New_York, Asia::Tokyo};
+use cigarette_counter::{
+    clock::{Clock, MockClock},
+    milestones::days_smoke_free,
+};
+use common::{create_user, set_smoked_at, setup};
+
+#[test]
+fn mock_clock_rejects_skipped_and_repeated_local_times() {
+    // 02:30 does not exist on the spring-forward day...
+    assert!(MockClock::at_local(New_York, 2024, 3, 10, 2, 30).is_none());
+    // ...and 01:30 happens twice on the fall-back day.
+    assert!(MockClock::at_local(New_York, 2024, 11, 3, 1, 30).is_none());
+    assert!(MockClock::at_local(New_York, 2024, 3, 10, 3, 30).is_some());
+}
+
+#[test]
+fn today_rolls_over_at_local_midnight() {
+    let clock = MockClock::at_local(Tokyo, 2025, 1, 10, 23, 59).unwrap();
+    assert_eq!(clock.today().to_string(), "2025-01-10");
+
+    clock.advance(Duration::minutes(1));
+    assert_eq!(clock.today().to_string(), "2025-01-11");
+}
+
+#[tokio::test]
+async fn smoke_free_days_count_local_midnights() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    test.db.log_smoking("1", None, 1, 1).await.unwrap();
+
+    let clock = MockClock::at_local(Tokyo, 2025, 1, 10, 23, 58).unwrap();
+    set_smoked_at(&test, "1", clock.now()).await;
+    assert_eq!(days_smoke_free(&test.db, &clock, "1").await.unwrap(), 0);
+
+    // Two minutes later it is already the next day.
+    clock.advance(Duration::minutes(2));
+    assert_eq!(days_smoke_free(&test.db, &clock, "1").await.unwrap(), 1);
+
+    clock.advance(Duration::hours(47));
+    assert_eq!(days_smoke_free(&test.db, &clock, "1").await.unwrap(), 2);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn smoke_free_days_follow_the_calendar_across_dst() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    test.db.log_smoking("1", None, 1, 1).await.unwrap();
+
+    // The spring-forward day has 23 hours; it still counts as one day.
+    let clock = MockClock::at_local(New_York, 2024, 3, 9, 23, 30).unwrap();
+    set_smoked_at(&test, "1", clock.now()).await;
+    clock.advance(Duration::hours(23));
+    assert_eq!(clock.today().to_string(), "2024-03-10");
+    assert_eq!(days_smoke_free(&test.db, &clock, "1").await.unwrap(), 1);
+
+    // The fall-back day has 25 hours; 24 hours later it is still the same day.
+    let clock = MockClock::at_local(New_York, 2024, 11, 3, 0, 30).unwrap();
+    set_smoked_at(&test, "1", clock.now()).await;
+    clock.advance(Duration::hours(24));
+    assert_eq!(clock.today().to_string(), "2024-11-03");
+    assert_eq!(days_smoke_free(&test.db, &clock, "1").await.unwrap(), 0);
+
+    clock.advance(Duration::hours(1));
+    assert_eq!(days_smoke_free(&test.db, &clock, "1").await.unwrap(), 1);
+
+    test.teardown().await;
+}
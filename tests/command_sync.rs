@@ -0,0 +1,152 @@
+//! Tests for re-registering the application commands.
+
+mod common;
+
+use cigarette_counter::{
+    command_sync::{CommandChanges, CommandSignature},
+    commands::{
+        admin::show_command_changes, application_commands, command_access, enabled_commands,
+        CommandAccess,
+    },
+    frontend::Reply,
+};
+use common::{Recorded, RecordingFrontend};
+use poise::serenity_prelude::{CommandOptionType, CreateCommand, CreateCommandOption, Permissions};
+
+fn signature(name: &str, description: &str, options: &[&str]) -> CommandSignature {
+    CommandSignature {
+        name: name.to_string(),
+        description: description.to_string(),
+        options: options.iter().map(|option| option.to_string()).collect(),
+    }
+}
+
+#[test]
+fn builders_are_read_like_registered_commands() {
+    let builder =
+        CreateCommand::new("stats")
+            .description("統計")
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::User,
+                "user",
+                "ユーザー",
+            ));
+
+    assert_eq!(
+        CommandSignature::of_builder(&builder),
+        signature("stats", "統計", &["user"])
+    );
+}
+
+#[test]
+fn changes_compare_commands_by_name() {
+    let registered = [
+        signature("stats", "統計", &[]),
+        signature("panel", "パネル", &[]),
+        signature("old", "古い", &[]),
+    ];
+    let desired = [
+        signature("stats", "統計", &["user"]),
+        signature("panel", "パネル", &[]),
+        signature("goal", "目標", &[]),
+    ];
+
+    assert_eq!(
+        CommandChanges::between(&registered, &desired),
+        CommandChanges {
+            added: vec!["goal".to_string()],
+            updated: vec!["stats".to_string()],
+            removed: vec!["old".to_string()],
+            unchanged: 1,
+        }
+    );
+    assert!(CommandChanges::between(&desired, &desired).is_empty());
+}
+
+#[tokio::test]
+async fn changes_are_reported_per_scope() {
+    let frontend = RecordingFrontend::default();
+    let changes = CommandChanges {
+        added: vec!["goal".to_string(), "stats".to_string()],
+        removed: vec!["old".to_string()],
+        ..CommandChanges::default()
+    };
+
+    show_command_changes(&frontend, &changes, None)
+        .await
+        .unwrap();
+    show_command_changes(
+        &frontend,
+        &CommandChanges {
+            unchanged: 3,
+            ..CommandChanges::default()
+        },
+        Some(10),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        vec![
+            Recorded::SendReply(Reply::new(
+                "グローバルコマンドを再登録しました。\n追加: goal, stats\n削除: old\nすべてのサーバーに反映されるまで時間がかかることがあります。"
+            )),
+            Recorded::SendReply(Reply::new(
+                "サーバー 10 のコマンドを再登録しました。\n変更はありません。\n変更なし: 3件"
+            )),
+        ]
+    );
+}
+
+#[test]
+fn access_comes_from_the_module_registry() {
+    assert_eq!(command_access("admin"), CommandAccess::OWNERS);
+    assert_eq!(
+        command_access("settings"),
+        CommandAccess::managers(Permissions::MANAGE_GUILD)
+    );
+    assert_eq!(
+        command_access("type"),
+        CommandAccess::managers(Permissions::MANAGE_GUILD)
+    );
+    assert_eq!(command_access("uptime"), CommandAccess::EVERYONE);
+    assert_eq!(command_access("missing"), CommandAccess::EVERYONE);
+}
+
+#[test]
+fn access_sets_permissions_and_contexts() {
+    let managers = serde_json::to_value(
+        CommandAccess::managers(Permissions::MANAGE_GUILD).apply(CreateCommand::new("caps")),
+    )
+    .unwrap();
+    let everyone =
+        serde_json::to_value(CommandAccess::EVERYONE.apply(CreateCommand::new("stats"))).unwrap();
+
+    assert_eq!(managers["default_member_permissions"], "32");
+    assert_eq!(managers["contexts"], serde_json::json!([0]));
+    assert_eq!(managers["integration_types"], serde_json::json!([0]));
+    // Leaving the permissions unset shows the command to everyone.
+    assert!(everyone.get("default_member_permissions").is_none());
+    assert_eq!(everyone["contexts"], serde_json::json!([0, 1]));
+}
+
+#[test]
+fn slash_commands_are_registered_with_their_modules_access() {
+    let commands: Vec<serde_json::Value> = application_commands(&enabled_commands(&[]))
+        .iter()
+        .map(|command| serde_json::to_value(command).unwrap())
+        .collect();
+    let find = |name: &str| commands.iter().find(|command| command["name"] == name);
+
+    let panel = find("panel").expect("panel is a slash command");
+    assert_eq!(panel["options"][0]["name"], "install");
+    assert_eq!(panel["options"][0]["options"][0]["name"], "types");
+    assert!(find("create_cigarette_ui").is_some());
+    // Owners register the commands; administrators see the command.
+    assert_eq!(
+        find("register").expect("register is a slash command")["default_member_permissions"],
+        "8"
+    );
+    assert!(find("admin").is_none());
+}
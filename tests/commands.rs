@@ -0,0 +1,644 @@
+//! Tests for command handlers using a recording frontend instead of Discord.
+
+mod common;
+
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::{Clock, MockClock, SystemClock},
+    commands::{
+        enabled_commands,
+        goals::update_daily_goal,
+        panel::{
+            button_label, install_panel, interaction_key, parse_panel_press, record_cigarette,
+            UnusableLabelError, MAX_LABEL_LENGTH,
+        },
+        smoke_break::update_smoke_break_prompt,
+        templates::update_message_template,
+    },
+    custom_id::CustomId,
+    database::{Database, SmokingType},
+    embed::{goal_embed, Title},
+    format::{format_date, Locale},
+    forum::forum_panel_id,
+    frontend::Reply,
+    templates::{Revision, TemplateKey},
+};
+use common::{create_user, logging_service, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::{futures::lock::Mutex, InteractionId, MessageId};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn button_press_responds_with_daily_summary() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(SystemClock::new(Tz::UTC));
+    let logging = logging_service(&database, clock.clone());
+    let frontend = RecordingFrontend::default();
+
+    record_cigarette(&frontend, &logging, "1", "alice", Some("10"), 1, None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        vec![Recorded::Respond(
+            Reply::new(format!(
+                "記録しました。\n本日（{}）の累計本数\n紙タバコ: 1本",
+                format_date(clock.today(), Locale::Japanese)
+            ))
+            .titled(Title::Confirmation)
+            .button("undo:1", "取り消し")
+        )]
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn quick_presses_edit_one_confirmation() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    // Logs are stored at the database's current time; only batching follows the clock.
+    let clock = Arc::new(MockClock::new(Tz::UTC, chrono::Utc::now()));
+    let logging = logging_service(&database, clock.clone());
+    let frontend = RecordingFrontend::default();
+    let summary = |count: i64| {
+        format!(
+            "記録しました。\n本日（{}）の累計本数\n紙タバコ: {}本",
+            format_date(clock.today(), Locale::Japanese),
+            count
+        )
+    };
+
+    for seconds in [0, 2, 3] {
+        clock.advance(chrono::Duration::seconds(seconds));
+        record_cigarette(&frontend, &logging, "1", "alice", Some("10"), 1, None)
+            .await
+            .unwrap();
+    }
+    // Too late to join the batch.
+    clock.advance(chrono::Duration::seconds(6));
+    record_cigarette(&frontend, &logging, "1", "alice", Some("10"), 1, None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        vec![
+            Recorded::Respond(
+                Reply::new(summary(1))
+                    .titled(Title::Confirmation)
+                    .button("undo:1", "取り消し")
+            ),
+            Recorded::EditMessage(
+                MessageId::new(1),
+                Reply::new(format!("紙タバコ x2\n{}", summary(2)))
+                    .titled(Title::Confirmation)
+                    .button("undo:1", "取り消し")
+            ),
+            Recorded::Acknowledge,
+            Recorded::EditMessage(
+                MessageId::new(1),
+                Reply::new(format!("紙タバコ x3\n{}", summary(3)))
+                    .titled(Title::Confirmation)
+                    .button("undo:1", "取り消し")
+            ),
+            Recorded::Acknowledge,
+            Recorded::Respond(
+                Reply::new(summary(4))
+                    .titled(Title::Confirmation)
+                    .button("undo:1", "取り消し")
+            ),
+        ]
+    );
+    assert_eq!(
+        test.db
+            .get_daily_type_total("1", 1, clock.today(), Tz::UTC)
+            .await
+            .unwrap(),
+        4
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn confirmations_show_progress_toward_the_daily_goal() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    test.db.set_daily_goal("1", Some(2)).await.unwrap();
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::new(Tz::UTC, chrono::Utc::now()));
+    let logging = logging_service(&database, clock.clone());
+    let frontend = RecordingFrontend::default();
+
+    for _ in 0..3 {
+        // Apart enough not to be batched.
+        clock.advance(chrono::Duration::seconds(30));
+        record_cigarette(&frontend, &logging, "1", "alice", Some("10"), 1, None)
+            .await
+            .unwrap();
+    }
+
+    let embeds: Vec<_> = frontend
+        .calls()
+        .into_iter()
+        .map(|call| match call {
+            Recorded::Respond(reply) => reply.embeds[0].clone(),
+            call => panic!("expected a confirmation, got {:?}", call),
+        })
+        .collect();
+    assert_eq!(
+        embeds,
+        vec![
+            goal_embed(1, 2, Locale::Japanese),
+            goal_embed(2, 2, Locale::Japanese),
+            goal_embed(3, 2, Locale::Japanese),
+        ]
+    );
+    assert_eq!(embeds[2].footer.as_deref(), Some("目標を1本超えています"));
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn quiet_users_get_one_confirmation_a_day() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    test.db.set_quiet_confirmations("1", true).await.unwrap();
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::new(Tz::UTC, chrono::Utc::now()));
+    let logging = logging_service(&database, clock.clone());
+    let frontend = RecordingFrontend::default();
+
+    for seconds in [0, 10, 20] {
+        clock.advance(chrono::Duration::seconds(seconds));
+        record_cigarette(&frontend, &logging, "1", "alice", Some("10"), 1, None)
+            .await
+            .unwrap();
+    }
+
+    let calls = frontend.calls();
+    assert_eq!(
+        calls[0],
+        Recorded::Respond(
+            Reply::new(format!(
+                "記録しました。\n本日（{}）の累計本数\n紙タバコ: 1本",
+                format_date(clock.today(), Locale::Japanese)
+            ))
+            .titled(Title::Confirmation)
+            .button("undo:1", "取り消し")
+        )
+    );
+    for (call, total) in [(&calls[1], 2), (&calls[3], 3)] {
+        let Recorded::EditMessage(message_id, reply) = call else {
+            panic!("expected an edit, got {:?}", call);
+        };
+        assert_eq!(*message_id, MessageId::new(1));
+        assert!(reply
+            .content
+            .starts_with(&format!("今日: {}本, 最終 ", total)));
+    }
+    assert_eq!(calls[2], Recorded::Acknowledge);
+
+    // The next day starts with a new confirmation.
+    clock.advance(chrono::Duration::days(1));
+    record_cigarette(&frontend, &logging, "1", "alice", Some("10"), 1, None)
+        .await
+        .unwrap();
+    assert!(matches!(frontend.calls()[5], Recorded::Respond(_)));
+    assert_eq!(
+        test.db
+            .get_daily_confirmation("1", clock.today())
+            .await
+            .unwrap(),
+        Some("6".to_string())
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn redelivered_button_press_is_logged_once() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(SystemClock::new(Tz::UTC));
+    let logging = logging_service(&database, clock.clone());
+    let frontend = RecordingFrontend::default();
+    let key = interaction_key(InteractionId::new(42));
+
+    for _ in 0..2 {
+        record_cigarette(&frontend, &logging, "1", "alice", None, 1, Some(&key))
+            .await
+            .unwrap();
+    }
+
+    let calls = frontend.calls();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0], calls[1]);
+    assert_eq!(
+        calls[1],
+        Recorded::Respond(
+            Reply::new(format!(
+                "記録しました。\n本日（{}）の累計本数\n紙タバコ: 1本",
+                format_date(clock.today(), Locale::Japanese)
+            ))
+            .titled(Title::Confirmation)
+            .button("undo:1", "取り消し")
+        )
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn button_press_formats_for_the_user_locale() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(SystemClock::new(Tz::UTC));
+    let logging = logging_service(&database, clock.clone());
+    let frontend = RecordingFrontend::with_locale(Locale::EnglishUs);
+
+    record_cigarette(&frontend, &logging, "1", "alice", None, 1, None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        [Recorded::Respond(
+            Reply::new(format!(
+                "記録しました。\n本日（{}）の累計本数\n紙タバコ: 1本",
+                format_date(clock.today(), Locale::EnglishUs)
+            ))
+            .titled(Title::Confirmation)
+            .button("undo:1", "取り消し")
+        )]
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn button_press_with_unknown_type_does_not_respond() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let frontend = RecordingFrontend::default();
+
+    let logging = logging_service(&database, Arc::new(SystemClock::new(Tz::UTC)));
+
+    assert!(
+        record_cigarette(&frontend, &logging, "1", "alice", None, -1, None)
+            .await
+            .is_err()
+    );
+    assert!(frontend.calls().is_empty());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn daily_goal_is_confirmed() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let frontend = RecordingFrontend::default();
+
+    update_daily_goal(&frontend, &database, "1", "alice", Some(5))
+        .await
+        .unwrap();
+    update_daily_goal(&frontend, &database, "1", "alice", None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        [
+            Recorded::SendReply(
+                Reply::new("1日の目標を5本に設定しました。").titled(Title::Settings)
+            ),
+            Recorded::SendReply(Reply::new("1日の目標を解除しました。").titled(Title::Settings)),
+        ]
+    );
+    assert_eq!(test.db.get_daily_goal("1").await.unwrap(), None);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn smoke_break_prompt_is_toggled() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let frontend = RecordingFrontend::default();
+
+    update_smoke_break_prompt(&frontend, &database, "1", "alice", true)
+        .await
+        .unwrap();
+    assert!(test.db.smoke_break_prompt_enabled("1").await.unwrap());
+
+    update_smoke_break_prompt(&frontend, &database, "1", "alice", false)
+        .await
+        .unwrap();
+    assert!(!test.db.smoke_break_prompt_enabled("1").await.unwrap());
+
+    assert_eq!(
+        frontend.calls(),
+        [
+            Recorded::SendReply(
+                Reply::new("喫煙所に入室したときにDMでパネルを送信します。")
+                    .titled(Title::Settings)
+            ),
+            Recorded::SendReply(
+                Reply::new("喫煙所のパネル送信を停止しました。").titled(Title::Settings)
+            ),
+        ]
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn guild_template_overrides_the_confirmation() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(SystemClock::new(Tz::UTC));
+    let logging = logging_service(&database, clock.clone());
+    let frontend = RecordingFrontend::default();
+
+    update_message_template(
+        &frontend,
+        &database,
+        "10",
+        TemplateKey::Confirmation,
+        Some("{user}さん、今日{total}目です"),
+        None,
+    )
+    .await
+    .unwrap();
+    update_message_template(
+        &frontend,
+        &database,
+        "10",
+        TemplateKey::Confirmation,
+        Some("{role}"),
+        None,
+    )
+    .await
+    .unwrap();
+    record_cigarette(&frontend, &logging, "1", "alice", Some("10"), 1, None)
+        .await
+        .unwrap();
+    record_cigarette(&frontend, &logging, "1", "alice", Some("20"), 1, None)
+        .await
+        .unwrap();
+    update_message_template(
+        &frontend,
+        &database,
+        "10",
+        TemplateKey::Confirmation,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        [
+            Recorded::SendReply(Reply::new("テンプレート「confirmation」を設定しました（v1）。").titled(Title::Settings)),
+            Recorded::SendReply(Reply::error(
+                "テンプレートが不正です: Unknown placeholder: {role}\n使用できる変数: {user}, {date}, {summary}, {total}"
+            )),
+            Recorded::Respond(Reply::new("aliceさん、今日1本目です").titled(Title::Confirmation).button("undo:1", "取り消し")),
+            Recorded::Respond(Reply::new(format!(
+                "記録しました。\n本日（{}）の累計本数\n紙タバコ: 2本",
+                format_date(clock.today(), Locale::Japanese)
+            )).titled(Title::Confirmation).button("undo:1", "取り消し")),
+            Recorded::SendReply(Reply::new("テンプレート「confirmation」を既定に戻しました。").titled(Title::Settings)),
+        ]
+    );
+    assert_eq!(
+        test.db
+            .guild("10")
+            .get_message_template("confirmation")
+            .await
+            .unwrap(),
+        None
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn stale_template_updates_are_refused() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let frontend = RecordingFrontend::default();
+    let update = |text: Option<&'static str>, revision: Option<&str>| {
+        let revision = revision.map(|revision| revision.parse::<Revision>().unwrap());
+        update_message_template(
+            &frontend,
+            &database,
+            "10",
+            TemplateKey::Milestone,
+            text,
+            revision,
+        )
+    };
+
+    // Two admins both start from the default (v0); the second one loses.
+    update(Some("{user} A"), Some("v0")).await.unwrap();
+    update(Some("{user} B"), Some("v0")).await.unwrap();
+    update(Some("{user} C"), Some("v1")).await.unwrap();
+    update(None, Some("v1")).await.unwrap();
+    update(None, Some("v2")).await.unwrap();
+    update(None, Some("v0")).await.unwrap();
+
+    let conflict = |current: &str| {
+        Recorded::SendReply(Reply::error(format!(
+            "テンプレート「milestone」は他の管理者によって変更されています（現在 {}）。`templates list` で最新の内容を確認してから、もう一度お試しください。",
+            current
+        )))
+    };
+    assert_eq!(
+        frontend.calls(),
+        [
+            Recorded::SendReply(
+                Reply::new("テンプレート「milestone」を設定しました（v1）。")
+                    .titled(Title::Settings)
+            ),
+            conflict("v1"),
+            Recorded::SendReply(
+                Reply::new("テンプレート「milestone」を設定しました（v2）。")
+                    .titled(Title::Settings)
+            ),
+            conflict("v2"),
+            Recorded::SendReply(
+                Reply::new("テンプレート「milestone」を既定に戻しました。").titled(Title::Settings)
+            ),
+            Recorded::SendReply(
+                Reply::new("テンプレート「milestone」は変更されていません。")
+                    .titled(Title::Settings)
+            ),
+        ]
+    );
+
+    test.teardown().await;
+}
+
+#[test]
+fn presses_on_any_stored_panel_are_dispatched() {
+    for panel in [forum_panel_id("900"), "123".to_string()] {
+        assert_eq!(
+            parse_panel_press(&CustomId::new(panel.clone(), 1).encode()),
+            Some(panel)
+        );
+    }
+
+    // Smoke-break prompts are handled by their own collector while they last.
+    assert_eq!(
+        parse_panel_press(&CustomId::new("smoke-break-42-1700000000", 1).encode()),
+        None
+    );
+    assert_eq!(parse_panel_press("refresh:1:forum-900"), None);
+    assert_eq!(parse_panel_press("forum-900"), None);
+}
+
+#[test]
+fn disabled_modules_are_left_out() {
+    let names = |commands: Vec<_>| -> Vec<String> {
+        commands
+            .into_iter()
+            .map(|command: poise::Command<_, _>| command.name)
+            .collect()
+    };
+
+    let all = names(enabled_commands(&[]));
+    assert!(all.contains(&"create_cigarette_ui".to_string()));
+    assert!(all.contains(&"admin".to_string()));
+
+    let enabled = names(enabled_commands(&[
+        "admin".to_string(),
+        "devices".to_string(),
+        "unknown".to_string(),
+    ]));
+    assert_eq!(enabled.len(), all.len() - 4);
+    assert!(!enabled.contains(&"admin".to_string()));
+    assert!(!enabled.contains(&"register".to_string()));
+    assert!(!enabled.contains(&"register_device".to_string()));
+    assert!(!enabled.contains(&"create_shortcut".to_string()));
+}
+
+#[tokio::test]
+async fn panels_only_show_their_types() {
+    let test = setup().await;
+    let database = Mutex::new(Database::new(test.pool.clone()));
+    let frontend = RecordingFrontend::default();
+
+    let all = install_panel(&frontend, &database, "1", Some("10"), "100", None)
+        .await
+        .unwrap()
+        .unwrap();
+    let iqos = install_panel(
+        &frontend,
+        &database,
+        "2",
+        Some("10"),
+        "100",
+        Some(" iqos, "),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert!(install_panel(
+        &frontend,
+        &database,
+        "3",
+        Some("10"),
+        "100",
+        Some("iqos vape")
+    )
+    .await
+    .unwrap()
+    .is_none());
+
+    assert_eq!((all.len(), iqos.len()), (5, 1));
+    assert_eq!(
+        frontend.calls(),
+        [Recorded::SendReply(Reply::error(
+            "不明な種類です: vape（使用できる種類: traditional, iqos, ploom, glo, other）"
+        ))]
+    );
+    let panel = test.db.get_panel("2", Some("10")).await.unwrap().unwrap();
+    assert_eq!(panel.smoking_type_ids, Some(vec![2]));
+    assert!(test
+        .db
+        .get_panel("1", Some("10"))
+        .await
+        .unwrap()
+        .unwrap()
+        .smoking_type_ids
+        .is_none());
+    assert!(test.db.get_panel("2", Some("20")).await.unwrap().is_none());
+    assert!(test.db.get_panel("3", Some("10")).await.unwrap().is_none());
+
+    test.teardown().await;
+}
+
+#[test]
+fn button_labels_fit_discords_limit() {
+    let smoking_type = |type_name: &str, description: Option<&str>| SmokingType {
+        id: 7,
+        type_name: type_name.to_string(),
+        description: description.map(str::to_string),
+        created_at: None,
+        emoji: None,
+        unit: None,
+        typical_price: None,
+        currency: None,
+    };
+
+    assert_eq!(
+        button_label(&smoking_type("iqos", Some("IQOS"))).as_deref(),
+        Ok("IQOS")
+    );
+    assert_eq!(
+        button_label(&smoking_type("iqos", Some("  "))).as_deref(),
+        Ok("iqos")
+    );
+    let long = button_label(&smoking_type("long", Some(&"煙".repeat(100)))).unwrap();
+    assert_eq!(long.chars().count(), MAX_LABEL_LENGTH);
+    assert!(long.ends_with("煙…"));
+    assert_eq!(
+        button_label(&smoking_type(" ", None)),
+        Err(UnusableLabelError(7))
+    );
+}
+
+#[tokio::test]
+async fn panels_with_unusable_labels_are_reported() {
+    let test = setup().await;
+    let database = Mutex::new(Database::new(test.pool.clone()));
+    let frontend = RecordingFrontend::default();
+    sqlx::query("INSERT INTO smoking_types (id, type_name, description) VALUES (99, '', NULL)")
+        .execute(&test.pool)
+        .await
+        .unwrap();
+
+    assert!(install_panel(&frontend, &database, "1", None, "100", None)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        frontend.calls(),
+        [Recorded::SendReply(Reply::error(
+            "種類 ID 99 の表示名が空のため、パネルを作成できません。種類の説明または名前を設定してください。"
+        ))]
+    );
+    assert!(test.db.get_panel("1", None).await.unwrap().is_none());
+
+    test.teardown().await;
+}
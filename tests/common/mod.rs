@@ -0,0 +1,276 @@
+//! Shared harness for database integration tests.
+//!
+//! Every test gets a freshly migrated, empty database. By default a disposable
+//! Postgres container is started with testcontainers; set `TEST_DATABASE_URL`
+//! to run against an existing server instead, in which case a uniquely named
+//! database is created on it for each test and dropped by `teardown`.
+
+#![allow(dead_code)]
+
+use std::{str::FromStr, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use cigarette_counter::{
+    clock::Clock,
+    consent::POLICY_VERSION,
+    database::Database,
+    event_bus::EventBus,
+    format::Locale,
+    frontend::{Frontend, Reply},
+    scripting::ScriptHooks,
+    service::LoggingService,
+    Error,
+};
+use poise::serenity_prelude::{futures::lock::Mutex, ChannelId, MessageId, ReactionType};
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    ConnectOptions, Executor, PgPool,
+};
+use testcontainers_modules::{
+    postgres::Postgres,
+    testcontainers::{runners::AsyncRunner, ContainerAsync},
+};
+
+/// A migrated database that lives for the duration of one test
+pub struct TestDatabase {
+    pub db: Database,
+    pub pool: PgPool,
+    server: Server,
+}
+
+/// Where the test database is hosted
+#[allow(clippy::large_enum_variant)]
+enum Server {
+    Container(ContainerAsync<Postgres>),
+    External {
+        admin: PgConnectOptions,
+        name: String,
+    },
+}
+
+impl TestDatabase {
+    /// Returns the current date in UTC, the time zone the tests count days in.
+    pub fn today(&self) -> NaiveDate {
+        Utc::now().date_naive()
+    }
+
+    /// Closes the pool and removes the test database.
+    pub async fn teardown(self) {
+        self.pool.close().await;
+
+        if let Server::External { admin, name } = self.server {
+            let mut conn = admin.connect().await.expect("connect to admin database");
+            conn.execute(format!("DROP DATABASE IF EXISTS \"{}\" WITH (FORCE)", name).as_str())
+                .await
+                .expect("drop test database");
+        }
+    }
+}
+
+/// Creates a fresh database and runs all migrations on it.
+pub async fn setup() -> TestDatabase {
+    let (options, server) = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => external_database(&url).await,
+        Err(_) => container_database().await,
+    };
+
+    let pool = PgPoolOptions::new()
+        .max_connections(5)
+        .connect_with(options)
+        .await
+        .expect("connect to test database");
+
+    sqlx::migrate!("./migrations")
+        .run(&pool)
+        .await
+        .expect("run migrations");
+
+    TestDatabase {
+        db: Database::new(pool.clone()),
+        pool,
+        server,
+    }
+}
+
+/// Creates a uniquely named database on an existing server.
+async fn external_database(url: &str) -> (PgConnectOptions, Server) {
+    let admin = PgConnectOptions::from_str(url).expect("parse TEST_DATABASE_URL");
+    let name = format!(
+        "cigarette_counter_test_{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    );
+
+    let mut conn = admin.connect().await.expect("connect to TEST_DATABASE_URL");
+    conn.execute(format!("CREATE DATABASE \"{}\"", name).as_str())
+        .await
+        .expect("create test database");
+
+    (
+        admin.clone().database(&name),
+        Server::External { admin, name },
+    )
+}
+
+/// Starts a disposable Postgres container.
+async fn container_database() -> (PgConnectOptions, Server) {
+    let container = Postgres::default()
+        .start()
+        .await
+        .expect("start Postgres container (set TEST_DATABASE_URL to use an existing server)");
+    let host = container.get_host().await.expect("container host");
+    let port = container
+        .get_host_port_ipv4(5432)
+        .await
+        .expect("container port");
+
+    let options = PgConnectOptions::new()
+        .host(&host.to_string())
+        .port(port)
+        .username("postgres")
+        .password("postgres")
+        .database("postgres");
+
+    (options, Server::Container(container))
+}
+
+/// Creates a user with the given Discord ID who accepted the current data policy.
+pub async fn create_user(test: &TestDatabase, discord_id: &str) {
+    test.db
+        .create_user(discord_id, &format!("user-{}", discord_id))
+        .await
+        .expect("create user");
+    test.db
+        .record_consent(discord_id, POLICY_VERSION, Utc::now())
+        .await
+        .expect("record consent");
+}
+
+/// Moves all of a user's logs to the given date (noon UTC).
+pub async fn move_logs_to(test: &TestDatabase, discord_id: &str, date: NaiveDate) {
+    sqlx::query(
+        "UPDATE smoking_logs SET smoked_at = ($2::date + TIME '12:00') AT TIME ZONE 'UTC' WHERE discord_id = $1",
+    )
+    .bind(discord_id)
+    .bind(date)
+    .execute(&test.pool)
+    .await
+    .expect("move logs");
+}
+
+/// Moves all of a user's logs to the given instant.
+pub async fn set_smoked_at(test: &TestDatabase, discord_id: &str, smoked_at: DateTime<Utc>) {
+    sqlx::query("UPDATE smoking_logs SET smoked_at = $2 WHERE discord_id = $1")
+        .bind(discord_id)
+        .bind(smoked_at)
+        .execute(&test.pool)
+        .await
+        .expect("set smoked_at");
+}
+
+/// Logs one cigarette for a user at the given instant.
+pub async fn log_at(test: &TestDatabase, discord_id: &str, smoked_at: DateTime<Utc>) {
+    sqlx::query(
+        "INSERT INTO smoking_logs (discord_id, smoking_type_id, quantity, smoked_at) VALUES ($1, 1, 1, $2)",
+    )
+    .bind(discord_id)
+    .bind(smoked_at)
+    .execute(&test.pool)
+    .await
+    .expect("insert log");
+}
+
+/// Creates a logging service without scripts on top of a shared database.
+pub fn logging_service(database: &Arc<Mutex<Database>>, clock: Arc<dyn Clock>) -> LoggingService {
+    LoggingService::new(
+        database.clone(),
+        clock,
+        Arc::new(ScriptHooks::default()),
+        Arc::new(EventBus::default()),
+    )
+}
+
+/// A call made on the `RecordingFrontend`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recorded {
+    SendReply(Reply),
+    Respond(Reply),
+    React(String),
+    EditMessage(MessageId, Reply),
+    Acknowledge,
+}
+
+/// A `Frontend` that records every call instead of talking to Discord
+#[derive(Default)]
+pub struct RecordingFrontend {
+    locale: Locale,
+    calls: std::sync::Mutex<Vec<Recorded>>,
+}
+
+impl RecordingFrontend {
+    /// Creates a frontend reporting the given locale.
+    pub fn with_locale(locale: Locale) -> Self {
+        Self {
+            locale,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the calls recorded so far.
+    pub fn calls(&self) -> Vec<Recorded> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Frontend for RecordingFrontend {
+    fn locale(&self) -> Locale {
+        self.locale
+    }
+
+    /// Every call happens in channel `1`.
+    fn channel_id(&self) -> Option<ChannelId> {
+        Some(ChannelId::new(1))
+    }
+
+    async fn send_reply(&self, reply: Reply) -> Result<(), Error> {
+        self.calls.lock().unwrap().push(Recorded::SendReply(reply));
+        Ok(())
+    }
+
+    async fn respond(&self, reply: Reply) -> Result<(), Error> {
+        self.calls.lock().unwrap().push(Recorded::Respond(reply));
+        Ok(())
+    }
+
+    /// Records a `Respond`; the message ID is the 1-based position of the call.
+    async fn respond_editable(&self, reply: Reply) -> Result<Option<MessageId>, Error> {
+        let mut calls = self.calls.lock().unwrap();
+        calls.push(Recorded::Respond(reply));
+        Ok(Some(MessageId::new(calls.len() as u64)))
+    }
+
+    async fn acknowledge(&self) -> Result<(), Error> {
+        self.calls.lock().unwrap().push(Recorded::Acknowledge);
+        Ok(())
+    }
+
+    async fn react(&self, emoji: ReactionType) -> Result<(), Error> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(Recorded::React(emoji.to_string()));
+        Ok(())
+    }
+
+    async fn edit_message(&self, message_id: MessageId, reply: Reply) -> Result<(), Error> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(Recorded::EditMessage(message_id, reply));
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+//! Tests for deleting old panel confirmations.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono::{Duration, TimeZone, Utc};
+use chrono_tz::UTC;
+use cigarette_counter::{
+    clock::MockClock,
+    commands::{panel::record_cigarette, reactions::update_confirmation_cleanup},
+    database::{ConfirmationMessage, Database},
+    embed::Title,
+    frontend::Reply,
+};
+use common::{create_user, logging_service, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+#[tokio::test]
+async fn cleanup_ages_must_be_in_range() {
+    let test = setup().await;
+    let database = Mutex::new(Database::new(test.pool.clone()));
+    let frontend = RecordingFrontend::default();
+
+    for hours in [Some(0), Some(721), Some(24), None, None] {
+        update_confirmation_cleanup(&frontend, &database, "10", hours)
+            .await
+            .unwrap();
+    }
+
+    let out_of_range = Recorded::SendReply(Reply::error("時間は1〜720の範囲で指定してください。"));
+    assert_eq!(
+        frontend.calls(),
+        [
+            out_of_range.clone(),
+            out_of_range,
+            Recorded::SendReply(
+                Reply::new("これから送るパネルの確認メッセージを24時間後に削除します。")
+                    .titled(Title::Settings)
+            ),
+            Recorded::SendReply(
+                Reply::new("パネルの確認メッセージを削除しないようにしました。")
+                    .titled(Title::Settings)
+            ),
+            Recorded::SendReply(
+                Reply::new("確認メッセージの削除は設定されていません。").titled(Title::Settings)
+            ),
+        ]
+    );
+    assert_eq!(
+        test.db
+            .guild("10")
+            .get_confirmation_cleanup()
+            .await
+            .unwrap(),
+        None
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn confirmations_expire_after_the_guilds_age() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    test.db
+        .guild("10")
+        .set_confirmation_cleanup(24)
+        .await
+        .unwrap();
+    let sent_at = Utc.with_ymd_and_hms(2024, 6, 10, 12, 0, 0).unwrap();
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let logging = logging_service(&database, Arc::new(MockClock::new(UTC, sent_at)));
+    let frontend = RecordingFrontend::default();
+
+    // Only the guild with cleanup remembers its confirmation.
+    record_cigarette(&frontend, &logging, "1", "alice", Some("10"), 1, None)
+        .await
+        .unwrap();
+    record_cigarette(&frontend, &logging, "1", "alice", Some("20"), 1, None)
+        .await
+        .unwrap();
+
+    assert!(test
+        .db
+        .get_expired_confirmations(sent_at + Duration::hours(23), 100)
+        .await
+        .unwrap()
+        .is_empty());
+    let expired = test
+        .db
+        .get_expired_confirmations(sent_at + Duration::hours(24), 100)
+        .await
+        .unwrap();
+    assert_eq!(
+        expired,
+        vec![ConfirmationMessage {
+            message_id: "1".to_string(),
+            guild_id: "10".to_string(),
+            channel_id: "1".to_string(),
+        }]
+    );
+
+    assert_eq!(
+        test.db
+            .forget_confirmations(&["1".to_string()])
+            .await
+            .unwrap(),
+        1
+    );
+    assert!(test
+        .db
+        .get_expired_confirmations(sent_at + Duration::hours(48), 100)
+        .await
+        .unwrap()
+        .is_empty());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn disabling_cleanup_forgets_the_remembered_confirmations() {
+    let test = setup().await;
+    let guild = test.db.guild("10");
+    let sent_at = Utc.with_ymd_and_hms(2024, 6, 10, 12, 0, 0).unwrap();
+
+    assert!(!guild
+        .remember_confirmation("100", "1000", sent_at)
+        .await
+        .unwrap());
+    guild.set_confirmation_cleanup(1).await.unwrap();
+    assert!(guild
+        .remember_confirmation("100", "1001", sent_at)
+        .await
+        .unwrap());
+    assert!(guild.remove_confirmation_cleanup().await.unwrap());
+
+    // Enabling it again does not delete confirmations remembered before it was disabled.
+    guild.set_confirmation_cleanup(1).await.unwrap();
+    assert!(test
+        .db
+        .get_expired_confirmations(sent_at + Duration::hours(2), 100)
+        .await
+        .unwrap()
+        .is_empty());
+
+    test.teardown().await;
+}
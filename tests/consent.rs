@@ -0,0 +1,73 @@
+//! Tests for the data policy consent required before logging.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::SystemClock,
+    consent::{accept_id, parse_accept_id, POLICY_VERSION},
+    database::Database,
+    service::ServiceError,
+};
+use common::{logging_service, setup};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+#[test]
+fn accept_ids_round_trip() {
+    assert_eq!(parse_accept_id(&accept_id(2)), Some(2));
+    assert_eq!(parse_accept_id("consent:accept:02"), None);
+    assert_eq!(parse_accept_id("consent:accept:"), None);
+    assert_eq!(parse_accept_id("panel:2"), None);
+}
+
+#[tokio::test]
+async fn logging_requires_the_current_policy_version() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let logging = logging_service(&database, Arc::new(SystemClock::new(Tz::UTC)));
+
+    assert!(matches!(
+        logging.log_for_user("1", "alice", None, 1, 1, false, None).await,
+        Err(ServiceError::ConsentRequired)
+    ));
+    // Nothing is stored before the policy is accepted.
+    assert!(!test.db.user_exists("1").await.unwrap());
+
+    logging.accept_policy("1", "alice").await.unwrap();
+    assert!(logging.has_consented("1").await.unwrap());
+    assert_eq!(
+        test.db.get_consent_version("1").await.unwrap(),
+        Some(POLICY_VERSION)
+    );
+    logging
+        .log_for_user("1", "alice", None, 1, 1, false, None)
+        .await
+        .unwrap();
+
+    // Consent to an older version of the policy prompts again.
+    test.db
+        .record_consent("1", POLICY_VERSION - 1, chrono::Utc::now())
+        .await
+        .unwrap();
+    assert!(!logging.has_consented("1").await.unwrap());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn devices_require_consent_of_their_owner() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let logging = logging_service(&database, Arc::new(SystemClock::new(Tz::UTC)));
+    test.db.create_user("1", "alice").await.unwrap();
+    test.db.create_device("1", "button", "hash").await.unwrap();
+
+    assert!(matches!(
+        logging.log_for_device("hash", 1, 1, false, None).await,
+        Err(ServiceError::ConsentRequired)
+    ));
+
+    test.teardown().await;
+}
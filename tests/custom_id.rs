@@ -0,0 +1,128 @@
+//! Property-based tests for the panel button custom ID codec.
+
+use cigarette_counter::custom_id::{CustomId, ParseCustomIdError, RefreshId, MAX_LENGTH};
+use proptest::prelude::*;
+
+/// Panel IDs as produced by commands (snowflakes) and smoke-break DMs
+fn panel() -> impl Strategy<Value = String> {
+    prop_oneof![
+        any::<u64>().prop_map(|id| id.to_string()),
+        (any::<u64>(), any::<i64>())
+            .prop_map(|(user, millis)| format!("smoke-break-{}-{}", user, millis)),
+        "[^:]{1,40}",
+    ]
+}
+
+proptest! {
+    #[test]
+    fn encode_then_decode_round_trips(panel in panel(), smoking_type_id in any::<i32>()) {
+        let custom_id = CustomId::new(panel.clone(), smoking_type_id);
+        let encoded = custom_id.encode();
+
+        prop_assert_eq!(encoded.parse::<CustomId>(), Ok(custom_id));
+        prop_assert!(CustomId::belongs_to(&encoded, &panel));
+    }
+
+    #[test]
+    fn decode_then_encode_is_canonical(input in "\\PC{0,120}") {
+        if let Ok(custom_id) = input.parse::<CustomId>() {
+            prop_assert_eq!(custom_id.encode(), input);
+        }
+    }
+
+    #[test]
+    fn arbitrary_bytes_never_panic(bytes in proptest::collection::vec(any::<u8>(), 0..256)) {
+        let input = String::from_utf8_lossy(&bytes);
+        let _ = input.parse::<CustomId>();
+        let _ = CustomId::belongs_to(&input, "panel");
+    }
+
+    #[test]
+    fn other_panels_are_rejected(
+        panel in panel(),
+        other in panel(),
+        smoking_type_id in any::<i32>(),
+    ) {
+        prop_assume!(panel != other);
+        let encoded = CustomId::new(panel, smoking_type_id).encode();
+
+        prop_assert!(!CustomId::belongs_to(&encoded, &other));
+    }
+
+    #[test]
+    fn non_canonical_type_ids_are_rejected(
+        panel in panel(),
+        smoking_type_id in "(\\+[0-9]{1,5}|0[0-9]{1,5}|-0|[0-9]{11,20}| [0-9]+|[0-9]+ |)",
+    ) {
+        let input = format!("{}:{}", panel, smoking_type_id);
+        prop_assume!(input.len() <= MAX_LENGTH);
+
+        prop_assert!(matches!(
+            input.parse::<CustomId>(),
+            Err(ParseCustomIdError::InvalidSmokingTypeId(_))
+        ));
+    }
+
+    #[test]
+    fn overlong_input_is_rejected(panel in "[a-z]{101,200}") {
+        let input = format!("{}:1", panel);
+
+        prop_assert_eq!(input.parse::<CustomId>(), Err(ParseCustomIdError::TooLong));
+    }
+}
+
+#[test]
+fn legacy_and_malformed_ids_are_rejected() {
+    // Buttons from panels created before the codec had no separator.
+    assert_eq!(
+        "11234567890123456781".parse::<CustomId>(),
+        Err(ParseCustomIdError::MissingPanel)
+    );
+    assert_eq!(
+        ":1".parse::<CustomId>(),
+        Err(ParseCustomIdError::MissingPanel)
+    );
+    assert_eq!(
+        "a:b:1".parse::<CustomId>(),
+        Err(ParseCustomIdError::MissingPanel)
+    );
+    assert_eq!(
+        "panel:".parse::<CustomId>(),
+        Err(ParseCustomIdError::InvalidSmokingTypeId(String::new()))
+    );
+}
+
+proptest! {
+    #[test]
+    fn refresh_ids_round_trip_and_never_alias_buttons(
+        message_id in any::<u64>(),
+        panel in panel(),
+    ) {
+        let refresh = RefreshId::new(message_id, panel.clone());
+        let encoded = refresh.encode();
+
+        if encoded.len() > MAX_LENGTH {
+            prop_assert_eq!(encoded.parse::<RefreshId>(), Err(ParseCustomIdError::TooLong));
+        } else {
+            prop_assert_eq!(encoded.parse::<RefreshId>(), Ok(refresh));
+        }
+        prop_assert!(encoded.parse::<CustomId>().is_err());
+        prop_assert!(!CustomId::belongs_to(&encoded, &panel));
+    }
+}
+
+#[test]
+fn malformed_refresh_ids_are_rejected() {
+    assert_eq!(
+        "refresh:01:1".parse::<RefreshId>(),
+        Err(ParseCustomIdError::InvalidMessageId("01".to_string()))
+    );
+    assert_eq!(
+        "refresh:1:".parse::<RefreshId>(),
+        Err(ParseCustomIdError::MissingPanel)
+    );
+    assert_eq!(
+        "1:2".parse::<RefreshId>(),
+        Err(ParseCustomIdError::MissingPanel)
+    );
+}
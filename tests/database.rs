@@ -0,0 +1,419 @@
+//! Integration tests for every `Database` method against a migrated Postgres.
+
+mod common;
+
+use chrono::{Duration, Utc};
+use chrono_tz::Tz;
+use cigarette_counter::{
+    database::EXPECTED_INDEXES,
+    explain::{self, ParamSource},
+};
+use common::{create_user, move_logs_to, setup};
+
+#[tokio::test]
+async fn users_are_created_and_renamed() {
+    let test = setup().await;
+
+    assert!(!test.db.user_exists("1").await.unwrap());
+    let user = test.db.create_user("1", "alice").await.unwrap();
+    assert_eq!(user.discord_id, "1");
+    assert_eq!(user.username, "alice");
+    assert!(test.db.user_exists("1").await.unwrap());
+
+    let same = test.db.get_or_create_user("1", "alice").await.unwrap();
+    assert_eq!(same.username, "alice");
+    let renamed = test.db.get_or_create_user("1", "alicia").await.unwrap();
+    assert_eq!(renamed.username, "alicia");
+
+    let created = test.db.get_or_create_user("2", "bob").await.unwrap();
+    assert_eq!(created.discord_id, "2");
+    assert!(test.db.user_exists("2").await.unwrap());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn smoking_types_are_seeded_and_queryable() {
+    let test = setup().await;
+
+    let types = test.db.get_smoking_types().await.unwrap();
+    let names: Vec<_> = types.iter().map(|t| t.type_name.as_str()).collect();
+    assert_eq!(names, ["traditional", "iqos", "ploom", "glo", "other"]);
+
+    let iqos = test
+        .db
+        .find_smoking_type_by_name("iqos")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        test.db.get_smoking_type(iqos.id).await.unwrap().type_name,
+        "iqos"
+    );
+    assert!(test.db.smoking_type_exists(iqos.id).await.unwrap());
+    assert!(!test.db.smoking_type_exists(-1).await.unwrap());
+    assert!(test
+        .db
+        .find_smoking_type_by_name("missing")
+        .await
+        .unwrap()
+        .is_none());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn logs_are_summarized_per_type() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let today = test.today();
+
+    let log = test.db.log_smoking("1", Some("10"), 1, 2).await.unwrap();
+    assert_eq!(log.quantity, 2);
+    assert_eq!(log.guild_id.as_deref(), Some("10"));
+    assert!(log.device_name.is_none());
+    test.db.log_smoking("1", None, 1, 1).await.unwrap();
+    test.db.log_smoking("1", None, 2, 1).await.unwrap();
+
+    let mut summary = test
+        .db
+        .get_daily_summary("1", today, Tz::UTC)
+        .await
+        .unwrap();
+    summary.sort_by(|a, b| a.type_name.cmp(&b.type_name));
+    let totals: Vec<_> = summary
+        .iter()
+        .map(|s| (s.type_name.as_str(), s.total_quantity))
+        .collect();
+    assert_eq!(totals, [("iqos", Some(1)), ("traditional", Some(3))]);
+
+    assert!(test
+        .db
+        .get_daily_summary("1", today - Duration::days(1), Tz::UTC)
+        .await
+        .unwrap()
+        .is_empty());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn log_with_summary_is_transactional() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let today = test.today();
+
+    let (log, summary) = test
+        .db
+        .log_smoking_with_summary("1", None, 1, 1, None, today, Tz::UTC)
+        .await
+        .unwrap();
+    assert_eq!(log.smoking_type_id, 1);
+    assert_eq!(summary.len(), 1);
+    assert_eq!(summary[0].total_quantity, Some(1));
+
+    // An invalid type fails the insert and leaves no partial state behind.
+    assert!(test
+        .db
+        .log_smoking_with_summary("1", None, -1, 1, None, today, Tz::UTC)
+        .await
+        .is_err());
+    let summary = test
+        .db
+        .get_daily_summary("1", today, Tz::UTC)
+        .await
+        .unwrap();
+    assert_eq!(summary[0].total_quantity, Some(1));
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn devices_authenticate_and_log() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+
+    let device = test.db.create_device("1", "balcony", "hash").await.unwrap();
+    assert_eq!(device.device_name, "balcony");
+    assert!(device.last_used_at.is_none());
+
+    assert!(test
+        .db
+        .authenticate_device("other")
+        .await
+        .unwrap()
+        .is_none());
+    let device = test.db.authenticate_device("hash").await.unwrap().unwrap();
+    assert!(device.last_used_at.is_some());
+
+    let log = test
+        .db
+        .log_device_smoking(&device, 1, 3, None)
+        .await
+        .unwrap();
+    assert_eq!(log.discord_id, "1");
+    assert_eq!(log.quantity, 3);
+    assert_eq!(log.device_name.as_deref(), Some("balcony"));
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn shortcut_links_are_resolved_by_token() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+
+    let link = test.db.create_shortcut_link("1", 2, "hash").await.unwrap();
+    assert_eq!(link.smoking_type_id, 2);
+    assert!(link.last_used_at.is_none());
+
+    assert!(test.db.use_shortcut_link("other").await.unwrap().is_none());
+    let used = test.db.use_shortcut_link("hash").await.unwrap().unwrap();
+    assert_eq!(used.id, link.id);
+    assert!(used.last_used_at.is_some());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn goals_and_daily_history() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let today = test.today();
+
+    assert_eq!(test.db.get_daily_goal("1").await.unwrap(), None);
+    test.db.set_daily_goal("1", Some(5)).await.unwrap();
+    assert_eq!(test.db.get_daily_goal("1").await.unwrap(), Some(5));
+    test.db.set_daily_goal("1", None).await.unwrap();
+    assert_eq!(test.db.get_daily_goal("1").await.unwrap(), None);
+
+    assert!(test.db.get_last_smoked_at("1").await.unwrap().is_none());
+    assert!(test
+        .db
+        .get_first_smoke_date("1", Tz::UTC)
+        .await
+        .unwrap()
+        .is_none());
+
+    let three_days_ago = today - Duration::days(3);
+    test.db.log_smoking("1", None, 1, 2).await.unwrap();
+    move_logs_to(&test, "1", three_days_ago).await;
+    test.db.log_smoking("1", None, 1, 1).await.unwrap();
+
+    assert_eq!(
+        test.db.get_first_smoke_date("1", Tz::UTC).await.unwrap(),
+        Some(three_days_ago)
+    );
+    assert!(test.db.get_last_smoked_at("1").await.unwrap().is_some());
+
+    let totals = test
+        .db
+        .get_daily_totals("1", three_days_ago, Tz::UTC)
+        .await
+        .unwrap();
+    let totals: Vec<_> = totals
+        .iter()
+        .map(|t| (t.smoke_date, t.total_quantity))
+        .collect();
+    assert_eq!(totals, [(three_days_ago, 2), (today, 1)]);
+    assert_eq!(
+        test.db
+            .get_daily_totals("1", today, Tz::UTC)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn role_connections_are_upserted() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let expires_at = Utc::now() + Duration::days(7);
+
+    test.db
+        .upsert_role_connection("1", "access", "refresh", expires_at)
+        .await
+        .unwrap();
+    test.db
+        .upsert_role_connection("1", "access2", "refresh2", expires_at)
+        .await
+        .unwrap();
+
+    let connections = test.db.get_role_connections().await.unwrap();
+    assert_eq!(connections.len(), 1);
+    assert_eq!(connections[0].access_token, "access2");
+    assert_eq!(connections[0].refresh_token, "refresh2");
+    assert!(connections[0].last_pushed_at.is_none());
+
+    test.db.mark_role_connection_pushed("1").await.unwrap();
+    let connections = test.db.get_role_connections().await.unwrap();
+    assert!(connections[0].last_pushed_at.is_some());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn milestone_roles_are_mapped_per_guild() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    create_user(&test, "2").await;
+
+    test.db
+        .guild("10")
+        .set_milestone_role("100", 7)
+        .await
+        .unwrap();
+    test.db
+        .guild("10")
+        .set_milestone_role("100", 30)
+        .await
+        .unwrap();
+    test.db
+        .guild("10")
+        .set_milestone_role("101", 1)
+        .await
+        .unwrap();
+    test.db
+        .guild("20")
+        .set_milestone_role("200", 3)
+        .await
+        .unwrap();
+
+    let roles = test.db.guild("10").get_milestone_roles().await.unwrap();
+    let roles: Vec<_> = roles
+        .iter()
+        .map(|r| (r.role_id.as_str(), r.smoke_free_days))
+        .collect();
+    assert_eq!(roles, [("101", 1), ("100", 30)]);
+    assert_eq!(test.db.get_milestone_guilds().await.unwrap(), ["10", "20"]);
+
+    assert!(test
+        .db
+        .guild("20")
+        .remove_milestone_role("200")
+        .await
+        .unwrap());
+    assert!(!test
+        .db
+        .guild("20")
+        .remove_milestone_role("200")
+        .await
+        .unwrap());
+    assert_eq!(test.db.get_milestone_guilds().await.unwrap(), ["10"]);
+
+    test.db.log_smoking("1", Some("10"), 1, 1).await.unwrap();
+    test.db.log_smoking("2", Some("10"), 1, 1).await.unwrap();
+    test.db.log_smoking("2", Some("20"), 1, 1).await.unwrap();
+    assert_eq!(
+        test.db.guild("10").get_user_ids().await.unwrap(),
+        ["1", "2"]
+    );
+    assert_eq!(test.db.guild("20").get_user_ids().await.unwrap(), ["2"]);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn smoke_break_prompts_and_channels() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+
+    assert!(!test.db.smoke_break_prompt_enabled("1").await.unwrap());
+    assert!(!test.db.smoke_break_prompt_enabled("unknown").await.unwrap());
+    test.db.set_smoke_break_prompt("1", true).await.unwrap();
+    assert!(test.db.smoke_break_prompt_enabled("1").await.unwrap());
+
+    test.db
+        .guild("10")
+        .add_smoke_break_channel("500")
+        .await
+        .unwrap();
+    test.db
+        .guild("10")
+        .add_smoke_break_channel("500")
+        .await
+        .unwrap();
+    assert!(test
+        .db
+        .guild("10")
+        .is_smoke_break_channel("500")
+        .await
+        .unwrap());
+    assert!(!test
+        .db
+        .guild("20")
+        .is_smoke_break_channel("500")
+        .await
+        .unwrap());
+    assert!(!test
+        .db
+        .guild("20")
+        .remove_smoke_break_channel("500")
+        .await
+        .unwrap());
+    assert!(test
+        .db
+        .guild("10")
+        .remove_smoke_break_channel("500")
+        .await
+        .unwrap());
+    assert!(!test
+        .db
+        .guild("10")
+        .is_smoke_break_channel("500")
+        .await
+        .unwrap());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn every_named_query_can_be_explained() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let today = test.today();
+
+    for query in explain::QUERIES {
+        let params = ParamSource {
+            discord_id: "1".to_string(),
+            guild_id: "10".to_string(),
+            smoking_type_id: 1,
+            today,
+            timezone: Tz::UTC,
+        }
+        .resolve(query.params);
+
+        let plan = test.db.explain_analyze(query.sql, &params).await.unwrap();
+        assert!(!plan.is_empty(), "empty plan for {}", query.name);
+    }
+
+    // EXPLAIN ANALYZE of the insert must not leave a row behind.
+    assert!(test.db.get_last_smoked_at("1").await.unwrap().is_none());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn expected_indexes_exist_after_migrations() {
+    let test = setup().await;
+
+    assert!(test
+        .db
+        .find_missing_indexes(EXPECTED_INDEXES)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        test.db
+            .find_missing_indexes(&["idx_does_not_exist"])
+            .await
+            .unwrap(),
+        ["idx_does_not_exist"]
+    );
+
+    test.teardown().await;
+}
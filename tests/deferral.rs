@@ -0,0 +1,80 @@
+//! Tests for deferring interactions whose handlers are slow to respond.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+use cigarette_counter::deferral::{begin_response, with_deferral, Delivery};
+
+const THRESHOLD: Duration = Duration::from_millis(50);
+
+/// Runs `work` with a defer callback that counts its calls.
+async fn run<W>(work: W) -> usize
+where
+    W: std::future::Future<Output = Result<(), cigarette_counter::Error>>,
+{
+    let defers = Arc::new(AtomicUsize::new(0));
+    let counter = defers.clone();
+    with_deferral(THRESHOLD, work, || async move {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    defers.load(Ordering::SeqCst)
+}
+
+#[tokio::test]
+async fn fast_handlers_respond_directly() {
+    let defers = run(async {
+        assert_eq!(begin_response(), Delivery::Response);
+        Ok(())
+    })
+    .await;
+
+    assert_eq!(defers, 0);
+}
+
+#[tokio::test]
+async fn slow_handlers_are_deferred_and_follow_up() {
+    let defers = run(async {
+        tokio::time::sleep(THRESHOLD * 3).await;
+        assert_eq!(begin_response(), Delivery::FollowUp);
+        Ok(())
+    })
+    .await;
+
+    assert_eq!(defers, 1);
+}
+
+#[tokio::test]
+async fn handlers_that_already_responded_are_not_deferred() {
+    let defers = run(async {
+        assert_eq!(begin_response(), Delivery::Response);
+        tokio::time::sleep(THRESHOLD * 3).await;
+        // Later messages of the handler are follow-ups to its own response.
+        assert_eq!(begin_response(), Delivery::Response);
+        Ok(())
+    })
+    .await;
+
+    assert_eq!(defers, 0);
+}
+
+#[tokio::test]
+async fn handler_errors_are_returned() {
+    let result = with_deferral(THRESHOLD, async { Err("failed".into()) }, || async {
+        Ok(())
+    })
+    .await;
+
+    assert_eq!(result.unwrap_err().to_string(), "failed");
+}
+
+#[test]
+fn responses_outside_a_handler_are_direct() {
+    assert_eq!(begin_response(), Delivery::Response);
+}
@@ -0,0 +1,77 @@
+//! Tests for seeding synthetic demo data.
+
+mod common;
+
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use cigarette_counter::{
+    database::Database,
+    demo_seed::{generate_members, seed_demo, SeedError, SeedPlan, DEMO_GUILD_ID},
+};
+use common::setup;
+use poise::serenity_prelude::futures::lock::Mutex;
+
+fn plan(users: u32) -> SeedPlan {
+    SeedPlan {
+        guild_id: DEMO_GUILD_ID.to_string(),
+        users,
+        months: 1,
+        seed: Some(7),
+    }
+}
+
+fn today() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 6, 30).unwrap()
+}
+
+#[test]
+fn seeded_generation_is_reproducible() {
+    let members = generate_members(&plan(3), &[1, 2], today(), Tz::Asia__Tokyo);
+
+    assert_eq!(
+        members,
+        generate_members(&plan(3), &[1, 2], today(), Tz::Asia__Tokyo)
+    );
+    assert_eq!(members.len(), 3);
+    for member in &members {
+        assert_eq!(member.discord_id.len(), 20);
+        assert!(member.discord_id.parse::<u64>().is_ok());
+        assert!(!member.logs.is_empty());
+        assert!(member
+            .logs
+            .windows(2)
+            .all(|pair| pair[0].smoked_at <= pair[1].smoked_at));
+        assert!(member.logs.iter().all(|log| {
+            let date = log.smoked_at.with_timezone(&Tz::Asia__Tokyo).date_naive();
+            date >= NaiveDate::from_ymd_opt(2024, 5, 31).unwrap() && date <= today()
+        }));
+    }
+}
+
+#[tokio::test]
+async fn seeding_fills_an_empty_guild_once() {
+    let test = setup().await;
+    let database = Mutex::new(Database::new(test.pool.clone()));
+
+    let summary = seed_demo(&database, &plan(2), today(), Tz::UTC)
+        .await
+        .unwrap();
+    assert_eq!(summary.members, 2);
+    assert!(summary.logs > 0);
+    assert_eq!(
+        test.db
+            .guild(DEMO_GUILD_ID)
+            .get_user_ids()
+            .await
+            .unwrap()
+            .len(),
+        2
+    );
+
+    assert!(matches!(
+        seed_demo(&database, &plan(2), today(), Tz::UTC).await,
+        Err(SeedError::GuildNotEmpty(_))
+    ));
+
+    test.teardown().await;
+}
@@ -0,0 +1,216 @@
+//! Tests for the weekly email digests.
+
+mod common;
+
+use std::sync::Mutex as StdMutex;
+
+use async_trait::async_trait;
+use chrono::{Duration, TimeZone, Utc};
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::{Clock, MockClock},
+    commands::email::{disable_email, subscribe_email},
+    database::Database,
+    email::{send_due_digests, Email, EmailError, Mailer},
+    frontend::Reply,
+};
+use common::{create_user, log_at, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+use sha2::{Digest, Sha256};
+
+const PUBLIC_URL: &str = "https://counter.example.com";
+
+/// Mailer recording the emails it is asked to send
+#[derive(Default)]
+struct RecordingMailer {
+    sent: StdMutex<Vec<(String, Email)>>,
+    failing: bool,
+}
+
+impl RecordingMailer {
+    fn sent(&self) -> Vec<(String, Email)> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Mailer for RecordingMailer {
+    async fn send(&self, to: &str, email: &Email) -> Result<(), EmailError> {
+        if self.failing {
+            return Err(EmailError::InvalidAddress(
+                "not-an-address".parse::<lettre::Address>().unwrap_err(),
+            ));
+        }
+        self.sent
+            .lock()
+            .unwrap()
+            .push((to.to_string(), email.clone()));
+
+        Ok(())
+    }
+}
+
+/// Returns the token at the end of the link with the given path in an email.
+fn link_token(email: &Email, path: &str) -> String {
+    let prefix = format!("{}{}", PUBLIC_URL, path);
+    email
+        .body
+        .lines()
+        .find_map(|line| line.split_once(&prefix))
+        .map(|(_, token)| token.to_string())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn digests_are_sent_weekly_to_verified_addresses_until_unsubscribed() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    log_at(
+        &test,
+        "1",
+        Utc.with_ymd_and_hms(2024, 5, 14, 12, 0, 0).unwrap(),
+    )
+    .await;
+    let database = Mutex::new(Database::new(test.pool.clone()));
+    let clock = MockClock::at_local(Tz::UTC, 2024, 5, 20, 9, 0).unwrap();
+    let frontend = RecordingFrontend::default();
+    let mailer = RecordingMailer::default();
+
+    subscribe_email(
+        &frontend,
+        &database,
+        &mailer,
+        PUBLIC_URL,
+        "1",
+        "alice",
+        "alice@example.com",
+    )
+    .await
+    .unwrap();
+    // Nothing but the verification email is sent before the address is verified.
+    assert_eq!(
+        send_due_digests(&database, &clock, &mailer, PUBLIC_URL)
+            .await
+            .unwrap(),
+        0
+    );
+    let sent = mailer.sent();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].0, "alice@example.com");
+    assert_eq!(sent[0].1.subject, "喫煙カウント: メールアドレスの確認");
+
+    let verification_hash = hex::encode(Sha256::digest(
+        link_token(&sent[0].1, "/email/verify/").as_bytes(),
+    ));
+    {
+        let db = database.lock().await;
+        assert_eq!(
+            db.verify_email(&verification_hash, clock.now())
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("alice@example.com")
+        );
+        assert_eq!(
+            db.verify_email(&verification_hash, clock.now())
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    for _ in 0..2 {
+        send_due_digests(&database, &clock, &mailer, PUBLIC_URL)
+            .await
+            .unwrap();
+    }
+    let sent = mailer.sent();
+    assert_eq!(sent.len(), 2);
+    let digest = &sent[1].1;
+    let unsubscribe_token = link_token(digest, "/email/unsubscribe/");
+    assert_eq!(
+        digest,
+        &Email {
+            subject: "喫煙カウント: 2024/05/13〜2024/05/19の週: 1本".to_string(),
+            body: format!(
+                "合計: 1本（前週比 +1本）\n\
+                 1日平均: 0.1本\n\
+                 - 紙タバコ: 1本（+1本）\n\n\
+                 配信を停止する: {}/email/unsubscribe/{}",
+                PUBLIC_URL, unsubscribe_token
+            ),
+        }
+    );
+
+    // The next week had no logs, so it is summarized against this one.
+    clock.advance(Duration::weeks(1));
+    send_due_digests(&database, &clock, &mailer, PUBLIC_URL)
+        .await
+        .unwrap();
+    assert_eq!(
+        mailer.sent()[2].1.subject,
+        "喫煙カウント: 2024/05/20〜2024/05/26の週: 0本"
+    );
+
+    assert_eq!(
+        database
+            .lock()
+            .await
+            .unsubscribe_email(&unsubscribe_token)
+            .await
+            .unwrap()
+            .as_deref(),
+        Some("alice@example.com")
+    );
+    clock.advance(Duration::weeks(1));
+    send_due_digests(&database, &clock, &mailer, PUBLIC_URL)
+        .await
+        .unwrap();
+    assert_eq!(mailer.sent().len(), 3);
+
+    disable_email(&frontend, &database, "1").await.unwrap();
+    assert_eq!(
+        frontend.calls(),
+        [
+            Recorded::SendReply(Reply::new(
+                "alice@example.com に確認メールを送信しました。メール内のリンクを開くと登録が完了します。"
+            )),
+            Recorded::SendReply(Reply::error("まとめメールは登録されていません。")),
+        ]
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn subscriptions_need_a_valid_address_that_accepts_mail() {
+    let test = setup().await;
+    let database = Mutex::new(Database::new(test.pool.clone()));
+    let frontend = RecordingFrontend::default();
+    let failing = RecordingMailer {
+        failing: true,
+        ..Default::default()
+    };
+
+    for address in ["not-an-address", "alice@example.com"] {
+        subscribe_email(
+            &frontend, &database, &failing, PUBLIC_URL, "1", "alice", address,
+        )
+        .await
+        .unwrap();
+    }
+    disable_email(&frontend, &database, "1").await.unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        [
+            Recorded::SendReply(Reply::error("メールアドレスの形式が正しくありません。")),
+            Recorded::SendReply(Reply::error(
+                "確認メールを送信できませんでした。時間をおいて再度お試しください。"
+            )),
+            Recorded::SendReply(Reply::error("まとめメールは登録されていません。")),
+        ]
+    );
+
+    test.teardown().await;
+}
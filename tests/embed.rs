@@ -0,0 +1,67 @@
+//! Tests for the embeds attached to replies.
+
+use cigarette_counter::{
+    embed::{goal_embed, progress_bar, GoalStatus, Title, BRAND_COLOR, ERROR_COLOR, FOOTER_TEXT},
+    format::Locale,
+    frontend::Reply,
+};
+
+#[test]
+fn progress_bars_fill_toward_the_goal() {
+    assert_eq!(progress_bar(0, 10), "▱▱▱▱▱▱▱▱▱▱");
+    assert_eq!(progress_bar(7, 10), "▰▰▰▰▰▰▰▱▱▱");
+    // A started day never shows as empty, and the bar stops at the goal.
+    assert_eq!(progress_bar(1, 40), "▰▱▱▱▱▱▱▱▱▱");
+    assert_eq!(progress_bar(15, 10), "▰▰▰▰▰▰▰▰▰▰");
+    assert_eq!(progress_bar(0, 0), "▰▰▰▰▰▰▰▰▰▰");
+}
+
+#[test]
+fn goal_status_turns_amber_near_the_goal_and_red_above_it() {
+    assert_eq!(GoalStatus::of(7, 10), GoalStatus::OnTrack);
+    assert_eq!(GoalStatus::of(8, 10), GoalStatus::Close);
+    assert_eq!(GoalStatus::of(10, 10), GoalStatus::Close);
+    assert_eq!(GoalStatus::of(11, 10), GoalStatus::Exceeded);
+}
+
+#[test]
+fn goal_embeds_show_the_remaining_budget() {
+    let embed = goal_embed(7, 10, Locale::Japanese);
+
+    assert_eq!(embed.description, "▰▰▰▰▰▰▰▱▱▱ 7本 / 10本");
+    assert_eq!(embed.color, GoalStatus::OnTrack.color());
+    assert_eq!(embed.footer.as_deref(), Some("残り 3本"));
+
+    let embed = goal_embed(12, 10, Locale::Japanese);
+    assert_eq!(embed.color, GoalStatus::Exceeded.color());
+    assert_eq!(embed.footer.as_deref(), Some("目標を2本超えています"));
+}
+
+#[test]
+fn replies_are_shown_as_styled_embeds_with_localized_titles() {
+    let reply = Reply::new("紙タバコ: 1本")
+        .titled(Title::Confirmation)
+        .embed(goal_embed(1, 10, Locale::EnglishUs));
+
+    let embeds = reply.styled_embeds(Locale::EnglishUs);
+
+    assert_eq!(embeds.len(), 2);
+    assert_eq!(embeds[0].title.as_deref(), Some("Logged"));
+    assert_eq!(embeds[0].description, "紙タバコ: 1本");
+    assert_eq!(embeds[0].color, BRAND_COLOR);
+    assert_eq!(embeds[0].footer.as_deref(), Some(FOOTER_TEXT));
+    assert!(embeds[0].timestamped);
+    assert_eq!(embeds[1].title.as_deref(), Some("Today's goal"));
+    assert_eq!(
+        Reply::new("紙タバコ: 1本").styled_embeds(Locale::Japanese)[0].title,
+        None
+    );
+}
+
+#[test]
+fn errors_are_shown_in_red() {
+    let embeds = Reply::error("不明な種類です: vape").styled_embeds(Locale::Japanese);
+
+    assert_eq!(embeds[0].title.as_deref(), Some("エラー"));
+    assert_eq!(embeds[0].color, ERROR_COLOR);
+}
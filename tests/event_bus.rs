@@ -0,0 +1,93 @@
+//! Tests for the internal event bus and the events published by services.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::{Clock, SystemClock},
+    database::Database,
+    event_bus::{DomainEvent, EventBus},
+    scripting::ScriptHooks,
+    service::LoggingService,
+};
+use common::{create_user, setup};
+use poise::serenity_prelude::futures::lock::Mutex;
+use tokio::sync::broadcast::error::TryRecvError;
+
+#[tokio::test]
+async fn events_reach_every_subscriber() {
+    let bus = EventBus::default();
+    // Publishing without subscribers is a no-op.
+    bus.publish(DomainEvent::LogDeleted {
+        log_id: 1,
+        discord_id: "1".to_string(),
+    });
+
+    let mut first = bus.subscribe();
+    let mut second = bus.subscribe();
+    let event = DomainEvent::LogDeleted {
+        log_id: 2,
+        discord_id: "1".to_string(),
+    };
+    bus.publish(event.clone());
+
+    assert_eq!(first.recv().await.unwrap(), event);
+    assert_eq!(second.recv().await.unwrap(), event);
+    assert!(matches!(first.try_recv(), Err(TryRecvError::Empty)));
+}
+
+#[tokio::test]
+async fn logging_publishes_log_created_and_goal_exceeded_once() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock::new(Tz::UTC));
+    let bus = Arc::new(EventBus::default());
+    let logging = LoggingService::new(
+        database,
+        clock.clone(),
+        Arc::new(ScriptHooks::default()),
+        bus.clone(),
+    );
+    create_user(&test, "1").await;
+    test.db.set_daily_goal("1", Some(2)).await.unwrap();
+    let mut events = bus.subscribe();
+
+    let mut published = Vec::new();
+    for _ in 0..3 {
+        let logged = logging
+            .log_for_user("1", "alice", Some("10"), 1, 1, false, None)
+            .await
+            .unwrap();
+        while let Ok(event) = events.try_recv() {
+            published.push((logged.today_total, event));
+        }
+    }
+
+    let names: Vec<_> = published
+        .iter()
+        .map(|(total, event)| (*total, event.name()))
+        .collect();
+    assert_eq!(
+        names,
+        [
+            (1, "log_created"),
+            (2, "log_created"),
+            (3, "log_created"),
+            (3, "goal_exceeded"),
+        ]
+    );
+    assert_eq!(
+        published[3].1,
+        DomainEvent::GoalExceeded {
+            discord_id: "1".to_string(),
+            guild_id: Some("10".to_string()),
+            date: clock.today(),
+            goal: 2,
+            today_total: 3,
+        }
+    );
+
+    test.teardown().await;
+}
@@ -0,0 +1,131 @@
+//! Tests for custom event tags and their correlation with smoking.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::MockClock,
+    commands::event_tags::{
+        normalize_tag, show_event_analysis, update_event_tag, EventChange, ANALYSIS_DAYS,
+    },
+    database::Database,
+    embed::Title,
+    frontend::Reply,
+    service::StatsService,
+};
+use common::{create_user, log_at, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+fn date(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 5, day).unwrap()
+}
+
+#[test]
+fn tags_are_normalized() {
+    assert_eq!(normalize_tag(" Exam "), Some("exam".to_string()));
+    assert_eq!(normalize_tag("残業"), Some("残業".to_string()));
+    assert_eq!(normalize_tag(""), None);
+    assert_eq!(normalize_tag("late night"), None);
+    assert_eq!(normalize_tag(&"x".repeat(33)), None);
+}
+
+#[tokio::test]
+async fn event_tags_are_added_and_removed() {
+    let test = setup().await;
+    let database = Mutex::new(Database::new(test.pool.clone()));
+    let frontend = RecordingFrontend::default();
+    let today = date(10);
+
+    for (change, tag, day) in [
+        (EventChange::Add, "Exam", None),
+        (EventChange::Add, "exam", Some(date(10))),
+        (EventChange::Add, "exam", Some(date(11))),
+        (EventChange::Remove, "exam", None),
+        (EventChange::Remove, "exam", None),
+    ] {
+        update_event_tag(&frontend, &database, "1", "alice", today, change, tag, day)
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(
+        frontend.calls(),
+        vec![
+            Recorded::SendReply(
+                Reply::new("2024/05/10に「exam」を記録しました。").titled(Title::Settings)
+            ),
+            Recorded::SendReply(
+                Reply::new("2024/05/10には「exam」が記録済みです。").titled(Title::Settings)
+            ),
+            Recorded::SendReply(Reply::error("未来の日付には記録できません。")),
+            Recorded::SendReply(
+                Reply::new("2024/05/10の「exam」を削除しました。").titled(Title::Settings)
+            ),
+            Recorded::SendReply(
+                Reply::new("2024/05/10に「exam」は記録されていません。").titled(Title::Settings)
+            ),
+        ]
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn analysis_compares_days_with_and_without_each_tag() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    for (day, count) in [(8, 4), (9, 1), (10, 6)] {
+        for _ in 0..count {
+            log_at(
+                &test,
+                "1",
+                Utc.with_ymd_and_hms(2024, 5, day, 12, 0, 0).unwrap(),
+            )
+            .await;
+        }
+    }
+    for day in [8, 10] {
+        test.db
+            .add_user_event("1", "exam", date(day))
+            .await
+            .unwrap();
+    }
+    test.db
+        .add_user_event("1", "overtime", date(9))
+        .await
+        .unwrap();
+    // Paused days count neither with nor without the tag.
+    test.db.pause_tracking("1", date(1), date(7)).await.unwrap();
+
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 10, 20, 0).unwrap());
+    let stats = StatsService::new(database, clock);
+    let correlations = stats.event_correlations("1", ANALYSIS_DAYS).await.unwrap();
+
+    assert_eq!(correlations.len(), 2);
+    let exam = &correlations[0];
+    assert_eq!(exam.tag, "exam");
+    assert_eq!((exam.days_with, exam.average_with), (2, 5.0));
+    let other_days = ANALYSIS_DAYS - 7 - 2;
+    assert_eq!(exam.days_without, other_days);
+    assert!((exam.average_without - 1.0 / other_days as f64).abs() < 1e-9);
+    assert_eq!(correlations[1].tag, "overtime");
+    assert_eq!(correlations[1].days_with, 1);
+
+    let frontend = RecordingFrontend::default();
+    show_event_analysis(&frontend, &stats, "2").await.unwrap();
+    assert_eq!(
+        frontend.calls(),
+        vec![Recorded::SendReply(
+            Reply::new(
+                "直近90日のイベント別の1日平均\nイベントが記録されていません。event add <タグ> で記録できます。"
+            )
+            .titled(Title::Stats)
+        )]
+    );
+
+    test.teardown().await;
+}
@@ -0,0 +1,161 @@
+//! Tests for exports of aggregated statistics.
+
+mod common;
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use cigarette_counter::{
+    database::PeriodTypeTotal,
+    export::{aggregate_csv, encode_aggregates, ExportFormat, Granularity},
+};
+use common::{create_user, log_at, setup};
+
+fn date(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+}
+
+#[test]
+fn granularities_are_parsed_by_name() {
+    assert_eq!("week".parse::<Granularity>().unwrap(), Granularity::Week);
+    assert_eq!("Month".parse::<Granularity>().unwrap(), Granularity::Month);
+    assert!("year".parse::<Granularity>().is_err());
+}
+
+#[test]
+fn formats_are_parsed_by_name() {
+    assert_eq!("CSV".parse::<ExportFormat>().unwrap(), ExportFormat::Csv);
+    assert_eq!(
+        "parquet".parse::<ExportFormat>().unwrap(),
+        ExportFormat::Parquet
+    );
+    assert!("xlsx".parse::<ExportFormat>().is_err());
+}
+
+#[cfg(not(feature = "parquet"))]
+#[test]
+fn parquet_needs_the_feature() {
+    use cigarette_counter::export::ExportError;
+
+    assert!(matches!(
+        encode_aggregates(&[], ExportFormat::Parquet),
+        Err(ExportError::ParquetDisabled)
+    ));
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn parquet_follows_the_documented_schema() {
+    use arrow::array::{Date32Array, Int64Array, StringArray};
+    use arrow::datatypes::DataType;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    let totals = [PeriodTypeTotal {
+        period: date(5, 1),
+        type_name: "traditional".to_string(),
+        total_quantity: 12,
+    }];
+    let file = encode_aggregates(&totals, ExportFormat::Parquet).unwrap();
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(file))
+        .unwrap()
+        .build()
+        .unwrap();
+    let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+    let batch = &batches[0];
+    let schema = batch.schema();
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            (
+                field.name().as_str(),
+                field.data_type().clone(),
+                field.is_nullable(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        columns,
+        vec![
+            ("period", DataType::Date32, false),
+            ("type", DataType::Utf8, false),
+            ("quantity", DataType::Int64, false),
+        ]
+    );
+    let period = batch
+        .column(0)
+        .as_any()
+        .downcast_ref::<Date32Array>()
+        .unwrap();
+    assert_eq!(period.value_as_date(0), Some(date(5, 1)));
+    let type_name = batch
+        .column(1)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(type_name.value(0), "traditional");
+    let quantity = batch
+        .column(2)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap();
+    assert_eq!(quantity.value(0), 12);
+}
+
+#[test]
+fn csv_has_one_row_per_period_and_type() {
+    let totals = [
+        PeriodTypeTotal {
+            period: date(5, 1),
+            type_name: "traditional".to_string(),
+            total_quantity: 12,
+        },
+        PeriodTypeTotal {
+            period: date(5, 1),
+            type_name: "menthol, \"light\"".to_string(),
+            total_quantity: 3,
+        },
+    ];
+
+    assert_eq!(
+        aggregate_csv(&totals),
+        "period,type,quantity\n2024-05-01,traditional,12\n2024-05-01,\"menthol, \"\"light\"\"\",3\n"
+    );
+}
+
+#[tokio::test]
+async fn totals_are_grouped_by_granularity() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    // Friday, Sunday and the following Monday
+    for (month, day) in [(5, 31), (6, 2), (6, 3)] {
+        log_at(
+            &test,
+            "1",
+            Utc.with_ymd_and_hms(2024, month, day, 12, 0, 0).unwrap(),
+        )
+        .await;
+    }
+
+    let totals = |granularity| test.db.get_period_type_totals("1", granularity, Tz::UTC);
+    let periods = |totals: Vec<PeriodTypeTotal>| {
+        totals
+            .into_iter()
+            .map(|total| (total.period, total.total_quantity))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        periods(totals(Granularity::Day).await.unwrap()),
+        vec![(date(5, 31), 1), (date(6, 2), 1), (date(6, 3), 1)]
+    );
+    assert_eq!(
+        periods(totals(Granularity::Week).await.unwrap()),
+        vec![(date(5, 27), 2), (date(6, 3), 1)]
+    );
+    assert_eq!(
+        periods(totals(Granularity::Month).await.unwrap()),
+        vec![(date(5, 1), 1), (date(6, 1), 2)]
+    );
+
+    test.teardown().await;
+}
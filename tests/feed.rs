@@ -0,0 +1,173 @@
+//! Tests for the personal Atom feeds of milestones and weekly summaries.
+
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::{Clock, MockClock},
+    commands::feed::{disable_feed, issue_feed_token},
+    database::Database,
+    event_bus::{DomainEvent, EventBus},
+    feed::{feed_entries, render_atom, spawn_milestone_recorder, FeedEntry},
+    frontend::Reply,
+};
+use common::{create_user, log_at, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+use sha2::{Digest, Sha256};
+
+#[tokio::test]
+async fn feeds_list_milestones_and_completed_weeks_newest_first() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    for (month, day) in [(4, 20), (5, 7), (5, 13), (5, 14), (5, 21)] {
+        log_at(
+            &test,
+            "1",
+            Utc.with_ymd_and_hms(2024, month, day, 12, 0, 0).unwrap(),
+        )
+        .await;
+    }
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 20, 9, 0).unwrap());
+    let events = EventBus::default();
+    spawn_milestone_recorder(&events, database.clone(), clock.clone());
+
+    let milestone = DomainEvent::MilestoneReached {
+        discord_id: "1".to_string(),
+        guild_id: "10".to_string(),
+        role_id: "100".to_string(),
+        smoke_free_days: 7,
+    };
+    // The same milestone granted in a second guild is recorded once.
+    events.publish(milestone.clone());
+    events.publish(milestone);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let today = NaiveDate::from_ymd_opt(2024, 5, 22).unwrap();
+    let entries = feed_entries(&database, "1", today, Tz::UTC).await.unwrap();
+
+    assert_eq!(
+        entries,
+        [
+            FeedEntry {
+                id: "urn:cigarette-counter:milestone:1:7:2024-05-20".to_string(),
+                title: "禁煙7日を達成しました".to_string(),
+                updated: clock.now(),
+                lines: vec!["2024/05/20に7日間の禁煙を達成しました。".to_string()],
+            },
+            FeedEntry {
+                id: "urn:cigarette-counter:week:1:2024-05-13".to_string(),
+                title: "2024/05/13〜2024/05/19の週: 2本".to_string(),
+                updated: Utc.with_ymd_and_hms(2024, 5, 20, 0, 0, 0).unwrap(),
+                lines: vec![
+                    "合計: 2本（前週比 +1本）".to_string(),
+                    "1日平均: 0.3本".to_string(),
+                    "- 紙タバコ: 2本（+1本）".to_string(),
+                ],
+            },
+            FeedEntry {
+                id: "urn:cigarette-counter:week:1:2024-05-06".to_string(),
+                title: "2024/05/06〜2024/05/12の週: 1本".to_string(),
+                updated: Utc.with_ymd_and_hms(2024, 5, 13, 0, 0, 0).unwrap(),
+                lines: vec![
+                    "合計: 1本（前週比 +1本）".to_string(),
+                    "1日平均: 0.1本".to_string(),
+                    "- 紙タバコ: 1本（+1本）".to_string(),
+                ],
+            },
+            // Weeks without logs are only listed if the week before had some.
+            FeedEntry {
+                id: "urn:cigarette-counter:week:1:2024-04-22".to_string(),
+                title: "2024/04/22〜2024/04/28の週: 0本".to_string(),
+                updated: Utc.with_ymd_and_hms(2024, 4, 29, 0, 0, 0).unwrap(),
+                lines: vec![
+                    "合計: 0本（前週比 -1本）".to_string(),
+                    "1日平均: 0.0本".to_string(),
+                    "- 紙タバコ: 0本（-1本）".to_string(),
+                ],
+            },
+            FeedEntry {
+                id: "urn:cigarette-counter:week:1:2024-04-15".to_string(),
+                title: "2024/04/15〜2024/04/21の週: 1本".to_string(),
+                updated: Utc.with_ymd_and_hms(2024, 4, 22, 0, 0, 0).unwrap(),
+                lines: vec![
+                    "合計: 1本（前週比 +1本）".to_string(),
+                    "1日平均: 0.1本".to_string(),
+                    "- 紙タバコ: 1本（+1本）".to_string(),
+                ],
+            },
+        ]
+    );
+    assert_eq!(
+        feed_entries(&database, "2", today, Tz::UTC).await.unwrap(),
+        []
+    );
+
+    test.teardown().await;
+}
+
+#[test]
+fn atom_feeds_escape_their_text() {
+    let now = Utc.with_ymd_and_hms(2024, 5, 22, 0, 0, 0).unwrap();
+    let entry = FeedEntry {
+        id: "urn:cigarette-counter:week:1:2024-05-13".to_string(),
+        title: "<b>&</b>".to_string(),
+        updated: Utc.with_ymd_and_hms(2024, 5, 20, 0, 0, 0).unwrap(),
+        lines: vec!["a".to_string(), "\"b\"".to_string()],
+    };
+
+    let xml = render_atom("1", &[entry], now);
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n"));
+    assert!(xml.contains("<id>urn:cigarette-counter:feed:1</id>"));
+    assert!(xml.contains("<updated>2024-05-20T00:00:00+00:00</updated>\n<author>"));
+    assert!(xml.contains("<title>&lt;b&gt;&amp;&lt;/b&gt;</title>"));
+    assert!(xml.contains("<content type=\"text\">a\n&quot;b&quot;</content>"));
+    assert!(xml.ends_with("</entry>\n</feed>\n"));
+
+    let empty = render_atom("1", &[], now);
+    assert!(empty.contains("<updated>2024-05-22T00:00:00+00:00</updated>"));
+    assert!(!empty.contains("<entry>"));
+}
+
+#[tokio::test]
+async fn new_feed_urls_replace_the_previous_one() {
+    let test = setup().await;
+    let database = Mutex::new(Database::new(test.pool.clone()));
+    let frontend = RecordingFrontend::default();
+    let hash = |token: &str| hex::encode(Sha256::digest(token.as_bytes()));
+
+    let first = issue_feed_token(&database, "1", "alice").await.unwrap();
+    let second = issue_feed_token(&database, "1", "alice").await.unwrap();
+    {
+        let db = database.lock().await;
+        assert_eq!(db.find_feed_owner(&hash(&first)).await.unwrap(), None);
+        assert_eq!(
+            db.find_feed_owner(&hash(&second)).await.unwrap().as_deref(),
+            Some("1")
+        );
+    }
+
+    disable_feed(&frontend, &database, "1").await.unwrap();
+    disable_feed(&frontend, &database, "1").await.unwrap();
+    assert_eq!(
+        database
+            .lock()
+            .await
+            .find_feed_owner(&hash(&second))
+            .await
+            .unwrap(),
+        None
+    );
+    assert_eq!(
+        frontend.calls(),
+        [
+            Recorded::SendReply(Reply::new("フィードを無効にしました。")),
+            Recorded::SendReply(Reply::error("フィードは作成されていません。")),
+        ]
+    );
+
+    test.teardown().await;
+}
@@ -0,0 +1,58 @@
+//! Tests for locale-aware number and date formatting.
+
+use chrono::{NaiveDate, NaiveTime};
+use cigarette_counter::format::{
+    format_count, format_date, format_number, format_quiet_confirmation, Locale,
+};
+
+#[test]
+fn numbers_use_the_locale_thousands_separator() {
+    assert_eq!(format_number(0, Locale::Japanese), "0");
+    assert_eq!(format_number(999, Locale::Japanese), "999");
+    assert_eq!(format_number(1000, Locale::Japanese), "1,000");
+    assert_eq!(format_number(1234567, Locale::EnglishUs), "1,234,567");
+    assert_eq!(format_number(1234567, Locale::German), "1.234.567");
+    assert_eq!(format_number(1234, Locale::French), "1\u{202F}234");
+    assert_eq!(format_number(-12345, Locale::Japanese), "-12,345");
+    assert_eq!(
+        format_number(i64::MIN, Locale::Japanese),
+        "-9,223,372,036,854,775,808"
+    );
+}
+
+#[test]
+fn dates_follow_the_locale_convention() {
+    let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+
+    assert_eq!(format_date(date, Locale::Japanese), "2024/05/01");
+    assert_eq!(format_date(date, Locale::EnglishUs), "May 1, 2024");
+    assert_eq!(format_date(date, Locale::EnglishGb), "1 May 2024");
+    assert_eq!(format_date(date, Locale::German), "01.05.2024");
+    assert_eq!(format_date(date, Locale::French), "01/05/2024");
+}
+
+#[test]
+fn discord_locale_tags_are_resolved() {
+    assert_eq!(Locale::from_discord(Some("ja")), Locale::Japanese);
+    assert_eq!(Locale::from_discord(Some("en-US")), Locale::EnglishUs);
+    assert_eq!(Locale::from_discord(Some("en-GB")), Locale::EnglishGb);
+    assert_eq!(Locale::from_discord(Some("de")), Locale::German);
+    assert_eq!(Locale::from_discord(Some("fr")), Locale::French);
+    assert_eq!(Locale::from_discord(Some("ko")), Locale::Japanese);
+    assert_eq!(Locale::from_discord(None), Locale::Japanese);
+}
+
+#[test]
+fn counts_carry_the_unit() {
+    assert_eq!(format_count(3650, Locale::Japanese), "3,650本");
+}
+
+#[test]
+fn quiet_confirmations_show_the_total_and_the_last_press() {
+    let at = NaiveTime::from_hms_opt(9, 5, 59).unwrap();
+
+    assert_eq!(
+        format_quiet_confirmation(8, at, Locale::Japanese),
+        "今日: 8本, 最終 09:05"
+    );
+}
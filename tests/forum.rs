@@ -0,0 +1,116 @@
+//! Tests for the weekly log threads in forum channels.
+
+mod common;
+
+use chrono::NaiveDate;
+use cigarette_counter::{
+    database::{ForumChannel, ForumThread, GuildTypeTotal},
+    forum::{thread_name, week_start, weekly_digest},
+};
+use common::setup;
+
+fn date(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+}
+
+#[test]
+fn weeks_start_on_monday_and_are_named_by_iso_week() {
+    assert_eq!(week_start(date(6, 3)), date(6, 3));
+    assert_eq!(week_start(date(6, 9)), date(6, 3));
+    assert_eq!(week_start(date(6, 10)), date(6, 10));
+    assert_eq!(thread_name(date(6, 3)), "2024年 第23週の喫煙ログ");
+    // The week of New Year's Day belongs to the year with most of its days.
+    assert_eq!(thread_name(date(12, 30)), "2025年 第1週の喫煙ログ");
+}
+
+#[test]
+fn digests_total_the_week_before() {
+    let totals = [GuildTypeTotal {
+        type_name: "iqos".to_string(),
+        description: Some("IQOS".to_string()),
+        total_quantity: 12,
+        member_count: 3,
+    }];
+
+    assert_eq!(
+        weekly_digest(date(6, 10), &totals),
+        "先週（2024/06/03〜2024/06/09）の集計\n- IQOS: 12本（3人）"
+    );
+    assert_eq!(
+        weekly_digest(date(6, 10), &[]),
+        "先週（2024/06/03〜2024/06/09）の集計\n記録はありません。"
+    );
+}
+
+#[tokio::test]
+async fn threads_are_recorded_per_guild_and_week() {
+    let test = setup().await;
+    let guild = test.db.guild("10");
+    let now = chrono::Utc::now();
+
+    assert_eq!(guild.get_forum_channel().await.unwrap(), None);
+    guild.set_forum_channel("500").await.unwrap();
+    guild.set_forum_channel("501").await.unwrap();
+    test.db.guild("20").set_forum_channel("600").await.unwrap();
+    assert_eq!(
+        guild.get_forum_channel().await.unwrap(),
+        Some("501".to_string())
+    );
+    assert_eq!(
+        test.db.get_forum_channels().await.unwrap(),
+        vec![
+            ForumChannel {
+                guild_id: "10".to_string(),
+                channel_id: "501".to_string(),
+            },
+            ForumChannel {
+                guild_id: "20".to_string(),
+                channel_id: "600".to_string(),
+            },
+        ]
+    );
+
+    guild.record_forum_thread(date(6, 3), "700").await.unwrap();
+    guild.record_forum_thread(date(6, 3), "701").await.unwrap();
+    guild.record_forum_thread(date(6, 10), "702").await.unwrap();
+    test.db
+        .guild("20")
+        .record_forum_thread(date(6, 3), "800")
+        .await
+        .unwrap();
+    assert_eq!(
+        guild.get_forum_thread(date(6, 3)).await.unwrap(),
+        Some("700".to_string())
+    );
+    assert_eq!(
+        guild
+            .get_open_forum_threads_before(date(6, 10))
+            .await
+            .unwrap(),
+        vec![ForumThread {
+            week_start: date(6, 3),
+            thread_id: "700".to_string(),
+        }]
+    );
+
+    guild
+        .mark_forum_thread_archived(date(6, 3), now)
+        .await
+        .unwrap();
+    assert_eq!(
+        guild
+            .get_open_forum_threads_before(date(6, 17))
+            .await
+            .unwrap(),
+        vec![ForumThread {
+            week_start: date(6, 10),
+            thread_id: "702".to_string(),
+        }]
+    );
+
+    assert!(guild.remove_forum_channel().await.unwrap());
+    assert!(!guild.remove_forum_channel().await.unwrap());
+    assert_eq!(test.db.get_forum_channels().await.unwrap().len(), 1);
+
+    test.teardown().await;
+}
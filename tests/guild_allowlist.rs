@@ -0,0 +1,13 @@
+//! Tests for the guild allowlist.
+
+use cigarette_counter::guild_allowlist::check;
+use poise::serenity_prelude::GuildId;
+
+#[test]
+fn only_listed_guilds_are_allowed() {
+    assert!(check(&[10, 20], Some(GuildId::new(20))).is_ok());
+    assert!(check(&[10, 20], Some(GuildId::new(30))).is_err());
+    // DMs are not guilds, and an empty list allows every guild.
+    assert!(check(&[10, 20], None).is_ok());
+    assert!(check(&[], Some(GuildId::new(30))).is_ok());
+}
@@ -0,0 +1,301 @@
+//! Tests for guild data archives.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use cigarette_counter::{
+    anonymize::Anonymizer,
+    clock::MockClock,
+    commands::admin::import_archive,
+    database::Database,
+    frontend::Reply,
+    guild_archive::{
+        check_archive, export_guild, ArchivedLog, ArchivedMilestoneRole, ArchivedMonthlyTotal,
+        ArchivedTypeCap, GuildArchive, ImportError, ARCHIVE_FORMAT, ARCHIVE_VERSION,
+    },
+    service::StatsService,
+};
+use common::{create_user, setup, Recorded, RecordingFrontend, TestDatabase};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+/// Logs one unit of a type in a guild at the given instant.
+async fn log_in_guild(
+    test: &TestDatabase,
+    discord_id: &str,
+    guild_id: &str,
+    type_id: i32,
+    at: chrono::DateTime<Utc>,
+) {
+    sqlx::query(
+        "INSERT INTO smoking_logs (discord_id, smoking_type_id, quantity, smoked_at, guild_id) VALUES ($1, $2, 1, $3, $4)",
+    )
+    .bind(discord_id)
+    .bind(type_id)
+    .bind(at)
+    .bind(guild_id)
+    .execute(&test.pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn archives_hold_settings_aggregates_and_shared_logs() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    create_user(&test, "2").await;
+    test.db.set_share_logs_in_exports("1", true).await.unwrap();
+    let guild = test.db.guild("10");
+    guild.set_timezone(Some(Tz::Asia__Tokyo)).await.unwrap();
+    guild.set_type_cap(2, 10).await.unwrap();
+    guild.set_panel_types(Some(&[1, 2])).await.unwrap();
+    guild.set_milestone_role("300", 7).await.unwrap();
+    guild.add_smoke_break_channel("400").await.unwrap();
+    // May 31 in UTC, June 1 in Tokyo.
+    let june = Utc.with_ymd_and_hms(2024, 5, 31, 20, 0, 0).unwrap();
+    log_in_guild(&test, "1", "10", 1, june).await;
+    log_in_guild(&test, "2", "10", 1, june).await;
+    log_in_guild(&test, "2", "10", 2, june).await;
+    // Another guild's logs stay out.
+    log_in_guild(&test, "1", "20", 1, june).await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let now = Utc.with_ymd_and_hms(2024, 6, 2, 0, 0, 0).unwrap();
+
+    let archive = export_guild(&database, "10", Tz::UTC, now).await.unwrap();
+
+    assert_eq!(archive.format, ARCHIVE_FORMAT);
+    assert_eq!(archive.version, ARCHIVE_VERSION);
+    assert_eq!(archive.guild_id, "10");
+    assert_eq!(archive.exported_at, now);
+    assert_eq!(archive.settings.timezone.as_deref(), Some("Asia/Tokyo"));
+    assert_eq!(
+        archive.settings.panel_types,
+        Some(vec!["traditional".to_string(), "iqos".to_string()])
+    );
+    assert_eq!(
+        archive.settings.type_caps,
+        vec![ArchivedTypeCap {
+            type_name: "iqos".to_string(),
+            daily_cap: 10
+        }]
+    );
+    assert_eq!(
+        archive.settings.milestone_roles,
+        vec![ArchivedMilestoneRole {
+            role_id: "300".to_string(),
+            smoke_free_days: 7
+        }]
+    );
+    assert_eq!(archive.settings.smoke_break_channels, vec!["400"]);
+    assert_eq!(archive.types.len(), 5);
+    let june_first = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+    assert_eq!(
+        archive.monthly_totals,
+        vec![
+            ArchivedMonthlyTotal {
+                month: june_first,
+                type_name: "traditional".to_string(),
+                total_quantity: 2,
+                member_count: 2,
+            },
+            ArchivedMonthlyTotal {
+                month: june_first,
+                type_name: "iqos".to_string(),
+                total_quantity: 1,
+                member_count: 1,
+            },
+        ]
+    );
+    assert_eq!(archive.members.len(), 1);
+    assert_eq!(archive.members[0].discord_id, "1");
+    assert_eq!(archive.members[0].username.as_deref(), Some("user-1"));
+    assert_eq!(
+        archive.members[0].logs,
+        vec![ArchivedLog {
+            type_name: "traditional".to_string(),
+            quantity: 1,
+            smoked_at: june,
+        }]
+    );
+    // The archive survives a round trip through JSON.
+    let json = serde_json::to_string(&archive).unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&json).unwrap()["version"],
+        1
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn archives_anonymize_ids_when_configured() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    test.db.set_share_logs_in_exports("1", true).await.unwrap();
+    log_in_guild(&test, "1", "10", 1, Utc::now()).await;
+    let anonymizer = Anonymizer::new(Some("salt".to_string()));
+    let database = Arc::new(Mutex::new(
+        Database::new(test.pool.clone()).with_anonymizer(anonymizer.clone()),
+    ));
+
+    let archive = export_guild(&database, "10", Tz::UTC, Utc::now())
+        .await
+        .unwrap();
+
+    assert_eq!(archive.guild_id, anonymizer.id("10"));
+    assert_eq!(archive.members[0].discord_id, anonymizer.id("1"));
+    assert_eq!(archive.members[0].username, None);
+
+    test.teardown().await;
+}
+
+/// Builds an archive of guild 10 with one shared log of each of two members and some aggregates.
+async fn sample_archive(test: &TestDatabase) -> GuildArchive {
+    create_user(test, "1").await;
+    create_user(test, "2").await;
+    create_user(test, "3").await;
+    test.db.set_share_logs_in_exports("1", true).await.unwrap();
+    test.db.set_share_logs_in_exports("2", true).await.unwrap();
+    let guild = test.db.guild("10");
+    guild.set_type_cap(2, 10).await.unwrap();
+    guild.set_panel_types(Some(&[2])).await.unwrap();
+    let at = Utc.with_ymd_and_hms(2024, 5, 10, 12, 0, 0).unwrap();
+    log_in_guild(test, "1", "10", 1, at).await;
+    log_in_guild(test, "2", "10", 2, at).await;
+    log_in_guild(test, "3", "10", 2, at).await;
+    let database = Mutex::new(Database::new(test.pool.clone()));
+
+    export_guild(&database, "10", Tz::UTC, at).await.unwrap()
+}
+
+#[tokio::test]
+async fn archives_move_to_a_fresh_instance() {
+    let source = setup().await;
+    let mut archive = sample_archive(&source).await;
+    // A type only the old instance knows is added on import.
+    archive.types[1].type_name = "heets".to_string();
+    for name in archive
+        .settings
+        .panel_types
+        .iter_mut()
+        .flatten()
+        .chain(
+            archive
+                .settings
+                .type_caps
+                .iter_mut()
+                .map(|cap| &mut cap.type_name),
+        )
+        .chain(
+            archive
+                .monthly_totals
+                .iter_mut()
+                .map(|total| &mut total.type_name),
+        )
+        .chain(
+            archive.members[1]
+                .logs
+                .iter_mut()
+                .map(|log| &mut log.type_name),
+        )
+    {
+        if name == "iqos" {
+            *name = "heets".to_string();
+        }
+    }
+    let contents = serde_json::to_vec(&archive).unwrap();
+    source.teardown().await;
+
+    let target = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(target.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 6, 1, 0, 0).unwrap());
+    let stats = StatsService::new(database.clone(), clock);
+    let frontend = RecordingFrontend::default();
+
+    import_archive(&frontend, &database, &stats, "99", &contents)
+        .await
+        .unwrap();
+    // A second import would duplicate the logs.
+    import_archive(&frontend, &database, &stats, "99", &contents)
+        .await
+        .unwrap();
+    import_archive(&frontend, &database, &stats, "99", b"not json")
+        .await
+        .unwrap();
+
+    let calls = frontend.calls();
+    assert_eq!(
+        calls[..2],
+        [
+            Recorded::SendReply(Reply::new(
+                "サーバー10のデータを取り込みました。\n追加した種類: 1件、記録: 2人分2件\n記録の共有を許可していないメンバーの1本は集計のみのため取り込まれません。"
+            )),
+            Recorded::SendReply(Reply::error(
+                "アーカイブを取り込めません: Guild 10 already has logs on this instance"
+            )),
+        ]
+    );
+    assert!(matches!(
+        &calls[2],
+        Recorded::SendReply(reply) if reply.content.starts_with("アーカイブを読み込めません: ")
+    ));
+
+    let heets = target
+        .db
+        .find_smoking_type_by_name("heets")
+        .await
+        .unwrap()
+        .unwrap();
+    let guild = target.db.guild("10");
+    assert_eq!(guild.get_user_ids().await.unwrap(), vec!["1", "2"]);
+    assert_eq!(
+        guild.get_settings().await.unwrap().panel_smoking_type_ids,
+        Some(vec![heets.id])
+    );
+    assert_eq!(guild.get_type_cap(heets.id).await.unwrap(), Some(10));
+    let audit = target.db.get_admin_audit_entries(10).await.unwrap();
+    assert_eq!(audit[0].action, "import_guild");
+
+    target.teardown().await;
+}
+
+#[tokio::test]
+async fn inconsistent_archives_are_rejected() {
+    let test = setup().await;
+    let archive = sample_archive(&test).await;
+    assert!(check_archive(&archive).is_ok());
+
+    let mut anonymized = archive.clone();
+    anonymized.members[0].discord_id = "ab12".to_string();
+    assert!(matches!(
+        check_archive(&anonymized),
+        Err(ImportError::InvalidId(id)) if id == "ab12"
+    ));
+
+    let mut missing_type = archive.clone();
+    missing_type
+        .types
+        .retain(|archived| archived.type_name != "iqos");
+    assert!(matches!(
+        check_archive(&missing_type),
+        Err(ImportError::MissingType(name)) if name == "iqos"
+    ));
+
+    let mut inflated = archive.clone();
+    inflated.members[0].logs[0].quantity = 5;
+    assert!(matches!(
+        check_archive(&inflated),
+        Err(ImportError::TotalsMismatch { type_name, .. }) if type_name == "traditional"
+    ));
+
+    let mut newer = archive;
+    newer.version = ARCHIVE_VERSION + 1;
+    assert!(matches!(
+        check_archive(&newer),
+        Err(ImportError::UnsupportedVersion(_))
+    ));
+
+    test.teardown().await;
+}
@@ -0,0 +1,241 @@
+//! Tests guaranteeing that one guild's data is never visible to another.
+//!
+//! Guild-scoped queries live on `GuildScope`; the review test below fails
+//! when a query on guild data is added anywhere else without being listed
+//! as a deliberate global query.
+
+mod common;
+
+use std::sync::Arc;
+
+use cigarette_counter::{
+    commands::templates::update_message_template,
+    database::Database,
+    embed::Title,
+    frontend::Reply,
+    templates::{load_template, Revision, Template, TemplateKey},
+};
+use common::{create_user, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+/// Tables whose rows belong to a guild
+const GUILD_TABLES: &[&str] = &[
+    "milestone_roles",
+    "message_templates",
+    "smoke_break_channels",
+    "guild_acknowledgment",
+    "guild_type_caps",
+    "guild_confirmation_reactions",
+    "forum_channels",
+    "forum_threads",
+    "guild_challenges",
+    "guild_challenge_weeks",
+    "guild_achievements",
+    "earned_achievements",
+    "leaderboard_settings",
+    "leaderboard_seasons",
+    "season_standings",
+    "smoke_free_board_members",
+    "channel_counters",
+    "confirmation_cleanup_settings",
+    "confirmation_messages",
+    "guild_removals",
+];
+
+/// Queries on `Database` that deliberately span guilds (background tasks and owner-only fixes)
+const GLOBAL_QUERIES: &[&str] = &[
+    "get_milestone_guilds",
+    "get_forum_channels",
+    "get_guild_challenges",
+    "get_achievement_guilds",
+    "get_leaderboard_settings",
+    "get_channel_counters",
+    "get_expired_confirmations",
+    "forget_confirmations",
+    "get_due_guild_deletions",
+    "get_posted_panels",
+    "merge_smoking_types",
+];
+
+/// A SQL literal in `database.rs` and the function it belongs to
+struct Query<'a> {
+    function: &'a str,
+    sql: &'a str,
+    arguments: &'a str,
+    in_scope: bool,
+}
+
+/// Extracts every SQL literal from the database module.
+fn queries(source: &str) -> Vec<Query<'_>> {
+    let scope_start = source
+        .find("impl GuildScope")
+        .expect("GuildScope impl exists");
+    let scope_end = scope_start + source[scope_start..].find("\n}\n").unwrap();
+
+    let mut queries = Vec::new();
+    let mut rest = 0;
+    while let Some(offset) = source[rest..].find("r#\"") {
+        let start = rest + offset + 3;
+        let end = start + source[start..].find("\"#").unwrap();
+        let arguments_end = end + source[end..].find("\n        )").unwrap_or(0);
+        let function = source[..start]
+            .rfind("fn ")
+            .map(|fn_start| {
+                let name = &source[fn_start + 3..];
+                &name[..name.find(['(', '<']).unwrap()]
+            })
+            .unwrap();
+
+        queries.push(Query {
+            function,
+            sql: &source[start..end],
+            arguments: &source[end..arguments_end],
+            in_scope: (scope_start..scope_end).contains(&start),
+        });
+        rest = end + 2;
+    }
+
+    queries
+}
+
+#[test]
+fn guild_data_is_only_queried_through_guild_scope() {
+    let source = include_str!("../src/database.rs");
+    let queries = queries(source);
+    assert!(queries.iter().any(|query| query.in_scope));
+
+    for query in queries {
+        let touches_guild_data = GUILD_TABLES.iter().any(|table| query.sql.contains(table))
+            || query.sql.contains("WHERE guild_id");
+
+        if query.in_scope {
+            assert!(
+                query.sql.contains("guild_id") && query.arguments.contains("self.guild_id"),
+                "GuildScope::{} does not filter on the scope's guild",
+                query.function
+            );
+        } else {
+            assert!(
+                !touches_guild_data || GLOBAL_QUERIES.contains(&query.function),
+                "Database::{} queries guild data outside GuildScope",
+                query.function
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn guild_settings_are_isolated() {
+    let test = setup().await;
+    let (first, second) = (test.db.guild("10"), test.db.guild("20"));
+
+    first.set_milestone_role("100", 7).await.unwrap();
+    assert!(second.get_milestone_roles().await.unwrap().is_empty());
+    assert!(!second.remove_milestone_role("100").await.unwrap());
+    assert_eq!(first.get_milestone_roles().await.unwrap().len(), 1);
+
+    first.add_smoke_break_channel("500").await.unwrap();
+    assert!(!second.is_smoke_break_channel("500").await.unwrap());
+    assert!(!second.remove_smoke_break_channel("500").await.unwrap());
+    assert!(first.is_smoke_break_channel("500").await.unwrap());
+
+    first
+        .set_message_template("milestone", "{user}", None)
+        .await
+        .unwrap();
+    assert!(second.get_message_templates().await.unwrap().is_empty());
+    assert_eq!(
+        second
+            .get_message_template_version("milestone")
+            .await
+            .unwrap(),
+        0
+    );
+    assert!(!second
+        .remove_message_template("milestone", None)
+        .await
+        .unwrap());
+    assert_eq!(
+        load_template(&test.db, Some("20"), TemplateKey::Milestone)
+            .await
+            .unwrap(),
+        Template::default_for(TemplateKey::Milestone)
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn guild_member_lists_only_include_the_guilds_logs() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    create_user(&test, "2").await;
+    test.db.log_smoking("1", Some("10"), 1, 1).await.unwrap();
+    test.db.log_smoking("2", Some("20"), 1, 1).await.unwrap();
+    test.db.log_smoking("2", None, 1, 1).await.unwrap();
+
+    assert_eq!(test.db.guild("10").get_user_ids().await.unwrap(), ["1"]);
+    assert_eq!(test.db.guild("20").get_user_ids().await.unwrap(), ["2"]);
+    assert!(test.db.guild("30").get_user_ids().await.unwrap().is_empty());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn template_commands_only_change_their_own_guild() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let frontend = RecordingFrontend::default();
+
+    for guild_id in ["10", "20"] {
+        update_message_template(
+            &frontend,
+            &database,
+            guild_id,
+            TemplateKey::Confirmation,
+            Some("{user}"),
+            Some(Revision(0)),
+        )
+        .await
+        .unwrap();
+    }
+    update_message_template(
+        &frontend,
+        &database,
+        "20",
+        TemplateKey::Confirmation,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        [
+            Recorded::SendReply(
+                Reply::new("テンプレート「confirmation」を設定しました（v1）。")
+                    .titled(Title::Settings)
+            ),
+            Recorded::SendReply(
+                Reply::new("テンプレート「confirmation」を設定しました（v1）。")
+                    .titled(Title::Settings)
+            ),
+            Recorded::SendReply(
+                Reply::new("テンプレート「confirmation」を既定に戻しました。")
+                    .titled(Title::Settings)
+            ),
+        ]
+    );
+    assert_eq!(
+        test.db
+            .guild("10")
+            .get_message_template("confirmation")
+            .await
+            .unwrap()
+            .as_deref(),
+        Some("{user}")
+    );
+
+    test.teardown().await;
+}
@@ -0,0 +1,149 @@
+//! Tests for pausing and deleting the data of guilds the bot was removed from.
+
+mod common;
+
+use std::time::Duration as StdDuration;
+
+use chrono::{Duration, TimeZone, Utc};
+use cigarette_counter::{
+    database::{Database, GuildRemoval},
+    guild_removal::{delete_due_guilds, mark_guild_removed},
+};
+use common::{create_user, log_at, setup};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+/// A week, as `GUILD_DATA_RETENTION_DAYS=7` configures it
+const WEEK: StdDuration = StdDuration::from_secs(7 * 24 * 60 * 60);
+
+#[tokio::test]
+async fn removed_guilds_are_left_out_of_background_work() {
+    let test = setup().await;
+    let database = Mutex::new(Database::new(test.pool.clone()));
+    let removed_at = Utc.with_ymd_and_hms(2024, 6, 10, 12, 0, 0).unwrap();
+
+    for guild_id in ["10", "20"] {
+        let guild = test.db.guild(guild_id);
+        guild.set_milestone_role("100", 7).await.unwrap();
+        guild
+            .set_channel_counter("100", "daily-total", "topic")
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(
+        mark_guild_removed(&database, "10", removed_at, None)
+            .await
+            .unwrap(),
+        None
+    );
+    assert_eq!(
+        test.db.guild("10").get_removal().await.unwrap(),
+        Some(GuildRemoval {
+            guild_id: "10".to_string(),
+            removed_at,
+            delete_after: None,
+        })
+    );
+    assert_eq!(test.db.get_milestone_guilds().await.unwrap(), ["20"]);
+    assert_eq!(test.db.get_channel_counters().await.unwrap().len(), 1);
+
+    // Rejoining resumes the guild with its settings intact.
+    assert!(test.db.guild("10").unmark_removed().await.unwrap());
+    assert!(!test.db.guild("10").unmark_removed().await.unwrap());
+    assert_eq!(test.db.get_milestone_guilds().await.unwrap(), ["10", "20"]);
+    assert_eq!(test.db.get_channel_counters().await.unwrap().len(), 2);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn data_is_deleted_after_the_grace_period() {
+    let test = setup().await;
+    let database = Mutex::new(Database::new(test.pool.clone()));
+    let removed_at = Utc.with_ymd_and_hms(2024, 6, 10, 12, 0, 0).unwrap();
+
+    create_user(&test, "1").await;
+    log_at(&test, "1", removed_at - Duration::days(1)).await;
+    sqlx::query("UPDATE smoking_logs SET guild_id = '10'")
+        .execute(&test.pool)
+        .await
+        .unwrap();
+    for guild_id in ["10", "20"] {
+        test.db
+            .guild(guild_id)
+            .set_milestone_role("100", 7)
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(
+        mark_guild_removed(&database, "10", removed_at, Some(WEEK))
+            .await
+            .unwrap(),
+        Some(removed_at + Duration::days(7))
+    );
+    // Without a retention policy the data is kept.
+    mark_guild_removed(&database, "20", removed_at, None)
+        .await
+        .unwrap();
+
+    assert!(delete_due_guilds(&database, removed_at + Duration::days(6))
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        delete_due_guilds(&database, removed_at + Duration::days(7))
+            .await
+            .unwrap(),
+        ["10"]
+    );
+
+    let guild = test.db.guild("10");
+    assert!(guild.get_milestone_roles().await.unwrap().is_empty());
+    assert_eq!(guild.get_removal().await.unwrap(), None);
+    assert_eq!(guild.get_last_smoked_at().await.unwrap(), None);
+    let (logs,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM smoking_logs")
+        .fetch_one(&test.pool)
+        .await
+        .unwrap();
+    assert_eq!(logs, 1);
+    assert_eq!(
+        test.db
+            .guild("20")
+            .get_milestone_roles()
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn rejoining_cancels_the_deletion() {
+    let test = setup().await;
+    let database = Mutex::new(Database::new(test.pool.clone()));
+    let removed_at = Utc.with_ymd_and_hms(2024, 6, 10, 12, 0, 0).unwrap();
+    let guild = test.db.guild("10");
+
+    guild.set_milestone_role("100", 7).await.unwrap();
+    mark_guild_removed(&database, "10", removed_at, Some(WEEK))
+        .await
+        .unwrap();
+    guild.unmark_removed().await.unwrap();
+
+    assert!(
+        delete_due_guilds(&database, removed_at + Duration::days(30))
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(!guild
+        .delete_removed_data(removed_at + Duration::days(30))
+        .await
+        .unwrap());
+    assert_eq!(guild.get_milestone_roles().await.unwrap().len(), 1);
+
+    test.teardown().await;
+}
@@ -0,0 +1,99 @@
+//! Tests for the heartbeat and the `uptime` report.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{SubsecRound, TimeZone, Utc};
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::MockClock,
+    commands::status::{render_uptime, show_uptime},
+    database::Database,
+    frontend::Reply,
+    heartbeat::{self, HeartbeatReport},
+};
+use common::{setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+#[tokio::test]
+async fn probing_measures_how_long_due_jobs_have_waited() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let now = Utc::now().trunc_subsecs(0);
+    let clock = MockClock::new(Tz::UTC, now);
+
+    assert_eq!(
+        heartbeat::probe(&database, &clock).await.unwrap(),
+        Duration::ZERO
+    );
+
+    test.db
+        .schedule_job("count", "{}", now - chrono::Duration::minutes(3))
+        .await
+        .unwrap();
+    test.db
+        .schedule_job("count", "{}", now - chrono::Duration::minutes(1))
+        .await
+        .unwrap();
+    // Jobs that are not due yet are not late.
+    test.db
+        .schedule_job("count", "{}", now + chrono::Duration::hours(1))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        heartbeat::probe(&database, &clock).await.unwrap(),
+        Duration::from_secs(180)
+    );
+    let report = heartbeat::report();
+    assert_eq!(report.last_db_success, Some(now));
+    assert_eq!(report.scheduler_lag, Some(Duration::from_secs(180)));
+    let metrics = heartbeat::render_metrics();
+    assert!(metrics.contains("cigarette_counter_scheduler_lag_seconds 180\n"));
+    assert!(metrics.contains(&format!(
+        "cigarette_counter_last_db_success_timestamp_seconds {}\n",
+        now.timestamp()
+    )));
+    assert!(metrics.contains("cigarette_counter_uptime_seconds "));
+
+    test.teardown().await;
+}
+
+#[test]
+fn the_report_lists_every_value() {
+    let now = Utc.with_ymd_and_hms(2024, 5, 3, 12, 0, 0).unwrap();
+    let report = HeartbeatReport {
+        uptime: Duration::from_secs(2 * 86400 + 3 * 3600 + 5),
+        gateway_latency: Some(Duration::from_millis(42)),
+        last_db_success: Some(now - chrono::Duration::seconds(12)),
+        scheduler_lag: Some(Duration::from_secs(90)),
+    };
+
+    assert_eq!(
+        render_uptime(&report, now),
+        "稼働時間: 2日 3時間 0分 5秒\nGatewayの遅延: 42ms\n最後に成功したDBクエリ: 12秒前\nスケジューラーの遅延: 1分 30秒"
+    );
+}
+
+#[tokio::test]
+async fn unknown_values_are_reported_as_such() {
+    let now = Utc.with_ymd_and_hms(2024, 5, 3, 12, 0, 0).unwrap();
+    let frontend = RecordingFrontend::default();
+    let report = HeartbeatReport {
+        uptime: Duration::from_secs(7),
+        gateway_latency: None,
+        last_db_success: None,
+        scheduler_lag: None,
+    };
+
+    show_uptime(&frontend, &report, now).await.unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        vec![Recorded::SendReply(Reply::new(
+            "稼働時間: 7秒\nGatewayの遅延: 不明\n最後に成功したDBクエリ: まだありません\nスケジューラーの遅延: 不明"
+        ))]
+    );
+}
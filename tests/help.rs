@@ -0,0 +1,90 @@
+//! Tests for the interactive help.
+
+use cigarette_counter::{
+    commands::{
+        enabled_commands,
+        help::{find_entry, help_page, with_help_button, HelpCategory, HelpId, HELP_ENTRIES},
+        Command,
+    },
+    embed::Title,
+    format::Locale,
+    frontend::Reply,
+};
+
+/// Collects the qualified names of the commands, with whether they have no subcommands.
+fn command_names(commands: &[Command], parent: Option<&str>, names: &mut Vec<(String, bool)>) {
+    for command in commands {
+        let name = match parent {
+            Some(parent) => format!("{} {}", parent, command.name),
+            None => command.name.clone(),
+        };
+        names.push((name.clone(), command.subcommands.is_empty()));
+        command_names(&command.subcommands, Some(&name), names);
+    }
+}
+
+fn all_commands() -> Vec<(String, bool)> {
+    let mut names = Vec::new();
+    command_names(&enabled_commands(&[]), None, &mut names);
+    names
+}
+
+#[test]
+fn help_ids_round_trip() {
+    assert_eq!(HelpId::parse(&HelpId::Menu.custom_id()), Some(HelpId::Menu));
+    for category in HelpCategory::ALL {
+        let id = HelpId::Category(category);
+        assert_eq!(HelpId::parse(&id.custom_id()), Some(id));
+    }
+    assert_eq!(HelpId::parse("help:unknown"), None);
+    assert_eq!(HelpId::parse("settings:menu"), None);
+}
+
+#[test]
+fn every_command_has_help() {
+    for (name, _) in all_commands().into_iter().filter(|(_, leaf)| *leaf) {
+        assert!(find_entry(&name).is_some(), "{} has no help entry", name);
+    }
+}
+
+#[test]
+fn every_help_entry_names_a_command() {
+    let names = all_commands();
+    for entry in HELP_ENTRIES {
+        assert!(
+            names.iter().any(|(name, _)| name == entry.command),
+            "{} is not a command",
+            entry.command
+        );
+    }
+}
+
+#[test]
+fn pages_show_examples_of_enabled_commands() {
+    let enabled = vec!["stats".to_string(), "help".to_string()];
+
+    let page = help_page(HelpCategory::Stats, "c:", &enabled, Locale::Japanese);
+    assert_eq!(page.title, Some(Title::Help));
+    assert!(page.content.contains("`c:stats month`"));
+    assert!(page.content.contains("今月の日ごとの合計"));
+    assert!(!page.content.contains("export aggregates"));
+    assert_eq!(page.selects.len(), 1);
+    assert_eq!(page.selects[0].custom_id, HelpId::Menu.custom_id());
+
+    let page = help_page(HelpCategory::Logging, "c:", &enabled, Locale::EnglishUs);
+    assert!(page.content.contains("Example: `c:help stats month`"));
+    assert!(!page.content.contains("create_cigarette_ui"));
+}
+
+#[test]
+fn errors_link_to_the_command_category() {
+    let reply = with_help_button(Reply::error("failed"), "cap set", Locale::Japanese);
+    assert_eq!(reply.buttons.len(), 1);
+    assert_eq!(
+        reply.buttons[0].custom_id,
+        HelpId::Category(HelpCategory::Server).custom_id()
+    );
+
+    let reply = with_help_button(Reply::error("failed"), "unknown", Locale::Japanese);
+    assert!(reply.buttons.is_empty());
+}
@@ -0,0 +1,128 @@
+//! Tests for the leaderboards by reduction percentage.
+
+mod common;
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use chrono_tz::UTC;
+use cigarette_counter::{
+    clock::MockClock,
+    commands::leaderboard::improvement_reply,
+    database::MemberPeriodTotals,
+    improvement::{
+        comparison_heading, improvement_lines, rank_improvements, ImprovementOrder,
+        ImprovementPeriod,
+    },
+};
+use common::{create_user, log_at, setup};
+
+fn date(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+}
+
+fn totals(discord_id: &str, previous: i64, current: i64) -> MemberPeriodTotals {
+    MemberPeriodTotals {
+        discord_id: discord_id.to_string(),
+        previous,
+        current,
+    }
+}
+
+#[test]
+fn the_last_two_completed_periods_are_compared() {
+    // Wednesday, June 12th
+    assert_eq!(
+        ImprovementPeriod::Week.completed(date(6, 12)),
+        (date(5, 27), date(6, 3), date(6, 10))
+    );
+    assert_eq!(
+        ImprovementPeriod::Month.completed(date(6, 12)),
+        (date(4, 1), date(5, 1), date(6, 1))
+    );
+    assert_eq!(
+        comparison_heading(ImprovementPeriod::Week, date(6, 12)),
+        "2024/06/03〜2024/06/09 と前の週の比較"
+    );
+    assert_eq!(ImprovementPeriod::from_name("year"), None);
+}
+
+#[test]
+fn members_are_ranked_by_reduction_percentage() {
+    let members = [
+        totals("1", 40, 30),
+        totals("2", 4, 2),
+        totals("3", 10, 12),
+        totals("4", 0, 5),
+        totals("5", 20, 10),
+    ];
+
+    let most = rank_improvements(&members, ImprovementOrder::Most);
+    assert_eq!(
+        most.iter()
+            .map(|improvement| (improvement.discord_id.as_str(), improvement.rank))
+            .collect::<Vec<_>>(),
+        vec![("2", 1), ("5", 1), ("1", 3), ("3", 4)]
+    );
+    assert_eq!(
+        improvement_lines(&most, 10),
+        vec![
+            "1位 <@2> 50%減（4本 → 2本）",
+            "1位 <@5> 50%減（20本 → 10本）",
+            "3位 <@1> 25%減（40本 → 30本）",
+            "4位 <@3> 20%増（10本 → 12本）",
+        ]
+    );
+
+    let least = rank_improvements(&members, ImprovementOrder::Least);
+    assert_eq!(least[0].discord_id, "3");
+    assert_eq!(improvement_lines(&least, 1).len(), 1);
+}
+
+#[tokio::test]
+async fn improvement_is_computed_from_the_guilds_logs() {
+    let test = setup().await;
+    let guild = test.db.guild("10");
+
+    for user_id in ["1", "2"] {
+        create_user(&test, user_id).await;
+    }
+    // Member 1 halves from 4 to 2, member 2 goes from 2 to 3; logs of other guilds do not count.
+    for (user_id, day, count) in [("1", 28, 4), ("1", 4, 2), ("2", 29, 2), ("2", 5, 3)] {
+        let month = if day > 20 { 5 } else { 6 };
+        for _ in 0..count {
+            log_at(
+                &test,
+                user_id,
+                Utc.with_ymd_and_hms(2024, month, day, 12, 0, 0).unwrap(),
+            )
+            .await;
+        }
+    }
+    sqlx::query("UPDATE smoking_logs SET guild_id = '10'")
+        .execute(&test.pool)
+        .await
+        .unwrap();
+    log_at(
+        &test,
+        "1",
+        Utc.with_ymd_and_hms(2024, 6, 4, 12, 0, 0).unwrap(),
+    )
+    .await;
+
+    let clock = MockClock::new(UTC, Utc.with_ymd_and_hms(2024, 6, 12, 9, 0, 0).unwrap());
+    let reply = improvement_reply(&guild, &clock, None, ImprovementOrder::Most)
+        .await
+        .unwrap();
+    assert_eq!(
+        reply.content,
+        "2024/06/03〜2024/06/09 と前の週の比較（減らした割合が大きい順）\n1位 <@1> 50%減（4本 → 2本）\n2位 <@2> 50%増（2本 → 3本）\n前の期間に記録のないメンバーは含まれません。"
+    );
+    assert!(
+        improvement_reply(&guild, &clock, Some("year"), ImprovementOrder::Least)
+            .await
+            .unwrap()
+            .content
+            .starts_with("期間を指定してください")
+    );
+
+    test.teardown().await;
+}
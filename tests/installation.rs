@@ -0,0 +1,61 @@
+//! Tests for telling guild installs from user installs.
+
+use cigarette_counter::{
+    commands::{command_access, CommandAccess},
+    installation::installed_guild,
+};
+use poise::serenity_prelude::{
+    AuthorizingIntegrationOwner, AuthorizingIntegrationOwners, GuildId, UserId,
+};
+
+fn owners(owners: Vec<AuthorizingIntegrationOwner>) -> AuthorizingIntegrationOwners {
+    AuthorizingIntegrationOwners(owners)
+}
+
+#[test]
+fn guilds_count_only_where_the_bot_is_installed() {
+    let guild = GuildId::new(10);
+    let user_install = AuthorizingIntegrationOwner::UserInstall(UserId::new(1));
+
+    assert_eq!(
+        installed_guild(
+            Some(guild),
+            &owners(vec![AuthorizingIntegrationOwner::GuildInstall(Some(guild))])
+        ),
+        Some(guild)
+    );
+    // Both installs authorize interactions in a guild the bot was added to.
+    assert_eq!(
+        installed_guild(
+            Some(guild),
+            &owners(vec![
+                user_install.clone(),
+                AuthorizingIntegrationOwner::GuildInstall(Some(guild)),
+            ])
+        ),
+        Some(guild)
+    );
+    assert_eq!(
+        installed_guild(Some(guild), &owners(vec![user_install.clone()])),
+        None
+    );
+    assert_eq!(installed_guild(None, &owners(vec![user_install])), None);
+    assert_eq!(
+        installed_guild(Some(guild), &owners(Vec::new())),
+        Some(guild)
+    );
+}
+
+#[test]
+fn personal_commands_are_available_through_user_installs() {
+    assert_eq!(command_access("panel"), CommandAccess::PERSONAL);
+    assert_eq!(command_access("stats"), CommandAccess::PERSONAL);
+    assert_eq!(command_access("set_goal"), CommandAccess::PERSONAL);
+
+    let json = serde_json::to_value(
+        CommandAccess::PERSONAL.apply(poise::serenity_prelude::CreateCommand::new("panel")),
+    )
+    .unwrap();
+    assert_eq!(json["contexts"], serde_json::json!([0, 1, 2]));
+    assert_eq!(json["integration_types"], serde_json::json!([0, 1]));
+}
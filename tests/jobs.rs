@@ -0,0 +1,223 @@
+//! Tests for the scheduled job worker and the owners' job commands.
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, SubsecRound, Utc};
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::MockClock,
+    commands::admin::{cancel_scheduled_job, list_jobs},
+    database::{Database, ScheduledJob},
+    frontend::Reply,
+    jobs::{JobHandler, JobWorker, MAX_ATTEMPTS},
+    Error,
+};
+use common::{setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+/// Counts its runs, failing every run if asked to
+#[derive(Default)]
+struct CountingJob {
+    runs: AtomicUsize,
+    fail: bool,
+    every: Option<Duration>,
+}
+
+#[async_trait]
+impl JobHandler for CountingJob {
+    async fn run(&self, _job: &ScheduledJob) -> Result<(), Error> {
+        self.runs.fetch_add(1, Ordering::SeqCst);
+        if self.fail {
+            return Err("boom".into());
+        }
+        Ok(())
+    }
+
+    fn repeat_every(&self) -> Option<Duration> {
+        self.every
+    }
+}
+
+fn worker_at(
+    database: &Arc<Mutex<Database>>,
+    now: DateTime<Utc>,
+    handler: &Arc<CountingJob>,
+) -> JobWorker {
+    JobWorker::new(database.clone(), Arc::new(MockClock::new(Tz::UTC, now)))
+        .register("count", handler.clone())
+}
+
+async fn job(database: &Mutex<Database>, id: i32) -> ScheduledJob {
+    database
+        .lock()
+        .await
+        .get_open_jobs(100)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|job| job.id == id)
+        .unwrap()
+}
+
+#[tokio::test]
+async fn due_jobs_run_once_and_future_jobs_wait() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let now = Utc::now();
+    let due = test
+        .db
+        .schedule_job("count", "{}", now - chrono::Duration::minutes(1))
+        .await
+        .unwrap();
+    let later = test
+        .db
+        .schedule_job("count", "{}", now + chrono::Duration::hours(1))
+        .await
+        .unwrap();
+    let handler = Arc::new(CountingJob::default());
+    let worker = worker_at(&database, now, &handler);
+
+    assert_eq!(worker.run_due().await.unwrap(), 1);
+    assert_eq!(worker.run_due().await.unwrap(), 0);
+
+    assert_eq!(handler.runs.load(Ordering::SeqCst), 1);
+    let open: Vec<i32> = database
+        .lock()
+        .await
+        .get_open_jobs(100)
+        .await
+        .unwrap()
+        .iter()
+        .map(|job| job.id)
+        .collect();
+    assert_eq!(open, vec![later.id]);
+    assert_ne!(due.id, later.id);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn failed_jobs_are_retried_and_then_given_up_on() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let mut now = Utc::now();
+    let scheduled = test.db.schedule_job("count", "{}", now).await.unwrap();
+    let handler = Arc::new(CountingJob {
+        fail: true,
+        ..Default::default()
+    });
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        assert_eq!(
+            worker_at(&database, now, &handler).run_due().await.unwrap(),
+            0
+        );
+        let failed = job(&database, scheduled.id).await;
+        assert_eq!(failed.attempts, attempt);
+        assert_eq!(failed.last_error.as_deref(), Some("boom"));
+        if attempt < MAX_ATTEMPTS {
+            assert_eq!(failed.status, "pending");
+            assert!(failed.run_at > now);
+            now = failed.run_at;
+        } else {
+            assert_eq!(failed.status, "failed");
+        }
+    }
+
+    worker_at(&database, now + chrono::Duration::days(1), &handler)
+        .run_due()
+        .await
+        .unwrap();
+    assert_eq!(handler.runs.load(Ordering::SeqCst), MAX_ATTEMPTS as usize);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn recurring_jobs_schedule_their_next_run() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    // Postgres keeps microseconds.
+    let now = Utc::now().trunc_subsecs(6);
+    let handler = Arc::new(CountingJob {
+        every: Some(Duration::from_secs(60 * 60)),
+        ..Default::default()
+    });
+    let worker = worker_at(&database, now, &handler);
+
+    worker.schedule_recurring().await.unwrap();
+    // A pending run already exists, so none is added.
+    worker.schedule_recurring().await.unwrap();
+    assert_eq!(worker.run_due().await.unwrap(), 1);
+
+    let open = database.lock().await.get_open_jobs(100).await.unwrap();
+    assert_eq!(open.len(), 1);
+    assert_eq!(open[0].status, "pending");
+    assert_eq!(open[0].run_at, now + chrono::Duration::hours(1));
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn jobs_without_a_handler_fail() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let now = Utc::now();
+    let scheduled = test.db.schedule_job("unknown", "{}", now).await.unwrap();
+    let handler = Arc::new(CountingJob::default());
+
+    assert_eq!(
+        worker_at(&database, now, &handler).run_due().await.unwrap(),
+        0
+    );
+
+    let failed = job(&database, scheduled.id).await;
+    assert_eq!(failed.status, "failed");
+    assert_eq!(handler.runs.load(Ordering::SeqCst), 0);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn owners_can_list_and_cancel_pending_jobs() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let run_at = "2024-05-03T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+    let scheduled = test.db.schedule_job("count", "{}", run_at).await.unwrap();
+    let frontend = RecordingFrontend::default();
+
+    list_jobs(&frontend, &database).await.unwrap();
+    cancel_scheduled_job(&frontend, &database, scheduled.id)
+        .await
+        .unwrap();
+    cancel_scheduled_job(&frontend, &database, scheduled.id)
+        .await
+        .unwrap();
+    list_jobs(&frontend, &database).await.unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        vec![
+            Recorded::SendReply(Reply::new(format!(
+                "ジョブ:\n- #{} count pending 2024-05-03 12:00 (試行0回)",
+                scheduled.id
+            ))),
+            Recorded::SendReply(Reply::new(format!(
+                "ジョブ#{}を取り消しました。",
+                scheduled.id
+            ))),
+            Recorded::SendReply(Reply::error(format!(
+                "ジョブ#{}は実行待ちではないため取り消せません。",
+                scheduled.id
+            ))),
+            Recorded::SendReply(Reply::new("予定されているジョブはありません。")),
+        ]
+    );
+
+    test.teardown().await;
+}
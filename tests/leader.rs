@@ -0,0 +1,61 @@
+//! Tests for leader election between replicas.
+
+mod common;
+
+use cigarette_counter::leader::LeaderElection;
+use common::setup;
+
+#[tokio::test]
+async fn exactly_one_replica_leads() {
+    let test = setup().await;
+    let first = LeaderElection::new(test.pool.clone());
+    let second = LeaderElection::new(test.pool.clone());
+
+    assert!(first.is_leader().await);
+    assert!(!second.is_leader().await);
+    // Leadership is kept across checks.
+    assert!(first.is_leader().await);
+    assert!(!second.is_leader().await);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn another_replica_takes_over_after_resigning() {
+    let test = setup().await;
+    let first = LeaderElection::new(test.pool.clone());
+    let second = LeaderElection::new(test.pool.clone());
+    assert!(first.is_leader().await);
+
+    first.resign().await;
+
+    assert!(second.is_leader().await);
+    assert!(!first.is_leader().await);
+
+    second.resign().await;
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn leadership_ends_with_the_replica() {
+    let test = setup().await;
+    let first = LeaderElection::new(test.pool.clone());
+    assert!(first.is_leader().await);
+
+    // A crashed replica never resigns; its session ends with the connection.
+    drop(first);
+
+    let second = LeaderElection::new(test.pool.clone());
+    let mut led = false;
+    for _ in 0..50 {
+        if second.is_leader().await {
+            led = true;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(led);
+
+    second.resign().await;
+    test.teardown().await;
+}
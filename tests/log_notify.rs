@@ -0,0 +1,76 @@
+//! Tests for Postgres notifications about inserted and deleted logs.
+
+mod common;
+
+use std::time::Duration;
+
+use chrono::{TimeZone, Utc};
+use cigarette_counter::database::{Database, DuplicateResolution};
+use common::{create_user, log_at, setup};
+use sqlx::postgres::PgListener;
+
+/// Channel the tests listen on
+const CHANNEL: &str = "smoking_logs";
+
+/// Waits briefly for the next notification's payload.
+async fn next_payload(listener: &mut PgListener) -> Option<serde_json::Value> {
+    let notification = tokio::time::timeout(Duration::from_millis(500), listener.recv())
+        .await
+        .ok()?
+        .unwrap();
+    assert_eq!(notification.channel(), CHANNEL);
+
+    Some(serde_json::from_str(notification.payload()).unwrap())
+}
+
+#[tokio::test]
+async fn logs_are_announced_only_when_enabled() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let mut listener = PgListener::connect_with(&test.pool).await.unwrap();
+    listener.listen(CHANNEL).await.unwrap();
+
+    test.db.log_smoking("1", None, 1, 1).await.unwrap();
+    assert!(next_payload(&mut listener).await.is_none());
+
+    let db = Database::new(test.pool.clone()).with_log_notifications(CHANNEL);
+    let log = db.log_smoking("1", Some("10"), 1, 2).await.unwrap();
+    let payload = next_payload(&mut listener).await.unwrap();
+    assert_eq!(payload["event"], "created");
+    assert_eq!(payload["id"], log.id);
+    assert_eq!(payload["discord_id"], "1");
+    assert_eq!(payload["guild_id"], "10");
+    assert_eq!(payload["smoking_type_id"], 1);
+    assert_eq!(payload["quantity"], 2);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn removed_duplicates_are_announced() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let smoked_at = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+    log_at(&test, "1", smoked_at).await;
+    log_at(&test, "1", smoked_at + chrono::Duration::seconds(1)).await;
+    let mut listener = PgListener::connect_with(&test.pool).await.unwrap();
+    listener.listen(CHANNEL).await.unwrap();
+
+    let db = Database::new(test.pool.clone()).with_log_notifications(CHANNEL);
+    let removed = db
+        .resolve_duplicate_logs(
+            "1",
+            "1",
+            chrono::Duration::seconds(5),
+            DuplicateResolution::Delete,
+        )
+        .await
+        .unwrap();
+    assert_eq!(removed, 1);
+    let payload = next_payload(&mut listener).await.unwrap();
+    assert_eq!(payload["event"], "deleted");
+    assert_eq!(payload["smoked_at"], "2024-05-01T12:00:01Z");
+    assert!(next_payload(&mut listener).await.is_none());
+
+    test.teardown().await;
+}
@@ -0,0 +1,220 @@
+//! Tests for routing personal notifications across channels.
+
+mod common;
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex as StdMutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use cigarette_counter::{
+    commands::notify::choose_notification_channel,
+    database::Database,
+    frontend::Reply,
+    notify::{
+        Delivery, Notification, NotificationRouter, Notifier, NotifyChannel, NotifyError, RateLimit,
+    },
+};
+use common::{create_user, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+/// Notifier answering with scripted outcomes, then `Delivery::Sent`
+#[derive(Default)]
+struct ScriptedNotifier {
+    outcomes: StdMutex<VecDeque<Result<Delivery, NotifyError>>>,
+    calls: StdMutex<Vec<String>>,
+}
+
+impl ScriptedNotifier {
+    fn with(outcomes: impl IntoIterator<Item = Result<Delivery, NotifyError>>) -> Arc<Self> {
+        Arc::new(Self {
+            outcomes: StdMutex::new(outcomes.into_iter().collect()),
+            ..Default::default()
+        })
+    }
+
+    fn calls(&self) -> usize {
+        self.calls.lock().unwrap().len()
+    }
+}
+
+#[async_trait]
+impl Notifier for ScriptedNotifier {
+    async fn notify(
+        &self,
+        discord_id: &str,
+        _notification: &Notification,
+    ) -> Result<Delivery, NotifyError> {
+        self.calls.lock().unwrap().push(discord_id.to_string());
+        self.outcomes
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or(Ok(Delivery::Sent))
+    }
+}
+
+fn notification() -> Notification {
+    Notification {
+        title: "禁煙7日を達成しました".to_string(),
+        message: "alice 禁煙7日を達成しました！".to_string(),
+    }
+}
+
+fn unlimited() -> RateLimit {
+    RateLimit::per_minute(100)
+}
+
+#[tokio::test]
+async fn notifications_follow_the_preference_and_fall_back_to_dms() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    create_user(&test, "2").await;
+    test.db
+        .set_notification_channel("1", Some("ntfy"))
+        .await
+        .unwrap();
+    test.db
+        .set_notification_channel("2", Some("email"))
+        .await
+        .unwrap();
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let dm = ScriptedNotifier::with([]);
+    let ntfy = ScriptedNotifier::with([
+        Err(NotifyError::Transient("timeout".to_string())),
+        Err(NotifyError::Transient("timeout".to_string())),
+        Ok(Delivery::Sent),
+        Ok(Delivery::Unavailable),
+        Err(NotifyError::Permanent("forbidden".to_string())),
+    ]);
+    let router = NotificationRouter::new(database)
+        .with(NotifyChannel::Dm, dm.clone(), unlimited())
+        .with(NotifyChannel::Ntfy, ntfy.clone(), unlimited())
+        .with_retries(3, Duration::ZERO);
+
+    assert_eq!(
+        router.channels_for("1").await.unwrap(),
+        [NotifyChannel::Ntfy, NotifyChannel::Dm]
+    );
+    assert_eq!(router.channels_for("3").await.unwrap(), [NotifyChannel::Dm]);
+
+    // Transient failures are retried on the preferred channel.
+    assert_eq!(
+        router.send("1", &notification()).await.unwrap(),
+        Some(NotifyChannel::Ntfy)
+    );
+    assert_eq!((ntfy.calls(), dm.calls()), (3, 0));
+    // Members without a target, or whose channel rejects them, get a DM.
+    for _ in 0..2 {
+        assert_eq!(
+            router.send("1", &notification()).await.unwrap(),
+            Some(NotifyChannel::Dm)
+        );
+    }
+    assert_eq!((ntfy.calls(), dm.calls()), (5, 2));
+    // Channels the instance has not enabled are skipped.
+    assert_eq!(
+        router.send("2", &notification()).await.unwrap(),
+        Some(NotifyChannel::Dm)
+    );
+    assert_eq!(dm.calls(), 3);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn channels_over_their_rate_limit_are_skipped() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    test.db
+        .set_notification_channel("1", Some("pushover"))
+        .await
+        .unwrap();
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let dm = ScriptedNotifier::with([Err(NotifyError::Transient("timeout".to_string()))]);
+    let pushover = ScriptedNotifier::with([]);
+    let router = NotificationRouter::new(database)
+        .with(NotifyChannel::Dm, dm.clone(), RateLimit::per_minute(1))
+        .with(
+            NotifyChannel::Pushover,
+            pushover.clone(),
+            RateLimit::per_minute(1),
+        )
+        .with_retries(1, Duration::ZERO);
+
+    assert_eq!(
+        router.send("1", &notification()).await.unwrap(),
+        Some(NotifyChannel::Pushover)
+    );
+    // A failed DM still counts against the limit, so nothing is left.
+    assert_eq!(router.send("1", &notification()).await.unwrap(), None);
+    assert_eq!(router.send("1", &notification()).await.unwrap(), None);
+    assert_eq!((pushover.calls(), dm.calls()), (1, 1));
+
+    let limit = RateLimit::new(2, Duration::from_millis(50));
+    assert!(limit.try_acquire());
+    assert!(limit.try_acquire());
+    assert!(!limit.try_acquire());
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert!(limit.try_acquire());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn members_choose_channels_they_can_be_reached_on() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let database = Mutex::new(Database::new(test.pool.clone()));
+    let frontend = RecordingFrontend::default();
+    let enabled = [NotifyChannel::Dm, NotifyChannel::Ntfy];
+
+    for channel in [None, Some("pushover"), Some("ntfy")] {
+        choose_notification_channel(&frontend, &database, &enabled, "1", "alice", channel)
+            .await
+            .unwrap();
+    }
+    database
+        .lock()
+        .await
+        .set_push_subscription("1", "ntfy", "alice-topic")
+        .await
+        .unwrap();
+    for channel in [Some("ntfy"), None, Some("dm")] {
+        choose_notification_channel(&frontend, &database, &enabled, "1", "alice", channel)
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(
+        test.db.get_notification_channel("1").await.unwrap(),
+        Some("dm".to_string())
+    );
+    assert_eq!(
+        frontend.calls(),
+        [
+            Recorded::SendReply(Reply::new("現在の通知先: DM\n使い方: notify <dm|ntfy>")),
+            Recorded::SendReply(Reply::error(
+                "このインスタンスでは「pushover」に通知できません。\n使い方: notify <dm|ntfy>"
+            )),
+            Recorded::SendReply(Reply::error(
+                "先に `push ntfy` でトピックを登録してください。"
+            )),
+            Recorded::SendReply(Reply::new("通知をntfyで受け取るように設定しました。")),
+            Recorded::SendReply(Reply::new("現在の通知先: ntfy\n使い方: notify <dm|ntfy>")),
+            Recorded::SendReply(Reply::new("通知をDMで受け取るように設定しました。")),
+        ]
+    );
+
+    test.teardown().await;
+}
+
+#[test]
+fn channel_names_round_trip() {
+    for channel in NotifyChannel::ALL {
+        assert_eq!(channel.name().parse::<NotifyChannel>().unwrap(), channel);
+    }
+    assert!("sms".parse::<NotifyChannel>().is_err());
+}
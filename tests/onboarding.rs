@@ -0,0 +1,228 @@
+//! Tests for the guild setup wizard.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::MockClock,
+    commands::panel::install_panel,
+    database::Database,
+    frontend::ReplySelectKind,
+    onboarding::{
+        apply_onboarding, render_wizard, OnboardingId, OnboardingOutcome, OnboardingStep,
+    },
+    service::StatsService,
+};
+use common::{setup, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+fn control(step: OnboardingStep) -> OnboardingId {
+    OnboardingId {
+        guild_id: "10".to_string(),
+        step,
+    }
+}
+
+fn updated_content(outcome: OnboardingOutcome) -> String {
+    match outcome {
+        OnboardingOutcome::Updated(reply) => reply.content,
+        other => panic!("expected an updated wizard, got {:?}", other),
+    }
+}
+
+#[test]
+fn onboarding_ids_round_trip() {
+    for step in [
+        OnboardingStep::Timezone,
+        OnboardingStep::PanelChannel,
+        OnboardingStep::PanelTypes,
+        OnboardingStep::Preset,
+        OnboardingStep::AgeGate,
+        OnboardingStep::Finish,
+    ] {
+        let id = control(step);
+        assert_eq!(OnboardingId::parse(&id.custom_id()), Some(id));
+    }
+    assert_eq!(OnboardingId::parse("onboarding:10:language"), None);
+    assert_eq!(OnboardingId::parse("onboarding:guild:finish"), None);
+    assert_eq!(OnboardingId::parse("settings:10:goal"), None);
+}
+
+#[tokio::test]
+async fn the_wizard_offers_every_step() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+
+    let wizard = render_wizard(&database, "10", Tz::UTC).await.unwrap();
+
+    assert!(wizard.content.ends_with(
+        "タイムゾーン: UTC（ボットの設定）\nパネルのチャンネル: 未選択\nパネルの種類: すべて\n年齢確認: オフ"
+    ));
+    let labels: Vec<&str> = wizard
+        .buttons
+        .iter()
+        .map(|button| button.label.as_str())
+        .collect();
+    assert_eq!(labels, vec!["年齢確認をオンにする", "設定を完了"]);
+    assert_eq!(wizard.selects.len(), 4);
+    assert_eq!(wizard.selects[1].kind, ReplySelectKind::Channel);
+    assert_eq!(wizard.selects[2].max_values, 5);
+    // One row of buttons and one per select menu, within Discord's five rows.
+    assert_eq!(wizard.components().len(), 5);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn choices_are_stored_as_they_are_made() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 3, 12, 0).unwrap());
+    let stats = StatsService::new(database.clone(), clock);
+    let apply = |step, values: &[&str]| {
+        let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        let database = database.clone();
+        let stats = &stats;
+        async move {
+            apply_onboarding(&database, stats, &control(step), &values, true, Tz::UTC)
+                .await
+                .unwrap()
+        }
+    };
+
+    apply(OnboardingStep::Timezone, &["Asia/Tokyo"]).await;
+    // Time zones the menu does not offer are ignored.
+    apply(OnboardingStep::Timezone, &["Mars/Olympus"]).await;
+    apply(OnboardingStep::PanelChannel, &["500"]).await;
+    apply(OnboardingStep::PanelTypes, &["2", "3", "99"]).await;
+    let content = updated_content(apply(OnboardingStep::AgeGate, &[]).await);
+
+    assert!(content.ends_with(
+        "タイムゾーン: Asia/Tokyo\nパネルのチャンネル: <#500>\nパネルの種類: IQOS, プルーム\n年齢確認: オン"
+    ));
+    let settings = test.db.guild("10").get_settings().await.unwrap();
+    assert_eq!(settings.panel_smoking_type_ids, Some(vec![2, 3]));
+    assert_eq!(settings.onboarded_at, None);
+    assert!(test
+        .db
+        .guild("10")
+        .get_acknowledgment_policy()
+        .await
+        .unwrap()
+        .is_some());
+
+    // The default types apply to panels installed without a filter.
+    let buttons = install_panel(
+        &RecordingFrontend::default(),
+        &database,
+        "panel",
+        Some("10"),
+        "500",
+        None,
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(buttons.len(), 2);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn choosing_a_preset_puts_its_types_on_the_panel() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 3, 12, 0).unwrap());
+    let stats = StatsService::new(database.clone(), clock);
+
+    let outcome = apply_onboarding(
+        &database,
+        &stats,
+        &control(OnboardingStep::Preset),
+        &["vape".to_string()],
+        true,
+        Tz::UTC,
+    )
+    .await
+    .unwrap();
+
+    assert!(updated_content(outcome).contains("パネルの種類: Disposable vape, Pod vape, E-liquid"));
+    let settings = test.db.guild("10").get_settings().await.unwrap();
+    assert_eq!(
+        settings.panel_smoking_type_ids.map(|ids| ids.len()),
+        Some(3)
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn finishing_installs_the_panel_once() {
+    let test = setup().await;
+    test.db.guild("10").set_panel_channel("500").await.unwrap();
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 3, 12, 0).unwrap());
+    let stats = StatsService::new(database.clone(), clock);
+    let finish = control(OnboardingStep::Finish);
+
+    let first = apply_onboarding(&database, &stats, &finish, &[], true, Tz::UTC)
+        .await
+        .unwrap();
+    let second = apply_onboarding(&database, &stats, &finish, &[], true, Tz::UTC)
+        .await
+        .unwrap();
+
+    let OnboardingOutcome::Finished {
+        reply,
+        panel_channel_id,
+    } = first
+    else {
+        panic!("expected the wizard to finish");
+    };
+    assert!(reply.content.starts_with("初期設定が完了しました。"));
+    assert!(reply.content.ends_with("<#500> にパネルを設置します。"));
+    assert_eq!(panel_channel_id.as_deref(), Some("500"));
+    assert!(matches!(
+        second,
+        OnboardingOutcome::Finished {
+            panel_channel_id: None,
+            ..
+        }
+    ));
+    assert!(test
+        .db
+        .guild("10")
+        .get_settings()
+        .await
+        .unwrap()
+        .onboarded_at
+        .is_some());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn members_who_cannot_manage_the_guild_change_nothing() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 3, 12, 0).unwrap());
+    let stats = StatsService::new(database.clone(), clock);
+
+    let outcome = apply_onboarding(
+        &database,
+        &stats,
+        &control(OnboardingStep::Timezone),
+        &["Asia/Tokyo".to_string()],
+        false,
+        Tz::UTC,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(outcome, OnboardingOutcome::Forbidden);
+    assert_eq!(test.db.guild("10").get_timezone().await.unwrap(), None);
+
+    test.teardown().await;
+}
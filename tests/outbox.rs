@@ -0,0 +1,135 @@
+//! Tests for the transactional outbox and its delivery worker.
+
+mod common;
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::{Clock, MockClock},
+    database::{Database, OutboxMessage, LOG_CREATED_TOPIC},
+    outbox::{backoff, deliver_due, DeliveryError, OutboxSink},
+};
+use common::{create_user, setup};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+/// A sink failing a fixed number of times before accepting messages
+struct FlakySink {
+    failures: usize,
+    calls: AtomicUsize,
+}
+
+impl FlakySink {
+    fn new(failures: usize) -> Self {
+        Self {
+            failures,
+            calls: AtomicUsize::new(0),
+        }
+    }
+}
+
+#[async_trait]
+impl OutboxSink for FlakySink {
+    async fn deliver(&self, _message: &OutboxMessage) -> Result<(), DeliveryError> {
+        if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+            return Err(DeliveryError::Status(
+                reqwest::StatusCode::SERVICE_UNAVAILABLE,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn backoff_doubles_up_to_the_cap() {
+    assert_eq!(backoff(1), Duration::from_secs(30));
+    assert_eq!(backoff(2), Duration::from_secs(60));
+    assert_eq!(backoff(4), Duration::from_secs(240));
+    assert_eq!(backoff(20), Duration::from_secs(60 * 60));
+}
+
+#[tokio::test]
+async fn logs_write_outbox_messages_only_when_enabled() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+
+    test.db.log_smoking("1", None, 1, 1).await.unwrap();
+    assert!(test
+        .db
+        .get_due_outbox_messages(chrono::Utc::now(), 10)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let db = Database::new(test.pool.clone()).with_outbox();
+    let log = db.log_smoking("1", Some("10"), 1, 2).await.unwrap();
+    let messages = db
+        .get_due_outbox_messages(chrono::Utc::now(), 10)
+        .await
+        .unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].topic, LOG_CREATED_TOPIC);
+    let payload: serde_json::Value = serde_json::from_str(&messages[0].payload).unwrap();
+    assert_eq!(payload["id"], log.id);
+    assert_eq!(payload["guild_id"], "10");
+    assert_eq!(payload["quantity"], 2);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_with_backoff() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    test.db.enqueue_outbox_message("test", "{}").await.unwrap();
+    let clock = MockClock::new(Tz::UTC, chrono::Utc::now());
+    let sink = FlakySink::new(1);
+
+    assert_eq!(deliver_due(&database, &clock, &sink, 3).await.unwrap(), 0);
+    // Not due again until the backoff has passed.
+    assert_eq!(deliver_due(&database, &clock, &sink, 3).await.unwrap(), 0);
+    assert_eq!(sink.calls.load(Ordering::SeqCst), 1);
+
+    clock.advance(chrono::Duration::from_std(backoff(1)).unwrap());
+    assert_eq!(deliver_due(&database, &clock, &sink, 3).await.unwrap(), 1);
+    assert!(test
+        .db
+        .get_due_outbox_messages(clock.now() + chrono::Duration::days(1), 10)
+        .await
+        .unwrap()
+        .is_empty());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn messages_are_dead_lettered_after_max_attempts() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    test.db.enqueue_outbox_message("test", "{}").await.unwrap();
+    let clock = MockClock::new(Tz::UTC, chrono::Utc::now());
+    let sink = FlakySink::new(usize::MAX);
+
+    for attempt in 1..=3 {
+        deliver_due(&database, &clock, &sink, 3).await.unwrap();
+        clock.advance(chrono::Duration::from_std(backoff(attempt)).unwrap());
+    }
+    deliver_due(&database, &clock, &sink, 3).await.unwrap();
+
+    assert_eq!(sink.calls.load(Ordering::SeqCst), 3);
+    let dead = test.db.get_dead_lettered_outbox_messages().await.unwrap();
+    assert_eq!(dead.len(), 1);
+    assert_eq!(dead[0].attempts, 3);
+    assert_eq!(
+        dead[0].last_error.as_deref(),
+        Some("Sink responded with status 503 Service Unavailable")
+    );
+
+    test.teardown().await;
+}
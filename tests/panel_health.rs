@@ -0,0 +1,82 @@
+//! Tests for disabling panels while the database is unreachable.
+
+mod common;
+
+use cigarette_counter::panel_health::{PanelAction, PanelHealth, FAILURES_BEFORE_DISABLE};
+use common::setup;
+
+#[tokio::test]
+async fn only_panels_with_a_message_are_posted() {
+    let test = setup().await;
+    test.db
+        .create_panel("a", Some("10"), "500", Some(&[1, 2]))
+        .await
+        .unwrap();
+    test.db.create_panel("b", None, "501", None).await.unwrap();
+
+    test.db.set_panel_message("a", "900").await.unwrap();
+
+    let panels = test.db.get_posted_panels().await.unwrap();
+    assert_eq!(panels.len(), 1);
+    assert_eq!(panels[0].panel_id, "a");
+    assert_eq!(panels[0].message_id.as_deref(), Some("900"));
+    assert_eq!(
+        test.db
+            .get_panel("a", Some("10"))
+            .await
+            .unwrap()
+            .unwrap()
+            .message_id
+            .as_deref(),
+        Some("900")
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn panels_are_disabled_after_repeated_failures_and_restored_on_recovery() {
+    let test = setup().await;
+    test.db
+        .create_panel("a", Some("10"), "500", Some(&[1, 2]))
+        .await
+        .unwrap();
+    test.db.set_panel_message("a", "900").await.unwrap();
+    let panels = test.db.get_posted_panels().await.unwrap();
+    let smoking_types = test.db.get_smoking_types().await.unwrap();
+    let mut health = PanelHealth::default();
+
+    assert_eq!(
+        health.healthy(panels.clone(), smoking_types.clone()),
+        PanelAction::None
+    );
+    for _ in 1..FAILURES_BEFORE_DISABLE {
+        assert_eq!(health.unhealthy(), PanelAction::None);
+    }
+    assert_eq!(health.unhealthy(), PanelAction::Disable(panels.clone()));
+    // Panels are disabled once per outage.
+    assert_eq!(health.unhealthy(), PanelAction::None);
+
+    let buttons = serde_json::to_value(health.buttons(&panels[0], false).unwrap()).unwrap();
+    let buttons = buttons.as_array().unwrap();
+    assert_eq!(buttons.len(), 2);
+    assert!(buttons.iter().all(|button| button["disabled"] == true));
+
+    assert_eq!(
+        health.healthy(panels.clone(), smoking_types.clone()),
+        PanelAction::Restore(panels.clone())
+    );
+    assert_eq!(health.healthy(panels, smoking_types), PanelAction::None);
+
+    test.teardown().await;
+}
+
+#[test]
+fn nothing_is_disabled_before_panels_are_known() {
+    let mut health = PanelHealth::default();
+
+    for _ in 0..FAILURES_BEFORE_DISABLE + 1 {
+        assert_eq!(health.unhealthy(), PanelAction::None);
+    }
+    assert_eq!(health.healthy(Vec::new(), Vec::new()), PanelAction::None);
+}
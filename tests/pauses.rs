@@ -0,0 +1,129 @@
+//! Tests for vacation mode (paused tracking).
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::MockClock,
+    commands::pauses::update_tracking_pause,
+    database::{Database, TrackingPause},
+    embed::Title,
+    frontend::Reply,
+    linked_roles::compute_metadata,
+    pauses::{is_paused, tracked_days},
+    service::StatsService,
+};
+use common::{create_user, log_at, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+fn date(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 5, day).unwrap()
+}
+
+#[test]
+fn paused_days_are_not_tracked() {
+    let pauses = [TrackingPause {
+        id: 1,
+        discord_id: "1".to_string(),
+        start_date: date(3),
+        end_date: date(4),
+    }];
+
+    assert!(!is_paused(&pauses, date(2)));
+    assert!(is_paused(&pauses, date(3)));
+    assert!(is_paused(&pauses, date(4)));
+    assert_eq!(tracked_days(&pauses, date(1), date(7)), 5);
+    assert_eq!(tracked_days(&pauses, date(3), date(4)), 0);
+}
+
+#[tokio::test]
+async fn pause_command_validates_and_resumes() {
+    let test = setup().await;
+    let database = Mutex::new(Database::new(test.pool.clone()));
+    let frontend = RecordingFrontend::default();
+    let today = date(10);
+
+    for until in [Some(date(9)), Some(date(10) + Duration::days(400)), None] {
+        update_tracking_pause(&frontend, &database, "1", "alice", today, until)
+            .await
+            .unwrap();
+    }
+    update_tracking_pause(&frontend, &database, "1", "alice", today, Some(date(20)))
+        .await
+        .unwrap();
+    assert!(test.db.is_tracking_paused("1", date(15)).await.unwrap());
+    update_tracking_pause(&frontend, &database, "1", "alice", today, None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        [
+            Recorded::SendReply(Reply::error("終了日には今日以降の日付を指定してください。")),
+            Recorded::SendReply(Reply::error("一時停止できるのは1年先までです。")),
+            Recorded::SendReply(Reply::new("記録は一時停止されていません。").titled(Title::Settings)),
+            Recorded::SendReply(Reply::new(
+                "2024/05/20まで記録を一時停止しました。この間はリマインダーが届かず、連続記録も途切れません。"
+            ).titled(Title::Settings)),
+            Recorded::SendReply(Reply::new("記録の一時停止を解除しました。").titled(Title::Settings)),
+        ]
+    );
+    assert!(!test.db.is_tracking_paused("1", date(15)).await.unwrap());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn paused_days_are_excluded_from_averages_and_streaks() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 10, 12, 0).unwrap());
+    let stats = StatsService::new(database.clone(), clock.clone());
+    test.db.set_daily_goal("1", Some(1)).await.unwrap();
+
+    // Two cigarettes on each of May 6 to 8, over the goal.
+    for day in 6..=8 {
+        for _ in 0..2 {
+            log_at(
+                &test,
+                "1",
+                Utc.with_ymd_and_hms(2024, 5, day, 12, 0, 0).unwrap(),
+            )
+            .await;
+        }
+    }
+    // One on May 3, within the goal.
+    log_at(
+        &test,
+        "1",
+        Utc.with_ymd_and_hms(2024, 5, 3, 12, 0, 0).unwrap(),
+    )
+    .await;
+
+    assert_eq!(stats.daily_average("1", 10).await.unwrap(), Some(0.7));
+    let metadata = compute_metadata(&test.db, clock.as_ref(), "1")
+        .await
+        .unwrap();
+    assert_eq!(metadata.under_goal_streak, 2);
+
+    test.db.pause_tracking("1", date(6), date(8)).await.unwrap();
+    assert_eq!(stats.daily_average("1", 10).await.unwrap(), Some(1.0 / 7.0));
+    let metadata = compute_metadata(&test.db, clock.as_ref(), "1")
+        .await
+        .unwrap();
+    // May 9 and 10, then May 3 to 5: counting starts at the first log.
+    assert_eq!(metadata.under_goal_streak, 5);
+
+    test.db
+        .pause_tracking("1", date(1), date(10))
+        .await
+        .unwrap();
+    assert_eq!(stats.daily_average("1", 10).await.unwrap(), None);
+    assert!(stats.is_tracking_paused("1").await.unwrap());
+
+    test.teardown().await;
+}
@@ -0,0 +1,287 @@
+//! Tests for comparing a user's consumption between two periods.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::MockClock,
+    commands::stats::{show_period_comparison, show_period_summary},
+    database::{Database, UserTypeTotal},
+    embed::Title,
+    frontend::Reply,
+    periods::{
+        compare, parse_periods, resolve_shortcut, CostDelta, Period, SummarySpan, TypeDelta,
+    },
+    service::StatsService,
+};
+use common::{create_user, log_at, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+fn date(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+}
+
+fn period(from: NaiveDate, to: NaiveDate) -> Period {
+    Period { from, to }
+}
+
+fn total(type_name: &str, price: Option<i32>, quantity: i64) -> UserTypeTotal {
+    UserTypeTotal {
+        type_name: type_name.to_string(),
+        description: None,
+        typical_price: price,
+        currency: price.map(|_| "JPY".to_string()),
+        total_quantity: quantity,
+    }
+}
+
+#[test]
+fn shortcuts_resolve_relative_to_today() {
+    // A Wednesday
+    let today = date(5, 15);
+
+    assert_eq!(
+        resolve_shortcut("this-week", today),
+        Some(period(date(5, 13), today))
+    );
+    assert_eq!(
+        resolve_shortcut("last-week", today),
+        Some(period(date(5, 6), date(5, 12)))
+    );
+    assert_eq!(
+        resolve_shortcut("Last-Month", today),
+        Some(period(date(4, 1), date(4, 30)))
+    );
+    assert_eq!(
+        resolve_shortcut("golden-week", today),
+        Some(period(date(4, 29), date(5, 5)))
+    );
+    assert_eq!(
+        resolve_shortcut("normal-week", today),
+        Some(period(date(4, 22), date(4, 28)))
+    );
+    // Before this year's Golden Week, the previous one is meant.
+    assert_eq!(
+        resolve_shortcut("golden-week", date(2, 1)),
+        Some(period(
+            NaiveDate::from_ymd_opt(2023, 4, 29).unwrap(),
+            NaiveDate::from_ymd_opt(2023, 5, 5).unwrap()
+        ))
+    );
+    assert_eq!(resolve_shortcut("next-week", today), None);
+}
+
+#[test]
+fn periods_are_parsed_from_dates_and_shortcuts() {
+    let today = date(5, 15);
+
+    assert_eq!(
+        parse_periods(&["golden-week", "normal-week"], today),
+        Ok((
+            period(date(4, 29), date(5, 5)),
+            period(date(4, 22), date(4, 28))
+        ))
+    );
+    assert_eq!(
+        parse_periods(&["2024-03-01", "2024-03-31", "last-month"], today),
+        Ok((
+            period(date(3, 1), date(3, 31)),
+            period(date(4, 1), date(4, 30))
+        ))
+    );
+
+    for args in [
+        &["golden-week"][..],
+        &["golden-week", "normal-week", "this-week"],
+        &["2024-03-01"],
+        &["2024-03-31", "2024-03-01", "this-week"],
+        &["2023-01-01", "2024-03-01", "this-week"],
+        &["march", "this-week"],
+    ] {
+        assert!(parse_periods(args, today).is_err(), "{:?}", args);
+    }
+}
+
+#[test]
+fn comparisons_report_deltas_per_type_and_currency() {
+    let comparison = compare(
+        &[total("mevius", Some(30), 10), total("iqos", None, 4)],
+        &[total("mevius", Some(30), 6), total("peace", Some(40), 2)],
+    );
+
+    assert_eq!(comparison.first_total, 14);
+    assert_eq!(comparison.second_total, 8);
+    assert_eq!(
+        comparison.types,
+        vec![
+            TypeDelta {
+                name: "mevius".to_string(),
+                first: 10,
+                second: 6,
+            },
+            TypeDelta {
+                name: "iqos".to_string(),
+                first: 4,
+                second: 0,
+            },
+            TypeDelta {
+                name: "peace".to_string(),
+                first: 0,
+                second: 2,
+            },
+        ]
+    );
+    assert_eq!(
+        comparison.costs,
+        vec![CostDelta {
+            currency: "JPY".to_string(),
+            first: 300,
+            second: 260,
+        }]
+    );
+}
+
+#[tokio::test]
+async fn compare_periods_shows_totals_and_costs() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    sqlx::query("UPDATE smoking_types SET typical_price = 30, currency = 'JPY' WHERE id = 1")
+        .execute(&test.pool)
+        .await
+        .unwrap();
+    for (month, day) in [(4, 23), (4, 24), (4, 30), (5, 2), (5, 2), (5, 5)] {
+        log_at(
+            &test,
+            "1",
+            Utc.with_ymd_and_hms(2024, month, day, 12, 0, 0).unwrap(),
+        )
+        .await;
+    }
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 15, 12, 0).unwrap());
+    let stats = StatsService::new(database, clock);
+    let frontend = RecordingFrontend::default();
+
+    show_period_comparison(&frontend, &stats, "1", &["normal-week", "golden-week"])
+        .await
+        .unwrap();
+    show_period_comparison(&frontend, &stats, "1", &["golden-week"])
+        .await
+        .unwrap();
+
+    let calls = frontend.calls();
+    assert_eq!(
+        calls[0],
+        Recorded::SendReply(
+            Reply::new(
+                [
+                    "期間A: 2024/04/22〜2024/04/28（7日）",
+                    "期間B: 2024/04/29〜2024/05/05（7日）",
+                    "合計: 2本 → 4本（+2本）",
+                    "1日平均: 0.3本 → 0.6本",
+                    "- 紙タバコ: 2本 → 4本（+2本）",
+                    "費用: 60 JPY → 120 JPY（+60 JPY）",
+                ]
+                .join("\n")
+            )
+            .titled(Title::Stats)
+        )
+    );
+    assert!(matches!(
+        &calls[1],
+        Recorded::SendReply(reply) if reply.title == Some(Title::Error)
+    ));
+
+    test.teardown().await;
+}
+
+#[test]
+fn summaries_compare_the_span_so_far_with_the_whole_previous_one() {
+    assert_eq!(
+        SummarySpan::Week.periods(date(5, 15)),
+        (
+            period(date(5, 13), date(5, 15)),
+            period(date(5, 6), date(5, 12))
+        )
+    );
+    assert_eq!(
+        SummarySpan::Month.periods(date(5, 15)),
+        (
+            period(date(5, 1), date(5, 15)),
+            period(date(4, 1), date(4, 30))
+        )
+    );
+    assert_eq!(
+        SummarySpan::Month.periods(date(3, 31)).1,
+        period(date(2, 1), date(2, 29))
+    );
+    assert_eq!(
+        SummarySpan::Week.periods(date(5, 13)).0,
+        period(date(5, 13), date(5, 13))
+    );
+}
+
+#[tokio::test]
+async fn weekly_and_monthly_summaries_show_deltas_and_averages() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    sqlx::query("UPDATE smoking_types SET typical_price = 30, currency = 'JPY' WHERE id = 1")
+        .execute(&test.pool)
+        .await
+        .unwrap();
+    for (month, day) in [(4, 20), (5, 7), (5, 13), (5, 14), (5, 14)] {
+        log_at(
+            &test,
+            "1",
+            Utc.with_ymd_and_hms(2024, month, day, 12, 0, 0).unwrap(),
+        )
+        .await;
+    }
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 15, 12, 0).unwrap());
+    let stats = StatsService::new(database, clock);
+    let frontend = RecordingFrontend::default();
+
+    show_period_summary(&frontend, &stats, "1", SummarySpan::Week)
+        .await
+        .unwrap();
+    show_period_summary(&frontend, &stats, "1", SummarySpan::Month)
+        .await
+        .unwrap();
+    show_period_summary(&frontend, &stats, "2", SummarySpan::Week)
+        .await
+        .unwrap();
+
+    let reply =
+        |lines: &[&str]| Recorded::SendReply(Reply::new(lines.join("\n")).titled(Title::Stats));
+    assert_eq!(
+        frontend.calls(),
+        [
+            reply(&[
+                "今週: 2024/05/13〜2024/05/15（3日）",
+                "合計: 3本（先週比 +2本）",
+                "1日平均: 1.0本（先週 0.1本）",
+                "- 紙タバコ: 3本（+2本）",
+                "費用: 90 JPY（先週比 +60 JPY）",
+            ]),
+            reply(&[
+                "今月: 2024/05/01〜2024/05/15（15日）",
+                "合計: 4本（先月比 +3本）",
+                "1日平均: 0.3本（先月 0.0本）",
+                "- 紙タバコ: 4本（+3本）",
+                "費用: 120 JPY（先月比 +90 JPY）",
+            ]),
+            reply(&[
+                "今週: 2024/05/13〜2024/05/15（3日）",
+                "合計: 0本（先週比 ±0本）",
+                "1日平均: 0.0本（先週 0.0本）",
+                "今週も先週も記録はありません。",
+            ]),
+        ]
+    );
+
+    test.teardown().await;
+}
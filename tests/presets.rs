@@ -0,0 +1,103 @@
+//! Tests for the curated smoking-type presets.
+
+mod common;
+
+use std::sync::Arc;
+
+use cigarette_counter::{
+    commands::types::{apply_preset, list_type_presets},
+    database::Database,
+    format::Locale,
+    frontend::Reply,
+    presets::{default_preset, find_preset, format_price, PRESETS},
+};
+use common::{setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+#[test]
+fn presets_fit_in_one_row_of_buttons() {
+    for preset in PRESETS {
+        assert!(!preset.types.is_empty() && preset.types.len() <= 5);
+        assert_eq!(find_preset(&preset.name.to_uppercase()), Some(preset));
+    }
+    assert_eq!(find_preset("menthol"), None);
+    assert_eq!(default_preset(Locale::Japanese).name, "japanese");
+    assert_eq!(default_preset(Locale::German).name, "english");
+}
+
+#[test]
+fn prices_are_shown_in_major_units() {
+    assert_eq!(format_price(30, "JPY"), "30 JPY");
+    assert_eq!(format_price(50, "USD"), "0.50 USD");
+    assert_eq!(format_price(1205, "EUR"), "12.05 EUR");
+}
+
+#[tokio::test]
+async fn applying_a_preset_adds_only_unknown_types() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let known = test.db.get_smoking_types().await.unwrap().len();
+    let frontend = RecordingFrontend::default();
+
+    // The Japanese preset matches the installed types and fills in their details.
+    apply_preset(&frontend, &database, None).await.unwrap();
+    apply_preset(&frontend, &database, Some("vape"))
+        .await
+        .unwrap();
+    apply_preset(&frontend, &database, Some("vape"))
+        .await
+        .unwrap();
+    apply_preset(&frontend, &database, Some("menthol"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        vec![
+            Recorded::SendReply(Reply::new(
+                "プリセット「日本の銘柄」を適用しました。0種類を追加し、5種類は既存の種類を使います。"
+            )),
+            Recorded::SendReply(Reply::new(
+                "プリセット「Vape」を適用しました。3種類を追加し、0種類は既存の種類を使います。"
+            )),
+            Recorded::SendReply(Reply::new(
+                "プリセット「Vape」を適用しました。0種類を追加し、3種類は既存の種類を使います。"
+            )),
+            Recorded::SendReply(Reply::error(
+                "不明なプリセットです: menthol（japanese, english, vape）"
+            )),
+        ]
+    );
+    let types = test.db.get_smoking_types().await.unwrap();
+    assert_eq!(types.len(), known + 3);
+    let iqos = test
+        .db
+        .find_smoking_type_by_name("iqos")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(iqos.description.as_deref(), Some("IQOS"));
+    assert_eq!(iqos.emoji.as_deref(), Some("🔥"));
+    assert_eq!(iqos.unit.as_deref(), Some("本"));
+    assert_eq!(iqos.typical_price, Some(29));
+    assert_eq!(iqos.currency.as_deref(), Some("JPY"));
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn presets_are_listed_with_their_types() {
+    let frontend = RecordingFrontend::default();
+
+    list_type_presets(&frontend).await.unwrap();
+
+    let Recorded::SendReply(reply) = &frontend.calls()[0] else {
+        panic!("expected a reply");
+    };
+    assert!(reply.content.starts_with(
+        "種類のプリセット:\njapanese (日本の銘柄)\n- 🚬 紙タバコ (traditional): 1本あたり30 JPY\n"
+    ));
+    assert!(reply
+        .content
+        .ends_with("- 💧 E-liquid (vape_liquid): 1mlあたり0.50 USD"));
+}
@@ -0,0 +1,160 @@
+//! Tests for push notifications through ntfy and Pushover.
+
+mod common;
+
+use std::sync::{Arc, Mutex as StdMutex};
+
+use async_trait::async_trait;
+use cigarette_counter::{
+    commands::push::{disable_push, subscribe_push},
+    database::Database,
+    frontend::Reply,
+    notify::{Delivery, Notification, Notifier, PushNotifier},
+    push::{PushError, PushProvider, PushProviders, PushService},
+};
+use common::{setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+/// Provider recording the notifications it is asked to push
+#[derive(Default)]
+struct RecordingProvider {
+    pushed: StdMutex<Vec<(String, Notification)>>,
+    failing: bool,
+}
+
+impl RecordingProvider {
+    fn pushed(&self) -> Vec<(String, Notification)> {
+        self.pushed.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl PushProvider for RecordingProvider {
+    async fn push(&self, target: &str, notification: &Notification) -> Result<(), PushError> {
+        if self.failing {
+            return Err(PushError::Status(reqwest::StatusCode::FORBIDDEN));
+        }
+        self.pushed
+            .lock()
+            .unwrap()
+            .push((target.to_string(), notification.clone()));
+
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn notifications_are_pushed_to_the_registered_target_until_disabled() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let frontend = RecordingFrontend::default();
+    let ntfy = Arc::new(RecordingProvider::default());
+    let providers = PushProviders::default().with(PushService::Ntfy, ntfy.clone());
+    let failing_ntfy = Arc::new(RecordingProvider {
+        failing: true,
+        ..Default::default()
+    });
+    let failing = PushProviders::default().with(PushService::Ntfy, failing_ntfy.clone());
+    let notification = Notification {
+        title: "禁煙7日を達成しました".to_string(),
+        message: "alice 禁煙7日を達成しました！".to_string(),
+    };
+
+    for (providers, service, target) in [
+        (
+            &providers,
+            PushService::Pushover,
+            "uQiRzpo4DXghDmr9QzzfQu27cmVRsG",
+        ),
+        (&providers, PushService::Ntfy, "no spaces"),
+        (&failing, PushService::Ntfy, "alice-topic"),
+        (&providers, PushService::Ntfy, "alice-topic"),
+    ] {
+        subscribe_push(
+            &frontend, &database, providers, "1", "alice", service, target,
+        )
+        .await
+        .unwrap();
+    }
+    let notifier = PushNotifier::new(database.clone(), PushService::Ntfy, ntfy.clone());
+    assert_eq!(
+        notifier.notify("1", &notification).await.unwrap(),
+        Delivery::Sent
+    );
+    assert_eq!(
+        database
+            .lock()
+            .await
+            .get_notification_channel("1")
+            .await
+            .unwrap(),
+        Some("ntfy".to_string())
+    );
+    // A rejected push is not worth retrying.
+    let error = PushNotifier::new(database.clone(), PushService::Ntfy, failing_ntfy)
+        .notify("1", &notification)
+        .await
+        .unwrap_err();
+    assert!(!error.is_transient());
+    assert_eq!(
+        PushNotifier::new(database.clone(), PushService::Pushover, ntfy.clone())
+            .notify("1", &notification)
+            .await
+            .unwrap(),
+        Delivery::Unavailable
+    );
+    disable_push(&frontend, &database, "1").await.unwrap();
+    assert_eq!(
+        notifier.notify("1", &notification).await.unwrap(),
+        Delivery::Unavailable
+    );
+    assert_eq!(
+        database
+            .lock()
+            .await
+            .get_notification_channel("1")
+            .await
+            .unwrap(),
+        None
+    );
+
+    let pushed = ntfy.pushed();
+    assert_eq!(pushed.len(), 2);
+    assert_eq!(pushed[0].1.title, "通知のテスト");
+    assert_eq!(pushed[1], ("alice-topic".to_string(), notification));
+    assert_eq!(
+        frontend.calls(),
+        [
+            Recorded::SendReply(Reply::error(
+                "このインスタンスではPushoverが有効になっていません。"
+            )),
+            Recorded::SendReply(Reply::error(
+                "トピックは英数字、-、_ の64文字以内で指定してください。"
+            )),
+            Recorded::SendReply(Reply::error("ntfyにテスト通知を送信できませんでした。")),
+            Recorded::SendReply(Reply::new(
+                "通知をntfyで受け取るように設定しました。テスト通知が届いているか確認してください。"
+            )),
+            Recorded::SendReply(Reply::new("通知をDMで受け取るように戻しました。")),
+        ]
+    );
+
+    test.teardown().await;
+}
+
+#[test]
+fn push_targets_are_validated_per_service() {
+    assert!(PushService::Ntfy.is_valid_target("alice_topic-1"));
+    assert!(!PushService::Ntfy.is_valid_target(""));
+    assert!(!PushService::Ntfy.is_valid_target("a/b"));
+    assert!(!PushService::Ntfy.is_valid_target(&"a".repeat(65)));
+
+    assert!(PushService::Pushover.is_valid_target("uQiRzpo4DXghDmr9QzzfQu27cmVRsG"));
+    assert!(!PushService::Pushover.is_valid_target("uQiRzpo4DXghDmr9QzzfQu27cmVRs"));
+    assert!(!PushService::Pushover.is_valid_target("uQiRzpo4DXghDmr9QzzfQu27cmVRs-"));
+
+    for service in [PushService::Ntfy, PushService::Pushover] {
+        assert_eq!(service.name().parse::<PushService>().unwrap(), service);
+    }
+    assert!("email".parse::<PushService>().is_err());
+}
@@ -0,0 +1,93 @@
+//! Tests for confirming unusually large quantities.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::SystemClock,
+    commands::quantity::{confirmation_prompt, QuantityId},
+    database::Database,
+    format::Locale,
+    service::{ServiceError, LARGE_QUANTITY_THRESHOLD},
+};
+use common::{create_user, logging_service, setup};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+#[test]
+fn quantity_ids_round_trip() {
+    for id in [
+        QuantityId::Confirm {
+            user_id: 1,
+            smoking_type_id: 2,
+            quantity: 200,
+        },
+        QuantityId::Cancel { user_id: 1 },
+    ] {
+        assert_eq!(QuantityId::parse(&id.custom_id()), Some(id));
+    }
+    assert_eq!(QuantityId::parse("quantity:confirm:1:2"), None);
+    assert_eq!(QuantityId::parse("quantity:cancel:x"), None);
+    assert_eq!(QuantityId::parse("help:menu"), None);
+}
+
+#[test]
+fn the_prompt_offers_to_log_or_cancel() {
+    let prompt = confirmation_prompt(1, 2, "紙タバコ", 200, 20, Locale::Japanese);
+
+    assert!(prompt.content.contains("紙タバコを200本"));
+    assert_eq!(
+        prompt
+            .buttons
+            .iter()
+            .map(|button| QuantityId::parse(&button.custom_id))
+            .collect::<Vec<_>>(),
+        vec![
+            Some(QuantityId::Confirm {
+                user_id: 1,
+                smoking_type_id: 2,
+                quantity: 200
+            }),
+            Some(QuantityId::Cancel { user_id: 1 }),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn large_quantities_need_confirmation() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    test.db.create_device("1", "watch", "device").await.unwrap();
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let logging = logging_service(&database, Arc::new(SystemClock::new(Tz::UTC)));
+    let large = LARGE_QUANTITY_THRESHOLD + 1;
+
+    logging
+        .log_for_user("1", "alice", None, 1, LARGE_QUANTITY_THRESHOLD, false, None)
+        .await
+        .unwrap();
+    assert!(matches!(
+        logging.log_for_user("1", "alice", None, 1, large, false, None).await,
+        Err(ServiceError::UnconfirmedQuantity { quantity, threshold })
+            if quantity == large && threshold == LARGE_QUANTITY_THRESHOLD
+    ));
+    assert!(matches!(
+        logging.log_for_device("device", 1, large, false, None).await,
+        Err(ServiceError::UnconfirmedQuantity { .. })
+    ));
+
+    let logged = logging
+        .log_for_user("1", "alice", None, 1, large, true, None)
+        .await
+        .unwrap();
+    assert_eq!(logged.today_total, i64::from(LARGE_QUANTITY_THRESHOLD + large));
+    let (_, logged) = logging
+        .log_for_device("device", 1, large, true, None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(logged.log.quantity, large);
+
+    test.teardown().await;
+}
@@ -0,0 +1,112 @@
+//! Tests for confirming panel presses with a reaction instead of a message.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::SystemClock,
+    commands::{panel::record_cigarette, reactions::update_confirmation_reaction},
+    database::Database,
+    embed::Title,
+    frontend::Reply,
+};
+use common::{create_user, logging_service, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::{futures::lock::Mutex, EmojiId};
+
+#[tokio::test]
+async fn reaction_mode_only_accepts_the_guilds_custom_emojis() {
+    let test = setup().await;
+    let database = Mutex::new(Database::new(test.pool.clone()));
+    let frontend = RecordingFrontend::default();
+    let guild_emojis = [EmojiId::new(123)];
+
+    for emoji in ["🚬", "<:other:456>", " <:smoke:123> "] {
+        update_confirmation_reaction(&frontend, &database, "10", Some(emoji), &guild_emojis)
+            .await
+            .unwrap();
+    }
+    assert_eq!(
+        test.db
+            .guild("10")
+            .get_confirmation_reaction()
+            .await
+            .unwrap()
+            .as_deref(),
+        Some("<:smoke:123>")
+    );
+    assert_eq!(
+        test.db
+            .guild("20")
+            .get_confirmation_reaction()
+            .await
+            .unwrap(),
+        None
+    );
+    for _ in 0..2 {
+        update_confirmation_reaction(&frontend, &database, "10", None, &[])
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(
+        frontend.calls(),
+        [
+            Recorded::SendReply(Reply::error(
+                "このサーバーのカスタム絵文字を指定してください。"
+            )),
+            Recorded::SendReply(Reply::error(
+                "このサーバーのカスタム絵文字を指定してください。"
+            )),
+            Recorded::SendReply(
+                Reply::new(
+                    "パネルの記録をメッセージの代わりに <:smoke:123> のリアクションで確認します。"
+                )
+                .titled(Title::Settings)
+            ),
+            Recorded::SendReply(
+                Reply::new("パネルの記録をメッセージで確認します。").titled(Title::Settings)
+            ),
+            Recorded::SendReply(
+                Reply::new("リアクションでの確認は設定されていません。").titled(Title::Settings)
+            ),
+        ]
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn button_presses_are_confirmed_with_the_guilds_reaction() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    test.db
+        .guild("10")
+        .set_confirmation_reaction("<a:smoke:123>")
+        .await
+        .unwrap();
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let logging = logging_service(&database, Arc::new(SystemClock::new(Tz::UTC)));
+    let frontend = RecordingFrontend::default();
+
+    record_cigarette(&frontend, &logging, "1", "alice", Some("10"), 1, None)
+        .await
+        .unwrap();
+    record_cigarette(&frontend, &logging, "1", "alice", Some("20"), 1, None)
+        .await
+        .unwrap();
+
+    let calls = frontend.calls();
+    assert_eq!(calls[0], Recorded::React("<a:smoke:123>".to_string()));
+    assert!(matches!(&calls[1], Recorded::Respond(_)));
+    assert_eq!(
+        test.db
+            .get_daily_type_total("1", 1, test.today(), Tz::UTC)
+            .await
+            .unwrap(),
+        2
+    );
+
+    test.teardown().await;
+}
@@ -0,0 +1,137 @@
+//! Tests for the reporting views dashboards read.
+
+mod common;
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use common::{create_user, log_at, setup};
+use sqlx::{pool::PoolConnection, Executor, Postgres};
+
+fn date(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+}
+
+/// Logs cigarettes for a user in a guild at noon UTC on a date.
+async fn log_in_guild(
+    test: &common::TestDatabase,
+    discord_id: &str,
+    day: NaiveDate,
+    quantity: i32,
+) {
+    sqlx::query(
+        "INSERT INTO smoking_logs (discord_id, smoking_type_id, quantity, smoked_at, guild_id) VALUES ($1, 1, $2, ($3::date + TIME '12:00') AT TIME ZONE 'UTC', '10')",
+    )
+    .bind(discord_id)
+    .bind(quantity)
+    .bind(day)
+    .execute(&test.pool)
+    .await
+    .expect("insert log");
+}
+
+/// Opens a connection counting days in UTC, like a reporting role would.
+async fn reporting_connection(test: &common::TestDatabase) -> PoolConnection<Postgres> {
+    let mut conn = test.pool.acquire().await.unwrap();
+    conn.execute("SET TIME ZONE 'UTC'").await.unwrap();
+
+    conn
+}
+
+#[tokio::test]
+async fn daily_totals_are_grouped_per_day_user_and_type() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    log_in_guild(&test, "1", date(5, 1), 2).await;
+    log_in_guild(&test, "1", date(5, 1), 1).await;
+    log_in_guild(&test, "1", date(5, 2), 4).await;
+    let mut conn = reporting_connection(&test).await;
+
+    let totals: Vec<(NaiveDate, String, Option<String>, String, i64, i64)> = sqlx::query_as(
+        "SELECT day, discord_id, guild_id, type_name, total_quantity, log_count FROM v_daily_totals ORDER BY day",
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .unwrap();
+    assert_eq!(
+        totals,
+        vec![
+            (
+                date(5, 1),
+                "1".to_string(),
+                Some("10".to_string()),
+                "traditional".to_string(),
+                3,
+                2
+            ),
+            (
+                date(5, 2),
+                "1".to_string(),
+                Some("10".to_string()),
+                "traditional".to_string(),
+                4,
+                1
+            ),
+        ]
+    );
+
+    drop(conn);
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn streaks_count_days_without_logs() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    for day in [1, 5, 6] {
+        log_at(
+            &test,
+            "1",
+            Utc.with_ymd_and_hms(2024, 5, day, 12, 0, 0).unwrap(),
+        )
+        .await;
+    }
+    let mut conn = reporting_connection(&test).await;
+
+    let (last_day, current, longest): (NaiveDate, i32, i32) = sqlx::query_as(
+        "SELECT last_smoking_day, current_streak_days, longest_streak_days FROM v_user_streaks WHERE discord_id = '1'",
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .unwrap();
+    let since_last = (Utc::now().date_naive() - date(5, 6)).num_days() as i32;
+    assert_eq!(last_day, date(5, 6));
+    assert_eq!(current, since_last);
+    assert_eq!(longest, since_last.max(3));
+
+    drop(conn);
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn leaderboard_ranks_members_by_fewest_per_month() {
+    let test = setup().await;
+    for (discord_id, quantity) in [("1", 5), ("2", 2), ("3", 2)] {
+        create_user(&test, discord_id).await;
+        log_in_guild(&test, discord_id, date(5, 10), quantity).await;
+    }
+    log_in_guild(&test, "1", date(6, 1), 1).await;
+    let mut conn = reporting_connection(&test).await;
+
+    let standings: Vec<(NaiveDate, String, i64, i64)> = sqlx::query_as(
+        "SELECT month, discord_id, total_quantity, rank FROM v_guild_leaderboard WHERE guild_id = '10' ORDER BY month, rank, discord_id",
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .unwrap();
+    assert_eq!(
+        standings,
+        vec![
+            (date(5, 1), "2".to_string(), 2, 1),
+            (date(5, 1), "3".to_string(), 2, 1),
+            (date(5, 1), "1".to_string(), 5, 3),
+            (date(6, 1), "1".to_string(), 1, 1),
+        ]
+    );
+
+    drop(conn);
+    test.teardown().await;
+}
@@ -0,0 +1,155 @@
+//! Rollover semantics across DST transitions and leap days.
+
+mod common;
+
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::{
+    America::{New_York, Santiago},
+    Tz,
+};
+use cigarette_counter::rollover::{
+    day_bounds, previous_month_to_date, shift_months, start_of_day, year_ago,
+};
+use common::{create_user, log_at, setup};
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+fn day_length(timezone: Tz, date: NaiveDate) -> Duration {
+    let (start, end) = day_bounds(timezone, date);
+    end - start
+}
+
+#[test]
+fn days_around_dst_transitions_are_23_and_25_hours() {
+    assert_eq!(day_length(New_York, date(2024, 3, 9)), Duration::hours(24));
+    assert_eq!(day_length(New_York, date(2024, 3, 10)), Duration::hours(23));
+    assert_eq!(day_length(New_York, date(2024, 11, 3)), Duration::hours(25));
+    assert_eq!(day_length(Tz::UTC, date(2024, 2, 29)), Duration::hours(24));
+}
+
+#[test]
+fn consecutive_days_tile_without_gaps() {
+    let mut day = date(2024, 1, 1);
+    while day < date(2025, 1, 1) {
+        let next = day.succ_opt().unwrap();
+        assert_eq!(day_bounds(New_York, day).1, day_bounds(New_York, next).0);
+        day = next;
+    }
+}
+
+#[test]
+fn day_starts_after_a_skipped_midnight() {
+    // Chile moves from 00:00 to 01:00 at the start of DST.
+    let start = start_of_day(Santiago, date(2024, 9, 8));
+    let local = start.with_timezone(&Santiago);
+
+    assert_eq!(local.date_naive(), date(2024, 9, 8));
+    assert_eq!(local.format("%H:%M").to_string(), "01:00");
+    assert_eq!(day_length(Santiago, date(2024, 9, 8)), Duration::hours(23));
+}
+
+#[test]
+fn month_shifts_clamp_to_month_end() {
+    assert_eq!(shift_months(date(2024, 3, 31), -1), date(2024, 2, 29));
+    assert_eq!(shift_months(date(2023, 3, 31), -1), date(2023, 2, 28));
+    assert_eq!(shift_months(date(2024, 1, 31), 1), date(2024, 2, 29));
+    assert_eq!(shift_months(date(2024, 1, 15), -1), date(2023, 12, 15));
+    assert_eq!(year_ago(date(2024, 2, 29)), date(2023, 2, 28));
+    assert_eq!(year_ago(date(2025, 3, 1)), date(2024, 3, 1));
+}
+
+#[test]
+fn previous_month_comparison_spans_match() {
+    assert_eq!(
+        previous_month_to_date(date(2024, 3, 31)),
+        (date(2024, 2, 1), date(2024, 2, 29))
+    );
+    assert_eq!(
+        previous_month_to_date(date(2023, 3, 30)),
+        (date(2023, 2, 1), date(2023, 2, 28))
+    );
+    assert_eq!(
+        previous_month_to_date(date(2024, 3, 15)),
+        (date(2024, 2, 1), date(2024, 2, 15))
+    );
+    assert_eq!(
+        previous_month_to_date(date(2025, 1, 31)),
+        (date(2024, 12, 1), date(2024, 12, 31))
+    );
+}
+
+#[tokio::test]
+async fn logs_in_the_repeated_hour_count_once_towards_that_day() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+
+    // 01:30 EDT and 01:30 EST on the fall-back day, plus both ends of the day.
+    let instants = [
+        Utc.with_ymd_and_hms(2024, 11, 3, 4, 0, 0).unwrap(),
+        Utc.with_ymd_and_hms(2024, 11, 3, 5, 30, 0).unwrap(),
+        Utc.with_ymd_and_hms(2024, 11, 3, 6, 30, 0).unwrap(),
+        Utc.with_ymd_and_hms(2024, 11, 4, 4, 59, 59).unwrap(),
+        Utc.with_ymd_and_hms(2024, 11, 4, 5, 0, 0).unwrap(),
+    ];
+    for instant in instants {
+        log_at(&test, "1", instant).await;
+    }
+
+    let summary = test
+        .db
+        .get_daily_summary("1", date(2024, 11, 3), New_York)
+        .await
+        .unwrap();
+    assert_eq!(summary.len(), 1);
+    assert_eq!(summary[0].total_quantity, Some(4));
+    assert_eq!(summary[0].smoke_date, date(2024, 11, 3));
+
+    let totals: Vec<_> = test
+        .db
+        .get_daily_totals("1", date(2024, 11, 3), New_York)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|total| (total.smoke_date, total.total_quantity))
+        .collect();
+    assert_eq!(totals, [(date(2024, 11, 3), 4), (date(2024, 11, 4), 1)]);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn logs_around_the_skipped_hour_count_towards_the_short_day() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+
+    // 01:59 EST, immediately before clocks jump to 03:00 EDT.
+    log_at(
+        &test,
+        "1",
+        Utc.with_ymd_and_hms(2024, 3, 10, 6, 59, 0).unwrap(),
+    )
+    .await;
+
+    let summary = test
+        .db
+        .get_daily_summary("1", date(2024, 3, 10), New_York)
+        .await
+        .unwrap();
+    assert_eq!(summary[0].total_quantity, Some(1));
+    assert_eq!(
+        test.db.get_first_smoke_date("1", New_York).await.unwrap(),
+        Some(date(2024, 3, 10))
+    );
+    // The same instant is already March 10 in UTC but still March 9 further west.
+    assert_eq!(
+        test.db
+            .get_first_smoke_date("1", chrono_tz::America::Los_Angeles)
+            .await
+            .unwrap(),
+        Some(date(2024, 3, 9))
+    );
+
+    test.teardown().await;
+}
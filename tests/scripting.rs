@@ -0,0 +1,112 @@
+//! Tests for the sandboxed scripting hooks.
+
+#![cfg(feature = "scripting")]
+
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use chrono::NaiveDate;
+use cigarette_counter::scripting::{ScriptError, ScriptEvent, ScriptHooks};
+
+/// Writes scripts to a fresh directory and loads them.
+fn load(name: &str, scripts: &[(&str, &str)]) -> Result<ScriptHooks, ScriptError> {
+    let dir: PathBuf = std::env::temp_dir().join(format!(
+        "cigarette-counter-scripts-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for (file, source) in scripts {
+        std::fs::write(dir.join(file), source).unwrap();
+    }
+
+    let hooks = ScriptHooks::load(&dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+    hooks
+}
+
+fn log_created(today_total: i64) -> ScriptEvent {
+    ScriptEvent::LogCreated {
+        user_id: "1".to_string(),
+        username: "alice".to_string(),
+        guild_id: None,
+        smoking_type_id: 1,
+        date: NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(),
+        today_total,
+    }
+}
+
+#[test]
+fn hooks_add_lines_in_file_order() {
+    let hooks = load(
+        "order",
+        &[
+            (
+                "b.rhai",
+                r#"fn on_log_created(event) { `${event.username}: ${event.today_total * 30}円` }"#,
+            ),
+            (
+                "a.rhai",
+                r#"fn on_log_created(event) { if event.today_total >= 10 { "多すぎます" } }"#,
+            ),
+            (
+                "c.rhai",
+                r#"fn on_milestone_reached(event) { "おめでとう" }"#,
+            ),
+            ("notes.txt", "not a script"),
+        ],
+    )
+    .unwrap();
+
+    assert_eq!(hooks.len(), 3);
+    assert_eq!(hooks.run(&log_created(2)), ["alice: 60円"]);
+    assert_eq!(hooks.run(&log_created(10)), ["多すぎます", "alice: 300円"]);
+}
+
+#[test]
+fn runaway_scripts_are_aborted() {
+    let hooks = load(
+        "runaway",
+        &[
+            ("loop.rhai", "fn on_log_created(event) { loop {} }"),
+            (
+                "recursion.rhai",
+                "fn on_log_created(event) { on_log_created(event) }",
+            ),
+            (
+                "string.rhai",
+                r#"fn on_log_created(event) { let s = "x"; loop { s += s; } }"#,
+            ),
+            ("ok.rhai", r#"fn on_log_created(event) { "ok" }"#),
+        ],
+    )
+    .unwrap();
+
+    let started = Instant::now();
+    assert_eq!(hooks.run(&log_created(1)), ["ok"]);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn scripts_cannot_eval() {
+    let result = load(
+        "eval",
+        &[("eval.rhai", r#"fn on_log_created(event) { eval("1") }"#)],
+    );
+
+    assert!(matches!(result, Err(ScriptError::Compile { .. })));
+}
+
+#[test]
+fn compile_errors_name_the_script() {
+    let Err(ScriptError::Compile { path, .. }) =
+        load("syntax", &[("broken.rhai", "fn on_log_created(event) {")])
+    else {
+        panic!("expected a compile error");
+    };
+
+    assert!(path.ends_with("broken.rhai"));
+}
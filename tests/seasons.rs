@@ -0,0 +1,206 @@
+//! Tests for leaderboard seasons.
+
+mod common;
+
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::UTC;
+use cigarette_counter::{
+    clock::MockClock,
+    commands::leaderboard::{parse_season, standings_reply},
+    database::{LeaderboardSeason, LeaderboardSettings, MemberTotal, Standing},
+    seasons::{announcement, rank, SeasonLength},
+};
+use common::{create_user, log_at, setup};
+
+fn date(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+}
+
+fn total(discord_id: &str, total: i64) -> MemberTotal {
+    MemberTotal {
+        discord_id: discord_id.to_string(),
+        total,
+    }
+}
+
+fn standing(discord_id: &str, rank: i32, total: i64) -> Standing {
+    Standing {
+        discord_id: discord_id.to_string(),
+        rank,
+        total,
+    }
+}
+
+#[test]
+fn seasons_follow_the_calendar() {
+    assert_eq!(
+        SeasonLength::Month.bounds(date(6, 15)),
+        (date(6, 1), date(7, 1))
+    );
+    assert_eq!(
+        SeasonLength::Quarter.bounds(date(6, 15)),
+        (date(4, 1), date(7, 1))
+    );
+    assert_eq!(
+        SeasonLength::Quarter.bounds(date(12, 31)),
+        (date(10, 1), NaiveDate::from_ymd_opt(2025, 1, 1).unwrap())
+    );
+    assert_eq!(SeasonLength::from_name("year"), None);
+    assert_eq!(parse_season("season:3"), Some(3));
+    assert_eq!(parse_season("#2"), Some(2));
+    assert_eq!(parse_season("latest"), None);
+}
+
+#[test]
+fn fewest_units_rank_first_and_ties_share_a_rank() {
+    assert_eq!(
+        rank(&[total("3", 9), total("1", 4), total("2", 4), total("4", 0)]),
+        vec![
+            standing("4", 1, 0),
+            standing("1", 2, 4),
+            standing("2", 2, 4),
+            standing("3", 4, 9),
+        ]
+    );
+}
+
+#[test]
+fn announcements_name_the_winners() {
+    let season = LeaderboardSeason {
+        id: 1,
+        number: 3,
+        starts_on: date(6, 1),
+        ends_on: date(7, 1),
+        archived_at: None,
+    };
+
+    assert_eq!(
+        announcement(
+            &season,
+            &[
+                standing("1", 1, 2),
+                standing("2", 2, 5),
+                standing("3", 3, 8),
+                standing("4", 4, 9),
+            ]
+        ),
+        "🏆 シーズン #3（2024/06/01〜2024/06/30）が終了しました！ 記録した本数が少なかったメンバーは:\n1位 <@1>（2本）\n2位 <@2>（5本）\n3位 <@3>（8本）\n新しいシーズンが始まります。"
+    );
+    assert_eq!(
+        announcement(&season, &[]),
+        "🏁 シーズン #3（2024/06/01〜2024/06/30）が終了しました。記録したメンバーはいませんでした。"
+    );
+}
+
+#[tokio::test]
+async fn seasons_are_archived_and_browsed_per_guild() {
+    let test = setup().await;
+    let guild = test.db.guild("10");
+    let june = Utc.with_ymd_and_hms(2024, 6, 10, 12, 0, 0).unwrap();
+
+    guild.set_leaderboard("500", "quarter").await.unwrap();
+    guild.set_leaderboard("500", "month").await.unwrap();
+    assert_eq!(
+        test.db.get_leaderboard_settings().await.unwrap(),
+        vec![LeaderboardSettings {
+            guild_id: "10".to_string(),
+            channel_id: "500".to_string(),
+            season_length: "month".to_string(),
+        }]
+    );
+
+    for user_id in ["1", "2"] {
+        create_user(&test, user_id).await;
+        test.db
+            .log_smoking(user_id, Some("10"), 1, 1)
+            .await
+            .unwrap();
+    }
+    // Member 1 logs in May and June, member 2 only before June.
+    sqlx::query("UPDATE smoking_logs SET smoked_at = $1")
+        .bind(june - Duration::days(30))
+        .execute(&test.pool)
+        .await
+        .unwrap();
+    log_at(&test, "1", june).await;
+    sqlx::query("UPDATE smoking_logs SET guild_id = '10'")
+        .execute(&test.pool)
+        .await
+        .unwrap();
+
+    let first = guild.open_season(date(6, 1), date(7, 1)).await.unwrap();
+    assert_eq!(first.number, 1);
+    assert_eq!(guild.get_open_season().await.unwrap(), Some(first.clone()));
+    assert!(test
+        .db
+        .guild("20")
+        .get_open_season()
+        .await
+        .unwrap()
+        .is_none());
+
+    let clock = MockClock::new(UTC, june + Duration::hours(1));
+    let running = standings_reply(&guild, &clock, None).await.unwrap();
+    assert_eq!(
+        running.content,
+        "シーズン #1（2024/06/01〜2024/06/30）の順位（開催中）\n1位 <@2> 0本\n2位 <@1> 1本"
+    );
+
+    let totals = guild
+        .get_member_totals(
+            Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(totals, vec![total("2", 0), total("1", 1)]);
+    let standings = rank(&totals);
+    assert!(guild
+        .archive_season(first.id, &standings, june)
+        .await
+        .unwrap());
+    assert!(!guild
+        .archive_season(first.id, &standings, june)
+        .await
+        .unwrap());
+    assert_eq!(guild.get_open_season().await.unwrap(), None);
+    assert_eq!(
+        guild.get_season_standings(first.id).await.unwrap(),
+        standings
+    );
+    assert!(test
+        .db
+        .guild("20")
+        .get_season_standings(first.id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let second = guild.open_season(date(7, 1), date(8, 1)).await.unwrap();
+    assert_eq!(second.number, 2);
+    let archived = standings_reply(&guild, &clock, Some("season:1"))
+        .await
+        .unwrap();
+    assert_eq!(
+        archived.content,
+        "シーズン #1（2024/06/01〜2024/06/30）の最終順位\n1位 <@2> 0本\n2位 <@1> 1本\n過去のシーズン: #1（leaderboard season:<番号>）"
+    );
+    assert!(standings_reply(&guild, &clock, Some("2"))
+        .await
+        .unwrap()
+        .content
+        .starts_with("シーズン #2 は開催中です。"));
+    assert!(standings_reply(&guild, &clock, Some("9"))
+        .await
+        .unwrap()
+        .content
+        .starts_with("シーズン #9 は見つかりません。"));
+
+    // Stopping the seasons drops the running one but keeps the archive.
+    assert!(guild.remove_leaderboard().await.unwrap());
+    assert!(!guild.remove_leaderboard().await.unwrap());
+    assert_eq!(guild.get_open_season().await.unwrap(), None);
+    assert_eq!(guild.get_archived_seasons().await.unwrap().len(), 1);
+
+    test.teardown().await;
+}
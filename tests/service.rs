@@ -0,0 +1,160 @@
+//! Tests for the domain services shared by commands and the HTTP API.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono::Duration;
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::{Clock, MockClock},
+    database::Database,
+    service::{ServiceError, StatsService},
+};
+use common::{create_user, logging_service, setup};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+#[tokio::test]
+async fn logs_are_validated_for_every_source() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 1, 12, 0).unwrap());
+    let logging = logging_service(&database, clock);
+    create_user(&test, "1").await;
+    test.db.create_device("1", "button", "hash").await.unwrap();
+
+    assert!(matches!(
+        logging.log_for_user("1", "alice", None, 1, 0, false, None).await,
+        Err(ServiceError::InvalidQuantity)
+    ));
+    assert!(matches!(
+        logging.log_for_user("1", "alice", None, 999, 1, false, None).await,
+        Err(ServiceError::UnknownSmokingType(999))
+    ));
+    assert!(matches!(
+        logging.log_for_device("hash", 999, 1, false, None).await,
+        Err(ServiceError::UnknownSmokingType(999))
+    ));
+    assert!(logging
+        .log_for_device("other", 1, 1, false, None)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(logging
+        .log_for_shortcut("other", None)
+        .await
+        .unwrap()
+        .is_none());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn every_source_reports_the_same_daily_total() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock: Arc<dyn Clock> = Arc::new(MockClock::new(Tz::UTC, chrono::Utc::now()));
+    let logging = logging_service(&database, clock.clone());
+    create_user(&test, "1").await;
+    test.db
+        .create_device("1", "button", "device")
+        .await
+        .unwrap();
+    test.db.create_shortcut_link("1", 1, "link").await.unwrap();
+
+    let from_panel = logging
+        .log_for_user("1", "alice", Some("10"), 1, 1, false, None)
+        .await
+        .unwrap();
+    let (device, from_device) = logging
+        .log_for_device("device", 1, 2, false, None)
+        .await
+        .unwrap()
+        .unwrap();
+    let from_shortcut = logging
+        .log_for_shortcut("link", None)
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(from_panel.today_total, 1);
+    assert_eq!(from_panel.log.guild_id.as_deref(), Some("10"));
+    assert_eq!(device.device_name, "button");
+    assert_eq!(from_device.log.device_name.as_deref(), Some("button"));
+    assert_eq!(from_device.today_total, 3);
+    assert_eq!(from_shortcut.today_total, 4);
+    assert_eq!(from_shortcut.date, clock.today());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn retried_requests_with_an_idempotency_key_log_once() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock: Arc<dyn Clock> = Arc::new(MockClock::new(Tz::UTC, chrono::Utc::now()));
+    let logging = logging_service(&database, clock);
+    create_user(&test, "1").await;
+    test.db
+        .create_device("1", "button", "device")
+        .await
+        .unwrap();
+    test.db.create_shortcut_link("1", 1, "link").await.unwrap();
+
+    let (_, first) = logging
+        .log_for_device("device", 1, 2, false, Some("abc"))
+        .await
+        .unwrap()
+        .unwrap();
+    let (_, retried) = logging
+        .log_for_device("device", 1, 2, false, Some("abc"))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!first.replayed);
+    assert!(retried.replayed);
+    assert_eq!(retried.log.id, first.log.id);
+    assert_eq!(retried.today_total, 2);
+    assert!(matches!(
+        logging.log_for_device("device", 1, 3, false, Some("abc")).await,
+        Err(ServiceError::IdempotencyKeyReused)
+    ));
+
+    let shortcut = logging
+        .log_for_shortcut("link", Some("def"))
+        .await
+        .unwrap()
+        .unwrap();
+    let retried = logging
+        .log_for_shortcut("link", Some("def"))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(retried.replayed);
+    assert_eq!(retried.log.id, shortcut.log.id);
+    assert_eq!(retried.today_total, 3);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn stats_count_smoke_free_days() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::new(Tz::UTC, chrono::Utc::now()));
+    let logging = logging_service(&database, clock.clone());
+    let stats = StatsService::new(database.clone(), clock.clone());
+
+    logging
+        .log_for_user("1", "alice", None, 1, 1, false, None)
+        .await
+        .unwrap();
+    assert_eq!(stats.days_smoke_free("1").await.unwrap(), 0);
+
+    clock.advance(Duration::days(3));
+    assert_eq!(stats.days_smoke_free("1").await.unwrap(), 3);
+    assert_eq!(stats.role_metadata("1").await.unwrap().days_smoke_free, 3);
+
+    test.teardown().await;
+}
@@ -0,0 +1,278 @@
+//! Tests for the settings panel and the per-guild time zone.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::MockClock,
+    commands::{
+        panel::record_cigarette,
+        settings::{
+            apply_user_setting, render_user_settings, show_guild_timezone, update_guild_timezone,
+            Setting, SettingsId,
+        },
+    },
+    database::Database,
+    embed::Title,
+    format::Locale,
+    frontend::Reply,
+    service::StatsService,
+};
+use common::{create_user, log_at, logging_service, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+#[tokio::test]
+async fn guild_timezone_can_be_set_shown_and_reset() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 3, 12, 0).unwrap());
+    let stats = StatsService::new(database.clone(), clock);
+    let frontend = RecordingFrontend::default();
+
+    show_guild_timezone(&frontend, &database, "10", Tz::UTC)
+        .await
+        .unwrap();
+    update_guild_timezone(&frontend, &database, &stats, "10", "Mars/Olympus", Tz::UTC)
+        .await
+        .unwrap();
+    update_guild_timezone(&frontend, &database, &stats, "10", "Asia/Tokyo", Tz::UTC)
+        .await
+        .unwrap();
+    show_guild_timezone(&frontend, &database, "10", Tz::UTC)
+        .await
+        .unwrap();
+    // Other guilds keep the bot's time zone.
+    assert_eq!(stats.guild_timezone("20").await.unwrap(), Tz::UTC);
+    assert_eq!(stats.guild_timezone("10").await.unwrap(), Tz::Asia__Tokyo);
+    update_guild_timezone(&frontend, &database, &stats, "10", "reset", Tz::UTC)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        vec![
+            Recorded::SendReply(
+                Reply::new("このサーバーはボットのタイムゾーン（UTC）を使っています。")
+                    .titled(Title::Settings)
+            ),
+            Recorded::SendReply(Reply::error(
+                "不明なタイムゾーンです: Mars/Olympus（例: Asia/Tokyo）"
+            )),
+            Recorded::SendReply(
+                Reply::new("サーバーのタイムゾーンをAsia/Tokyoにしました。")
+                    .titled(Title::Settings)
+            ),
+            Recorded::SendReply(
+                Reply::new("このサーバーのタイムゾーンはAsia/Tokyoです。").titled(Title::Settings)
+            ),
+            Recorded::SendReply(
+                Reply::new("サーバーのタイムゾーンをボットの設定（UTC）に戻しました。")
+                    .titled(Title::Settings)
+            ),
+        ]
+    );
+    assert_eq!(stats.guild_timezone("10").await.unwrap(), Tz::UTC);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn guild_months_are_counted_in_the_guild_timezone() {
+    let test = setup().await;
+    test.db
+        .guild("10")
+        .set_timezone(Some(Tz::Asia__Tokyo))
+        .await
+        .unwrap();
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    // 2024-06-01 05:00 in Tokyo.
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 31, 20, 0).unwrap());
+    let stats = StatsService::new(database, clock);
+
+    assert_eq!(
+        stats.current_month(),
+        NaiveDate::from_ymd_opt(2024, 5, 1).unwrap()
+    );
+    assert_eq!(
+        stats.guild_current_month("10").await.unwrap(),
+        NaiveDate::from_ymd_opt(2024, 6, 1).unwrap()
+    );
+    assert_eq!(
+        stats.guild_current_month("20").await.unwrap(),
+        NaiveDate::from_ymd_opt(2024, 5, 1).unwrap()
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn daily_caps_count_the_day_in_the_guild_timezone() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    test.db.guild("10").set_type_cap(1, 2).await.unwrap();
+    test.db.guild("20").set_type_cap(1, 2).await.unwrap();
+    test.db
+        .guild("10")
+        .set_timezone(Some(Tz::Asia__Tokyo))
+        .await
+        .unwrap();
+    // Both logs fall on May 3 in Tokyo but on May 2 in UTC.
+    for hour in [16, 17] {
+        log_at(
+            &test,
+            "1",
+            Utc.with_ymd_and_hms(2024, 5, 2, hour, 0, 0).unwrap(),
+        )
+        .await;
+    }
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 3, 1, 0).unwrap());
+    let logging = logging_service(&database, clock);
+    let frontend = RecordingFrontend::default();
+
+    record_cigarette(&frontend, &logging, "1", "alice", Some("10"), 1, None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        vec![Recorded::Respond(
+            Reply::error("本日の紙タバコはこのサーバーの上限（2本）に達しているため記録できません。上限を超えて記録する場合は ignore-caps on を使ってください。")
+                .ephemeral()
+        )]
+    );
+    // The guild on UTC has not counted any log today.
+    let logged = logging
+        .log_for_user("1", "alice", Some("20"), 1, 1, false, None)
+        .await;
+    assert!(logged.is_ok());
+
+    test.teardown().await;
+}
+
+#[test]
+fn settings_ids_round_trip() {
+    for setting in [
+        Setting::DailyGoal,
+        Setting::SmokeBreakPrompt,
+        Setting::IgnoreCaps,
+        Setting::ShareLogs,
+        Setting::QuietConfirmations,
+    ] {
+        let id = SettingsId {
+            owner_id: "42".to_string(),
+            setting,
+        };
+        assert_eq!(SettingsId::parse(&id.custom_id()), Some(id));
+    }
+    assert_eq!(SettingsId::parse("settings:42:volume"), None);
+    assert_eq!(SettingsId::parse("settings:me:goal"), None);
+    assert_eq!(SettingsId::parse("cleanup:merge:1:2"), None);
+}
+
+#[tokio::test]
+async fn the_panel_changes_settings_in_place() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let control = |setting| SettingsId {
+        owner_id: "1".to_string(),
+        setting,
+    };
+
+    let panel = render_user_settings(&database, "1", Locale::Japanese)
+        .await
+        .unwrap();
+    assert!(panel.ephemeral);
+    assert_eq!(
+        panel.content,
+        "あなたの設定\n1日の目標: なし\n喫煙所のパネル送信: オフ\nサーバーの上限を無視: オフ\nサーバーのデータ書き出しに記録を含める: オフ\n確認メッセージの簡潔表示: オフ\nサーバーのタイムゾーンは settings timezone で変更できます。"
+    );
+    assert_eq!(panel.buttons.len(), 4);
+    assert_eq!(panel.selects[0].custom_id, "settings:1:goal");
+
+    apply_user_setting(
+        &database,
+        "1",
+        "alice",
+        &control(Setting::DailyGoal),
+        Some("10"),
+        Locale::Japanese,
+    )
+    .await
+    .unwrap();
+    apply_user_setting(
+        &database,
+        "1",
+        "alice",
+        &control(Setting::SmokeBreakPrompt),
+        None,
+        Locale::Japanese,
+    )
+    .await
+    .unwrap();
+    let panel = apply_user_setting(
+        &database,
+        "1",
+        "alice",
+        &control(Setting::IgnoreCaps),
+        None,
+        Locale::Japanese,
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(
+        panel.content,
+        "あなたの設定\n1日の目標: 10本\n喫煙所のパネル送信: オン\nサーバーの上限を無視: オン\nサーバーのデータ書き出しに記録を含める: オフ\n確認メッセージの簡潔表示: オフ\nサーバーのタイムゾーンは settings timezone で変更できます。"
+    );
+    assert_eq!(panel.buttons[0].label, "喫煙所のパネル送信をオフにする");
+
+    // Goals that were never offered are ignored, and "none" clears the goal.
+    apply_user_setting(
+        &database,
+        "1",
+        "alice",
+        &control(Setting::DailyGoal),
+        Some("7"),
+        Locale::Japanese,
+    )
+    .await
+    .unwrap();
+    assert_eq!(test.db.get_daily_goal("1").await.unwrap(), Some(10));
+    apply_user_setting(
+        &database,
+        "1",
+        "alice",
+        &control(Setting::DailyGoal),
+        Some("none"),
+        Locale::Japanese,
+    )
+    .await
+    .unwrap();
+    assert_eq!(test.db.get_daily_goal("1").await.unwrap(), None);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn only_the_owner_can_use_the_panel() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let control = SettingsId {
+        owner_id: "1".to_string(),
+        setting: Setting::IgnoreCaps,
+    };
+
+    let updated = apply_user_setting(&database, "2", "bob", &control, None, Locale::Japanese)
+        .await
+        .unwrap();
+
+    assert_eq!(updated, None);
+    assert!(!test.db.get_ignore_type_caps("1").await.unwrap());
+    assert!(!test.db.get_ignore_type_caps("2").await.unwrap());
+
+    test.teardown().await;
+}
@@ -0,0 +1,120 @@
+//! Tests for the shard monitor and `admin shards`.
+
+use std::time::Duration;
+
+use chrono::{TimeZone, Utc};
+use cigarette_counter::{
+    commands::admin::render_shards,
+    shards::{guild_counts, render_metrics, ShardStatus, ShardTracker},
+};
+use poise::serenity_prelude::{ConnectionStage, GuildId};
+
+fn minutes(minutes: i64) -> chrono::Duration {
+    chrono::Duration::minutes(minutes)
+}
+
+#[test]
+fn connected_shards_without_heartbeats_are_wedged() {
+    let start = Utc.with_ymd_and_hms(2024, 6, 10, 12, 0, 0).unwrap();
+    let mut tracker = ShardTracker::new(start);
+
+    assert!(!tracker.observe(ConnectionStage::Connected, None, start));
+    assert!(!tracker.observe(
+        ConnectionStage::Connected,
+        Some(Duration::from_millis(40)),
+        start + minutes(1)
+    ));
+    // The same latency means no heartbeat was acknowledged since.
+    assert!(!tracker.observe(
+        ConnectionStage::Connected,
+        Some(Duration::from_millis(40)),
+        start + minutes(3)
+    ));
+    assert!(tracker.observe(
+        ConnectionStage::Connected,
+        Some(Duration::from_millis(40)),
+        start + minutes(4)
+    ));
+
+    tracker.restarted(start + minutes(4));
+    assert!(!tracker.observe(
+        ConnectionStage::Connecting,
+        Some(Duration::from_millis(40)),
+        start + minutes(6)
+    ));
+    assert!(tracker.observe(
+        ConnectionStage::Connecting,
+        Some(Duration::from_millis(40)),
+        start + minutes(7)
+    ));
+    assert_eq!(
+        tracker.status(0, ConnectionStage::Connecting, 3),
+        ShardStatus {
+            id: 0,
+            stage: ConnectionStage::Connecting,
+            latency: Some(Duration::from_millis(40)),
+            guilds: 3,
+            last_heartbeat: Some(start + minutes(1)),
+            restarts: 1,
+        }
+    );
+}
+
+#[test]
+fn guilds_are_counted_per_shard() {
+    // Shard IDs come from the guild ID's timestamp bits: (id >> 22) % shards.
+    let guilds = [
+        GuildId::new(1 << 22),
+        GuildId::new(2 << 22),
+        GuildId::new(3 << 22),
+    ];
+
+    let counts = guild_counts(&guilds, 2);
+    assert_eq!((counts.get(&0), counts.get(&1)), (Some(&1), Some(&2)));
+    assert_eq!(guild_counts(&guilds, 0).get(&0), Some(&3));
+}
+
+#[test]
+fn shards_are_reported_one_line_and_sample_each() {
+    let now = Utc.with_ymd_and_hms(2024, 6, 10, 12, 0, 0).unwrap();
+    let statuses = [
+        ShardStatus {
+            id: 0,
+            stage: ConnectionStage::Connected,
+            latency: Some(Duration::from_millis(42)),
+            guilds: 12,
+            last_heartbeat: Some(now - chrono::Duration::seconds(15)),
+            restarts: 0,
+        },
+        ShardStatus {
+            id: 1,
+            stage: ConnectionStage::Resuming,
+            latency: None,
+            guilds: 0,
+            last_heartbeat: None,
+            restarts: 2,
+        },
+    ];
+
+    assert_eq!(
+        render_shards(&statuses, now),
+        "シャード0: 接続済み / 遅延 42ms / 12サーバー / 最後のハートビート 15秒前\n\
+         シャード1: 接続中 / 遅延 不明 / 0サーバー / 最後のハートビート なし / 再起動 2回"
+    );
+    assert_eq!(render_shards(&[], now), "シャードの情報はまだありません。");
+
+    let metrics = render_metrics(&statuses);
+    assert!(metrics.contains("cigarette_counter_shard_connected{shard=\"1\"} 0\n"));
+    assert!(metrics.contains("cigarette_counter_shard_latency_seconds{shard=\"0\"} 0.042\n"));
+    assert!(!metrics.contains("cigarette_counter_shard_latency_seconds{shard=\"1\"}"));
+    assert!(metrics.contains(&format!(
+        "cigarette_counter_shard_last_heartbeat_timestamp_seconds{{shard=\"0\"}} {}\n",
+        now.timestamp() - 15
+    )));
+    assert!(metrics.contains(
+        "# TYPE cigarette_counter_shard_restarts_total counter\n\
+         cigarette_counter_shard_restarts_total{shard=\"0\"} 0\n\
+         cigarette_counter_shard_restarts_total{shard=\"1\"} 2\n"
+    ));
+    assert_eq!(render_metrics(&[]), "");
+}
@@ -0,0 +1,138 @@
+//! Tests for the opt-in smoke-free board.
+
+mod common;
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use chrono_tz::{Asia::Tokyo, UTC};
+use cigarette_counter::{
+    clock::MockClock,
+    commands::leaderboard::smoke_free_reply,
+    database::SmokeFreeMember,
+    smoke_free::{digest_section, rank_streaks, Streak},
+};
+use common::{create_user, log_at, setup};
+
+fn member(discord_id: &str, last_smoked_at: Option<(u32, u32)>) -> SmokeFreeMember {
+    SmokeFreeMember {
+        discord_id: discord_id.to_string(),
+        last_smoked_at: last_smoked_at
+            .map(|(day, hour)| Utc.with_ymd_and_hms(2024, 6, day, hour, 0, 0).unwrap()),
+    }
+}
+
+#[test]
+fn longer_streaks_rank_first() {
+    let today = NaiveDate::from_ymd_opt(2024, 6, 10).unwrap();
+    let members = [
+        member("1", Some((9, 12))),
+        member("2", Some((3, 12))),
+        member("3", None),
+        member("4", Some((7, 12))),
+        // 20:00 UTC on the 6th is already the 7th in Tokyo.
+        member("5", Some((6, 20))),
+    ];
+
+    assert_eq!(
+        rank_streaks(&members, today, Tokyo),
+        vec![
+            Streak {
+                discord_id: "2".to_string(),
+                rank: 1,
+                days: 7,
+            },
+            Streak {
+                discord_id: "4".to_string(),
+                rank: 2,
+                days: 3,
+            },
+            Streak {
+                discord_id: "5".to_string(),
+                rank: 2,
+                days: 3,
+            },
+            Streak {
+                discord_id: "1".to_string(),
+                rank: 4,
+                days: 1,
+            },
+            Streak {
+                discord_id: "3".to_string(),
+                rank: 5,
+                days: 0,
+            },
+        ]
+    );
+    assert_eq!(rank_streaks(&members, today, UTC)[1].discord_id, "5");
+}
+
+#[test]
+fn the_digest_shows_the_board_only_when_members_joined() {
+    let streaks = [Streak {
+        discord_id: "1".to_string(),
+        rank: 1,
+        days: 12,
+    }];
+
+    assert_eq!(
+        digest_section(&streaks).as_deref(),
+        Some("禁煙ボード（連続で記録のない日数）\n1位 <@1> 12日")
+    );
+    assert_eq!(digest_section(&[]), None);
+}
+
+#[tokio::test]
+async fn only_members_who_joined_are_on_the_board() {
+    let test = setup().await;
+    let guild = test.db.guild("10");
+
+    for user_id in ["1", "2", "3"] {
+        create_user(&test, user_id).await;
+    }
+    log_at(
+        &test,
+        "1",
+        Utc.with_ymd_and_hms(2024, 6, 5, 12, 0, 0).unwrap(),
+    )
+    .await;
+    log_at(
+        &test,
+        "2",
+        Utc.with_ymd_and_hms(2024, 6, 8, 12, 0, 0).unwrap(),
+    )
+    .await;
+    log_at(
+        &test,
+        "3",
+        Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap(),
+    )
+    .await;
+
+    assert!(guild.join_smoke_free_board("1").await.unwrap());
+    assert!(!guild.join_smoke_free_board("1").await.unwrap());
+    assert!(guild.join_smoke_free_board("2").await.unwrap());
+    assert!(test
+        .db
+        .guild("20")
+        .join_smoke_free_board("3")
+        .await
+        .unwrap());
+
+    let clock = MockClock::new(UTC, Utc.with_ymd_and_hms(2024, 6, 10, 9, 0, 0).unwrap());
+    assert_eq!(
+        smoke_free_reply(&guild, &clock).await.unwrap().content,
+        "禁煙ボード（連続で記録のない日数）\n1位 <@1> 5日\n2位 <@2> 2日\n参加: leaderboard smoke-free join / 退出: leaderboard smoke-free leave"
+    );
+
+    assert!(guild.leave_smoke_free_board("1").await.unwrap());
+    assert!(!guild.leave_smoke_free_board("1").await.unwrap());
+    assert!(!guild.leave_smoke_free_board("3").await.unwrap());
+    assert_eq!(
+        guild.get_smoke_free_board().await.unwrap(),
+        vec![SmokeFreeMember {
+            discord_id: "2".to_string(),
+            last_smoked_at: Some(Utc.with_ymd_and_hms(2024, 6, 8, 12, 0, 0).unwrap()),
+        }]
+    );
+
+    test.teardown().await;
+}
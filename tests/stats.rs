@@ -0,0 +1,278 @@
+//! Tests for cached statistics and their freshness indicator.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::{Clock, MockClock},
+    commands::stats::{format_freshness, show_stats, StatsView},
+    database::Database,
+    embed::Title,
+    event_bus::{DomainEvent, EventBus},
+    frontend::Reply,
+    service::StatsService,
+};
+use common::{create_user, log_at, move_logs_to, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+fn may() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 5, 1).unwrap()
+}
+
+fn log_created(discord_id: &str, guild_id: Option<&str>) -> DomainEvent {
+    DomainEvent::LogCreated {
+        log_id: 1,
+        discord_id: discord_id.to_string(),
+        guild_id: guild_id.map(str::to_string),
+        smoking_type_id: 1,
+        quantity: 1,
+        date: NaiveDate::from_ymd_opt(2024, 5, 3).unwrap(),
+        today_total: 1,
+    }
+}
+
+#[test]
+fn refresh_ids_round_trip() {
+    let views = [
+        StatsView::Month {
+            discord_id: "1".to_string(),
+            month: may(),
+        },
+        StatsView::Guild {
+            guild_id: "10".to_string(),
+            month: may(),
+        },
+        StatsView::Channel {
+            channel_id: "50".to_string(),
+            month: may(),
+        },
+    ];
+    for view in views {
+        assert_eq!(StatsView::parse_refresh_id(&view.refresh_id()), Some(view));
+    }
+
+    for custom_id in [
+        "stats:refresh:month:1:2024-05-02",
+        "stats:refresh:month:x:2024-05-01",
+        "stats:refresh:year:1:2024-05-01",
+        "stats:refresh:guild:10:2024-05-01:1",
+        "refresh:1:2024-05-01",
+    ] {
+        assert_eq!(
+            StatsView::parse_refresh_id(custom_id),
+            None,
+            "{}",
+            custom_id
+        );
+    }
+}
+
+#[test]
+fn freshness_is_reported_in_minutes() {
+    let now = Utc.with_ymd_and_hms(2024, 5, 3, 12, 0, 0).unwrap();
+
+    assert_eq!(
+        format_freshness(now - chrono::Duration::seconds(59), now),
+        "たった今集計しました（🔄で更新）"
+    );
+    assert_eq!(
+        format_freshness(now - chrono::Duration::minutes(3), now),
+        "3分前に集計しました（🔄で更新）"
+    );
+}
+
+#[tokio::test]
+async fn monthly_totals_are_cached_until_they_expire() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 3, 12, 0).unwrap());
+    let stats =
+        StatsService::new(database, clock.clone()).with_cache_ttl(Duration::from_secs(5 * 60));
+    log_at(
+        &test,
+        "1",
+        Utc.with_ymd_and_hms(2024, 5, 2, 12, 0, 0).unwrap(),
+    )
+    .await;
+
+    let first = stats.monthly_totals("1", may(), false).await.unwrap();
+    assert_eq!(first.value.len(), 1);
+    log_at(
+        &test,
+        "1",
+        Utc.with_ymd_and_hms(2024, 5, 3, 9, 0, 0).unwrap(),
+    )
+    .await;
+
+    clock.advance(chrono::Duration::minutes(3));
+    let cached = stats.monthly_totals("1", may(), false).await.unwrap();
+    assert_eq!(cached.value.len(), 1);
+    assert_eq!(cached.generated_at, first.generated_at);
+
+    let refreshed = stats.monthly_totals("1", may(), true).await.unwrap();
+    assert_eq!(refreshed.value.len(), 2);
+    assert_eq!(refreshed.generated_at, clock.now());
+
+    clock.advance(chrono::Duration::minutes(5));
+    let expired = stats.monthly_totals("1", may(), false).await.unwrap();
+    assert_eq!(expired.generated_at, clock.now());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn log_events_invalidate_the_affected_entries() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    create_user(&test, "2").await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 3, 12, 0).unwrap());
+    let stats = Arc::new(StatsService::new(database, clock.clone()));
+    let events = EventBus::default();
+    stats.spawn_invalidation_task(&events);
+
+    for user in ["1", "2"] {
+        test.db.log_smoking(user, Some("10"), 1, 1).await.unwrap();
+        move_logs_to(&test, user, NaiveDate::from_ymd_opt(2024, 5, 2).unwrap()).await;
+    }
+    let guild = stats.guild_totals("10", may(), false).await.unwrap();
+    assert_eq!(guild.value[0].total_quantity, 2);
+    assert_eq!(guild.value[0].member_count, 2);
+    let other_user = stats.monthly_totals("2", may(), false).await.unwrap();
+
+    test.db.log_smoking("1", Some("10"), 1, 1).await.unwrap();
+    move_logs_to(&test, "1", NaiveDate::from_ymd_opt(2024, 5, 2).unwrap()).await;
+    clock.advance(chrono::Duration::minutes(1));
+    events.publish(log_created("1", Some("10")));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let guild = stats.guild_totals("10", may(), false).await.unwrap();
+    assert_eq!(guild.value[0].total_quantity, 3);
+    assert_eq!(guild.generated_at, clock.now());
+    let unaffected = stats.monthly_totals("2", may(), false).await.unwrap();
+    assert_eq!(unaffected.generated_at, other_user.generated_at);
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn group_dm_logs_are_aggregated_per_channel() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    create_user(&test, "2").await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 3, 12, 0).unwrap());
+    let stats = StatsService::new(database, clock.clone());
+    let log = |user: &'static str, guild_id: Option<&'static str>, key: &'static str| {
+        let db = &test.db;
+        async move {
+            db.log_smoking_with_summary(user, guild_id, 1, 2, Some(key), may(), Tz::UTC)
+                .await
+                .unwrap();
+        }
+    };
+    log("1", None, "a").await;
+    log("2", None, "b").await;
+    log("2", None, "c").await;
+    log("1", Some("10"), "d").await;
+    for user in ["1", "2"] {
+        move_logs_to(&test, user, NaiveDate::from_ymd_opt(2024, 5, 2).unwrap()).await;
+    }
+
+    test.db.assign_log_channel("1", "a", "50").await.unwrap();
+    test.db.assign_log_channel("2", "b", "50").await.unwrap();
+    // Retried presses and other channels are counted once and apart.
+    test.db.assign_log_channel("2", "b", "60").await.unwrap();
+    test.db.assign_log_channel("2", "c", "60").await.unwrap();
+    // Guild logs are aggregated per guild instead.
+    test.db.assign_log_channel("1", "d", "50").await.unwrap();
+
+    let group = stats.channel_totals("50", may(), false).await.unwrap();
+    assert_eq!(group.value.len(), 1);
+    assert_eq!(group.value[0].total_quantity, 4);
+    assert_eq!(group.value[0].member_count, 2);
+    let other = stats.channel_totals("60", may(), false).await.unwrap();
+    assert_eq!(other.value[0].total_quantity, 2);
+    assert_eq!(other.value[0].member_count, 1);
+
+    clock.advance(chrono::Duration::minutes(1));
+    stats.forget_channel("60");
+    let cached = stats.channel_totals("50", may(), false).await.unwrap();
+    assert_eq!(cached.generated_at, group.generated_at);
+    let recomputed = stats.channel_totals("60", may(), false).await.unwrap();
+    assert_eq!(recomputed.generated_at, clock.now());
+
+    let frontend = RecordingFrontend::default();
+    let view = StatsView::Channel {
+        channel_id: "70".to_string(),
+        month: may(),
+    };
+    show_stats(&frontend, &stats, &view, clock.now())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        vec![Recorded::SendReply(
+            Reply::new(
+                "2024年5月のグループ集計\n記録はありません。\nたった今集計しました（🔄で更新）"
+            )
+            .titled(Title::Stats)
+            .button(view.refresh_id(), "🔄")
+        )]
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn views_show_their_freshness_and_a_refresh_button() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 3, 12, 0).unwrap());
+    let stats = StatsService::new(database, clock.clone());
+    for hour in [9, 10] {
+        log_at(
+            &test,
+            "1",
+            Utc.with_ymd_and_hms(2024, 5, 2, hour, 0, 0).unwrap(),
+        )
+        .await;
+    }
+    let frontend = RecordingFrontend::default();
+    let view = StatsView::Month {
+        discord_id: "1".to_string(),
+        month: may(),
+    };
+
+    show_stats(&frontend, &stats, &view, clock.now())
+        .await
+        .unwrap();
+    clock.advance(chrono::Duration::minutes(2));
+    stats.invalidate(&log_created("2", None));
+    show_stats(&frontend, &stats, &view, clock.now())
+        .await
+        .unwrap();
+
+    let calls = frontend.calls();
+    let Recorded::SendReply(first) = &calls[0] else {
+        panic!("expected a reply");
+    };
+    assert_eq!(
+        first.content,
+        "2024年5月の記録\n 1日  0本\n 2日 ██ 2本\n 3日  0本\n合計: 2本\nたった今集計しました（🔄で更新）"
+    );
+    assert_eq!(first.buttons[0].custom_id, view.refresh_id());
+    let Recorded::SendReply(second) = &calls[1] else {
+        panic!("expected a reply");
+    };
+    assert!(second.content.ends_with("2分前に集計しました（🔄で更新）"));
+
+    test.teardown().await;
+}
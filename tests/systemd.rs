@@ -0,0 +1,50 @@
+//! Tests for systemd service notifications.
+
+use std::{os::unix::net::UnixDatagram, time::Duration};
+
+use cigarette_counter::systemd::{feed_watchdog, gateway_healthy, notify_ready, watchdog_interval};
+use poise::serenity_prelude::ConnectionStage;
+
+#[test]
+fn the_gateway_is_healthy_only_when_every_shard_is_connected() {
+    assert!(gateway_healthy([
+        ConnectionStage::Connected,
+        ConnectionStage::Connected
+    ]));
+    assert!(!gateway_healthy([
+        ConnectionStage::Connected,
+        ConnectionStage::Resuming
+    ]));
+    assert!(!gateway_healthy([]));
+}
+
+// The notification socket is configured through the environment, so
+// everything touching it runs in a single test.
+#[test]
+fn notifications_reach_the_service_manager() {
+    let path =
+        std::env::temp_dir().join(format!("cigarette-counter-notify-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let socket = UnixDatagram::bind(&path).unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    std::env::set_var("NOTIFY_SOCKET", &path);
+    std::env::set_var("WATCHDOG_USEC", "30000000");
+    std::env::set_var("WATCHDOG_PID", std::process::id().to_string());
+
+    notify_ready();
+    feed_watchdog();
+
+    let mut buffer = [0; 64];
+    let received = socket.recv(&mut buffer).unwrap();
+    assert_eq!(&buffer[..received], b"READY=1\n");
+    let received = socket.recv(&mut buffer).unwrap();
+    assert_eq!(&buffer[..received], b"WATCHDOG=1\n");
+    assert_eq!(watchdog_interval(), Some(Duration::from_secs(30)));
+
+    // The watchdog of a parent process is not ours to feed.
+    std::env::set_var("WATCHDOG_PID", (std::process::id() + 1).to_string());
+    assert_eq!(watchdog_interval(), None);
+    std::fs::remove_file(&path).unwrap();
+}
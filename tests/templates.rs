@@ -0,0 +1,60 @@
+//! Tests for the message template engine.
+
+use cigarette_counter::templates::{Revision, Template, TemplateError, TemplateKey, MAX_LENGTH};
+
+#[test]
+fn placeholders_are_substituted() {
+    let template =
+        Template::parse(TemplateKey::Confirmation, "{user}: {total} ({ date })").unwrap();
+
+    assert_eq!(
+        template.render(&[("user", "alice"), ("total", "3本"), ("date", "2024/05/01")]),
+        "alice: 3本 (2024/05/01)"
+    );
+    assert_eq!(template.render(&[]), ":  ()");
+}
+
+#[test]
+fn doubled_braces_are_literal() {
+    let template = Template::parse(TemplateKey::Milestone, "{{{days}}} }}{{").unwrap();
+
+    assert_eq!(template.render(&[("days", "7")]), "{7} }{");
+}
+
+#[test]
+fn invalid_templates_are_rejected() {
+    let parse = |source: &str| Template::parse(TemplateKey::Milestone, source);
+
+    assert_eq!(parse(" \n"), Err(TemplateError::Empty));
+    assert_eq!(parse("{days"), Err(TemplateError::UnclosedPlaceholder));
+    assert_eq!(parse("{da{ys}"), Err(TemplateError::UnclosedPlaceholder));
+    assert_eq!(parse("days}"), Err(TemplateError::UnmatchedBrace));
+    assert_eq!(
+        parse("{summary}"),
+        Err(TemplateError::UnknownPlaceholder("summary".to_string()))
+    );
+    assert_eq!(
+        parse(&"a".repeat(MAX_LENGTH + 1)),
+        Err(TemplateError::TooLong)
+    );
+    assert!(parse(&"あ".repeat(MAX_LENGTH)).is_ok());
+}
+
+#[test]
+fn keys_round_trip_and_defaults_are_valid() {
+    for key in TemplateKey::ALL {
+        assert_eq!(key.name().parse::<TemplateKey>().unwrap(), key);
+        assert!(Template::parse(key, key.default_template()).is_ok());
+    }
+    assert!("welcome".parse::<TemplateKey>().is_err());
+}
+
+#[test]
+fn revisions_parse_with_a_v_prefix() {
+    assert_eq!("v3".parse::<Revision>().unwrap(), Revision(3));
+    assert_eq!("V0".parse::<Revision>().unwrap(), Revision(0));
+    assert!("3".parse::<Revision>().is_err());
+    assert!("v-1".parse::<Revision>().is_err());
+    assert!("{user}".parse::<Revision>().is_err());
+    assert_eq!(Revision(3).to_string(), "v3");
+}
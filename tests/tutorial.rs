@@ -0,0 +1,50 @@
+//! Tests for the tutorial shown after a new user's first press.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::{MockClock, SystemClock},
+    database::Database,
+    tutorial::NEW_USER_WINDOW,
+};
+use common::{logging_service, setup};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+#[tokio::test]
+async fn only_new_users_before_their_first_log_get_the_tutorial() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let logging = logging_service(&database, Arc::new(SystemClock::new(Tz::UTC)));
+
+    // Unknown users are asked for consent first.
+    assert!(!logging.is_new_user("1").await.unwrap());
+
+    logging.accept_policy("1", "alice").await.unwrap();
+    assert!(logging.is_new_user("1").await.unwrap());
+
+    logging
+        .log_for_user("1", "alice", None, 1, 1, false, None)
+        .await
+        .unwrap();
+    assert!(!logging.is_new_user("1").await.unwrap());
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn existing_users_accepting_again_do_not_get_the_tutorial() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::new(Tz::UTC, chrono::Utc::now()));
+    let logging = logging_service(&database, clock.clone());
+
+    logging.accept_policy("1", "alice").await.unwrap();
+    clock.advance(NEW_USER_WINDOW + chrono::Duration::minutes(1));
+
+    assert!(!logging.is_new_user("1").await.unwrap());
+
+    test.teardown().await;
+}
@@ -0,0 +1,246 @@
+//! Tests for merging smoking types.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono::{TimeZone, Utc};
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::MockClock,
+    commands::types::{list_conversions, merge_types, set_conversion},
+    database::Database,
+    frontend::Reply,
+    service::StatsService,
+    units::{convert, format_factor},
+};
+use common::{create_user, log_at, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+#[tokio::test]
+async fn merging_moves_everything_to_the_kept_type() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    for day in [1, 2] {
+        log_at(
+            &test,
+            "1",
+            Utc.with_ymd_and_hms(2024, 5, day, 12, 0, 0).unwrap(),
+        )
+        .await;
+    }
+    let guild = test.db.guild("10");
+    guild.set_type_cap(1, 3).await.unwrap();
+    guild.set_type_cap(2, 5).await.unwrap();
+    guild.set_panel_types(Some(&[1, 2])).await.unwrap();
+    test.db
+        .create_panel("panel", Some("10"), "500", Some(&[1, 3]))
+        .await
+        .unwrap();
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 3, 12, 0).unwrap());
+    let stats = StatsService::new(database.clone(), clock);
+    let frontend = RecordingFrontend::default();
+
+    merge_types(
+        &frontend,
+        &database,
+        &stats,
+        "99",
+        "traditional",
+        "iqos",
+        None,
+    )
+    .await
+    .unwrap();
+    // The merged name now finds the kept type.
+    merge_types(
+        &frontend,
+        &database,
+        &stats,
+        "99",
+        "traditional",
+        "iqos",
+        None,
+    )
+    .await
+    .unwrap();
+    merge_types(&frontend, &database, &stats, "99", "mevius", "iqos", None)
+        .await
+        .unwrap();
+
+    let calls = frontend.calls();
+    assert_eq!(
+        calls[..2],
+        [
+            Recorded::SendReply(Reply::new(
+                "紙タバコをIQOSに統合しました（2件の記録を移動）。"
+            )),
+            Recorded::SendReply(Reply::error("traditionalとiqosはすでに同じ種類です。")),
+        ]
+    );
+    let Recorded::SendReply(unknown) = &calls[2] else {
+        panic!("expected a reply");
+    };
+    assert!(unknown
+        .content
+        .starts_with("不明な種類です: mevius / iqos（使用できる種類: iqos, "));
+
+    let moved: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM smoking_logs WHERE smoking_type_id = 2")
+            .fetch_one(&test.pool)
+            .await
+            .unwrap();
+    assert_eq!(moved, 2);
+    assert!(!test.db.smoking_type_exists(1).await.unwrap());
+    assert!(test
+        .db
+        .get_smoking_types()
+        .await
+        .unwrap()
+        .iter()
+        .all(|smoking_type| smoking_type.id != 1));
+    assert_eq!(
+        test.db
+            .find_smoking_type_by_name("traditional")
+            .await
+            .unwrap()
+            .map(|smoking_type| smoking_type.id),
+        Some(2)
+    );
+    let caps: Vec<(i32, i32)> = guild
+        .get_type_caps()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|cap| (cap.smoking_type_id, cap.daily_cap))
+        .collect();
+    assert_eq!(caps, vec![(2, 5)]);
+    assert_eq!(
+        guild.get_settings().await.unwrap().panel_smoking_type_ids,
+        Some(vec![2])
+    );
+    assert_eq!(
+        test.db
+            .get_panel("panel", Some("10"))
+            .await
+            .unwrap()
+            .unwrap()
+            .smoking_type_ids,
+        Some(vec![2, 3])
+    );
+    let audit = test.db.get_admin_audit_entries(10).await.unwrap();
+    assert_eq!(audit.len(), 1);
+    assert_eq!(audit[0].action, "merge_types");
+    assert_eq!(audit[0].details, "type 1 -> 2: 2 logs moved");
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn the_kept_type_takes_over_missing_details() {
+    let test = setup().await;
+    sqlx::query("UPDATE smoking_types SET emoji = '🚬', unit = '本', typical_price = 30, currency = 'JPY' WHERE id = 1")
+        .execute(&test.pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE smoking_types SET emoji = '🔥' WHERE id = 2")
+        .execute(&test.pool)
+        .await
+        .unwrap();
+
+    test.db.merge_smoking_types("99", 1, 2, None).await.unwrap();
+
+    let kept = test.db.get_smoking_type(2).await.unwrap();
+    assert_eq!(kept.description.as_deref(), Some("IQOS"));
+    assert_eq!(kept.emoji.as_deref(), Some("🔥"));
+    assert_eq!(kept.unit.as_deref(), Some("本"));
+    assert_eq!(kept.typical_price, Some(30));
+    assert_eq!(kept.currency.as_deref(), Some("JPY"));
+
+    test.teardown().await;
+}
+
+#[test]
+fn conversions_round_to_at_least_one_unit() {
+    assert_eq!(convert(2, 20.0), 40);
+    assert_eq!(convert(30, 0.05), 2);
+    assert_eq!(convert(3, 0.05), 1);
+    assert_eq!(format_factor(20.0), "20");
+    assert_eq!(format_factor(0.05), "0.05");
+}
+
+#[tokio::test]
+async fn merging_types_with_different_units_needs_a_factor() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    sqlx::query("UPDATE smoking_types SET unit = '回' WHERE id = 2")
+        .execute(&test.pool)
+        .await
+        .unwrap();
+    test.db.log_smoking("1", None, 1, 3).await.unwrap();
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 3, 12, 0).unwrap());
+    let stats = StatsService::new(database.clone(), clock);
+    let frontend = RecordingFrontend::default();
+
+    set_conversion(&frontend, &database, "traditional", "iqos", 0.0)
+        .await
+        .unwrap();
+    set_conversion(&frontend, &database, "traditional", "iqos", 15.0)
+        .await
+        .unwrap();
+    list_conversions(&frontend, &database).await.unwrap();
+    merge_types(
+        &frontend,
+        &database,
+        &stats,
+        "99",
+        "traditional",
+        "iqos",
+        None,
+    )
+    .await
+    .unwrap();
+    merge_types(
+        &frontend,
+        &database,
+        &stats,
+        "99",
+        "traditional",
+        "iqos",
+        Some(15.0),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        vec![
+            Recorded::SendReply(Reply::error("換算係数は0より大きい数で指定してください。")),
+            Recorded::SendReply(Reply::new(
+                "紙タバコからIQOSへの換算の目安を登録しました: 1本 = 15回"
+            )),
+            Recorded::SendReply(Reply::new("換算の目安\n- 紙タバコ → IQOS: 1本 = 15回（×15）")),
+            Recorded::SendReply(Reply::error(
+                "紙タバコの単位は「本」、IQOSの単位は「回」です。換算係数（1本あたりの回）を指定して type merge traditional iqos <換算係数> を実行し直してください。\n換算の目安: 1本 = 15回"
+            )),
+            Recorded::SendReply(Reply::new(
+                "紙タバコをIQOSに統合しました（1件の記録を移動）。"
+            )),
+        ]
+    );
+    let quantity: i32 =
+        sqlx::query_scalar("SELECT quantity FROM smoking_logs WHERE smoking_type_id = 2")
+            .fetch_one(&test.pool)
+            .await
+            .unwrap();
+    assert_eq!(quantity, 45);
+    let audit = test.db.get_admin_audit_entries(10).await.unwrap();
+    assert_eq!(
+        audit[0].details,
+        "type 1 -> 2: 1 logs moved, quantities x15"
+    );
+
+    test.teardown().await;
+}
@@ -0,0 +1,82 @@
+//! Tests for undoing the most recent log.
+
+mod common;
+
+use std::sync::Arc;
+
+use chrono_tz::Tz;
+use cigarette_counter::{
+    clock::{Clock, SystemClock},
+    commands::undo::{apply_undo, UndoId},
+    database::Database,
+    embed::Title,
+    format::{format_date, Locale},
+    frontend::Reply,
+};
+use common::{create_user, logging_service, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+#[tokio::test]
+async fn undo_buttons_delete_the_pressers_last_log() {
+    let test = setup().await;
+    create_user(&test, "1").await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(SystemClock::new(Tz::UTC));
+    let logging = logging_service(&database, clock.clone());
+    let frontend = RecordingFrontend::default();
+    for smoking_type_id in [1, 2] {
+        logging
+            .log_for_user("1", "alice", Some("10"), smoking_type_id, 1, false, None)
+            .await
+            .unwrap();
+    }
+    let undo = UndoId {
+        user_id: "1".to_string(),
+    };
+
+    for presser_id in ["2", "1", "1", "1"] {
+        apply_undo(&frontend, &logging, presser_id, &undo)
+            .await
+            .unwrap();
+    }
+
+    let heading = format!(
+        "本日（{}）の累計本数",
+        format_date(clock.today(), Locale::Japanese)
+    );
+    assert_eq!(
+        frontend.calls(),
+        [
+            Recorded::Respond(Reply::error("このボタンは記録した人だけが使えます。").ephemeral()),
+            Recorded::Respond(
+                Reply::new(format!(
+                    "IQOS 1本の記録を取り消しました。\n{}\n紙タバコ: 1本",
+                    heading
+                ))
+                .titled(Title::Confirmation)
+            ),
+            Recorded::Respond(
+                Reply::new(format!(
+                    "紙タバコ 1本の記録を取り消しました。\n{}\n記録はありません。",
+                    heading
+                ))
+                .titled(Title::Confirmation)
+            ),
+            Recorded::Respond(Reply::error("取り消せる記録がありません。")),
+        ]
+    );
+
+    test.teardown().await;
+}
+
+#[test]
+fn undo_ids_round_trip() {
+    let undo = UndoId {
+        user_id: "123".to_string(),
+    };
+
+    assert_eq!(UndoId::parse(&undo.custom_id()), Some(undo));
+    assert_eq!(UndoId::parse("undo:"), None);
+    assert_eq!(UndoId::parse("undo:12a"), None);
+    assert_eq!(UndoId::parse("cleanup:delete:1:1"), None);
+}
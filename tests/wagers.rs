@@ -0,0 +1,158 @@
+//! Tests for friendly wagers between users.
+
+mod common;
+
+use chrono::{Duration, TimeZone, Utc};
+use cigarette_counter::{
+    commands::wagers::{proposal, WagerId},
+    wagers::{announcement, describe_terms, Side, WagerMetric, WagerPeriod, PENDING_EXPIRY},
+};
+use common::setup;
+
+#[test]
+fn wager_ids_round_trip() {
+    for id in [
+        WagerId::Accept { wager_id: 7 },
+        WagerId::Decline { wager_id: 7 },
+    ] {
+        assert_eq!(WagerId::parse(&id.custom_id()), Some(id));
+    }
+    assert_eq!(WagerId::parse("wager:accept:x"), None);
+    assert_eq!(WagerId::parse("wager:cancel:7"), None);
+    assert_eq!(WagerId::parse("quantity:cancel:7"), None);
+}
+
+#[test]
+fn the_lowest_count_wins() {
+    assert_eq!(
+        WagerMetric::from_name("lowest-count"),
+        Some(WagerMetric::LowestCount)
+    );
+    assert_eq!(WagerMetric::from_name("highest-count"), None);
+    assert_eq!(WagerPeriod::from_name("week"), Some(WagerPeriod::Week));
+    assert_eq!(WagerPeriod::from_name("month"), None);
+
+    let metric = WagerMetric::LowestCount;
+    assert_eq!(metric.winner(3, 5), Some(Side::Challenger));
+    assert_eq!(metric.winner(5, 3), Some(Side::Opponent));
+    assert_eq!(metric.winner(4, 4), None);
+    assert_eq!(
+        describe_terms(metric, WagerPeriod::Week),
+        "1週間で記録した本数が少なかった方が勝ち"
+    );
+}
+
+#[tokio::test]
+async fn wagers_are_accepted_and_settled() {
+    let test = setup().await;
+    let now = Utc.with_ymd_and_hms(2024, 6, 3, 12, 0, 0).unwrap();
+
+    let wager = test
+        .db
+        .create_wager("500", "1", "2", "lowest-count", "week")
+        .await
+        .unwrap();
+    assert_eq!(wager.status, "pending");
+    let prompt = proposal(&wager, WagerMetric::LowestCount, WagerPeriod::Week);
+    assert!(prompt
+        .content
+        .starts_with("<@2> さん、<@1> さんから勝負の申し込みです"));
+    assert_eq!(
+        prompt
+            .buttons
+            .iter()
+            .map(|button| WagerId::parse(&button.custom_id))
+            .collect::<Vec<_>>(),
+        vec![
+            Some(WagerId::Accept { wager_id: wager.id }),
+            Some(WagerId::Decline { wager_id: wager.id }),
+        ]
+    );
+
+    // Proposals older than the expiry can no longer be accepted.
+    let ends_at = now + Duration::weeks(1);
+    assert_eq!(
+        test.db
+            .accept_wager(wager.id, wager.created_at, now, ends_at)
+            .await
+            .unwrap(),
+        None
+    );
+    let accepted = test
+        .db
+        .accept_wager(wager.id, wager.created_at - PENDING_EXPIRY, now, ends_at)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(accepted.status, "accepted");
+    assert!(!test.db.decline_wager(wager.id).await.unwrap());
+
+    assert!(test.db.get_due_wagers(now).await.unwrap().is_empty());
+    let due = test.db.get_due_wagers(ends_at).await.unwrap();
+    assert_eq!(due, vec![accepted.clone()]);
+
+    test.db
+        .settle_wager(wager.id, 10, 12, Some("1"), ends_at)
+        .await
+        .unwrap();
+    assert!(test.db.get_due_wagers(ends_at).await.unwrap().is_empty());
+    let settled = test.db.get_wager(wager.id).await.unwrap().unwrap();
+    assert_eq!(settled.status, "settled");
+    assert_eq!(settled.winner_id.as_deref(), Some("1"));
+    assert_eq!(
+        announcement(&accepted, 10, 12, Some(Side::Challenger)),
+        format!(
+            "🏁 勝負 #{} の結果: <@1> さんの勝ちです！\n<@1>: 10本\n<@2>: 12本",
+            wager.id
+        )
+    );
+
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn unanswered_wagers_expire() {
+    let test = setup().await;
+
+    let wager = test
+        .db
+        .create_wager("500", "1", "2", "lowest-count", "day")
+        .await
+        .unwrap();
+    let declined = test
+        .db
+        .create_wager("500", "1", "3", "lowest-count", "day")
+        .await
+        .unwrap();
+    assert!(test.db.decline_wager(declined.id).await.unwrap());
+
+    assert_eq!(
+        test.db
+            .expire_pending_wagers(wager.created_at - Duration::seconds(1))
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        test.db
+            .expire_pending_wagers(wager.created_at + PENDING_EXPIRY)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        test.db.get_wager(wager.id).await.unwrap().unwrap().status,
+        "expired"
+    );
+    assert_eq!(
+        test.db
+            .get_wager(declined.id)
+            .await
+            .unwrap()
+            .unwrap()
+            .status,
+        "declined"
+    );
+
+    test.teardown().await;
+}
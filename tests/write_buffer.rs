@@ -0,0 +1,141 @@
+//! Tests for buffering panel presses during database outages.
+
+mod common;
+
+use std::{path::PathBuf, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use cigarette_counter::{
+    circuit_breaker::UNAVAILABLE_MESSAGE,
+    clock::MockClock,
+    commands::panel::buffer_press,
+    database::{BufferedLog, Database},
+    frontend::Reply,
+    write_buffer::{BufferError, ReplaySummary, WriteBuffer},
+};
+use common::{create_user, logging_service, setup, Recorded, RecordingFrontend};
+use poise::serenity_prelude::futures::lock::Mutex;
+
+/// Returns a path for a buffer file that does not exist yet.
+fn buffer_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "cigarette-counter-{}-{}.jsonl",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn press(discord_id: &str, smoking_type_id: i32, key: &str) -> BufferedLog {
+    BufferedLog {
+        discord_id: discord_id.to_string(),
+        guild_id: Some("10".to_string()),
+        smoking_type_id,
+        quantity: 1,
+        smoked_at: DateTime::parse_from_rfc3339("2024-05-01T09:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc),
+        idempotency_key: Some(key.to_string()),
+    }
+}
+
+#[test]
+fn buffered_logs_survive_a_restart_up_to_the_capacity() {
+    let path = buffer_path("restart");
+    let buffer = WriteBuffer::open(&path, 2).unwrap();
+
+    buffer.push(press("1", 1, "a")).unwrap();
+    buffer.push(press("1", 2, "b")).unwrap();
+    assert!(matches!(
+        buffer.push(press("1", 3, "c")),
+        Err(BufferError::Full)
+    ));
+
+    let reopened = WriteBuffer::open(&path, 2).unwrap();
+    assert_eq!(reopened.len(), 2);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn replay_writes_marked_logs_once() {
+    let test = setup().await;
+    let database = Arc::new(Mutex::new(Database::new(test.pool.clone())));
+    let clock = Arc::new(MockClock::at_local(Tz::UTC, 2024, 5, 1, 12, 0).unwrap());
+    let logging = logging_service(&database, clock);
+    create_user(&test, "1").await;
+    // Written before the outage was noticed.
+    logging
+        .log_for_user("1", "user-1", Some("10"), 1, 1, false, Some("a"))
+        .await
+        .unwrap();
+    let path = buffer_path("replay");
+    let buffer = WriteBuffer::open(&path, 10).unwrap();
+    buffer.push(press("1", 1, "a")).unwrap();
+    buffer.push(press("1", 2, "b")).unwrap();
+    // Unknown types and users who never consented cannot be recorded.
+    buffer.push(press("1", 999, "c")).unwrap();
+    buffer.push(press("2", 1, "d")).unwrap();
+
+    let summary = buffer.replay(&logging).await.unwrap();
+
+    assert_eq!(
+        summary,
+        ReplaySummary {
+            written: 1,
+            duplicates: 1,
+            dropped: 2,
+            remaining: 0,
+        }
+    );
+    let logs: Vec<(i32, String, bool)> = sqlx::query_as(
+        "SELECT smoking_type_id, idempotency_key, replayed_at IS NOT NULL FROM smoking_logs ORDER BY id",
+    )
+    .fetch_all(&test.pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        logs,
+        vec![(1, "a".to_string(), false), (2, "b".to_string(), true)]
+    );
+    assert!(buffer.is_empty());
+    assert!(WriteBuffer::open(&path, 10).unwrap().is_empty());
+
+    std::fs::remove_file(&path).unwrap();
+    test.teardown().await;
+}
+
+#[tokio::test]
+async fn presses_are_rejected_without_room_in_a_buffer() {
+    let path = buffer_path("press");
+    let buffer = WriteBuffer::open(&path, 1).unwrap();
+    let frontend = RecordingFrontend::default();
+
+    buffer_press(&frontend, Some(&buffer), press("1", 1, "a"))
+        .await
+        .unwrap();
+    buffer_press(&frontend, Some(&buffer), press("1", 1, "b"))
+        .await
+        .unwrap();
+    buffer_press(&frontend, None, press("1", 1, "c"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        frontend.calls(),
+        vec![
+            Recorded::Respond(
+                Reply::new(
+                    "データベースに一時的に接続できないため、この記録を保留しました。接続が戻りしだい記録します。"
+                )
+                .ephemeral()
+            ),
+            Recorded::Respond(Reply::error(UNAVAILABLE_MESSAGE).ephemeral()),
+            Recorded::Respond(Reply::error(UNAVAILABLE_MESSAGE).ephemeral()),
+        ]
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}